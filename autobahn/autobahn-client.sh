@@ -46,5 +46,6 @@ docker run -d --rm \
     wstest -m fuzzingserver -s 'autobahn/config/fuzzingserver.json'
 
 sleep 3
-cargo run --release --example autobahn-client
+cargo run --release --example autobahn-client --features deflate
 test_diff
+cargo run --example autobahn-report -- autobahn/reports/client/index.json
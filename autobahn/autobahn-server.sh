@@ -35,8 +35,8 @@ function test_diff() {
     rm -f "$expected_filtered" "$actual_filtered"
 }
 
-cargo build --release --example autobahn-server
-cargo run --release --example autobahn-server & WSSERVER_PID=$!
+cargo build --release --example autobahn-server --features deflate
+cargo run --release --example autobahn-server --features deflate & WSSERVER_PID=$!
 sleep 3
 
 docker run --rm \
@@ -47,3 +47,4 @@ docker run --rm \
     wstest -m fuzzingclient -s 'autobahn/config/fuzzingclient.json'
 
 test_diff
+cargo run --example autobahn-report -- autobahn/reports/server/index.json
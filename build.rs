@@ -0,0 +1,21 @@
+//! Detects a nightly compiler, so that the `simd` feature only enables `core::simd` where it compiles.
+
+use std::{env, process::Command};
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(websocketz_nightly)");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+
+    let nightly = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .is_some_and(|version| version.contains("nightly") || version.contains("-dev"));
+
+    if nightly {
+        println!("cargo:rustc-cfg=websocketz_nightly");
+    }
+}
@@ -1,15 +1,19 @@
 //! Run with
 //!
 //! ```not_rust
-//! cargo run --example autobahn-client
+//! cargo run --example autobahn-client --features deflate
 //! ```
 
 use embedded_io_adapters::tokio_1::FromTokio;
 use rand::{SeedableRng, rngs::StdRng};
 use tokio::net::TcpStream;
 use websocketz::{
-    CloseCode, CloseFrame, Message, WebSocket, http::Header, next, options::ConnectOptions, send,
-    send_fragmented,
+    CloseCode, CloseFrame, Message, WebSocket,
+    deflate::Inflater,
+    http::Header,
+    next,
+    options::{ConnectOptions, Profile},
+    send, send_fragmented,
 };
 
 async fn connect<'buf>(
@@ -17,6 +21,7 @@ async fn connect<'buf>(
     read_buf: &'buf mut [u8],
     write_buf: &'buf mut [u8],
     fragments_buf: &'buf mut [u8],
+    permessage_deflate: bool,
 ) -> Result<WebSocket<'buf, FromTokio<TcpStream>, StdRng>, Box<dyn std::error::Error>> {
     let stream = TcpStream::connect("localhost:9001").await?;
 
@@ -26,14 +31,17 @@ async fn connect<'buf>(
     }];
 
     let websocketz = WebSocket::connect::<16>(
-        ConnectOptions::new_unchecked(path).with_headers(headers),
+        ConnectOptions::new_unchecked(path)
+            .with_headers(headers)?
+            .with_permessage_deflate(permessage_deflate),
         FromTokio::new(stream),
         StdRng::from_os_rng(),
         read_buf,
         write_buf,
         fragments_buf,
     )
-    .await?;
+    .await?
+    .with_profile(Profile::Autobahn);
 
     println!(
         "Number of framable bytes after handshake: {}",
@@ -48,7 +56,8 @@ async fn get_case_count() -> Result<u32, Box<dyn std::error::Error>> {
     let write_buf = &mut [0u8; 1024];
     let fragments_buf = &mut [0u8; 1024];
 
-    let mut websocketz = connect("/getCaseCount", read_buf, write_buf, fragments_buf).await?;
+    let mut websocketz =
+        connect("/getCaseCount", read_buf, write_buf, fragments_buf, false).await?;
 
     let count = match next!(websocketz)
         .transpose()?
@@ -82,14 +91,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let mut read_buf = vec![0u8; SIZE];
         let mut write_buf = vec![0u8; SIZE];
         let mut fragments_buf = vec![0u8; SIZE];
+        let mut inflater = Box::new(Inflater::new());
+        let mut inflate_buf = vec![0u8; SIZE];
 
         let mut websocketz = connect(
             &format!("/runCase?case={case}&agent=websocketz"),
             &mut read_buf,
             &mut write_buf,
             &mut fragments_buf,
+            true,
         )
-        .await?;
+        .await?
+        .with_inflater(&mut inflater, &mut inflate_buf);
 
         while let Some(message) = next!(websocketz) {
             match message {
@@ -120,6 +133,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         read_buf,
         write_buf,
         &mut [],
+        false,
     )
     .await?;
 
@@ -0,0 +1,437 @@
+//! Checks an Autobahn `index.json` report, failing if any case did not pass.
+//!
+//! Run with
+//!
+//! ```not_rust
+//! cargo run --example autobahn-report -- autobahn/reports/server/index.json
+//! ```
+//!
+//! A case passes if its behavior is `OK` or `NON-STRICT` and its close behavior is `OK`.
+//! `INFORMATIONAL` cases are only informative, they are counted but do not fail the check.
+//! `UNIMPLEMENTED` cases fail: the `permessage-deflate` cases run against the examples with the extension negotiated.
+
+use std::{collections::BTreeMap, process::ExitCode};
+
+/// Behaviors of a passing case.
+const PASSING: &[&str] = &["OK", "NON-STRICT"];
+
+/// Behaviors of cases that are reported but do not fail the check.
+const IGNORED: &[&str] = &["INFORMATIONAL"];
+
+/// A JSON value.
+///
+/// Just enough JSON to read Autobahn reports without any dependencies.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(input: &'a str) -> Result<Value, String> {
+        let mut parser = Self {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+
+        let value = parser.value()?;
+
+        parser.whitespace();
+
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => Err(parser.error("Trailing characters")),
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{message} at byte {}", self.pos)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.whitespace();
+
+        match self.peek() {
+            Some(b) if b == byte => {
+                self.pos += 1;
+
+                Ok(())
+            }
+            _ => Err(self.error(&format!("Expected `{}`", byte as char))),
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+        match self.input[self.pos..].starts_with(literal.as_bytes()) {
+            true => {
+                self.pos += literal.len();
+
+                Ok(value)
+            }
+            false => Err(self.error("Invalid literal")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.whitespace();
+
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("Expected a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect(b'{')?;
+
+        let mut entries = Vec::new();
+
+        self.whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.whitespace();
+
+            let key = self.string()?;
+
+            self.expect(b':')?;
+
+            entries.push((key, self.value()?));
+
+            self.whitespace();
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("Expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect(b'[')?;
+
+        let mut values = Vec::new();
+
+        self.whitespace();
+
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            self.whitespace();
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("Expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("Expected a string"));
+        }
+
+        self.pos += 1;
+
+        let mut string = String::new();
+
+        loop {
+            let start = self.pos;
+
+            while !matches!(self.peek(), Some(b'"' | b'\\') | None) {
+                self.pos += 1;
+            }
+
+            string.push_str(
+                std::str::from_utf8(&self.input[start..self.pos])
+                    .map_err(|_| self.error("Invalid UTF-8"))?,
+            );
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+
+                    return Ok(string);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self
+                                .input
+                                .get(self.pos + 1..self.pos + 5)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+
+                            self.pos += 4;
+
+                            // Surrogate pairs are not needed for reports and are replaced.
+                            char::from_u32(hex).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+
+                    self.pos += 1;
+
+                    string.push(escaped);
+                }
+                _ => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("Invalid number"))
+    }
+}
+
+/// The result of checking a report.
+#[derive(Debug, Default)]
+struct Summary {
+    /// Number of cases per behavior.
+    behaviors: BTreeMap<String, usize>,
+    /// Agent, case, behavior and close behavior of the failed cases.
+    failures: Vec<(String, String, String, String)>,
+}
+
+fn check(report: &Value) -> Result<Summary, String> {
+    let mut summary = Summary::default();
+
+    let agents = report.as_object().ok_or("Report is not an object")?;
+
+    for (agent, cases) in agents {
+        let cases = cases
+            .as_object()
+            .ok_or_else(|| format!("Cases of `{agent}` are not an object"))?;
+
+        for (case, result) in cases {
+            let field = |name: &str| {
+                result
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .map(String::from)
+                    .ok_or_else(|| format!("Case `{case}` of `{agent}` has no `{name}`"))
+            };
+
+            let behavior = field("behavior")?;
+            let behavior_close = field("behaviorClose")?;
+
+            *summary.behaviors.entry(behavior.clone()).or_default() += 1;
+
+            let passed = match behavior.as_str() {
+                behavior if PASSING.contains(&behavior) => behavior_close == "OK",
+                behavior => IGNORED.contains(&behavior),
+            };
+
+            if !passed {
+                summary
+                    .failures
+                    .push((agent.clone(), case.clone(), behavior, behavior_close));
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: autobahn-report <index.json>");
+
+        return ExitCode::from(2);
+    };
+
+    let summary = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read `{path}`: {err}"))
+        .and_then(|report| Parser::parse(&report))
+        .and_then(|report| check(&report));
+
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("{err}");
+
+            return ExitCode::from(2);
+        }
+    };
+
+    for (behavior, count) in &summary.behaviors {
+        println!("{behavior}: {count}");
+    }
+
+    if summary.failures.is_empty() {
+        println!("All cases passed");
+
+        return ExitCode::SUCCESS;
+    }
+
+    for (agent, case, behavior, behavior_close) in &summary.failures {
+        eprintln!("{agent} {case}: behavior {behavior}, close behavior {behavior_close}");
+    }
+
+    eprintln!("{} cases failed", summary.failures.len());
+
+    ExitCode::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let value =
+            Parser::parse(r#" {"a": [1, -2.5e1, true, false, null], "b": "x\"A\n", "c": {}} "#)
+                .expect("Valid JSON");
+
+        assert_eq!(
+            value,
+            Value::Object(vec![
+                (
+                    "a".into(),
+                    Value::Array(vec![
+                        Value::Number(1.0),
+                        Value::Number(-25.0),
+                        Value::Bool(true),
+                        Value::Bool(false),
+                        Value::Null,
+                    ])
+                ),
+                ("b".into(), Value::String("x\"A\n".into())),
+                ("c".into(), Value::Object(vec![])),
+            ])
+        );
+
+        assert!(Parser::parse(r#"{"a": 1,}"#).is_err());
+        assert!(Parser::parse(r#"{"a": "unterminated}"#).is_err());
+        assert!(Parser::parse("[1] 2").is_err());
+    }
+
+    #[test]
+    fn failed_cases() {
+        let report = Parser::parse(
+            r#"{"websocketz": {
+                "1.1.1": {"behavior": "OK", "behaviorClose": "OK"},
+                "6.4.1": {"behavior": "NON-STRICT", "behaviorClose": "OK"},
+                "7.1.6": {"behavior": "INFORMATIONAL", "behaviorClose": "INFORMATIONAL"},
+                "12.1.1": {"behavior": "UNIMPLEMENTED", "behaviorClose": "UNIMPLEMENTED"},
+                "5.1": {"behavior": "FAILED", "behaviorClose": "OK"},
+                "7.3.1": {"behavior": "OK", "behaviorClose": "FAILED"}
+            }}"#,
+        )
+        .expect("Valid JSON");
+
+        let summary = check(&report).expect("Valid report");
+
+        assert_eq!(summary.behaviors["OK"], 2);
+        assert_eq!(
+            summary
+                .failures
+                .iter()
+                .map(|(_, case, _, _)| case.as_str())
+                .collect::<Vec<_>>(),
+            ["12.1.1", "5.1", "7.3.1"]
+        );
+    }
+
+    #[test]
+    fn expected_results() {
+        let report = include_str!("../autobahn/expected-results.json");
+
+        let summary = check(&Parser::parse(report).expect("Valid JSON")).expect("Valid report");
+
+        assert!(summary.failures.is_empty());
+    }
+}
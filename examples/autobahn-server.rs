@@ -1,13 +1,20 @@
 //! Run with
 //!
 //! ```not_rust
-//! cargo run --example autobahn-server
+//! cargo run --example autobahn-server --features deflate
 //! ```
 
 use embedded_io_adapters::tokio_1::FromTokio;
 use rand::{SeedableRng, rngs::StdRng};
 use tokio::net::TcpListener;
-use websocketz::{Message, WebSocket, error::Error, next, options::AcceptOptions, send};
+use websocketz::{
+    Message, WebSocket,
+    deflate::Inflater,
+    error::Error,
+    next,
+    options::{AcceptOptions, Profile},
+    send,
+};
 
 const SIZE: usize = 24 * 1024 * 1024;
 
@@ -23,16 +30,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut read_buf = vec![0u8; SIZE];
             let mut write_buf = vec![0u8; SIZE];
             let mut fragments_buf = vec![0u8; SIZE];
+            let mut inflater = Box::new(Inflater::new());
+            let mut inflate_buf = vec![0u8; SIZE];
 
             let mut websocketz = WebSocket::accept::<16>(
-                AcceptOptions::default(),
+                AcceptOptions::default().with_permessage_deflate(true),
                 FromTokio::new(stream),
                 StdRng::from_os_rng(),
                 &mut read_buf,
                 &mut write_buf,
                 &mut fragments_buf,
             )
-            .await?;
+            .await?
+            .with_profile(Profile::Autobahn)
+            .with_inflater(&mut inflater, &mut inflate_buf);
 
             while let Some(message) = next!(websocketz) {
                 match message {
@@ -27,13 +27,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let fragments_buf = &mut [0u8; 8192];
     let rng = StdRng::from_os_rng();
 
-    let (mut websocketz, custom) = WebSocket::connect_with(
+    let (mut websocketz, custom, _) = WebSocket::connect_with(
         ConnectOptions::default()
             // Additional request headers
             .with_headers(&[Header {
                 name: "Client-Header",
                 value: b"Client-Value",
-            }]),
+            }])?,
         FromTokio::new(stream),
         rng,
         read_buf,
@@ -9,6 +9,7 @@
 use embassy_executor::Spawner;
 use embassy_net::{tcp::TcpSocket, Runner, StackResources};
 use embassy_time::{Duration, Timer};
+use embedded_tls::{Aes128GcmSha256, TlsConfig, UnsecureProvider};
 use esp_hal::{clock::CpuClock, rng::Trng, timer::timg::TimerGroup};
 use esp_wifi::{
     wifi::{ClientConfiguration, Configuration, WifiController, WifiDevice, WifiEvent, WifiState},
@@ -17,7 +18,12 @@ use esp_wifi::{
 use httparse::Header;
 use log::{error, info};
 use smoltcp::wire::DnsQueryType;
-use websocketz::{next, options::ConnectOptions, Message, WebSocket};
+use websocketz::{
+    next,
+    options::ConnectOptions,
+    tls::{open_embedded_tls, TlsBufferPlan, TlsBuffers},
+    Message, WebSocket,
+};
 
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo) -> ! {
@@ -39,6 +45,15 @@ macro_rules! mk_static {
     }};
 }
 
+// The server may send records of the maximum size.
+const PLAN: TlsBufferPlan = TlsBufferPlan::new(
+    TlsBufferPlan::RECORD_LEN,
+    TlsBufferPlan::RECORD_LEN,
+    1024,
+    1024,
+    1024,
+);
+
 const SSID: &str = env!("WIFI_SSID");
 const PASSWORD: &str = env!("WIFI_PASSWORD");
 
@@ -108,6 +123,12 @@ async fn main(spawner: Spawner) {
     let mut rx_buffer = [0; 1024];
     let mut tx_buffer = [0; 1024];
 
+    // The TLS and WebSocket buffers, too large for the stack of the task.
+    let buffer = mk_static!([u8; PLAN.len()], [0; PLAN.len()]);
+
+    // The TRNG is taken by the TLS handshake, the WebSocket masks its frames with the RNG.
+    let mut rng = trng.rng;
+
     let domain = "websockets.chilkat.io";
     let ip = *stack
         .dns_query(domain, DnsQueryType::A)
@@ -127,7 +148,7 @@ async fn main(spawner: Spawner) {
 
         info!("Connecting...");
 
-        let r = socket.connect((ip, 80)).await;
+        let r = socket.connect((ip, 443)).await;
 
         if let Err(e) = r {
             error!("Connect error: {:?}", e);
@@ -137,29 +158,29 @@ async fn main(spawner: Spawner) {
 
         info!("Connected!");
 
-        let mut read_buf = [0u8; 1024];
-        let mut write_buf = [0u8; 1024];
-        let mut fragments_buf = [0u8; 1024];
+        // Does not verify the certificate of the server.
+        let config = TlsConfig::new().with_server_name(domain);
+        let provider = UnsecureProvider::new::<Aes128GcmSha256>(&mut trng);
 
-        let mut websocketz = WebSocket::connect::<16>(
+        let mut websocketz = WebSocket::connect_tls::<16, _, _, _>(
             ConnectOptions::default()
                 .with_path_unchecked("/wsChilkatEcho.ashx")
                 .with_headers(&[Header {
                     name: "Host",
                     value: domain.as_bytes(),
-                }]),
+                }])
+                .expect("Valid headers"),
             &mut socket,
-            &mut trng,
-            &mut read_buf,
-            &mut write_buf,
-            &mut fragments_buf,
+            async |socket, buffers: TlsBuffers<'_>| {
+                open_embedded_tls(socket, buffers, &config, provider).await
+            },
+            &mut rng,
+            &mut *buffer,
+            PLAN,
         )
         .await
         .expect("Failed to create WebSocket connection");
 
-        // split the WebSocket into read and write halves
-        // let (mut websocketz_read, mut websocketz_write) = websocketz.split_with(|socket| socket.split());
-
         'ws: loop {
             websocketz
                 .send(Message::Text("Hello, WebSocket!"))
@@ -30,7 +30,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut write_buf = vec![0u8; SIZE];
             let mut fragments_buf = vec![0u8; SIZE];
 
-            let (mut websocketz, custom) = WebSocket::accept_with(
+            let (mut websocketz, custom, _) = WebSocket::accept_with(
                 AcceptOptions::default()
                     // Additional response headers
                     .with_headers(&[Header {
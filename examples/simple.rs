@@ -31,7 +31,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .with_headers(&[Header {
                 name: "Host",
                 value: domain.as_bytes(),
-            }]),
+            }])?,
         FromTokio::new(stream),
         rng,
         read_buf,
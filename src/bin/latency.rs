@@ -0,0 +1,201 @@
+//! Measures the round-trip time to a WebSocket echo endpoint.
+//!
+//! Run with
+//!
+//! ```not_rust
+//! cargo run --features tools --bin websocketz-latency -- <host:port> [path] [count] [size] [--ping]
+//! ```
+//!
+//! Every round trip sends a binary message starting with a sequence number and waits for the echo carrying the same sequence number.
+//! With `--ping`, `Ping` frames are sent instead and the `Pong` responses of the endpoint are awaited.
+
+use std::time::{Duration, Instant};
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use rand::{SeedableRng, rngs::StdRng};
+use tokio::net::TcpStream;
+use websocketz::{
+    CloseCode, CloseFrame, Message, WebSocket, http::Header, options::ConnectOptions,
+};
+
+const USAGE: &str = "Usage: websocketz-latency <host:port> [path] [count] [size] [--ping]";
+
+/// Maximum time to wait for a single echo.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Length of the sequence number at the start of every payload.
+const SEQUENCE_LEN: usize = size_of::<u64>();
+
+struct Args {
+    endpoint: String,
+    path: String,
+    count: usize,
+    size: usize,
+    ping: bool,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut ping = false;
+        let mut positional = Vec::new();
+
+        for arg in std::env::args().skip(1) {
+            match arg.as_str() {
+                "--ping" => ping = true,
+                "-h" | "--help" => return Err(USAGE.into()),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+
+        let endpoint = positional.next().ok_or(USAGE)?;
+        let path = positional.next().unwrap_or_else(|| "/".into());
+
+        let count = match positional.next() {
+            Some(count) => count.parse().map_err(|_| "Invalid count")?,
+            None => 100,
+        };
+
+        let size = match positional.next() {
+            Some(size) => size.parse().map_err(|_| "Invalid size")?,
+            None => 32,
+        };
+
+        if count == 0 {
+            return Err("Count must be at least 1".into());
+        }
+
+        if u32::try_from(count).is_err() {
+            return Err(format!("Count must not exceed {}", u32::MAX));
+        }
+
+        if size < SEQUENCE_LEN {
+            return Err(format!("Size must be at least {SEQUENCE_LEN} bytes"));
+        }
+
+        if ping && size > 125 {
+            return Err("Ping payloads must not exceed 125 bytes".into());
+        }
+
+        Ok(Self {
+            endpoint,
+            path,
+            count,
+            size,
+            ping,
+        })
+    }
+}
+
+/// Returns the `percentile` of the sorted `rtts`.
+fn percentile(rtts: &[Duration], percentile: usize) -> Duration {
+    rtts[(rtts.len() - 1) * percentile / 100]
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+
+            std::process::exit(2);
+        }
+    };
+
+    let stream = TcpStream::connect(&args.endpoint).await?;
+    stream.set_nodelay(true)?;
+
+    let read_buf = &mut vec![0u8; args.size + 1024];
+    let write_buf = &mut vec![0u8; args.size + 1024];
+    let fragments_buf = &mut vec![0u8; args.size];
+
+    let headers = &[Header {
+        name: "Host",
+        value: args.endpoint.as_bytes(),
+    }];
+
+    let mut websocketz = WebSocket::connect::<16>(
+        ConnectOptions::new(&args.path)?.with_headers(headers)?,
+        FromTokio::new(stream),
+        StdRng::from_os_rng(),
+        read_buf,
+        write_buf,
+        fragments_buf,
+    )
+    .await?;
+
+    let mode = if args.ping { "ping" } else { "echo" };
+
+    println!(
+        "Measuring {} round trips of {} bytes to {}{} ({mode})",
+        args.count, args.size, args.endpoint, args.path
+    );
+
+    let mut payload = vec![0u8; args.size];
+    let mut rtts = Vec::with_capacity(args.count);
+
+    for sequence in 0..args.count as u64 {
+        let sequence = sequence.to_be_bytes();
+
+        payload[..SEQUENCE_LEN].copy_from_slice(&sequence);
+
+        let start = Instant::now();
+
+        match args.ping {
+            true => websocketz.send(Message::Ping(&payload)).await?,
+            false => websocketz.send(Message::Binary(&payload)).await?,
+        }
+
+        // Messages not carrying the current sequence number, e.g. late echoes, are skipped.
+        let echo = async {
+            loop {
+                let echoed = websocketz
+                    .with_next(|message| match message {
+                        Message::Pong(payload) | Message::Binary(payload) => {
+                            payload.starts_with(&sequence)
+                        }
+                        _ => false,
+                    })
+                    .await;
+
+                match echoed {
+                    Some(Ok(true)) => return Ok(()),
+                    Some(Ok(false)) => continue,
+                    Some(Err(err)) => return Err(err.to_string()),
+                    None => return Err(String::from("Connection closed")),
+                }
+            }
+        };
+
+        tokio::time::timeout(TIMEOUT, echo)
+            .await
+            .map_err(|_| "Timed out waiting for the echo")??;
+
+        rtts.push(start.elapsed());
+    }
+
+    let _ = websocketz
+        .send_close_and_flush(Some(CloseFrame::no_reason(CloseCode::Normal)))
+        .await;
+
+    rtts.sort_unstable();
+
+    let (Some(min), Some(max)) = (rtts.first(), rtts.last()) else {
+        println!("No round trips measured");
+
+        return Ok(());
+    };
+
+    let total: Duration = rtts.iter().sum();
+
+    println!("min: {min:?}");
+    println!("avg: {:?}", total / u32::try_from(rtts.len())?);
+    println!("p50: {:?}", percentile(&rtts, 50));
+    println!("p90: {:?}", percentile(&rtts, 90));
+    println!("p99: {:?}", percentile(&rtts, 99));
+    println!("max: {max:?}");
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! An interactive WebSocket client, reading lines from stdin and printing received messages.
+//!
+//! Run with
+//!
+//! ```not_rust
+//! cargo run --features tools --bin websocketz-repl -- ws://<host>[:port][/path]
+//! ```
+//!
+//! Every line is sent as a text message, unless it is one of the following commands:
+//!
+//! - `/binary <text>`: Sends `text` as a binary message.
+//! - `/ping [payload]`: Sends a `Ping` frame.
+//! - `/close [reason]`: Sends a `Close` frame and waits for the endpoint to close the connection.
+//! - `/quit`: Exits without closing the connection.
+//!
+//! Closing stdin (e.g. `Ctrl-D`) closes the connection. Received `Ping` frames are answered with a `Pong`.
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use rand::{SeedableRng, rngs::StdRng};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, ReadHalf, WriteHalf},
+    net::TcpStream,
+};
+use websocketz::{
+    CloseCode, CloseFrame, Message, WebSocket, http::Header, options::ConnectOptions,
+};
+
+const USAGE: &str = "Usage: websocketz-repl ws://<host>[:port][/path]";
+
+/// Size of the read, write and fragments buffers.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A parsed `ws://` URL.
+struct Url<'a> {
+    /// Host and port, used for the `Host` header.
+    authority: &'a str,
+    /// Address to connect to, with the default port if none was given.
+    address: String,
+    path: &'a str,
+}
+
+impl<'a> Url<'a> {
+    fn parse(url: &'a str) -> Result<Self, String> {
+        let rest = match url.strip_prefix("ws://") {
+            Some(rest) => rest,
+            None if url.starts_with("wss://") => return Err("TLS (wss://) is not supported".into()),
+            None => return Err(format!("Invalid URL `{url}`\n{USAGE}")),
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        if authority.is_empty() {
+            return Err(format!("Missing host in `{url}`"));
+        }
+
+        let address = match authority.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => authority.to_string(),
+            _ => format!("{authority}:80"),
+        };
+
+        Ok(Self {
+            authority,
+            address,
+            path,
+        })
+    }
+}
+
+/// A line read from stdin.
+enum Command<'a> {
+    Send(Message<'a>),
+    Quit,
+}
+
+impl<'a> Command<'a> {
+    fn parse(line: &'a str) -> Result<Self, String> {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+
+        let command = match command {
+            "/binary" => Command::Send(Message::Binary(arg.as_bytes())),
+            "/ping" if arg.len() > 125 => {
+                return Err("Ping payloads must not exceed 125 bytes".into());
+            }
+            "/ping" => Command::Send(Message::Ping(arg.as_bytes())),
+            "/close" => Command::Send(Message::Close(Some(CloseFrame::new_truncated(
+                CloseCode::Normal,
+                arg,
+                CloseFrame::MAX_REASON_LEN,
+            )))),
+            "/quit" => Command::Quit,
+            command if command.starts_with('/') => {
+                return Err(format!("Unknown command `{command}`"));
+            }
+            _ => Command::Send(Message::Text(line)),
+        };
+
+        Ok(command)
+    }
+}
+
+/// What the reader saw, handled once the read half is no longer borrowed.
+enum Received {
+    /// A `Ping` to answer with its payload.
+    Ping(Vec<u8>),
+    Close,
+    Other,
+}
+
+fn print(message: Message<'_>) -> Received {
+    match message {
+        Message::Text(text) => println!("< {text}"),
+        Message::TextUnchecked(payload) => println!("< {}", String::from_utf8_lossy(payload)),
+        Message::Binary(payload) => match std::str::from_utf8(payload) {
+            Ok(text) => println!("< [binary, {} bytes] {text}", payload.len()),
+            Err(_) => println!("< [binary, {} bytes] {payload:02x?}", payload.len()),
+        },
+        Message::Ping(payload) => {
+            println!("< [ping] {}", String::from_utf8_lossy(payload));
+
+            return Received::Ping(payload.to_vec());
+        }
+        Message::Pong(payload) => println!("< [pong] {}", String::from_utf8_lossy(payload)),
+        Message::Close(frame) => {
+            match frame {
+                Some(frame) => println!("< [close] {:?} {}", frame.code(), frame.reason()),
+                None => println!("< [close]"),
+            }
+
+            return Received::Close;
+        }
+    }
+
+    Received::Other
+}
+
+fn split(
+    stream: FromTokio<TcpStream>,
+) -> (
+    FromTokio<ReadHalf<TcpStream>>,
+    FromTokio<WriteHalf<TcpStream>>,
+) {
+    let (read, write) = tokio::io::split(stream.into_inner());
+
+    (FromTokio::new(read), FromTokio::new(write))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = match std::env::args().nth(1) {
+        Some(url) if url != "-h" && url != "--help" => url,
+        _ => {
+            eprintln!("{USAGE}");
+
+            std::process::exit(2);
+        }
+    };
+
+    let url = match Url::parse(&url) {
+        Ok(url) => url,
+        Err(err) => {
+            eprintln!("{err}");
+
+            std::process::exit(2);
+        }
+    };
+
+    let stream = TcpStream::connect(&url.address).await?;
+
+    let read_buf = &mut vec![0u8; BUFFER_SIZE];
+    let write_buf = &mut vec![0u8; BUFFER_SIZE];
+    let fragments_buf = &mut vec![0u8; BUFFER_SIZE];
+
+    let headers = &[Header {
+        name: "Host",
+        value: url.authority.as_bytes(),
+    }];
+
+    let websocketz = WebSocket::connect::<32>(
+        ConnectOptions::new(url.path)?.with_headers(headers)?,
+        FromTokio::new(stream),
+        StdRng::from_os_rng(),
+        read_buf,
+        write_buf,
+        fragments_buf,
+    )
+    .await?;
+
+    eprintln!("Connected to {}{}", url.authority, url.path);
+
+    // Reading is cancel-safe, so the read half can wait for messages while stdin is read.
+    let (mut read, mut write) = websocketz.split_with(split);
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line(), if stdin_open => {
+                let Some(line) = line? else {
+                    stdin_open = false;
+
+                    write
+                        .send_close_and_flush(Some(CloseFrame::no_reason(CloseCode::Normal)))
+                        .await?;
+
+                    continue;
+                };
+
+                match Command::parse(&line) {
+                    Ok(Command::Send(message)) => {
+                        if let Err(err) = write.send(message).await {
+                            eprintln!("{err}");
+                        }
+                    }
+                    Ok(Command::Quit) => return Ok(()),
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+            received = read.with_next(print) => {
+                match received.transpose()? {
+                    Some(Received::Ping(payload)) => {
+                        if let Err(err) = write.send(Message::Pong(&payload)).await {
+                            eprintln!("{err}");
+                        }
+                    }
+                    Some(Received::Close) => {
+                        // Answers the Close frame, unless it answered ours.
+                        write.send_close_and_flush(None).await?;
+
+                        return Ok(());
+                    }
+                    Some(Received::Other) => {}
+                    None => {
+                        eprintln!("Connection closed");
+
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
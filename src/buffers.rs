@@ -0,0 +1,563 @@
+//! Buffers of WebSocket connections.
+//!
+//! A `BufferPool` (`buffer-pool` feature) holds the buffers of a fixed number of connections, so that servers handling many connections
+//! can lend every connection its buffers and take them back once it is closed, without any `unsafe` static buffers.
+//!
+//! A [`BufferPlan`] computes the sizes the buffers need for the expected frames, messages and handshake,
+//! and a [`WebSocketBuilder`] checks the buffers against it before they are used,
+//! instead of failing deep in the handshake or on the first large frame.
+
+#[cfg(feature = "buffer-pool")]
+use spin::mutex::{SpinMutex, SpinMutexGuard};
+
+use crate::{
+    WebSocket,
+    codec::Staged,
+    error::BufferError,
+    http::{estimated_request_size, estimated_response_size},
+};
+
+/// The read, write and fragments buffers of a single connection.
+#[derive(Debug)]
+pub struct Buffers<'buf> {
+    /// The read buffer.
+    pub read: &'buf mut [u8],
+    /// The write buffer.
+    pub write: &'buf mut [u8],
+    /// The fragments buffer.
+    pub fragments: &'buf mut [u8],
+}
+
+impl<'buf> Buffers<'buf> {
+    /// Creates new [`Buffers`].
+    pub const fn new(
+        read: &'buf mut [u8],
+        write: &'buf mut [u8],
+        fragments: &'buf mut [u8],
+    ) -> Self {
+        Self {
+            read,
+            write,
+            fragments,
+        }
+    }
+}
+
+/// Returns the length of a frame with a payload of `payload_len` bytes.
+const fn frame_len(payload_len: usize, masked: bool) -> usize {
+    let extended = match payload_len {
+        0..=125 => 0,
+        126..=65535 => 2,
+        _ => 8,
+    };
+
+    let mask = if masked { 4 } else { 0 };
+
+    2 + extended + mask + payload_len
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+/// The minimum sizes of the buffers of a client or server connection.
+///
+/// Built from the largest expected frames and messages and the size of the handshake.
+/// The handshake defaults to a request to `/` and a response without additional headers,
+/// see [`estimated_request_size`] and [`estimated_response_size`].
+///
+/// The sizes are `const`, so that buffers can be sized at compile time.
+///
+/// # Example
+///
+#[cfg_attr(feature = "client", doc = "```")]
+#[cfg_attr(not(feature = "client"), doc = "```ignore")]
+/// use websocketz::buffers::BufferPlan;
+///
+/// const PLAN: BufferPlan = BufferPlan::client()
+///     .with_max_received_frame(1024)
+///     .with_max_sent_frame(256)
+///     .with_max_message(4096);
+///
+/// let read_buffer = &mut [0u8; PLAN.read_len()];
+/// let write_buffer = &mut [0u8; PLAN.write_len()];
+/// let fragments_buffer = &mut [0u8; PLAN.fragments_len()];
+/// # assert_eq!((read_buffer.len(), write_buffer.len(), fragments_buffer.len()), (1028, 270, 4096));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPlan {
+    client: bool,
+    max_received_frame: usize,
+    max_sent_frame: usize,
+    max_message: usize,
+    request_size: usize,
+    response_size: usize,
+}
+
+impl BufferPlan {
+    const fn new(client: bool) -> Self {
+        Self {
+            client,
+            max_received_frame: 0,
+            max_sent_frame: 0,
+            max_message: 0,
+            request_size: estimated_request_size(1, 0),
+            response_size: estimated_response_size(0),
+        }
+    }
+
+    /// Creates a new [`BufferPlan`] for a client, which masks the frames it sends.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub const fn client() -> Self {
+        Self::new(true)
+    }
+
+    /// Creates a new [`BufferPlan`] for a server, which receives masked frames.
+    pub const fn server() -> Self {
+        Self::new(false)
+    }
+
+    /// Returns `true` if the plan is for a client.
+    pub const fn is_client(&self) -> bool {
+        self.client
+    }
+
+    /// Sets the payload length of the largest frame expected to be received.
+    pub const fn with_max_received_frame(mut self, len: usize) -> Self {
+        self.max_received_frame = len;
+        self
+    }
+
+    /// Sets the payload length of the largest frame to be sent.
+    pub const fn with_max_sent_frame(mut self, len: usize) -> Self {
+        self.max_sent_frame = len;
+        self
+    }
+
+    /// Sets the length of the largest fragmented message expected to be received.
+    ///
+    /// `0` if no fragmented messages are expected.
+    pub const fn with_max_message(mut self, len: usize) -> Self {
+        self.max_message = len;
+        self
+    }
+
+    /// Sets the size of the handshake request, see [`estimated_request_size`].
+    pub const fn with_request_size(mut self, size: usize) -> Self {
+        self.request_size = size;
+        self
+    }
+
+    /// Sets the size of the handshake response, see [`estimated_response_size`].
+    pub const fn with_response_size(mut self, size: usize) -> Self {
+        self.response_size = size;
+        self
+    }
+
+    /// Returns the minimum length of the read buffer.
+    ///
+    /// Holds the largest received frame and the received handshake request or response.
+    pub const fn read_len(&self) -> usize {
+        let handshake = if self.client {
+            self.response_size
+        } else {
+            self.request_size
+        };
+
+        max(frame_len(self.max_received_frame, !self.client), handshake)
+    }
+
+    /// Returns the minimum length of the write buffer.
+    ///
+    /// Holds the largest sent frame and the sent handshake request or response.
+    /// Frames whose payload is written directly into the write buffer, e.g. with [`WebSocket::send_binary_with`]
+    /// or a [`MessageWriter`](crate::MessageWriter), reserve room for the largest frame header of 14 bytes,
+    /// which is accounted for.
+    pub const fn write_len(&self) -> usize {
+        let handshake = if self.client {
+            self.request_size
+        } else {
+            self.response_size
+        };
+
+        max(Staged::OFFSET + self.max_sent_frame, handshake)
+    }
+
+    /// Returns the minimum length of the fragments buffer.
+    pub const fn fragments_len(&self) -> usize {
+        self.max_message
+    }
+
+    /// Checks the lengths of `buffers` against the plan.
+    ///
+    /// # Errors
+    /// The first buffer that is too small, in the order read, write, fragments.
+    pub const fn validate(&self, buffers: &Buffers<'_>) -> Result<(), BufferError> {
+        if buffers.read.len() < self.read_len() {
+            return Err(BufferError::ReadTooSmall {
+                required: self.read_len(),
+                actual: buffers.read.len(),
+            });
+        }
+
+        if buffers.write.len() < self.write_len() {
+            return Err(BufferError::WriteTooSmall {
+                required: self.write_len(),
+                actual: buffers.write.len(),
+            });
+        }
+
+        if buffers.fragments.len() < self.fragments_len() {
+            return Err(BufferError::FragmentsTooSmall {
+                required: self.fragments_len(),
+                actual: buffers.fragments.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a [`WebSocket`] from buffers checked against a [`BufferPlan`].
+///
+/// Buffers that are not set are empty.
+///
+/// # Example
+///
+/// ```
+/// # async fn run() -> Result<(), websocketz::error::BufferError> {
+/// # use websocketz::mock::Noop;
+/// use websocketz::{WebSocket, buffers::{BufferPlan, WebSocketBuilder}, options::AcceptOptions};
+///
+/// let (read_buffer, write_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024]);
+///
+/// let plan = BufferPlan::server().with_max_received_frame(512);
+///
+/// // Fails with `BufferError::ReadTooSmall` if the read buffer can not hold a frame of 512 bytes.
+/// let buffers = WebSocketBuilder::new(plan)
+///     .with_read_buffer(read_buffer)
+///     .with_write_buffer(write_buffer)
+///     .build()?;
+///
+/// let websocketz = WebSocket::accept::<16>(
+///     AcceptOptions::default(),
+///     Noop,
+///     Noop,
+///     buffers.read,
+///     buffers.write,
+///     buffers.fragments,
+/// )
+/// .await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct WebSocketBuilder<'buf> {
+    plan: BufferPlan,
+    buffers: Buffers<'buf>,
+}
+
+impl<'buf> WebSocketBuilder<'buf> {
+    /// Creates a new [`WebSocketBuilder`] checking the buffers against `plan`.
+    pub fn new(plan: BufferPlan) -> Self {
+        Self {
+            plan,
+            buffers: Buffers::new(&mut [], &mut [], &mut []),
+        }
+    }
+
+    /// Returns the plan the buffers are checked against.
+    pub const fn plan(&self) -> &BufferPlan {
+        &self.plan
+    }
+
+    /// Sets the read buffer.
+    pub fn with_read_buffer(mut self, read: &'buf mut [u8]) -> Self {
+        self.buffers.read = read;
+        self
+    }
+
+    /// Sets the write buffer.
+    pub fn with_write_buffer(mut self, write: &'buf mut [u8]) -> Self {
+        self.buffers.write = write;
+        self
+    }
+
+    /// Sets the fragments buffer.
+    pub fn with_fragments_buffer(mut self, fragments: &'buf mut [u8]) -> Self {
+        self.buffers.fragments = fragments;
+        self
+    }
+
+    /// Sets all buffers, e.g. from a `Checkout` of a `BufferPool`.
+    pub fn with_buffers(mut self, buffers: Buffers<'buf>) -> Self {
+        self.buffers = buffers;
+        self
+    }
+
+    /// Checks the buffers against the plan and returns them, e.g. to perform a handshake.
+    ///
+    /// # Errors
+    /// See [`BufferPlan::validate`].
+    pub fn build(self) -> Result<Buffers<'buf>, BufferError> {
+        self.plan.validate(&self.buffers)?;
+
+        Ok(self.buffers)
+    }
+
+    /// Checks the buffers against the plan and creates a [`WebSocket`] client or server, depending on the plan.
+    ///
+    /// No handshake is performed, the connection must already be established, e.g. by a handshake done with the buffers
+    /// returned by [`WebSocketBuilder::build`].
+    ///
+    /// # Errors
+    /// See [`BufferPlan::validate`].
+    pub fn into_websocket<RW, Rng>(
+        self,
+        inner: RW,
+        rng: Rng,
+    ) -> Result<WebSocket<'buf, RW, Rng>, BufferError> {
+        let client = self.plan.is_client();
+        let Buffers {
+            read,
+            write,
+            fragments,
+        } = self.build()?;
+
+        #[cfg(feature = "client")]
+        if client {
+            return Ok(WebSocket::client(inner, rng, read, write, fragments));
+        }
+
+        #[cfg(not(feature = "client"))]
+        let _ = client;
+
+        Ok(WebSocket::server(inner, rng, read, write, fragments))
+    }
+}
+
+/// A pool of [`Buffers`] for up to `N` connections.
+///
+/// Every buffer is `SZ` bytes long. The pool can be a `static` shared by tasks of several executors or interrupt priorities:
+/// every slot is taken with a single atomic flag, checking out never blocks.
+///
+/// # Example
+///
+/// ```
+/// # async fn serve() {
+/// # use websocketz::mock::Noop;
+/// use websocketz::{WebSocket, buffers::BufferPool, options::AcceptOptions};
+///
+/// static POOL: BufferPool<4, 1024> = BufferPool::new();
+///
+/// // Reject the connection if all buffers are in use.
+/// let Some(mut checkout) = POOL.checkout() else {
+///     return;
+/// };
+///
+/// let websocketz = WebSocket::accept_pooled::<16, 1024>(AcceptOptions::default(), Noop, Noop, &mut checkout)
+///     .await
+///     .expect("Handshake failed");
+///
+/// // The buffers are checked back in when `checkout` is dropped.
+/// # }
+/// ```
+#[cfg(feature = "buffer-pool")]
+#[cfg_attr(docsrs, doc(cfg(feature = "buffer-pool")))]
+#[derive(Debug)]
+pub struct BufferPool<const N: usize, const SZ: usize> {
+    slots: [SpinMutex<[[u8; SZ]; 3]>; N],
+}
+
+#[cfg(feature = "buffer-pool")]
+impl<const N: usize, const SZ: usize> Default for BufferPool<N, SZ> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "buffer-pool")]
+impl<const N: usize, const SZ: usize> BufferPool<N, SZ> {
+    /// Creates a new [`BufferPool`].
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { SpinMutex::new([[0; SZ]; 3]) }; N],
+        }
+    }
+
+    /// Checks out the buffers of a free slot.
+    ///
+    /// Returns `None` if all slots are in use.
+    pub fn checkout(&self) -> Option<Checkout<'_, SZ>> {
+        self.slots
+            .iter()
+            .enumerate()
+            .find_map(|(index, slot)| slot.try_lock().map(|buffers| Checkout { index, buffers }))
+    }
+
+    /// Checks the buffers of `checkout` back in.
+    ///
+    /// Same as dropping the [`Checkout`].
+    pub fn checkin(&self, checkout: Checkout<'_, SZ>) {
+        drop(checkout)
+    }
+
+    /// Returns the number of free slots.
+    ///
+    /// The count is a snapshot of every slot's lock flag at the time it is read:
+    /// slots may be checked out or in by other tasks while counting and right after,
+    /// so a following [`checkout`](Self::checkout) can still fail. Use it for metrics, not to reserve a slot.
+    pub fn available(&self) -> usize {
+        self.slots.iter().filter(|slot| !slot.is_locked()).count()
+    }
+
+    /// Returns the total number of slots.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+/// The buffers of a slot checked out from a [`BufferPool`].
+///
+/// The slot is checked back in when dropped.
+#[cfg(feature = "buffer-pool")]
+#[cfg_attr(docsrs, doc(cfg(feature = "buffer-pool")))]
+#[derive(Debug)]
+pub struct Checkout<'pool, const SZ: usize> {
+    index: usize,
+    buffers: SpinMutexGuard<'pool, [[u8; SZ]; 3]>,
+}
+
+#[cfg(feature = "buffer-pool")]
+impl<const SZ: usize> Checkout<'_, SZ> {
+    /// Returns the index of the slot.
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the buffers of the slot.
+    pub fn buffers(&mut self) -> Buffers<'_> {
+        let [read, write, fragments] = &mut *self.buffers;
+
+        Buffers::new(read, write, fragments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "buffer-pool")]
+    fn checkout_and_checkin() {
+        let pool = BufferPool::<2, 8>::new();
+
+        let first = pool.checkout().expect("Slot must be free");
+        let second = pool.checkout().expect("Slot must be free");
+
+        assert_eq!(first.index(), 0);
+        assert_eq!(second.index(), 1);
+        assert_eq!(pool.available(), 0);
+        assert!(pool.checkout().is_none());
+
+        pool.checkin(first);
+
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.checkout().map(|checkout| checkout.index()), Some(0));
+
+        drop(second);
+
+        assert_eq!(pool.available(), pool.capacity());
+    }
+
+    #[test]
+    #[cfg(feature = "buffer-pool")]
+    fn static_pool() {
+        static POOL: BufferPool<2, 8> = BufferPool::new();
+
+        let checkout = std::thread::spawn(|| POOL.checkout().map(|checkout| checkout.index()))
+            .join()
+            .expect("Thread panicked");
+
+        assert_eq!(checkout, Some(0));
+        assert_eq!(POOL.available(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "buffer-pool")]
+    fn buffers_have_the_slot_size() {
+        let pool = BufferPool::<1, 8>::new();
+
+        let mut checkout = pool.checkout().expect("Slot must be free");
+        let buffers = checkout.buffers();
+
+        assert_eq!(buffers.read.len(), 8);
+        assert_eq!(buffers.write.len(), 8);
+        assert_eq!(buffers.fragments.len(), 8);
+    }
+
+    #[test]
+    fn plan_lengths() {
+        let plan = BufferPlan::server()
+            .with_max_received_frame(1024)
+            .with_max_sent_frame(100)
+            .with_response_size(64);
+
+        // The handshake request is larger than the received frames.
+        assert_eq!(
+            plan.with_max_received_frame(0).read_len(),
+            estimated_request_size(1, 0)
+        );
+        assert_eq!(plan.read_len(), 2 + 2 + 4 + 1024);
+        assert_eq!(plan.with_max_sent_frame(0).write_len(), 64);
+        assert_eq!(plan.write_len(), 14 + 100);
+        assert_eq!(plan.with_max_sent_frame(70000).write_len(), 14 + 70000);
+        assert_eq!(plan.fragments_len(), 0);
+    }
+
+    #[test]
+    fn builder_reports_buffer_too_small() {
+        let plan = BufferPlan::server()
+            .with_max_received_frame(256)
+            .with_max_message(512);
+
+        let error = WebSocketBuilder::new(plan)
+            .with_read_buffer(&mut [0u8; 256])
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            BufferError::ReadTooSmall {
+                required: 264,
+                actual: 256
+            }
+        );
+
+        let error = WebSocketBuilder::new(plan)
+            .with_read_buffer(&mut [0u8; 264])
+            .with_write_buffer(&mut [0u8; 512])
+            .with_fragments_buffer(&mut [0u8; 511])
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            BufferError::FragmentsTooSmall {
+                required: 512,
+                actual: 511
+            }
+        );
+
+        let pool = BufferPool::<1, 512>::new();
+        let mut checkout = pool.checkout().expect("Slot must be free");
+
+        let buffers = WebSocketBuilder::new(plan)
+            .with_buffers(checkout.buffers())
+            .build()
+            .expect("Buffers are large enough");
+
+        assert_eq!(buffers.fragments.len(), 512);
+    }
+}
@@ -0,0 +1,181 @@
+//! Monotonic time sources.
+//!
+//! This library has no timers of its own. Subsystems depending on time, e.g. [`HandshakeStats::duration`](crate::HandshakeStats::duration),
+//! read it from a [`Clock`] provided by the caller.
+//!
+//! With the `std` feature, [`StdClock`] reads [`std::time::Instant`],
+//! and with the `embassy-time` feature, `EmbassyClock` reads `embassy_time::Instant`.
+//! Other time sources implement [`Clock`] directly, e.g. a 32768 Hz hardware timer:
+//!
+//! ```
+//! # mod rtc { pub fn counter() -> u64 { 0 } }
+//! use websocketz::clock::Clock;
+//!
+//! struct RtcClock;
+//!
+//! impl Clock for RtcClock {
+//!     fn tick_hz(&self) -> u64 {
+//!         32_768
+//!     }
+//!
+//!     fn now(&self) -> u64 {
+//!         rtc::counter()
+//!     }
+//! }
+//! ```
+//!
+//! Functions returning the time elapsed since a fixed point in time implement [`Clock`] with a microsecond resolution.
+
+use core::time::Duration;
+
+/// A monotonic clock counting ticks at a fixed rate.
+///
+/// Clocks must be [`Sync`] so that the options holding them can be held across `.await` points of `Send` futures.
+pub trait Clock: Sync {
+    /// Returns the number of ticks per second.
+    fn tick_hz(&self) -> u64;
+
+    /// Returns the number of ticks elapsed since an arbitrary, fixed point in time.
+    ///
+    /// Must never decrease.
+    fn now(&self) -> u64;
+
+    /// Returns the time elapsed since `start`, a value previously returned by [`Clock::now`].
+    fn elapsed(&self, start: u64) -> Duration {
+        ticks_to_duration(self.now().saturating_sub(start), self.tick_hz())
+    }
+}
+
+impl Clock for fn() -> Duration {
+    fn tick_hz(&self) -> u64 {
+        1_000_000
+    }
+
+    fn now(&self) -> u64 {
+        self().as_micros() as u64
+    }
+}
+
+/// Converts a number of ticks at `tick_hz` ticks per second to a [`Duration`].
+///
+/// Returns [`Duration::ZERO`] if `tick_hz` is `0`.
+pub const fn ticks_to_duration(ticks: u64, tick_hz: u64) -> Duration {
+    if tick_hz == 0 {
+        return Duration::ZERO;
+    }
+
+    let secs = ticks / tick_hz;
+    let nanos = (ticks % tick_hz) as u128 * 1_000_000_000 / tick_hz as u128;
+
+    Duration::new(secs, nanos as u32)
+}
+
+/// A [`Clock`] reading [`std::time::Instant`], counting nanoseconds since its creation.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Creates a new [`StdClock`] starting now.
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn tick_hz(&self) -> u64 {
+        1_000_000_000
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+}
+
+/// A [`Clock`] reading `embassy_time::Instant`, counting ticks of the configured embassy tick rate.
+#[cfg(feature = "embassy-time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embassy-time")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbassyClock;
+
+#[cfg(feature = "embassy-time")]
+impl Clock for EmbassyClock {
+    fn tick_hz(&self) -> u64 {
+        embassy_time::TICK_HZ
+    }
+
+    fn now(&self) -> u64 {
+        embassy_time::Instant::now().as_ticks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks() {
+        assert_eq!(ticks_to_duration(0, 1_000), Duration::ZERO);
+        assert_eq!(
+            ticks_to_duration(1_500, 1_000),
+            Duration::from_millis(1_500)
+        );
+        assert_eq!(ticks_to_duration(3, 32_768), Duration::from_nanos(91_552));
+        assert_eq!(
+            ticks_to_duration(u64::MAX, 1),
+            Duration::from_secs(u64::MAX)
+        );
+        assert_eq!(ticks_to_duration(5, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn function_clock() {
+        fn now() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        let clock: fn() -> Duration = now;
+
+        assert_eq!(clock.now(), 10_000);
+        assert_eq!(clock.elapsed(4_000), Duration::from_millis(6));
+        assert_eq!(clock.elapsed(20_000), Duration::ZERO);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn std_clock() {
+        let clock = StdClock::new();
+
+        let start = clock.now();
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(clock.elapsed(start) >= Duration::from_millis(1));
+    }
+
+    #[cfg(feature = "embassy-time")]
+    #[test]
+    fn embassy_clock() {
+        let clock = EmbassyClock;
+
+        let start = clock.now();
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(clock.elapsed(start) >= Duration::from_millis(1));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::CloseFrame;
+
 /// A WebSocket Close code.
 ///
 /// Indicate why an endpoint is closing the WebSocket connection.
@@ -63,6 +65,9 @@ pub enum CloseCode {
     /// to a different IP (when multiple targets exist), or reconnect to the same IP
     /// when a user has performed an action.
     Again = 1013,
+    /// Indicates that the server was acting as a gateway or proxy and received an
+    /// invalid response from the upstream server.
+    BadGateway = 1014,
     #[doc(hidden)]
     Tls = 1015,
     #[doc(hidden)]
@@ -76,7 +81,24 @@ pub enum CloseCode {
 }
 
 impl CloseCode {
-    pub(crate) const fn is_allowed(self) -> bool {
+    /// Returns `true` if the code may be sent in a Close frame.
+    ///
+    /// Codes below `1000`, above `4999` and reserved codes, e.g. `1004`, [`CloseCode::Status`] and [`CloseCode::Abnormal`],
+    /// are not allowed (RFC 6455, section 7.4). Received Close frames carrying such codes fail with
+    /// [`ProtocolError::InvalidCloseCode`](crate::error::ProtocolError::InvalidCloseCode).
+    ///
+    /// # Example
+    ///
+    /// Validating a code chosen by the application before closing with it:
+    ///
+    /// ```
+    /// use websocketz::CloseCode;
+    ///
+    /// assert!(CloseCode::from_u16(4001).is_allowed());
+    /// assert!(!CloseCode::from_u16(1005).is_allowed());
+    /// assert!(!CloseCode::from_u16(5000).is_allowed());
+    /// ```
+    pub const fn is_allowed(self) -> bool {
         !matches!(
             self,
             CloseCode::Bad(_)
@@ -87,7 +109,10 @@ impl CloseCode {
         )
     }
 
-    pub(crate) const fn from_u16(code: u16) -> Self {
+    /// Returns the [`CloseCode`] of the numeric `code`.
+    ///
+    /// Every `u16` maps to a code, [`CloseCode::into_u16`] returns `code` again.
+    pub const fn from_u16(code: u16) -> Self {
         match code {
             1000 => Self::Normal,
             1001 => Self::Away,
@@ -102,16 +127,17 @@ impl CloseCode {
             1011 => Self::Error,
             1012 => Self::Restart,
             1013 => Self::Again,
+            1014 => Self::BadGateway,
             1015 => Self::Tls,
-            1..=999 => Self::Bad(code),
-            1016..=2999 => Self::Reserved(code),
+            1004 | 1016..=2999 => Self::Reserved(code),
             3000..=3999 => Self::Iana(code),
             4000..=4999 => Self::Library(code),
-            _ => Self::Bad(code),
+            0..=999 | 5000..=u16::MAX => Self::Bad(code),
         }
     }
 
-    pub(crate) const fn into_u16(self) -> u16 {
+    /// Returns the numeric close code.
+    pub const fn into_u16(self) -> u16 {
         match self {
             Self::Normal => 1000,
             Self::Away => 1001,
@@ -126,6 +152,7 @@ impl CloseCode {
             Self::Error => 1011,
             Self::Restart => 1012,
             Self::Again => 1013,
+            Self::BadGateway => 1014,
             Self::Tls => 1015,
             Self::Reserved(code) => code,
             Self::Iana(code) => code,
@@ -134,3 +161,265 @@ impl CloseCode {
         }
     }
 }
+
+impl From<u16> for CloseCode {
+    fn from(code: u16) -> Self {
+        Self::from_u16(code)
+    }
+}
+
+impl From<CloseCode> for u16 {
+    fn from(code: CloseCode) -> Self {
+        code.into_u16()
+    }
+}
+
+/// An application close code in the range `4000..=4999`.
+///
+/// Converts into a [`CloseCode`] to close a connection with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LibraryCloseCode(u16);
+
+impl LibraryCloseCode {
+    /// The smallest application close code.
+    pub const MIN: u16 = 4000;
+
+    /// The largest application close code.
+    pub const MAX: u16 = 4999;
+
+    /// Creates a new [`LibraryCloseCode`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `code` is not in the range `4000..=4999`, at compile time when used in a const context.
+    pub const fn new(code: u16) -> Self {
+        match Self::try_new(code) {
+            Some(code) => code,
+            None => panic!("Application close codes must be in the range 4000..=4999"),
+        }
+    }
+
+    /// Creates a new [`LibraryCloseCode`], returning `None` if `code` is not in the range `4000..=4999`.
+    pub const fn try_new(code: u16) -> Option<Self> {
+        match code {
+            Self::MIN..=Self::MAX => Some(Self(code)),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`LibraryCloseCode`] of `code`, if it is an application close code.
+    pub const fn from_close_code(code: CloseCode) -> Option<Self> {
+        match code {
+            CloseCode::Library(code) => Some(Self(code)),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric close code.
+    pub const fn code(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns the [`CloseCode`].
+    pub const fn close_code(&self) -> CloseCode {
+        CloseCode::Library(self.0)
+    }
+}
+
+impl From<LibraryCloseCode> for CloseCode {
+    fn from(code: LibraryCloseCode) -> Self {
+        code.close_code()
+    }
+}
+
+impl core::fmt::Display for LibraryCloseCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A named application close code, registered in [`CloseReasons`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CloseReason<'a> {
+    code: LibraryCloseCode,
+    name: &'a str,
+}
+
+impl<'a> CloseReason<'a> {
+    /// Creates a new [`CloseReason`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `code` is not in the range `4000..=4999` or if `name` is longer than [`CloseFrame::MAX_REASON_LEN`] bytes,
+    /// so that [`CloseReason::frame`] always fits into a Close frame. At compile time when used in a const context.
+    pub const fn new(code: u16, name: &'a str) -> Self {
+        if name.len() > CloseFrame::MAX_REASON_LEN {
+            panic!("Close reason names must fit into a Close frame");
+        }
+
+        Self {
+            code: LibraryCloseCode::new(code),
+            name,
+        }
+    }
+
+    /// Returns the application close code.
+    pub const fn code(&self) -> LibraryCloseCode {
+        self.code
+    }
+
+    /// Returns the name of the reason.
+    pub const fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns a [`CloseFrame`] with this code and its name as the reason.
+    pub const fn frame(&self) -> CloseFrame<'a> {
+        CloseFrame::new(self.code.close_code(), self.name)
+    }
+}
+
+impl core::fmt::Display for CloseReason<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({})", self.name, self.code)
+    }
+}
+
+/// A table of the [`CloseReason`]s of an application protocol.
+///
+/// # Example
+///
+/// ```
+/// use websocketz::{CloseReason, CloseReasons};
+///
+/// const UNAUTHORIZED: CloseReason = CloseReason::new(4001, "unauthorized");
+/// const FIRMWARE_OUTDATED: CloseReason = CloseReason::new(4002, "firmware outdated");
+///
+/// const REASONS: CloseReasons = CloseReasons::new(&[UNAUTHORIZED, FIRMWARE_OUTDATED]);
+///
+/// let frame = FIRMWARE_OUTDATED.frame();
+///
+/// assert_eq!(REASONS.get(frame.code()), Some(FIRMWARE_OUTDATED));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CloseReasons<'a> {
+    reasons: &'a [CloseReason<'a>],
+}
+
+impl<'a> CloseReasons<'a> {
+    /// Creates a new [`CloseReasons`] table.
+    pub const fn new(reasons: &'a [CloseReason<'a>]) -> Self {
+        Self { reasons }
+    }
+
+    /// Returns the [`CloseReason`] registered for `code`.
+    pub fn get(&self, code: CloseCode) -> Option<CloseReason<'a>> {
+        let code = LibraryCloseCode::from_close_code(code)?;
+
+        self.reasons
+            .iter()
+            .find(|reason| reason.code == code)
+            .copied()
+    }
+
+    /// Returns the [`CloseReason`] registered with `name`.
+    pub fn get_by_name(&self, name: &str) -> Option<CloseReason<'a>> {
+        self.reasons
+            .iter()
+            .find(|reason| reason.name == name)
+            .copied()
+    }
+
+    /// Returns an iterator over the registered [`CloseReason`]s.
+    pub fn iter(&self) -> impl Iterator<Item = CloseReason<'a>> + 'a {
+        self.reasons.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    const UNAUTHORIZED: CloseReason = CloseReason::new(4001, "unauthorized");
+    const OUTDATED: CloseReason = CloseReason::new(4002, "firmware outdated");
+    const REASONS: CloseReasons = CloseReasons::new(&[UNAUTHORIZED, OUTDATED]);
+
+    #[test]
+    fn library_close_code() {
+        assert_eq!(LibraryCloseCode::try_new(3999), None);
+        assert_eq!(LibraryCloseCode::try_new(5000), None);
+
+        let code = LibraryCloseCode::new(4999);
+
+        assert_eq!(CloseCode::from(code), CloseCode::from_u16(4999));
+        assert_eq!(
+            LibraryCloseCode::from_close_code(CloseCode::from_u16(4999)),
+            Some(code)
+        );
+        assert_eq!(LibraryCloseCode::from_close_code(CloseCode::Normal), None);
+        assert_eq!(code.to_string(), "4999");
+    }
+
+    #[test]
+    fn round_trip() {
+        for code in 0..=u16::MAX {
+            let close_code = CloseCode::from_u16(code);
+
+            assert_eq!(close_code.into_u16(), code);
+            assert_eq!(u16::from(CloseCode::from(code)), code);
+            assert_eq!(CloseCode::from_u16(close_code.into_u16()), close_code);
+        }
+    }
+
+    #[test]
+    fn allowed() {
+        for code in 0..=u16::MAX {
+            let allowed = matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999);
+
+            assert_eq!(CloseCode::from_u16(code).is_allowed(), allowed, "{code}");
+        }
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert_eq!(CloseCode::from_u16(0), CloseCode::Bad(0));
+        assert_eq!(CloseCode::from_u16(999), CloseCode::Bad(999));
+        assert_eq!(CloseCode::from_u16(1004), CloseCode::Reserved(1004));
+        assert_eq!(CloseCode::from_u16(1014), CloseCode::BadGateway);
+        assert_eq!(CloseCode::from_u16(1016), CloseCode::Reserved(1016));
+        assert_eq!(CloseCode::from_u16(2999), CloseCode::Reserved(2999));
+        assert_eq!(CloseCode::from_u16(3000), CloseCode::Iana(3000));
+        assert_eq!(CloseCode::from_u16(4999), CloseCode::Library(4999));
+        assert_eq!(CloseCode::from_u16(5000), CloseCode::Bad(5000));
+        assert_eq!(CloseCode::from_u16(u16::MAX), CloseCode::Bad(u16::MAX));
+    }
+
+    #[test]
+    #[should_panic]
+    fn library_close_code_out_of_range() {
+        LibraryCloseCode::new(1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn close_reason_name_too_long() {
+        CloseReason::new(4000, &"a".repeat(CloseFrame::MAX_REASON_LEN + 1));
+    }
+
+    #[test]
+    fn reasons() {
+        assert_eq!(REASONS.get(CloseCode::from_u16(4001)), Some(UNAUTHORIZED));
+        assert_eq!(REASONS.get(CloseCode::from_u16(4003)), None);
+        assert_eq!(REASONS.get(CloseCode::Normal), None);
+        assert_eq!(REASONS.get_by_name("firmware outdated"), Some(OUTDATED));
+        assert!(REASONS.iter().eq([UNAUTHORIZED, OUTDATED]));
+
+        assert_eq!(OUTDATED.to_string(), "firmware outdated (4002)");
+        assert_eq!(
+            OUTDATED.frame(),
+            CloseFrame::new(CloseCode::from_u16(4002), "firmware outdated")
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::CloseCode;
 
 /// A WebSocket Close frame.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CloseFrame<'a> {
     /// The reason as a code.
     code: CloseCode,
@@ -10,11 +10,28 @@ pub struct CloseFrame<'a> {
 }
 
 impl<'a> CloseFrame<'a> {
+    /// Maximum length of the reason in bytes, so that the payload fits in a control frame.
+    pub const MAX_REASON_LEN: usize = 123;
+
     /// Creates a new [`CloseFrame`].
     pub const fn new(code: CloseCode, reason: &'a str) -> Self {
         Self { code, reason }
     }
 
+    /// Creates a new [`CloseFrame`] with the reason truncated to at most `max_len` bytes.
+    ///
+    /// `max_len` is capped at [`CloseFrame::MAX_REASON_LEN`].
+    /// The reason is truncated at a UTF-8 character boundary, so dynamic reasons, e.g. error messages, always produce valid close frames.
+    pub fn new_truncated(code: CloseCode, reason: &'a str, max_len: usize) -> Self {
+        let mut len = reason.len().min(max_len).min(Self::MAX_REASON_LEN);
+
+        while !reason.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        Self::new(code, &reason[..len])
+    }
+
     /// Creates a new [`CloseFrame`] with no reason.
     pub const fn no_reason(code: CloseCode) -> Self {
         Self::new(code, "")
@@ -30,3 +47,29 @@ impl<'a> CloseFrame<'a> {
         self.reason
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_truncated() {
+        let frame = CloseFrame::new_truncated(CloseCode::Error, "short", 16);
+        assert_eq!(frame.reason(), "short");
+
+        let frame = CloseFrame::new_truncated(CloseCode::Error, "truncated", 5);
+        assert_eq!(frame.reason(), "trunc");
+
+        // `ä` is 2 bytes long and must not be split.
+        let frame = CloseFrame::new_truncated(CloseCode::Error, "aää", 4);
+        assert_eq!(frame.reason(), "aä");
+
+        let reason = "€".repeat(64);
+        let frame = CloseFrame::new_truncated(CloseCode::Error, &reason, usize::MAX);
+        assert_eq!(frame.reason().len(), 123);
+
+        let reason = "ä".repeat(64);
+        let frame = CloseFrame::new_truncated(CloseCode::Error, &reason, usize::MAX);
+        assert_eq!(frame.reason().len(), 122);
+    }
+}
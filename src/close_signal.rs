@@ -0,0 +1,107 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use framez::state::ReadState;
+
+use crate::CloseCode;
+
+/// A close request shared by the halves of a split connection.
+///
+/// Attach the same [`CloseSignal`] to both halves with [`WebSocketRead::with_close_signal`](crate::WebSocketRead::with_close_signal)
+/// and [`WebSocketWrite::with_close_signal`](crate::WebSocketWrite::with_close_signal).
+/// A close requested by the read half, e.g. after a read error, is sent by the next operation on the write half.
+///
+/// Only atomic loads and stores are used, so the signal can be shared between tasks on targets without compare-and-swap.
+///
+/// # Example
+///
+/// ```
+/// # use websocketz::mock::Noop;
+/// # async fn run<Rng: websocketz::rng::RngCore>(websocketz: websocketz::WebSocket<'_, Noop, Rng>) {
+/// use websocketz::{CloseCode, CloseSignal, Message, error::{Error, WriteError}};
+///
+/// let signal = CloseSignal::new();
+///
+/// let (read, write) = websocketz.split_with(|_| (Noop, Noop));
+/// let (read, mut write) = (read.with_close_signal(&signal), write.with_close_signal(&signal));
+///
+/// // In the read task, e.g. after receiving an invalid message.
+/// read.request_close(CloseCode::Policy);
+///
+/// // In the write task, the Close frame is sent before the message is rejected.
+/// let result = write.send(Message::Text("Hello")).await;
+///
+/// assert!(matches!(result, Err(Error::Write(WriteError::ConnectionClosed))));
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct CloseSignal {
+    /// The requested close code, tagged with bit 16, or `0` if none was requested.
+    code: AtomicU32,
+}
+
+impl CloseSignal {
+    const REQUESTED: u32 = 1 << 16;
+
+    /// Creates a new [`CloseSignal`] without a request.
+    pub const fn new() -> Self {
+        Self {
+            code: AtomicU32::new(0),
+        }
+    }
+
+    /// Requests the connection to be closed with `code`.
+    ///
+    /// Replaces a previous request whose Close frame has not been sent yet.
+    pub fn request(&self, code: CloseCode) {
+        self.code.store(
+            Self::REQUESTED | u32::from(code.into_u16()),
+            Ordering::Release,
+        );
+    }
+
+    /// Returns the requested close code, if any.
+    pub fn requested(&self) -> Option<CloseCode> {
+        let code = self.code.load(Ordering::Acquire);
+
+        (code & Self::REQUESTED != 0).then(|| CloseCode::from_u16(code as u16))
+    }
+}
+
+/// The part of a connection core holding the [`CloseSignal`] checked before every frame it sends.
+///
+/// Only the write half of a split connection has one, in place of the read state it does not need.
+pub(crate) trait CloseSignalSlot<'buf> {
+    fn close_signal(&self) -> Option<&'buf CloseSignal>;
+}
+
+impl<'buf> CloseSignalSlot<'buf> for ReadState<'_> {
+    fn close_signal(&self) -> Option<&'buf CloseSignal> {
+        None
+    }
+}
+
+impl<'buf> CloseSignalSlot<'buf> for Option<&'buf CloseSignal> {
+    fn close_signal(&self) -> Option<&'buf CloseSignal> {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request() {
+        let signal = CloseSignal::new();
+
+        assert_eq!(signal.requested(), None);
+
+        signal.request(CloseCode::Away);
+
+        assert_eq!(signal.requested(), Some(CloseCode::Away));
+
+        signal.request(CloseCode::Library(4000));
+
+        assert_eq!(signal.requested(), Some(CloseCode::Library(4000)));
+    }
+}
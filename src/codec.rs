@@ -1,10 +1,11 @@
 use framez::{decode::Decoder, encode::Encoder};
-use rand::Rng;
-use rand_core::RngCore;
 
 use crate::{
     Frame, FrameMut, Header, Message, OpCode,
     error::{FrameDecodeError, FrameEncodeError},
+    extensions::CodecConfig,
+    options::Limits,
+    rng::RngCore,
 };
 
 #[derive(Debug)]
@@ -12,6 +13,8 @@ enum DecodeState {
     Init,
     DecodedHeader {
         fin: bool,
+        compressed: bool,
+        reserved_bits: u8,
         opcode: OpCode,
         masked: bool,
         length_code: u8,
@@ -20,6 +23,8 @@ enum DecodeState {
     },
     DecodedPayloadLength {
         fin: bool,
+        compressed: bool,
+        reserved_bits: u8,
         opcode: OpCode,
         mask: Option<[u8; 4]>,
         payload_len: usize,
@@ -31,6 +36,13 @@ enum DecodeState {
 pub struct FramesCodec<R = ()> {
     unmask: bool,
     mask: bool,
+    /// Compress sent and decompress received data messages with `permessage-deflate`.
+    #[cfg(feature = "deflate")]
+    deflate: bool,
+    /// Parameters of the extensions implemented by the application.
+    config: CodecConfig,
+    /// Limits of received frames, checked as soon as the payload length is decoded.
+    limits: Limits,
     decode_state: DecodeState,
     rng: R,
 }
@@ -40,6 +52,10 @@ impl<R> FramesCodec<R> {
         Self {
             unmask: false,
             mask: false,
+            #[cfg(feature = "deflate")]
+            deflate: false,
+            config: CodecConfig::new(),
+            limits: Limits::new(),
             decode_state: DecodeState::Init,
             rng,
         }
@@ -53,6 +69,43 @@ impl<R> FramesCodec<R> {
         self.mask = mask;
     }
 
+    #[cfg(feature = "deflate")]
+    pub const fn set_deflate(&mut self, deflate: bool) {
+        self.deflate = deflate;
+    }
+
+    #[cfg(feature = "deflate")]
+    pub const fn deflate(&self) -> bool {
+        self.deflate
+    }
+
+    pub const fn set_config(&mut self, config: CodecConfig) {
+        self.config = config;
+    }
+
+    pub const fn config(&self) -> CodecConfig {
+        self.config
+    }
+
+    pub const fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Check if RSV1 may be set on a frame with `opcode`, marking the first frame of a compressed message. (RFC 7692, section 6)
+    const fn allows_rsv1(&self, opcode: OpCode) -> bool {
+        #[cfg(feature = "deflate")]
+        {
+            self.deflate && matches!(opcode, OpCode::Text | OpCode::Binary)
+        }
+
+        #[cfg(not(feature = "deflate"))]
+        {
+            let _ = opcode;
+
+            false
+        }
+    }
+
     pub const fn rng_mut(&mut self) -> &mut R {
         &mut self.rng
     }
@@ -60,14 +113,14 @@ impl<R> FramesCodec<R> {
     /// Check if the codec is configured for a client.
     ///
     /// [`Self::mask`] and `NOT` [`Self::unmask`]
-    const fn is_client(&self) -> bool {
+    pub const fn is_client(&self) -> bool {
         self.mask && !self.unmask
     }
 
     /// Check if the codec is configured a server.
     ///
     /// [`Self::unmask`] and `NOT` [`Self::mask`]
-    const fn is_server(&self) -> bool {
+    pub const fn is_server(&self) -> bool {
         self.unmask && !self.mask
     }
 
@@ -76,12 +129,20 @@ impl<R> FramesCodec<R> {
             FramesCodec {
                 unmask: self.unmask,
                 mask: self.mask,
+                #[cfg(feature = "deflate")]
+                deflate: self.deflate,
+                config: self.config,
+                limits: self.limits,
                 decode_state: self.decode_state,
                 rng: (),
             },
             FramesCodec {
                 unmask: self.unmask,
                 mask: self.mask,
+                #[cfg(feature = "deflate")]
+                deflate: self.deflate,
+                config: self.config,
+                limits: self.limits,
                 decode_state: DecodeState::Init, // We don't care about the decode state in the second codec (writer)
                 rng: self.rng,
             },
@@ -108,7 +169,7 @@ impl<R> framez::decode::DecodeError for FramesCodec<R> {
 }
 
 impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
-    type Item = Frame<'buf>;
+    type Item = FrameMut<'buf>;
 
     fn decode(&mut self, src: &'buf mut [u8]) -> Result<Option<(Self::Item, usize)>, Self::Error> {
         const MIN_HEADER_SIZE: usize = 2;
@@ -121,15 +182,31 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
                     }
 
                     let fin = src[0] & 0b10000000 != 0;
-                    let rsv1 = src[0] & 0b01000000 != 0;
-                    let rsv2 = src[0] & 0b00100000 != 0;
-                    let rsv3 = src[0] & 0b00010000 != 0;
+                    let reserved_bits = src[0] & CodecConfig::RSV;
 
-                    if rsv1 || rsv2 || rsv3 {
+                    // RSV1 may be allowed by `permessage-deflate`, which depends on the opcode.
+                    if !self.config.allows(reserved_bits & !CodecConfig::RSV1) {
                         return Err(FrameDecodeError::ReservedBitsNotZero);
                     }
 
                     let opcode = OpCode::try_from_u8(src[0] & 0b00001111)?;
+
+                    let compressed =
+                        reserved_bits & CodecConfig::RSV1 != 0 && self.allows_rsv1(opcode);
+
+                    // The bits left to the extensions of the application.
+                    let reserved_bits = if compressed {
+                        reserved_bits & !CodecConfig::RSV1
+                    } else {
+                        reserved_bits
+                    };
+
+                    if reserved_bits != 0
+                        && (opcode.is_control() || !self.config.allows(reserved_bits))
+                    {
+                        return Err(FrameDecodeError::ReservedBitsNotZero);
+                    }
+
                     let masked = src[1] & 0b10000000 != 0;
 
                     if self.is_server() && !masked {
@@ -151,6 +228,8 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
 
                     self.decode_state = DecodeState::DecodedHeader {
                         fin,
+                        compressed,
+                        reserved_bits,
                         opcode,
                         masked,
                         length_code,
@@ -160,6 +239,8 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
                 }
                 DecodeState::DecodedHeader {
                     fin,
+                    compressed,
+                    reserved_bits,
                     opcode,
                     masked,
                     length_code,
@@ -201,10 +282,17 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
                         }
                     }
 
+                    // Rejected before the payload is buffered.
+                    if self.limits.exceeds_frame(opcode, payload_len) {
+                        return Err(FrameDecodeError::FrameTooLarge);
+                    }
+
                     let min_src_len = min_src_len + payload_len;
 
                     self.decode_state = DecodeState::DecodedPayloadLength {
                         fin,
+                        compressed,
+                        reserved_bits,
                         opcode,
                         mask,
                         payload_len,
@@ -213,6 +301,8 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
                 }
                 DecodeState::DecodedPayloadLength {
                     fin,
+                    compressed,
+                    reserved_bits,
                     opcode,
                     mask,
                     payload_len,
@@ -226,7 +316,9 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
                     let end = min_src_len;
                     let payload = &mut src[start..end];
 
-                    let mut frame = FrameMut::new(fin, opcode, mask, payload);
+                    let mut frame = FrameMut::new(fin, opcode, mask, payload)
+                        .with_compressed(compressed)
+                        .with_reserved_bits(reserved_bits);
 
                     if self.is_server() {
                         frame.unmask();
@@ -234,14 +326,87 @@ impl<'buf, R> Decoder<'buf> for FramesCodec<R> {
 
                     self.decode_state = DecodeState::Init;
 
-                    return Ok(Some((frame.into_frame(), min_src_len)));
+                    return Ok(Some((frame, min_src_len)));
                 }
             }
         }
     }
+
+    fn decode_eof(
+        &mut self,
+        src: &'buf mut [u8],
+    ) -> Result<Option<(Self::Item, usize)>, Self::Error> {
+        let received = src.len();
+
+        match self.decode(src)? {
+            Some(decoded) => Ok(Some(decoded)),
+            None if received == 0 => Ok(None),
+            None => {
+                self.decode_state = DecodeState::Init;
+
+                Err(FrameDecodeError::UnexpectedEof { received })
+            }
+        }
+    }
+}
+
+/// Decodes frames without consuming them, so that they are decoded again by the next read.
+///
+/// Payloads of masked frames are unmasked in place by a server and must be masked again with [`FrameMut::unmask`]
+/// before the next read.
+#[derive(Debug)]
+pub struct Peek<'a, R> {
+    codec: &'a mut FramesCodec<R>,
+}
+
+impl<'a, R> Peek<'a, R> {
+    pub const fn new(codec: &'a mut FramesCodec<R>) -> Self {
+        Self { codec }
+    }
+}
+
+impl<R> framez::decode::DecodeError for Peek<'_, R> {
+    type Error = FrameDecodeError;
+}
+
+impl<'buf, R> Decoder<'buf> for Peek<'_, R> {
+    type Item = FrameMut<'buf>;
+
+    fn decode(&mut self, src: &'buf mut [u8]) -> Result<Option<(Self::Item, usize)>, Self::Error> {
+        self.codec
+            .decode(src)
+            .map(|decoded| decoded.map(|(frame, _)| (frame, 0)))
+    }
+
+    fn decode_eof(
+        &mut self,
+        src: &'buf mut [u8],
+    ) -> Result<Option<(Self::Item, usize)>, Self::Error> {
+        self.codec
+            .decode_eof(src)
+            .map(|decoded| decoded.map(|(frame, _)| (frame, 0)))
+    }
 }
 
 impl<R: RngCore> FramesCodec<R> {
+    /// Returns a random mask if the codec is configured for a client, `None` otherwise.
+    ///
+    /// Always `None` without the `client` feature, as only clients mask their frames.
+    #[inline(always)]
+    pub fn mask_key(&mut self) -> Option<[u8; 4]> {
+        #[cfg(feature = "client")]
+        {
+            use rand::Rng;
+
+            self.is_client().then(|| self.rng.random())
+        }
+
+        #[cfg(not(feature = "client"))]
+        {
+            None
+        }
+    }
+
     #[inline(always)]
     fn encode_inner<F>(
         &mut self,
@@ -252,38 +417,160 @@ impl<R: RngCore> FramesCodec<R> {
         dst: &mut [u8],
     ) -> Result<usize, FrameEncodeError>
     where
-        F: FnOnce(&mut [u8]) -> Option<usize>,
+        F: FnOnce(&mut PayloadWriter<'_>) -> Option<()>,
     {
-        let header = Header::new(fin, opcode, payload_len);
+        let mask = self.mask_key();
 
-        let head_len = header
-            .write(&mut dst[..])
-            .ok_or(FrameEncodeError::BufferTooSmall)?;
+        encode_frame(fin, opcode, payload_len, mask, write_payload, dst)
+    }
+}
 
-        let mask: Option<[u8; 4]> = self.is_client().then(|| self.rng.random());
+/// Writes the payload of a frame after its header, masking it while it is copied if the frame is masked.
+#[derive(Debug)]
+pub struct PayloadWriter<'a> {
+    dst: &'a mut [u8],
+    mask: Option<[u8; 4]>,
+    /// Number of payload bytes written.
+    len: usize,
+}
 
-        let head_len = match mask {
-            None => head_len,
-            Some(mask) => {
-                if head_len + 4 > dst.len() {
-                    return Err(FrameEncodeError::BufferTooSmall);
-                }
+impl<'a> PayloadWriter<'a> {
+    const fn new(dst: &'a mut [u8], mask: Option<[u8; 4]>) -> Self {
+        Self { dst, mask, len: 0 }
+    }
+
+    /// Returns the number of bytes available for the payload.
+    pub const fn capacity(&self) -> usize {
+        self.dst.len()
+    }
+
+    /// Appends `src` to the payload.
+    ///
+    /// Returns `None` if it does not fit.
+    #[inline(always)]
+    pub fn write(&mut self, src: &[u8]) -> Option<()> {
+        let dst = self.dst.get_mut(self.len..self.len + src.len())?;
+
+        match self.mask {
+            Some(mask) => crate::mask::copy_masked(dst, src, mask, self.len),
+            None => dst.copy_from_slice(src),
+        }
+
+        self.len += src.len();
+
+        Some(())
+    }
+
+    /// Moves `len` bytes already at `start` of the payload buffer to the start of the payload.
+    ///
+    /// Returns `None` if they do not fit or the payload is not empty.
+    #[inline(always)]
+    pub fn write_within(&mut self, start: usize, len: usize) -> Option<()> {
+        if self.len != 0 || start + len > self.dst.len() {
+            return None;
+        }
 
-                dst[1] |= 0x80;
-                dst[head_len..head_len + 4].copy_from_slice(&mask);
+        match self.mask {
+            Some(mask) => crate::mask::copy_within_masked(self.dst, start, len, mask),
+            None => self.dst.copy_within(start..start + len, 0),
+        }
+
+        self.len = len;
+
+        Some(())
+    }
+}
 
-                head_len + 4
+/// Writes a frame with the given `mask` into `dst`, returning the number of bytes written.
+///
+/// The payload is masked while `write_payload` copies it into `dst`, in a single pass.
+#[inline(always)]
+pub fn encode_frame<F>(
+    fin: bool,
+    opcode: OpCode,
+    payload_len: usize,
+    mask: Option<[u8; 4]>,
+    write_payload: F,
+    dst: &mut [u8],
+) -> Result<usize, FrameEncodeError>
+where
+    F: FnOnce(&mut PayloadWriter<'_>) -> Option<()>,
+{
+    let header = Header::new(fin, opcode, payload_len);
+
+    let head_len = header
+        .write(&mut dst[..])
+        .ok_or(FrameEncodeError::BufferTooSmall)?;
+
+    let head_len = match mask {
+        None => head_len,
+        Some(mask) => {
+            if head_len + 4 > dst.len() {
+                return Err(FrameEncodeError::BufferTooSmall);
             }
-        };
 
-        let payload_len_written =
-            write_payload(&mut dst[head_len..]).ok_or(FrameEncodeError::BufferTooSmall)?;
+            dst[1] |= 0x80;
+            dst[head_len..head_len + 4].copy_from_slice(&mask);
 
-        if let Some(mask) = mask {
-            crate::mask::unmask(&mut dst[head_len..head_len + payload_len_written], mask);
+            head_len + 4
         }
+    };
+
+    let mut payload = PayloadWriter::new(&mut dst[head_len..], mask);
+
+    write_payload(&mut payload).ok_or(FrameEncodeError::BufferTooSmall)?;
+
+    Ok(head_len + payload.len)
+}
+
+impl<R: RngCore> FramesCodec<R> {
+    /// Encodes a final frame whose payload was already written at [`Staged::OFFSET`] of `dst`.
+    #[inline(always)]
+    fn encode_staged(
+        &mut self,
+        opcode: OpCode,
+        len: usize,
+        dst: &mut [u8],
+    ) -> Result<usize, FrameEncodeError> {
+        let total = dst.len();
+
+        self.encode_inner(
+            true,
+            opcode,
+            len,
+            |payload| {
+                // The header is at most `OFFSET` bytes long, so the payload moves towards the start.
+                let start = Staged::OFFSET - (total - payload.capacity());
+
+                payload.write_within(start, len)
+            },
+            dst,
+        )
+    }
 
-        Ok(head_len + payload_len_written)
+    /// Compresses a data message into `dst` with `permessage-deflate`, setting RSV1.
+    ///
+    /// Returns `None` for control messages and if the compressed payload does not fit or is not smaller,
+    /// so that the message is sent uncompressed.
+    #[cfg(feature = "deflate")]
+    fn encode_compressed(
+        &mut self,
+        item: &Message<'_>,
+        dst: &mut [u8],
+    ) -> Option<Result<usize, FrameEncodeError>> {
+        let payload = match item {
+            Message::Text(text) => text.as_bytes(),
+            Message::TextUnchecked(payload) | Message::Binary(payload) => payload,
+            _ => return None,
+        };
+
+        let len = crate::deflate::compress(payload, dst.get_mut(Staged::OFFSET..)?)
+            .filter(|len| *len < payload.len())?;
+
+        Some(
+            self.encode_staged(item.opcode(), len, dst)
+                .inspect(|_| dst[0] |= 0x40),
+        )
     }
 }
 
@@ -291,11 +578,18 @@ impl<R: RngCore> Encoder<Message<'_>> for FramesCodec<R> {
     type Error = FrameEncodeError;
 
     fn encode(&mut self, item: Message, dst: &mut [u8]) -> Result<usize, Self::Error> {
+        #[cfg(feature = "deflate")]
+        if self.deflate {
+            if let Some(encoded) = self.encode_compressed(&item, dst) {
+                return encoded;
+            }
+        }
+
         self.encode_inner(
             true,
             item.opcode(),
             item.payload_len(),
-            |buf| item.write(buf),
+            |payload| item.write(payload),
             dst,
         )
     }
@@ -309,12 +603,33 @@ impl<R: RngCore> Encoder<Frame<'_>> for FramesCodec<R> {
             item.is_final(),
             item.opcode(),
             item.payload().len(),
-            |buf| item.write_payload(buf),
+            |payload| payload.write(item.payload()),
             dst,
         )
     }
 }
 
+/// A final binary frame whose payload was already written at [`Staged::OFFSET`] of the encoding buffer.
+///
+/// Avoids a separate buffer for payloads produced directly into the write buffer, e.g. compressed payloads.
+#[derive(Debug, Clone, Copy)]
+pub struct Staged {
+    pub len: usize,
+}
+
+impl Staged {
+    /// The maximum length of a frame header: 2 bytes, an 8-byte extended payload length and a 4-byte mask.
+    pub const OFFSET: usize = 14;
+}
+
+impl<R: RngCore> Encoder<Staged> for FramesCodec<R> {
+    type Error = FrameEncodeError;
+
+    fn encode(&mut self, item: Staged, dst: &mut [u8]) -> Result<usize, Self::Error> {
+        self.encode_staged(OpCode::Binary, item.len, dst)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -333,6 +648,52 @@ mod tests {
             assert!(matches!(error, FrameDecodeError::ReservedBitsNotZero));
         }
 
+        #[test]
+        #[cfg(feature = "deflate")]
+        fn reserved_bit_compressed() {
+            let mut codec = FramesCodec::new(());
+
+            let error = codec.decode(&mut [0xc1, 0x00]).unwrap_err();
+
+            assert!(matches!(error, FrameDecodeError::ReservedBitsNotZero));
+
+            codec.set_deflate(true);
+
+            let src = &mut [0xc1, 0x00];
+            let (frame, _) = codec.decode(src).unwrap().unwrap();
+
+            assert!(frame.is_compressed());
+
+            // Control and continuation frames must not set RSV1, nor RSV2 and RSV3 any frame.
+            for src in [[0xc9, 0x00], [0xc0, 0x00], [0xe1, 0x00]] {
+                let error = codec.decode(&mut src.clone()).unwrap_err();
+
+                assert!(matches!(error, FrameDecodeError::ReservedBitsNotZero));
+            }
+        }
+
+        #[test]
+        fn reserved_bits_allowed_by_config() {
+            let mut codec = FramesCodec::new(());
+
+            codec.set_config(CodecConfig::new().with_reserved_bits(CodecConfig::RSV2));
+
+            // Text and continuation frames with RSV2.
+            for src in [[0xa1, 0x00], [0xa0, 0x00]] {
+                let (frame, _) = codec.decode(&mut src.clone()).unwrap().unwrap();
+
+                assert_eq!(frame.reserved_bits(), CodecConfig::RSV2);
+                assert!(!frame.is_compressed());
+            }
+
+            // Control frames never allow reserved bits, and RSV3 is not allowed.
+            for src in [[0xa9, 0x00], [0x91, 0x00], [0xb1, 0x00]] {
+                let error = codec.decode(&mut src.clone()).unwrap_err();
+
+                assert!(matches!(error, FrameDecodeError::ReservedBitsNotZero));
+            }
+        }
+
         #[test]
         fn unmasked_frame_from_client() {
             const UNMASKED_FRAME: &[u8] = &[
@@ -388,6 +749,29 @@ mod tests {
             //TODO
         }
 
+        #[test]
+        fn unexpected_eof() {
+            let mut codec = FramesCodec::new(());
+
+            let mut src = [0x81, 0x02, b'H'];
+
+            let error = codec.decode_eof(&mut src).unwrap_err();
+
+            assert!(matches!(
+                error,
+                FrameDecodeError::UnexpectedEof { received: 3 }
+            ));
+
+            let mut src = [0x81, 0x02, b'H', b'i'];
+
+            let (frame, len) = codec.decode_eof(&mut src).unwrap().unwrap();
+
+            assert_eq!(frame.payload(), b"Hi");
+            assert_eq!(len, 4);
+
+            assert!(codec.decode_eof(&mut []).unwrap().is_none());
+        }
+
         #[test]
         fn control_frame_fragmented() {
             const FRAGMENTED_CONTROL_FRAME: &[u8] = &[
@@ -431,6 +815,19 @@ mod tests {
 
             assert!(matches!(error, FrameDecodeError::ControlFrameTooLarge));
         }
+
+        #[test]
+        fn frame_too_large_before_payload() {
+            // Header of an unmasked 256 byte binary frame, without its payload.
+            let src = &mut [0x82, 126, 0x01, 0x00];
+
+            let mut codec = FramesCodec::new(());
+            codec.set_limits(Limits::new().with_max_frame(255));
+
+            let error = codec.decode(src).unwrap_err();
+
+            assert!(matches!(error, FrameDecodeError::FrameTooLarge));
+        }
     }
 
     mod encode {
@@ -449,5 +846,59 @@ mod tests {
 
             assert!(matches!(error, FrameEncodeError::BufferTooSmall));
         }
+
+        #[test]
+        fn masked_while_copied() {
+            let close = CloseFrame::new(CloseCode::Normal, "masked in two parts");
+            let binary = [0xAB; 300];
+
+            let mut codec = FramesCodec::new(StdRng::from_os_rng()).into_client();
+
+            for (message, head_len) in [
+                (Message::Close(Some(close)), 2),
+                (Message::Binary(&binary), 4),
+                (Message::Text(""), 2),
+            ] {
+                let mut dst = [0u8; 512];
+                let len = codec.encode(message, &mut dst).unwrap();
+
+                let mut expected = [0u8; 512];
+                let expected_len = message.payload_len();
+                FramesCodec::new(())
+                    .encode_inner(
+                        true,
+                        message.opcode(),
+                        expected_len,
+                        |payload| message.write(payload),
+                        &mut expected,
+                    )
+                    .unwrap();
+
+                let (mask, payload) = dst[head_len..len].split_at_mut(4);
+
+                crate::mask::unmask(payload, [mask[0], mask[1], mask[2], mask[3]]);
+
+                assert_eq!(dst[1] & 0x80, 0x80);
+                assert_eq!(payload, &expected[head_len..head_len + expected_len]);
+            }
+        }
+
+        #[test]
+        fn staged_masked() {
+            let mut codec = FramesCodec::new(StdRng::from_os_rng()).into_client();
+
+            let mut dst = [0u8; 64];
+            dst[Staged::OFFSET..Staged::OFFSET + 20].copy_from_slice(&[7; 20]);
+
+            let len = codec.encode(Staged { len: 20 }, &mut dst).unwrap();
+
+            assert_eq!(len, 2 + 4 + 20);
+
+            let (mask, payload) = dst[2..len].split_at_mut(4);
+
+            crate::mask::unmask(payload, [mask[0], mask[1], mask[2], mask[3]]);
+
+            assert_eq!(payload, &[7; 20]);
+        }
     }
 }
@@ -0,0 +1,439 @@
+//! Application-level compression of binary payloads.
+//!
+//! This is not the `permessage-deflate` extension (RFC 7692): payloads are compressed by the application and sent as regular binary messages,
+//! prefixed with a 1-byte header identifying the [`PayloadCodec`] used, or [`STORED`] if the payload did not shrink.
+//! Both peers must therefore agree on this framing, e.g. through a subprotocol.
+//!
+//! Send payloads with [`WebSocket::send_compressed_binary`](crate::WebSocket::send_compressed_binary)
+//! and decompress received binary payloads with [`decompress`].
+//!
+//! With the `lz4` feature, [`Lz4`] compresses into the LZ4 block format without any allocation.
+
+use crate::error::CompressionError;
+
+/// Header of a payload sent uncompressed, because compressing it did not make it smaller.
+pub const STORED: u8 = 0;
+
+/// A codec compressing and decompressing payloads.
+pub trait PayloadCodec {
+    /// Identifies the codec in the header of compressed payloads.
+    ///
+    /// Must not be [`STORED`].
+    const ID: u8;
+
+    /// Compresses `src` into `dst`, returning the number of bytes written.
+    ///
+    /// Returns `None` if the compressed payload does not fit into `dst`.
+    fn compress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize>;
+
+    /// Decompresses `src` into `dst`, returning the number of bytes written.
+    ///
+    /// Returns `None` if `src` is corrupted or the decompressed payload does not fit into `dst`.
+    fn decompress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize>;
+}
+
+/// Writes the header and the compressed `payload` into `dst`, returning the number of bytes written.
+///
+/// The payload is stored uncompressed if compressing it does not make it smaller.
+/// Returns `None` if `dst` is too small for the stored payload.
+pub fn compress<C: PayloadCodec>(codec: &C, payload: &[u8], dst: &mut [u8]) -> Option<usize> {
+    debug_assert_ne!(C::ID, STORED, "Codec ID must not be STORED");
+
+    let (header, body) = dst.split_first_mut()?;
+
+    // Bounded by the payload length, so that compression never costs more than the header.
+    let limit = body.len().min(payload.len().saturating_sub(1));
+
+    if let Some(len) = codec.compress(payload, &mut body[..limit]) {
+        *header = C::ID;
+
+        return Some(len + 1);
+    }
+
+    *header = STORED;
+
+    body.get_mut(..payload.len())?.copy_from_slice(payload);
+
+    Some(payload.len() + 1)
+}
+
+/// Decompresses a received `payload` into `dst` if needed, returning the original payload.
+///
+/// Stored payloads are returned without being copied.
+pub fn decompress<'a, C: PayloadCodec>(
+    codec: &C,
+    payload: &'a [u8],
+    dst: &'a mut [u8],
+) -> Result<&'a [u8], CompressionError> {
+    match payload.split_first() {
+        None => Err(CompressionError::MissingHeader),
+        Some((&STORED, body)) => Ok(body),
+        Some((&id, body)) if id == C::ID => {
+            let len = codec
+                .decompress(body, dst)
+                .ok_or(CompressionError::Corrupted)?;
+
+            Ok(&dst[..len])
+        }
+        Some((&id, _)) => Err(CompressionError::UnknownCodec(id)),
+    }
+}
+
+/// A compressor for the [LZ4 block format](https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md).
+///
+/// Uses a greedy match finder with a small hash table on the stack, trading compression ratio for speed and memory.
+/// Payloads are interoperable with other LZ4 block implementations, e.g. `LZ4_decompress_safe`.
+#[cfg(feature = "lz4")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lz4")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Lz4 {
+    /// Number of bits of the hash table index.
+    const HASH_LOG: u32 = 10;
+    /// Minimum length of a match.
+    const MIN_MATCH: usize = 4;
+    /// Matches must start at least this many bytes before the end of the input.
+    const MF_LIMIT: usize = 12;
+    /// The last bytes of the input are always literals.
+    const LAST_LITERALS: usize = 5;
+    /// Maximum distance between a match and its source.
+    const MAX_OFFSET: usize = u16::MAX as usize;
+
+    fn read_u32(src: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], src[pos + 3]])
+    }
+
+    fn hash(sequence: u32) -> usize {
+        (sequence.wrapping_mul(2_654_435_761) >> (u32::BITS - Self::HASH_LOG)) as usize
+    }
+
+    fn push(dst: &mut [u8], pos: &mut usize, byte: u8) -> Option<()> {
+        *dst.get_mut(*pos)? = byte;
+        *pos += 1;
+
+        Some(())
+    }
+
+    /// Writes the remainder of a length that did not fit into its token nibble.
+    fn push_len(dst: &mut [u8], pos: &mut usize, mut len: usize) -> Option<()> {
+        while len >= 255 {
+            Self::push(dst, pos, 255)?;
+            len -= 255;
+        }
+
+        Self::push(dst, pos, len as u8)
+    }
+
+    /// Reads the remainder of a length that did not fit into its token nibble.
+    fn read_len(src: &[u8], pos: &mut usize) -> Option<usize> {
+        let mut len = 0usize;
+
+        loop {
+            let byte = *src.get(*pos)?;
+            *pos += 1;
+
+            len = len.checked_add(usize::from(byte))?;
+
+            if byte != 255 {
+                return Some(len);
+            }
+        }
+    }
+
+    /// Writes a sequence of `literals` followed by an optional match of `(offset, len)`.
+    fn push_sequence(
+        dst: &mut [u8],
+        pos: &mut usize,
+        literals: &[u8],
+        matched: Option<(usize, usize)>,
+    ) -> Option<()> {
+        let match_len = matched.map_or(0, |(_, len)| len - Self::MIN_MATCH);
+
+        let token = (literals.len().min(15) << 4) as u8 | match_len.min(15) as u8;
+
+        Self::push(dst, pos, token)?;
+
+        if literals.len() >= 15 {
+            Self::push_len(dst, pos, literals.len() - 15)?;
+        }
+
+        dst.get_mut(*pos..*pos + literals.len())?
+            .copy_from_slice(literals);
+        *pos += literals.len();
+
+        if let Some((offset, _)) = matched {
+            for byte in (offset as u16).to_le_bytes() {
+                Self::push(dst, pos, byte)?;
+            }
+
+            if match_len >= 15 {
+                Self::push_len(dst, pos, match_len - 15)?;
+            }
+        }
+
+        Some(())
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl PayloadCodec for Lz4 {
+    const ID: u8 = 1;
+
+    fn compress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+        let mut pos = 0;
+        let mut anchor = 0;
+
+        if src.len() > Self::MF_LIMIT {
+            // Positions are stored plus one, zero marks an empty slot.
+            let mut table = [0u32; 1 << Self::HASH_LOG];
+
+            let match_limit = src.len() - Self::MF_LIMIT;
+            let end_limit = src.len() - Self::LAST_LITERALS;
+
+            let mut i = 0;
+
+            while i < match_limit {
+                let sequence = Self::read_u32(src, i);
+                let slot = &mut table[Self::hash(sequence)];
+                let candidate = *slot as usize;
+
+                *slot = (i + 1) as u32;
+
+                let Some(candidate) = candidate.checked_sub(1) else {
+                    i += 1;
+
+                    continue;
+                };
+
+                if i - candidate > Self::MAX_OFFSET || Self::read_u32(src, candidate) != sequence {
+                    i += 1;
+
+                    continue;
+                }
+
+                let mut len = Self::MIN_MATCH;
+
+                while i + len < end_limit && src[candidate + len] == src[i + len] {
+                    len += 1;
+                }
+
+                Self::push_sequence(dst, &mut pos, &src[anchor..i], Some((i - candidate, len)))?;
+
+                i += len;
+                anchor = i;
+            }
+        }
+
+        Self::push_sequence(dst, &mut pos, &src[anchor..], None)?;
+
+        Some(pos)
+    }
+
+    fn decompress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+        let mut i = 0;
+        let mut pos = 0usize;
+
+        loop {
+            let token = *src.get(i)?;
+            i += 1;
+
+            let mut literals = usize::from(token >> 4);
+
+            if literals == 15 {
+                literals = literals.checked_add(Self::read_len(src, &mut i)?)?;
+            }
+
+            let end = i.checked_add(literals)?;
+
+            dst.get_mut(pos..pos.checked_add(literals)?)?
+                .copy_from_slice(src.get(i..end)?);
+
+            i = end;
+            pos += literals;
+
+            // The last sequence has no match.
+            if i == src.len() {
+                return Some(pos);
+            }
+
+            let offset = usize::from(u16::from_le_bytes([*src.get(i)?, *src.get(i + 1)?]));
+            i += 2;
+
+            if offset == 0 || offset > pos {
+                return None;
+            }
+
+            let mut len = usize::from(token & 0x0F);
+
+            if len == 15 {
+                len = len.checked_add(Self::read_len(src, &mut i)?)?;
+            }
+
+            len += Self::MIN_MATCH;
+
+            if pos.checked_add(len)? > dst.len() {
+                return None;
+            }
+
+            // Byte by byte, as the match may overlap the bytes it produces.
+            for k in pos..pos + len {
+                dst[k] = dst[k - offset];
+            }
+
+            pos += len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run-length encoding of repeated bytes, as `(count, byte)` pairs.
+    struct Rle;
+
+    impl PayloadCodec for Rle {
+        const ID: u8 = 0xAA;
+
+        fn compress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+            let mut pos = 0;
+
+            for chunk in src.chunk_by(|a, b| a == b) {
+                for part in chunk.chunks(255) {
+                    dst.get_mut(pos..pos + 2)?
+                        .copy_from_slice(&[part.len() as u8, part[0]]);
+                    pos += 2;
+                }
+            }
+
+            Some(pos)
+        }
+
+        fn decompress(&self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+            let mut pos = 0;
+
+            for pair in src.chunks(2) {
+                let &[count, byte] = pair else { return None };
+                let count = usize::from(count);
+
+                dst.get_mut(pos..pos + count)?.fill(byte);
+                pos += count;
+            }
+
+            Some(pos)
+        }
+    }
+
+    #[test]
+    fn compressed_and_stored() {
+        let mut buf = [0; 64];
+        let mut dst = [0; 64];
+
+        let payload = [7; 40];
+        let len = compress(&Rle, &payload, &mut buf).expect("Buffer large enough");
+
+        assert_eq!(&buf[..len], &[Rle::ID, 40, 7]);
+        assert_eq!(
+            decompress(&Rle, &buf[..len], &mut dst).expect("Valid payload"),
+            &payload
+        );
+
+        // Would grow when compressed.
+        let payload = [1, 2, 3];
+        let len = compress(&Rle, &payload, &mut buf).expect("Buffer large enough");
+
+        assert_eq!(&buf[..len], &[STORED, 1, 2, 3]);
+        assert_eq!(
+            decompress(&Rle, &buf[..len], &mut dst).expect("Valid payload"),
+            &payload
+        );
+
+        assert_eq!(compress(&Rle, &[1, 2, 3], &mut [0; 3]), None);
+    }
+
+    #[test]
+    fn invalid_payloads() {
+        let dst = &mut [0; 4];
+
+        assert!(matches!(
+            decompress(&Rle, &[], dst),
+            Err(CompressionError::MissingHeader)
+        ));
+        assert!(matches!(
+            decompress(&Rle, &[3, 1], dst),
+            Err(CompressionError::UnknownCodec(3))
+        ));
+        assert!(matches!(
+            decompress(&Rle, &[Rle::ID, 8, 1], dst),
+            Err(CompressionError::Corrupted)
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    mod lz4 {
+        use super::*;
+
+        fn roundtrip(src: &[u8]) -> usize {
+            let compressed = &mut [0; 2048];
+            let decompressed = &mut [0; 2048];
+
+            let len = Lz4.compress(src, compressed).expect("Buffer large enough");
+            let decompressed_len = Lz4
+                .decompress(&compressed[..len], decompressed)
+                .expect("Valid payload");
+
+            assert_eq!(&decompressed[..decompressed_len], src);
+
+            len
+        }
+
+        #[test]
+        fn roundtrips() {
+            assert_eq!(roundtrip(&[]), 1);
+            assert_eq!(roundtrip(b"short"), 6);
+
+            let repeated = [b'a'; 1000];
+            assert!(roundtrip(&repeated) < 20);
+
+            let text = b"temperature=21.5;humidity=40;temperature=21.6;humidity=41;temperature=21.5;humidity=40;";
+            assert!(roundtrip(text) < text.len());
+
+            let mut state = 0x1234_5678u32;
+            let noise: [u8; 512] = core::array::from_fn(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            });
+            roundtrip(&noise);
+        }
+
+        #[test]
+        fn known_block() {
+            // One literal, a 14-byte match at offset 1 and 5 trailing literals.
+            let block = [0x1A, b'a', 0x01, 0x00, 0x50, b'a', b'a', b'a', b'a', b'a'];
+            let dst = &mut [0; 32];
+
+            let len = Lz4.decompress(&block, dst).expect("Valid block");
+
+            assert_eq!(&dst[..len], &[b'a'; 20]);
+        }
+
+        #[test]
+        fn invalid_blocks() {
+            let dst = &mut [0; 8];
+
+            // Offset beyond the decompressed bytes.
+            assert_eq!(Lz4.decompress(&[0x10, b'a', 0x02, 0x00], dst), None);
+            // Truncated literals.
+            assert_eq!(Lz4.decompress(&[0x30, b'a'], dst), None);
+            // Does not fit.
+            assert_eq!(
+                Lz4.decompress(&[0x90, 0, 0, 0, 0, 0, 0, 0, 0, 0], dst),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Recipes for common tasks.
+//!
+//! Every recipe is compiled as a doctest. Timers are not part of this library,
+//! so the recipes use a `sleep` function standing in for the timer of your runtime, e.g. `embassy_time::Timer::after`.
+//!
+//! # Echo server
+//!
+//! Accept a connection and send every data message back.
+//! `Ping` and `Close` messages are answered automatically while reading.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{Message, WebSocket, error::Error, next, options::AcceptOptions, rng::RngCore, send};
+//!
+//! async fn echo<RW: Read + Write, Rng: RngCore>(stream: RW, rng: Rng) -> Result<(), Error<RW::Error>> {
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 1024];
+//!
+//!     let mut websocketz = WebSocket::accept::<16>(
+//!         AcceptOptions::default(),
+//!         stream,
+//!         rng,
+//!         read_buffer,
+//!         write_buffer,
+//!         fragments_buffer,
+//!     )
+//!     .await?;
+//!
+//!     while let Some(message) = next!(websocketz) {
+//!         match message? {
+//!             Message::Text(text) => send!(websocketz, Message::Text(text))?,
+//!             Message::Binary(payload) => send!(websocketz, Message::Binary(payload))?,
+//!             _ => {}
+//!         }
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Subprotocols
+//!
+//! Offer subprotocols with [`ConnectOptions::with_protocols`](crate::options::ConnectOptions::with_protocols)
+//! and support them with [`AcceptOptions::with_protocols`](crate::options::AcceptOptions::with_protocols).
+//! The `Sec-WebSocket-Protocol` headers are sent and validated by the handshake, no callback is needed.
+//! [`WebSocket::connect_with`](crate::WebSocket::connect_with) and [`WebSocket::accept_with`](crate::WebSocket::accept_with)
+//! return the negotiated subprotocol in a [`HandshakeResult`](crate::HandshakeResult).
+//! Afterwards, it is available as an index into the options' list from [`WebSocket::protocol`](crate::WebSocket::protocol).
+//!
+//! ```
+//! use core::convert::Infallible;
+//!
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{WebSocket, error::Error, http::Request, options::AcceptOptions, rng::RngCore};
+//!
+//! const PROTOCOLS: &[&str] = &["mqtt", "graphql-ws"];
+//!
+//! async fn accept<RW: Read + Write, Rng: RngCore>(stream: RW, rng: Rng) -> Result<(), Error<RW::Error>> {
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 1024];
+//!
+//!     let (websocketz, (), result) = WebSocket::accept_with(
+//!         AcceptOptions::default().with_protocols(PROTOCOLS),
+//!         stream,
+//!         rng,
+//!         read_buffer,
+//!         write_buffer,
+//!         fragments_buffer,
+//!         |_: &Request<'_, 16>| Ok::<_, Infallible>(()),
+//!     )
+//!     .await?;
+//!
+//!     match result.protocol() {
+//!         Some("mqtt") => { /* Serve MQTT over WebSocket. */ }
+//!         Some(_) => { /* Serve GraphQL subscriptions. */ }
+//!         // The client offered none of the supported subprotocols.
+//!         None => {}
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Ping keepalive
+//!
+//! Race reading against a timer and send a `Ping` whenever the connection has been idle for too long.
+//! [`WebSocket::with_max_unanswered_pings`](crate::WebSocket::with_max_unanswered_pings) turns a silent peer into [`Error::PeerUnresponsive`](crate::error::Error::PeerUnresponsive).
+//!
+//! ```
+//! # async fn sleep(_: core::time::Duration) {}
+//! use core::{pin::pin, time::Duration};
+//!
+//! use embedded_io_async::{Read, Write};
+//! use futures::future::{Either, select};
+//! use websocketz::{Message, WebSocket, error::Error, next, rng::RngCore};
+//!
+//! async fn keepalive<RW: Read + Write, Rng: RngCore>(
+//!     websocketz: WebSocket<'_, RW, Rng>,
+//! ) -> Result<(), Error<RW::Error>> {
+//!     let mut websocketz = websocketz.with_max_unanswered_pings(3);
+//!
+//!     loop {
+//!         let idle = {
+//!             let read = pin!(async { next!(websocketz).map(|message| message.map(|m| m.is_close())) });
+//!             let timeout = pin!(sleep(Duration::from_secs(30)));
+//!
+//!             match select(read, timeout).await {
+//!                 Either::Left((None, _)) => return Ok(()),
+//!                 Either::Left((Some(Err(err)), _)) => return Err(err.into()),
+//!                 Either::Left((Some(Ok(true)), _)) => return Ok(()),
+//!                 Either::Left((Some(Ok(false)), _)) => false,
+//!                 Either::Right(_) => true,
+//!             }
+//!         };
+//!
+//!         if idle {
+//!             websocketz.send(Message::Ping(b"keepalive")).await?;
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Applications that only ever read can instead queue the ping with [`WebSocket::queue_ping`](crate::WebSocket::queue_ping),
+//! it is sent by the next call to [`next!`](crate::next).
+//!
+//! [`WebSocket::with_keepalive`](crate::WebSocket::with_keepalive) keeps track of the idle time and the pong deadline instead,
+//! calling [`WebSocket::tick`](crate::WebSocket::tick) with a [`Clock`](crate::clock::Clock) after every read or timeout sends the pings
+//! and turns a missing pong into [`Error::PingTimeout`](crate::error::Error::PingTimeout).
+//!
+//! # Split with embassy
+//!
+//! With `embassy-net`, `TcpSocket::split` returns a reader and a writer borrowing the socket.
+//! Hand a `&mut TcpSocket` to the [`WebSocket`](crate::WebSocket) and split it, then drive both halves concurrently,
+//! e.g. with `embassy_futures::join::join`.
+//!
+//! The halves do not answer `Ping` and `Close` messages automatically, since the read half cannot write,
+//! unless the write half is attached while reading with [`WebSocketRead::attach_writer`](crate::WebSocketRead::attach_writer).
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # use websocketz::mock::Noop;
+//! # struct TcpSocket;
+//! # impl embedded_io_async::ErrorType for TcpSocket { type Error = core::convert::Infallible; }
+//! # impl embedded_io_async::Read for TcpSocket { async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> { Ok(buf.len()) } }
+//! # impl embedded_io_async::Write for TcpSocket { async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> { Ok(buf.len()) } }
+//! # impl TcpSocket { fn split(&mut self) -> (Noop, Noop) { (Noop, Noop) } }
+//! # use futures::future::join;
+//! use websocketz::{Message, WebSocket, next, options::ConnectOptions, rng::RngCore};
+//!
+//! async fn run<Rng: RngCore>(socket: &mut TcpSocket, rng: Rng) {
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 1024];
+//!
+//!     let websocketz = WebSocket::connect::<16>(
+//!         ConnectOptions::default(),
+//!         socket,
+//!         rng,
+//!         read_buffer,
+//!         write_buffer,
+//!         fragments_buffer,
+//!     )
+//!     .await
+//!     .expect("Handshake failed");
+//!
+//!     let (mut read, mut write) = websocketz.split_with(|socket| socket.split());
+//!
+//!     let reader = async {
+//!         while let Some(Ok(message)) = next!(read) {
+//!             let _ = message;
+//!         }
+//!     };
+//!
+//!     let writer = async {
+//!         let _ = write.send(Message::Text("Hello from embassy")).await;
+//!     };
+//!
+//!     join(reader, writer).await;
+//! }
+//! ```
+//!
+//! # Reconnect loop
+//!
+//! Reconnect after the peer closed the connection, waiting as long as the [`ReconnectAdvice`](crate::ReconnectAdvice)
+//! derived from its close code suggests.
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # async fn sleep(_: core::time::Duration) {}
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{
+//!     CloseCode, Message, ReconnectAdvice, WebSocket, next, options::ConnectOptions, rng::RngCore,
+//! };
+//!
+//! async fn reconnect<RW, Rng>(mut connect: impl AsyncFnMut() -> RW, mut rng: Rng)
+//! where
+//!     RW: Read + Write,
+//!     Rng: RngCore + Clone,
+//! {
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 1024];
+//!
+//!     loop {
+//!         let mut websocketz = match WebSocket::connect::<16>(
+//!             ConnectOptions::default(),
+//!             connect().await,
+//!             rng.clone(),
+//!             read_buffer,
+//!             write_buffer,
+//!             fragments_buffer,
+//!         )
+//!         .await
+//!         {
+//!             Ok(websocketz) => websocketz,
+//!             Err(_) => {
+//!                 sleep(core::time::Duration::from_secs(1)).await;
+//!                 continue;
+//!             }
+//!         };
+//!
+//!         // Closing without a close frame is treated as an abnormal closure.
+//!         let mut code = CloseCode::Abnormal;
+//!
+//!         while let Some(Ok(message)) = next!(websocketz) {
+//!             if let Message::Close(Some(frame)) = message {
+//!                 code = frame.code();
+//!             }
+//!         }
+//!
+//!         match ReconnectAdvice::from_close(code).delay(&mut rng) {
+//!             Some(delay) => sleep(delay).await,
+//!             None => return,
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # Handshake authentication
+//!
+//! Check credentials in the handshake callback. Requests rejected by the callback fail the handshake with [`HandshakeError::Other`](crate::error::HandshakeError::Other),
+//! which [`WebSocket::try_accept`](crate::WebSocket::try_accept) hands back together with the connection, so the client can be answered with `401 Unauthorized`.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{
+//!     WebSocket,
+//!     error::{Error, HandshakeError},
+//!     options::AcceptOptions,
+//!     rng::RngCore,
+//! };
+//!
+//! struct Unauthorized;
+//!
+//! async fn authenticate<'buf, RW: Read + Write, Rng: RngCore>(
+//!     websocketz: WebSocket<'buf, RW, Rng>,
+//!     token: &[u8],
+//! ) -> Option<WebSocket<'buf, RW, Rng>> {
+//!     let (mut websocketz, result) = websocketz
+//!         .try_accept::<16, _, _, _>(AcceptOptions::default(), |request| {
+//!             request
+//!                 .headers()
+//!                 .iter()
+//!                 .find(|header| header.name.eq_ignore_ascii_case("authorization"))
+//!                 .filter(|header| header.value == token)
+//!                 .map(|_| ())
+//!                 .ok_or(Unauthorized)
+//!         })
+//!         .await;
+//!
+//!     match result {
+//!         Ok(()) => Some(websocketz),
+//!         Err(Error::Handshake(HandshakeError::Other(Unauthorized))) => {
+//!             let _ = websocketz.reject("401", "Unauthorized", &[]).await;
+//!
+//!             None
+//!         }
+//!         Err(_) => None,
+//!     }
+//! }
+//! ```
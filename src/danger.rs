@@ -0,0 +1,199 @@
+//! Frames violating the WebSocket protocol.
+//!
+//! A [`RawFrame`] can carry arbitrary reserved (RSV) bits and reserved opcodes,
+//! which is useful for testing how peers handle invalid frames or for developing new extensions.
+//! Peers are expected to fail the connection when receiving such frames unless an extension defining them was negotiated.
+//!
+//! Only available with the `danger-frames` feature, the rest of the API can not emit these frames.
+
+use framez::encode::Encoder;
+
+use crate::{
+    OpCode,
+    codec::{FramesCodec, encode_frame},
+    error::FrameEncodeError,
+    rng::RngCore,
+};
+
+/// A frame with arbitrary header bits.
+///
+/// Sent as-is with [`WebSocket::send_raw`](crate::WebSocket::send_raw) or [`WebSocketWrite::send_raw`](crate::WebSocketWrite::send_raw),
+/// masked if sent by a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawFrame<'a> {
+    fin: bool,
+    rsv: u8,
+    opcode: u8,
+    payload: &'a [u8],
+    validate_text: bool,
+}
+
+impl<'a> RawFrame<'a> {
+    /// Creates a new final [`RawFrame`] without reserved bits.
+    ///
+    /// Only the lower 4 bits of `opcode` are used.
+    pub const fn new(opcode: u8, payload: &'a [u8]) -> Self {
+        Self {
+            fin: true,
+            rsv: 0,
+            opcode: opcode & 0x0F,
+            payload,
+            validate_text: false,
+        }
+    }
+
+    /// Sets the FIN bit.
+    pub const fn with_fin(mut self, fin: bool) -> Self {
+        self.fin = fin;
+        self
+    }
+
+    /// Sets the RSV1, RSV2 and RSV3 bits.
+    ///
+    /// Only the lower 3 bits of `rsv` are used, RSV1 being the most significant one.
+    pub const fn with_rsv(mut self, rsv: u8) -> Self {
+        self.rsv = rsv & 0b111;
+        self
+    }
+
+    /// Sets whether the payload of a final text frame is validated as UTF-8 before it is sent.
+    ///
+    /// Disabled by default, since raw frames may deliberately carry invalid payloads.
+    /// If enabled, sending a final frame with the text opcode and an invalid payload fails with
+    /// [`FrameEncodeError::InvalidUTF8`]. Fragments are not validated, as code points may span several of them.
+    pub const fn with_text_validation(mut self, validate_text: bool) -> Self {
+        self.validate_text = validate_text;
+        self
+    }
+
+    /// Returns the FIN bit.
+    pub const fn fin(&self) -> bool {
+        self.fin
+    }
+
+    /// Returns the RSV1, RSV2 and RSV3 bits.
+    pub const fn rsv(&self) -> u8 {
+        self.rsv
+    }
+
+    /// Returns the opcode.
+    pub const fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    /// Returns the payload.
+    pub const fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    const fn first_byte(&self) -> u8 {
+        ((self.fin as u8) << 7) | (self.rsv << 4) | self.opcode
+    }
+}
+
+impl<R: RngCore> Encoder<RawFrame<'_>> for FramesCodec<R> {
+    type Error = FrameEncodeError;
+
+    fn encode(&mut self, item: RawFrame<'_>, dst: &mut [u8]) -> Result<usize, Self::Error> {
+        let mask = self.mask_key();
+
+        let payload = item.payload();
+
+        if item.validate_text
+            && item.fin()
+            && item.opcode() == OpCode::Text as u8
+            && core::str::from_utf8(payload).is_err()
+        {
+            return Err(FrameEncodeError::InvalidUTF8);
+        }
+
+        // The opcode is replaced below, it only must not be a control opcode to allow any payload length.
+        let len = encode_frame(
+            item.fin(),
+            OpCode::Binary,
+            payload.len(),
+            mask,
+            |writer| writer.write(payload),
+            dst,
+        )?;
+
+        dst[0] = item.first_byte();
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn header_bits() {
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+        let mut buf = [0; 16];
+
+        let frame = RawFrame::new(0x3, b"rsv").with_rsv(0b100).with_fin(false);
+        let len = codec.encode(frame, &mut buf).unwrap();
+
+        assert_eq!(&buf[..len], &[0x43, 0x03, b'r', b's', b'v']);
+    }
+
+    #[test]
+    fn truncates_bits() {
+        let frame = RawFrame::new(0xFF, &[]).with_rsv(0xFF);
+
+        assert_eq!(frame.opcode(), 0x0F);
+        assert_eq!(frame.rsv(), 0b111);
+        assert_eq!(frame.first_byte(), 0xFF);
+    }
+
+    #[test]
+    fn text_validation() {
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+        let mut buf = [0; 16];
+
+        let invalid = RawFrame::new(0x1, &[0xFF, 0xFE]);
+
+        assert!(codec.encode(invalid, &mut buf).is_ok());
+        assert!(matches!(
+            codec.encode(invalid.with_text_validation(true), &mut buf),
+            Err(FrameEncodeError::InvalidUTF8)
+        ));
+
+        // Fragments and other opcodes are not validated.
+        assert!(
+            codec
+                .encode(invalid.with_text_validation(true).with_fin(false), &mut buf)
+                .is_ok()
+        );
+        assert!(
+            codec
+                .encode(
+                    RawFrame::new(0x2, &[0xFF]).with_text_validation(true),
+                    &mut buf
+                )
+                .is_ok()
+        );
+
+        let valid = RawFrame::new(0x1, "é".as_bytes()).with_text_validation(true);
+
+        assert!(codec.encode(valid, &mut buf).is_ok());
+    }
+
+    #[test]
+    fn masked() {
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+        codec.set_mask(true);
+
+        let mut buf = [0; 16];
+
+        let frame = RawFrame::new(0xB, b"ping").with_rsv(0b001);
+        let len = codec.encode(frame, &mut buf).unwrap();
+
+        assert_eq!(len, 2 + 4 + 4);
+        assert_eq!(buf[0], 0x9B);
+        assert_eq!(buf[1], 0x84);
+    }
+}
@@ -0,0 +1,453 @@
+//! The `permessage-deflate` extension (RFC 7692).
+//!
+//! Enable it with [`ConnectOptions::with_permessage_deflate`](crate::options::ConnectOptions::with_permessage_deflate)
+//! or [`AcceptOptions::with_permessage_deflate`](crate::options::AcceptOptions::with_permessage_deflate).
+//! Once negotiated, see [`WebSocket::permessage_deflate`](crate::WebSocket::permessage_deflate),
+//! text and binary messages are compressed when sent, unless compressing them does not make them smaller,
+//! and compressed messages are decompressed when received.
+//!
+//! Compression uses no context takeover in both directions, so that no sliding window has to be kept between messages:
+//! - Compressed payloads are written directly into the write buffer.
+//! - Received payloads are decompressed into the buffer set with [`WebSocket::with_inflater`](crate::WebSocket::with_inflater),
+//!   along with the state of the decompressor, an [`Inflater`]. It must hold the largest decompressed message.
+//!
+//! Messages read with `with_next_into` or peeked with [`WebSocket::peek_message`](crate::WebSocket::peek_message) are not decompressed,
+//! and fail with [`MessageError::DecompressionFailed`](crate::error::MessageError::DecompressionFailed) if compressed.
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # async fn deflate() {
+//! # use websocketz::mock::Noop;
+//! use websocketz::{WebSocket, deflate::Inflater, options::ConnectOptions};
+//!
+//! let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+//!
+//! // The decompressor state is about 11KiB, keep it out of the stack, e.g. in a `static_cell`.
+//! let inflater = &mut Inflater::new();
+//! let inflate_buffer = &mut [0u8; 4096];
+//!
+//! let websocketz = WebSocket::connect::<16>(
+//!     ConnectOptions::default().with_permessage_deflate(true),
+//!     Noop,
+//!     Noop,
+//!     read_buffer,
+//!     write_buffer,
+//!     fragments_buffer,
+//! )
+//! .await
+//! .expect("Handshake failed")
+//! .with_inflater(inflater, inflate_buffer);
+//!
+//! if websocketz.permessage_deflate() {
+//!     // Messages are compressed from now on.
+//! }
+//! # }
+//! ```
+//!
+//! Messages are compressed with fixed Huffman codes and a greedy match finder with a small hash table on the stack,
+//! trading compression ratio for speed and memory. Any DEFLATE stream is decompressed.
+
+use miniz_oxide::inflate::{
+    TINFLStatus,
+    core::{
+        DecompressorOxide,
+        inflate_flags::{TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF},
+    },
+};
+
+use crate::{extensions::Extension, http::Header};
+
+/// The name of the extension.
+pub(crate) const NAME: &str = "permessage-deflate";
+
+/// The offer of a client, asking for no context takeover in both directions.
+pub(crate) const OFFER: Header<'static> = Header {
+    name: "sec-websocket-extensions",
+    value: b"permessage-deflate; client_no_context_takeover; server_no_context_takeover",
+};
+
+/// The response of a server, requiring no context takeover in both directions.
+pub(crate) const RESPONSE: Header<'static> = OFFER;
+
+/// The state of a DEFLATE decompressor.
+///
+/// See the [module](crate::deflate) documentation.
+#[derive(Clone)]
+pub struct Inflater {
+    decompressor: DecompressorOxide,
+}
+
+impl core::fmt::Debug for Inflater {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Inflater").finish_non_exhaustive()
+    }
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inflater {
+    /// Creates a new [`Inflater`].
+    pub fn new() -> Self {
+        Self {
+            decompressor: DecompressorOxide::new(),
+        }
+    }
+
+    /// Decompresses the payload of a compressed message into `dst`, returning the number of bytes written.
+    ///
+    /// Returns `None` if `src` is corrupted or the decompressed payload does not fit into `dst`.
+    pub(crate) fn decompress(&mut self, src: &[u8], dst: &mut [u8]) -> Option<usize> {
+        // Removed by the sender, see RFC 7692, Section 7.2.2.
+        const TAIL: &[u8] = &[0x00, 0x00, 0xff, 0xff];
+
+        let flags = TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF | TINFL_FLAG_HAS_MORE_INPUT;
+
+        self.decompressor.init();
+
+        let mut written = 0;
+
+        for input in [src, TAIL] {
+            let (status, read, out) = miniz_oxide::inflate::core::decompress(
+                &mut self.decompressor,
+                input,
+                dst,
+                written,
+                flags,
+            );
+
+            written += out;
+
+            match status {
+                // The message ends with a final block.
+                TINFLStatus::Done => return Some(written),
+                TINFLStatus::NeedsMoreInput if read == input.len() => {}
+                _ => return None,
+            }
+        }
+
+        Some(written)
+    }
+}
+
+/// Returns `true` if `offer` is a `permessage-deflate` offer the server can accept.
+///
+/// Offers limiting the window of the server are declined, as they require an answer with the same parameter.
+pub(crate) fn is_acceptable_offer(offer: &Extension<'_>) -> bool {
+    offer.name().eq_ignore_ascii_case(NAME)
+        && offer.params().all(|(name, _)| {
+            [
+                "client_no_context_takeover",
+                "server_no_context_takeover",
+                "client_max_window_bits",
+            ]
+            .iter()
+            .any(|known| name.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Returns `true` if `response` is a valid answer to [`OFFER`].
+///
+/// The server must not use context takeover, as the decompressor keeps no window between messages.
+#[cfg(feature = "client")]
+pub(crate) fn is_valid_response(response: &Extension<'_>) -> bool {
+    let mut server_no_context_takeover = false;
+
+    for (name, value) in response.params() {
+        if name.eq_ignore_ascii_case("server_no_context_takeover") && value.is_none() {
+            server_no_context_takeover = true;
+        } else if name.eq_ignore_ascii_case("server_max_window_bits") {
+            if !value
+                .and_then(|bits| bits.parse::<u8>().ok())
+                .is_some_and(|bits| (8..=15).contains(&bits))
+            {
+                return false;
+            }
+        } else if !(name.eq_ignore_ascii_case("client_no_context_takeover") && value.is_none()) {
+            return false;
+        }
+    }
+
+    server_no_context_takeover
+}
+
+/// Number of bits of the hash table index.
+const HASH_LOG: u32 = 10;
+/// Minimum length of a match.
+const MIN_MATCH: usize = 3;
+/// Maximum length of a match.
+const MAX_MATCH: usize = 258;
+/// Maximum distance between a match and its source, the default window of 15 bits.
+const MAX_DISTANCE: usize = 1 << 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes bits least significant bit first, as DEFLATE streams are packed.
+struct BitWriter<'a> {
+    dst: &'a mut [u8],
+    pos: usize,
+    bits: u64,
+    len: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    const fn new(dst: &'a mut [u8]) -> Self {
+        Self {
+            dst,
+            pos: 0,
+            bits: 0,
+            len: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, len: u32) -> Option<()> {
+        self.bits |= u64::from(value) << self.len;
+        self.len += len;
+
+        while self.len >= 8 {
+            *self.dst.get_mut(self.pos)? = self.bits as u8;
+            self.pos += 1;
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+
+        Some(())
+    }
+
+    /// Writes a Huffman code, which is packed most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) -> Option<()> {
+        self.write(code.reverse_bits() >> (u32::BITS - len), len)
+    }
+
+    /// Writes a literal/length symbol with the fixed Huffman code.
+    fn write_symbol(&mut self, symbol: u16) -> Option<()> {
+        let symbol = u32::from(symbol);
+
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, len: usize, distance: usize) -> Option<()> {
+        let index = LENGTH_BASE.partition_point(|&base| usize::from(base) <= len) - 1;
+
+        self.write_symbol(257 + index as u16)?;
+        self.write(
+            (len - usize::from(LENGTH_BASE[index])) as u32,
+            u32::from(LENGTH_EXTRA[index]),
+        )?;
+
+        let index = DISTANCE_BASE.partition_point(|&base| usize::from(base) <= distance) - 1;
+
+        self.write_code(index as u32, 5)?;
+        self.write(
+            (distance - usize::from(DISTANCE_BASE[index])) as u32,
+            u32::from(DISTANCE_EXTRA[index]),
+        )
+    }
+
+    /// Writes the remaining bits, returning the number of bytes written.
+    fn finish(mut self) -> Option<usize> {
+        if self.len > 0 {
+            self.write(0, 8 - self.len)?;
+        }
+
+        Some(self.pos)
+    }
+}
+
+fn hash(src: &[u8], pos: usize) -> usize {
+    let sequence = u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], 0]);
+
+    (sequence.wrapping_mul(2_654_435_761) >> (u32::BITS - HASH_LOG)) as usize
+}
+
+/// Compresses `src` into a single final DEFLATE block with fixed Huffman codes, returning the number of bytes written.
+///
+/// Returns `None` if the compressed payload does not fit into `dst`.
+pub(crate) fn compress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = BitWriter::new(dst);
+
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman codes).
+    out.write(0b011, 3)?;
+
+    // Positions are stored plus one, zero marks an empty slot.
+    let mut table = [0u32; 1 << HASH_LOG];
+
+    let mut i = 0;
+
+    while i < src.len() {
+        if i + MIN_MATCH <= src.len() {
+            let slot = &mut table[hash(src, i)];
+            let candidate = *slot as usize;
+
+            *slot = (i + 1) as u32;
+
+            if let Some(candidate) = candidate.checked_sub(1) {
+                if i - candidate <= MAX_DISTANCE
+                    && src[candidate..candidate + MIN_MATCH] == src[i..i + MIN_MATCH]
+                {
+                    let max = (src.len() - i).min(MAX_MATCH);
+                    let mut len = MIN_MATCH;
+
+                    while len < max && src[candidate + len] == src[i + len] {
+                        len += 1;
+                    }
+
+                    out.write_match(len, i - candidate)?;
+
+                    i += len;
+
+                    continue;
+                }
+            }
+        }
+
+        out.write_symbol(u16::from(src[i]))?;
+
+        i += 1;
+    }
+
+    // End of block.
+    out.write_symbol(256)?;
+
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(src: &[u8]) -> usize {
+        let compressed = &mut [0u8; 4096];
+        let len = compress(src, compressed).expect("Buffer is large enough");
+
+        let decompressed = &mut [0u8; 4096];
+        let decompressed_len = Inflater::new()
+            .decompress(&compressed[..len], decompressed)
+            .expect("Valid payload");
+
+        assert_eq!(&decompressed[..decompressed_len], src);
+
+        len
+    }
+
+    #[test]
+    fn compress_roundtrip() {
+        roundtrip(b"");
+        roundtrip(b"a");
+        roundtrip(b"Hello");
+        roundtrip(&[0xFF; 1000]);
+        roundtrip(&core::array::from_fn::<u8, 1000, _>(|i| {
+            (i * 7 % 251) as u8
+        }));
+
+        let repeated = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20);
+
+        assert!(roundtrip(&repeated) < repeated.len() / 4);
+    }
+
+    #[test]
+    fn compress_buffer_too_small() {
+        assert_eq!(compress(b"Hello", &mut [0u8; 4]), None);
+    }
+
+    #[test]
+    fn decompress_rfc_examples() {
+        let dst = &mut [0u8; 16];
+        let mut inflater = Inflater::new();
+
+        // "Hello" compressed with a sync flush, RFC 7692, Section 7.2.3.1.
+        let len = inflater
+            .decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], dst)
+            .expect("Valid payload");
+
+        assert_eq!(&dst[..len], b"Hello");
+
+        // "Hello" in a stored block, RFC 7692, Section 7.2.3.3.
+        let len = inflater
+            .decompress(
+                &[
+                    0x00, 0x05, 0x00, 0xfa, 0xff, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x00,
+                ],
+                dst,
+            )
+            .expect("Valid payload");
+
+        assert_eq!(&dst[..len], b"Hello");
+
+        // "Hello" in a final block, RFC 7692, Section 7.2.3.4.
+        let len = inflater
+            .decompress(&[0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x00], dst)
+            .expect("Valid payload");
+
+        assert_eq!(&dst[..len], b"Hello");
+    }
+
+    #[test]
+    fn decompress_invalid() {
+        let mut inflater = Inflater::new();
+
+        assert_eq!(
+            inflater.decompress(&[0xff, 0xff, 0xff], &mut [0u8; 16]),
+            None
+        );
+        assert_eq!(
+            inflater.decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], &mut [0u8; 4]),
+            None
+        );
+    }
+
+    #[test]
+    fn negotiation_offer() {
+        let offer = |offer| is_acceptable_offer(&Extension::parse(offer).expect("Valid offer"));
+
+        assert!(offer("permessage-deflate"));
+        assert!(offer("permessage-deflate; client_max_window_bits"));
+        assert!(offer(
+            "Permessage-Deflate; client_no_context_takeover; server_no_context_takeover"
+        ));
+        assert!(!offer("permessage-deflate; server_max_window_bits=10"));
+        assert!(!offer("permessage-deflate; unknown"));
+        assert!(!offer("x-webkit-deflate-frame"));
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn negotiation_response() {
+        let response =
+            |response| is_valid_response(&Extension::parse(response).expect("Valid response"));
+
+        assert!(response("permessage-deflate; server_no_context_takeover"));
+        assert!(response(
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; server_max_window_bits=10"
+        ));
+        assert!(!response("permessage-deflate"));
+        assert!(!response(
+            "permessage-deflate; server_no_context_takeover; client_max_window_bits=10"
+        ));
+        assert!(!response(
+            "permessage-deflate; server_no_context_takeover; server_max_window_bits=16"
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! Resumable downloads of large binary streams, e.g. firmware images for over-the-air updates.
+//!
+//! A [`Download`] sends a request carrying the offset to start from, then receives the stream as consecutive binary messages (chunks),
+//! each of which must fit into the read buffer, or into the fragments buffer if fragmented.
+//! Every chunk is passed to a callback along with its absolute offset, e.g. to write it to flash.
+//!
+//! The total length of the stream must be known beforehand, e.g. from an update manifest.
+//! Chunks exceeding it are rejected. If the connection is lost, reconnect and run the same [`Download`] again:
+//! the request is sent again with the offset of the first missing byte.
+//!
+//! The format of the request is up to the application, only the chunks are defined by this module.
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # use websocketz::{WebSocket, mock::Noop};
+//! # async fn sleep(_: core::time::Duration) {}
+//! # async fn write_flash(_: u64, _: &[u8]) -> Result<(), ()> { Ok(()) }
+//! # async fn connect<'buf>(
+//! #     read_buffer: &'buf mut [u8],
+//! #     write_buffer: &'buf mut [u8],
+//! #     fragments_buffer: &'buf mut [u8],
+//! # ) -> WebSocket<'buf, Noop, Noop> {
+//! #     // A new connection, e.g. with `WebSocket::connect`.
+//! #     WebSocket::client(Noop, Noop, read_buffer, write_buffer, fragments_buffer)
+//! # }
+//! use websocketz::{download::Download, error::DownloadError};
+//!
+//! async fn update(image_len: u64) {
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 4096];
+//!
+//!     let mut download = Download::new(image_len);
+//!
+//!     while !download.is_complete() {
+//!         let mut websocketz = connect(read_buffer, write_buffer, fragments_buffer).await;
+//!
+//!         let result = download
+//!             .run(
+//!                 &mut websocketz,
+//!                 // Request the image from `offset` as 8 big-endian bytes.
+//!                 |offset, dst| {
+//!                     dst.get_mut(..8)?.copy_from_slice(&offset.to_be_bytes());
+//!
+//!                     Some(8)
+//!                 },
+//!                 async |offset, chunk| write_flash(offset, chunk).await,
+//!             )
+//!             .await;
+//!
+//!         match result {
+//!             Ok(()) => break,
+//!             // Resumed from `download.offset()` after reconnecting.
+//!             Err(DownloadError::Interrupted { .. } | DownloadError::WebSocket(_)) => {
+//!                 sleep(core::time::Duration::from_secs(1)).await
+//!             }
+//!             Err(_) => return,
+//!         }
+//!     }
+//! }
+//! ```
+
+use embedded_io_async::{Read, Write};
+
+use crate::{
+    Message, WebSocket,
+    error::{DownloadError, Error},
+    rng::RngCore,
+};
+
+/// The progress of a resumable download.
+///
+/// See the [module](crate::download) documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Download {
+    offset: u64,
+    total: u64,
+}
+
+impl Download {
+    /// Creates a new [`Download`] of `total` bytes, starting at offset `0`.
+    pub const fn new(total: u64) -> Self {
+        Self { offset: 0, total }
+    }
+
+    /// Creates a new [`Download`] of `total` bytes, resuming at `offset`, e.g. restored from persistent storage after a reboot.
+    ///
+    /// `offset` is clamped to `total`.
+    pub const fn resume(offset: u64, total: u64) -> Self {
+        let offset = if offset > total { total } else { offset };
+
+        Self { offset, total }
+    }
+
+    /// Returns the offset of the next expected byte, i.e. the number of bytes received so far.
+    #[inline]
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the total length of the stream.
+    #[inline]
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the number of bytes left to receive.
+    #[inline]
+    pub const fn remaining(&self) -> u64 {
+        self.total - self.offset
+    }
+
+    /// Returns `true` if all bytes have been received.
+    #[inline]
+    pub const fn is_complete(&self) -> bool {
+        self.offset == self.total
+    }
+
+    /// Sends a request for the rest of the stream and receives it until complete.
+    ///
+    /// - `request` writes the request for the stream starting at the given offset into the write buffer, returning its length,
+    ///   or `None` if it does not fit. It is sent as a binary message.
+    /// - `on_chunk` is called with the absolute offset and the payload of every received chunk.
+    ///   The offset only advances once `on_chunk` succeeds, so a failed chunk is requested again when resuming.
+    ///
+    /// Returns right away if the download is already complete. Messages other than binary messages are ignored,
+    /// `Ping` and `Close` messages are handled as by [`next!`](crate::next).
+    ///
+    /// # Errors
+    /// - [`DownloadError::Interrupted`]: The connection was closed before the download completed.
+    /// - [`DownloadError::TooLong`]: A chunk exceeds the total length. The chunk is not passed to `on_chunk`.
+    /// - [`DownloadError::Chunk`]: `on_chunk` failed.
+    /// - [`DownloadError::WebSocket`]: Sending the request or reading a chunk failed.
+    pub async fn run<RW, Rng, Q, C, E>(
+        &mut self,
+        websocketz: &mut WebSocket<'_, RW, Rng>,
+        request: Q,
+        mut on_chunk: C,
+    ) -> Result<(), DownloadError<RW::Error, E>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+        Q: FnOnce(u64, &mut [u8]) -> Option<usize>,
+        C: AsyncFnMut(u64, &[u8]) -> Result<(), E>,
+    {
+        if self.is_complete() {
+            return Ok(());
+        }
+
+        let offset = self.offset;
+
+        websocketz
+            .core
+            .send_staged(|dst| request(offset, dst))
+            .await?;
+
+        while !self.is_complete() {
+            let chunk = match crate::next!(websocketz) {
+                Some(Ok(Message::Binary(chunk))) => chunk,
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(DownloadError::Interrupted {
+                        offset: self.offset,
+                    });
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(Error::Message(err).into()),
+            };
+
+            if chunk.len() as u64 > self.remaining() {
+                return Err(DownloadError::TooLong {
+                    offset: self.offset,
+                    len: chunk.len(),
+                });
+            }
+
+            on_chunk(self.offset, chunk)
+                .await
+                .map_err(DownloadError::Chunk)?;
+
+            self.offset += chunk.len() as u64;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+//! Connecting to hosts resolving to IPv6 and IPv4 addresses.
+//!
+//! Name resolution and sockets are not part of this library. Resolve the `AAAA` and `A` records of the host,
+//! e.g. with `embassy_net::Stack::dns_query`, and let [`DualStack::connect`] try the addresses in the order of
+//! [`DualStack::addresses`], alternating between the address families (RFC 8305, "Happy Eyeballs").
+//! A network with broken IPv6 or IPv4 connectivity then costs a single failed attempt before the other family is tried.
+//!
+//! The attempts are made one after another. Bound every attempt with a timeout, e.g. with `embassy_time::with_timeout`,
+//! so that an unreachable address does not stall the following ones.
+//!
+//! With the `embassy-net` feature, `Resolved::resolve` queries both record types and holds the addresses for a [`DualStack`].
+//!
+//! The connected socket is then handed to [`WebSocket::connect`](crate::WebSocket::connect).
+//!
+//! # Example
+//!
+//! Connecting with `embassy-net`:
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # use websocketz::mock::Noop;
+//! # mod embassy_net {
+//! #     #[derive(Debug, Clone, Copy, Default)]
+//! #     pub struct IpAddress;
+//! #     pub enum DnsQueryType { A, Aaaa }
+//! #     pub struct Stack;
+//! #     impl Stack { pub async fn dns_query(&self, _: &str, _: DnsQueryType) -> Result<[IpAddress; 1], ()> { Ok([IpAddress]) } }
+//! # }
+//! # async fn with_timeout<F: core::future::Future>(_: core::time::Duration, future: F) -> Result<F::Output, ()> { Ok(future.await) }
+//! # async fn tcp_connect(_: embassy_net::IpAddress, _: u16) -> Result<Noop, ()> { Ok(Noop) }
+//! use core::time::Duration;
+//!
+//! use embassy_net::{DnsQueryType, Stack};
+//! use websocketz::{WebSocket, dual_stack::DualStack, options::ConnectOptions};
+//!
+//! async fn run(stack: &Stack, rng: Noop) {
+//!     let ipv6 = stack.dns_query("example.com", DnsQueryType::Aaaa).await.unwrap_or_default();
+//!     let ipv4 = stack.dns_query("example.com", DnsQueryType::A).await.unwrap_or_default();
+//!
+//!     let (socket, _address) = DualStack::new(&ipv6, &ipv4)
+//!         .connect(async |address| {
+//!             with_timeout(Duration::from_millis(250), tcp_connect(address, 80))
+//!                 .await
+//!                 .and_then(|connected| connected)
+//!         })
+//!         .await
+//!         .expect("Failed to connect");
+//!
+//!     let read_buffer = &mut [0u8; 1024];
+//!     let write_buffer = &mut [0u8; 1024];
+//!     let fragments_buffer = &mut [0u8; 1024];
+//!
+//!     let websocketz = WebSocket::connect::<16>(
+//!         ConnectOptions::new("/ws").expect("Valid path"),
+//!         socket,
+//!         rng,
+//!         read_buffer,
+//!         write_buffer,
+//!         fragments_buffer,
+//!     )
+//!     .await
+//!     .expect("Handshake failed");
+//! # let _ = websocketz;
+//! }
+//! ```
+
+#[cfg(feature = "embassy-net")]
+use embassy_net::{
+    IpAddress, Stack,
+    dns::{self, DnsQueryType},
+};
+
+use crate::error::DualStackError;
+
+/// The resolved addresses of a host, tried by [`DualStack::connect`].
+///
+/// Generic over the address type, e.g. [`core::net::IpAddr`] or `embassy_net::IpAddress`.
+#[derive(Debug, Clone, Copy)]
+pub struct DualStack<'a, A> {
+    /// The IPv6 addresses, in the order of the resolver.
+    ipv6: &'a [A],
+    /// The IPv4 addresses, in the order of the resolver.
+    ipv4: &'a [A],
+    /// Try an IPv4 address first.
+    prefer_ipv4: bool,
+}
+
+impl<'a, A> DualStack<'a, A> {
+    /// Creates a new [`DualStack`] with the resolved IPv6 and IPv4 addresses, preferring IPv6.
+    pub const fn new(ipv6: &'a [A], ipv4: &'a [A]) -> Self {
+        Self {
+            ipv6,
+            ipv4,
+            prefer_ipv4: false,
+        }
+    }
+
+    /// Sets whether to try an IPv4 address first, e.g. on networks known to have broken IPv6 connectivity.
+    pub const fn with_prefer_ipv4(mut self, prefer_ipv4: bool) -> Self {
+        self.prefer_ipv4 = prefer_ipv4;
+        self
+    }
+
+    /// Returns the addresses in the order they are tried, alternating between the preferred family and the other one.
+    ///
+    /// Once the addresses of one family are exhausted, the remaining addresses of the other family follow.
+    pub fn addresses(&self) -> Addresses<'a, A> {
+        let (first, second) = match self.prefer_ipv4 {
+            true => (self.ipv4, self.ipv6),
+            false => (self.ipv6, self.ipv4),
+        };
+
+        Addresses {
+            first: first.iter(),
+            second: second.iter(),
+            take_second: false,
+        }
+    }
+
+    /// Opens a socket to the first address in the order of [`DualStack::addresses`] that `open` connects to,
+    /// returning the socket along with the address.
+    ///
+    /// If no address could be connected, the error of the last attempt is returned.
+    pub async fn connect<F, S, C>(&self, mut open: F) -> Result<(S, A), DualStackError<C>>
+    where
+        F: AsyncFnMut(A) -> Result<S, C>,
+        A: Copy,
+    {
+        let mut error = DualStackError::NoAddresses;
+
+        for address in self.addresses() {
+            match open(*address).await {
+                Ok(socket) => return Ok((socket, *address)),
+                Err(err) => error = DualStackError::Connect(err),
+            }
+        }
+
+        Err(error)
+    }
+}
+
+/// The IPv6 and IPv4 addresses of a host, resolved with `embassy-net`.
+///
+/// Holds up to `N` addresses of each family, further addresses are dropped.
+///
+/// ```no_run
+/// # async fn run(stack: embassy_net::Stack<'_>) {
+/// use websocketz::dual_stack::Resolved;
+///
+/// let resolved = Resolved::<2>::resolve(stack, "example.com")
+///     .await
+///     .expect("Failed to resolve");
+///
+/// let (socket, _address) = resolved
+///     .dual_stack()
+///     .connect(async |address| {
+///         // Connect a `TcpSocket` to `(address, 80)`, bounded with a timeout.
+///         # let _ = address;
+///         # Ok::<_, ()>(())
+///     })
+///     .await
+///     .expect("Failed to connect");
+/// # let _ = socket;
+/// # }
+/// ```
+#[cfg(feature = "embassy-net")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embassy-net")))]
+#[derive(Debug, Clone)]
+pub struct Resolved<const N: usize> {
+    ipv6: [IpAddress; N],
+    ipv6_len: usize,
+    ipv4: [IpAddress; N],
+    ipv4_len: usize,
+}
+
+#[cfg(feature = "embassy-net")]
+impl<const N: usize> Resolved<N> {
+    /// Resolves the `AAAA` and `A` records of `host` with `stack`.
+    ///
+    /// A family that fails to resolve, e.g. because the host has no such record, has no addresses.
+    /// Fails with the error of the `A` query if neither family resolves.
+    pub async fn resolve(stack: Stack<'_>, host: &str) -> Result<Self, dns::Error> {
+        let ipv6 = stack.dns_query(host, DnsQueryType::Aaaa).await;
+        let ipv4 = stack.dns_query(host, DnsQueryType::A).await;
+
+        let (ipv6, ipv4) = match (ipv6, ipv4) {
+            (Err(_), Err(err)) => return Err(err),
+            (ipv6, ipv4) => (ipv6.unwrap_or_default(), ipv4.unwrap_or_default()),
+        };
+
+        let (ipv6, ipv6_len) = Self::collect(&ipv6);
+        let (ipv4, ipv4_len) = Self::collect(&ipv4);
+
+        Ok(Self {
+            ipv6,
+            ipv6_len,
+            ipv4,
+            ipv4_len,
+        })
+    }
+
+    fn collect(addresses: &[IpAddress]) -> ([IpAddress; N], usize) {
+        let mut collected = [IpAddress::v4(0, 0, 0, 0); N];
+        let len = addresses.len().min(N);
+
+        collected[..len].copy_from_slice(&addresses[..len]);
+
+        (collected, len)
+    }
+
+    /// Returns the resolved IPv6 addresses.
+    pub fn ipv6(&self) -> &[IpAddress] {
+        &self.ipv6[..self.ipv6_len]
+    }
+
+    /// Returns the resolved IPv4 addresses.
+    pub fn ipv4(&self) -> &[IpAddress] {
+        &self.ipv4[..self.ipv4_len]
+    }
+
+    /// Returns a [`DualStack`] trying the resolved addresses, preferring IPv6.
+    pub fn dual_stack(&self) -> DualStack<'_, IpAddress> {
+        DualStack::new(self.ipv6(), self.ipv4())
+    }
+}
+
+/// Iterator over the addresses of a [`DualStack`], alternating between the address families.
+///
+/// See [`DualStack::addresses`].
+#[derive(Debug, Clone)]
+pub struct Addresses<'a, A> {
+    first: core::slice::Iter<'a, A>,
+    second: core::slice::Iter<'a, A>,
+    /// The next address is taken from `second`.
+    take_second: bool,
+}
+
+impl<'a, A> Iterator for Addresses<'a, A> {
+    type Item = &'a A;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (next, other) = match self.take_second {
+            true => (&mut self.second, &mut self.first),
+            false => (&mut self.first, &mut self.second),
+        };
+
+        self.take_second = !self.take_second;
+
+        next.next().or_else(|| other.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    const IPV6: &[IpAddr] = &[
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)),
+        IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 3)),
+    ];
+
+    const IPV4: &[IpAddr] = &[
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+    ];
+
+    #[test]
+    fn addresses() {
+        let order = [IPV6[0], IPV4[0], IPV6[1], IPV4[1], IPV6[2]];
+
+        assert!(DualStack::new(IPV6, IPV4).addresses().eq(order.iter()));
+
+        let order = [IPV4[0], IPV6[0], IPV4[1], IPV6[1], IPV6[2]];
+
+        assert!(
+            DualStack::new(IPV6, IPV4)
+                .with_prefer_ipv4(true)
+                .addresses()
+                .eq(order.iter())
+        );
+
+        assert!(DualStack::new(&[], IPV4).addresses().eq(IPV4.iter()));
+        assert!(DualStack::new(IPV6, &[]).addresses().eq(IPV6.iter()));
+    }
+
+    #[cfg(feature = "embassy-net")]
+    #[test]
+    fn resolved_keeps_n_addresses() {
+        use embassy_net::IpAddress;
+
+        let addresses = [
+            IpAddress::v4(192, 0, 2, 1),
+            IpAddress::v4(192, 0, 2, 2),
+            IpAddress::v4(192, 0, 2, 3),
+        ];
+
+        let (collected, len) = Resolved::<2>::collect(&addresses);
+        assert_eq!(&collected[..len], &addresses[..2]);
+
+        let (_, len) = Resolved::<4>::collect(&addresses);
+        assert_eq!(len, 3);
+    }
+}
@@ -4,6 +4,8 @@
 
 use core::convert::Infallible;
 
+use crate::http::{Redirect, SecVersion};
+
 /// Error decoding a WebSocket frame.
 #[derive(Debug, thiserror::Error)]
 pub enum FrameDecodeError {
@@ -37,6 +39,21 @@ pub enum FrameDecodeError {
     /// Control frames must have a payload length of 125 bytes or less.
     #[error("Control frame too large")]
     ControlFrameTooLarge,
+    /// Data frame exceeding the frame limit.
+    ///
+    /// Reported as [`MessageError::MessageTooLarge`] by the reads of a [`WebSocket`](crate::WebSocket),
+    /// see [`Limits::with_max_frame`](crate::options::Limits::with_max_frame).
+    #[error("Frame too large")]
+    FrameTooLarge,
+    /// The connection was closed in the middle of a frame.
+    ///
+    /// Unlike a connection closed between two frames, which ends reading cleanly,
+    /// this usually indicates a broken link rather than a normal termination.
+    #[error("Unexpected EOF after {received} bytes of a frame")]
+    UnexpectedEof {
+        /// The number of bytes of the incomplete frame received before EOF.
+        received: usize,
+    },
 }
 
 /// Error encoding a WebSocket frame.
@@ -45,6 +62,11 @@ pub enum FrameEncodeError {
     /// Write buffer is too small to hold the encoded frame.
     #[error("Buffer too small")]
     BufferTooSmall,
+    /// Text frame payload is not valid UTF-8.
+    ///
+    /// Only returned for raw frames with text validation enabled.
+    #[error("Invalid UTF-8")]
+    InvalidUTF8,
 }
 
 /// Error decoding an HTTP request/response.
@@ -67,6 +89,26 @@ pub enum HttpEncodeError {
     /// Write buffer is too small to hold the encoded HTTP request/response.
     #[error("Buffer too small")]
     BufferTooSmall,
+    /// A dynamic header is one of the [`ConnectOptions::RESERVED_HEADERS`](crate::options::ConnectOptions::RESERVED_HEADERS).
+    #[error("Reserved header")]
+    ReservedHeader,
+    /// A header name is empty or contains characters other than visible ASCII characters, or a colon.
+    ///
+    /// See [`validate_header`](crate::http::validate_header).
+    #[error("Invalid header name")]
+    InvalidHeaderName,
+    /// A header value contains a CR, LF or NUL character.
+    ///
+    /// See [`validate_header`](crate::http::validate_header).
+    #[error("Invalid header value")]
+    InvalidHeaderValue,
+    /// A header name or value exceeds [`MAX_HEADER_NAME_LEN`](crate::http::MAX_HEADER_NAME_LEN)
+    /// or [`MAX_HEADER_VALUE_LEN`](crate::http::MAX_HEADER_VALUE_LEN).
+    #[error("Header too long")]
+    HeaderTooLong,
+    /// The status code of a response is not three digits, or its reason-phrase contains control characters other than tabs.
+    #[error("Invalid status line")]
+    InvalidStatusLine,
 }
 
 /// Protocol specific errors/violations.
@@ -83,9 +125,16 @@ pub enum ProtocolError {
     InvalidUTF8,
     /// Fragment is invalid.
     ///
-    /// This happens when a final fragment is received without any prior fragments.
+    /// This happens when a new text or binary frame is received while a fragmented message of the same type is ongoing,
+    /// or a final text or binary frame is received while any fragmented message is ongoing.
     #[error("Invalid fragment")]
     InvalidFragment,
+    /// Fragment opcode does not match the ongoing fragmented message.
+    ///
+    /// This happens when a non-final text or binary frame is received while a fragmented message of the other type is ongoing,
+    /// e.g. a peer continuing a fragmented text message with binary frames instead of continuation frames.
+    #[error("Fragment opcode mismatch")]
+    FragmentOpcodeMismatch,
     /// Continuation frame is invalid.
     ///
     /// This happens when a continuation frame is received without an ongoing fragmented message.
@@ -93,9 +142,42 @@ pub enum ProtocolError {
     InvalidContinuationFrame,
 }
 
-/// Error reading from a WebSocket connection.
+/// Error reading from a WebSocket connection during the handshake.
 #[derive(Debug, thiserror::Error)]
 pub enum ReadError<I> {
+    /// Error reading an HTTP request/response from the underlying I/O.
+    #[error("Read http error: {0}")]
+    ReadHttp(
+        #[source]
+        #[from]
+        framez::ReadError<I, HttpDecodeError>,
+    ),
+}
+
+impl<I> ReadError<I> {
+    /// Returns the underlying I/O error, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            ReadError::ReadHttp(framez::ReadError::IO(err)) => Some(err),
+            ReadError::ReadHttp(_) => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            ReadError::ReadHttp(framez::ReadError::IO(err)) => Some(err),
+            ReadError::ReadHttp(_) => None,
+        }
+    }
+}
+
+/// Error reading a message from an established WebSocket connection.
+///
+/// Returned by the read path after a successful handshake.
+/// Unlike [`Error`], it can not contain any HTTP or handshake errors.
+#[derive(Debug, thiserror::Error)]
+pub enum MessageError<I> {
     /// Error reading a WebSocket frame from the underlying I/O.
     #[error("Read frame error: {0}")]
     ReadFrame(
@@ -103,23 +185,80 @@ pub enum ReadError<I> {
         #[from]
         framez::ReadError<I, FrameDecodeError>,
     ),
-    /// Error reading an HTTP request/response from the underlying I/O.
-    #[error("Read http error: {0}")]
-    ReadHttp(
+    /// Error writing an automatic reply, such as a Pong or a Close frame, to the underlying I/O.
+    #[error("Write frame error: {0}")]
+    WriteFrame(
         #[source]
         #[from]
-        framez::ReadError<I, HttpDecodeError>,
+        framez::WriteError<I, FrameEncodeError>,
     ),
     /// Protocol error.
-    #[error("Protocol error: {0}")]
-    Protocol(
+    #[error("Protocol error at frame #{frame}: {error}")]
+    Protocol {
+        /// The protocol violation.
         #[source]
-        #[from]
-        ProtocolError,
-    ),
+        error: ProtocolError,
+        /// The number of the offending frame on the connection.
+        ///
+        /// See [`FrameMeta::sequence`](crate::FrameMeta::sequence).
+        frame: u64,
+    },
     /// Fragments buffer is too small to read a frame.
     #[error("Fragments buffer too small to read a frame")]
     FragmentsBufferTooSmall,
+    /// Message exceeds the configured size limits.
+    ///
+    /// See [`Limits`](crate::options::Limits).
+    #[error("Message too large")]
+    MessageTooLarge,
+    /// Fragmented message contains more empty non-final fragments than allowed.
+    ///
+    /// See [`Limits::with_max_empty_fragments`](crate::options::Limits::with_max_empty_fragments).
+    #[error("Too many empty fragments")]
+    TooManyEmptyFragments,
+    /// Binary message failed the integrity check.
+    ///
+    /// See [`WebSocket::with_integrity_verifier`](crate::WebSocket::with_integrity_verifier).
+    #[error("Integrity check failed")]
+    IntegrityCheckFailed,
+    /// Compressed message is corrupted or does not fit into the inflate buffer, or no inflate buffer was set.
+    ///
+    /// See [`WebSocket::with_inflater`](crate::WebSocket::with_inflater).
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    #[error("Decompression failed")]
+    DecompressionFailed,
+    /// Reading is paused, no frame was read.
+    ///
+    /// Unlike other errors, reading can continue once resumed.
+    /// See [`WebSocket::pause_reads`](crate::WebSocket::pause_reads).
+    #[error("Reads paused")]
+    ReadsPaused,
+    /// The next message is fragmented and can not be peeked.
+    ///
+    /// See [`WebSocket::peek_message`](crate::WebSocket::peek_message).
+    #[error("Can not peek a fragmented message")]
+    FragmentedPeek,
+}
+
+impl<I> MessageError<I> {
+    /// Returns the underlying I/O error, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            MessageError::ReadFrame(framez::ReadError::IO(err))
+            | MessageError::WriteFrame(framez::WriteError::IO(err)) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            MessageError::ReadFrame(framez::ReadError::IO(err))
+            | MessageError::WriteFrame(framez::WriteError::IO(err)) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 /// Error writing to a WebSocket connection.
@@ -137,6 +276,16 @@ pub enum WriteError<I> {
         #[from]
         framez::WriteError<I, FrameEncodeError>,
     ),
+    /// A [`PreEncodedMessage`](crate::PreEncodedMessage) or a pre-masked payload was masked for the wrong side.
+    ///
+    /// Clients must send masked frames and servers unmasked ones.
+    #[error("Pre-encoded message mask mismatch")]
+    PreEncodedMaskMismatch,
+    /// A frame was only partly written, because the future writing it was dropped or the write failed.
+    ///
+    /// Returned by `with_inner_async`, as bytes written to the stream would land in the middle of the frame.
+    #[error("Frame partly written")]
+    PartialFrame,
     /// Error writing an HTTP request/response to the underlying I/O.
     #[error("Write http error: {0}")]
     WriteHttp(
@@ -146,6 +295,26 @@ pub enum WriteError<I> {
     ),
 }
 
+impl<I> WriteError<I> {
+    /// Returns the underlying I/O error, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            WriteError::WriteFrame(framez::WriteError::IO(err))
+            | WriteError::WriteHttp(framez::WriteError::IO(err)) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            WriteError::WriteFrame(framez::WriteError::IO(err))
+            | WriteError::WriteHttp(framez::WriteError::IO(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 /// Error establishing a WebSocket handshake.
 ///
 /// # Generic Parameters
@@ -176,9 +345,60 @@ pub enum HandshakeError<E = Infallible> {
     /// Missing or invalid (`Sec-WebSocket-Version`) header.
     #[error("Missing or invalid sec websocket version header")]
     MissingOrInvalidSecVersion,
+    /// The client requested a `Sec-WebSocket-Version` other than [`SUPPORTED_VERSION`](crate::http::SUPPORTED_VERSION).
+    ///
+    /// Carries the requested version. Such requests should be answered with `426 Upgrade Required`
+    /// and a `Sec-WebSocket-Version: 13` header, see [`WebSocket::reject`](crate::WebSocket::reject).
+    #[error("Unsupported sec websocket version: {0}")]
+    UnsupportedSecVersion(SecVersion),
+    /// The server answered with `426 Upgrade Required` and the `Sec-WebSocket-Version`s it supports.
+    ///
+    /// A response with any other status code or without the header fails with [`HandshakeError::InvalidStatusCode`].
+    #[error("Upgrade required, supported sec websocket versions: {0}")]
+    UpgradeRequired(SecVersion),
+    /// The server answered with a redirect status code, e.g. `301 Moved Permanently`, and a `Location` header.
+    ///
+    /// Relative locations are followed on the same connection up to
+    /// [`ConnectOptions::with_max_redirects`](crate::options::ConnectOptions::with_max_redirects) times,
+    /// if the response body has a `Content-Length` and the server does not close the connection.
+    #[error("Redirected: {0}")]
+    Redirect(Redirect),
     /// Missing (`Sec-WebSocket-Key`) header.
     #[error("Missing sec websocket key header")]
     MissingSecKey,
+    /// Invalid (`Sec-WebSocket-Key`) header.
+    ///
+    /// The key must be a base64-encoded 16-byte value (24 characters).
+    /// See [`AcceptOptions::with_lenient_sec_key`](crate::options::AcceptOptions::with_lenient_sec_key).
+    #[error("Invalid sec websocket key header")]
+    InvalidSecKey,
+    /// The client sent frames before receiving the handshake response.
+    ///
+    /// See [`AcceptOptions::with_reject_early_frames`](crate::options::AcceptOptions::with_reject_early_frames).
+    #[error("Frames received before the handshake completed")]
+    EarlyFrames,
+    /// The server selected more than one subprotocol or one that was not offered.
+    ///
+    /// See [`ConnectOptions::with_protocols`](crate::options::ConnectOptions::with_protocols).
+    #[error("Invalid subprotocol")]
+    InvalidProtocol,
+    /// The server answered the offered `permessage-deflate` extension with parameters that were not offered, or more than once.
+    ///
+    /// See [`ConnectOptions::with_permessage_deflate`](crate::options::ConnectOptions::with_permessage_deflate).
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    #[error("Invalid extension")]
+    InvalidExtension,
+    /// Missing, empty or repeated `Host` header in a HTTP/1.1 request.
+    ///
+    /// See [`AcceptOptions::with_strict_request`](crate::options::AcceptOptions::with_strict_request).
+    #[error("Missing or invalid host header")]
+    MissingOrInvalidHost,
+    /// The request path does not start with `/` or contains characters that must be percent-encoded.
+    ///
+    /// See [`AcceptOptions::with_strict_request`](crate::options::AcceptOptions::with_strict_request).
+    #[error("Invalid request path")]
+    InvalidPath,
     /// Other error.
     ///
     /// User-defined error type.
@@ -186,6 +406,294 @@ pub enum HandshakeError<E = Infallible> {
     Other(E),
 }
 
+/// Invalid buffer configuration, detected when a connection is created, before its stream is used.
+///
+/// Buffers can not alias, as they are exclusive references.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The read buffer is empty.
+    ///
+    /// Nothing, not even the handshake, can be read without a read buffer.
+    #[error("Read buffer must not be empty")]
+    EmptyReadBuffer,
+    /// The write buffer is empty.
+    ///
+    /// The handshake can not be written without a write buffer.
+    #[error("Write buffer must not be empty")]
+    EmptyWriteBuffer,
+    /// The fragments buffer is empty, but fragmented messages are expected.
+    ///
+    /// See [`ConnectOptions::with_require_fragments`](crate::options::ConnectOptions::with_require_fragments)
+    /// and [`AcceptOptions::with_require_fragments`](crate::options::AcceptOptions::with_require_fragments).
+    #[error("Fragments buffer must not be empty")]
+    EmptyFragmentsBuffer,
+}
+
+/// Error checking buffers against a [`BufferPlan`](crate::buffers::BufferPlan).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BufferError {
+    /// The read buffer can not hold the largest received frame or the received handshake.
+    #[error("Read buffer too small: {required} bytes required, {actual} provided")]
+    ReadTooSmall {
+        /// The minimum length.
+        required: usize,
+        /// The length of the buffer.
+        actual: usize,
+    },
+    /// The write buffer can not hold the largest sent frame or the sent handshake.
+    #[error("Write buffer too small: {required} bytes required, {actual} provided")]
+    WriteTooSmall {
+        /// The minimum length.
+        required: usize,
+        /// The length of the buffer.
+        actual: usize,
+    },
+    /// The fragments buffer can not hold the largest fragmented message.
+    #[error("Fragments buffer too small: {required} bytes required, {actual} provided")]
+    FragmentsTooSmall {
+        /// The minimum length.
+        required: usize,
+        /// The length of the buffer.
+        actual: usize,
+    },
+}
+
+/// Error queueing a ping with [`WebSocket::queue_ping`](crate::WebSocket::queue_ping).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum QueuePingError {
+    /// The queue already holds the maximum number of pings.
+    #[error("Ping queue is full")]
+    Full,
+    /// The payload exceeds the maximum length of a queued ping.
+    #[error("Queued ping payload too long")]
+    PayloadTooLong,
+    /// Sending the ping would exceed the maximum number of unanswered pings.
+    ///
+    /// See [`WebSocket::with_max_unanswered_pings`](crate::WebSocket::with_max_unanswered_pings).
+    #[error("Peer unresponsive")]
+    PeerUnresponsive,
+    /// The connection is closed.
+    #[error("Connection closed")]
+    ConnectionClosed,
+}
+
+/// Error decompressing a received payload.
+///
+/// See [`compression::decompress`](crate::compression::decompress).
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    /// The payload is empty and has no header.
+    #[error("Missing compression header")]
+    MissingHeader,
+    /// The header identifies another codec.
+    #[error("Unknown codec: {0}")]
+    UnknownCodec(u8),
+    /// The payload is corrupted or does not fit into the buffer once decompressed.
+    #[error("Corrupted compressed payload")]
+    Corrupted,
+}
+
+/// Error handling a frame of a [`Mux`](crate::mux::Mux).
+#[cfg(feature = "mux")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mux")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MuxError {
+    /// The frame is truncated or has an unknown kind.
+    #[error("Malformed mux frame")]
+    Malformed,
+    /// No channel with the given id exists.
+    #[error("Unknown channel: {0}")]
+    UnknownChannel(u16),
+    /// The channel was already opened by the same side.
+    #[error("Channel already open: {0}")]
+    AlreadyOpen(u16),
+    /// The channel is not open in the required direction.
+    #[error("Channel not open: {0}")]
+    NotOpen(u16),
+    /// All channel slots are in use.
+    #[error("Too many channels")]
+    TooManyChannels,
+    /// The peer did not grant enough credits to send the data.
+    #[error("Not enough credits on channel {channel}: {available} available")]
+    NotEnoughCredits {
+        /// The channel id.
+        channel: u16,
+        /// The credits granted by the peer.
+        available: u32,
+    },
+    /// The peer sent more data than the credits granted to it.
+    #[error("Credits exceeded on channel {0}")]
+    CreditsExceeded(u16),
+    /// Only text and binary messages can be sent on a channel.
+    #[error("Unsupported message")]
+    UnsupportedMessage,
+    /// Data of another channel was received through a [`MuxChannel`](crate::mux::MuxChannel).
+    #[error("Data received on another channel: {0}")]
+    OtherChannel(u16),
+}
+
+/// Error sending a frame of a [`Mux`](crate::mux::Mux).
+///
+/// # Generic Parameters
+/// `E`: The error type of the [`MessageSink`](crate::mux::MessageSink).
+#[cfg(feature = "mux")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mux")))]
+#[derive(Debug, thiserror::Error)]
+pub enum MuxSendError<E> {
+    /// The frame can not be sent in the current state of the channel.
+    #[error("Mux error: {0}")]
+    Mux(
+        #[from]
+        #[source]
+        MuxError,
+    ),
+    /// The sink failed to send the frame.
+    #[error("Send error: {0}")]
+    Send(#[source] E),
+}
+
+/// Error receiving a frame of a [`Mux`](crate::mux::Mux).
+///
+/// # Generic Parameters
+/// `E`: The error type of the [`MessageSource`](crate::mux::MessageSource).
+#[cfg(feature = "mux")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mux")))]
+#[derive(Debug, thiserror::Error)]
+pub enum MuxReceiveError<E> {
+    /// The received frame is invalid in the current state of the channel.
+    #[error("Mux error: {0}")]
+    Mux(
+        #[from]
+        #[source]
+        MuxError,
+    ),
+    /// The source failed to receive a message.
+    #[error("Receive error: {0}")]
+    Receive(#[source] E),
+}
+
+/// Error handling a JSON-RPC message of a [`JsonRpc`](crate::jsonrpc::JsonRpc).
+#[cfg(feature = "jsonrpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonrpc")))]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum JsonRpcError {
+    /// The message is not valid JSON or a field has an unexpected type.
+    #[error("Deserialize error: {0}")]
+    Deserialize(#[from] serde_json_core::de::Error),
+    /// The message is not a JSON-RPC 2.0 request, notification or response.
+    #[error("Malformed JSON-RPC message")]
+    Malformed,
+    /// The response does not belong to an outstanding call.
+    #[error("Unknown id: {0}")]
+    UnknownId(u32),
+    /// All call slots are in use.
+    #[error("Too many outstanding calls")]
+    TooManyCalls,
+    /// The serialized message does not fit into the buffer.
+    #[error("Buffer too small")]
+    BufferTooSmall,
+}
+
+/// Error sending a message of a [`JsonRpc`](crate::jsonrpc::JsonRpc).
+///
+/// # Generic Parameters
+/// `E`: The error type of the [`TextSink`](crate::jsonrpc::TextSink).
+#[cfg(feature = "jsonrpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonrpc")))]
+#[derive(Debug, thiserror::Error)]
+pub enum JsonRpcSendError<E> {
+    /// The message can not be sent in the current state.
+    #[error("JSON-RPC error: {0}")]
+    JsonRpc(
+        #[from]
+        #[source]
+        JsonRpcError,
+    ),
+    /// The sink failed to send the message.
+    #[error("Send error: {0}")]
+    Send(#[source] E),
+}
+
+/// Error reading from or writing to a [`ByteStream`](crate::stream::ByteStream).
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError<I> {
+    /// The WebSocket connection failed.
+    #[error("WebSocket error: {0}")]
+    WebSocket(
+        #[from]
+        #[source]
+        Error<I>,
+    ),
+    /// The peer sent a text message. The stream is only carried by binary messages.
+    #[error("Unexpected text message")]
+    Text,
+    /// The unread part of a received message does not fit into the pending buffer.
+    #[error("Pending buffer too small")]
+    BufferTooSmall,
+}
+
+impl<I: embedded_io_async::Error> embedded_io_async::Error for StreamError<I> {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        match self {
+            StreamError::WebSocket(err) => err.kind(),
+            StreamError::Text => embedded_io_async::ErrorKind::InvalidData,
+            StreamError::BufferTooSmall => embedded_io_async::ErrorKind::OutOfMemory,
+        }
+    }
+}
+
+/// Error returned by [`Download::run`](crate::download::Download::run).
+///
+/// # Generic Parameters
+/// `E`: The error type of the chunk callback.
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError<I, E> {
+    /// The connection was closed before the download completed.
+    ///
+    /// Reconnect and run the download again to resume it at `offset`.
+    #[error("Connection closed at offset {offset}")]
+    Interrupted {
+        /// The offset of the first missing byte.
+        offset: u64,
+    },
+    /// A chunk exceeds the total length of the stream.
+    #[error("Chunk of {len} bytes at offset {offset} exceeds the total length")]
+    TooLong {
+        /// The offset of the chunk.
+        offset: u64,
+        /// The length of the chunk.
+        len: usize,
+    },
+    /// The chunk callback failed.
+    #[error("Chunk error: {0}")]
+    Chunk(#[source] E),
+    /// WebSocket error.
+    #[error("WebSocket error: {0}")]
+    WebSocket(
+        #[from]
+        #[source]
+        Error<I>,
+    ),
+}
+
+impl<I, E> DownloadError<I, E> {
+    /// Returns the underlying I/O error of the [`WebSocket`](DownloadError::WebSocket) variant, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            DownloadError::WebSocket(err) => err.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error of the [`WebSocket`](DownloadError::WebSocket) variant, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            DownloadError::WebSocket(err) => err.into_io_error(),
+            _ => None,
+        }
+    }
+}
+
 /// Fragmentation error.
 #[derive(Debug, thiserror::Error)]
 pub enum FragmentationError {
@@ -226,6 +734,13 @@ pub enum Error<I, E = Infallible> {
         #[source]
         HandshakeError<E>,
     ),
+    /// Invalid buffer configuration.
+    #[error("Config error: {0}")]
+    Config(
+        #[from]
+        #[source]
+        ConfigError,
+    ),
     /// Fragmentation error.
     #[error("Fragment error: {0}")]
     Fragmentation(
@@ -233,4 +748,274 @@ pub enum Error<I, E = Infallible> {
         #[source]
         FragmentationError,
     ),
+    /// Error reading a message.
+    #[error("Message error: {0}")]
+    Message(
+        #[from]
+        #[source]
+        MessageError<I>,
+    ),
+    /// The peer did not answer the maximum number of pings with a pong.
+    ///
+    /// The connection should be considered dead.
+    #[error("Peer unresponsive")]
+    PeerUnresponsive,
+    /// No pong was received within the [`Keepalive::timeout`](crate::options::Keepalive::timeout) after a keepalive ping.
+    ///
+    /// See [`WebSocket::tick`](crate::WebSocket::tick). The connection should be considered dead.
+    #[error("Ping timeout")]
+    PingTimeout,
+}
+
+impl<I, E> Error<I, E> {
+    /// Returns the underlying I/O error, if any.
+    ///
+    /// Useful to branch on the transport error, e.g. on its [`embedded_io_async::ErrorKind`],
+    /// without matching every wrapper variant.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            Error::Read(err) => err.io_error(),
+            Error::Write(err) => err.io_error(),
+            Error::Message(err) => err.io_error(),
+            Error::Handshake(_)
+            | Error::Config(_)
+            | Error::Fragmentation(_)
+            | Error::PeerUnresponsive
+            | Error::PingTimeout => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            Error::Read(err) => err.into_io_error(),
+            Error::Write(err) => err.into_io_error(),
+            Error::Message(err) => err.into_io_error(),
+            Error::Handshake(_)
+            | Error::Config(_)
+            | Error::Fragmentation(_)
+            | Error::PeerUnresponsive
+            | Error::PingTimeout => None,
+        }
+    }
+}
+
+/// Returns the kind of the underlying I/O error, or a matching [`embedded_io_async::ErrorKind`].
+///
+/// Lets a [`WebSocket`](crate::WebSocket) be wrapped into a transport implementing [`embedded_io_async::Read`] and [`embedded_io_async::Write`].
+impl<I, E> embedded_io_async::Error for Error<I, E>
+where
+    I: embedded_io_async::Error,
+    E: core::fmt::Debug,
+{
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        if let Some(err) = self.io_error() {
+            return err.kind();
+        }
+
+        io_error_kind(self)
+    }
+}
+
+/// Error returned by [`WebSocket::connect_any`](crate::WebSocket::connect_any) if no target could be connected.
+///
+/// Holds the error of the last target tried.
+///
+/// # Generic Parameters
+/// `C`: The error type of the callback opening the streams.
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectAnyError<I, C> {
+    /// No targets were given.
+    #[error("No targets")]
+    NoTargets,
+    /// The callback failed to open a stream.
+    #[error("Connect error: {0}")]
+    Connect(#[source] C),
+    /// WebSocket error.
+    #[error("WebSocket error: {0}")]
+    WebSocket(
+        #[from]
+        #[source]
+        Error<I>,
+    ),
+}
+
+impl<I, C> ConnectAnyError<I, C> {
+    /// Returns the underlying I/O error of the [`WebSocket`](ConnectAnyError::WebSocket) variant, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            ConnectAnyError::WebSocket(err) => err.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error of the [`WebSocket`](ConnectAnyError::WebSocket) variant, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            ConnectAnyError::WebSocket(err) => err.into_io_error(),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned by [`DualStack::connect`](crate::dual_stack::DualStack::connect).
+///
+/// # Generic Parameters
+/// `C`: The error type of the callback opening the sockets.
+#[cfg(feature = "dual-stack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dual-stack")))]
+#[derive(Debug, thiserror::Error)]
+pub enum DualStackError<C> {
+    /// No addresses were given.
+    #[error("No addresses")]
+    NoAddresses,
+    /// The callback failed to open a socket to the last address.
+    #[error("Connect error: {0}")]
+    Connect(#[source] C),
+}
+
+/// Error establishing a WebSocket connection over TLS.
+///
+/// See [`WebSocket::connect_tls`](crate::WebSocket::connect_tls) and [`WebSocket::accept_tls`](crate::WebSocket::accept_tls).
+///
+/// # Generic Parameters
+/// `T`: The error type of the callback performing the TLS handshake.
+#[cfg(feature = "tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError<I, T> {
+    /// The buffer is shorter than the [`TlsBufferPlan`](crate::tls::TlsBufferPlan).
+    #[error("Buffer too small")]
+    BufferTooSmall,
+    /// The TLS handshake failed.
+    #[error("TLS error: {0}")]
+    Tls(#[source] T),
+    /// WebSocket error.
+    #[error("WebSocket error: {0}")]
+    WebSocket(
+        #[from]
+        #[source]
+        Error<I>,
+    ),
+}
+
+#[cfg(feature = "tls")]
+impl<I, T> TlsError<I, T> {
+    /// Returns the underlying I/O error of the [`WebSocket`](TlsError::WebSocket) variant, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            TlsError::WebSocket(err) => err.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error of the [`WebSocket`](TlsError::WebSocket) variant, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            TlsError::WebSocket(err) => err.into_io_error(),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned by a [`Pool`](crate::pool::Pool).
+///
+/// # Generic Parameters
+/// `C`: The error type of the [`Connector`](crate::pool::Connector).
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError<I, C> {
+    /// No connection slot exists at the given index.
+    #[error("Invalid slot index")]
+    InvalidIndex,
+    /// The slot lost its buffers because a previous connection attempt was cancelled.
+    #[error("Slot lost")]
+    SlotLost,
+    /// The connector failed to open a new stream.
+    #[error("Connect error: {0}")]
+    Connect(#[source] C),
+    /// WebSocket error.
+    #[error("WebSocket error: {0}")]
+    WebSocket(
+        #[from]
+        #[source]
+        Error<I>,
+    ),
+}
+
+#[cfg(feature = "alloc")]
+impl<I, C> PoolError<I, C> {
+    /// Returns the underlying I/O error of the [`WebSocket`](PoolError::WebSocket) variant, if any.
+    pub fn io_error(&self) -> Option<&I> {
+        match self {
+            PoolError::WebSocket(err) => err.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Consumes the error and returns the underlying I/O error of the [`WebSocket`](PoolError::WebSocket) variant, if any.
+    pub fn into_io_error(self) -> Option<I> {
+        match self {
+            PoolError::WebSocket(err) => err.into_io_error(),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the [`embedded_io_async::ErrorKind`] matching an error without an underlying I/O error.
+fn io_error_kind<I, E>(err: &Error<I, E>) -> embedded_io_async::ErrorKind {
+    use embedded_io_async::ErrorKind;
+
+    match err {
+        Error::Write(WriteError::ConnectionClosed) => ErrorKind::NotConnected,
+        Error::Config(_) | Error::Fragmentation(_) => ErrorKind::InvalidInput,
+        Error::PeerUnresponsive | Error::PingTimeout => ErrorKind::TimedOut,
+        _ => ErrorKind::InvalidData,
+    }
+}
+
+/// Returns the underlying I/O error, or wraps the error into a [`std::io::Error`] of a matching [`std::io::ErrorKind`].
+///
+/// Lets `std` applications propagate WebSocket errors with `?` from functions returning [`std::io::Error`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<E> From<Error<std::io::Error, E>> for std::io::Error
+where
+    E: core::error::Error + Send + Sync + 'static,
+{
+    fn from(err: Error<std::io::Error, E>) -> Self {
+        if err.io_error().is_none() {
+            return std::io::Error::new(io_error_kind(&err).into(), err);
+        }
+
+        err.into_io_error().expect("Bug: io error checked above")
+    }
+}
+
+/// Returns the underlying I/O error, or wraps the error, as the conversion of [`Error`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl From<MessageError<std::io::Error>> for std::io::Error {
+    fn from(err: MessageError<std::io::Error>) -> Self {
+        Error::<std::io::Error>::Message(err).into()
+    }
+}
+
+/// Returns the underlying I/O error, or wraps the error, as the conversion of [`Error`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl From<WriteError<std::io::Error>> for std::io::Error {
+    fn from(err: WriteError<std::io::Error>) -> Self {
+        Error::<std::io::Error>::Write(err).into()
+    }
+}
+
+/// Returns the underlying I/O error, or wraps the error, as the conversion of [`Error`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl From<ReadError<std::io::Error>> for std::io::Error {
+    fn from(err: ReadError<std::io::Error>) -> Self {
+        Error::<std::io::Error>::Read(err).into()
+    }
 }
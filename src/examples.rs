@@ -36,7 +36,8 @@ mod lib {
                         name: "User-Agent",
                         value: b"WebSocketz",
                     },
-                ]),
+                ])
+                .expect("Valid headers"),
             stream,
             rng,
             read_buffer,
@@ -171,6 +172,78 @@ mod lib {
         }
     }
 
+    #[tokio::test]
+    #[ignore = "Example"]
+    async fn with_next() {
+        use crate::mock::Noop;
+        use crate::{WebSocket, options::ConnectOptions};
+
+        let stream = Noop;
+        let read_buffer = &mut [0u8; 1024];
+        let write_buffer = &mut [0u8; 1024];
+        let fragments_buffer = &mut [0u8; 1024];
+        let rng = Noop;
+
+        let websocketz = WebSocket::connect::<16>(
+            ConnectOptions::default()
+                .with_path("/ws")
+                .expect("Valid path"),
+            stream,
+            rng,
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+        )
+        .await
+        .expect("Handshake failed");
+
+        let existing_websocket = || websocketz;
+
+        let mut websocketz = existing_websocket();
+
+        while let Some(Ok(is_text)) = websocketz.with_next(|msg| msg.is_text()).await {
+            // The closure's return value must not borrow the message.
+            let _ = is_text;
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "Example"]
+    async fn next_mut_macro() {
+        use crate::mock::Noop;
+        use crate::{MessageMut, WebSocket, next_mut, options::ConnectOptions};
+
+        let stream = Noop;
+        let read_buffer = &mut [0u8; 1024];
+        let write_buffer = &mut [0u8; 1024];
+        let fragments_buffer = &mut [0u8; 1024];
+        let rng = Noop;
+
+        let websocketz = WebSocket::connect::<16>(
+            ConnectOptions::default()
+                .with_path("/ws")
+                .expect("Valid path"),
+            stream,
+            rng,
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+        )
+        .await
+        .expect("Handshake failed");
+
+        let existing_websocket = || websocketz;
+
+        let mut websocketz = existing_websocket();
+
+        while let Some(Ok(msg)) = next_mut!(websocketz) {
+            if let MessageMut::Binary(payload) = msg {
+                // Transform the payload in place.
+                payload.iter_mut().for_each(|byte| *byte ^= 0xFF);
+            }
+        }
+    }
+
     #[tokio::test]
     #[ignore = "Example"]
     async fn send_method_no_compile() {
@@ -0,0 +1,319 @@
+//! Negotiation of WebSocket extensions.
+//!
+//! Apart from `permessage-deflate` with the `deflate` feature, this library does not implement any extension, it only negotiates them,
+//! so that applications can implement their own (e.g. vendor-specific) extensions, e.g. on top of the `danger` module with the `danger-frames` feature.
+//!
+//! A server declares its [`SupportedExtension`]s in order of preference with [`AcceptOptions::with_extensions`](crate::options::AcceptOptions::with_extensions).
+//! Every supported extension offered by the client is accepted and answered in a single `Sec-WebSocket-Extensions` response header.
+//! The accepted extensions are returned by [`WebSocket::extensions`](crate::WebSocket::extensions).
+//! Their parameters are then applied to the decoding of frames with a [`CodecConfig`].
+//!
+//! # Note
+//!
+//! Offers are split at every comma, quoted parameter values containing commas are not supported.
+
+use crate::http::HeaderValues;
+
+/// An extension offered in a `Sec-WebSocket-Extensions` header, e.g. `permessage-deflate; client_max_window_bits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extension<'a> {
+    name: &'a str,
+    params: &'a str,
+}
+
+impl<'a> Extension<'a> {
+    /// Parses a single extension offer.
+    ///
+    /// Returns `None` if the name is empty.
+    pub fn parse(offer: &'a str) -> Option<Self> {
+        let (name, params) = offer.split_once(';').unwrap_or((offer, ""));
+        let name = name.trim();
+
+        if name.is_empty() {
+            return None;
+        }
+
+        Some(Self { name, params })
+    }
+
+    /// Returns the name of the extension.
+    pub const fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the parameters of the extension.
+    pub fn params(&self) -> Params<'a> {
+        Params {
+            params: self.params.split(';'),
+        }
+    }
+}
+
+/// Iterator over the parameters of an [`Extension`], yielding their names and optional values.
+///
+/// Quotes around values are removed.
+#[derive(Debug, Clone)]
+pub struct Params<'a> {
+    params: core::str::Split<'a, char>,
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let param = self.params.next()?.trim();
+
+            if param.is_empty() {
+                continue;
+            }
+
+            return match param.split_once('=') {
+                Some((name, value)) => Some((name.trim(), Some(value.trim().trim_matches('"')))),
+                None => Some((param, None)),
+            };
+        }
+    }
+}
+
+/// Iterator over the extensions offered in all `Sec-WebSocket-Extensions` header lines.
+///
+/// See [`Request::extensions`](crate::http::Request::extensions) and [`Response::extensions`](crate::http::Response::extensions).
+#[derive(Debug, Clone)]
+pub struct Extensions<'a, 'buf> {
+    values: HeaderValues<'a, 'buf>,
+}
+
+impl<'a, 'buf> Extensions<'a, 'buf> {
+    pub(crate) const fn new(values: HeaderValues<'a, 'buf>) -> Self {
+        Self { values }
+    }
+}
+
+impl<'a> Iterator for Extensions<'a, '_> {
+    type Item = Extension<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.values.by_ref().find_map(Extension::parse)
+    }
+}
+
+/// An extension supported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupportedExtension<'a> {
+    name: &'a str,
+    response: &'a str,
+}
+
+impl<'a> SupportedExtension<'a> {
+    /// Creates a new [`SupportedExtension`], answered with its name only.
+    pub const fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            response: name,
+        }
+    }
+
+    /// Sets the response of the extension, e.g. `x-vendor; version=2`.
+    ///
+    /// Must start with the name of the extension.
+    pub const fn with_response(mut self, response: &'a str) -> Self {
+        self.response = response;
+        self
+    }
+
+    /// Returns the name of the extension.
+    pub const fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the response of the extension.
+    pub const fn response(&self) -> &'a str {
+        self.response
+    }
+}
+
+/// The set of accepted extensions, indexed by their position in the table of [`SupportedExtension`]s.
+///
+/// Only the first [`AcceptedExtensions::MAX`] supported extensions can be accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AcceptedExtensions(u32);
+
+impl AcceptedExtensions {
+    /// Maximum number of supported extensions that can be accepted.
+    pub const MAX: usize = u32::BITS as usize;
+
+    /// Creates an empty [`AcceptedExtensions`].
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Accepts every supported extension offered in `offered`.
+    pub fn negotiate<'e>(
+        offered: impl IntoIterator<Item = Extension<'e>>,
+        supported: &[SupportedExtension<'_>],
+    ) -> Self {
+        let supported = &supported[..supported.len().min(Self::MAX)];
+
+        offered
+            .into_iter()
+            .filter_map(|offer| {
+                supported
+                    .iter()
+                    .position(|extension| extension.name.eq_ignore_ascii_case(offer.name))
+            })
+            .fold(Self::new(), |accepted, index| {
+                Self(accepted.0 | (1 << index))
+            })
+    }
+
+    /// Returns whether no extension was accepted.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether the supported extension at `index` was accepted.
+    pub const fn contains(&self, index: usize) -> bool {
+        index < Self::MAX && self.0 & (1 << index) != 0
+    }
+
+    /// Returns the indices of the accepted extensions in order of preference.
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::MAX).filter(|index| self.contains(*index))
+    }
+
+    /// Writes the value of the `Sec-WebSocket-Extensions` response header into `dst`, returning the number of bytes written.
+    ///
+    /// Returns `None` if the value does not fit.
+    pub fn encode(&self, supported: &[SupportedExtension<'_>], dst: &mut [u8]) -> Option<usize> {
+        let mut pos = 0;
+
+        for (i, index) in self.indices().enumerate() {
+            let response = supported.get(index)?.response.as_bytes();
+            let separator: &[u8] = if i == 0 { b"" } else { b", " };
+
+            for bytes in [separator, response] {
+                dst.get_mut(pos..pos + bytes.len())?.copy_from_slice(bytes);
+                pos += bytes.len();
+            }
+        }
+
+        Some(pos)
+    }
+}
+
+/// Per-connection parameters of the negotiated extensions, applied to the frame codec after the handshake.
+///
+/// Extensions implemented by the application may use the reserved bits of data frames (RFC 6455, section 5.2),
+/// which are rejected by default. Allowed bits are reported by [`FrameMeta::reserved_bits`](crate::FrameMeta::reserved_bits)
+/// for every received frame. The bits of sent frames are set with the `danger` module.
+///
+/// See [`WebSocket::with_codec_config`](crate::WebSocket::with_codec_config).
+///
+/// ```
+/// use websocketz::extensions::CodecConfig;
+///
+/// // The accepted vendor extension marks frames with RSV2.
+/// let config = CodecConfig::new().with_reserved_bits(CodecConfig::RSV2);
+///
+/// assert!(config.allows(CodecConfig::RSV2));
+/// assert!(!config.allows(CodecConfig::RSV2 | CodecConfig::RSV3));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CodecConfig {
+    /// The reserved bits allowed on data frames, in the position of the first header byte.
+    reserved_bits: u8,
+}
+
+impl CodecConfig {
+    /// The RSV1 bit in the first header byte.
+    ///
+    /// With the `deflate` feature, RSV1 is always allowed on the first frame of a message if `permessage-deflate` was negotiated.
+    pub const RSV1: u8 = 0b0100_0000;
+    /// The RSV2 bit in the first header byte.
+    pub const RSV2: u8 = 0b0010_0000;
+    /// The RSV3 bit in the first header byte.
+    pub const RSV3: u8 = 0b0001_0000;
+    /// All reserved bits.
+    pub const RSV: u8 = Self::RSV1 | Self::RSV2 | Self::RSV3;
+
+    /// Creates a new [`CodecConfig`] rejecting all reserved bits.
+    pub const fn new() -> Self {
+        Self { reserved_bits: 0 }
+    }
+
+    /// Sets the reserved bits allowed on data frames, e.g. `CodecConfig::RSV2 | CodecConfig::RSV3`.
+    ///
+    /// Bits other than [`CodecConfig::RSV`] are ignored. Control frames never allow reserved bits.
+    pub const fn with_reserved_bits(mut self, bits: u8) -> Self {
+        self.reserved_bits = bits & Self::RSV;
+        self
+    }
+
+    /// Returns the reserved bits allowed on data frames.
+    pub const fn reserved_bits(&self) -> u8 {
+        self.reserved_bits
+    }
+
+    /// Returns whether all of `bits` are allowed on data frames.
+    pub const fn allows(&self, bits: u8) -> bool {
+        bits & !self.reserved_bits == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORTED: &[SupportedExtension<'static>] = &[
+        SupportedExtension::new("x-vendor").with_response("x-vendor; version=2"),
+        SupportedExtension::new("x-other"),
+        SupportedExtension::new("x-unused"),
+    ];
+
+    #[test]
+    fn parse() {
+        let extension = Extension::parse(
+            " permessage-deflate; client_max_window_bits ; server_max_window_bits=\"10\"",
+        )
+        .expect("Valid extension");
+
+        assert_eq!(extension.name(), "permessage-deflate");
+        assert!(extension.params().eq([
+            ("client_max_window_bits", None),
+            ("server_max_window_bits", Some("10")),
+        ]));
+
+        assert_eq!(Extension::parse(" ; param"), None);
+    }
+
+    #[test]
+    fn negotiate_in_preference_order() {
+        let offered = ["x-other", "x-unknown; a=1", "X-Vendor; version=1"]
+            .into_iter()
+            .filter_map(Extension::parse);
+
+        let accepted = AcceptedExtensions::negotiate(offered, SUPPORTED);
+
+        assert!(accepted.contains(0));
+        assert!(accepted.contains(1));
+        assert!(!accepted.contains(2));
+        assert!(accepted.indices().eq([0, 1]));
+
+        let mut dst = [0; 64];
+        let len = accepted
+            .encode(SUPPORTED, &mut dst)
+            .expect("Buffer large enough");
+
+        assert_eq!(&dst[..len], b"x-vendor; version=2, x-other");
+        assert_eq!(accepted.encode(SUPPORTED, &mut [0; 8]), None);
+    }
+
+    #[test]
+    fn nothing_offered() {
+        let accepted = AcceptedExtensions::negotiate([], SUPPORTED);
+
+        assert!(accepted.is_empty());
+        assert_eq!(accepted.encode(SUPPORTED, &mut []), Some(0));
+    }
+}
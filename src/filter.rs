@@ -0,0 +1,73 @@
+use crate::OpCode;
+
+/// The first frame of a received data message.
+///
+/// Passed to a message filter before the message is reassembled.
+/// See [`WebSocket::with_message_filter`](crate::WebSocket::with_message_filter).
+#[derive(Debug)]
+pub struct FirstFrame<'a> {
+    /// The number of the frame on the connection.
+    sequence: u64,
+    /// Indicates if this is the only frame of the message.
+    fin: bool,
+    /// The opcode of the message.
+    opcode: OpCode,
+    /// The payload of the first frame.
+    payload: &'a [u8],
+}
+
+impl<'a> FirstFrame<'a> {
+    pub(crate) const fn new(sequence: u64, fin: bool, opcode: OpCode, payload: &'a [u8]) -> Self {
+        Self {
+            sequence,
+            fin,
+            opcode,
+            payload,
+        }
+    }
+
+    /// Returns the number of the frame on the connection.
+    ///
+    /// See [`FrameMeta::sequence`](crate::FrameMeta::sequence).
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Indicates whether the message is a text message.
+    pub const fn is_text(&self) -> bool {
+        matches!(self.opcode, OpCode::Text)
+    }
+
+    /// Indicates whether the message is a binary message.
+    pub const fn is_binary(&self) -> bool {
+        matches!(self.opcode, OpCode::Binary)
+    }
+
+    /// Indicates whether this is the only frame of the message.
+    ///
+    /// `false` means that the message is fragmented and continuation frames will follow.
+    pub const fn is_final(&self) -> bool {
+        self.fin
+    }
+
+    /// Returns the payload of the first frame.
+    ///
+    /// For fragmented messages, this is only the first fragment.
+    pub const fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+/// The decision of a message filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The message is reassembled and returned to the caller.
+    Accept,
+    /// The message is skipped.
+    ///
+    /// Remaining continuation frames of a fragmented message are discarded without being copied into the fragments buffer.
+    Reject,
+}
+
+/// A filter invoked on the first frame of every received data message.
+pub type MessageFilter = fn(&FirstFrame<'_>) -> Verdict;
@@ -0,0 +1,64 @@
+use crate::Message;
+
+/// A frame of a fragmented text or binary message, passed through without reassembling the message.
+///
+/// See [`next_fragment!`](crate::next_fragment).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragment<'a> {
+    /// Indicates if the message is a text message.
+    text: bool,
+    /// Indicates if this is the first frame of the message.
+    first: bool,
+    /// Indicates if this is the last frame of the message.
+    fin: bool,
+    /// The payload of the frame.
+    payload: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    pub(crate) const fn new(text: bool, first: bool, fin: bool, payload: &'a [u8]) -> Self {
+        Self {
+            text,
+            first,
+            fin,
+            payload,
+        }
+    }
+
+    /// Indicates whether the message is a text message.
+    ///
+    /// The payload of a single fragment is not validated as UTF-8, since a UTF-8 sequence may span several fragments.
+    pub const fn is_text(&self) -> bool {
+        self.text
+    }
+
+    /// Indicates whether the message is a binary message.
+    pub const fn is_binary(&self) -> bool {
+        !self.text
+    }
+
+    /// Indicates whether this is the first frame of the message.
+    pub const fn is_first(&self) -> bool {
+        self.first
+    }
+
+    /// Indicates whether this is the last frame of the message.
+    pub const fn is_final(&self) -> bool {
+        self.fin
+    }
+
+    /// Returns the payload of the frame.
+    pub const fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+/// An item read by [`next_fragment!`](crate::next_fragment).
+#[derive(Debug)]
+pub enum StreamItem<'a> {
+    /// A message received in a single frame, a control message,
+    /// or a message reassembled in the fragments buffer because it could not be passed through.
+    Message(Message<'a>),
+    /// A frame of a fragmented text or binary message.
+    Fragment(Fragment<'a>),
+}
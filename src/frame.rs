@@ -1,7 +1,7 @@
 use crate::OpCode;
 
 /// A received frame.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Frame<'a> {
     /// Indicates if this is the final frame in a message.
     fin: bool,
@@ -35,16 +35,6 @@ impl<'a> Frame<'a> {
     pub const fn payload(&self) -> &'a [u8] {
         self.payload
     }
-
-    pub fn write_payload(&self, dst: &mut [u8]) -> Option<usize> {
-        if dst.len() < self.payload.len() {
-            return None;
-        }
-
-        dst[..self.payload.len()].copy_from_slice(self.payload);
-
-        Some(self.payload.len())
-    }
 }
 
 /// A mutable received frame.
@@ -56,6 +46,10 @@ pub struct FrameMut<'a> {
     opcode: OpCode,
     /// The masking key of the frame, if any.
     mask: Option<[u8; 4]>,
+    /// RSV1 is set, the frame starts a message compressed with `permessage-deflate`.
+    compressed: bool,
+    /// The reserved bits allowed by the [`CodecConfig`](crate::extensions::CodecConfig), without the RSV1 of `permessage-deflate`.
+    reserved_bits: u8,
     /// The payload of the frame.
     payload: &'a mut [u8],
 }
@@ -72,10 +66,59 @@ impl<'a> FrameMut<'a> {
             fin,
             opcode,
             mask,
+            compressed: false,
+            reserved_bits: 0,
             payload,
         }
     }
 
+    /// Sets whether the frame starts a compressed message.
+    pub(crate) const fn with_compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Sets the reserved bits of the frame allowed by the codec.
+    pub(crate) const fn with_reserved_bits(mut self, reserved_bits: u8) -> Self {
+        self.reserved_bits = reserved_bits;
+        self
+    }
+
+    /// Returns whether this is the final frame in a message.
+    pub const fn is_final(&self) -> bool {
+        self.fin
+    }
+
+    /// Returns the reserved bits set on the frame, in the position of the first header byte.
+    ///
+    /// Only bits allowed by the [`CodecConfig`](crate::extensions::CodecConfig) are ever set,
+    /// RSV1 of a compressed frame is reported by [`FrameMut::is_compressed`] instead.
+    pub const fn reserved_bits(&self) -> u8 {
+        self.reserved_bits
+    }
+
+    /// Returns whether the frame starts a message compressed with `permessage-deflate`, i.e. RSV1 is set.
+    ///
+    /// Always `false` without the `deflate` feature.
+    pub const fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Returns the opcode of the frame.
+    pub const fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    /// Returns the payload of the frame.
+    pub const fn payload(&self) -> &[u8] {
+        self.payload
+    }
+
+    /// Consumes the [`FrameMut`] and returns the mutable payload.
+    pub const fn into_payload(self) -> &'a mut [u8] {
+        self.payload
+    }
+
     pub const fn into_frame(self) -> Frame<'a> {
         Frame {
             fin: self.fin,
@@ -91,6 +134,62 @@ impl<'a> FrameMut<'a> {
     }
 }
 
+/// Metadata of the last frame received on a connection.
+///
+/// See [`WebSocket::last_frame`](crate::WebSocket::last_frame).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameMeta {
+    /// The number of the frame on the connection, starting at `1`.
+    sequence: u64,
+    /// Indicates if this is the final frame in a message.
+    fin: bool,
+    /// The reserved bits of the frame, see [`FrameMut::reserved_bits`].
+    reserved_bits: u8,
+    /// The length of the payload.
+    payload_len: usize,
+}
+
+impl FrameMeta {
+    pub(crate) const fn new(
+        sequence: u64,
+        fin: bool,
+        reserved_bits: u8,
+        payload_len: usize,
+    ) -> Self {
+        Self {
+            sequence,
+            fin,
+            reserved_bits,
+            payload_len,
+        }
+    }
+
+    /// Returns the number of the frame on the connection.
+    ///
+    /// Frames are numbered from `1` in the order they were received, including control frames.
+    /// The same number is reported by [`MessageError::Protocol`](crate::error::MessageError::Protocol).
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns whether this is the final frame in a message.
+    pub const fn is_final(&self) -> bool {
+        self.fin
+    }
+
+    /// Returns the reserved bits set on the frame, in the position of the first header byte.
+    ///
+    /// Always `0` unless allowed with a [`CodecConfig`](crate::extensions::CodecConfig).
+    pub const fn reserved_bits(&self) -> u8 {
+        self.reserved_bits
+    }
+
+    /// Returns the length of the payload.
+    pub const fn payload_len(&self) -> usize {
+        self.payload_len
+    }
+}
+
 #[derive(Debug)]
 pub struct Header {
     /// Indicates if this is the final frame in a message.
@@ -1,14 +1,63 @@
-use embedded_io_async::{Read, Write};
-use framez::state::{ReadState, WriteState};
-use rand::RngCore;
+use embedded_io_async::{ErrorType, Read, Write};
+use framez::{
+    decode::Decoder,
+    encode::Encoder,
+    state::{ReadState, WriteState},
+};
 
 use crate::{
-    ConnectionState, Frame, Message, OnFrame, WebSocketCore,
+    CloseCode, CloseFrame, CloseSignal, ConnectionState, FrameMut, Message, MessageMut, OnFrame,
+    OpCode, PreEncodedMessage, StreamItem, WebSocketCore,
     codec::FramesCodec,
-    error::{Error, ProtocolError, ReadError, WriteError},
-    websocket_core::FragmentsState,
+    error::{Error, FrameDecodeError, FrameEncodeError, MessageError, ProtocolError, WriteError},
+    rng::RngCore,
+    websocket_core::{Core, FragmentsState, OnFrameError, ReadCore, WriteCore},
 };
 
+/// Same as [`framez::functions::maybe_next`], but reads until at least `min_read_chunk` new bytes are buffered,
+/// the read buffer is full or the end of the stream is reached before decoding.
+///
+/// The read state is updated after every read, so that no bytes are lost if the future is dropped.
+/// The bytes read so far are then decoded on the next call.
+///
+/// This relies on the fields of [`ReadState`] and on how framez shifts the buffer,
+/// which is why framez is pinned to an exact version.
+pub(crate) async fn maybe_next<'buf, C, R>(
+    read_state: &'buf mut ReadState<'_>,
+    codec: &mut C,
+    inner: &mut R,
+    min_read_chunk: usize,
+) -> Option<Result<Option<C::Item>, framez::ReadError<R::Error, FrameDecodeError>>>
+where
+    C: Decoder<'buf, Error = FrameDecodeError>,
+    R: Read,
+{
+    // The bytes buffered so far are decoded first, or the buffer is shifted.
+    if !read_state.is_framable && !read_state.shift {
+        let start = read_state.index;
+
+        while read_state.index - start < min_read_chunk
+            && read_state.index < read_state.buffer.len()
+        {
+            match inner.read(&mut read_state.buffer[read_state.index..]).await {
+                Ok(0) => {
+                    read_state.eof = true;
+                    read_state.is_framable = true;
+
+                    break;
+                }
+                Ok(n) => {
+                    read_state.index += n;
+                    read_state.is_framable = true;
+                }
+                Err(err) => return Some(Err(framez::ReadError::IO(err))),
+            }
+        }
+    }
+
+    framez::functions::maybe_next(read_state, codec, inner).await
+}
+
 #[derive(Debug)]
 pub struct ReadAutoCaller;
 
@@ -23,39 +72,299 @@ impl ReadAutoCaller {
         write_state: &mut WriteState<'_>,
         fragments_state: &'this mut FragmentsState<'_>,
         state: &mut ConnectionState,
-    ) -> Option<Result<Option<Message<'this>>, Error<RW::Error>>>
+    ) -> Option<Result<Option<Message<'this>>, MessageError<RW::Error>>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+        F: FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
+    {
+        self.call_mut(
+            auto,
+            codec,
+            inner,
+            read_state,
+            write_state,
+            fragments_state,
+            state,
+        )
+        .await
+        .map(|result| result.map(|message| message.map(MessageMut::into_message)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn call_mut<'this, F, RW, Rng>(
+        &self,
+        auto: F,
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        write_state: &mut WriteState<'_>,
+        fragments_state: &'this mut FragmentsState<'_>,
+        state: &mut ConnectionState,
+    ) -> Option<Result<Option<MessageMut<'this>>, MessageError<RW::Error>>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+        F: FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
+    {
+        Self::call_with(
+            auto,
+            codec,
+            inner,
+            read_state,
+            write_state,
+            fragments_state,
+            state,
+            WebSocketCore::<RW, Rng>::on_frame,
+        )
+        .await
+    }
+
+    /// Same as [`ReadAutoCaller::call_mut`], but passes the frames of fragmented data messages through.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn call_fragment<'this, F, RW, Rng>(
+        &self,
+        auto: F,
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        write_state: &mut WriteState<'_>,
+        fragments_state: &'this mut FragmentsState<'_>,
+        state: &mut ConnectionState,
+    ) -> Option<Result<Option<StreamItem<'this>>, MessageError<RW::Error>>>
     where
         RW: Read + Write,
         Rng: RngCore,
-        F: FnOnce(Frame<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
+        F: FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
     {
-        let frame = match framez::functions::maybe_next(read_state, codec, inner).await {
+        Self::call_with(
+            auto,
+            codec,
+            inner,
+            read_state,
+            write_state,
+            fragments_state,
+            state,
+            WebSocketCore::<RW, Rng>::on_fragment,
+        )
+        .await
+    }
+
+    /// Reads the next frame, handling it with `on_frame` unless it is handled automatically.
+    ///
+    /// The frames sent while reading, i.e. queued pings and automatic replies, are sent with `write`.
+    #[allow(clippy::too_many_arguments)]
+    async fn call_with<'this, 'buf, F, C, RW, Wr, H, T>(
+        auto: F,
+        codec: &mut FramesCodec<C>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        write: &mut Wr,
+        fragments_state: &'this mut FragmentsState<'buf>,
+        state: &mut ConnectionState,
+        on_frame: H,
+    ) -> Option<Result<Option<T>, MessageError<RW::Error>>>
+    where
+        RW: Read,
+        Wr: ReadWriter<FramesCodec<C>, RW>,
+        F: FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
+        H: FnOnce(
+            &'this mut FragmentsState<'buf>,
+            FrameMut<'this>,
+            u64,
+        ) -> Option<Result<Option<T>, OnFrameError>>,
+    {
+        if state.reads_paused() {
+            return Some(Err(MessageError::ReadsPaused));
+        }
+
+        while let Some((payload, len)) = state.pop_queued_ping() {
+            let message = Message::Ping(&payload[..len]);
+
+            if let Err(err) = write.send(codec, inner, state, message).await {
+                return Some(Err(MessageError::WriteFrame(err)));
+            }
+
+            state.on_ping_sent();
+        }
+
+        let frame = match maybe_next(read_state, codec, inner, state.min_read_chunk()).await {
             Some(Ok(Some(frame))) => frame,
             Some(Ok(None)) => return Some(Ok(None)),
-            Some(Err(err)) => return Some(Err(Error::Read(ReadError::ReadFrame(err)))),
+            Some(Err(framez::ReadError::Decode(FrameDecodeError::FrameTooLarge))) => {
+                let sequence = state.next_frame_sequence();
+
+                return Some(Err(Self::fail(
+                    codec,
+                    inner,
+                    write,
+                    state,
+                    OnFrameError::MessageTooLarge,
+                    sequence,
+                )
+                .await));
+            }
+            Some(Err(err)) => return Some(Err(MessageError::ReadFrame(err))),
             None => return None,
         };
 
+        let sequence = state.on_frame_received(&frame);
+
+        if state.closed && frame.opcode() != OpCode::Close && !state.on_close_wait_frame() {
+            return None;
+        }
+
         let frame = match auto(frame) {
             Ok(on_frame) => match on_frame {
                 OnFrame::Send(message) => {
-                    state.closed = message.is_close();
+                    let is_close = message.is_close();
 
-                    match framez::functions::send(write_state, codec, inner, message).await {
-                        Ok(_) => match state.closed {
+                    match write.send(codec, inner, state, message).await {
+                        Ok(_) => match is_close {
                             false => return Some(Ok(None)),
                             true => return None,
                         },
-                        Err(err) => return Some(Err(Error::Write(WriteError::WriteFrame(err)))),
+                        Err(err) => return Some(Err(MessageError::WriteFrame(err))),
                     }
                 }
                 OnFrame::Noop(frame) => frame,
             },
-            Err(err) => return Some(Err(Error::Read(ReadError::Protocol(err)))),
+            Err(error) => {
+                return Some(Err(MessageError::Protocol {
+                    error,
+                    frame: sequence,
+                }));
+            }
         };
 
-        WebSocketCore::<RW, Rng>::on_frame(fragments_state, frame)
-            .map(|result| result.map_err(Error::from))
+        if frame.opcode() == OpCode::Pong {
+            state.on_pong_received();
+            write.on_pong_received();
+        }
+
+        match on_frame(fragments_state, frame, sequence) {
+            Some(Err(err)) => Some(Err(
+                Self::fail(codec, inner, write, state, err, sequence).await
+            )),
+            result => result.map(|result| result.map_err(|err| err.into_message_error(sequence))),
+        }
+    }
+
+    /// Converts the error of the frame numbered `sequence` into a [`MessageError`],
+    /// sending a Close frame first if the connection closes automatically on `err`.
+    async fn fail<C, RW, Wr>(
+        codec: &mut FramesCodec<C>,
+        inner: &mut RW,
+        write: &mut Wr,
+        state: &mut ConnectionState,
+        err: OnFrameError,
+        sequence: u64,
+    ) -> MessageError<RW::Error>
+    where
+        RW: Read,
+        Wr: ReadWriter<FramesCodec<C>, RW>,
+    {
+        if state.auto_closes_on(&err) {
+            let code = match err {
+                OnFrameError::TooManyEmptyFragments => CloseCode::Policy,
+                _ => CloseCode::Size,
+            };
+
+            let message = Message::Close(Some(CloseFrame::no_reason(code)));
+
+            if let Err(err) = write.send(codec, inner, state, message).await {
+                return MessageError::WriteFrame(err);
+            }
+        }
+
+        err.into_message_error(sequence)
+    }
+}
+
+/// Where the frames sent while reading are written, e.g. automatic replies.
+///
+/// A full connection writes them with the codec and the reader/writer of the read,
+/// a [`WebSocketRead`](crate::WebSocketRead) with the core of its attached [`WebSocketWrite`](crate::WebSocketWrite).
+pub(crate) trait ReadWriter<C, RW: ErrorType> {
+    /// Sends `message`, closing the connection if it is a Close frame.
+    async fn send(
+        &mut self,
+        codec: &mut C,
+        inner: &mut RW,
+        state: &mut ConnectionState,
+        message: Message<'_>,
+    ) -> Result<(), framez::WriteError<RW::Error, FrameEncodeError>>;
+
+    /// Must be called after a pong has been received.
+    fn on_pong_received(&mut self);
+}
+
+impl<RW, Rng> ReadWriter<FramesCodec<Rng>, RW> for WriteState<'_>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    async fn send(
+        &mut self,
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        state: &mut ConnectionState,
+        message: Message<'_>,
+    ) -> Result<(), framez::WriteError<RW::Error, FrameEncodeError>> {
+        state.closed |= message.is_close();
+
+        send_frame(self, codec, inner, state, message).await
+    }
+
+    #[inline]
+    fn on_pong_received(&mut self) {}
+}
+
+impl<R, W, Rng> ReadWriter<FramesCodec<()>, R> for WriteCore<'_, W, Rng>
+where
+    R: Read,
+    W: Write<Error = R::Error>,
+    Rng: RngCore,
+{
+    async fn send(
+        &mut self,
+        _codec: &mut FramesCodec<()>,
+        _inner: &mut R,
+        state: &mut ConnectionState,
+        message: Message<'_>,
+    ) -> Result<(), framez::WriteError<R::Error, FrameEncodeError>> {
+        send_requested_close(
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
+            &mut self.state,
+            self.read,
+        )
+        .await?;
+
+        // Nothing is sent after a Close frame, including the one requested through the close signal.
+        if self.state.closed {
+            state.closed = true;
+
+            return Ok(());
+        }
+
+        state.closed |= message.is_close();
+        self.state.closed |= state.closed;
+
+        send_frame(
+            &mut self.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.state,
+            message,
+        )
+        .await
+    }
+
+    #[inline]
+    fn on_pong_received(&mut self) {
+        self.state.on_pong_received();
     }
 }
 
@@ -65,30 +374,160 @@ pub struct ReadCaller;
 impl ReadCaller {
     #[allow(clippy::too_many_arguments)]
     pub async fn call<'this, RW, Rng>(
+        &self,
+        auto: (),
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        write_state: &mut (),
+        fragments_state: &'this mut FragmentsState<'_>,
+        state: &mut ConnectionState,
+    ) -> Option<Result<Option<Message<'this>>, MessageError<RW::Error>>>
+    where
+        RW: Read,
+    {
+        self.call_mut(
+            auto,
+            codec,
+            inner,
+            read_state,
+            write_state,
+            fragments_state,
+            state,
+        )
+        .await
+        .map(|result| result.map(|message| message.map(MessageMut::into_message)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn call_mut<'this, RW, Rng>(
         &self,
         _auto: (),
         codec: &mut FramesCodec<Rng>,
         inner: &mut RW,
         read_state: &'this mut ReadState<'_>,
-        _write_state: &mut WriteState<'_>,
+        _write_state: &mut (),
         fragments_state: &'this mut FragmentsState<'_>,
-        _state: &mut ConnectionState,
-    ) -> Option<Result<Option<Message<'this>>, Error<RW::Error>>>
+        state: &mut ConnectionState,
+    ) -> Option<Result<Option<MessageMut<'this>>, MessageError<RW::Error>>>
     where
         RW: Read,
     {
-        let frame = match framez::functions::maybe_next(read_state, codec, inner).await {
+        Self::call_with(
+            codec,
+            inner,
+            read_state,
+            fragments_state,
+            state,
+            WebSocketCore::<RW, Rng>::on_frame,
+        )
+        .await
+    }
+
+    /// Same as [`ReadCaller::call_mut`], but passes the frames of fragmented data messages through.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn call_fragment<'this, RW, Rng>(
+        &self,
+        _auto: (),
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        _write_state: &mut (),
+        fragments_state: &'this mut FragmentsState<'_>,
+        state: &mut ConnectionState,
+    ) -> Option<Result<Option<StreamItem<'this>>, MessageError<RW::Error>>>
+    where
+        RW: Read,
+    {
+        Self::call_with(
+            codec,
+            inner,
+            read_state,
+            fragments_state,
+            state,
+            WebSocketCore::<RW, Rng>::on_fragment,
+        )
+        .await
+    }
+
+    /// Reads the next frame and handles it with `on_frame`.
+    async fn call_with<'this, 'buf, RW, Rng, H, T>(
+        codec: &mut FramesCodec<Rng>,
+        inner: &mut RW,
+        read_state: &'this mut ReadState<'_>,
+        fragments_state: &'this mut FragmentsState<'buf>,
+        state: &mut ConnectionState,
+        on_frame: H,
+    ) -> Option<Result<Option<T>, MessageError<RW::Error>>>
+    where
+        RW: Read,
+        H: FnOnce(
+            &'this mut FragmentsState<'buf>,
+            FrameMut<'this>,
+            u64,
+        ) -> Option<Result<Option<T>, OnFrameError>>,
+    {
+        if state.reads_paused() {
+            return Some(Err(MessageError::ReadsPaused));
+        }
+
+        let frame = match maybe_next(read_state, codec, inner, state.min_read_chunk()).await {
             Some(Ok(Some(frame))) => frame,
             Some(Ok(None)) => return Some(Ok(None)),
-            Some(Err(err)) => return Some(Err(Error::Read(ReadError::ReadFrame(err)))),
+            Some(Err(framez::ReadError::Decode(FrameDecodeError::FrameTooLarge))) => {
+                return Some(Err(MessageError::MessageTooLarge));
+            }
+            Some(Err(err)) => return Some(Err(MessageError::ReadFrame(err))),
             None => return None,
         };
 
-        WebSocketCore::<RW, Rng>::on_frame(fragments_state, frame)
-            .map(|result| result.map_err(Error::from))
+        let sequence = state.on_frame_received(&frame);
+
+        if frame.opcode() == OpCode::Pong {
+            state.on_pong_received();
+        }
+
+        on_frame(fragments_state, frame, sequence)
+            .map(|result| result.map_err(|err| err.into_message_error(sequence)))
     }
 }
 
+/// Reads the next frame with `read`, sending automatic replies with `write`.
+///
+/// Same as [`ReadAutoCaller::call_mut`] for the halves of a split connection.
+pub async fn next_attached<'this, F, R, W, Rng>(
+    auto: F,
+    read: &'this mut ReadCore<'_, R>,
+    write: &mut WriteCore<'_, W, Rng>,
+) -> Option<Result<Option<MessageMut<'this>>, MessageError<R::Error>>>
+where
+    R: Read,
+    W: Write<Error = R::Error>,
+    Rng: RngCore,
+    F: FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static,
+{
+    let Core {
+        codec,
+        inner,
+        read: read_state,
+        fragments_state,
+        state,
+        ..
+    } = read;
+
+    ReadAutoCaller::call_with(
+        auto,
+        codec,
+        inner,
+        read_state,
+        write,
+        fragments_state,
+        state,
+        WebSocketCore::<R, ()>::on_frame,
+    )
+    .await
+}
+
 pub async fn send<RW, Rng>(
     codec: &mut FramesCodec<Rng>,
     inner: &mut RW,
@@ -96,6 +535,47 @@ pub async fn send<RW, Rng>(
     state: &mut ConnectionState,
     message: Message<'_>,
 ) -> Result<(), Error<RW::Error>>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    send_without_flush(codec, inner, write_state, state, message).await?;
+
+    flush(inner).await
+}
+
+/// Sends the Close frame requested through `signal`, if any and if none has been sent yet.
+pub(crate) async fn send_requested_close<RW, Rng>(
+    codec: &mut FramesCodec<Rng>,
+    inner: &mut RW,
+    write_state: &mut WriteState<'_>,
+    state: &mut ConnectionState,
+    signal: Option<&CloseSignal>,
+) -> Result<(), framez::WriteError<RW::Error, FrameEncodeError>>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    match signal.and_then(CloseSignal::requested) {
+        Some(code) if !state.closed => {
+            state.closed = true;
+
+            let message = Message::Close(Some(CloseFrame::no_reason(code)));
+
+            send_frame(write_state, codec, inner, state, message).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Same as [`send`], but leaves flushing the writer to the caller.
+pub async fn send_without_flush<RW, Rng>(
+    codec: &mut FramesCodec<Rng>,
+    inner: &mut RW,
+    write_state: &mut WriteState<'_>,
+    state: &mut ConnectionState,
+    message: Message<'_>,
+) -> Result<(), Error<RW::Error>>
 where
     RW: Write,
     Rng: RngCore,
@@ -104,11 +584,112 @@ where
         return Err(Error::Write(WriteError::ConnectionClosed));
     }
 
+    let is_ping = message.is_ping();
+
+    if is_ping && !state.can_ping() {
+        return Err(Error::PeerUnresponsive);
+    }
+
     state.closed = message.is_close();
 
-    framez::functions::send(write_state, codec, inner, message)
+    let len = Encoder::encode(codec, message, write_state.buffer)
+        .map_err(|err| Error::Write(WriteError::WriteFrame(framez::WriteError::Encode(err))))?;
+
+    state.on_write_started();
+
+    inner
+        .write_all(&write_state.buffer[..len])
         .await
-        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))?;
+        .map_err(|err| Error::Write(WriteError::WriteFrame(framez::WriteError::IO(err))))?;
+
+    state.on_write_finished();
+
+    if is_ping {
+        state.on_ping_sent();
+    }
+
+    Ok(())
+}
+
+/// Same as [`framez::functions::send`], but marks the connection as being in the middle of a frame while it is written.
+///
+/// The mark stays if the future is dropped or the write fails with an I/O error, see [`ConnectionState::mid_frame`].
+/// Encoding errors write nothing and clear it.
+pub(crate) async fn send_frame<C, RW, I>(
+    write_state: &mut WriteState<'_>,
+    codec: &mut C,
+    inner: &mut RW,
+    state: &mut ConnectionState,
+    item: I,
+) -> Result<(), framez::WriteError<RW::Error, C::Error>>
+where
+    C: Encoder<I>,
+    RW: Write,
+{
+    state.on_write_started();
+
+    let result = framez::functions::send(write_state, codec, inner, item).await;
+
+    if !matches!(result, Err(framez::WriteError::IO(_))) {
+        state.on_write_finished();
+    }
+
+    result
+}
+
+/// Flushes the writer.
+pub async fn flush<RW>(inner: &mut RW) -> Result<(), Error<RW::Error>>
+where
+    RW: Write,
+{
+    inner
+        .flush()
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(framez::WriteError::IO(err))))
+}
+
+pub async fn send_pre_encoded<RW, Rng>(
+    codec: &FramesCodec<Rng>,
+    inner: &mut RW,
+    state: &mut ConnectionState,
+    message: &PreEncodedMessage<'_>,
+) -> Result<(), Error<RW::Error>>
+where
+    RW: Write,
+{
+    if state.closed {
+        return Err(Error::Write(WriteError::ConnectionClosed));
+    }
+
+    if message.is_masked() != codec.is_client() {
+        return Err(Error::Write(WriteError::PreEncodedMaskMismatch));
+    }
+
+    let is_ping = message.is_ping();
+
+    if is_ping && !state.can_ping() {
+        return Err(Error::PeerUnresponsive);
+    }
+
+    state.closed = message.is_close();
+
+    state.on_write_started();
+
+    inner
+        .write_all(message.as_bytes())
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(framez::WriteError::IO(err))))?;
+
+    state.on_write_finished();
+
+    inner
+        .flush()
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(framez::WriteError::IO(err))))?;
+
+    if is_ping {
+        state.on_ping_sent();
+    }
 
     Ok(())
 }
@@ -133,7 +714,7 @@ where
         .fragments(fragment_size)
         .map_err(Error::Fragmentation)?
     {
-        framez::functions::send(write_state, codec, inner, frame)
+        send_frame(write_state, codec, inner, state, frame)
             .await
             .map_err(|err| Error::Write(WriteError::WriteFrame(err)))?;
     }
@@ -0,0 +1,294 @@
+use crate::http::Header;
+
+/// A scratch buffer receiving copies of handshake metadata, turned into a [`HandshakeInfo`].
+///
+/// See [`WebSocket::connect_full`](crate::WebSocket::connect_full) and [`WebSocket::accept_full`](crate::WebSocket::accept_full).
+#[derive(Debug)]
+pub struct HandshakeCapture<'s> {
+    buffer: &'s mut [u8],
+    /// Names of additional headers to copy.
+    headers: &'s [&'s str],
+    /// Number of bytes written to `buffer`.
+    len: usize,
+    /// A value did not fit into `buffer`.
+    truncated: bool,
+}
+
+impl<'s> HandshakeCapture<'s> {
+    /// Creates a new [`HandshakeCapture`] copying into `buffer`.
+    ///
+    /// Every copied value takes 3 bytes in addition to its length.
+    pub const fn new(buffer: &'s mut [u8]) -> Self {
+        Self {
+            buffer,
+            headers: &[],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Sets the names of additional headers to copy (case-insensitive), e.g. `Authorization` or `X-Request-Id`.
+    pub const fn with_headers(mut self, headers: &'s [&'s str]) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Copies the path and the query of a request target.
+    pub(crate) fn target(&mut self, target: &str) {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+
+        self.push(Field::PATH, path.as_bytes());
+
+        if let Some(query) = query {
+            self.push(Field::QUERY, query.as_bytes());
+        }
+    }
+
+    /// Copies the negotiated subprotocol.
+    pub(crate) fn protocol(&mut self, protocol: &str) {
+        self.push(Field::PROTOCOL, protocol.as_bytes());
+    }
+
+    /// Copies the extensions, the cookies named `cookie` and the additional headers.
+    pub(crate) fn headers(&mut self, headers: &[Header<'_>], cookie: &str) {
+        for header in headers {
+            let field = if header.name.eq_ignore_ascii_case("sec-websocket-extensions") {
+                Some(Field::EXTENSIONS)
+            } else if header.name.eq_ignore_ascii_case(cookie) {
+                Some(Field::COOKIE)
+            } else {
+                self.headers
+                    .iter()
+                    .position(|name| header.name.eq_ignore_ascii_case(name))
+                    .and_then(|index| u8::try_from(index).ok())
+                    .and_then(|index| index.checked_add(Field::HEADER))
+            };
+
+            if let Some(field) = field {
+                self.push(field, header.value);
+            }
+        }
+    }
+
+    fn push(&mut self, field: u8, value: &[u8]) {
+        let Ok(len) = u16::try_from(value.len()) else {
+            self.truncated = true;
+
+            return;
+        };
+
+        let Some(dst) = self.buffer.get_mut(self.len..self.len + 3 + value.len()) else {
+            self.truncated = true;
+
+            return;
+        };
+
+        dst[0] = field;
+        dst[1..3].copy_from_slice(&len.to_le_bytes());
+        dst[3..].copy_from_slice(value);
+
+        self.len += 3 + value.len();
+    }
+
+    pub(crate) fn finish(self) -> HandshakeInfo<'s> {
+        let buffer: &'s [u8] = self.buffer;
+
+        HandshakeInfo {
+            buffer: &buffer[..self.len],
+            headers: self.headers,
+            truncated: self.truncated,
+        }
+    }
+}
+
+/// The kinds of copied values.
+struct Field;
+
+impl Field {
+    const PATH: u8 = 0;
+    const QUERY: u8 = 1;
+    const PROTOCOL: u8 = 2;
+    const EXTENSIONS: u8 = 3;
+    const COOKIE: u8 = 4;
+    /// Additional headers, offset by their index.
+    const HEADER: u8 = 5;
+}
+
+/// Metadata of a handshake, copied into the scratch buffer of a [`HandshakeCapture`].
+///
+/// Returned by [`WebSocket::connect_full`](crate::WebSocket::connect_full) and [`WebSocket::accept_full`](crate::WebSocket::accept_full),
+/// so that values of the handshake request or response outlive the handshake without extracting them in a callback.
+#[derive(Debug, Clone, Copy)]
+pub struct HandshakeInfo<'s> {
+    buffer: &'s [u8],
+    headers: &'s [&'s str],
+    truncated: bool,
+}
+
+impl<'s> HandshakeInfo<'s> {
+    /// Returns the path of the request, without the query.
+    ///
+    /// `None` for clients.
+    pub fn path(&self) -> Option<&'s str> {
+        self.str(Field::PATH)
+    }
+
+    /// Returns the query of the request, without the leading `?`.
+    ///
+    /// `None` for clients and for requests without a query.
+    pub fn query(&self) -> Option<&'s str> {
+        self.str(Field::QUERY)
+    }
+
+    /// Returns the negotiated subprotocol.
+    ///
+    /// Servers only copy a subprotocol of [`AcceptOptions::with_protocols`](crate::options::AcceptOptions::with_protocols).
+    pub fn protocol(&self) -> Option<&'s str> {
+        self.str(Field::PROTOCOL)
+    }
+
+    /// Returns the values of the `Sec-WebSocket-Extensions` header lines.
+    ///
+    /// The extensions accepted by the server for clients, the extensions offered by the client for servers.
+    pub fn extensions(&self) -> CapturedValues<'s> {
+        CapturedValues::new(self.buffer, Field::EXTENSIONS)
+    }
+
+    /// Returns the values of the `Set-Cookie` header lines of the response for clients,
+    /// or of the `Cookie` header lines of the request for servers.
+    pub fn cookies(&self) -> CapturedValues<'s> {
+        CapturedValues::new(self.buffer, Field::COOKIE)
+    }
+
+    /// Returns the value of the first header line named `name`, one of the names set with [`HandshakeCapture::with_headers`].
+    pub fn header(&self, name: &str) -> Option<&'s [u8]> {
+        self.header_values(name).next()
+    }
+
+    /// Returns the values of all header lines named `name`, one of the names set with [`HandshakeCapture::with_headers`].
+    pub fn header_values(&self, name: &str) -> CapturedValues<'s> {
+        let field = self
+            .headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .and_then(|index| u8::try_from(index).ok())
+            .and_then(|index| index.checked_add(Field::HEADER));
+
+        match field {
+            Some(field) => CapturedValues::new(self.buffer, field),
+            None => CapturedValues::new(&[], 0),
+        }
+    }
+
+    /// Indicates whether some values did not fit into the scratch buffer and were not copied.
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn str(&self, field: u8) -> Option<&'s str> {
+        CapturedValues::new(self.buffer, field)
+            .next()
+            .and_then(|value| core::str::from_utf8(value).ok())
+    }
+}
+
+/// Iterator over the values of the header lines copied into a [`HandshakeInfo`].
+#[derive(Debug, Clone)]
+pub struct CapturedValues<'s> {
+    buffer: &'s [u8],
+    field: u8,
+}
+
+impl<'s> CapturedValues<'s> {
+    const fn new(buffer: &'s [u8], field: u8) -> Self {
+        Self { buffer, field }
+    }
+}
+
+impl<'s> Iterator for CapturedValues<'s> {
+    type Item = &'s [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let [field, a, b, rest @ ..] = self.buffer {
+            let len = usize::from(u16::from_le_bytes([*a, *b]));
+            let (value, rest) = rest.split_at(len);
+
+            self.buffer = rest;
+
+            if *field == self.field {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture() {
+        let buffer = &mut [0u8; 128];
+
+        let mut capture = HandshakeCapture::new(buffer).with_headers(&["x-device"]);
+
+        capture.target("/ws?token=abc");
+        capture.headers(
+            &[
+                Header {
+                    name: "Cookie",
+                    value: b"a=1",
+                },
+                Header {
+                    name: "X-Device",
+                    value: b"sensor-1",
+                },
+                Header {
+                    name: "Sec-WebSocket-Extensions",
+                    value: b"permessage-deflate",
+                },
+                Header {
+                    name: "cookie",
+                    value: b"b=2",
+                },
+                Header {
+                    name: "X-Other",
+                    value: b"ignored",
+                },
+            ],
+            "cookie",
+        );
+        capture.protocol("chat");
+
+        let info = capture.finish();
+
+        assert_eq!(info.path(), Some("/ws"));
+        assert_eq!(info.query(), Some("token=abc"));
+        assert_eq!(info.protocol(), Some("chat"));
+        assert!(info.extensions().eq([&b"permessage-deflate"[..]]));
+        assert!(info.cookies().eq([&b"a=1"[..], b"b=2"]));
+        assert_eq!(info.header("X-DEVICE"), Some(&b"sensor-1"[..]));
+        assert_eq!(info.header("X-Other"), None);
+        assert!(!info.is_truncated());
+    }
+
+    #[test]
+    fn truncated() {
+        let buffer = &mut [0u8; 8];
+
+        let mut capture = HandshakeCapture::new(buffer);
+
+        capture.target("/chat?room=1");
+
+        let info = capture.finish();
+
+        assert_eq!(info.path(), Some("/chat"));
+        assert_eq!(info.query(), None);
+        assert!(info.is_truncated());
+    }
+}
@@ -6,7 +6,455 @@ use framez::{decode::Decoder, encode::Encoder};
 pub use httparse::Header;
 use httparse::Status;
 
-use crate::error::{HttpDecodeError, HttpEncodeError};
+use crate::{
+    error::{HttpDecodeError, HttpEncodeError},
+    extensions::{AcceptedExtensions, Extensions, SupportedExtension},
+    options::ConnectOptions,
+};
+
+/// The GUID appended to the `Sec-WebSocket-Key` to compute the `Sec-WebSocket-Accept` header. (RFC 6455)
+pub const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The only `Sec-WebSocket-Version` supported by this library. (RFC 6455)
+pub const SUPPORTED_VERSION: u8 = 13;
+
+/// A bounded copy of a `Sec-WebSocket-Version` header value, e.g. `8` or `13, 8, 7`.
+///
+/// Values longer than [`SecVersion::CAPACITY`] bytes are truncated.
+///
+/// See [`HandshakeError::UnsupportedSecVersion`](crate::error::HandshakeError::UnsupportedSecVersion)
+/// and [`HandshakeError::UpgradeRequired`](crate::error::HandshakeError::UpgradeRequired).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SecVersion {
+    bytes: [u8; SecVersion::CAPACITY],
+    len: usize,
+    truncated: bool,
+}
+
+impl SecVersion {
+    /// The maximum number of bytes copied from the header value.
+    pub const CAPACITY: usize = 32;
+
+    pub(crate) fn new(value: &[u8]) -> Self {
+        let len = value.len().min(Self::CAPACITY);
+        let mut bytes = [0; Self::CAPACITY];
+
+        bytes[..len].copy_from_slice(&value[..len]);
+
+        Self {
+            bytes,
+            len,
+            truncated: len < value.len(),
+        }
+    }
+
+    /// Returns the copied header value.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Indicates whether the header value was longer than [`SecVersion::CAPACITY`] bytes.
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns the comma-separated versions of the header value, skipping values that are not version numbers.
+    pub fn versions(&self) -> impl Iterator<Item = u8> + '_ {
+        self.as_bytes()
+            .split(|byte| *byte == b',')
+            .filter_map(|version| {
+                core::str::from_utf8(version.trim_ascii())
+                    .ok()?
+                    .parse()
+                    .ok()
+            })
+    }
+}
+
+impl core::fmt::Debug for SecVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SecVersion")
+            .field("value", &format_args!("{}", self.as_bytes().escape_ascii()))
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+impl core::fmt::Display for SecVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_bytes().escape_ascii())?;
+
+        if self.truncated {
+            write!(f, "...")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A bounded copy of the `Location` header of a redirect response, e.g. `/ws/`.
+///
+/// Locations longer than [`Redirect::CAPACITY`] bytes are truncated. The whole location can be copied
+/// from the response in the callback of [`WebSocket::connect_with`](crate::WebSocket::connect_with), see [`Response::copy_header`].
+///
+/// See [`HandshakeError::Redirect`](crate::error::HandshakeError::Redirect).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Redirect {
+    status: u16,
+    bytes: [u8; Redirect::CAPACITY],
+    len: usize,
+    truncated: bool,
+    /// The length of the response body, if the redirect can be followed on the same connection.
+    follow: Option<usize>,
+}
+
+impl Redirect {
+    /// The maximum number of bytes copied from the `Location` header.
+    pub const CAPACITY: usize = 64;
+
+    /// The status codes of redirect responses.
+    pub const STATUS_CODES: &'static [u16] = &[301, 302, 303, 307, 308];
+
+    /// Returns the [`Redirect`] of a response with a redirect status code and a `Location` header.
+    #[cfg(feature = "client")]
+    pub(crate) fn from_response<const N: usize>(response: &Response<'_, N>) -> Option<Self> {
+        if !Self::STATUS_CODES.contains(&response.code()) {
+            return None;
+        }
+
+        let value = response.headers().header_value("location")?;
+
+        let len = value.len().min(Self::CAPACITY);
+        let mut bytes = [0; Self::CAPACITY];
+
+        bytes[..len].copy_from_slice(&value[..len]);
+
+        let mut redirect = Self {
+            status: response.code(),
+            bytes,
+            len,
+            truncated: len < value.len(),
+            follow: None,
+        };
+
+        if !redirect.truncated && redirect.is_relative() {
+            redirect.follow = reusable_body_len(response);
+        }
+
+        Some(redirect)
+    }
+
+    /// Returns the status code, e.g. `301`.
+    pub const fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the copied location.
+    pub fn location(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Returns the copied location, if it is valid UTF-8.
+    pub fn location_str(&self) -> Option<&str> {
+        core::str::from_utf8(self.location()).ok()
+    }
+
+    /// Indicates whether the location was longer than [`Redirect::CAPACITY`] bytes.
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns `true` if the location is a path on the same host, e.g. `/ws/`, rather than a URL or a network-path reference.
+    pub fn is_relative(&self) -> bool {
+        self.location_str().is_some_and(|location| {
+            location.starts_with('/') && !location.starts_with("//") && is_valid_path(location)
+        })
+    }
+
+    /// Returns the length of the response body to discard before following the redirect on the same connection.
+    ///
+    /// `None` if the location is not relative or the connection can not be reused.
+    #[cfg(feature = "client")]
+    pub(crate) const fn follow(&self) -> Option<usize> {
+        self.follow
+    }
+}
+
+impl core::fmt::Debug for Redirect {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Redirect")
+            .field("status", &self.status)
+            .field(
+                "location",
+                &format_args!("{}", self.location().escape_ascii()),
+            )
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+impl core::fmt::Display for Redirect {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} {}", self.status, self.location().escape_ascii())?;
+
+        if self.truncated {
+            write!(f, "...")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the length of the body of `response` if the connection can be reused after it.
+///
+/// Bodies delimited by the end of the connection or sent in chunks are not supported.
+#[cfg(feature = "client")]
+fn reusable_body_len<const N: usize>(response: &Response<'_, N>) -> Option<usize> {
+    if response.headers().header("transfer-encoding").is_some()
+        || response
+            .header_values("connection")
+            .any(|value| value.eq_ignore_ascii_case("close"))
+    {
+        return None;
+    }
+
+    response
+        .headers()
+        .header_value_str("content-length")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Discards the body of a response, yielding the number of bytes left to discard.
+#[derive(Debug)]
+#[cfg(feature = "client")]
+pub(crate) struct SkipBodyCodec {
+    remaining: usize,
+}
+
+#[cfg(feature = "client")]
+impl SkipBodyCodec {
+    pub(crate) const fn new(len: usize) -> Self {
+        Self { remaining: len }
+    }
+}
+
+#[cfg(feature = "client")]
+impl framez::decode::DecodeError for SkipBodyCodec {
+    type Error = HttpDecodeError;
+}
+
+#[cfg(feature = "client")]
+impl<'buf> Decoder<'buf> for SkipBodyCodec {
+    type Item = usize;
+
+    fn decode(&mut self, src: &'buf mut [u8]) -> Result<Option<(Self::Item, usize)>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        let len = self.remaining.min(src.len());
+
+        self.remaining -= len;
+
+        Ok(Some((self.remaining, len)))
+    }
+}
+
+/// The maximum length of the name of an outgoing header.
+pub const MAX_HEADER_NAME_LEN: usize = 256;
+
+/// The maximum length of the value of an outgoing header.
+pub const MAX_HEADER_VALUE_LEN: usize = 8 * 1024;
+
+/// Checks that an outgoing header can be sent as a single `name: value` line.
+///
+/// Every header of an encoded request or response is checked, so that header names and values composed at runtime,
+/// e.g. from device names or tokens, can not inject additional header lines.
+///
+/// # Errors
+/// - [`HttpEncodeError::InvalidHeaderName`]: `name` is empty or contains characters other than visible ASCII characters, or a colon.
+/// - [`HttpEncodeError::InvalidHeaderValue`]: `value` contains a CR, LF or NUL character.
+/// - [`HttpEncodeError::HeaderTooLong`]: `name` exceeds [`MAX_HEADER_NAME_LEN`] or `value` exceeds [`MAX_HEADER_VALUE_LEN`] bytes.
+///
+/// # Example
+///
+/// ```
+/// use websocketz::{error::HttpEncodeError, http::validate_header};
+///
+/// assert!(validate_header("X-Device", b"sensor-1").is_ok());
+///
+/// assert!(matches!(
+///     validate_header("X-Device", b"sensor-1\r\nX-Admin: true"),
+///     Err(HttpEncodeError::InvalidHeaderValue)
+/// ));
+/// ```
+pub fn validate_header(name: &str, value: &[u8]) -> Result<(), HttpEncodeError> {
+    if name.len() > MAX_HEADER_NAME_LEN || value.len() > MAX_HEADER_VALUE_LEN {
+        return Err(HttpEncodeError::HeaderTooLong);
+    }
+
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b':')
+    {
+        return Err(HttpEncodeError::InvalidHeaderName);
+    }
+
+    if value
+        .iter()
+        .any(|byte| matches!(byte, b'\r' | b'\n' | b'\0'))
+    {
+        return Err(HttpEncodeError::InvalidHeaderValue);
+    }
+
+    Ok(())
+}
+
+/// Returns the number of bytes `headers` take in an encoded request or response, e.g. to compute the `headers_total_len`
+/// of [`estimated_request_size`] and [`estimated_response_size`].
+///
+/// Every header is encoded as `name: value\r\n`.
+pub const fn headers_size(headers: &[Header<'_>]) -> usize {
+    let mut size = 0;
+    let mut i = 0;
+
+    while i < headers.len() {
+        size += headers[i].name.len() + headers[i].value.len() + 4;
+        i += 1;
+    }
+
+    size
+}
+
+/// Returns the number of bytes of the handshake request sent by a client.
+///
+/// `path_len` is the length of the request path and `headers_total_len` the encoded length of the additional headers,
+/// including the offered subprotocols and dynamic headers, see [`headers_size`].
+/// With the `deflate` feature, the `permessage-deflate` offer is included, whether it is enabled or not.
+/// The client's write buffer and the server's read buffer must be at least this large for the handshake to succeed.
+///
+/// # Example
+///
+/// ```
+/// use websocketz::http::{Header, estimated_request_size, headers_size};
+///
+/// const HEADERS: &[Header<'static>] = &[Header {
+///     name: "Host",
+///     value: b"example.com",
+/// }];
+///
+/// const WRITE_BUFFER_SIZE: usize = estimated_request_size("/ws".len(), headers_size(HEADERS));
+///
+/// let write_buffer = &mut [0u8; WRITE_BUFFER_SIZE];
+/// # assert_eq!(write_buffer.len(), if cfg!(feature = "deflate") { 254 } else { 152 });
+/// ```
+pub const fn estimated_request_size(path_len: usize, headers_total_len: usize) -> usize {
+    // `GET `, ` HTTP/1.1\r\n` and the final `\r\n`.
+    const FIXED: usize = 4 + 11 + 2;
+    // `upgrade`, `connection`, `sec-websocket-version` and `sec-websocket-key` with a 24-byte key.
+    const HANDSHAKE_HEADERS: usize = (7 + 9 + 4) + (10 + 7 + 4) + (21 + 2 + 4) + (17 + 24 + 4);
+
+    FIXED + HANDSHAKE_HEADERS + DEFLATE_HEADER + path_len + headers_total_len
+}
+
+/// Returns the number of bytes of the handshake response sent by a server.
+///
+/// `headers_total_len` is the encoded length of the additional headers, including the selected subprotocol
+/// and the accepted extensions, see [`headers_size`].
+/// With the `deflate` feature, the `permessage-deflate` response is included, whether it is enabled or not.
+/// The server's write buffer and the client's read buffer must be at least this large for the handshake to succeed.
+pub const fn estimated_response_size(headers_total_len: usize) -> usize {
+    // `HTTP/1.1 101 Switching Protocols\r\n` and the final `\r\n`.
+    const FIXED: usize = 34 + 2;
+    // `upgrade`, `connection`, `sec-websocket-version` and `sec-websocket-accept` with a 28-byte value.
+    const HANDSHAKE_HEADERS: usize = (7 + 9 + 4) + (10 + 7 + 4) + (21 + 2 + 4) + (20 + 28 + 4);
+
+    FIXED + HANDSHAKE_HEADERS + DEFLATE_HEADER + headers_total_len
+}
+
+/// The size of the `permessage-deflate` offer of a client and response of a server.
+#[cfg(feature = "deflate")]
+const DEFLATE_HEADER: usize = headers_size(&[crate::deflate::OFFER]);
+
+#[cfg(not(feature = "deflate"))]
+const DEFLATE_HEADER: usize = 0;
+
+/// Indicates whether the request asks for a WebSocket upgrade.
+///
+/// Checks the method and the `Upgrade` and `Connection` headers only,
+/// so that HTTP servers can cheaply route requests before committing buffers to [`WebSocket::accept`](crate::WebSocket::accept).
+/// The remaining headers are validated by the handshake.
+pub fn is_upgrade_request<const N: usize>(request: &Request<'_, N>) -> bool {
+    let headers = request.headers();
+
+    request.method() == "GET"
+        && headers
+            .header_value_str("upgrade")
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("websocket"))
+        && request
+            .header_values("connection")
+            .any(|token| token.eq_ignore_ascii_case("upgrade"))
+}
+
+/// Indicates whether the path only contains visible ASCII characters and no fragment (`#`).
+///
+/// Spaces, control characters and non-ASCII characters must be percent-encoded in the request line.
+pub(crate) fn is_valid_path(path: &str) -> bool {
+    !path.is_empty()
+        && path
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b'#')
+}
+
+/// Iterator over the comma-separated values of all header lines with the same name (case-insensitive).
+///
+/// Values are trimmed and empty values are skipped. Header lines that are not valid UTF-8 are skipped.
+///
+/// See [`Request::header_values`] and [`Response::header_values`].
+#[derive(Debug, Clone)]
+pub struct HeaderValues<'a, 'buf> {
+    name: &'a str,
+    headers: core::slice::Iter<'a, Header<'buf>>,
+    values: core::str::Split<'a, char>,
+}
+
+impl<'a, 'buf> HeaderValues<'a, 'buf> {
+    fn new(headers: &'a [Header<'buf>], name: &'a str) -> Self {
+        Self {
+            name,
+            headers: headers.iter(),
+            values: "".split(','),
+        }
+    }
+}
+
+impl<'a> Iterator for HeaderValues<'a, '_> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.values.next() {
+                let value = value.trim();
+
+                if !value.is_empty() {
+                    return Some(value);
+                }
+
+                continue;
+            }
+
+            let name = self.name;
+            let header = self
+                .headers
+                .find(|header| header.name.eq_ignore_ascii_case(name))?;
+
+            self.values = core::str::from_utf8(header.value).unwrap_or("").split(',');
+        }
+    }
+}
 
 pub(crate) trait HeaderExt {
     fn header(&self, name: &str) -> Option<&Header<'_>>;
@@ -27,16 +475,27 @@ impl HeaderExt for [Header<'_>] {
     }
 }
 
+/// Copies the value of the first header named `name` into `dst`, returning the number of bytes copied.
+fn copy_header(headers: &[Header<'_>], name: &str, dst: &mut [u8]) -> Option<usize> {
+    let value = headers.header_value(name)?;
+
+    dst.get_mut(..value.len())?.copy_from_slice(value);
+
+    Some(value.len())
+}
+
 #[derive(Debug)]
 pub(crate) struct OutResponse<'headers, 'buf> {
     code: &'buf str,
     status: &'buf str,
     headers: &'headers [Header<'buf>],
     additional_headers: &'headers [Header<'buf>],
+    /// Accepted extensions, answered in a single `Sec-WebSocket-Extensions` header.
+    extensions: (&'headers [SupportedExtension<'headers>], AcceptedExtensions),
 }
 
 impl<'headers, 'buf> OutResponse<'headers, 'buf> {
-    const fn new(
+    pub const fn new(
         code: &'buf str,
         status: &'buf str,
         headers: &'headers [Header<'buf>],
@@ -47,9 +506,19 @@ impl<'headers, 'buf> OutResponse<'headers, 'buf> {
             status,
             headers,
             additional_headers,
+            extensions: (&[], AcceptedExtensions::new()),
         }
     }
 
+    pub const fn with_extensions(
+        mut self,
+        supported: &'headers [SupportedExtension<'headers>],
+        accepted: AcceptedExtensions,
+    ) -> Self {
+        self.extensions = (supported, accepted);
+        self
+    }
+
     pub const fn switching_protocols(
         headers: &'headers [Header<'buf>],
         additional_headers: &'headers [Header<'buf>],
@@ -73,23 +542,32 @@ impl Encoder<OutResponse<'_, '_>> for OutResponseCodec {
     fn encode(&mut self, item: OutResponse<'_, '_>, dst: &mut [u8]) -> Result<usize, Self::Error> {
         let mut pos = 0;
 
+        validate_status_line(item.code, item.status)?;
+
         write(dst, &mut pos, b"HTTP/1.1 ")?;
         write(dst, &mut pos, item.code.as_bytes())?;
         write(dst, &mut pos, b" ")?;
         write(dst, &mut pos, item.status.as_bytes())?;
         write(dst, &mut pos, b"\r\n")?;
 
-        for header in item.headers.iter() {
-            write(dst, &mut pos, header.name.as_bytes())?;
-            write(dst, &mut pos, b": ")?;
-            write(dst, &mut pos, header.value)?;
-            write(dst, &mut pos, b"\r\n")?;
+        for header in item.headers.iter().chain(item.additional_headers) {
+            write_header(dst, &mut pos, header.name, header.value)?;
         }
 
-        for header in item.additional_headers.iter() {
-            write(dst, &mut pos, header.name.as_bytes())?;
-            write(dst, &mut pos, b": ")?;
-            write(dst, &mut pos, header.value)?;
+        let (supported, accepted) = item.extensions;
+
+        if !accepted.is_empty() {
+            write(dst, &mut pos, b"sec-websocket-extensions: ")?;
+
+            let start = pos;
+
+            pos += accepted
+                .encode(supported, dst.get_mut(pos..).unwrap_or_default())
+                .ok_or(HttpEncodeError::BufferTooSmall)?;
+
+            // The responses of the supported extensions are not checked when configured.
+            validate_header("sec-websocket-extensions", &dst[start..pos])?;
+
             write(dst, &mut pos, b"\r\n")?;
         }
 
@@ -111,7 +589,11 @@ pub struct Response<'buf, const N: usize> {
     /// Contains an empty string if the reason-phrase was missing or contained invalid characters.
     pub reason: &'buf str,
     /// The response headers.
+    ///
+    /// Slots after the parsed headers are left empty. Use [`Response::headers`] to get only the parsed headers.
     pub headers: [Header<'buf>; N],
+    /// The number of parsed headers.
+    len: usize,
 }
 
 impl<'buf, const N: usize> Response<'buf, N> {
@@ -121,12 +603,26 @@ impl<'buf, const N: usize> Response<'buf, N> {
         code: u16,
         reason: &'buf str,
         headers: [Header<'buf>; N],
+    ) -> Self {
+        let len = populated(&headers);
+
+        Self::parsed(version, code, reason, headers, len)
+    }
+
+    /// Creates a new [`Response`] with the first `len` headers populated.
+    const fn parsed(
+        version: u8,
+        code: u16,
+        reason: &'buf str,
+        headers: [Header<'buf>; N],
+        len: usize,
     ) -> Self {
         Response {
             version,
             code,
             reason,
             headers,
+            len,
         }
     }
 
@@ -145,25 +641,49 @@ impl<'buf, const N: usize> Response<'buf, N> {
         self.reason
     }
 
-    /// Returns the headers.
+    /// Returns the parsed headers, excluding empty slots.
     pub const fn headers(&self) -> &[Header<'buf>] {
-        &self.headers
+        self.headers.split_at(self.len).0
+    }
+
+    /// Returns the comma-separated values of all header lines named `name` (case-insensitive).
+    ///
+    /// E.g. `Sec-WebSocket-Protocol` values offered in multiple header lines.
+    pub fn header_values<'a>(&'a self, name: &'a str) -> HeaderValues<'a, 'buf> {
+        HeaderValues::new(self.headers(), name)
+    }
+
+    /// Returns the extensions of all `Sec-WebSocket-Extensions` header lines.
+    pub fn extensions(&self) -> Extensions<'_, 'buf> {
+        Extensions::new(self.header_values("sec-websocket-extensions"))
+    }
+
+    /// Copies the value of the header `name` (case-insensitive) into `dst`, returning the number of bytes copied.
+    ///
+    /// Lets handshake callbacks keep values, e.g. a device id, beyond the lifetime of the response.
+    /// Returns `None` if the header is missing or its value does not fit into `dst`, values are never truncated.
+    pub fn copy_header(&self, name: &str, dst: &mut [u8]) -> Option<usize> {
+        copy_header(self.headers(), name, dst)
     }
 }
 
+#[cfg(feature = "client")]
 #[derive(Debug)]
 pub(crate) struct InResponseCodec<const N: usize> {}
 
+#[cfg(feature = "client")]
 impl<const N: usize> InResponseCodec<N> {
     pub const fn new() -> Self {
         InResponseCodec {}
     }
 }
 
+#[cfg(feature = "client")]
 impl<const N: usize> framez::decode::DecodeError for InResponseCodec<N> {
     type Error = HttpDecodeError;
 }
 
+#[cfg(feature = "client")]
 impl<'buf, const N: usize> Decoder<'buf> for InResponseCodec<N> {
     type Item = Response<'buf, N>;
 
@@ -172,21 +692,95 @@ impl<'buf, const N: usize> Decoder<'buf> for InResponseCodec<N> {
         let mut response = httparse::Response::new(&mut headers);
 
         match response.parse(src)? {
-            Status::Complete(len) => Ok(Some((
-                Response::new(
-                    response.version.expect("must be some"),
-                    response.code.expect("must be some"),
-                    response.reason.expect("must be some"),
-                    headers,
-                ),
-                len,
-            ))),
+            Status::Complete(len) => {
+                let count = response.headers.len();
+
+                Ok(Some((
+                    Response::parsed(
+                        response.version.expect("must be some"),
+                        response.code.expect("must be some"),
+                        response.reason.expect("must be some"),
+                        headers,
+                        count,
+                    ),
+                    len,
+                )))
+            }
             Status::Partial => Ok(None),
         }
     }
 }
 
+/// A callback computing headers when the handshake request is encoded.
+///
+/// Receives the request path and a [`HeaderWriter`] to write the headers with.
+pub type DynamicHeaders<'a> =
+    &'a dyn Fn(&str, &mut HeaderWriter<'_>) -> Result<(), HttpEncodeError>;
+
+/// Writes headers directly into the write buffer while a handshake request is being encoded.
+///
+/// See [`ConnectOptions::with_dynamic_headers`](crate::options::ConnectOptions::with_dynamic_headers).
 #[derive(Debug)]
+pub struct HeaderWriter<'a> {
+    dst: &'a mut [u8],
+    pos: &'a mut usize,
+}
+
+impl<'a> HeaderWriter<'a> {
+    #[cfg(feature = "client")]
+    const fn new(dst: &'a mut [u8], pos: &'a mut usize) -> Self {
+        Self { dst, pos }
+    }
+
+    /// Writes a header.
+    pub fn header(&mut self, name: &str, value: &[u8]) -> Result<(), HttpEncodeError> {
+        self.header_with(name, |dst| {
+            if let Some(dst) = dst.get_mut(..value.len()) {
+                dst.copy_from_slice(value);
+            }
+
+            value.len()
+        })
+    }
+
+    /// Writes a header whose value is written by `value` into the remaining write buffer.
+    ///
+    /// `value` must return the number of bytes written.
+    /// Returning more than the length of the provided buffer fails with [`HttpEncodeError::BufferTooSmall`].
+    ///
+    /// The name and the written value are checked with [`validate_header`].
+    pub fn header_with<F>(&mut self, name: &str, value: F) -> Result<(), HttpEncodeError>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        if ConnectOptions::RESERVED_HEADERS
+            .iter()
+            .any(|reserved| name.eq_ignore_ascii_case(reserved))
+        {
+            return Err(HttpEncodeError::ReservedHeader);
+        }
+
+        validate_header(name, &[])?;
+
+        write(self.dst, self.pos, name.as_bytes())?;
+        write(self.dst, self.pos, b": ")?;
+
+        let available = self.dst.len().saturating_sub(*self.pos + 2);
+        let written = value(&mut self.dst[*self.pos..*self.pos + available]);
+
+        if written > available {
+            return Err(HttpEncodeError::BufferTooSmall);
+        }
+
+        validate_header(name, &self.dst[*self.pos..*self.pos + written])?;
+
+        *self.pos += written;
+
+        write(self.dst, self.pos, b"\r\n")
+    }
+}
+
+#[cfg(feature = "client")]
 pub(crate) struct OutRequest<'headers, 'buf> {
     /// XXX: Must be valid
     method: &'buf str,
@@ -194,8 +788,26 @@ pub(crate) struct OutRequest<'headers, 'buf> {
     path: &'buf str,
     headers: &'headers [Header<'buf>],
     additional_headers: &'headers [Header<'buf>],
+    dynamic_headers: Option<DynamicHeaders<'headers>>,
+    /// Offered subprotocols, each one sent in its own `Sec-WebSocket-Protocol` header line.
+    protocols: &'headers [&'headers str],
 }
 
+#[cfg(feature = "client")]
+impl core::fmt::Debug for OutRequest<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OutRequest")
+            .field("method", &self.method)
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("additional_headers", &self.additional_headers)
+            .field("dynamic_headers", &self.dynamic_headers.is_some())
+            .field("protocols", &self.protocols)
+            .finish()
+    }
+}
+
+#[cfg(feature = "client")]
 impl<'headers, 'buf> OutRequest<'headers, 'buf> {
     /// See [`OutRequest`] docs.
     const fn new_unchecked(
@@ -203,12 +815,16 @@ impl<'headers, 'buf> OutRequest<'headers, 'buf> {
         path: &'buf str,
         headers: &'headers [Header<'buf>],
         additional_headers: &'headers [Header<'buf>],
+        dynamic_headers: Option<DynamicHeaders<'headers>>,
+        protocols: &'headers [&'headers str],
     ) -> Self {
         OutRequest {
             method,
             path,
             headers,
             additional_headers,
+            dynamic_headers,
+            protocols,
         }
     }
 
@@ -217,20 +833,32 @@ impl<'headers, 'buf> OutRequest<'headers, 'buf> {
         path: &'buf str,
         headers: &'headers [Header<'buf>],
         additional_headers: &'headers [Header<'buf>],
+        dynamic_headers: Option<DynamicHeaders<'headers>>,
+        protocols: &'headers [&'headers str],
     ) -> Self {
-        Self::new_unchecked("GET", path, headers, additional_headers)
+        Self::new_unchecked(
+            "GET",
+            path,
+            headers,
+            additional_headers,
+            dynamic_headers,
+            protocols,
+        )
     }
 }
 
+#[cfg(feature = "client")]
 #[derive(Debug)]
 pub(crate) struct OutRequestCodec {}
 
+#[cfg(feature = "client")]
 impl OutRequestCodec {
     pub const fn new() -> Self {
         OutRequestCodec {}
     }
 }
 
+#[cfg(feature = "client")]
 impl Encoder<OutRequest<'_, '_>> for OutRequestCodec {
     type Error = HttpEncodeError;
 
@@ -242,18 +870,16 @@ impl Encoder<OutRequest<'_, '_>> for OutRequestCodec {
         write(dst, &mut pos, item.path.as_bytes())?;
         write(dst, &mut pos, b" HTTP/1.1\r\n")?;
 
-        for header in item.headers.iter() {
-            write(dst, &mut pos, header.name.as_bytes())?;
-            write(dst, &mut pos, b": ")?;
-            write(dst, &mut pos, header.value)?;
-            write(dst, &mut pos, b"\r\n")?;
+        for header in item.headers.iter().chain(item.additional_headers) {
+            write_header(dst, &mut pos, header.name, header.value)?;
         }
 
-        for header in item.additional_headers.iter() {
-            write(dst, &mut pos, header.name.as_bytes())?;
-            write(dst, &mut pos, b": ")?;
-            write(dst, &mut pos, header.value)?;
-            write(dst, &mut pos, b"\r\n")?;
+        for protocol in item.protocols.iter() {
+            write_header(dst, &mut pos, "sec-websocket-protocol", protocol.as_bytes())?;
+        }
+
+        if let Some(dynamic_headers) = item.dynamic_headers {
+            dynamic_headers(item.path, &mut HeaderWriter::new(dst, &mut pos))?;
         }
 
         write(dst, &mut pos, b"\r\n")?;
@@ -262,6 +888,347 @@ impl Encoder<OutRequest<'_, '_>> for OutRequestCodec {
     }
 }
 
+/// A parsed `Origin` header, e.g. `https://example.com:8443`.
+///
+/// Scheme and host are borrowed from the header value as sent, they should be compared case-insensitively.
+///
+/// # Example
+///
+/// Allowing every subdomain of `example.com` in an [`accept`](crate::WebSocket::accept) callback:
+///
+/// ```
+/// use websocketz::http::Origin;
+///
+/// fn allowed(origin: &Origin<'_>) -> bool {
+///     let host = origin.host();
+///
+///     origin.scheme().eq_ignore_ascii_case("https")
+///         && (host.eq_ignore_ascii_case("example.com")
+///             || host
+///                 .len()
+///                 .checked_sub(".example.com".len())
+///                 .and_then(|start| host.get(start..))
+///                 .is_some_and(|suffix| suffix.eq_ignore_ascii_case(".example.com")))
+/// }
+///
+/// assert!(allowed(&Origin::parse(b"https://chat.example.com").unwrap()));
+/// assert!(!allowed(&Origin::parse(b"https://example.com.evil.net").unwrap()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<u16>,
+}
+
+impl<'a> Origin<'a> {
+    /// Parses the value of an `Origin` header.
+    ///
+    /// Returns `None` for the opaque origin `null` and for values that are not of the form `scheme://host[:port]`.
+    /// Brackets around IPv6 hosts are removed.
+    pub fn parse(value: &'a [u8]) -> Option<Self> {
+        let value = core::str::from_utf8(value).ok()?.trim();
+
+        let (scheme, authority) = value.split_once("://")?;
+
+        let scheme_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'-' | b'.'));
+
+        if !scheme_valid || authority.contains(['/', '?', '#', '@']) {
+            return None;
+        }
+
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(ipv6) => {
+                let (host, rest) = ipv6.split_once(']')?;
+
+                match rest {
+                    "" => (host, None),
+                    rest => (host, Some(rest.strip_prefix(':')?)),
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+
+        if host.is_empty() || host.contains(|c: char| !c.is_ascii_graphic()) {
+            return None;
+        }
+
+        let port = match port {
+            Some(port) if port.bytes().all(|byte| byte.is_ascii_digit()) => {
+                Some(port.parse().ok()?)
+            }
+            Some(_) => return None,
+            None => None,
+        };
+
+        Some(Self { scheme, host, port })
+    }
+
+    /// Returns the scheme, e.g. `https`.
+    pub const fn scheme(&self) -> &'a str {
+        self.scheme
+    }
+
+    /// Returns the host, e.g. `example.com` or `::1`.
+    pub const fn host(&self) -> &'a str {
+        self.host
+    }
+
+    /// Returns the explicit port, if any.
+    pub const fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Returns the explicit port, or the default port of the `http`, `https`, `ws` and `wss` schemes.
+    pub fn port_or_default(&self) -> Option<u16> {
+        let is = |scheme: &str| self.scheme.eq_ignore_ascii_case(scheme);
+
+        match self.port {
+            Some(port) => Some(port),
+            None if is("http") || is("ws") => Some(80),
+            None if is("https") || is("wss") => Some(443),
+            None => None,
+        }
+    }
+}
+
+/// The path and the query of a request target, e.g. `/ws?room=abc&token=xyz`.
+///
+/// Both are borrowed from the request target as sent, without percent-decoding, see [`percent_decode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathAndQuery<'a> {
+    path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> PathAndQuery<'a> {
+    /// Parses a request target in origin-form, e.g. `/ws?room=abc`.
+    ///
+    /// Returns `None` if the target does not start with `/` or contains bytes that are not visible ASCII characters.
+    /// A trailing fragment is ignored.
+    pub fn parse(target: &'a str) -> Option<Self> {
+        if !target.starts_with('/') {
+            return None;
+        }
+
+        Self::split(target)
+    }
+
+    /// Splits a path that is empty or starts with `/` from its query.
+    fn split(target: &'a str) -> Option<Self> {
+        if target.bytes().any(|byte| !byte.is_ascii_graphic()) {
+            return None;
+        }
+
+        let target = match target.split_once('#') {
+            Some((target, _)) => target,
+            None => target,
+        };
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+
+        Some(Self { path, query })
+    }
+
+    /// Returns the path, e.g. `/ws`.
+    pub const fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// Returns the query without the leading `?`, e.g. `room=abc&token=xyz`.
+    pub const fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// Returns the `name=value` pairs of the query.
+    pub fn query_params(&self) -> QueryParams<'a> {
+        QueryParams::new(self.query.unwrap_or_default())
+    }
+
+    /// Returns the value of the first query parameter named `name`, e.g. `abc` for `room`.
+    pub fn query_param(&self, name: &str) -> Option<&'a str> {
+        self.query_params()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// A parsed request target in origin-form, e.g. `/ws?room=abc`, or in absolute-form, e.g. `ws://example.com/ws?room=abc`.
+///
+/// # Example
+///
+/// Routing in an [`accept`](crate::WebSocket::accept) callback:
+///
+/// ```
+/// use websocketz::http::{Request, percent_decode};
+///
+/// let request = Request::<0>::new("GET", "/rooms/lobby?token=a%2Bb&debug", 1, []);
+///
+/// let uri = request.uri().expect("Valid request target");
+///
+/// let room = uri.path().strip_prefix("/rooms/");
+/// let token = uri
+///     .query_param("token")
+///     .and_then(|token| percent_decode(token, &mut [0u8; 32]).map(|token| token == b"a+b"));
+///
+/// assert_eq!(room, Some("lobby"));
+/// assert_eq!(token, Some(true));
+/// assert!(request.query_params().any(|(name, _)| name == "debug"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uri<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path_and_query: PathAndQuery<'a>,
+}
+
+impl<'a> Uri<'a> {
+    /// Parses a request target in origin-form or in absolute-form.
+    ///
+    /// Returns `None` for other forms, e.g. `*`, and for targets containing bytes that are not visible ASCII characters.
+    /// The path of a target in absolute-form without a path is `/`. A trailing fragment is ignored.
+    pub fn parse(target: &'a str) -> Option<Self> {
+        if target.starts_with('/') {
+            return Some(Self {
+                scheme: None,
+                authority: None,
+                path_and_query: PathAndQuery::split(target)?,
+            });
+        }
+
+        let (scheme, rest) = target.split_once("://")?;
+
+        let scheme_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'-' | b'.'));
+
+        if !scheme_valid {
+            return None;
+        }
+
+        let index = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let (authority, rest) = rest.split_at(index);
+
+        if authority.is_empty() {
+            return None;
+        }
+
+        let mut path_and_query = PathAndQuery::split(rest)?;
+
+        if path_and_query.path.is_empty() {
+            path_and_query.path = "/";
+        }
+
+        Some(Self {
+            scheme: Some(scheme),
+            authority: Some(authority),
+            path_and_query,
+        })
+    }
+
+    /// Returns the scheme of a target in absolute-form, e.g. `ws`.
+    pub const fn scheme(&self) -> Option<&'a str> {
+        self.scheme
+    }
+
+    /// Returns the authority of a target in absolute-form, e.g. `example.com:8080`.
+    pub const fn authority(&self) -> Option<&'a str> {
+        self.authority
+    }
+
+    /// Returns the path and the query.
+    pub const fn path_and_query(&self) -> PathAndQuery<'a> {
+        self.path_and_query
+    }
+
+    /// Returns the path, e.g. `/ws`.
+    pub const fn path(&self) -> &'a str {
+        self.path_and_query.path
+    }
+
+    /// Returns the query without the leading `?`, e.g. `room=abc&token=xyz`.
+    pub const fn query(&self) -> Option<&'a str> {
+        self.path_and_query.query
+    }
+
+    /// Returns the `name=value` pairs of the query.
+    pub fn query_params(&self) -> QueryParams<'a> {
+        self.path_and_query.query_params()
+    }
+
+    /// Returns the value of the first query parameter named `name`.
+    pub fn query_param(&self, name: &str) -> Option<&'a str> {
+        self.path_and_query.query_param(name)
+    }
+}
+
+/// Iterator over the `name=value` pairs of a query, separated by `&`.
+///
+/// Names and values are not percent-decoded, see [`percent_decode`]. Pairs without `=` have an empty value,
+/// empty pairs are skipped.
+///
+/// See [`Request::query_params`] and [`Uri::query_params`].
+#[derive(Debug, Clone)]
+pub struct QueryParams<'a> {
+    pairs: core::str::Split<'a, char>,
+}
+
+impl<'a> QueryParams<'a> {
+    fn new(query: &'a str) -> Self {
+        Self {
+            pairs: query.split('&'),
+        }
+    }
+}
+
+impl<'a> Iterator for QueryParams<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pairs
+            .by_ref()
+            .find(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+    }
+}
+
+/// Decodes a percent-encoded query name or value into `dst`, e.g. `a%2Bb+c` into `a+b c`.
+///
+/// `+` is decoded as a space. Returns the decoded bytes, or `None` if `value` contains an invalid escape
+/// or the decoded bytes do not fit into `dst`.
+pub fn percent_decode<'d>(value: &str, dst: &'d mut [u8]) -> Option<&'d [u8]> {
+    let mut bytes = value.bytes();
+    let mut len = 0;
+
+    while let Some(byte) = bytes.next() {
+        let byte = match byte {
+            b'+' => b' ',
+            b'%' => {
+                let high = char::from(bytes.next()?).to_digit(16)?;
+                let low = char::from(bytes.next()?).to_digit(16)?;
+
+                (high * 16 + low) as u8
+            }
+            byte => byte,
+        };
+
+        *dst.get_mut(len)? = byte;
+        len += 1;
+    }
+
+    Some(&dst[..len])
+}
+
 /// An HTTP request.
 #[derive(Debug)]
 pub struct Request<'buf, const N: usize> {
@@ -272,7 +1239,11 @@ pub struct Request<'buf, const N: usize> {
     /// The request minor version, such as `1` for `HTTP/1.1`.
     pub version: u8,
     /// The request headers.
+    ///
+    /// Slots after the parsed headers are left empty. Use [`Request::headers`] to get only the parsed headers.
     pub headers: [Header<'buf>; N],
+    /// The number of parsed headers.
+    len: usize,
 }
 
 impl<'buf, const N: usize> Request<'buf, N> {
@@ -282,12 +1253,26 @@ impl<'buf, const N: usize> Request<'buf, N> {
         path: &'buf str,
         version: u8,
         headers: [Header<'buf>; N],
+    ) -> Self {
+        let len = populated(&headers);
+
+        Self::parsed(method, path, version, headers, len)
+    }
+
+    /// Creates a new [`Request`] with the first `len` headers populated.
+    const fn parsed(
+        method: &'buf str,
+        path: &'buf str,
+        version: u8,
+        headers: [Header<'buf>; N],
+        len: usize,
     ) -> Self {
         Request {
             method,
             path,
             version,
             headers,
+            len,
         }
     }
 
@@ -301,14 +1286,59 @@ impl<'buf, const N: usize> Request<'buf, N> {
         self.path
     }
 
-    /// Returns the HTTP version.
-    pub const fn version(&self) -> u8 {
-        self.version
+    /// Returns the HTTP version.
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the parsed headers, excluding empty slots.
+    pub const fn headers(&self) -> &[Header<'buf>] {
+        self.headers.split_at(self.len).0
+    }
+
+    /// Returns the comma-separated values of all header lines named `name` (case-insensitive).
+    ///
+    /// E.g. `Sec-WebSocket-Protocol` values offered in multiple header lines.
+    pub fn header_values<'a>(&'a self, name: &'a str) -> HeaderValues<'a, 'buf> {
+        HeaderValues::new(self.headers(), name)
+    }
+
+    /// Returns the extensions of all `Sec-WebSocket-Extensions` header lines.
+    pub fn extensions(&self) -> Extensions<'_, 'buf> {
+        Extensions::new(self.header_values("sec-websocket-extensions"))
+    }
+
+    /// Returns the parsed request target, see [`Uri::parse`].
+    pub fn uri(&self) -> Option<Uri<'buf>> {
+        Uri::parse(self.path)
+    }
+
+    /// Returns the `name=value` pairs of the query of the request target.
+    ///
+    /// Empty if the target has no query or is invalid.
+    pub fn query_params(&self) -> QueryParams<'buf> {
+        match self.uri() {
+            Some(uri) => uri.query_params(),
+            None => QueryParams::new(""),
+        }
+    }
+
+    /// Returns the parsed `Origin` header.
+    ///
+    /// `None` if the header is missing, `null` or invalid, see [`Origin::parse`].
+    pub fn origin(&self) -> Option<Origin<'buf>> {
+        self.headers()
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("origin"))
+            .and_then(|header| Origin::parse(header.value))
     }
 
-    /// Returns the headers.
-    pub const fn headers(&self) -> &[Header<'buf>] {
-        &self.headers
+    /// Copies the value of the header `name` (case-insensitive) into `dst`, returning the number of bytes copied.
+    ///
+    /// Lets handshake callbacks keep values, e.g. a device id, beyond the lifetime of the request.
+    /// Returns `None` if the header is missing or its value does not fit into `dst`, values are never truncated.
+    pub fn copy_header(&self, name: &str, dst: &mut [u8]) -> Option<usize> {
+        copy_header(self.headers(), name, dst)
     }
 }
 
@@ -333,20 +1363,67 @@ impl<'buf, const N: usize> Decoder<'buf> for InRequestCodec<N> {
         let mut request = httparse::Request::new(&mut headers);
 
         match request.parse(src)? {
-            Status::Complete(len) => Ok(Some((
-                Request::new(
-                    request.method.expect("must be some"),
-                    request.path.expect("must be some"),
-                    request.version.expect("must be some"),
-                    headers,
-                ),
-                len,
-            ))),
+            Status::Complete(len) => {
+                let count = request.headers.len();
+
+                Ok(Some((
+                    Request::parsed(
+                        request.method.expect("must be some"),
+                        request.path.expect("must be some"),
+                        request.version.expect("must be some"),
+                        headers,
+                        count,
+                    ),
+                    len,
+                )))
+            }
             Status::Partial => Ok(None),
         }
     }
 }
 
+/// Returns the number of headers before the first empty slot.
+const fn populated(headers: &[Header<'_>]) -> usize {
+    let mut len = 0;
+
+    while len < headers.len() && !headers[len].name.is_empty() {
+        len += 1;
+    }
+
+    len
+}
+
+/// Checks that the status code and reason-phrase of a response can not inject additional lines.
+fn validate_status_line(code: &str, reason: &str) -> Result<(), HttpEncodeError> {
+    if code.len() != 3 || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(HttpEncodeError::InvalidStatusLine);
+    }
+
+    if reason
+        .bytes()
+        .any(|byte| byte.is_ascii_control() && byte != b'\t')
+    {
+        return Err(HttpEncodeError::InvalidStatusLine);
+    }
+
+    Ok(())
+}
+
+/// Writes a `name: value` header line, see [`validate_header`].
+fn write_header(
+    dst: &mut [u8],
+    pos: &mut usize,
+    name: &str,
+    value: &[u8],
+) -> Result<(), HttpEncodeError> {
+    validate_header(name, value)?;
+
+    write(dst, pos, name.as_bytes())?;
+    write(dst, pos, b": ")?;
+    write(dst, pos, value)?;
+    write(dst, pos, b"\r\n")
+}
+
 fn write(dst: &mut [u8], pos: &mut usize, data: &[u8]) -> Result<(), HttpEncodeError> {
     if *pos + data.len() > dst.len() {
         return Err(HttpEncodeError::BufferTooSmall);
@@ -368,6 +1445,7 @@ mod tests {
 
         use super::*;
 
+        #[cfg(feature = "client")]
         mod response {
             use super::*;
 
@@ -403,6 +1481,18 @@ mod tests {
                 assert_eq!(len, 64);
             }
 
+            #[test]
+            fn empty_slots_are_excluded() {
+                let mut response = ok_response();
+                let mut codec = InResponseCodec::<8>::new();
+
+                let (response, _) = codec.decode(&mut response).unwrap().unwrap();
+
+                assert_eq!(response.headers().len(), 2);
+                assert!(response.headers().iter().all(|h| !h.name.is_empty()));
+                assert_eq!(response.headers.len(), 8);
+            }
+
             #[test]
             fn too_many_headers() {
                 let mut response = ok_response();
@@ -465,6 +1555,21 @@ mod tests {
                 assert_eq!(request.headers().len(), 3);
             }
 
+            #[test]
+            fn empty_slots_are_excluded() {
+                let mut request = ok_request();
+                let mut codec = InRequestCodec::<8>::new();
+
+                let (request, _) = codec.decode(&mut request).unwrap().unwrap();
+
+                assert_eq!(request.headers().len(), 3);
+                assert_eq!(request.headers().header_value_str(""), None);
+
+                let request = Request::new("GET", "/", 1, request.headers);
+
+                assert_eq!(request.headers().len(), 3);
+            }
+
             #[test]
             fn too_many_headers() {
                 let mut request = ok_request();
@@ -490,9 +1595,393 @@ mod tests {
         }
     }
 
+    mod copy_header {
+        use super::*;
+
+        const HEADERS: [Header<'static>; 2] = [
+            Header {
+                name: "X-Device-Id",
+                value: b"device-42",
+            },
+            Header {
+                name: "",
+                value: b"",
+            },
+        ];
+
+        #[test]
+        fn copies_value() {
+            let request = Request::new("GET", "/", 1, HEADERS);
+            let mut dst = [0; 16];
+
+            let len = request.copy_header("x-device-id", &mut dst);
+
+            assert_eq!(len, Some(9));
+            assert_eq!(&dst[..9], b"device-42");
+
+            let response = Response::new(1, 101, "Switching Protocols", HEADERS);
+            let mut dst = [0; 9];
+
+            assert_eq!(response.copy_header("X-DEVICE-ID", &mut dst), Some(9));
+            assert_eq!(&dst, b"device-42");
+        }
+
+        #[test]
+        fn missing_or_too_large() {
+            let request = Request::new("GET", "/", 1, HEADERS);
+
+            assert_eq!(request.copy_header("authorization", &mut [0; 16]), None);
+            assert_eq!(request.copy_header("x-device-id", &mut [0; 8]), None);
+            assert_eq!(request.copy_header("", &mut [0; 8]), None);
+        }
+    }
+
+    mod header_values {
+        use super::*;
+
+        const HEADERS: [Header<'static>; 4] = [
+            Header {
+                name: "Sec-WebSocket-Protocol",
+                value: b"chat, , superchat",
+            },
+            Header {
+                name: "Host",
+                value: b"example.com",
+            },
+            Header {
+                name: "sec-websocket-protocol",
+                value: b" v2.json ",
+            },
+            Header {
+                name: "Sec-WebSocket-Protocol",
+                value: b"\xFF",
+            },
+        ];
+
+        #[test]
+        fn across_lines() {
+            let request = Request::new("GET", "/", 1, HEADERS);
+
+            assert!(request.header_values("sec-websocket-protocol").eq([
+                "chat",
+                "superchat",
+                "v2.json"
+            ]));
+
+            let response = Response::new(1, 101, "Switching Protocols", HEADERS);
+
+            assert!(response.header_values("HOST").eq(["example.com"]));
+        }
+
+        #[test]
+        fn missing() {
+            let request = Request::new("GET", "/", 1, HEADERS);
+
+            assert_eq!(request.header_values("connection").next(), None);
+        }
+    }
+
+    mod upgrade {
+        use super::*;
+
+        fn request<const N: usize>(headers: [Header<'static>; N]) -> Request<'static, N> {
+            Request::new("GET", "/", 1, headers)
+        }
+
+        #[test]
+        fn is_upgrade() {
+            let upgrade = request([
+                Header {
+                    name: "Upgrade",
+                    value: b"WebSocket",
+                },
+                Header {
+                    name: "Connection",
+                    value: b"keep-alive, Upgrade",
+                },
+            ]);
+
+            assert!(is_upgrade_request(&upgrade));
+
+            let post = Request::new("POST", "/", 1, upgrade.headers);
+
+            assert!(!is_upgrade_request(&post));
+        }
+
+        #[test]
+        fn is_not_upgrade() {
+            let plain = request([Header {
+                name: "Connection",
+                value: b"keep-alive",
+            }]);
+
+            assert!(!is_upgrade_request(&plain));
+
+            let other_protocol = request([
+                Header {
+                    name: "Upgrade",
+                    value: b"h2c",
+                },
+                Header {
+                    name: "Connection",
+                    value: b"Upgrade",
+                },
+            ]);
+
+            assert!(!is_upgrade_request(&other_protocol));
+        }
+    }
+
+    mod sec_version {
+        use std::string::ToString;
+
+        use super::*;
+
+        #[test]
+        fn truncated() {
+            let version = SecVersion::new(b"13, 8");
+
+            assert!(!version.is_truncated());
+            assert!(version.versions().eq([13, 8]));
+            assert_eq!(version.to_string(), "13, 8");
+
+            let long = [b'1'; SecVersion::CAPACITY + 1];
+            let version = SecVersion::new(&long);
+
+            assert!(version.is_truncated());
+            assert_eq!(version.as_bytes(), &long[..SecVersion::CAPACITY]);
+            assert!(version.to_string().ends_with("..."));
+            assert_eq!(version.versions().count(), 0);
+        }
+    }
+
+    mod origin {
+        use super::*;
+
+        #[test]
+        fn parse() {
+            let origin = Origin::parse(b" https://example.com:8443 ").expect("Valid origin");
+
+            assert_eq!(origin.scheme(), "https");
+            assert_eq!(origin.host(), "example.com");
+            assert_eq!(origin.port(), Some(8443));
+
+            let origin = Origin::parse(b"HTTP://localhost").expect("Valid origin");
+
+            assert_eq!(origin.host(), "localhost");
+            assert_eq!(origin.port(), None);
+            assert_eq!(origin.port_or_default(), Some(80));
+
+            let origin = Origin::parse(b"wss://[::1]:9001").expect("Valid origin");
+
+            assert_eq!(origin.host(), "::1");
+            assert_eq!(origin.port(), Some(9001));
+            assert_eq!(
+                Origin::parse(b"app://[::1]").unwrap().port_or_default(),
+                None
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            for value in [
+                &b"null"[..],
+                b"example.com",
+                b"https://",
+                b"https://example.com/path",
+                b"https://user@example.com",
+                b"https://example.com:",
+                b"https://example.com:65536",
+                b"https://example.com:+80",
+                b"https://[::1",
+                b"https://[::1]8080",
+                b"1http://example.com",
+                b"https://exa mple.com",
+                b"https://\xff",
+            ] {
+                assert_eq!(Origin::parse(value), None);
+            }
+        }
+
+        #[test]
+        fn request() {
+            let request = Request::new(
+                "GET",
+                "/",
+                1,
+                [Header {
+                    name: "Origin",
+                    value: b"https://example.com",
+                }],
+            );
+
+            assert_eq!(
+                request.origin(),
+                Some(Origin {
+                    scheme: "https",
+                    host: "example.com",
+                    port: None,
+                })
+            );
+
+            let request = Request::new(
+                "GET",
+                "/",
+                1,
+                [Header {
+                    name: "Origin",
+                    value: b"null",
+                }],
+            );
+
+            assert_eq!(request.origin(), None);
+        }
+    }
+
+    mod uri {
+        use super::*;
+
+        #[test]
+        fn origin_form() {
+            let uri = Uri::parse("/ws?room=abc&token=xyz#top").expect("Valid target");
+
+            assert_eq!(uri.scheme(), None);
+            assert_eq!(uri.authority(), None);
+            assert_eq!(uri.path(), "/ws");
+            assert_eq!(uri.query(), Some("room=abc&token=xyz"));
+            assert!(uri.query_params().eq([("room", "abc"), ("token", "xyz")]));
+            assert_eq!(uri.query_param("token"), Some("xyz"));
+            assert_eq!(uri.query_param("missing"), None);
+
+            let uri = Uri::parse("/").expect("Valid target");
+
+            assert_eq!(uri.path(), "/");
+            assert_eq!(uri.query(), None);
+            assert_eq!(uri.query_params().next(), None);
+        }
+
+        #[test]
+        fn absolute_form() {
+            let uri = Uri::parse("ws://example.com:8080/chat?room=1").expect("Valid target");
+
+            assert_eq!(uri.scheme(), Some("ws"));
+            assert_eq!(uri.authority(), Some("example.com:8080"));
+            assert_eq!(uri.path(), "/chat");
+            assert_eq!(uri.query(), Some("room=1"));
+
+            let uri = Uri::parse("wss://example.com?room=1").expect("Valid target");
+
+            assert_eq!(uri.path(), "/");
+            assert_eq!(uri.query_param("room"), Some("1"));
+        }
+
+        #[test]
+        fn invalid() {
+            for target in [
+                "",
+                "*",
+                "ws",
+                "example.com/ws",
+                "ws:///ws",
+                "1ws://example.com/",
+                "/w s",
+                "/ws?room=\u{e9}",
+            ] {
+                assert_eq!(Uri::parse(target), None);
+            }
+
+            assert_eq!(PathAndQuery::parse("ws://example.com/"), None);
+        }
+
+        #[test]
+        fn query_params() {
+            let params = QueryParams::new("a=1&&b&c=&=d&e=f=g");
+
+            assert!(params.eq([("a", "1"), ("b", ""), ("c", ""), ("", "d"), ("e", "f=g")]));
+        }
+
+        #[test]
+        fn request() {
+            let request = Request::<0>::new("GET", "/ws?room=abc&token=xyz", 1, []);
+
+            assert_eq!(request.uri().map(|uri| uri.path()), Some("/ws"));
+            assert!(
+                request
+                    .query_params()
+                    .eq([("room", "abc"), ("token", "xyz")])
+            );
+
+            let request = Request::<0>::new("GET", "*", 1, []);
+
+            assert_eq!(request.uri(), None);
+            assert_eq!(request.query_params().next(), None);
+        }
+
+        #[test]
+        fn decode() {
+            let dst = &mut [0u8; 16];
+
+            assert_eq!(percent_decode("a%2Bb+c%2f", dst), Some(&b"a+b c/"[..]));
+            assert_eq!(percent_decode("", dst), Some(&b""[..]));
+            assert_eq!(percent_decode("%ff", dst), Some(&b"\xff"[..]));
+            assert_eq!(percent_decode("%2", dst), None);
+            assert_eq!(percent_decode("%zz", dst), None);
+            assert_eq!(percent_decode("abc", &mut [0u8; 2]), None);
+        }
+    }
+
+    mod validate_header {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            validate_header("X-Device", b"sensor-1").unwrap();
+            validate_header("X-Empty", b"").unwrap();
+            validate_header("X-Utf8", "café".as_bytes()).unwrap();
+        }
+
+        #[test]
+        fn invalid_name() {
+            for name in ["", "X Device", "X-Device:", "X-Device\r\n", "X-Dévice"] {
+                let error = validate_header(name, b"sensor").unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderName));
+            }
+        }
+
+        #[test]
+        fn invalid_value() {
+            for value in [
+                &b"sensor\r\n"[..],
+                b"sensor\nX-Admin: 1",
+                b"sensor\r",
+                b"sensor\0",
+            ] {
+                let error = validate_header("X-Device", value).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderValue));
+            }
+        }
+
+        #[test]
+        fn too_long() {
+            let name = "X".repeat(MAX_HEADER_NAME_LEN + 1);
+            let value = std::vec![b'a'; MAX_HEADER_VALUE_LEN + 1];
+
+            let error = validate_header(&name, b"sensor").unwrap_err();
+            assert!(matches!(error, HttpEncodeError::HeaderTooLong));
+
+            let error = validate_header("X-Device", &value).unwrap_err();
+            assert!(matches!(error, HttpEncodeError::HeaderTooLong));
+
+            validate_header(&name[1..], &value[1..]).unwrap();
+        }
+    }
+
     mod encode {
         use super::*;
 
+        #[cfg(feature = "client")]
         mod request {
             use super::*;
 
@@ -517,7 +2006,13 @@ mod tests {
 
             #[test]
             fn ok() {
-                let request = OutRequest::get_unchecked("/index.html", HEADERS, ADDITIONAL_HEADERS);
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    None,
+                    &[],
+                );
 
                 let mut codec = OutRequestCodec::new();
 
@@ -531,7 +2026,13 @@ mod tests {
 
             #[test]
             fn buffer_too_small() {
-                let request = OutRequest::get_unchecked("/index.html", HEADERS, ADDITIONAL_HEADERS);
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    None,
+                    &[],
+                );
 
                 let mut codec = OutRequestCodec::new();
 
@@ -541,6 +2042,153 @@ mod tests {
 
                 assert!(matches!(error, HttpEncodeError::BufferTooSmall));
             }
+
+            #[test]
+            fn dynamic_headers() {
+                let dynamic_headers = |path: &str, writer: &mut HeaderWriter<'_>| {
+                    writer.header("X-Path", path.as_bytes())?;
+                    writer.header_with("X-Nonce", |dst| {
+                        dst[..2].copy_from_slice(b"42");
+
+                        2
+                    })
+                };
+
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    Some(&dynamic_headers),
+                    &[],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let len = codec.encode(request, &mut buf).unwrap();
+
+                assert_eq!(
+                    &buf[..len],
+                    b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: test-agent\r\nAccept: text/html\r\nX-Path: /index.html\r\nX-Nonce: 42\r\n\r\n"
+                );
+            }
+
+            #[test]
+            fn dynamic_headers_buffer_too_small() {
+                let dynamic_headers = |_: &str, writer: &mut HeaderWriter<'_>| {
+                    writer.header_with("X-Nonce", |dst| dst.len() + 1)
+                };
+
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    Some(&dynamic_headers),
+                    &[],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(request, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::BufferTooSmall));
+            }
+
+            #[test]
+            fn dynamic_headers_reserved() {
+                let dynamic_headers = |_: &str, writer: &mut HeaderWriter<'_>| {
+                    writer.header("Sec-WebSocket-Key", b"key")
+                };
+
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    Some(&dynamic_headers),
+                    &[],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(request, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::ReservedHeader));
+            }
+
+            #[test]
+            fn dynamic_headers_invalid_value() {
+                let dynamic_headers = |_: &str, writer: &mut HeaderWriter<'_>| {
+                    writer.header_with("X-Device", |dst| {
+                        dst[..13].copy_from_slice(b"a\r\nX-Admin: 1");
+
+                        13
+                    })
+                };
+
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    Some(&dynamic_headers),
+                    &[],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(request, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderValue));
+            }
+
+            #[test]
+            fn invalid_header() {
+                let additional_headers = &[Header {
+                    name: "X-Device",
+                    value: b"sensor\r\nX-Admin: 1",
+                }];
+
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    additional_headers,
+                    None,
+                    &[],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(request, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderValue));
+            }
+
+            #[test]
+            fn invalid_protocol() {
+                let request = OutRequest::get_unchecked(
+                    "/index.html",
+                    HEADERS,
+                    ADDITIONAL_HEADERS,
+                    None,
+                    &["chat\r\nX-Admin: 1"],
+                );
+
+                let mut codec = OutRequestCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(request, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderValue));
+            }
         }
 
         mod response {
@@ -602,6 +2250,66 @@ mod tests {
 
                 assert!(matches!(error, HttpEncodeError::BufferTooSmall));
             }
+
+            #[test]
+            fn invalid_header() {
+                let additional_headers = &[Header {
+                    name: "X-Admin: 1\r\nX-Device",
+                    value: b"sensor",
+                }];
+
+                let response = OutResponse::new("200", "OK", HEADERS, additional_headers);
+
+                let mut codec = OutResponseCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(response, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderName));
+            }
+
+            #[test]
+            fn invalid_status_line() {
+                let mut codec = OutResponseCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                for (code, reason) in [
+                    ("200", "OK\r\nX-Admin: 1"),
+                    ("20", "OK"),
+                    ("2000", "OK"),
+                    ("20\n", "OK"),
+                ] {
+                    let response = OutResponse::new(code, reason, HEADERS, ADDITIONAL_HEADERS);
+
+                    let error = codec.encode(response, &mut buf).unwrap_err();
+
+                    assert!(matches!(error, HttpEncodeError::InvalidStatusLine));
+                }
+
+                let response = OutResponse::new("400", "Bad\tRequest", HEADERS, ADDITIONAL_HEADERS);
+
+                codec.encode(response, &mut buf).unwrap();
+            }
+
+            #[test]
+            fn invalid_extension_response() {
+                let supported =
+                    &[SupportedExtension::new("x-vendor").with_response("x-vendor\r\nX-Admin: 1")];
+                let offered = [crate::extensions::Extension::parse("x-vendor").unwrap()];
+
+                let response = OutResponse::switching_protocols(HEADERS, ADDITIONAL_HEADERS)
+                    .with_extensions(supported, AcceptedExtensions::negotiate(offered, supported));
+
+                let mut codec = OutResponseCodec::new();
+
+                let mut buf = std::vec![0; 1024];
+
+                let error = codec.encode(response, &mut buf).unwrap_err();
+
+                assert!(matches!(error, HttpEncodeError::InvalidHeaderValue));
+            }
         }
     }
 }
@@ -0,0 +1,195 @@
+use crate::{HandshakeStats, extensions::AcceptedExtensions};
+
+/// Information about an established connection.
+///
+/// See [`WebSocket::info`](crate::WebSocket::info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// The path followed by the origin.
+    buffer: [u8; ConnectionInfo::CAPACITY],
+    path_len: Option<u8>,
+    origin_len: Option<u8>,
+    protocol: Option<usize>,
+    extensions: AcceptedExtensions,
+}
+
+impl ConnectionInfo {
+    /// The number of bytes available to store the path and the origin.
+    ///
+    /// A path or an origin not fitting in the remaining space is not stored.
+    pub const CAPACITY: usize = 128;
+
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: [0; Self::CAPACITY],
+            path_len: None,
+            origin_len: None,
+            protocol: None,
+            extensions: AcceptedExtensions::new(),
+        }
+    }
+
+    /// Stores the path and the origin, replacing the previous ones.
+    pub(crate) fn set_path_and_origin(&mut self, path: &str, origin: Option<&str>) {
+        self.path_len = Self::store(&mut self.buffer, path);
+
+        let offset = self.path_len.map_or(0, usize::from);
+
+        self.origin_len = origin.and_then(|origin| Self::store(&mut self.buffer[offset..], origin));
+    }
+
+    fn store(dst: &mut [u8], value: &str) -> Option<u8> {
+        let len = u8::try_from(value.len()).ok()?;
+
+        dst.get_mut(..value.len())?
+            .copy_from_slice(value.as_bytes());
+
+        Some(len)
+    }
+
+    pub(crate) const fn set_protocol(&mut self, protocol: Option<usize>) {
+        self.protocol = protocol;
+    }
+
+    pub(crate) const fn set_extensions(&mut self, extensions: AcceptedExtensions) {
+        self.extensions = extensions;
+    }
+
+    /// Returns the path of the handshake request.
+    ///
+    /// `None` if it did not fit in [`ConnectionInfo::CAPACITY`] bytes or no handshake was performed.
+    pub fn path(&self) -> Option<&str> {
+        let len = usize::from(self.path_len?);
+
+        core::str::from_utf8(&self.buffer[..len]).ok()
+    }
+
+    /// Returns the `Origin` header declared by the client.
+    ///
+    /// `None` for clients, if the client declared no valid UTF-8 origin, or if it did not fit in the space left by the path.
+    pub fn origin(&self) -> Option<&str> {
+        let offset = self.path_len.map_or(0, usize::from);
+        let len = usize::from(self.origin_len?);
+
+        core::str::from_utf8(&self.buffer[offset..offset + len]).ok()
+    }
+
+    /// Returns the index of the negotiated subprotocol.
+    ///
+    /// See [`WebSocket::protocol`](crate::WebSocket::protocol).
+    pub const fn protocol(&self) -> Option<usize> {
+        self.protocol
+    }
+
+    /// Returns the accepted extensions.
+    ///
+    /// See [`WebSocket::extensions`](crate::WebSocket::extensions).
+    pub const fn extensions(&self) -> AcceptedExtensions {
+        self.extensions
+    }
+}
+
+impl Default for ConnectionInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The outcome of a completed handshake.
+///
+/// Returned alongside the connection by [`WebSocket::connect_with`](crate::WebSocket::connect_with)
+/// and [`WebSocket::accept_with`](crate::WebSocket::accept_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeResult<'a> {
+    protocols: &'a [&'a str],
+    protocol: Option<usize>,
+    stats: HandshakeStats,
+}
+
+impl<'a> HandshakeResult<'a> {
+    pub(crate) const fn new(
+        protocols: &'a [&'a str],
+        protocol: Option<usize>,
+        stats: HandshakeStats,
+    ) -> Self {
+        Self {
+            protocols,
+            protocol,
+            stats,
+        }
+    }
+
+    /// Returns the negotiated subprotocol.
+    ///
+    /// `None` if no subprotocol was negotiated.
+    pub fn protocol(&self) -> Option<&'a str> {
+        self.protocols.get(self.protocol?).copied()
+    }
+
+    /// Returns the index of the negotiated subprotocol in the protocols of the handshake options.
+    ///
+    /// See [`WebSocket::protocol`](crate::WebSocket::protocol).
+    pub const fn protocol_index(&self) -> Option<usize> {
+        self.protocol
+    }
+
+    /// Returns the statistics of the handshake.
+    ///
+    /// See [`WebSocket::handshake_stats`](crate::WebSocket::handshake_stats).
+    pub const fn stats(&self) -> HandshakeStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_and_origin() {
+        let mut info = ConnectionInfo::new();
+
+        assert_eq!(info.path(), None);
+        assert_eq!(info.origin(), None);
+
+        info.set_path_and_origin("/ws", Some("https://example.com"));
+
+        assert_eq!(info.path(), Some("/ws"));
+        assert_eq!(info.origin(), Some("https://example.com"));
+
+        info.set_path_and_origin("/chat", None);
+
+        assert_eq!(info.path(), Some("/chat"));
+        assert_eq!(info.origin(), None);
+    }
+
+    #[test]
+    fn handshake_result() {
+        let result = HandshakeResult::new(&["mqtt", "graphql-ws"], Some(1), HandshakeStats::new());
+
+        assert_eq!(result.protocol(), Some("graphql-ws"));
+        assert_eq!(result.protocol_index(), Some(1));
+
+        let result = HandshakeResult::new(&["mqtt"], None, HandshakeStats::new());
+
+        assert_eq!(result.protocol(), None);
+        assert_eq!(result.protocol_index(), None);
+    }
+
+    #[test]
+    fn too_long() {
+        let long = "a".repeat(ConnectionInfo::CAPACITY - 2);
+
+        let mut info = ConnectionInfo::new();
+
+        info.set_path_and_origin(&long, Some("https://example.com"));
+
+        assert_eq!(info.path(), Some(long.as_str()));
+        assert_eq!(info.origin(), None);
+
+        info.set_path_and_origin(&"a".repeat(ConnectionInfo::CAPACITY + 1), Some("origin"));
+
+        assert_eq!(info.path(), None);
+        assert_eq!(info.origin(), Some("origin"));
+    }
+}
@@ -0,0 +1,29 @@
+/// Verifies the integrity of received binary messages.
+///
+/// Invoked with the reassembled payload of every binary message before it is returned to the caller.
+/// See [`WebSocket::with_integrity_verifier`](crate::WebSocket::with_integrity_verifier).
+///
+/// Implemented for every `Fn(&[u8]) -> bool + Sync`.
+/// Verifiers must be [`Sync`] so that connections using them remain [`Send`].
+pub trait IntegrityVerifier: Sync {
+    /// Returns `true` if the payload is intact, e.g. its trailing CRC or authentication tag is valid.
+    fn verify(&self, payload: &[u8]) -> bool;
+}
+
+impl<F> IntegrityVerifier for F
+where
+    F: Fn(&[u8]) -> bool + Sync,
+{
+    fn verify(&self, payload: &[u8]) -> bool {
+        self(payload)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Verifier<'a>(pub(crate) &'a dyn IntegrityVerifier);
+
+impl core::fmt::Debug for Verifier<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Verifier").finish_non_exhaustive()
+    }
+}
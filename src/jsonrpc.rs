@@ -0,0 +1,664 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) over text messages.
+//!
+//! Correlates the responses of the peer with the calls made by this side, using numeric ids and a table of up to `N` outstanding calls.
+//! Messages are serialized with [`serde-json-core`](https://docs.rs/serde-json-core/latest/serde_json_core/) into a buffer owned by the [`JsonRpc`],
+//! then sent through a [`TextSink`], e.g. a [`WebSocket`] or a [`WebSocketWrite`]. Received text messages are passed to [`JsonRpc::receive`].
+//!
+//! Only numeric ids are supported. Parameters and results are deserialized on demand, so that their type can depend on the method.
+//! Strings borrowed from a received message are not unescaped.
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # async fn jsonrpc() {
+//! # use websocketz::mock::Noop;
+//! use websocketz::{Message, WebSocket, jsonrpc::{ErrorObject, Incoming, JsonRpc}, next};
+//!
+//! # let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+//! let mut websocketz = WebSocket::client(Noop, Noop, read_buffer, write_buffer, fragments_buffer);
+//!
+//! // Up to 4 outstanding calls, serialized into a buffer of 256 bytes.
+//! let rpc_buffer = &mut [0u8; 256];
+//! let mut rpc = JsonRpc::<4>::new(rpc_buffer);
+//!
+//! let id = rpc
+//!     .call(&mut websocketz, "light.set", Some(&("kitchen", true)))
+//!     .await
+//!     .expect("Failed to call");
+//!
+//! while let Some(Ok(Message::Text(text))) = next!(websocketz) {
+//!     match rpc.receive(text) {
+//!         Ok(Incoming::Result { id: response, result }) if response == id => {
+//!             let on: bool = result.deserialize().expect("Valid result");
+//!         }
+//!         Ok(Incoming::Error { error, .. }) => {
+//!             let _ = (error.code(), error.message());
+//!         }
+//!         Ok(Incoming::Request { id, method, .. }) => {
+//!             let _ = method;
+//!
+//!             let error = ErrorObject::new(ErrorObject::METHOD_NOT_FOUND, "Method not found");
+//!
+//!             rpc.respond_error(&mut websocketz, Some(id), error)
+//!                 .await
+//!                 .expect("Failed to respond");
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! # }
+//! ```
+
+use embedded_io_async::Write;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, WebSocket, WebSocketWrite,
+    error::{Error, JsonRpcError, JsonRpcSendError},
+    rng::RngCore,
+};
+
+const VERSION: &str = "2.0";
+
+/// A destination of text messages.
+pub trait TextSink {
+    /// The error returned when a message can not be sent.
+    type Error;
+
+    /// Sends a text message.
+    fn send_text(&mut self, text: &str) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+impl<RW, Rng> TextSink for WebSocket<'_, RW, Rng>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    type Error = Error<RW::Error>;
+
+    fn send_text(&mut self, text: &str) -> impl Future<Output = Result<(), Self::Error>> {
+        self.send(Message::Text(text))
+    }
+}
+
+impl<RW, Rng> TextSink for WebSocketWrite<'_, RW, Rng>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    type Error = Error<RW::Error>;
+
+    fn send_text(&mut self, text: &str) -> impl Future<Output = Result<(), Self::Error>> {
+        self.send(Message::Text(text))
+    }
+}
+
+/// The error of a failed call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorObject<'a> {
+    code: i32,
+    message: &'a str,
+}
+
+impl<'a> ErrorObject<'a> {
+    /// Invalid JSON was received.
+    pub const PARSE_ERROR: i32 = -32700;
+    /// The JSON sent is not a valid request.
+    pub const INVALID_REQUEST: i32 = -32600;
+    /// The method does not exist or is not available.
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    /// Invalid method parameters.
+    pub const INVALID_PARAMS: i32 = -32602;
+    /// Internal JSON-RPC error.
+    pub const INTERNAL_ERROR: i32 = -32603;
+
+    /// Creates a new [`ErrorObject`].
+    pub const fn new(code: i32, message: &'a str) -> Self {
+        Self { code, message }
+    }
+
+    /// Returns the error code.
+    pub const fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Returns the error message.
+    pub const fn message(&self) -> &'a str {
+        self.message
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a, P: ?Sized> {
+    jsonrpc: &'static str,
+    method: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<&'a P>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ResultResponse<'a, R: ?Sized> {
+    jsonrpc: &'static str,
+    result: &'a R,
+    id: u32,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    jsonrpc: &'static str,
+    error: ErrorObject<'a>,
+    id: Option<u32>,
+}
+
+/// The fields of any message, ignoring the parameters and the result.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    jsonrpc: &'a str,
+    #[serde(borrow)]
+    method: Option<&'a str>,
+    id: Option<u32>,
+    #[serde(borrow)]
+    error: Option<ErrorObject<'a>>,
+}
+
+#[derive(Deserialize)]
+struct ParamsField<T> {
+    params: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ResultField<T> {
+    result: T,
+}
+
+/// The parameters of a received request or notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params<'a> {
+    message: &'a str,
+}
+
+impl<'a> Params<'a> {
+    /// Deserializes the parameters, returning `None` if they were omitted.
+    pub fn deserialize<T: Deserialize<'a>>(&self) -> Result<Option<T>, JsonRpcError> {
+        let (field, _) = serde_json_core::from_str::<ParamsField<T>>(self.message)?;
+
+        Ok(field.params)
+    }
+}
+
+/// The result of a successful call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output<'a> {
+    message: &'a str,
+}
+
+impl<'a> Output<'a> {
+    /// Deserializes the result.
+    pub fn deserialize<T: Deserialize<'a>>(&self) -> Result<T, JsonRpcError> {
+        let (field, _) = serde_json_core::from_str::<ResultField<T>>(self.message)?;
+
+        Ok(field.result)
+    }
+}
+
+/// A received message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incoming<'a> {
+    /// The peer calls a method. Answer with [`JsonRpc::respond`] or [`JsonRpc::respond_error`].
+    Request {
+        /// The id to respond with.
+        id: u32,
+        /// The called method.
+        method: &'a str,
+        /// The parameters.
+        params: Params<'a>,
+    },
+    /// The peer calls a method without expecting a response.
+    Notification {
+        /// The called method.
+        method: &'a str,
+        /// The parameters.
+        params: Params<'a>,
+    },
+    /// A call of this side succeeded.
+    Result {
+        /// The id returned by [`JsonRpc::call`].
+        id: u32,
+        /// The result.
+        result: Output<'a>,
+    },
+    /// A call of this side failed, or the peer could not parse a message.
+    Error {
+        /// The id returned by [`JsonRpc::call`], or `None` if the peer could not determine it.
+        id: Option<u32>,
+        /// The error.
+        error: ErrorObject<'a>,
+    },
+}
+
+/// The state of up to `N` outstanding calls.
+///
+/// See the [module](crate::jsonrpc) documentation.
+#[derive(Debug)]
+pub struct JsonRpc<'buf, const N: usize> {
+    buffer: &'buf mut [u8],
+    next_id: u32,
+    pending: [Option<u32>; N],
+}
+
+impl<'buf, const N: usize> JsonRpc<'buf, N> {
+    /// Creates a new [`JsonRpc`] without outstanding calls, serializing messages into `buffer`.
+    pub const fn new(buffer: &'buf mut [u8]) -> Self {
+        Self {
+            buffer,
+            next_id: 0,
+            pending: [None; N],
+        }
+    }
+
+    /// Returns the number of outstanding calls.
+    pub fn pending(&self) -> usize {
+        self.pending.iter().flatten().count()
+    }
+
+    /// Returns `true` if the call with the given `id` is outstanding.
+    pub fn is_pending(&self, id: u32) -> bool {
+        self.pending.contains(&Some(id))
+    }
+
+    /// Forgets the outstanding call with the given `id`, e.g. after a timeout.
+    ///
+    /// A response received afterwards is reported as [`JsonRpcError::UnknownId`].
+    ///
+    /// Returns `false` if the call is not outstanding.
+    pub fn cancel(&mut self, id: u32) -> bool {
+        match self.pending.iter_mut().find(|slot| **slot == Some(id)) {
+            Some(slot) => {
+                *slot = None;
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Calls `method` with the optional `params`, returning the id of the call.
+    ///
+    /// The response is returned by [`JsonRpc::receive`] as [`Incoming::Result`] or [`Incoming::Error`] with the same id.
+    pub async fn call<S, P>(
+        &mut self,
+        sink: &mut S,
+        method: &str,
+        params: Option<&P>,
+    ) -> Result<u32, JsonRpcSendError<S::Error>>
+    where
+        S: TextSink,
+        P: Serialize + ?Sized,
+    {
+        let index = self
+            .pending
+            .iter()
+            .position(Option::is_none)
+            .ok_or(JsonRpcError::TooManyCalls)?;
+
+        let id = self.next_id;
+
+        let request = Request {
+            jsonrpc: VERSION,
+            method,
+            params,
+            id: Some(id),
+        };
+
+        send(sink, self.buffer, &request).await?;
+
+        self.pending[index] = Some(id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        Ok(id)
+    }
+
+    /// Calls `method` with the optional `params` without expecting a response.
+    pub async fn notify<S, P>(
+        &mut self,
+        sink: &mut S,
+        method: &str,
+        params: Option<&P>,
+    ) -> Result<(), JsonRpcSendError<S::Error>>
+    where
+        S: TextSink,
+        P: Serialize + ?Sized,
+    {
+        let request = Request {
+            jsonrpc: VERSION,
+            method,
+            params,
+            id: None,
+        };
+
+        send(sink, self.buffer, &request).await
+    }
+
+    /// Responds to the [`Incoming::Request`] with the given `id` with its `result`.
+    pub async fn respond<S, R>(
+        &mut self,
+        sink: &mut S,
+        id: u32,
+        result: &R,
+    ) -> Result<(), JsonRpcSendError<S::Error>>
+    where
+        S: TextSink,
+        R: Serialize + ?Sized,
+    {
+        let response = ResultResponse {
+            jsonrpc: VERSION,
+            result,
+            id,
+        };
+
+        send(sink, self.buffer, &response).await
+    }
+
+    /// Responds to the [`Incoming::Request`] with the given `id` with an `error`.
+    ///
+    /// `id` is `None` if the request could not be parsed.
+    pub async fn respond_error<S>(
+        &mut self,
+        sink: &mut S,
+        id: Option<u32>,
+        error: ErrorObject<'_>,
+    ) -> Result<(), JsonRpcSendError<S::Error>>
+    where
+        S: TextSink,
+    {
+        let response = ErrorResponse {
+            jsonrpc: VERSION,
+            error,
+            id,
+        };
+
+        send(sink, self.buffer, &response).await
+    }
+
+    /// Parses a received text message.
+    ///
+    /// A response completes the outstanding call with the same id, releasing its slot.
+    /// Error responses without an id do not complete any call.
+    pub fn receive<'a>(&mut self, text: &'a str) -> Result<Incoming<'a>, JsonRpcError> {
+        let (envelope, _) = serde_json_core::from_str::<Envelope<'a>>(text)?;
+
+        if envelope.jsonrpc != VERSION {
+            return Err(JsonRpcError::Malformed);
+        }
+
+        let params = Params { message: text };
+
+        let incoming = match envelope {
+            Envelope {
+                method: Some(method),
+                id: Some(id),
+                ..
+            } => Incoming::Request { id, method, params },
+            Envelope {
+                method: Some(method),
+                id: None,
+                ..
+            } => Incoming::Notification { method, params },
+            Envelope {
+                error: Some(error),
+                id,
+                ..
+            } => Incoming::Error { id, error },
+            Envelope { id: Some(id), .. } => Incoming::Result {
+                id,
+                result: Output { message: text },
+            },
+            _ => return Err(JsonRpcError::Malformed),
+        };
+
+        match incoming {
+            Incoming::Result { id, .. } | Incoming::Error { id: Some(id), .. } => {
+                if !self.cancel(id) {
+                    return Err(JsonRpcError::UnknownId(id));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(incoming)
+    }
+}
+
+async fn send<S, T>(
+    sink: &mut S,
+    buffer: &mut [u8],
+    value: &T,
+) -> Result<(), JsonRpcSendError<S::Error>>
+where
+    S: TextSink,
+    T: Serialize,
+{
+    let len = serde_json_core::to_slice(value, buffer).map_err(|_| JsonRpcError::BufferTooSmall)?;
+
+    let text = core::str::from_utf8(&buffer[..len]).expect("serde-json-core writes valid UTF-8");
+
+    sink.send_text(text).await.map_err(JsonRpcSendError::Send)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use std::{string::String, vec::Vec};
+
+    use super::*;
+
+    /// Collects the sent messages.
+    #[derive(Debug, Default)]
+    struct Sink {
+        messages: Vec<String>,
+    }
+
+    impl TextSink for Sink {
+        type Error = Infallible;
+
+        async fn send_text(&mut self, text: &str) -> Result<(), Self::Error> {
+            self.messages.push(text.into());
+
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn call_result() {
+        let buffer = &mut [0u8; 128];
+        let mut rpc = JsonRpc::<2>::new(buffer);
+        let mut sink = Sink::default();
+
+        let id = rpc
+            .call(&mut sink, "add", Some(&[1, 2]))
+            .await
+            .expect("Failed to call");
+
+        assert_eq!(
+            sink.messages[0],
+            r#"{"jsonrpc":"2.0","method":"add","params":[1,2],"id":0}"#
+        );
+        assert!(rpc.is_pending(id));
+
+        let response = r#"{"jsonrpc":"2.0","result":3,"id":0}"#;
+
+        match rpc.receive(response) {
+            Ok(Incoming::Result { id: 0, result }) => {
+                assert_eq!(result.deserialize::<u32>(), Ok(3))
+            }
+            incoming => panic!("Unexpected message: {incoming:?}"),
+        }
+
+        assert_eq!(rpc.pending(), 0);
+        assert_eq!(rpc.receive(response), Err(JsonRpcError::UnknownId(0)));
+    }
+
+    #[tokio::test]
+    async fn call_error() {
+        let buffer = &mut [0u8; 128];
+        let mut rpc = JsonRpc::<2>::new(buffer);
+        let mut sink = Sink::default();
+
+        rpc.call::<_, ()>(&mut sink, "reboot", None)
+            .await
+            .expect("Failed to call");
+        let id = rpc
+            .call::<_, ()>(&mut sink, "reboot", None)
+            .await
+            .expect("Failed to call");
+
+        assert_eq!(id, 1);
+        assert_eq!(
+            sink.messages[1],
+            r#"{"jsonrpc":"2.0","method":"reboot","id":1}"#
+        );
+
+        assert_eq!(
+            rpc.receive(r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found","data":[1]},"id":1}"#),
+            Ok(Incoming::Error {
+                id: Some(1),
+                error: ErrorObject::new(ErrorObject::METHOD_NOT_FOUND, "Method not found")
+            })
+        );
+        assert!(!rpc.is_pending(1));
+        assert!(rpc.is_pending(0));
+
+        assert!(matches!(
+            rpc.receive(
+                r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#
+            ),
+            Ok(Incoming::Error { id: None, .. })
+        ));
+        assert!(rpc.is_pending(0));
+    }
+
+    #[tokio::test]
+    async fn too_many_calls() {
+        let buffer = &mut [0u8; 128];
+        let mut rpc = JsonRpc::<1>::new(buffer);
+        let mut sink = Sink::default();
+
+        let id = rpc
+            .call::<_, ()>(&mut sink, "a", None)
+            .await
+            .expect("Failed to call");
+
+        assert!(matches!(
+            rpc.call::<_, ()>(&mut sink, "b", None).await,
+            Err(JsonRpcSendError::JsonRpc(JsonRpcError::TooManyCalls))
+        ));
+        assert_eq!(sink.messages.len(), 1);
+
+        assert!(rpc.cancel(id));
+        assert!(!rpc.cancel(id));
+
+        rpc.call::<_, ()>(&mut sink, "b", None)
+            .await
+            .expect("Failed to call");
+    }
+
+    #[tokio::test]
+    async fn buffer_too_small() {
+        let buffer = &mut [0u8; 16];
+        let mut rpc = JsonRpc::<1>::new(buffer);
+        let mut sink = Sink::default();
+
+        assert!(matches!(
+            rpc.notify(&mut sink, "a long method name", Some(&[0u8; 8]))
+                .await,
+            Err(JsonRpcSendError::JsonRpc(JsonRpcError::BufferTooSmall))
+        ));
+        assert!(sink.messages.is_empty());
+        assert_eq!(rpc.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Set<'a> {
+            room: &'a str,
+            on: bool,
+        }
+
+        let buffer = &mut [0u8; 128];
+        let mut rpc = JsonRpc::<0>::new(buffer);
+        let mut sink = Sink::default();
+
+        match rpc.receive(
+            r#"{"jsonrpc":"2.0","method":"set","params":{"room":"kitchen","on":true},"id":7}"#,
+        ) {
+            Ok(Incoming::Request {
+                id: 7,
+                method: "set",
+                params,
+            }) => {
+                assert_eq!(
+                    params.deserialize::<Set>(),
+                    Ok(Some(Set {
+                        room: "kitchen",
+                        on: true
+                    }))
+                );
+
+                rpc.respond(&mut sink, 7, &true)
+                    .await
+                    .expect("Failed to respond");
+            }
+            incoming => panic!("Unexpected message: {incoming:?}"),
+        }
+
+        match rpc.receive(r#"{"jsonrpc":"2.0","method":"heartbeat"}"#) {
+            Ok(Incoming::Notification {
+                method: "heartbeat",
+                params,
+            }) => assert_eq!(params.deserialize::<[u8; 1]>(), Ok(None)),
+            incoming => panic!("Unexpected message: {incoming:?}"),
+        }
+
+        rpc.respond_error(
+            &mut sink,
+            None,
+            ErrorObject::new(ErrorObject::PARSE_ERROR, "Parse error"),
+        )
+        .await
+        .expect("Failed to respond");
+
+        assert_eq!(
+            sink.messages,
+            [
+                r#"{"jsonrpc":"2.0","result":true,"id":7}"#,
+                r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let buffer = &mut [];
+        let mut rpc = JsonRpc::<1>::new(buffer);
+
+        assert_eq!(
+            rpc.receive(r#"{"jsonrpc":"1.0","method":"a"}"#),
+            Err(JsonRpcError::Malformed)
+        );
+        assert_eq!(
+            rpc.receive(r#"{"jsonrpc":"2.0"}"#),
+            Err(JsonRpcError::Malformed)
+        );
+        assert!(matches!(
+            rpc.receive(r#"{"method":"a"}"#),
+            Err(JsonRpcError::Deserialize(_))
+        ));
+        assert!(matches!(
+            rpc.receive("not json"),
+            Err(JsonRpcError::Deserialize(_))
+        ));
+    }
+}
@@ -9,14 +9,51 @@
 //!
 //! See the examples folder for more information.
 //!
+//! # Features
+//!
+//! - `client` (default): Enables clients, which need an RNG to mask their frames, see the [`rng`] module.
+//!   Disable default features for server-only builds without the `rand` dependency.
+//! - `alloc`: Enables the `pool` module, a pool of persistent client connections.
+//!   Implies `client`.
+//! - `buffer-pool`: Enables [`buffers::BufferPool`], a pool of connection buffers that can be a `static`.
+//!   Its slots are taken with compare-and-swap atomics, which targets such as `thumbv6m` and `riscv32imc` do not have.
+//! - `deflate`: Enables the `deflate` module, negotiating the `permessage-deflate` extension (RFC 7692) and compressing messages with it.
+//! - `dual-stack`: Enables the `dual_stack` module, trying the resolved IPv6 and IPv4 addresses of a host in alternating order,
+//!   e.g. with the DNS resolver and sockets of `embassy-net`.
+//! - `embassy-net`: Enables `dual_stack::Resolved`, resolving the IPv6 and IPv4 addresses of a host with `embassy-net` for a [`dual_stack::DualStack`].
+//!   Implies `dual-stack`.
+//! - `embassy-time`: Enables [`clock::EmbassyClock`], a [`clock::Clock`] reading `embassy_time::Instant`.
+//! - `embedded-tls`: Enables `tls::open_embedded_tls`, opening an `embedded-tls` connection for [`WebSocket::connect_tls`]. Implies `tls`.
+//! - `handshake-trace`: Traces handshake decision points, such as the received status code or a missing header.
+//!   Requires `log` and/or `defmt` to emit the traces.
+//! - `log`: Emits traces using the [`log`](https://docs.rs/log/latest/log/) crate.
+//! - `defmt`: Emits traces using the [`defmt`](https://docs.rs/defmt/latest/defmt/) crate.
+//! - `danger-frames`: Enables the `danger` module, sending frames with arbitrary reserved bits and opcodes.
+//! - `jsonrpc`: Enables the `jsonrpc` module, correlating JSON-RPC 2.0 calls and responses over text messages with `serde-json-core`.
+//! - `lz4`: Enables [`compression::Lz4`], an allocation-free LZ4 block compressor for application-level payload compression.
+//! - `mqtt`: Enables the `mqtt` module, exposing a WebSocket as an MQTT transport, see [`stream::ByteStream`].
+//! - `mux`: Enables the `mux` module, multiplexing several logical channels with flow control over the binary messages of a single connection.
+//! - `pre-masked`: Enables the `pre_masked` module, sending payloads already masked by a hardware offload engine or taken from test vectors.
+//!   Implies `client`.
+//! - `simd`: Unmasks received payloads with [`core::simd`](https://doc.rust-lang.org/core/simd/index.html) vectors on a nightly compiler.
+//!   Ignored on stable and beta compilers, which keep the 8-byte word path.
+//! - `tls`: Enables the `tls` module, establishing WebSocket connections over a TLS stream, e.g. from `embedded-tls` or `esp-mbedtls`,
+//!   with a single buffer split into the TLS and WebSocket buffers.
+//! - `serde`: Implements `Serialize` and `Deserialize` for [`options::Config`], e.g. to load it from a configuration blob.
+//! - `std`: Enables [`clock::StdClock`], a [`clock::Clock`] reading [`std::time::Instant`],
+//!   and converts the error types over [`std::io::Error`] into [`std::io::Error`], so that they can be propagated with `?`.
+//! - `tools`: Builds the `websocketz-latency` binary, measuring the round-trip time to an echo endpoint,
+//!   and the `websocketz-repl` binary, an interactive client to send messages to and print messages from an endpoint. Requires `std`.
+//!
 //! # Examples
 //!
 //! In the following examples, `Noop` is a mock type that implements the required traits for using a [`WebSocket`].
 //! - A `stream` is anything that implements [`embedded_io_async::Read`] + [`embedded_io_async::Write`].
-//! - An `rng` is anything that implements [`rand_core::RngCore`].
+//! - An `rng` is anything that implements [`rng::RngCore`].
 //!
 //! ### Client
-//! ```
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
 //! # async fn client() {
 //! # use websocketz::mock::Noop;
 //! use websocketz::{Message, WebSocket, http::Header, next, options::ConnectOptions};
@@ -49,7 +86,8 @@
 //!                 name: "User-Agent",
 //!                 value: b"WebSocketz",
 //!             },
-//!         ]),
+//!         ])
+//!         .expect("Valid headers"),
 //!     stream,
 //!     rng,
 //!     read_buffer,
@@ -101,7 +139,7 @@
 //! let write_buffer = &mut [0u8; 1024];
 //! let fragments_buffer = &mut [0u8; 1024];
 //!
-//! // Impl rand_core RngCore.
+//! // Impl RngCore, see the `rng` module. Servers never use it.
 //! let rng: Noop = Noop;
 //!
 //! // Perform a WebSocket handshake as a server.
@@ -161,6 +199,8 @@
 //!
 //! `auto_pong` and `auto_close` are enabled by default, but can be set using [`WebSocket::with_auto_pong`] and [`WebSocket::with_auto_close`] respectively.
 //!
+//! [`WebSocket::with_profile`] applies a [`Profile`](options::Profile) bundling these settings with message limits and ping tracking.
+//!
 //! # Reading from the connection
 //!
 //! This library allocates nothing. It only uses exclusive references and stack memory. It is quite challenging to offer a clean API while adhering to rust's borrowing rules.
@@ -173,7 +213,7 @@
 //! ```
 //! # async fn next_macro() {
 //! # use websocketz::mock::Noop;
-//! # use websocketz::{WebSocket, next, options::ConnectOptions};
+//! # use websocketz::{WebSocket, next, options::AcceptOptions};
 //! #
 //! # let stream = Noop;
 //! # let read_buffer = &mut [0u8; 1024];
@@ -181,10 +221,8 @@
 //! # let fragments_buffer = &mut [0u8; 1024];
 //! # let rng = Noop;
 //! #
-//! # let websocketz = WebSocket::connect::<16>(
-//! #     ConnectOptions::default()
-//! #         .with_path("/ws")
-//! #         .expect("Valid path"),
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
 //! #     stream,
 //! #     rng,
 //! #     read_buffer,
@@ -204,6 +242,86 @@
 //! # }
 //! ```
 //!
+//! If you prefer functions over macros, [`WebSocket::with_next`] and [`WebSocketRead::with_next`] read the next message and pass it to a closure.
+//!
+//! ```
+//! # async fn with_next() {
+//! # use websocketz::mock::Noop;
+//! # use websocketz::{WebSocket, options::AcceptOptions};
+//! #
+//! # let stream = Noop;
+//! # let read_buffer = &mut [0u8; 1024];
+//! # let write_buffer = &mut [0u8; 1024];
+//! # let fragments_buffer = &mut [0u8; 1024];
+//! # let rng = Noop;
+//! #
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
+//! #     stream,
+//! #     rng,
+//! #     read_buffer,
+//! #     write_buffer,
+//! #     fragments_buffer,
+//! # )
+//! # .await
+//! # .expect("Handshake failed");
+//! #
+//! # let existing_websocket = || websocketz;
+//! let mut websocketz = existing_websocket();
+//!
+//! while let Some(Ok(is_text)) = websocketz.with_next(|msg| msg.is_text()).await {
+//!     // The closure's return value must not borrow the message.
+//!     let _ = is_text;
+//! }
+//! # }
+//! ```
+//!
+//! To build your own read loops or combinators, [`WebSocket::maybe_next`] and [`WebSocketRead::maybe_next`] read and handle at most one frame,
+//! returning `Some(Ok(None))` until a message is complete.
+//!
+//! If you need to transform a payload in place (e.g. decrypting it) before processing it, use the [`next_mut!`] macro instead.
+//! It yields a [`MessageMut`], whose payload is a mutable reference into the read or fragments buffer.
+//!
+//! ```
+//! # async fn next_mut_macro() {
+//! # use websocketz::mock::Noop;
+//! # use websocketz::{MessageMut, WebSocket, next_mut, options::AcceptOptions};
+//! #
+//! # let stream = Noop;
+//! # let read_buffer = &mut [0u8; 1024];
+//! # let write_buffer = &mut [0u8; 1024];
+//! # let fragments_buffer = &mut [0u8; 1024];
+//! # let rng = Noop;
+//! #
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
+//! #     stream,
+//! #     rng,
+//! #     read_buffer,
+//! #     write_buffer,
+//! #     fragments_buffer,
+//! # )
+//! # .await
+//! # .expect("Handshake failed");
+//! #
+//! # let existing_websocket = || websocketz;
+//! let mut websocketz = existing_websocket();
+//!
+//! while let Some(Ok(msg)) = next_mut!(websocketz) {
+//!     if let MessageMut::Binary(payload) = msg {
+//!         // Transform the payload in place.
+//!         payload.iter_mut().for_each(|byte| *byte ^= 0xFF);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! In soft real-time loops, [`next_bounded!`] gives control back after a maximum number of internal iterations,
+//! even if no complete message was read yet.
+//!
+//! To process large fragmented messages without reassembling them, e.g. to write them to flash,
+//! [`next_fragment!`] passes their frames through as [`Fragment`]s as they arrive.
+//!
 //! # Writing to the connection
 //!
 //! [`WebSocket`] offers two methods to send messages, [`WebSocket::send`] and [`WebSocket::send_fragmented`].
@@ -211,7 +329,7 @@
 //! ```compile_fail
 //! # async fn send_method_no_compile() {
 //! # use crate::mock::Noop;
-//! # use crate::{WebSocket, next, options::ConnectOptions};
+//! # use crate::{WebSocket, next, options::AcceptOptions};
 //! #
 //! # let stream = Noop;
 //! # let read_buffer = &mut [0u8; 1024];
@@ -219,10 +337,8 @@
 //! # let fragments_buffer = &mut [0u8; 1024];
 //! # let rng = Noop;
 //! #
-//! # let websocketz = WebSocket::connect::<16>(
-//! #     ConnectOptions::default()
-//! #         .with_path("/ws")
-//! #         .expect("Valid path"),
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
 //! #     stream,
 //! #     rng,
 //! #     read_buffer,
@@ -249,7 +365,7 @@
 //! ```
 //! # async fn send_macro() {
 //! # use websocketz::mock::Noop;
-//! # use websocketz::{WebSocket, next, options::ConnectOptions, send};
+//! # use websocketz::{WebSocket, next, options::AcceptOptions, send};
 //! #
 //! # let stream = Noop;
 //! # let read_buffer = &mut [0u8; 1024];
@@ -257,10 +373,8 @@
 //! # let fragments_buffer = &mut [0u8; 1024];
 //! # let rng = Noop;
 //! #
-//! # let websocketz = WebSocket::connect::<16>(
-//! #     ConnectOptions::default()
-//! #         .with_path("/ws")
-//! #         .expect("Valid path"),
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
 //! #     stream,
 //! #     rng,
 //! #     read_buffer,
@@ -279,6 +393,10 @@
 //! # }
 //!```
 //!
+//! Every frame is written as a whole and the writer is flushed afterwards, including automatic replies and handshake messages,
+//! so transports buffering their output until flushed, such as TLS adapters or buffered UARTs, transmit every message right away.
+//! To batch several messages into a single flush, use [`WebSocket::send_without_flush`] followed by [`WebSocket::flush`].
+//!
 //! # Splitting the connection
 //!
 //! In some cases, you might want to split the WebSocket connection into a read half and a write half.
@@ -291,7 +409,7 @@
 //! ```
 //! # async fn split() {
 //! # use websocketz::mock::Noop;
-//! # use websocketz::{Message, WebSocket, next, options::ConnectOptions};
+//! # use websocketz::{Message, WebSocket, next, options::AcceptOptions};
 //! #
 //! # let stream = Noop;
 //! # let read_buffer = &mut [0u8; 1024];
@@ -299,10 +417,8 @@
 //! # let fragments_buffer = &mut [0u8; 1024];
 //! # let rng = Noop;
 //! #
-//! # let websocketz = WebSocket::connect::<16>(
-//! #     ConnectOptions::default()
-//! #         .with_path("/ws")
-//! #         .expect("Valid path"),
+//! # let websocketz = WebSocket::accept::<16>(
+//! #     AcceptOptions::default(),
 //! #     stream,
 //! #     rng,
 //! #     read_buffer,
@@ -340,39 +456,98 @@
 //!```
 
 #![no_std]
+#![forbid(unsafe_code)]
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(all(feature = "simd", websocketz_nightly), feature(portable_simd))]
+
+pub mod buffers;
+
+pub mod clock;
 
 mod close_code;
-pub use close_code::CloseCode;
+pub use close_code::{CloseCode, CloseReason, CloseReasons, LibraryCloseCode};
 
 mod close_frame;
 pub use close_frame::CloseFrame;
 
+mod close_signal;
+pub use close_signal::CloseSignal;
+
 mod codec;
 use codec::FramesCodec;
 
+pub mod compression;
+
+pub mod cookbook;
+
+#[cfg(feature = "danger-frames")]
+#[cfg_attr(docsrs, doc(cfg(feature = "danger-frames")))]
+pub mod danger;
+
+#[cfg(feature = "deflate")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+pub mod deflate;
+
+pub mod download;
+
+#[cfg(feature = "dual-stack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dual-stack")))]
+pub mod dual_stack;
+
 pub mod error;
 
+pub mod extensions;
+
+mod filter;
+pub use filter::{FirstFrame, MessageFilter, Verdict};
+
+mod fragment;
+pub use fragment::{Fragment, StreamItem};
+
 mod fragments;
 
 mod frame;
+pub use frame::FrameMeta;
 use frame::{Frame, FrameMut, Header};
 
 #[doc(hidden)]
 pub mod functions;
 
+mod handshake_info;
+pub use handshake_info::{CapturedValues, HandshakeCapture, HandshakeInfo};
+
 pub mod http;
 
+mod info;
+pub use info::{ConnectionInfo, HandshakeResult};
+
+mod integrity;
+pub use integrity::IntegrityVerifier;
+
+#[cfg(feature = "jsonrpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonrpc")))]
+pub mod jsonrpc;
+
 mod mask;
 
 mod message;
-pub use message::Message;
+pub use message::{Message, MessageMut};
+
+mod message_writer;
+pub use message_writer::MessageWriter;
 
-#[doc(hidden)]
 pub mod mock;
 
+#[cfg(feature = "mqtt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mqtt")))]
+pub mod mqtt;
+
+#[cfg(feature = "mux")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mux")))]
+pub mod mux;
+
 mod macros;
 
 mod opcode;
@@ -380,17 +555,51 @@ use opcode::OpCode;
 
 pub mod options;
 
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod pool;
+
+mod pre_encoded;
+pub use pre_encoded::PreEncodedMessage;
+
+#[cfg(feature = "pre-masked")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pre-masked")))]
+pub mod pre_masked;
+
+mod progress;
+pub use progress::{Progress, ProgressObserver};
+
+mod reconnect;
+pub use reconnect::ReconnectAdvice;
+
+pub mod rng;
+
+#[cfg(feature = "tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+pub mod tls;
+
+mod stats;
+pub use stats::HandshakeStats;
+
+pub mod stream;
+
+mod trace;
+use trace::handshake_trace;
+
 mod websocket_core;
 use websocket_core::{ConnectionState, FragmentsState, OnFrame, WebSocketCore};
 
 mod websocket;
-pub use websocket::{WebSocket, WebSocketRead, WebSocketWrite};
+pub use websocket::{AttachedRead, WebSocket, WebSocketRead, WebSocketWrite};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "client"))]
 mod tests;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "client"))]
 mod examples;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
@@ -6,7 +6,9 @@
 ///
 /// # Return
 /// - `Some(Ok(Message))`: A message was successfully read.
-/// - `Some(Err(Error))`: An error occurred while reading a message. The caller should stop reading.
+/// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`](crate::WebSocket::pause_reads), nothing was read.
+///   Reading can continue once resumed.
+/// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
 /// - `None`: The WebSocket connection has been closed (EOF). The caller should stop reading.
 #[macro_export]
 macro_rules! next {
@@ -16,10 +18,159 @@ macro_rules! next {
                 .caller()
                 .call(
                     $websocketz.auto(),
-                    &mut $websocketz.core.framed.core.codec,
-                    &mut $websocketz.core.framed.core.inner,
-                    &mut $websocketz.core.framed.core.state.read,
-                    &mut $websocketz.core.framed.core.state.write,
+                    &mut $websocketz.core.codec,
+                    &mut $websocketz.core.inner,
+                    &mut $websocketz.core.read,
+                    &mut $websocketz.core.write,
+                    &mut $websocketz.core.fragments_state,
+                    &mut $websocketz.core.state,
+                )
+                .await
+            {
+                Some(Ok(None)) => continue 'next,
+                Some(Ok(Some(item))) => break 'next Some(Ok(item)),
+                Some(Err(err)) => break 'next Some(Err(err)),
+                None => break 'next None,
+            }
+        }
+    }};
+}
+
+/// Read a [`Message`](crate::Message) from a [`WebSocket`](crate::WebSocket) or [`WebSocketRead`](crate::WebSocketRead),
+/// giving control back to the caller after at most `$max_iterations` internal iterations.
+///
+/// Works like [`next!`](crate::next), but instead of looping until a complete message is available,
+/// returns `Some(Ok(None))` once `$max_iterations` iterations produced no message, e.g. after control frames handled internally,
+/// non-final fragments or partial reads. The next call resumes where the previous one stopped.
+///
+/// Every iteration performs at most one read from the underlying connection, or, with a
+/// [minimum read chunk](crate::WebSocket::with_min_read_chunk) set, as many reads as it takes to buffer that many new bytes,
+/// fill the read buffer or reach the end of the stream.
+/// The time spent per call is therefore bounded as long as reads are bounded, e.g. by a timeout or a non-blocking connection.
+///
+/// # Parameters
+///
+/// - `$websocketz`: The WebSocket instance to read from.
+/// - `$max_iterations`: The maximum number of internal iterations.
+///
+/// # Return
+/// - `Some(Ok(Some(Message)))`: A message was successfully read.
+/// - `Some(Ok(None))`: No complete message was read within `$max_iterations` iterations. The caller may read again.
+/// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`](crate::WebSocket::pause_reads), nothing was read.
+///   Reading can continue once resumed.
+/// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+/// - `None`: The WebSocket connection has been closed (EOF). The caller should stop reading.
+#[macro_export]
+macro_rules! next_bounded {
+    ($websocketz:expr, $max_iterations:expr) => {{
+        let max_iterations: usize = $max_iterations;
+        let mut iterations = 0;
+
+        'next: loop {
+            if iterations >= max_iterations {
+                break 'next Some(Ok(None));
+            }
+
+            iterations += 1;
+
+            match $websocketz
+                .caller()
+                .call(
+                    $websocketz.auto(),
+                    &mut $websocketz.core.codec,
+                    &mut $websocketz.core.inner,
+                    &mut $websocketz.core.read,
+                    &mut $websocketz.core.write,
+                    &mut $websocketz.core.fragments_state,
+                    &mut $websocketz.core.state,
+                )
+                .await
+            {
+                Some(Ok(None)) => continue 'next,
+                Some(Ok(Some(item))) => break 'next Some(Ok(Some(item))),
+                Some(Err(err)) => break 'next Some(Err(err)),
+                None => break 'next None,
+            }
+        }
+    }};
+}
+
+/// Read a [`MessageMut`](crate::MessageMut) from a [`WebSocket`](crate::WebSocket) or [`WebSocketRead`](crate::WebSocketRead).
+///
+/// Works like [`next!`](crate::next), but the returned message grants mutable access to its payload,
+/// so it can be transformed in place (e.g. decrypted) within the read/fragments buffer.
+///
+/// # Parameters
+///
+/// - `$websocketz`: The WebSocket instance to read from.
+///
+/// # Return
+/// - `Some(Ok(MessageMut))`: A message was successfully read.
+/// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`](crate::WebSocket::pause_reads), nothing was read.
+///   Reading can continue once resumed.
+/// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+/// - `None`: The WebSocket connection has been closed (EOF). The caller should stop reading.
+#[macro_export]
+macro_rules! next_mut {
+    ($websocketz:expr) => {{
+        'next: loop {
+            match $websocketz
+                .caller()
+                .call_mut(
+                    $websocketz.auto(),
+                    &mut $websocketz.core.codec,
+                    &mut $websocketz.core.inner,
+                    &mut $websocketz.core.read,
+                    &mut $websocketz.core.write,
+                    &mut $websocketz.core.fragments_state,
+                    &mut $websocketz.core.state,
+                )
+                .await
+            {
+                Some(Ok(None)) => continue 'next,
+                Some(Ok(Some(item))) => break 'next Some(Ok(item)),
+                Some(Err(err)) => break 'next Some(Err(err)),
+                None => break 'next None,
+            }
+        }
+    }};
+}
+
+/// Read a [`StreamItem`](crate::StreamItem) from a [`WebSocket`](crate::WebSocket) or [`WebSocketRead`](crate::WebSocketRead).
+///
+/// Works like [`next!`](crate::next), but passes the frames of fragmented text and binary messages through as [`Fragment`](crate::Fragment)s
+/// as they arrive, instead of reassembling the messages in the fragments buffer.
+/// This lets fragmented messages of any size be processed on the fly, e.g. written to flash, with a fragments buffer sized for the messages that are still reassembled.
+///
+/// Messages received in a single frame and control messages are returned as [`StreamItem::Message`](crate::StreamItem::Message),
+/// as are fragmented messages compressed with `permessage-deflate`, which are reassembled to be decompressed.
+/// The size limits apply to the total length of a passed through message. Text fragments are not validated as UTF-8,
+/// and integrity verifiers are not applied to passed through messages.
+///
+/// The remaining fragments of a message partially read with this macro are discarded if reading continues with [`next!`](crate::next).
+///
+/// # Parameters
+///
+/// - `$websocketz`: The WebSocket instance to read from.
+///
+/// # Return
+/// - `Some(Ok(StreamItem))`: A message or a fragment was successfully read.
+/// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`](crate::WebSocket::pause_reads), nothing was read.
+///   Reading can continue once resumed.
+/// - `Some(Err(MessageError))`: Any other error occurred while reading. The caller should stop reading.
+/// - `None`: The WebSocket connection has been closed (EOF). The caller should stop reading.
+#[macro_export]
+macro_rules! next_fragment {
+    ($websocketz:expr) => {{
+        'next: loop {
+            match $websocketz
+                .caller()
+                .call_fragment(
+                    $websocketz.auto(),
+                    &mut $websocketz.core.codec,
+                    &mut $websocketz.core.inner,
+                    &mut $websocketz.core.read,
+                    &mut $websocketz.core.write,
                     &mut $websocketz.core.fragments_state,
                     &mut $websocketz.core.state,
                 )
@@ -43,9 +194,9 @@ macro_rules! next {
 macro_rules! send {
     ($websocketz:expr, $message:expr) => {{
         $crate::functions::send(
-            &mut $websocketz.core.framed.core.codec,
-            &mut $websocketz.core.framed.core.inner,
-            &mut $websocketz.core.framed.core.state.write,
+            &mut $websocketz.core.codec,
+            &mut $websocketz.core.inner,
+            &mut $websocketz.core.write,
             &mut $websocketz.core.state,
             $message,
         )
@@ -63,9 +214,9 @@ macro_rules! send {
 macro_rules! send_fragmented {
     ($websocketz:expr, $message:expr, $fragment_size:expr) => {{
         $crate::functions::send_fragmented(
-            &mut $websocketz.core.framed.core.codec,
-            &mut $websocketz.core.framed.core.inner,
-            &mut $websocketz.core.framed.core.state.write,
+            &mut $websocketz.core.codec,
+            &mut $websocketz.core.inner,
+            &mut $websocketz.core.write,
             &mut $websocketz.core.state,
             $message,
             $fragment_size,
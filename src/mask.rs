@@ -7,36 +7,135 @@ fn unmask_easy(payload: &mut [u8], mask: [u8; 4]) {
     });
 }
 
-// Faster version of `unmask_easy()` which operates on 4-byte blocks.
-// https://github.com/snapview/tungstenite-rs/blob/e5efe537b87a6705467043fe44bb220ddf7c1ce8/src/protocol/frame/mask.rs#L23
+// Faster version of `unmask_easy()` which operates on 8-byte blocks without any `unsafe` alignment tricks.
 //
-// https://godbolt.org/z/EPTYo5jK8
+// Every block starts at a multiple of 4, so the mask is always applied in phase,
+// independently of the alignment of the buffer and the endianness of the target.
+#[cfg_attr(all(feature = "simd", websocketz_nightly), allow(dead_code))]
 #[inline]
 fn unmask_fallback(buf: &mut [u8], mask: [u8; 4]) {
-    let mask_u32 = u32::from_ne_bytes(mask);
+    let mask_u64 = mask_u64(mask);
+
+    let mut blocks = buf.chunks_exact_mut(8);
+
+    for block in &mut blocks {
+        let mut word = [0; 8];
+        word.copy_from_slice(block);
+
+        block.copy_from_slice(&(u64::from_ne_bytes(word) ^ mask_u64).to_ne_bytes());
+    }
+
+    unmask_easy(blocks.into_remainder(), mask);
+}
+
+// Version of `unmask_fallback()` operating on 16-byte `core::simd` vectors.
+//
+// The vectors are aligned to their size, so the mask is rotated by the length of the unaligned prefix.
+#[cfg(all(feature = "simd", websocketz_nightly))]
+#[inline]
+fn unmask_simd(buf: &mut [u8], mask: [u8; 4]) {
+    use core::simd::u8x16;
+
+    let (prefix, middle, suffix) = buf.as_simd_mut::<16>();
 
-    let (prefix, words, suffix) = unsafe { buf.align_to_mut::<u32>() };
     unmask_easy(prefix, mask);
-    let head = prefix.len() & 3;
-    let mask_u32 = if head > 0 {
-        if cfg!(target_endian = "big") {
-            mask_u32.rotate_left(8 * head as u32)
-        } else {
-            mask_u32.rotate_right(8 * head as u32)
-        }
-    } else {
-        mask_u32
-    };
-    for word in words.iter_mut() {
-        *word ^= mask_u32;
+
+    // Every vector is a multiple of 4 bytes long, so the same rotation applies to the suffix.
+    let mask = rotate(mask, prefix.len());
+    let mask_simd = u8x16::from_array(core::array::from_fn(|i| mask[i & 3]));
+
+    for block in middle {
+        *block ^= mask_simd;
     }
-    unmask_easy(suffix, mask_u32.to_ne_bytes());
+
+    unmask_easy(suffix, mask);
 }
 
 /// Unmask a payload using the given 4-byte mask.
 #[inline]
 pub fn unmask(payload: &mut [u8], mask: [u8; 4]) {
-    unmask_fallback(payload, mask)
+    #[cfg(all(feature = "simd", websocketz_nightly))]
+    unmask_simd(payload, mask);
+
+    #[cfg(not(all(feature = "simd", websocketz_nightly)))]
+    unmask_fallback(payload, mask);
+}
+
+/// Returns `mask` rotated so that its first byte applies to the payload byte at `offset`.
+#[inline]
+const fn rotate(mask: [u8; 4], offset: usize) -> [u8; 4] {
+    [
+        mask[offset & 3],
+        mask[(offset + 1) & 3],
+        mask[(offset + 2) & 3],
+        mask[(offset + 3) & 3],
+    ]
+}
+
+#[inline]
+const fn mask_u64(mask: [u8; 4]) -> u64 {
+    u64::from_ne_bytes([
+        mask[0], mask[1], mask[2], mask[3], mask[0], mask[1], mask[2], mask[3],
+    ])
+}
+
+/// Copies `src` into the start of `dst` and masks it in the same pass, operating on 8-byte blocks.
+///
+/// `offset` is the position of `src` in the payload, so that a payload can be written in several parts.
+///
+/// # Panics
+///
+/// Panics if `dst` is shorter than `src`.
+#[inline]
+pub fn copy_masked(dst: &mut [u8], src: &[u8], mask: [u8; 4], offset: usize) {
+    let mask = rotate(mask, offset);
+    let mask_u64 = mask_u64(mask);
+
+    let dst = &mut dst[..src.len()];
+
+    let mut dst_blocks = dst.chunks_exact_mut(8);
+    let mut src_blocks = src.chunks_exact(8);
+
+    for (dst, src) in (&mut dst_blocks).zip(&mut src_blocks) {
+        let mut word = [0; 8];
+        word.copy_from_slice(src);
+
+        dst.copy_from_slice(&(u64::from_ne_bytes(word) ^ mask_u64).to_ne_bytes());
+    }
+
+    for (i, (dst, src)) in dst_blocks
+        .into_remainder()
+        .iter_mut()
+        .zip(src_blocks.remainder())
+        .enumerate()
+    {
+        *dst = *src ^ mask[i & 3];
+    }
+}
+
+/// Moves the `len` bytes at `start` of `buf` to its start and masks them in the same pass, operating on 8-byte blocks.
+///
+/// # Panics
+///
+/// Panics if `buf` is shorter than `start + len`.
+#[inline]
+pub fn copy_within_masked(buf: &mut [u8], start: usize, len: usize, mask: [u8; 4]) {
+    let mask_u64 = mask_u64(mask);
+
+    let blocks = len / 8 * 8;
+
+    // Every block is read before it is written, and the source never trails the destination,
+    // so no source byte is overwritten before it is read.
+    for index in (0..blocks).step_by(8) {
+        let mut word = [0; 8];
+        word.copy_from_slice(&buf[start + index..start + index + 8]);
+
+        buf[index..index + 8].copy_from_slice(&(u64::from_ne_bytes(word) ^ mask_u64).to_ne_bytes());
+    }
+
+    for index in blocks..len {
+        buf[index] = buf[start + index] ^ mask[index & 3];
+    }
 }
 
 #[cfg(test)]
@@ -82,4 +181,89 @@ mod tests {
             assert_eq!(payload, expected);
         }
     }
+
+    #[test]
+    fn copy_masked_matches_unmask() {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let src: std::vec::Vec<u8> = (0..67).collect();
+
+        for len in [0, 1, 3, 7, 8, 9, 16, 31, 67] {
+            let mut expected = src[..len].to_vec();
+            unmask_easy(&mut expected, mask);
+
+            let mut dst = [0xFFu8; 80];
+            copy_masked(&mut dst, &src[..len], mask, 0);
+
+            assert_eq!(&dst[..len], &expected[..]);
+            assert!(dst[len..].iter().all(|byte| *byte == 0xFF));
+        }
+    }
+
+    #[test]
+    fn copy_masked_in_parts() {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let src: std::vec::Vec<u8> = (0..67).collect();
+
+        let mut expected = src.clone();
+        unmask_easy(&mut expected, mask);
+
+        for split in 0..src.len() {
+            let (first, second) = src.split_at(split);
+
+            let mut dst = [0u8; 67];
+            copy_masked(&mut dst, first, mask, 0);
+            copy_masked(&mut dst[split..], second, mask, split);
+
+            assert_eq!(&dst[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn copy_within_masked_matches_unmask() {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+
+        for start in 0..16 {
+            for len in [0, 1, 7, 8, 9, 33] {
+                let mut buf = [0u8; 64];
+
+                for (i, v) in buf.iter_mut().enumerate() {
+                    *v = i as u8;
+                }
+
+                let mut expected = buf[start..start + len].to_vec();
+                unmask_easy(&mut expected, mask);
+
+                copy_within_masked(&mut buf, start, len, mask);
+
+                assert_eq!(&buf[..len], &expected[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_easy_at_any_offset() {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut buf = [0u8; 67];
+
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+
+        // Covers every alignment of the 8-byte blocks and the 16-byte vectors.
+        for offset in 0..32 {
+            let mut fast = buf;
+            let mut easy = buf;
+
+            unmask(&mut fast[offset..], mask);
+            unmask_easy(&mut easy[offset..], mask);
+
+            assert_eq!(fast, easy);
+
+            let mut fallback = buf;
+
+            unmask_fallback(&mut fallback[offset..], mask);
+
+            assert_eq!(fallback, easy);
+        }
+    }
 }
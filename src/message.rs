@@ -1,10 +1,18 @@
-use crate::{CloseFrame, Frame, OpCode, error::FragmentationError, fragments::FragmentsIterator};
+use crate::{
+    CloseFrame, Frame, OpCode, codec::PayloadWriter, error::FragmentationError,
+    fragments::FragmentsIterator,
+};
 
 /// A WebSocket message.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Message<'a> {
     /// A text WebSocket message
     Text(&'a str),
+    /// A text WebSocket message whose payload was not validated as UTF-8,
+    /// see [`WebSocket::with_text_validation`](crate::WebSocket::with_text_validation).
+    ///
+    /// Sent as a text message without validation.
+    TextUnchecked(&'a [u8]),
     /// A binary WebSocket message
     Binary(&'a [u8]),
     /// A ping message with the specified payload
@@ -20,9 +28,9 @@ pub enum Message<'a> {
 }
 
 impl<'a> Message<'a> {
-    /// Indicates whether a message is a text message.
+    /// Indicates whether a message is a text message, validated or not.
     pub const fn is_text(&self) -> bool {
-        matches!(*self, Message::Text(_))
+        matches!(*self, Message::Text(_) | Message::TextUnchecked(_))
     }
 
     /// Indicates whether a message is a binary message.
@@ -47,7 +55,7 @@ impl<'a> Message<'a> {
 
     pub(crate) const fn opcode(&self) -> OpCode {
         match self {
-            Message::Text(_) => OpCode::Text,
+            Message::Text(_) | Message::TextUnchecked(_) => OpCode::Text,
             Message::Binary(_) => OpCode::Binary,
             Message::Ping(_) => OpCode::Ping,
             Message::Pong(_) => OpCode::Pong,
@@ -59,6 +67,7 @@ impl<'a> Message<'a> {
     pub(crate) const fn payload_len(&self) -> usize {
         match self {
             Message::Text(payload) => payload.len(),
+            Message::TextUnchecked(payload) => payload.len(),
             Message::Binary(payload) => payload.len(),
             Message::Ping(payload) => payload.len(),
             Message::Pong(payload) => payload.len(),
@@ -67,35 +76,21 @@ impl<'a> Message<'a> {
         }
     }
 
-    pub(crate) fn write(&self, dst: &mut [u8]) -> Option<usize> {
-        if dst.len() < self.payload_len() {
-            return None;
-        }
-
+    pub(crate) fn write(&self, payload: &mut PayloadWriter<'_>) -> Option<()> {
         match self {
-            Message::Text(payload) => {
-                dst[..payload.len()].copy_from_slice(payload.as_bytes());
-            }
-            Message::Binary(payload) => {
-                dst[..payload.len()].copy_from_slice(payload);
-            }
-            Message::Ping(payload) => {
-                dst[..payload.len()].copy_from_slice(payload);
-            }
-            Message::Pong(payload) => {
-                dst[..payload.len()].copy_from_slice(payload);
-            }
+            Message::Text(text) => payload.write(text.as_bytes()),
+            Message::TextUnchecked(bytes) => payload.write(bytes),
+            Message::Binary(bytes) => payload.write(bytes),
+            Message::Ping(bytes) => payload.write(bytes),
+            Message::Pong(bytes) => payload.write(bytes),
             Message::Close(Some(frame)) => {
                 let code: u16 = frame.code().into_u16();
-                let code = code.to_be_bytes();
 
-                dst[0..2].copy_from_slice(&code);
-                dst[2..2 + frame.reason().len()].copy_from_slice(frame.reason().as_bytes());
+                payload.write(&code.to_be_bytes())?;
+                payload.write(frame.reason().as_bytes())
             }
-            Message::Close(None) => {}
+            Message::Close(None) => Some(()),
         }
-
-        Some(self.payload_len())
     }
 
     /// See [`FragmentsIterator::new()`] for details on how to create the iterator.
@@ -113,6 +108,9 @@ impl<'a> Message<'a> {
                 payload.as_bytes(),
                 fragment_size,
             )),
+            Message::TextUnchecked(payload) => {
+                Ok(FragmentsIterator::new(OpCode::Text, payload, fragment_size))
+            }
             Message::Binary(payload) => Ok(FragmentsIterator::new(
                 OpCode::Binary,
                 payload,
@@ -122,3 +120,143 @@ impl<'a> Message<'a> {
         }
     }
 }
+
+/// A received WebSocket message with mutable access to its payload.
+///
+/// The payload borrows the read or fragments buffer of the [`WebSocket`](crate::WebSocket),
+/// allowing it to be transformed in place (e.g. decrypted) without copying it into an extra buffer.
+///
+/// See [`next_mut!`](crate::next_mut).
+#[derive(Debug)]
+pub enum MessageMut<'a> {
+    /// A text WebSocket message
+    Text(&'a mut str),
+    /// A text WebSocket message whose payload was not validated as UTF-8, see [`Message::TextUnchecked`].
+    TextUnchecked(&'a mut [u8]),
+    /// A binary WebSocket message
+    Binary(&'a mut [u8]),
+    /// A ping message with the specified payload
+    Ping(&'a mut [u8]),
+    /// A pong message with the specified payload
+    Pong(&'a mut [u8]),
+    /// A close message with the optional close frame.
+    Close(Option<CloseFrame<'a>>),
+}
+
+impl<'a> MessageMut<'a> {
+    /// Indicates whether a message is a text message, validated or not.
+    pub const fn is_text(&self) -> bool {
+        matches!(*self, MessageMut::Text(_) | MessageMut::TextUnchecked(_))
+    }
+
+    /// Indicates whether a message is a binary message.
+    pub const fn is_binary(&self) -> bool {
+        matches!(*self, MessageMut::Binary(_))
+    }
+
+    /// Indicates whether a message is a ping message.
+    pub const fn is_ping(&self) -> bool {
+        matches!(*self, MessageMut::Ping(_))
+    }
+
+    /// Indicates whether a message is a pong message.
+    pub const fn is_pong(&self) -> bool {
+        matches!(*self, MessageMut::Pong(_))
+    }
+
+    /// Indicates whether a message is a close message.
+    pub const fn is_close(&self) -> bool {
+        matches!(*self, MessageMut::Close(_))
+    }
+
+    /// Takes the payload of a binary message, handing any other message back.
+    ///
+    /// The payload is a view into the read or fragments buffer, it is not copied.
+    /// It is only valid until the next read, which reuses the buffer, so protocols mutating received payloads
+    /// (e.g. decrypting them in place) must be done with it before reading again.
+    pub fn take_binary_in_place(self) -> Result<&'a mut [u8], Self> {
+        match self {
+            MessageMut::Binary(payload) => Ok(payload),
+            message => Err(message),
+        }
+    }
+
+    /// Converts the [`MessageMut`] into a [`Message`].
+    pub fn into_message(self) -> Message<'a> {
+        match self {
+            MessageMut::Text(payload) => Message::Text(payload),
+            MessageMut::TextUnchecked(payload) => Message::TextUnchecked(payload),
+            MessageMut::Binary(payload) => Message::Binary(payload),
+            MessageMut::Ping(payload) => Message::Ping(payload),
+            MessageMut::Pong(payload) => Message::Pong(payload),
+            MessageMut::Close(frame) => Message::Close(frame),
+        }
+    }
+}
+
+impl<'a> From<MessageMut<'a>> for Message<'a> {
+    fn from(message: MessageMut<'a>) -> Self {
+        message.into_message()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CloseCode, CloseFrame};
+
+    use super::*;
+
+    #[test]
+    fn eq() {
+        let message = Message::Close(Some(CloseFrame::new(CloseCode::Normal, "bye")));
+        let copy = message;
+
+        assert_eq!(message, copy);
+        assert_eq!(
+            message.clone(),
+            Message::Close(Some(CloseFrame::new(CloseCode::Normal, "bye")))
+        );
+        assert_ne!(
+            message,
+            Message::Close(Some(CloseFrame::no_reason(CloseCode::Normal)))
+        );
+        assert_ne!(Message::Text("Hello"), Message::Binary(b"Hello"));
+
+        let mut payload = *b"Hello";
+
+        assert_eq!(
+            Message::from(MessageMut::Binary(&mut payload)),
+            Message::Binary(b"Hello")
+        );
+    }
+
+    #[test]
+    fn text_unchecked() {
+        let message = Message::TextUnchecked(&[0xFF]);
+
+        assert!(message.is_text() && !message.is_binary());
+        assert_eq!(message.opcode(), OpCode::Text);
+        assert_ne!(message, Message::Binary(&[0xFF]));
+    }
+
+    #[test]
+    fn take_binary_in_place() {
+        let mut payload = *b"Hello";
+
+        let taken = MessageMut::Binary(&mut payload)
+            .take_binary_in_place()
+            .expect("Binary message");
+
+        taken.make_ascii_uppercase();
+
+        assert_eq!(&payload, b"HELLO");
+
+        let mut payload = *b"Hello";
+
+        let message = MessageMut::Ping(&mut payload)
+            .take_binary_in_place()
+            .expect_err("Ping message");
+
+        assert!(message.is_ping());
+    }
+}
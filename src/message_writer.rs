@@ -0,0 +1,184 @@
+use embedded_io_async::Write;
+use framez::state::WriteState;
+
+use crate::{
+    CloseSignal, ConnectionState, Frame, OpCode,
+    codec::{FramesCodec, Staged},
+    error::{Error, FrameEncodeError, WriteError},
+    rng::RngCore,
+};
+
+/// Sends a text or binary message incrementally, as a sequence of fragments.
+///
+/// Created with [`WebSocket::send_text_stream`](crate::WebSocket::send_text_stream), [`WebSocket::send_binary_stream`](crate::WebSocket::send_binary_stream)
+/// or the corresponding methods of [`WebSocketWrite`](crate::WebSocketWrite).
+///
+/// Every chunk passed to [`MessageWriter::write`] is sent right away as one or more non-final fragments,
+/// split to fit into the write buffer, so messages much larger than the write buffer can be sent without holding them in memory.
+/// [`MessageWriter::finish`] sends the final fragment.
+///
+/// # Note
+///
+/// A [`MessageWriter`] dropped before [`MessageWriter::finish`] leaves the message incomplete.
+/// The peer then rejects the next data message, so the connection should be closed.
+///
+/// The chunks of a text message may split UTF-8 sequences, but the message as a whole must be valid UTF-8.
+///
+/// # Example
+///
+/// ```
+/// # async fn run<Rng: websocketz::rng::RngCore>(mut websocketz: websocketz::WebSocket<'_, websocketz::mock::Noop, Rng>) {
+/// # async fn read_flash(_: u64, _: &mut [u8]) -> usize { 0 }
+/// let mut writer = websocketz.send_binary_stream();
+///
+/// let mut chunk = [0u8; 512];
+/// let mut offset = 0;
+///
+/// loop {
+///     let len = read_flash(offset, &mut chunk).await;
+///
+///     if len == 0 {
+///         break;
+///     }
+///
+///     writer.write(&chunk[..len]).await.expect("Failed to send chunk");
+///
+///     offset += len as u64;
+/// }
+///
+/// writer.finish(&[]).await.expect("Failed to finish message");
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use = "the message is incomplete until `finish` is called"]
+pub struct MessageWriter<'a, 'buf, RW, Rng> {
+    codec: &'a mut FramesCodec<Rng>,
+    inner: &'a mut RW,
+    write: &'a mut WriteState<'buf>,
+    state: &'a mut ConnectionState,
+    close_signal: Option<&'buf CloseSignal>,
+    /// The opcode of the next fragment, [`OpCode::Continuation`] once the first one is sent.
+    opcode: OpCode,
+}
+
+impl<'a, 'buf, RW, Rng> MessageWriter<'a, 'buf, RW, Rng> {
+    pub(crate) const fn new(
+        codec: &'a mut FramesCodec<Rng>,
+        inner: &'a mut RW,
+        write: &'a mut WriteState<'buf>,
+        state: &'a mut ConnectionState,
+        close_signal: Option<&'buf CloseSignal>,
+        opcode: OpCode,
+    ) -> Self {
+        Self {
+            codec,
+            inner,
+            write,
+            state,
+            close_signal,
+            opcode,
+        }
+    }
+
+    /// Returns `true` if no fragment has been sent yet.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        !matches!(self.opcode, OpCode::Continuation)
+    }
+
+    /// Sends `chunk` as one or more non-final fragments.
+    ///
+    /// Empty chunks are not sent.
+    ///
+    /// # Errors
+    /// - [`WriteError::ConnectionClosed`]: The connection is closed.
+    /// - [`WriteError::WriteFrame`]: The write buffer can not hold a frame header and at least one payload byte, or writing failed.
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        let fragment_size = self.fragment_size()?;
+
+        for fragment in chunk.chunks(fragment_size) {
+            self.send(false, fragment).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends `chunk` as the final fragment, completing the message.
+    ///
+    /// `chunk` may be empty. A message without previous fragments is sent as a single frame.
+    ///
+    /// # Errors
+    /// See [`MessageWriter::write`].
+    pub async fn finish(mut self, chunk: &[u8]) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        let fragment_size = self.fragment_size()?;
+
+        let mut fragments = chunk.chunks(fragment_size);
+        let last = fragments.next_back().unwrap_or_default();
+
+        for fragment in fragments {
+            self.send(false, fragment).await?;
+        }
+
+        self.send(true, last).await
+    }
+
+    /// Returns the maximum payload length of a fragment fitting into the write buffer.
+    fn fragment_size(&self) -> Result<usize, Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        match self.write.buffer.len().saturating_sub(Staged::OFFSET) {
+            0 => Err(Error::Write(WriteError::WriteFrame(
+                framez::WriteError::Encode(FrameEncodeError::BufferTooSmall),
+            ))),
+            size => Ok(size),
+        }
+    }
+
+    async fn send(&mut self, fin: bool, payload: &[u8]) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        // Control frames may be injected in the middle of a fragmented message.
+        crate::functions::send_requested_close(
+            self.codec,
+            self.inner,
+            self.write,
+            self.state,
+            self.close_signal,
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))?;
+
+        if self.state.closed {
+            return Err(Error::Write(WriteError::ConnectionClosed));
+        }
+
+        crate::functions::send_frame(
+            self.write,
+            self.codec,
+            self.inner,
+            self.state,
+            Frame::new(fin, self.opcode, payload),
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))?;
+
+        self.opcode = OpCode::Continuation;
+
+        Ok(())
+    }
+}
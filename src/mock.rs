@@ -1,10 +1,29 @@
-//! Noop implementation of embedded-io-async traits and rand-core for testing purposes.
+//! Mocks and helpers for testing code built on this library.
+//!
+//! [`FixedRng`] makes the handshake key and the masks of a client deterministic,
+//! so that golden handshake bytes can be asserted with [`connect_request`] without depending on the behavior of a particular RNG.
+//! [`render_connect_request`] takes the key directly, e.g. to snapshot-test the headers of [`ConnectOptions`]
+//! or to replay a request rejected by a server offline.
 
 use core::convert::Infallible;
 
 use embedded_io_async::{ErrorType, Read, Write};
-use rand_core::RngCore;
+#[cfg(feature = "client")]
+use framez::encode::Encoder;
 
+use crate::websocket_core::generate_sec_accept;
+#[cfg(feature = "client")]
+use crate::{
+    error::HttpEncodeError,
+    http::{OutRequest, OutRequestCodec},
+    options::ConnectOptions,
+    rng::RngCore,
+    websocket_core::{connect_request_headers, generate_sec_key},
+};
+
+/// Noop implementation of the `embedded-io-async` traits and [`RngCore`].
+///
+/// Reads fill the whole buffer without changing it, writes discard the bytes and the RNG only yields zeros.
 #[derive(Debug)]
 pub struct Noop;
 
@@ -24,6 +43,7 @@ impl Write for Noop {
     }
 }
 
+#[cfg(feature = "client")]
 impl RngCore for Noop {
     fn next_u32(&mut self) -> u32 {
         0
@@ -35,3 +55,186 @@ impl RngCore for Noop {
 
     fn fill_bytes(&mut self, _dst: &mut [u8]) {}
 }
+
+/// A deterministic RNG repeating a fixed sequence of bytes.
+///
+/// Yields only zeros if the sequence is empty.
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+#[derive(Debug, Clone)]
+pub struct FixedRng<'a> {
+    seq: &'a [u8],
+    pos: usize,
+}
+
+#[cfg(feature = "client")]
+impl<'a> FixedRng<'a> {
+    /// Creates a new [`FixedRng`] starting at the beginning of `seq`.
+    pub const fn new(seq: &'a [u8]) -> Self {
+        Self { seq, pos: 0 }
+    }
+}
+
+#[cfg(feature = "client")]
+impl RngCore for FixedRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+
+        self.fill_bytes(&mut bytes);
+
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+
+        self.fill_bytes(&mut bytes);
+
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        if self.seq.is_empty() {
+            dst.fill(0);
+
+            return;
+        }
+
+        for byte in dst {
+            *byte = self.seq[self.pos];
+
+            self.pos = (self.pos + 1) % self.seq.len();
+        }
+    }
+}
+
+/// Returns the `Sec-WebSocket-Key` a client generates with `rng`.
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub fn sec_key<Rng: RngCore>(rng: &mut Rng) -> [u8; 24] {
+    generate_sec_key(rng)
+}
+
+/// Returns the `Sec-WebSocket-Accept` value a server answers `sec_key` with.
+pub fn sec_accept(sec_key: &[u8]) -> [u8; 28] {
+    generate_sec_accept(sec_key)
+}
+
+/// Writes the exact handshake request a client connecting with `options` and `rng` sends into `dst`, returning the number of bytes written.
+///
+/// Consumes the same random bytes as the client, so `rng` must be in the state the client's RNG is in when connecting.
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub fn connect_request<Rng: RngCore>(
+    options: ConnectOptions<'_, '_>,
+    rng: &mut Rng,
+    dst: &mut [u8],
+) -> Result<usize, HttpEncodeError> {
+    render_connect_request(&options, &generate_sec_key(rng), dst)
+}
+
+/// Writes the exact handshake request a client connecting with `options` sends into `dst`, using `sec_key` as its `Sec-WebSocket-Key`.
+///
+/// Returns the number of bytes written. No I/O is performed, [`DynamicHeaders`](crate::http::DynamicHeaders) are invoked as when connecting.
+///
+/// ```
+/// use websocketz::{http::Header, mock::render_connect_request, options::ConnectOptions};
+///
+/// let headers = &[Header {
+///     name: "Host",
+///     value: b"localhost",
+/// }];
+///
+/// let options = ConnectOptions::new("/chat")
+///     .expect("Valid path")
+///     .with_headers(headers)
+///     .expect("Headers not reserved")
+///     .with_protocols(&["chat"]);
+///
+/// let mut request = [0u8; 256];
+/// let len = render_connect_request(&options, b"dGhlIHNhbXBsZSBub25jZQ==", &mut request)
+///     .expect("Buffer large enough");
+///
+/// assert!(request[..len].starts_with(b"GET /chat HTTP/1.1\r\n"));
+/// ```
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub fn render_connect_request(
+    options: &ConnectOptions<'_, '_>,
+    sec_key: &[u8],
+    dst: &mut [u8],
+) -> Result<usize, HttpEncodeError> {
+    let (headers, len) = connect_request_headers(options, sec_key);
+    let headers = &headers[..len];
+
+    let request = OutRequest::get_unchecked(
+        options.path,
+        headers,
+        options.headers,
+        options.dynamic_headers,
+        options.protocols,
+    );
+
+    OutRequestCodec::new().encode(request, dst)
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_rng_repeats() {
+        let mut rng = FixedRng::new(&[1, 2, 3]);
+
+        let mut bytes = [0; 7];
+        rng.fill_bytes(&mut bytes);
+
+        assert_eq!(bytes, [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(rng.next_u32(), u32::from_le_bytes([2, 3, 1, 2]));
+
+        assert_eq!(FixedRng::new(&[]).next_u64(), 0);
+    }
+
+    #[test]
+    fn sec_key_and_accept() {
+        // The example of RFC 6455, section 1.3.
+        let key = sec_key(&mut FixedRng::new(b"the sample nonce"));
+
+        assert_eq!(&key, b"dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(&sec_accept(&key), b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn render_request() {
+        const SEC_KEY: &[u8] = b"dGhlIHNhbXBsZSBub25jZQ==";
+
+        let options = ConnectOptions::new_unchecked("/chat").with_protocols(&["chat", "v2"]);
+
+        let dst = &mut [0u8; 256];
+        let len = render_connect_request(&options, SEC_KEY, dst).expect("Buffer large enough");
+
+        assert_eq!(
+            core::str::from_utf8(&dst[..len]).expect("Valid request"),
+            "GET /chat HTTP/1.1\r\n\
+            upgrade: websocket\r\n\
+            connection: upgrade\r\n\
+            sec-websocket-version: 13\r\n\
+            sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            sec-websocket-protocol: chat\r\n\
+            sec-websocket-protocol: v2\r\n\
+            \r\n"
+        );
+
+        // Same bytes as the request sent with the key generated by the RNG.
+        let sent = &mut [0u8; 256];
+        let sent_len = connect_request(options, &mut FixedRng::new(b"the sample nonce"), sent)
+            .expect("Buffer large enough");
+
+        assert_eq!(&sent[..sent_len], &dst[..len]);
+
+        assert!(matches!(
+            render_connect_request(&options, SEC_KEY, &mut [0u8; 16]),
+            Err(HttpEncodeError::BufferTooSmall)
+        ));
+    }
+}
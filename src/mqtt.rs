@@ -0,0 +1,60 @@
+//! MQTT over WebSocket.
+//!
+//! [`MqttStream`] exposes the binary messages of a [`WebSocket`](crate::WebSocket) as an [`embedded_io_async::Read`] + [`embedded_io_async::Write`] byte stream,
+//! so that MQTT clients taking a transport, e.g. `rust-mqtt` or `minimq`, run over a WebSocket connection unchanged,
+//! as required by brokers only reachable over HTTPS ports, e.g. AWS IoT Core or Azure IoT Hub.
+//!
+//! MQTT control packets may span several messages and a message may carry several packets (MQTT 5.0, section 6),
+//! which is exactly the behavior of a [`ByteStream`].
+//!
+//! The client must offer the `mqtt` subprotocol, see [`PROTOCOLS`].
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # async fn mqtt() {
+//! # use websocketz::mock::Noop;
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{WebSocket, mqtt::{self, MqttStream}, options::ConnectOptions};
+//!
+//! # let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+//! let websocketz = WebSocket::connect::<16>(
+//!     ConnectOptions::default()
+//!         .with_path("/mqtt")
+//!         .expect("Valid path")
+//!         .with_protocols(mqtt::PROTOCOLS),
+//!     Noop,
+//!     Noop,
+//!     read_buffer,
+//!     write_buffer,
+//!     fragments_buffer,
+//! )
+//! .await
+//! .expect("Handshake failed");
+//!
+//! // The server must select the `mqtt` subprotocol.
+//! assert_eq!(websocketz.protocol(), Some(0));
+//!
+//! // Holds the part of a message not read yet.
+//! let pending = &mut [0u8; 1024];
+//!
+//! let mut stream = MqttStream::new(websocketz).with_pending_buffer(pending);
+//!
+//! // Pass `stream` to the MQTT client.
+//! stream.write_all(&[0xC0, 0x00]).await.expect("Failed to send PINGREQ");
+//! stream.flush().await.expect("Failed to flush");
+//!
+//! let mut packet = [0u8; 2];
+//! stream.read_exact(&mut packet).await.expect("Failed to receive PINGRESP");
+//! # }
+//! ```
+
+use crate::stream::ByteStream;
+
+/// The subprotocols to offer with [`ConnectOptions::with_protocols`](crate::options::ConnectOptions::with_protocols)
+/// or to support with [`AcceptOptions::with_protocols`](crate::options::AcceptOptions::with_protocols).
+pub const PROTOCOLS: &[&str] = &["mqtt"];
+
+/// A [`WebSocket`](crate::WebSocket) as an MQTT transport.
+///
+/// See the [module](crate::mqtt) documentation.
+pub type MqttStream<'buf, RW, Rng> = ByteStream<'buf, RW, Rng>;
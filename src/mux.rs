@@ -0,0 +1,1040 @@
+//! Multiplexing of logical channels over the binary messages of a single connection.
+//!
+//! Lets several independent streams, e.g. control, telemetry and firmware updates, share one [`WebSocket`]
+//! instead of opening a TCP connection each. Both peers must agree on this framing, e.g. through a subprotocol.
+//!
+//! Every frame is a single binary message starting with a 1-byte kind and the 2-byte big-endian channel id:
+//!
+//! - `Open` (`1`): Opens the channel, followed by the 4-byte big-endian credits granted to the peer.
+//! - `Data` (`2`): Followed by the payload.
+//! - `Credit` (`3`): Grants more credits to the peer, followed by the 4-byte big-endian credits.
+//! - `Close` (`4`): Closes the channel.
+//!
+//! A channel is established once both peers have opened it. Credits are counted in payload bytes:
+//! a peer may only send as many bytes of data as the other peer granted it, so a slow stream can not fill the buffers of the others.
+//!
+//! Frames are sent through a [`MessageSink`], e.g. a [`WebSocket`], a [`WebSocketWrite`]
+//! or a [`MuxChannel`], and received binary messages are passed to [`Mux::receive`],
+//! or read from a [`MessageSource`], e.g. a [`WebSocket`], a [`WebSocketRead`] or a [`MuxChannel`], with [`Mux::receive_from`].
+//!
+//! ```
+//! # async fn mux() {
+//! # use websocketz::mock::Noop;
+//! use websocketz::{Message, WebSocket, mux::{MessageSink, Mux, MuxFrame}, next};
+//!
+//! # let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+//! let mut websocketz = WebSocket::server(Noop, Noop, read_buffer, write_buffer, fragments_buffer);
+//!
+//! // Up to 4 channels.
+//! let mut mux = Mux::<4>::new();
+//!
+//! const TELEMETRY: u16 = 1;
+//!
+//! // Let the peer send up to 512 bytes on the telemetry channel.
+//! mux.open(&mut websocketz, TELEMETRY, 512).await.expect("Failed to open");
+//!
+//! while let Some(Ok(Message::Binary(payload))) = next!(websocketz) {
+//!     match mux.receive(payload) {
+//!         // The peer opened the channel too.
+//!         Ok(MuxFrame::Open { channel: TELEMETRY, .. }) => {
+//!             mux.channel(&mut websocketz, TELEMETRY)
+//!                 .send_binary(b"hello")
+//!                 .await
+//!                 .expect("Failed to send");
+//!         }
+//!         Ok(MuxFrame::Data { channel: TELEMETRY, payload }) => {
+//!             // Process the payload, then let the peer send more.
+//!             let processed = payload.len() as u32;
+//!
+//!             mux.grant(&mut websocketz, TELEMETRY, processed)
+//!                 .await
+//!                 .expect("Failed to grant");
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! # }
+//! ```
+
+use embedded_io_async::{Read, Write};
+
+use crate::{
+    Message, WebSocket, WebSocketRead, WebSocketWrite,
+    error::{Error, MessageError, MuxError, MuxReceiveError, MuxSendError},
+    rng::RngCore,
+};
+
+const OPEN: u8 = 1;
+const DATA: u8 = 2;
+const CREDIT: u8 = 3;
+const CLOSE: u8 = 4;
+
+/// Length of the kind and the channel id.
+const HEADER_LEN: usize = 3;
+
+/// A destination of binary messages.
+pub trait MessageSink {
+    /// The error returned when a message can not be sent.
+    type Error;
+
+    /// Sends a binary message of `len` bytes, written by `write` directly into the sink.
+    fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        F: FnOnce(&mut [u8]);
+
+    /// Sends a binary message.
+    fn send_binary(&mut self, payload: &[u8]) -> impl Future<Output = Result<(), Self::Error>> {
+        self.send_binary_with(payload.len(), |dst| dst.copy_from_slice(payload))
+    }
+}
+
+impl<RW, Rng> MessageSink for WebSocket<'_, RW, Rng>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    type Error = Error<RW::Error>;
+
+    fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        WebSocket::send_binary_with(self, len, write)
+    }
+}
+
+impl<RW, Rng> MessageSink for WebSocketWrite<'_, RW, Rng>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    type Error = Error<RW::Error>;
+
+    fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        WebSocketWrite::send_binary_with(self, len, write)
+    }
+}
+
+/// A source of binary messages.
+pub trait MessageSource {
+    /// The error returned when a message can not be received.
+    type Error;
+
+    /// Receives the next binary message and passes its payload to `f`.
+    ///
+    /// Other messages are skipped.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A binary message was received and passed to `f`.
+    /// - `Some(Err(Self::Error))`: An error occurred while receiving. The caller should stop receiving,
+    ///   unless the source documents the error as resumable, e.g. [`MessageError::ReadsPaused`](crate::error::MessageError::ReadsPaused).
+    /// - `None`: The source is exhausted, e.g. the connection was closed.
+    fn with_next_binary<F, T>(
+        &mut self,
+        f: F,
+    ) -> impl Future<Output = Option<Result<T, Self::Error>>>
+    where
+        F: FnOnce(&[u8]) -> T;
+}
+
+impl<RW, Rng> MessageSource for WebSocket<'_, RW, Rng>
+where
+    RW: Read + Write,
+    Rng: RngCore,
+{
+    type Error = MessageError<RW::Error>;
+
+    async fn with_next_binary<F, T>(&mut self, f: F) -> Option<Result<T, Self::Error>>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
+        let mut f = Some(f);
+
+        loop {
+            let result = self
+                .with_next(|message| match message {
+                    Message::Binary(payload) => f.take().map(|f| f(payload)),
+                    _ => None,
+                })
+                .await?;
+
+            match result {
+                Ok(Some(value)) => return Some(Ok(value)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl<RW> MessageSource for WebSocketRead<'_, RW>
+where
+    RW: Read,
+{
+    type Error = MessageError<RW::Error>;
+
+    async fn with_next_binary<F, T>(&mut self, f: F) -> Option<Result<T, Self::Error>>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
+        let mut f = Some(f);
+
+        loop {
+            let result = self
+                .with_next(|message| match message {
+                    Message::Binary(payload) => f.take().map(|f| f(payload)),
+                    _ => None,
+                })
+                .await?;
+
+            match result {
+                Ok(Some(value)) => return Some(Ok(value)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// A frame of a [`Mux`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuxFrame<'a> {
+    /// Opens a channel.
+    Open {
+        /// The channel id.
+        channel: u16,
+        /// The credits granted to the receiver of the frame.
+        credits: u32,
+    },
+    /// Data of a channel.
+    Data {
+        /// The channel id.
+        channel: u16,
+        /// The payload.
+        payload: &'a [u8],
+    },
+    /// Grants more credits to the receiver of the frame.
+    Credit {
+        /// The channel id.
+        channel: u16,
+        /// The credits granted to the receiver of the frame.
+        credits: u32,
+    },
+    /// Closes a channel.
+    Close {
+        /// The channel id.
+        channel: u16,
+    },
+}
+
+impl<'a> MuxFrame<'a> {
+    /// Parses a frame from the payload of a binary message.
+    pub fn parse(src: &'a [u8]) -> Result<Self, MuxError> {
+        let (&[kind, high, low], body) = src
+            .split_first_chunk::<HEADER_LEN>()
+            .ok_or(MuxError::Malformed)?;
+        let channel = u16::from_be_bytes([high, low]);
+
+        let credits = || {
+            <[u8; 4]>::try_from(body)
+                .map(u32::from_be_bytes)
+                .map_err(|_| MuxError::Malformed)
+        };
+
+        match kind {
+            OPEN => Ok(Self::Open {
+                channel,
+                credits: credits()?,
+            }),
+            DATA => Ok(Self::Data {
+                channel,
+                payload: body,
+            }),
+            CREDIT => Ok(Self::Credit {
+                channel,
+                credits: credits()?,
+            }),
+            CLOSE if body.is_empty() => Ok(Self::Close { channel }),
+            _ => Err(MuxError::Malformed),
+        }
+    }
+
+    /// Returns the channel id of the frame.
+    pub const fn channel(&self) -> u16 {
+        match self {
+            Self::Open { channel, .. }
+            | Self::Data { channel, .. }
+            | Self::Credit { channel, .. }
+            | Self::Close { channel } => *channel,
+        }
+    }
+
+    /// Returns the number of bytes of the encoded frame.
+    pub const fn encoded_len(&self) -> usize {
+        match self {
+            Self::Open { .. } | Self::Credit { .. } => HEADER_LEN + 4,
+            Self::Data { payload, .. } => HEADER_LEN + payload.len(),
+            Self::Close { .. } => HEADER_LEN,
+        }
+    }
+
+    /// Encodes the frame into `dst`, returning the number of bytes written.
+    ///
+    /// Returns `None` if `dst` is too small.
+    pub fn encode(&self, dst: &mut [u8]) -> Option<usize> {
+        let len = self.encoded_len();
+        let dst = dst.get_mut(..len)?;
+
+        let (header, body) = dst.split_at_mut(HEADER_LEN);
+
+        match self {
+            Self::Open { channel, credits } => {
+                write_header(header, OPEN, *channel);
+                body.copy_from_slice(&credits.to_be_bytes());
+            }
+            Self::Data { channel, payload } => {
+                write_header(header, DATA, *channel);
+                body.copy_from_slice(payload);
+            }
+            Self::Credit { channel, credits } => {
+                write_header(header, CREDIT, *channel);
+                body.copy_from_slice(&credits.to_be_bytes());
+            }
+            Self::Close { channel } => write_header(header, CLOSE, *channel),
+        }
+
+        Some(len)
+    }
+}
+
+fn write_header(dst: &mut [u8], kind: u8, channel: u16) {
+    dst[0] = kind;
+    dst[1..HEADER_LEN].copy_from_slice(&channel.to_be_bytes());
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    id: u16,
+    /// Opened by this side.
+    local_open: bool,
+    /// Opened by the peer.
+    remote_open: bool,
+    /// Bytes this side may still send.
+    send_credits: u32,
+    /// Bytes the peer may still send.
+    recv_credits: u32,
+}
+
+impl Channel {
+    const fn new(id: u16) -> Self {
+        Self {
+            id,
+            local_open: false,
+            remote_open: false,
+            send_credits: 0,
+            recv_credits: 0,
+        }
+    }
+
+    const fn is_established(&self) -> bool {
+        self.local_open && self.remote_open
+    }
+}
+
+/// The state of up to `N` multiplexed channels.
+///
+/// See the [module](crate::mux) documentation.
+#[derive(Debug, Clone)]
+pub struct Mux<const N: usize> {
+    channels: [Option<Channel>; N],
+}
+
+impl<const N: usize> Default for Mux<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Mux<N> {
+    /// Creates a new [`Mux`] without any channels.
+    pub const fn new() -> Self {
+        Self {
+            channels: [None; N],
+        }
+    }
+
+    fn index(&self, channel: u16) -> Option<usize> {
+        self.channels
+            .iter()
+            .position(|slot| matches!(slot, Some(slot) if slot.id == channel))
+    }
+
+    fn get(&self, channel: u16) -> Option<&Channel> {
+        self.channels
+            .iter()
+            .flatten()
+            .find(|slot| slot.id == channel)
+    }
+
+    fn get_mut(&mut self, channel: u16) -> Result<&mut Channel, MuxError> {
+        self.channels
+            .iter_mut()
+            .flatten()
+            .find(|slot| slot.id == channel)
+            .ok_or(MuxError::UnknownChannel(channel))
+    }
+
+    /// Returns the slot index of `channel`, or of a vacant slot.
+    fn index_or_vacant(&self, channel: u16) -> Result<usize, MuxError> {
+        self.index(channel)
+            .or_else(|| self.channels.iter().position(Option::is_none))
+            .ok_or(MuxError::TooManyChannels)
+    }
+
+    /// Returns `true` if both peers opened `channel`.
+    pub fn is_open(&self, channel: u16) -> bool {
+        self.get(channel).is_some_and(Channel::is_established)
+    }
+
+    /// Returns the number of bytes this side may still send on `channel`, if it exists.
+    pub fn send_credits(&self, channel: u16) -> Option<u32> {
+        self.get(channel).map(|slot| slot.send_credits)
+    }
+
+    /// Returns the number of bytes the peer may still send on `channel`, if it exists.
+    pub fn recv_credits(&self, channel: u16) -> Option<u32> {
+        self.get(channel).map(|slot| slot.recv_credits)
+    }
+
+    /// Opens `channel`, granting `credits` bytes to the peer.
+    ///
+    /// The channel is established once the peer opened it too, see [`MuxFrame::Open`].
+    pub async fn open<S: MessageSink>(
+        &mut self,
+        sink: &mut S,
+        channel: u16,
+        credits: u32,
+    ) -> Result<(), MuxSendError<S::Error>> {
+        let index = self.index_or_vacant(channel)?;
+
+        let mut slot = self.channels[index].unwrap_or(Channel::new(channel));
+
+        if slot.local_open {
+            return Err(MuxError::AlreadyOpen(channel).into());
+        }
+
+        send_frame(sink, MuxFrame::Open { channel, credits }).await?;
+
+        slot.local_open = true;
+        slot.recv_credits = credits;
+
+        self.channels[index] = Some(slot);
+
+        Ok(())
+    }
+
+    /// Grants `credits` more bytes to the peer on `channel`.
+    pub async fn grant<S: MessageSink>(
+        &mut self,
+        sink: &mut S,
+        channel: u16,
+        credits: u32,
+    ) -> Result<(), MuxSendError<S::Error>> {
+        let slot = self.get_mut(channel)?;
+
+        if !slot.local_open {
+            return Err(MuxError::NotOpen(channel).into());
+        }
+
+        send_frame(sink, MuxFrame::Credit { channel, credits }).await?;
+
+        slot.recv_credits = slot.recv_credits.saturating_add(credits);
+
+        Ok(())
+    }
+
+    /// Closes `channel`, releasing its slot.
+    ///
+    /// Frames the peer sent on the channel before receiving the Close frame are reported as [`MuxError::UnknownChannel`].
+    pub async fn close<S: MessageSink>(
+        &mut self,
+        sink: &mut S,
+        channel: u16,
+    ) -> Result<(), MuxSendError<S::Error>> {
+        let index = self
+            .index(channel)
+            .ok_or(MuxError::UnknownChannel(channel))?;
+
+        send_frame(sink, MuxFrame::Close { channel }).await?;
+
+        self.channels[index] = None;
+
+        Ok(())
+    }
+
+    /// Sends `payload` on the established `channel`, consuming as many credits.
+    pub async fn send<S: MessageSink>(
+        &mut self,
+        sink: &mut S,
+        channel: u16,
+        payload: &[u8],
+    ) -> Result<(), MuxSendError<S::Error>> {
+        self.send_with(sink, channel, payload.len(), |dst| {
+            dst.copy_from_slice(payload)
+        })
+        .await
+    }
+
+    /// Sends `len` bytes written by `write` on the established `channel`, consuming as many credits.
+    ///
+    /// Over a [`WebSocket`], the write buffer must be at least `17 + len` bytes long:
+    /// 14 bytes reserved for the frame header and 3 for the channel header, see [`WebSocket::send_binary_with`].
+    pub async fn send_with<S, F>(
+        &mut self,
+        sink: &mut S,
+        channel: u16,
+        len: usize,
+        write: F,
+    ) -> Result<(), MuxSendError<S::Error>>
+    where
+        S: MessageSink,
+        F: FnOnce(&mut [u8]),
+    {
+        let slot = self.get_mut(channel)?;
+
+        if !slot.is_established() {
+            return Err(MuxError::NotOpen(channel).into());
+        }
+
+        let available = slot.send_credits;
+        let len32 = u32::try_from(len)
+            .ok()
+            .filter(|&len| len <= available)
+            .ok_or(MuxError::NotEnoughCredits { channel, available })?;
+
+        sink.send_binary_with(HEADER_LEN + len, |dst| {
+            let (header, body) = dst.split_at_mut(HEADER_LEN);
+
+            write_header(header, DATA, channel);
+            write(body);
+        })
+        .await
+        .map_err(MuxSendError::Send)?;
+
+        slot.send_credits -= len32;
+
+        Ok(())
+    }
+
+    /// Returns a [`MuxChannel`] sending on `channel` through `sink`.
+    pub fn channel<'a, S>(&'a mut self, sink: &'a mut S, channel: u16) -> MuxChannel<'a, S, N> {
+        MuxChannel {
+            mux: self,
+            sink,
+            channel,
+        }
+    }
+
+    /// Parses the payload of a received binary message and updates the state of its channel.
+    ///
+    /// Returns the received frame:
+    /// - [`MuxFrame::Open`]: The peer opened the channel. Open it with [`Mux::open`], if not done already, to establish it.
+    /// - [`MuxFrame::Data`]: The peer sent data on an established channel. Grant more credits with [`Mux::grant`] once processed.
+    /// - [`MuxFrame::Credit`]: The peer granted more credits.
+    /// - [`MuxFrame::Close`]: The peer closed the channel, its slot is released.
+    pub fn receive<'a>(&mut self, payload: &'a [u8]) -> Result<MuxFrame<'a>, MuxError> {
+        let frame = MuxFrame::parse(payload)?;
+
+        match frame {
+            MuxFrame::Open { channel, credits } => {
+                let index = self.index_or_vacant(channel)?;
+                let slot = self.channels[index].get_or_insert(Channel::new(channel));
+
+                if slot.remote_open {
+                    return Err(MuxError::AlreadyOpen(channel));
+                }
+
+                slot.remote_open = true;
+                slot.send_credits = credits;
+            }
+            MuxFrame::Data { channel, payload } => {
+                let slot = self.get_mut(channel)?;
+
+                if !slot.is_established() {
+                    return Err(MuxError::NotOpen(channel));
+                }
+
+                slot.recv_credits = u32::try_from(payload.len())
+                    .ok()
+                    .and_then(|len| slot.recv_credits.checked_sub(len))
+                    .ok_or(MuxError::CreditsExceeded(channel))?;
+            }
+            MuxFrame::Credit { channel, credits } => {
+                let slot = self.get_mut(channel)?;
+
+                if !slot.remote_open {
+                    return Err(MuxError::NotOpen(channel));
+                }
+
+                slot.send_credits = slot.send_credits.saturating_add(credits);
+            }
+            MuxFrame::Close { channel } => {
+                let index = self
+                    .index(channel)
+                    .ok_or(MuxError::UnknownChannel(channel))?;
+
+                self.channels[index] = None;
+            }
+        }
+
+        Ok(frame)
+    }
+
+    /// Receives the next binary message from `source`, passes it to [`Mux::receive`] and the received frame to `f`.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A frame was received and passed to `f`.
+    /// - `Some(Err(MuxReceiveError::Mux))`: The received frame is invalid, see [`Mux::receive`].
+    /// - `Some(Err(MuxReceiveError::Receive))`: An error occurred while receiving, see [`MessageSource::with_next_binary`].
+    /// - `None`: The source is exhausted.
+    pub async fn receive_from<S, F, T>(
+        &mut self,
+        source: &mut S,
+        f: F,
+    ) -> Option<Result<T, MuxReceiveError<S::Error>>>
+    where
+        S: MessageSource,
+        F: FnOnce(MuxFrame<'_>) -> T,
+    {
+        let result = source
+            .with_next_binary(|payload| self.receive(payload).map(f))
+            .await?;
+
+        Some(match result {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => Err(MuxReceiveError::Mux(err)),
+            Err(err) => Err(MuxReceiveError::Receive(err)),
+        })
+    }
+}
+
+async fn send_frame<S: MessageSink>(
+    sink: &mut S,
+    frame: MuxFrame<'_>,
+) -> Result<(), MuxSendError<S::Error>> {
+    sink.send_binary_with(frame.encoded_len(), |dst| {
+        frame.encode(dst);
+    })
+    .await
+    .map_err(MuxSendError::Send)
+}
+
+/// A handle sending on a single channel of a [`Mux`].
+///
+/// Created by [`Mux::channel`]. Implements [`MessageSink`] and [`MessageSource`], so that a channel can be passed wherever a connection is expected,
+/// e.g. to multiplex another [`Mux`] over it.
+///
+/// As a [`MessageSource`], it receives from the wrapped source through the [`Mux`] and returns the data of its own channel.
+/// Frames opening, crediting or closing any channel are applied to the [`Mux`] and skipped,
+/// data of another channel fails with [`MuxError::OtherChannel`], so a channel read this way should own the connection,
+/// e.g. as the single channel carrying a nested [`Mux`].
+#[derive(Debug)]
+pub struct MuxChannel<'a, S, const N: usize> {
+    mux: &'a mut Mux<N>,
+    sink: &'a mut S,
+    channel: u16,
+}
+
+impl<S, const N: usize> MuxChannel<'_, S, N> {
+    /// Returns the channel id.
+    pub const fn id(&self) -> u16 {
+        self.channel
+    }
+
+    /// Returns the number of bytes this side may still send on the channel, if it exists.
+    pub fn send_credits(&self) -> Option<u32> {
+        self.mux.send_credits(self.channel)
+    }
+}
+
+impl<S: MessageSink, const N: usize> MessageSink for MuxChannel<'_, S, N> {
+    type Error = MuxSendError<S::Error>;
+
+    fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        self.mux.send_with(self.sink, self.channel, len, write)
+    }
+}
+
+impl<S: MessageSource, const N: usize> MessageSource for MuxChannel<'_, S, N> {
+    type Error = MuxReceiveError<S::Error>;
+
+    async fn with_next_binary<F, T>(&mut self, f: F) -> Option<Result<T, Self::Error>>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
+        let id = self.channel;
+        let mut f = Some(f);
+
+        loop {
+            let result = self
+                .mux
+                .receive_from(self.sink, |frame| match frame {
+                    MuxFrame::Data { channel, payload } if channel == id => {
+                        Ok(f.take().map(|f| f(payload)))
+                    }
+                    MuxFrame::Data { channel, .. } => Err(MuxError::OtherChannel(channel)),
+                    _ => Ok(None),
+                })
+                .await?;
+
+            match result {
+                Ok(Ok(Some(value))) => return Some(Ok(value)),
+                Ok(Ok(None)) => {}
+                Ok(Err(err)) => return Some(Err(MuxReceiveError::Mux(err))),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Collects the sent messages.
+    #[derive(Debug, Default)]
+    struct Sink {
+        messages: Vec<Vec<u8>>,
+    }
+
+    impl MessageSink for Sink {
+        type Error = Infallible;
+
+        async fn send_binary_with<F>(&mut self, len: usize, write: F) -> Result<(), Self::Error>
+        where
+            F: FnOnce(&mut [u8]),
+        {
+            let mut message = std::vec![0; len];
+
+            write(&mut message);
+
+            self.messages.push(message);
+
+            Ok(())
+        }
+    }
+
+    impl MessageSource for Sink {
+        type Error = Infallible;
+
+        async fn with_next_binary<F, T>(&mut self, f: F) -> Option<Result<T, Self::Error>>
+        where
+            F: FnOnce(&[u8]) -> T,
+        {
+            if self.messages.is_empty() {
+                return None;
+            }
+
+            Some(Ok(f(&self.messages.remove(0))))
+        }
+    }
+
+    /// Delivers the messages sent by `sink` to `mux`.
+    fn deliver<const N: usize>(sink: &mut Sink, mux: &mut Mux<N>) {
+        for message in sink.messages.drain(..) {
+            mux.receive(&message).expect("Valid frame");
+        }
+    }
+
+    /// Establishes `channel` between `a` and `b`, each granting `credits` to the other.
+    async fn establish<const N: usize>(a: &mut Mux<N>, b: &mut Mux<N>, channel: u16, credits: u32) {
+        let mut sink = Sink::default();
+
+        a.open(&mut sink, channel, credits)
+            .await
+            .expect("Failed to open");
+        deliver(&mut sink, b);
+
+        b.open(&mut sink, channel, credits)
+            .await
+            .expect("Failed to open");
+        deliver(&mut sink, a);
+    }
+
+    #[test]
+    fn encode_parse() {
+        let frames = [
+            MuxFrame::Open {
+                channel: 1,
+                credits: 1024,
+            },
+            MuxFrame::Data {
+                channel: 0xABCD,
+                payload: b"data",
+            },
+            MuxFrame::Data {
+                channel: 2,
+                payload: b"",
+            },
+            MuxFrame::Credit {
+                channel: 3,
+                credits: u32::MAX,
+            },
+            MuxFrame::Close { channel: 4 },
+        ];
+
+        for frame in frames {
+            let buf = &mut [0u8; 16];
+
+            let len = frame.encode(buf).expect("Buffer is large enough");
+
+            assert_eq!(len, frame.encoded_len());
+            assert_eq!(MuxFrame::parse(&buf[..len]), Ok(frame));
+        }
+
+        assert_eq!(
+            MuxFrame::Open {
+                channel: 1,
+                credits: 0
+            }
+            .encode(&mut [0u8; 6]),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for src in [
+            &b""[..],
+            &[DATA, 0],
+            &[OPEN, 0, 1, 0, 0, 0],
+            &[CREDIT, 0, 1, 0, 0, 0, 0, 0],
+            &[CLOSE, 0, 1, 0],
+            &[0, 0, 1],
+            &[5, 0, 1],
+        ] {
+            assert_eq!(MuxFrame::parse(src), Err(MuxError::Malformed), "{src:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn open_send_receive() {
+        let mut a = Mux::<2>::new();
+        let mut b = Mux::<2>::new();
+        let mut sink = Sink::default();
+
+        a.open(&mut sink, 7, 8).await.expect("Failed to open");
+        assert!(!a.is_open(7));
+
+        assert!(matches!(
+            a.send(&mut sink, 7, b"early").await,
+            Err(MuxSendError::Mux(MuxError::NotOpen(7)))
+        ));
+
+        assert_eq!(
+            b.receive(&sink.messages.pop().expect("Open sent")),
+            Ok(MuxFrame::Open {
+                channel: 7,
+                credits: 8
+            })
+        );
+
+        b.open(&mut sink, 7, 4).await.expect("Failed to open");
+        deliver(&mut sink, &mut a);
+
+        assert!(a.is_open(7));
+        assert!(b.is_open(7));
+        assert_eq!(a.send_credits(7), Some(4));
+        assert_eq!(b.send_credits(7), Some(8));
+
+        a.channel(&mut sink, 7)
+            .send_binary(b"ping")
+            .await
+            .expect("Failed to send");
+
+        assert_eq!(a.send_credits(7), Some(0));
+        assert_eq!(
+            b.receive(&sink.messages.pop().expect("Data sent")),
+            Ok(MuxFrame::Data {
+                channel: 7,
+                payload: b"ping"
+            })
+        );
+        assert_eq!(b.recv_credits(7), Some(0));
+    }
+
+    #[tokio::test]
+    async fn credits() {
+        let mut a = Mux::<1>::new();
+        let mut b = Mux::<1>::new();
+        let mut sink = Sink::default();
+
+        establish(&mut a, &mut b, 1, 2).await;
+
+        assert!(matches!(
+            a.send(&mut sink, 1, b"abc").await,
+            Err(MuxSendError::Mux(MuxError::NotEnoughCredits {
+                channel: 1,
+                available: 2
+            }))
+        ));
+        assert!(sink.messages.is_empty());
+
+        b.grant(&mut sink, 1, 1).await.expect("Failed to grant");
+        assert_eq!(b.recv_credits(1), Some(3));
+        deliver(&mut sink, &mut a);
+        assert_eq!(a.send_credits(1), Some(3));
+
+        a.send(&mut sink, 1, b"abc").await.expect("Failed to send");
+        deliver(&mut sink, &mut b);
+
+        // The peer ignores its credits.
+        let data = &mut [0u8; 4];
+        let len = MuxFrame::Data {
+            channel: 1,
+            payload: b"x",
+        }
+        .encode(data)
+        .expect("Buffer is large enough");
+
+        assert_eq!(b.receive(&data[..len]), Err(MuxError::CreditsExceeded(1)));
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let mut a = Mux::<1>::new();
+        let mut b = Mux::<1>::new();
+        let mut sink = Sink::default();
+
+        establish(&mut a, &mut b, 1, 16).await;
+
+        a.close(&mut sink, 1).await.expect("Failed to close");
+        assert_eq!(a.send_credits(1), None);
+
+        assert_eq!(
+            b.receive(&sink.messages.pop().expect("Close sent")),
+            Ok(MuxFrame::Close { channel: 1 })
+        );
+        assert!(!b.is_open(1));
+
+        assert!(matches!(
+            a.close(&mut sink, 1).await,
+            Err(MuxSendError::Mux(MuxError::UnknownChannel(1)))
+        ));
+
+        // The slot is released.
+        establish(&mut a, &mut b, 2, 16).await;
+        assert!(a.is_open(2));
+    }
+
+    #[tokio::test]
+    async fn too_many_channels() {
+        let mut a = Mux::<1>::new();
+        let mut sink = Sink::default();
+
+        a.open(&mut sink, 1, 0).await.expect("Failed to open");
+
+        assert!(matches!(
+            a.open(&mut sink, 2, 0).await,
+            Err(MuxSendError::Mux(MuxError::TooManyChannels))
+        ));
+        assert!(matches!(
+            a.open(&mut sink, 1, 0).await,
+            Err(MuxSendError::Mux(MuxError::AlreadyOpen(1)))
+        ));
+        assert_eq!(
+            a.receive(&[OPEN, 0, 2, 0, 0, 0, 0]),
+            Err(MuxError::TooManyChannels)
+        );
+        assert_eq!(a.receive(&[DATA, 0, 3]), Err(MuxError::UnknownChannel(3)));
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let mut outer_a = Mux::<1>::new();
+        let mut outer_b = Mux::<1>::new();
+        let mut inner_a = Mux::<1>::new();
+        let mut inner_b = Mux::<1>::new();
+        let mut sink = Sink::default();
+
+        establish(&mut outer_a, &mut outer_b, 1, 64).await;
+
+        inner_a
+            .open(&mut outer_a.channel(&mut sink, 1), 9, 32)
+            .await
+            .expect("Failed to open");
+
+        let message = sink.messages.pop().expect("Open sent");
+
+        match outer_b.receive(&message) {
+            Ok(MuxFrame::Data {
+                channel: 1,
+                payload,
+            }) => assert_eq!(
+                inner_b.receive(payload),
+                Ok(MuxFrame::Open {
+                    channel: 9,
+                    credits: 32
+                })
+            ),
+            frame => panic!("Unexpected frame: {frame:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn receive_from_channel() {
+        let mut a = Mux::<2>::new();
+        let mut b = Mux::<2>::new();
+        let mut sink = Sink::default();
+
+        establish(&mut a, &mut b, 1, 64).await;
+        establish(&mut a, &mut b, 2, 64).await;
+
+        a.grant(&mut sink, 1, 8).await.expect("Failed to grant");
+        a.send(&mut sink, 1, b"one").await.expect("Failed to send");
+        a.send(&mut sink, 2, b"two").await.expect("Failed to send");
+
+        let mut channel = b.channel(&mut sink, 1);
+
+        // The credit frame is applied and skipped.
+        assert!(matches!(
+            channel.with_next_binary(|payload| payload == b"one").await,
+            Some(Ok(true))
+        ));
+        assert_eq!(channel.send_credits(), Some(72));
+
+        assert!(matches!(
+            channel.with_next_binary(|_| ()).await,
+            Some(Err(MuxReceiveError::Mux(MuxError::OtherChannel(2))))
+        ));
+        assert!(channel.with_next_binary(|_| ()).await.is_none());
+
+        assert!(b.receive_from(&mut Sink::default(), |_| ()).await.is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::FrameDecodeError;
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpCode {
     Continuation = 0x0,
     Text = 0x1,
@@ -1,6 +1,14 @@
 //! Options for establishing and accepting WebSocket connections.
 
-use crate::http::Header;
+use core::time::Duration;
+
+use crate::{
+    OpCode,
+    clock::Clock,
+    error::HttpEncodeError,
+    extensions::SupportedExtension,
+    http::{DynamicHeaders, Header, HeaderValues, is_valid_path, validate_header},
+};
 
 /// Errors that can occur when creating [`ConnectOptions`].
 #[derive(Debug, thiserror::Error)]
@@ -8,10 +16,34 @@ pub enum ConnectOptionsError {
     /// The path must not be empty.
     #[error("path must not be empty")]
     EmptyPath,
+    /// The path contains spaces, control characters or non-ASCII characters, which must be percent-encoded.
+    #[error("path contains invalid characters")]
+    InvalidPath,
+    /// A header is reserved for the WebSocket handshake and is generated by the client.
+    ///
+    /// See [`ConnectOptions::RESERVED_HEADERS`].
+    #[error("header is reserved for the websocket handshake")]
+    ReservedHeader,
+    /// A header can not be sent as a single header line.
+    ///
+    /// See [`validate_header`].
+    #[error("invalid header: {0}")]
+    InvalidHeader(HttpEncodeError),
 }
 
+/// A callback selecting the subprotocol of an accepted connection.
+///
+/// Receives the path and the headers of the handshake request and the subprotocols offered by the client,
+/// and returns the subprotocol to echo in the response, if any.
+///
+/// See [`AcceptOptions::with_protocol_selector`].
+///
+/// `Sync`, so that [`AcceptOptions`] can be held across `.await` points of `Send` futures.
+pub type ProtocolSelector<'a> =
+    &'a (dyn Fn(&str, &[Header<'_>], HeaderValues<'_, '_>) -> Option<&'a str> + Sync);
+
 /// Options for establishing a WebSocket connection as a client.
-#[derive(Debug)]
+#[derive(Clone, Copy)]
 #[non_exhaustive]
 pub struct ConnectOptions<'a, 'b> {
     /// The request path for the WebSocket handshake.
@@ -19,7 +51,43 @@ pub struct ConnectOptions<'a, 'b> {
     /// Must not be empty.
     pub(crate) path: &'a str,
     /// Additional HTTP headers to include in the handshake request.
+    ///
+    /// Must not contain any of the [`ConnectOptions::RESERVED_HEADERS`].
+    /// Assigning this field is not checked, like [`ConnectOptions::with_headers_unchecked`].
     pub headers: &'a [Header<'b>],
+    /// Callback computing additional HTTP headers when the handshake request is encoded.
+    pub(crate) dynamic_headers: Option<DynamicHeaders<'a>>,
+    /// Clock measuring the duration of the handshake.
+    pub(crate) clock: Option<&'a dyn Clock>,
+    /// Subprotocols offered in the handshake request, in order of preference.
+    pub(crate) protocols: &'a [&'a str],
+    /// Fail creating the connection if the fragments buffer is empty.
+    pub(crate) require_fragments: bool,
+    /// The maximum number of redirects followed on the same connection.
+    pub(crate) max_redirects: u8,
+    /// Offer the `permessage-deflate` extension.
+    #[cfg(feature = "deflate")]
+    pub(crate) permessage_deflate: bool,
+}
+
+impl core::fmt::Debug for ConnectOptions<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("ConnectOptions");
+
+        debug
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("dynamic_headers", &self.dynamic_headers.is_some())
+            .field("clock", &self.clock.is_some())
+            .field("protocols", &self.protocols)
+            .field("require_fragments", &self.require_fragments)
+            .field("max_redirects", &self.max_redirects);
+
+        #[cfg(feature = "deflate")]
+        debug.field("permessage_deflate", &self.permessage_deflate);
+
+        debug.finish()
+    }
 }
 
 impl<'a, 'b> Default for ConnectOptions<'a, 'b> {
@@ -29,12 +97,22 @@ impl<'a, 'b> Default for ConnectOptions<'a, 'b> {
 }
 
 impl<'a, 'b> ConnectOptions<'a, 'b> {
-    /// Creates a new [`ConnectOptions`] with the given path, returning an error if the path is empty.
+    /// Headers generated by the client during the handshake.
+    ///
+    /// Additional headers must not contain any of these (case-insensitive), otherwise they would be sent twice.
+    pub const RESERVED_HEADERS: &'static [&'static str] = &[
+        "upgrade",
+        "connection",
+        "sec-websocket-version",
+        "sec-websocket-key",
+    ];
+
+    /// Creates a new [`ConnectOptions`] with the given path, returning an error if the path is empty or contains invalid characters.
     pub fn new(path: &'a str) -> Result<Self, ConnectOptionsError> {
         Self::default().with_path(path)
     }
 
-    /// Creates a new [`ConnectOptions`] with the given path without checking if the path is empty.
+    /// Creates a new [`ConnectOptions`] with the given path without checking it.
     pub const fn new_unchecked(path: &'a str) -> Self {
         Self::default().with_path_unchecked(path)
     }
@@ -44,17 +122,25 @@ impl<'a, 'b> ConnectOptions<'a, 'b> {
         self.path
     }
 
-    /// Sets the path, returning an error if the path is empty.
+    /// Sets the path, returning an error if the path is empty or contains invalid characters.
+    ///
+    /// The path is sent as is, so spaces, control characters and non-ASCII characters must already be percent-encoded.
     pub fn with_path(mut self, path: &'a str) -> Result<Self, ConnectOptionsError> {
-        if path.trim().is_empty() {
+        let path = path.trim();
+
+        if path.is_empty() {
             return Err(ConnectOptionsError::EmptyPath);
         };
 
-        self.path = path.trim();
+        if !is_valid_path(path) {
+            return Err(ConnectOptionsError::InvalidPath);
+        }
+
+        self.path = path;
         Ok(self)
     }
 
-    /// Sets the path without checking if it is empty.
+    /// Sets the path without checking it.
     pub const fn with_path_unchecked(mut self, path: &'a str) -> Self {
         self.path = path;
         self
@@ -65,12 +151,120 @@ impl<'a, 'b> ConnectOptions<'a, 'b> {
         self.headers
     }
 
-    /// Sets the headers.
-    pub const fn with_headers(mut self, headers: &'a [Header<'b>]) -> Self {
+    /// Sets the headers, returning an error if any of them is one of the [`ConnectOptions::RESERVED_HEADERS`]
+    /// or can not be sent as a single header line, see [`validate_header`].
+    pub fn with_headers(mut self, headers: &'a [Header<'b>]) -> Result<Self, ConnectOptionsError> {
+        if headers.iter().any(|header| {
+            Self::RESERVED_HEADERS
+                .iter()
+                .any(|reserved| header.name.eq_ignore_ascii_case(reserved))
+        }) {
+            return Err(ConnectOptionsError::ReservedHeader);
+        }
+
+        for header in headers {
+            validate_header(header.name, header.value)
+                .map_err(ConnectOptionsError::InvalidHeader)?;
+        }
+
+        self.headers = headers;
+        Ok(self)
+    }
+
+    /// Sets the headers without checking them.
+    ///
+    /// Headers that can not be sent as a single header line still fail the handshake when the request is encoded.
+    pub const fn with_headers_unchecked(mut self, headers: &'a [Header<'b>]) -> Self {
         self.headers = headers;
         self
     }
 
+    /// Sets a callback computing additional headers each time the handshake request is encoded.
+    ///
+    /// The callback receives the request path and writes its headers directly into the write buffer,
+    /// so that per-connection values (e.g. signatures over the path and a timestamp or nonce) do not need to outlive the handshake.
+    ///
+    /// Writing one of the [`ConnectOptions::RESERVED_HEADERS`] fails the handshake with [`HttpEncodeError::ReservedHeader`].
+    pub const fn with_dynamic_headers(mut self, dynamic_headers: DynamicHeaders<'a>) -> Self {
+        self.dynamic_headers = Some(dynamic_headers);
+        self
+    }
+
+    /// Sets the clock measuring the duration of the handshake.
+    ///
+    /// Without a clock, [`HandshakeStats::duration`](crate::HandshakeStats::duration) is `None`.
+    pub const fn with_clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Returns the offered subprotocols.
+    pub const fn protocols(&self) -> &'a [&'a str] {
+        self.protocols
+    }
+
+    /// Sets the subprotocols offered to the server, in order of preference.
+    ///
+    /// Every subprotocol is sent in its own `Sec-WebSocket-Protocol` header line.
+    /// The handshake fails with [`HandshakeError::InvalidProtocol`](crate::error::HandshakeError::InvalidProtocol)
+    /// if the server selects more than one subprotocol or one that was not offered.
+    /// The selected subprotocol is returned by [`WebSocket::protocol`](crate::WebSocket::protocol).
+    pub const fn with_protocols(mut self, protocols: &'a [&'a str]) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// Sets whether creating the connection fails if the fragments buffer is empty.
+    ///
+    /// Enable this if the server is expected to send fragmented messages, which can not be received without a fragments buffer.
+    /// Creating the connection then fails with [`ConfigError::EmptyFragmentsBuffer`](crate::error::ConfigError::EmptyFragmentsBuffer)
+    /// before the stream is used, instead of the first fragmented message failing with
+    /// [`MessageError::FragmentsBufferTooSmall`](crate::error::MessageError::FragmentsBufferTooSmall).
+    pub const fn with_require_fragments(mut self, require_fragments: bool) -> Self {
+        self.require_fragments = require_fragments;
+        self
+    }
+
+    /// Returns whether creating the connection fails if the fragments buffer is empty.
+    pub const fn require_fragments(&self) -> bool {
+        self.require_fragments
+    }
+
+    /// Sets the maximum number of redirects followed on the same connection. Defaults to `0`.
+    ///
+    /// A redirect is followed by sending the handshake request again with the path of the `Location` header,
+    /// if the location is a path on the same host, e.g. `/ws/`, the response body has a `Content-Length`
+    /// and the server does not close the connection. Other redirects, and redirects beyond the maximum,
+    /// fail with [`HandshakeError::Redirect`](crate::error::HandshakeError::Redirect).
+    pub const fn with_max_redirects(mut self, max_redirects: u8) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Returns the maximum number of redirects followed on the same connection.
+    pub const fn max_redirects(&self) -> u8 {
+        self.max_redirects
+    }
+
+    /// Sets whether to offer the `permessage-deflate` extension, without context takeover in both directions.
+    ///
+    /// The handshake fails with [`HandshakeError::InvalidExtension`](crate::error::HandshakeError::InvalidExtension)
+    /// if the server answers with parameters that were not offered, or more than once.
+    /// See the [`deflate`](crate::deflate) module.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn with_permessage_deflate(mut self, permessage_deflate: bool) -> Self {
+        self.permessage_deflate = permessage_deflate;
+        self
+    }
+
+    /// Returns whether the `permessage-deflate` extension is offered.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn permessage_deflate(&self) -> bool {
+        self.permessage_deflate
+    }
+
     /// Creates a new [`ConnectOptions`] with default values.
     ///
     /// This is an internal `const` function alternative to [`Default::default()`].
@@ -78,20 +272,98 @@ impl<'a, 'b> ConnectOptions<'a, 'b> {
         Self {
             path: "/",
             headers: &[],
+            dynamic_headers: None,
+            clock: None,
+            protocols: &[],
+            require_fragments: false,
+            max_redirects: 0,
+            #[cfg(feature = "deflate")]
+            permessage_deflate: false,
         }
     }
 }
 
+/// An endpoint tried by [`WebSocket::connect_any`](crate::WebSocket::connect_any).
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectTarget<'a, 'b> {
+    /// Identifies the endpoint to open a stream to, e.g. a host and port.
+    endpoint: &'a str,
+    /// The options for the handshake with this endpoint.
+    options: ConnectOptions<'a, 'b>,
+}
+
+impl<'a, 'b> ConnectTarget<'a, 'b> {
+    /// Creates a new [`ConnectTarget`].
+    pub const fn new(endpoint: &'a str, options: ConnectOptions<'a, 'b>) -> Self {
+        Self { endpoint, options }
+    }
+
+    /// Returns the endpoint.
+    pub const fn endpoint(&self) -> &'a str {
+        self.endpoint
+    }
+
+    /// Returns the options.
+    pub const fn options(&self) -> ConnectOptions<'a, 'b> {
+        self.options
+    }
+}
+
 /// Options for accepting a WebSocket connection as a server.
-#[derive(Debug, Default)]
+#[derive(Default)]
 #[non_exhaustive]
 pub struct AcceptOptions<'a, 'b> {
     /// Additional HTTP headers to include in the handshake response.
     pub headers: &'a [Header<'b>],
+    /// Accept any `Sec-WebSocket-Key` as an opaque value instead of requiring a base64-encoded 16-byte value.
+    pub(crate) lenient_sec_key: bool,
+    /// Clock measuring the duration of the handshake.
+    pub(crate) clock: Option<&'a dyn Clock>,
+    /// Reject handshake requests followed by frames sent before the handshake response.
+    pub(crate) reject_early_frames: bool,
+    /// Supported subprotocols, in order of preference.
+    pub(crate) protocols: &'a [&'a str],
+    /// Callback selecting the subprotocol, replacing the selection from `protocols`.
+    pub(crate) protocol_selector: Option<ProtocolSelector<'a>>,
+    /// Supported extensions, in order of preference.
+    pub(crate) extensions: &'a [SupportedExtension<'a>],
+    /// Fail creating the connection if the fragments buffer is empty.
+    pub(crate) require_fragments: bool,
+    /// Reject requests without a `Host` header or with an invalid path.
+    pub(crate) strict_request: bool,
+    /// Accept the `permessage-deflate` extension.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub(crate) permessage_deflate: bool,
+}
+
+impl core::fmt::Debug for AcceptOptions<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("AcceptOptions");
+
+        debug
+            .field("headers", &self.headers)
+            .field("lenient_sec_key", &self.lenient_sec_key)
+            .field("clock", &self.clock.is_some())
+            .field("reject_early_frames", &self.reject_early_frames)
+            .field("protocols", &self.protocols)
+            .field("protocol_selector", &self.protocol_selector.is_some())
+            .field("extensions", &self.extensions)
+            .field("require_fragments", &self.require_fragments)
+            .field("strict_request", &self.strict_request);
+
+        #[cfg(feature = "deflate")]
+        debug.field("permessage_deflate", &self.permessage_deflate);
+
+        debug.finish()
+    }
 }
 
 impl<'a, 'b> AcceptOptions<'a, 'b> {
     /// Sets the headers.
+    ///
+    /// Headers that can not be sent as a single header line, see [`validate_header`],
+    /// fail the handshake when the response is encoded.
     pub const fn with_headers(mut self, headers: &'a [Header<'b>]) -> Self {
         self.headers = headers;
         self
@@ -101,6 +373,538 @@ impl<'a, 'b> AcceptOptions<'a, 'b> {
     pub const fn headers(&self) -> &[Header<'b>] {
         self.headers
     }
+
+    /// Sets whether to accept any `Sec-WebSocket-Key` as an opaque value.
+    ///
+    /// By default, keys that are not a base64-encoded 16-byte value (24 characters) are rejected with
+    /// [`HandshakeError::InvalidSecKey`](crate::error::HandshakeError::InvalidSecKey), as required by RFC 6455.
+    /// Such requests should be answered with `400 Bad Request`, see [`WebSocket::reject`](crate::WebSocket::reject).
+    ///
+    /// Enable this to interoperate with non-compliant clients.
+    pub const fn with_lenient_sec_key(mut self, lenient_sec_key: bool) -> Self {
+        self.lenient_sec_key = lenient_sec_key;
+        self
+    }
+
+    /// Returns whether any `Sec-WebSocket-Key` is accepted as an opaque value.
+    pub const fn lenient_sec_key(&self) -> bool {
+        self.lenient_sec_key
+    }
+
+    /// Sets the clock measuring the duration of the handshake.
+    ///
+    /// Without a clock, [`HandshakeStats::duration`](crate::HandshakeStats::duration) is `None`.
+    pub const fn with_clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Sets whether to reject handshake requests followed by frames the client sent without waiting for the handshake response.
+    ///
+    /// By default, such early frames are kept and returned by the first reads after the handshake.
+    /// If enabled, the handshake fails with [`HandshakeError::EarlyFrames`](crate::error::HandshakeError::EarlyFrames)
+    /// if any bytes were read past the end of the request, before the response is sent.
+    /// Frames arriving after the request was read can not be detected.
+    pub const fn with_reject_early_frames(mut self, reject_early_frames: bool) -> Self {
+        self.reject_early_frames = reject_early_frames;
+        self
+    }
+
+    /// Returns whether handshake requests followed by early frames are rejected.
+    pub const fn reject_early_frames(&self) -> bool {
+        self.reject_early_frames
+    }
+
+    /// Sets the supported subprotocols, in order of preference.
+    ///
+    /// The first supported subprotocol offered by the client is selected, regardless of the client's order.
+    /// Offers may be spread over multiple `Sec-WebSocket-Protocol` header lines and comma-separated values.
+    /// If none is offered, the connection is accepted without a subprotocol.
+    /// The selected subprotocol is returned by [`WebSocket::protocol`](crate::WebSocket::protocol).
+    pub const fn with_protocols(mut self, protocols: &'a [&'a str]) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// Returns the supported subprotocols.
+    pub const fn protocols(&self) -> &'a [&'a str] {
+        self.protocols
+    }
+
+    /// Sets a callback selecting the subprotocol from the handshake request, e.g. depending on the path or an authorization header.
+    ///
+    /// Replaces the selection from the [supported subprotocols](AcceptOptions::with_protocols). A subprotocol not offered by the client
+    /// is never echoed, the connection is then accepted without a subprotocol.
+    /// [`WebSocket::protocol`](crate::WebSocket::protocol) returns the index of the selected subprotocol in the supported subprotocols,
+    /// `None` if it is not one of them.
+    pub const fn with_protocol_selector(mut self, protocol_selector: ProtocolSelector<'a>) -> Self {
+        self.protocol_selector = Some(protocol_selector);
+        self
+    }
+
+    /// Returns the callback selecting the subprotocol.
+    pub const fn protocol_selector(&self) -> Option<ProtocolSelector<'a>> {
+        self.protocol_selector
+    }
+
+    /// Sets the supported extensions, in order of preference.
+    ///
+    /// Every supported extension offered by the client is accepted and answered in the `Sec-WebSocket-Extensions` response header,
+    /// see the [`extensions`](crate::extensions) module. Only the first [`AcceptedExtensions::MAX`](crate::extensions::AcceptedExtensions::MAX) extensions are considered.
+    /// The accepted extensions are returned by [`WebSocket::extensions`](crate::WebSocket::extensions).
+    pub const fn with_extensions(mut self, extensions: &'a [SupportedExtension<'a>]) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Returns the supported extensions.
+    pub const fn extensions(&self) -> &'a [SupportedExtension<'a>] {
+        self.extensions
+    }
+
+    /// Sets whether creating the connection fails if the fragments buffer is empty.
+    ///
+    /// See [`ConnectOptions::with_require_fragments`].
+    pub const fn with_require_fragments(mut self, require_fragments: bool) -> Self {
+        self.require_fragments = require_fragments;
+        self
+    }
+
+    /// Returns whether creating the connection fails if the fragments buffer is empty.
+    pub const fn require_fragments(&self) -> bool {
+        self.require_fragments
+    }
+
+    /// Sets whether to validate the request line and the `Host` header.
+    ///
+    /// If enabled, the handshake fails with [`HandshakeError::MissingOrInvalidHost`](crate::error::HandshakeError::MissingOrInvalidHost)
+    /// if a HTTP/1.1 request does not contain exactly one non-empty `Host` header, and with
+    /// [`HandshakeError::InvalidPath`](crate::error::HandshakeError::InvalidPath) if the path does not start with `/`
+    /// or contains characters that must be percent-encoded.
+    /// Such requests should be answered with `400 Bad Request`, see [`WebSocket::reject`](crate::WebSocket::reject).
+    pub const fn with_strict_request(mut self, strict_request: bool) -> Self {
+        self.strict_request = strict_request;
+        self
+    }
+
+    /// Returns whether the request line and the `Host` header are validated.
+    pub const fn strict_request(&self) -> bool {
+        self.strict_request
+    }
+
+    /// Sets whether to accept the `permessage-deflate` extension.
+    ///
+    /// The first offer without `server_max_window_bits` is accepted and answered without context takeover in both directions,
+    /// in its own `Sec-WebSocket-Extensions` header line. See the [`deflate`](crate::deflate) module.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn with_permessage_deflate(mut self, permessage_deflate: bool) -> Self {
+        self.permessage_deflate = permessage_deflate;
+        self
+    }
+
+    /// Returns whether the `permessage-deflate` extension is accepted.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn permessage_deflate(&self) -> bool {
+        self.permessage_deflate
+    }
+}
+
+/// Limits for received messages.
+///
+/// Messages and frames exceeding a size limit are rejected with [`MessageError::MessageTooLarge`](crate::error::MessageError::MessageTooLarge)
+/// before they are copied into the fragments buffer.
+///
+/// All limits are disabled by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Maximum payload size of a text message in bytes.
+    pub(crate) max_text: Option<usize>,
+    /// Maximum payload size of a binary message in bytes.
+    pub(crate) max_binary: Option<usize>,
+    /// Maximum payload size of a control frame in bytes.
+    pub(crate) max_control_payload: Option<usize>,
+    /// Maximum payload size of a single data frame in bytes.
+    pub(crate) max_frame: Option<usize>,
+    /// Maximum number of empty non-final fragments in a single message.
+    pub(crate) max_empty_fragments: Option<usize>,
+}
+
+impl Limits {
+    /// Creates a new [`Limits`] with all limits disabled.
+    pub const fn new() -> Self {
+        Self {
+            max_text: None,
+            max_binary: None,
+            max_control_payload: None,
+            max_frame: None,
+            max_empty_fragments: None,
+        }
+    }
+
+    /// Returns the maximum payload size of a text message.
+    pub const fn max_text(&self) -> Option<usize> {
+        self.max_text
+    }
+
+    /// Sets the maximum payload size of a text message.
+    pub const fn with_max_text(mut self, max_text: usize) -> Self {
+        self.max_text = Some(max_text);
+        self
+    }
+
+    /// Returns the maximum payload size of a binary message.
+    pub const fn max_binary(&self) -> Option<usize> {
+        self.max_binary
+    }
+
+    /// Sets the maximum payload size of a binary message.
+    pub const fn with_max_binary(mut self, max_binary: usize) -> Self {
+        self.max_binary = Some(max_binary);
+        self
+    }
+
+    /// Returns the maximum payload size of a control frame.
+    pub const fn max_control_payload(&self) -> Option<usize> {
+        self.max_control_payload
+    }
+
+    /// Sets the maximum payload size of a control frame.
+    ///
+    /// Control frames can never exceed 125 bytes.
+    pub const fn with_max_control_payload(mut self, max_control_payload: usize) -> Self {
+        self.max_control_payload = Some(max_control_payload);
+        self
+    }
+
+    /// Sets the maximum payload size of both text and binary messages.
+    pub const fn with_max_message(self, max_message: usize) -> Self {
+        self.with_max_text(max_message).with_max_binary(max_message)
+    }
+
+    /// Returns the maximum payload size of a single data frame.
+    pub const fn max_frame(&self) -> Option<usize> {
+        self.max_frame
+    }
+
+    /// Sets the maximum payload size of a single data frame, i.e. a text, binary or continuation frame.
+    ///
+    /// Unlike the message limits, fragments of a message passed through to the application are checked as well.
+    /// The limit applies to the payload as received, before decompression, and is checked as soon as the frame header is read.
+    pub const fn with_max_frame(mut self, max_frame: usize) -> Self {
+        self.max_frame = Some(max_frame);
+        self
+    }
+
+    /// Returns the maximum number of empty non-final fragments in a single message.
+    pub const fn max_empty_fragments(&self) -> Option<usize> {
+        self.max_empty_fragments
+    }
+
+    /// Sets the maximum number of empty non-final fragments in a single message.
+    ///
+    /// Messages exceeding this limit are rejected with [`MessageError::TooManyEmptyFragments`](crate::error::MessageError::TooManyEmptyFragments),
+    /// preventing a peer from keeping the reassembly loop busy with an endless stream of empty fragments.
+    pub const fn with_max_empty_fragments(mut self, max_empty_fragments: usize) -> Self {
+        self.max_empty_fragments = Some(max_empty_fragments);
+        self
+    }
+
+    /// Checks if `count` empty non-final fragments exceed the limits.
+    pub(crate) const fn exceeds_empty_fragments(&self, count: usize) -> bool {
+        match self.max_empty_fragments {
+            Some(max) => count > max,
+            None => false,
+        }
+    }
+
+    /// Checks if a data frame with a payload of `len` bytes and the given `opcode` exceeds the frame limit.
+    pub(crate) const fn exceeds_frame(&self, opcode: OpCode, len: usize) -> bool {
+        match self.max_frame {
+            Some(max) if !opcode.is_control() => len > max,
+            _ => false,
+        }
+    }
+
+    /// Checks if a payload of `len` bytes with the given `opcode` exceeds the limits.
+    pub(crate) const fn exceeds(&self, opcode: OpCode, len: usize) -> bool {
+        let max = match opcode {
+            OpCode::Text => self.max_text,
+            OpCode::Binary => self.max_binary,
+            OpCode::Close | OpCode::Ping | OpCode::Pong => self.max_control_payload,
+            OpCode::Continuation => None,
+        };
+
+        match max {
+            Some(max) => len > max,
+            None => false,
+        }
+    }
+}
+
+/// Keepalive pings sent by [`WebSocket::tick`](crate::WebSocket::tick).
+///
+/// A `Ping` is sent once no frame has been received for [`Keepalive::interval`].
+/// If no `Pong` is received within [`Keepalive::timeout`] after the `Ping` was sent,
+/// [`WebSocket::tick`](crate::WebSocket::tick) returns [`Error::PingTimeout`](crate::error::Error::PingTimeout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keepalive {
+    /// Time without receiving a frame before a `Ping` is sent.
+    pub(crate) interval: Duration,
+    /// Time to wait for a `Pong` after a `Ping` was sent.
+    pub(crate) timeout: Duration,
+}
+
+impl Keepalive {
+    /// Creates a new [`Keepalive`] with the given interval and timeout.
+    pub const fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+
+    /// Returns the time without receiving a frame before a `Ping` is sent.
+    pub const fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sets the time without receiving a frame before a `Ping` is sent.
+    pub const fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the time to wait for a `Pong` after a `Ping` was sent.
+    pub const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the time to wait for a `Pong` after a `Ping` was sent.
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// A named bundle of strictness settings.
+///
+/// Applied with [`WebSocket::with_profile`](crate::WebSocket::with_profile),
+/// a profile sets the [`Limits`], auto pong and close handling, the maximum number of unanswered pings
+/// and the maximum number of frames read while waiting for a Close frame at once.
+/// Individual settings can still be overridden afterwards.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Bounded resource usage for connections to untrusted peers.
+    ///
+    /// - Text and binary messages are limited to 64 KiB.
+    /// - Control frames are limited to 125 bytes.
+    /// - At most 8 empty fragments per message.
+    /// - At most 3 unanswered pings.
+    /// - At most 8 frames read while waiting for the peer's Close frame.
+    /// - `auto_pong` and `auto_close` are enabled.
+    Strict,
+    /// The defaults: no limits, `auto_pong` and `auto_close` enabled.
+    Lenient,
+    /// The settings expected by the [`autobahn`](https://github.com/crossbario/autobahn-testsuite) test suite.
+    ///
+    /// Message sizes are bounded by the fragments buffer only, since the test suite sends messages of up to 16 MiB
+    /// and long runs of empty fragments.
+    /// Control frames are limited to 125 bytes.
+    /// `auto_pong` and `auto_close` are enabled.
+    Autobahn,
+}
+
+impl Profile {
+    /// Returns the limits of this profile.
+    pub const fn limits(&self) -> Limits {
+        match self {
+            Profile::Strict => Limits::new()
+                .with_max_text(64 * 1024)
+                .with_max_binary(64 * 1024)
+                .with_max_control_payload(125)
+                .with_max_empty_fragments(8),
+            Profile::Lenient => Limits::new(),
+            Profile::Autobahn => Limits::new().with_max_control_payload(125),
+        }
+    }
+
+    /// Returns whether this profile sends Pong responses automatically.
+    pub const fn auto_pong(&self) -> bool {
+        true
+    }
+
+    /// Returns whether this profile closes the connection automatically.
+    pub const fn auto_close(&self) -> bool {
+        true
+    }
+
+    /// Returns the maximum number of unanswered pings of this profile.
+    pub const fn max_unanswered_pings(&self) -> Option<usize> {
+        match self {
+            Profile::Strict => Some(3),
+            Profile::Lenient | Profile::Autobahn => None,
+        }
+    }
+
+    /// Returns the maximum number of frames read while waiting for the peer's Close frame of this profile.
+    pub const fn close_wait_frames(&self) -> Option<usize> {
+        match self {
+            Profile::Strict => Some(8),
+            Profile::Lenient | Profile::Autobahn => None,
+        }
+    }
+}
+
+/// Plain settings of a [`WebSocket`](crate::WebSocket), applied with [`WebSocket::with_config`](crate::WebSocket::with_config).
+///
+/// Unlike the builder methods, a [`Config`] can be stored as data, e.g. loaded from a configuration blob in flash
+/// with the `serde` feature. Missing fields are deserialized with their default values.
+///
+/// The defaults are the defaults of a [`WebSocket`](crate::WebSocket).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct Config {
+    /// Maximum payload size of a text message in bytes.
+    pub max_text: Option<usize>,
+    /// Maximum payload size of a binary message in bytes.
+    pub max_binary: Option<usize>,
+    /// Maximum payload size of a control frame in bytes.
+    pub max_control_payload: Option<usize>,
+    /// Maximum payload size of a single data frame in bytes.
+    pub max_frame: Option<usize>,
+    /// Maximum number of empty non-final fragments in a single message.
+    pub max_empty_fragments: Option<usize>,
+    /// Automatically send a Pong response.
+    pub auto_pong: bool,
+    /// Automatically close the connection on receiving a Close frame.
+    pub auto_close: bool,
+    /// Maximum number of ping payload bytes echoed in an automatic Pong response.
+    pub pong_payload_limit: Option<u8>,
+    /// Automatically close the connection when a fragmented message does not fit into the fragments buffer.
+    pub close_on_fragments_overflow: bool,
+    /// Maximum number of pings that may be sent without receiving a pong.
+    pub max_unanswered_pings: Option<usize>,
+    /// Maximum number of frames read while waiting for the peer's Close frame.
+    pub close_wait_frames: Option<usize>,
+    /// Validate the payload of received text messages as UTF-8.
+    pub text_validation: bool,
+    /// Keepalive pings sent by [`WebSocket::tick`](crate::WebSocket::tick), `None` to disable them.
+    pub keepalive: Option<Keepalive>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Profile> for Config {
+    fn from(profile: Profile) -> Self {
+        Self::from_profile(profile)
+    }
+}
+
+impl Config {
+    /// Creates a new [`Config`] with default values.
+    pub const fn new() -> Self {
+        Self {
+            max_text: None,
+            max_binary: None,
+            max_control_payload: None,
+            max_frame: None,
+            max_empty_fragments: None,
+            auto_pong: true,
+            auto_close: true,
+            pong_payload_limit: None,
+            close_on_fragments_overflow: false,
+            max_unanswered_pings: None,
+            close_wait_frames: None,
+            text_validation: true,
+            keepalive: None,
+        }
+    }
+
+    /// Creates a new [`Config`] with the settings of the given [`Profile`].
+    pub const fn from_profile(profile: Profile) -> Self {
+        Self::new()
+            .with_limits(profile.limits())
+            .with_auto_pong(profile.auto_pong())
+            .with_auto_close(profile.auto_close())
+            .with_max_unanswered_pings(profile.max_unanswered_pings())
+            .with_close_wait_frames(profile.close_wait_frames())
+    }
+
+    /// Returns the limits.
+    pub const fn limits(&self) -> Limits {
+        Limits {
+            max_text: self.max_text,
+            max_binary: self.max_binary,
+            max_control_payload: self.max_control_payload,
+            max_frame: self.max_frame,
+            max_empty_fragments: self.max_empty_fragments,
+        }
+    }
+
+    /// Sets the limits.
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_text = limits.max_text;
+        self.max_binary = limits.max_binary;
+        self.max_control_payload = limits.max_control_payload;
+        self.max_frame = limits.max_frame;
+        self.max_empty_fragments = limits.max_empty_fragments;
+        self
+    }
+
+    /// Sets whether to automatically send a Pong response.
+    pub const fn with_auto_pong(mut self, auto_pong: bool) -> Self {
+        self.auto_pong = auto_pong;
+        self
+    }
+
+    /// Sets whether to automatically close the connection on receiving a Close frame.
+    pub const fn with_auto_close(mut self, auto_close: bool) -> Self {
+        self.auto_close = auto_close;
+        self
+    }
+
+    /// Sets the maximum number of ping payload bytes echoed in an automatic Pong response.
+    pub const fn with_pong_payload_limit(mut self, limit: Option<u8>) -> Self {
+        self.pong_payload_limit = limit;
+        self
+    }
+
+    /// Sets whether to automatically close the connection when a fragmented message does not fit into the fragments buffer.
+    pub const fn with_close_on_fragments_overflow(mut self, close: bool) -> Self {
+        self.close_on_fragments_overflow = close;
+        self
+    }
+
+    /// Sets the maximum number of pings that may be sent without receiving a pong.
+    pub const fn with_max_unanswered_pings(mut self, max: Option<usize>) -> Self {
+        self.max_unanswered_pings = max;
+        self
+    }
+
+    /// Sets the maximum number of frames read while waiting for the peer's Close frame.
+    pub const fn with_close_wait_frames(mut self, max: Option<usize>) -> Self {
+        self.close_wait_frames = max;
+        self
+    }
+
+    /// Sets whether to validate the payload of received text messages as UTF-8.
+    pub const fn with_text_validation(mut self, text_validation: bool) -> Self {
+        self.text_validation = text_validation;
+        self
+    }
+
+    /// Sets the keepalive pings, `None` to disable them.
+    pub const fn with_keepalive(mut self, keepalive: Option<Keepalive>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +920,133 @@ mod tests {
         assert!(matches!(error, ConnectOptionsError::EmptyPath));
     }
 
+    #[test]
+    fn invalid_path() {
+        for path in ["/chat room", "/chat\troom", "/chat\r\nHost: evil", "/café"] {
+            let error = ConnectOptions::new(path).unwrap_err();
+            assert!(matches!(error, ConnectOptionsError::InvalidPath));
+        }
+
+        let options = ConnectOptions::new(" /chat%20room?user=1 ").unwrap();
+        assert_eq!(options.path(), "/chat%20room?user=1");
+    }
+
+    #[test]
+    fn reserved_header() {
+        for name in [
+            "Upgrade",
+            "connection",
+            "Sec-WebSocket-Version",
+            "SEC-WEBSOCKET-KEY",
+        ] {
+            let headers = &[Header {
+                name,
+                value: b"value",
+            }];
+
+            let error = ConnectOptions::default().with_headers(headers).unwrap_err();
+
+            assert!(matches!(error, ConnectOptionsError::ReservedHeader));
+        }
+
+        let headers = &[Header {
+            name: "Host",
+            value: b"example.com",
+        }];
+
+        let options = ConnectOptions::default().with_headers(headers).unwrap();
+        assert_eq!(options.headers().len(), 1);
+    }
+
+    #[test]
+    fn invalid_header() {
+        let headers = &[Header {
+            name: "X-Device",
+            value: b"sensor\r\nX-Admin: 1",
+        }];
+
+        let error = ConnectOptions::default().with_headers(headers).unwrap_err();
+
+        assert!(matches!(
+            error,
+            ConnectOptionsError::InvalidHeader(HttpEncodeError::InvalidHeaderValue)
+        ));
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits::new()
+            .with_max_text(8)
+            .with_max_binary(16)
+            .with_max_control_payload(4);
+
+        assert!(!limits.exceeds(OpCode::Text, 8));
+        assert!(limits.exceeds(OpCode::Text, 9));
+        assert!(!limits.exceeds(OpCode::Binary, 16));
+        assert!(limits.exceeds(OpCode::Binary, 17));
+        assert!(limits.exceeds(OpCode::Ping, 5));
+        assert!(limits.exceeds(OpCode::Close, 5));
+        assert!(!limits.exceeds(OpCode::Pong, 4));
+
+        assert!(!Limits::new().exceeds(OpCode::Binary, usize::MAX));
+
+        let limits = Limits::new().with_max_message(8).with_max_frame(4);
+
+        assert!(limits.exceeds(OpCode::Text, 9));
+        assert!(limits.exceeds(OpCode::Binary, 9));
+        assert!(!limits.exceeds_frame(OpCode::Continuation, 4));
+        assert!(limits.exceeds_frame(OpCode::Continuation, 5));
+        assert!(limits.exceeds_frame(OpCode::Binary, 5));
+        assert!(!limits.exceeds_frame(OpCode::Ping, 125));
+        assert!(!Limits::new().exceeds_frame(OpCode::Text, usize::MAX));
+
+        let limits = Limits::new().with_max_empty_fragments(2);
+
+        assert!(!limits.exceeds_empty_fragments(2));
+        assert!(limits.exceeds_empty_fragments(3));
+        assert!(!Limits::new().exceeds_empty_fragments(usize::MAX));
+    }
+
+    #[test]
+    fn profiles() {
+        let limits = Profile::Strict.limits();
+
+        assert!(limits.exceeds(OpCode::Text, 64 * 1024 + 1));
+        assert!(limits.exceeds(OpCode::Ping, 126));
+        assert!(limits.exceeds_empty_fragments(9));
+        assert_eq!(Profile::Strict.max_unanswered_pings(), Some(3));
+        assert_eq!(Profile::Strict.close_wait_frames(), Some(8));
+
+        let limits = Profile::Lenient.limits();
+
+        assert!(!limits.exceeds(OpCode::Binary, usize::MAX));
+        assert_eq!(Profile::Lenient.max_unanswered_pings(), None);
+        assert_eq!(Profile::Lenient.close_wait_frames(), None);
+
+        let limits = Profile::Autobahn.limits();
+
+        assert!(!limits.exceeds(OpCode::Binary, 16 * 1024 * 1024));
+        assert!(!limits.exceeds_empty_fragments(usize::MAX));
+        assert!(limits.exceeds(OpCode::Close, 126));
+    }
+
+    #[test]
+    fn config() {
+        let config = Config::default();
+
+        assert!(config.auto_pong && config.auto_close && config.text_validation);
+        assert_eq!(config.limits().max_text(), None);
+        assert_eq!(config.keepalive, None);
+
+        let config = Config::from(Profile::Strict);
+
+        assert_eq!(config.max_text, Some(64 * 1024));
+        assert_eq!(config.max_empty_fragments, Some(8));
+        assert_eq!(config.max_unanswered_pings, Some(3));
+        assert_eq!(config.close_wait_frames, Some(8));
+        assert_eq!(config.limits().max_control_payload(), Some(125));
+    }
+
     #[test]
     fn path_is_trimmed() {
         let options = ConnectOptions::new("  /test  ").unwrap();
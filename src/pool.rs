@@ -0,0 +1,241 @@
+//! A pool of persistent WebSocket client connections.
+//!
+//! A [`Pool`] manages a fixed number of connections to upstream servers.
+//! Connections are opened lazily on first use and reopened after they were closed or found unhealthy.
+
+use core::future::Future;
+
+use alloc::vec::Vec;
+use embedded_io_async::{Read, Write};
+use framez::state::{ReadState, WriteState};
+
+pub use crate::buffers::Buffers;
+
+use crate::{
+    Message, WebSocket,
+    error::{Error, PoolError},
+    options::ConnectOptions,
+    rng::RngCore,
+    websocket_core::{FragmentsState, WebSocketCore, check_buffers},
+};
+
+/// Opens the underlying streams of a [`Pool`].
+pub trait Connector<RW, Rng> {
+    /// The error returned when a stream can not be opened.
+    type Error;
+
+    /// Opens a new connected stream for the slot at `index`, along with the random number generator to use for the connection.
+    fn connect(&mut self, index: usize) -> impl Future<Output = Result<(RW, Rng), Self::Error>>;
+}
+
+// Slots are connected most of the time, boxing the connection would allocate on every reconnect.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+enum Slot<'buf, RW, Rng> {
+    Idle(Buffers<'buf>),
+    Connected(WebSocket<'buf, RW, Rng>),
+    /// The slot's buffers were lost because a connection attempt was cancelled.
+    Lost,
+}
+
+impl<'buf, RW, Rng> Slot<'buf, RW, Rng> {
+    fn disconnect(&mut self) {
+        if let Slot::Connected(websocket) = core::mem::replace(self, Slot::Lost) {
+            let (read, write, fragments) = websocket.core.into_buffers();
+
+            *self = Slot::Idle(Buffers::new(read, write, fragments));
+        }
+    }
+}
+
+/// A pool of persistent WebSocket client connections.
+///
+/// Every slot of the pool owns its own [`Buffers`].
+/// A slot connects on the first call to [`Pool::get`] and reconnects if its connection was closed or invalidated.
+///
+/// # Generic Parameters
+/// `N`: The maximum number of headers to accept in the handshake response.
+#[derive(Debug)]
+pub struct Pool<'buf, 'opts, RW, Rng, C, const N: usize> {
+    options: ConnectOptions<'opts, 'opts>,
+    connector: C,
+    max_unanswered_pings: Option<usize>,
+    slots: Vec<Slot<'buf, RW, Rng>>,
+}
+
+impl<'buf, 'opts, RW, Rng, C, const N: usize> Pool<'buf, 'opts, RW, Rng, C, N> {
+    /// Creates a new [`Pool`] with one slot per [`Buffers`].
+    ///
+    /// No connection is opened until [`Pool::get`] is called.
+    pub fn new(
+        options: ConnectOptions<'opts, 'opts>,
+        connector: C,
+        buffers: impl IntoIterator<Item = Buffers<'buf>>,
+    ) -> Self {
+        Self {
+            options,
+            connector,
+            max_unanswered_pings: None,
+            slots: buffers.into_iter().map(Slot::Idle).collect(),
+        }
+    }
+
+    /// Sets the maximum number of unanswered pings for every connection of the pool.
+    ///
+    /// See [`WebSocket::with_max_unanswered_pings`].
+    pub const fn with_max_unanswered_pings(mut self, max: usize) -> Self {
+        self.max_unanswered_pings = Some(max);
+        self
+    }
+
+    /// Returns a reference to the connector.
+    pub const fn connector(&self) -> &C {
+        &self.connector
+    }
+
+    /// Returns a mutable reference to the connector.
+    pub const fn connector_mut(&mut self) -> &mut C {
+        &mut self.connector
+    }
+
+    /// Returns the number of slots.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns `true` if the pool has no slots.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns `true` if the slot at `index` holds an open connection.
+    pub fn is_connected(&self, index: usize) -> bool {
+        matches!(
+            self.slots.get(index),
+            Some(Slot::Connected(websocket)) if !websocket.core.state.closed
+        )
+    }
+
+    /// Drops the connection of the slot at `index`.
+    ///
+    /// The slot reconnects on the next call to [`Pool::get`].
+    /// Call this after a connection returned an error.
+    pub fn invalidate(&mut self, index: usize) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            slot.disconnect();
+        }
+    }
+
+    /// Returns the connection of the slot at `index`, connecting it first if needed.
+    pub async fn get(
+        &mut self,
+        index: usize,
+    ) -> Result<&mut WebSocket<'buf, RW, Rng>, PoolError<RW::Error, C::Error>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+        C: Connector<RW, Rng>,
+    {
+        let slot = self.slots.get_mut(index).ok_or(PoolError::InvalidIndex)?;
+
+        if matches!(slot, Slot::Connected(websocket) if websocket.core.state.closed) {
+            slot.disconnect();
+        }
+
+        let buffers = match core::mem::replace(slot, Slot::Lost) {
+            Slot::Idle(buffers) => buffers,
+            Slot::Connected(websocket) => {
+                *slot = Slot::Connected(websocket);
+
+                return match slot {
+                    Slot::Connected(websocket) => Ok(websocket),
+                    _ => unreachable!(),
+                };
+            }
+            Slot::Lost => return Err(PoolError::SlotLost),
+        };
+
+        if let Err(err) = check_buffers(
+            buffers.read,
+            buffers.write,
+            buffers.fragments,
+            self.options.require_fragments,
+        ) {
+            *slot = Slot::Idle(buffers);
+
+            return Err(PoolError::WebSocket(Error::Config(err)));
+        }
+
+        let (inner, rng) = match self.connector.connect(index).await {
+            Ok(connected) => connected,
+            Err(err) => {
+                *slot = Slot::Idle(buffers);
+
+                return Err(PoolError::Connect(err));
+            }
+        };
+
+        let core = WebSocketCore::client(
+            inner,
+            rng,
+            ReadState::new(buffers.read),
+            WriteState::new(buffers.write),
+            FragmentsState::new(buffers.fragments),
+        );
+
+        let (mut core, result) = core
+            .client_handshake_recoverable::<N, _, _, _>(self.options, |_| Ok(()))
+            .await;
+
+        let info = match result {
+            Ok(((), info)) => info,
+            Err(err) => {
+                let (read, write, fragments) = core.into_buffers();
+
+                *slot = Slot::Idle(Buffers::new(read, write, fragments));
+
+                return Err(PoolError::WebSocket(err));
+            }
+        };
+
+        core.set_max_unanswered_pings(self.max_unanswered_pings);
+
+        *slot = Slot::Connected(WebSocket { core, info });
+
+        match slot {
+            Slot::Connected(websocket) => Ok(websocket),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Sends a ping with the given `payload` on every open connection.
+    ///
+    /// Connections that fail to send the ping, including those exceeding the maximum number of unanswered pings, are invalidated.
+    /// Pongs are consumed while reading from the connections.
+    ///
+    /// Returns the number of healthy connections.
+    pub async fn ping(&mut self, payload: &[u8]) -> usize
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let mut healthy = 0;
+
+        for slot in self.slots.iter_mut() {
+            let Slot::Connected(websocket) = slot else {
+                continue;
+            };
+
+            if websocket.core.state.closed || websocket.send(Message::Ping(payload)).await.is_err()
+            {
+                slot.disconnect();
+
+                continue;
+            }
+
+            healthy += 1;
+        }
+
+        healthy
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{Message, OpCode, codec::encode_frame, error::FrameEncodeError};
+
+/// A [`Message`] encoded into its wire representation once, ready to be sent to many peers.
+///
+/// Fan-out servers can encode a message once and pass it to [`WebSocketWrite::send_pre_encoded`](crate::WebSocketWrite::send_pre_encoded)
+/// for every client, instead of encoding the same payload for each of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreEncodedMessage<'buf> {
+    opcode: OpCode,
+    masked: bool,
+    bytes: &'buf [u8],
+}
+
+impl<'buf> PreEncodedMessage<'buf> {
+    /// Encodes `message` as a single final frame into `buf`.
+    ///
+    /// Servers must not mask their frames, so `mask` should be `None` for messages sent by a server.
+    /// Clients must mask every frame, ideally with a fresh random key per frame,
+    /// which is why pre-encoding is mostly useful on the server side.
+    pub fn encode(
+        message: Message<'_>,
+        mask: Option<[u8; 4]>,
+        buf: &'buf mut [u8],
+    ) -> Result<Self, FrameEncodeError> {
+        let len = encode_frame(
+            true,
+            message.opcode(),
+            message.payload_len(),
+            mask,
+            |payload| message.write(payload),
+            buf,
+        )?;
+
+        Ok(Self {
+            opcode: message.opcode(),
+            masked: mask.is_some(),
+            bytes: &buf[..len],
+        })
+    }
+
+    /// Returns the encoded frame.
+    pub const fn as_bytes(&self) -> &'buf [u8] {
+        self.bytes
+    }
+
+    /// Indicates whether the encoded frame is masked.
+    pub const fn is_masked(&self) -> bool {
+        self.masked
+    }
+
+    /// Indicates whether the encoded message is a ping message.
+    pub const fn is_ping(&self) -> bool {
+        matches!(self.opcode, OpCode::Ping)
+    }
+
+    /// Indicates whether the encoded message is a close message.
+    pub const fn is_close(&self) -> bool {
+        matches!(self.opcode, OpCode::Close)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use framez::encode::Encoder;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use crate::{CloseCode, CloseFrame, FramesCodec};
+
+    use super::*;
+
+    #[test]
+    fn matches_server_codec() {
+        let messages = [
+            Message::Text("Hello"),
+            Message::Binary(&[0; 300]),
+            Message::Ping(b"ping"),
+            Message::Close(Some(CloseFrame::new(CloseCode::Normal, "bye"))),
+        ];
+
+        for message in messages {
+            // Neither masks nor unmasks, just like a server.
+            let mut codec = FramesCodec::new(StdRng::from_os_rng());
+            let mut expected = [0; 512];
+            let len = codec.encode(message, &mut expected).unwrap();
+
+            let mut buf = [0; 512];
+            let encoded = PreEncodedMessage::encode(message, None, &mut buf).unwrap();
+
+            assert_eq!(encoded.as_bytes(), &expected[..len]);
+            assert!(!encoded.is_masked());
+        }
+    }
+
+    #[test]
+    fn masked() {
+        let mask = [1, 2, 3, 4];
+        let mut buf = [0; 16];
+
+        let encoded =
+            PreEncodedMessage::encode(Message::Binary(&[0; 4]), Some(mask), &mut buf).unwrap();
+
+        assert!(encoded.is_masked());
+        assert_eq!(encoded.as_bytes(), &[0x82, 0x84, 1, 2, 3, 4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buf = [0; 4];
+
+        let error = PreEncodedMessage::encode(Message::Text("Hello"), None, &mut buf).unwrap_err();
+
+        assert!(matches!(error, FrameEncodeError::BufferTooSmall));
+    }
+}
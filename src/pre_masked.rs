@@ -0,0 +1,199 @@
+//! Sending payloads that are already masked.
+//!
+//! Clients mask every frame with a random key, XOR-ing the whole payload. Hardware offload engines may mask payloads
+//! on their own, and precomputed test vectors come masked with a known key. [`WebSocket::send_pre_masked`](crate::WebSocket::send_pre_masked)
+//! sends such a payload as-is along with its key, skipping the random number generator and the masking step,
+//! while the frame header is still encoded by this library.
+//!
+//! Only available with the `pre-masked` feature. Only clients mask their frames.
+//!
+//! # Example
+//!
+//! ```
+//! # use websocketz::mock::Noop;
+//! # async fn send(websocketz: &mut websocketz::WebSocket<'_, Noop, Noop>) {
+//! use websocketz::pre_masked::FrameHeader;
+//!
+//! let mask = [0x37, 0xfa, 0x21, 0x3d];
+//!
+//! // "Hello" masked with `mask`, RFC 6455, section 5.7.
+//! let payload = [0x7f, 0x9f, 0x4d, 0x51, 0x58];
+//!
+//! websocketz
+//!     .send_pre_masked(FrameHeader::text(), mask, &payload)
+//!     .await
+//!     .expect("Failed to send");
+//! # }
+//! ```
+
+use framez::encode::Encoder;
+
+use crate::{FramesCodec, Header, OpCode, error::FrameEncodeError};
+
+/// The header of a data frame whose payload is already masked.
+///
+/// See the [module](crate::pre_masked) docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameHeader {
+    fin: bool,
+    opcode: OpCode,
+}
+
+impl FrameHeader {
+    /// Creates the header of a final text frame.
+    pub const fn text() -> Self {
+        Self::new(OpCode::Text)
+    }
+
+    /// Creates the header of a final binary frame.
+    pub const fn binary() -> Self {
+        Self::new(OpCode::Binary)
+    }
+
+    /// Creates the header of a final continuation frame.
+    pub const fn continuation() -> Self {
+        Self::new(OpCode::Continuation)
+    }
+
+    const fn new(opcode: OpCode) -> Self {
+        Self { fin: true, opcode }
+    }
+
+    /// Sets the FIN bit, `false` for every frame of a fragmented message but the last one.
+    pub const fn with_fin(mut self, fin: bool) -> Self {
+        self.fin = fin;
+        self
+    }
+
+    /// Returns the FIN bit.
+    pub const fn fin(&self) -> bool {
+        self.fin
+    }
+
+    /// Indicates whether this is the header of a text frame.
+    pub const fn is_text(&self) -> bool {
+        matches!(self.opcode, OpCode::Text)
+    }
+
+    /// Indicates whether this is the header of a binary frame.
+    pub const fn is_binary(&self) -> bool {
+        matches!(self.opcode, OpCode::Binary)
+    }
+
+    /// Indicates whether this is the header of a continuation frame.
+    pub const fn is_continuation(&self) -> bool {
+        matches!(self.opcode, OpCode::Continuation)
+    }
+}
+
+/// A frame whose payload was masked with `mask`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PreMasked<'a> {
+    pub(crate) header: FrameHeader,
+    pub(crate) mask: [u8; 4],
+    pub(crate) payload: &'a [u8],
+}
+
+impl<R> Encoder<PreMasked<'_>> for FramesCodec<R> {
+    type Error = FrameEncodeError;
+
+    fn encode(&mut self, item: PreMasked<'_>, dst: &mut [u8]) -> Result<usize, Self::Error> {
+        let payload = item.payload;
+
+        let head_len = Header::new(item.header.fin, item.header.opcode, payload.len())
+            .write(dst)
+            .ok_or(FrameEncodeError::BufferTooSmall)?;
+
+        let len = head_len + 4 + payload.len();
+
+        if len > dst.len() {
+            return Err(FrameEncodeError::BufferTooSmall);
+        }
+
+        dst[1] |= 0x80;
+        dst[head_len..head_len + 4].copy_from_slice(&item.mask);
+        dst[head_len + 4..len].copy_from_slice(payload);
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use crate::Message;
+
+    use super::*;
+
+    #[test]
+    fn matches_masked_codec() {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let payload = [0x7f, 0x9f, 0x4d, 0x51, 0x58];
+
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+        let mut buf = [0; 16];
+
+        let frame = PreMasked {
+            header: FrameHeader::text(),
+            mask,
+            payload: &payload,
+        };
+
+        let len = codec.encode(frame, &mut buf).unwrap();
+
+        // RFC 6455, section 5.7: a single-frame masked text message containing "Hello".
+        assert_eq!(
+            &buf[..len],
+            &[
+                0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58
+            ]
+        );
+
+        let mut expected = [0; 16];
+        let expected_len = crate::codec::encode_frame(
+            true,
+            OpCode::Text,
+            5,
+            Some(mask),
+            |payload| Message::Text("Hello").write(payload),
+            &mut expected,
+        )
+        .unwrap();
+
+        assert_eq!(&buf[..len], &expected[..expected_len]);
+    }
+
+    #[test]
+    fn fragment() {
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+        let mut buf = [0; 16];
+
+        let frame = PreMasked {
+            header: FrameHeader::binary().with_fin(false),
+            mask: [0; 4],
+            payload: &[1, 2],
+        };
+
+        let len = codec.encode(frame, &mut buf).unwrap();
+
+        assert_eq!(&buf[..len], &[0x02, 0x82, 0, 0, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut codec = FramesCodec::new(StdRng::from_os_rng());
+
+        let frame = PreMasked {
+            header: FrameHeader::binary(),
+            mask: [0; 4],
+            payload: &[0; 8],
+        };
+
+        assert!(matches!(
+            codec.encode(frame, &mut [0; 13]),
+            Err(FrameEncodeError::BufferTooSmall)
+        ));
+        assert!(codec.encode(frame, &mut [0; 14]).is_ok());
+    }
+}
@@ -0,0 +1,74 @@
+/// The progress of a fragmented message being reassembled.
+///
+/// Carries no payload data. See [`ProgressObserver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The message is a text message.
+    text: bool,
+    /// Number of payload bytes received so far.
+    received: usize,
+    /// The final fragment was received.
+    is_final: bool,
+}
+
+impl Progress {
+    #[inline]
+    pub(crate) const fn new(text: bool, received: usize, is_final: bool) -> Self {
+        Self {
+            text,
+            received,
+            is_final,
+        }
+    }
+
+    /// Returns `true` if the message is a text message, `false` if it is a binary message.
+    #[inline]
+    pub const fn is_text(&self) -> bool {
+        self.text
+    }
+
+    /// Returns the number of payload bytes of the message received so far.
+    ///
+    /// For messages compressed with `permessage-deflate`, the number of compressed bytes.
+    #[inline]
+    pub const fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Returns `true` if the final fragment of the message was received.
+    #[inline]
+    pub const fn is_final(&self) -> bool {
+        self.is_final
+    }
+}
+
+/// Observes the reassembly of received fragmented messages, e.g. to display the progress of an upload.
+///
+/// Invoked with a [`Progress`] after every fragment appended to the fragments buffer.
+/// Messages sent in a single frame and messages rejected by the message filter are not observed.
+/// See [`WebSocket::with_progress_observer`](crate::WebSocket::with_progress_observer).
+///
+/// Implemented for every `Fn(Progress) + Sync`.
+/// Observers must be [`Sync`] so that connections using them remain [`Send`].
+pub trait ProgressObserver: Sync {
+    /// Called after a fragment of a message was appended to the fragments buffer.
+    fn on_progress(&self, progress: Progress);
+}
+
+impl<F> ProgressObserver for F
+where
+    F: Fn(Progress) + Sync,
+{
+    fn on_progress(&self, progress: Progress) {
+        self(progress)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Observer<'a>(pub(crate) &'a dyn ProgressObserver);
+
+impl core::fmt::Debug for Observer<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Observer").finish_non_exhaustive()
+    }
+}
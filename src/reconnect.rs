@@ -0,0 +1,129 @@
+use core::time::Duration;
+
+use crate::CloseCode;
+#[cfg(feature = "client")]
+use crate::rng::RngCore;
+
+/// Advice on whether and when to reconnect after the connection was closed.
+///
+/// Derived from the [`CloseCode`] sent by the peer, following the meaning assigned to each code by RFC 6455 and the IANA registry.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReconnectAdvice {
+    /// Reconnecting with the same request would fail the same way.
+    DoNotReconnect,
+    /// Reconnect after a random delay between `min` and `max`.
+    After {
+        /// The minimum delay.
+        min: Duration,
+        /// The maximum delay.
+        max: Duration,
+    },
+}
+
+impl ReconnectAdvice {
+    const fn after_secs(min: u64, max: u64) -> Self {
+        Self::After {
+            min: Duration::from_secs(min),
+            max: Duration::from_secs(max),
+        }
+    }
+
+    /// Returns the advice for the given close code.
+    ///
+    /// - [`CloseCode::Restart`] and [`CloseCode::Again`]: reconnect after 5 to 30 seconds.
+    /// - [`CloseCode::Away`], [`CloseCode::Error`], [`CloseCode::Status`] and [`CloseCode::Abnormal`]: reconnect after 1 to 5 seconds.
+    /// - Any other code: do not reconnect.
+    ///   This includes [`CloseCode::Normal`] as well as application-defined codes, whose meaning is unknown to this library.
+    pub const fn from_close(code: CloseCode) -> Self {
+        match code {
+            CloseCode::Restart | CloseCode::Again => Self::after_secs(5, 30),
+            CloseCode::Away | CloseCode::Error | CloseCode::Status | CloseCode::Abnormal => {
+                Self::after_secs(1, 5)
+            }
+            _ => Self::DoNotReconnect,
+        }
+    }
+
+    /// Indicates whether reconnecting is advised.
+    pub const fn should_reconnect(&self) -> bool {
+        matches!(self, Self::After { .. })
+    }
+
+    /// Returns a random delay within the advised range, or `None` if reconnecting is not advised.
+    ///
+    /// The jitter has millisecond resolution.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub fn delay<Rng: RngCore>(&self, rng: &mut Rng) -> Option<Duration> {
+        match *self {
+            Self::DoNotReconnect => None,
+            Self::After { min, max } => {
+                let range = max.saturating_sub(min).as_millis() as u64;
+                let jitter = match range {
+                    0 => 0,
+                    range => rng.next_u64() % (range + 1),
+                };
+
+                Some(min + Duration::from_millis(jitter))
+            }
+        }
+    }
+}
+
+impl From<CloseCode> for ReconnectAdvice {
+    fn from(code: CloseCode) -> Self {
+        Self::from_close(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_close() {
+        assert!(ReconnectAdvice::from_close(CloseCode::Restart).should_reconnect());
+        assert!(ReconnectAdvice::from_close(CloseCode::Away).should_reconnect());
+        assert!(ReconnectAdvice::from_close(CloseCode::Abnormal).should_reconnect());
+
+        assert_eq!(
+            ReconnectAdvice::from_close(CloseCode::Policy),
+            ReconnectAdvice::DoNotReconnect
+        );
+        assert_eq!(
+            ReconnectAdvice::from_close(CloseCode::Normal),
+            ReconnectAdvice::DoNotReconnect
+        );
+        assert_eq!(
+            ReconnectAdvice::from_close(CloseCode::from_u16(4000)),
+            ReconnectAdvice::DoNotReconnect
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn delay() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut rng = StdRng::from_os_rng();
+
+        assert_eq!(ReconnectAdvice::DoNotReconnect.delay(&mut rng), None);
+
+        for _ in 0..100 {
+            let delay = ReconnectAdvice::from_close(CloseCode::Restart)
+                .delay(&mut rng)
+                .expect("Restart must advise to reconnect");
+
+            assert!(delay >= Duration::from_secs(5));
+            assert!(delay <= Duration::from_secs(30));
+        }
+
+        let advice = ReconnectAdvice::After {
+            min: Duration::from_secs(1),
+            max: Duration::from_secs(1),
+        };
+
+        assert_eq!(advice.delay(&mut rng), Some(Duration::from_secs(1)));
+    }
+}
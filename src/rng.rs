@@ -0,0 +1,20 @@
+//! The random number generator of a connection.
+//!
+//! Clients need an RNG to generate the `Sec-WebSocket-Key` of the handshake and the masks of the frames they send.
+//! Servers never use it.
+//!
+//! With the `client` feature, [`RngCore`] is [`rand_core::RngCore`].
+//! Without it, clients are not available and [`RngCore`] is implemented for every type,
+//! so that server-only builds do not depend on `rand`, e.g. `WebSocket::accept(options, stream, (), ...)`.
+
+#[cfg(feature = "client")]
+pub use rand_core::RngCore;
+
+/// Implemented for every type, as servers never generate random numbers.
+///
+/// Enable the `client` feature to use [`rand_core::RngCore`](https://docs.rs/rand_core/latest/rand_core/trait.RngCore.html) instead.
+#[cfg(not(feature = "client"))]
+pub trait RngCore {}
+
+#[cfg(not(feature = "client"))]
+impl<T> RngCore for T {}
@@ -0,0 +1,103 @@
+use core::time::Duration;
+
+use embedded_io_async::{ErrorType, Read, Write};
+
+use crate::clock::Clock;
+
+/// Statistics of a completed handshake.
+///
+/// See [`WebSocket::handshake_stats`](crate::WebSocket::handshake_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandshakeStats {
+    bytes_read: usize,
+    bytes_written: usize,
+    duration: Option<Duration>,
+}
+
+impl HandshakeStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            bytes_read: 0,
+            bytes_written: 0,
+            duration: None,
+        }
+    }
+
+    /// Returns the number of bytes read from the underlying I/O during the handshake.
+    ///
+    /// May include bytes of frames the peer sent right after its handshake message.
+    pub const fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes written to the underlying I/O during the handshake.
+    pub const fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+
+    /// Returns the duration of the handshake.
+    ///
+    /// `None` if no [`Clock`] was provided in the options.
+    pub const fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
+
+/// Counts the bytes read from and written to the underlying I/O during a handshake.
+pub(crate) struct Counted<'a, 'c, RW> {
+    inner: &'a mut RW,
+    clock: Option<&'c dyn Clock>,
+    start: u64,
+    read: usize,
+    written: usize,
+}
+
+impl<'a, 'c, RW> Counted<'a, 'c, RW> {
+    pub(crate) fn new(inner: &'a mut RW, clock: Option<&'c dyn Clock>) -> Self {
+        Self {
+            inner,
+            clock,
+            start: clock.map_or(0, |clock| clock.now()),
+            read: 0,
+            written: 0,
+        }
+    }
+
+    pub(crate) fn finish(&self) -> HandshakeStats {
+        let duration = self.clock.map(|clock| clock.elapsed(self.start));
+
+        HandshakeStats {
+            bytes_read: self.read,
+            bytes_written: self.written,
+            duration,
+        }
+    }
+}
+
+impl<RW: ErrorType> ErrorType for Counted<'_, '_, RW> {
+    type Error = RW::Error;
+}
+
+impl<RW: Read> Read for Counted<'_, '_, RW> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let read = self.inner.read(buf).await?;
+
+        self.read += read;
+
+        Ok(read)
+    }
+}
+
+impl<RW: Write> Write for Counted<'_, '_, RW> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let written = self.inner.write(buf).await?;
+
+        self.written += written;
+
+        Ok(written)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+}
@@ -0,0 +1,223 @@
+//! A WebSocket as a byte stream.
+//!
+//! [`ByteStream`] exposes the binary messages of a [`WebSocket`] as an [`embedded_io_async::Read`] + [`embedded_io_async::Write`] byte stream,
+//! so that protocols written against a transport, e.g. SSH, MQTT (see the `mqtt` module) or custom TLV framings,
+//! can be tunneled through WebSocket gateways unchanged.
+//!
+//! Received binary messages are concatenated into a single stream of bytes, message boundaries are not preserved.
+//! Every write is sent as one binary message, up to a configurable maximum length.
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # async fn tunnel() {
+//! # use websocketz::mock::Noop;
+//! use embedded_io_async::{Read, Write};
+//! use websocketz::{WebSocket, stream::ByteStream};
+//!
+//! # let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+//! let websocketz = WebSocket::client(Noop, Noop, read_buffer, write_buffer, fragments_buffer);
+//!
+//! // Holds the part of a message not read yet.
+//! let pending = &mut [0u8; 1024];
+//!
+//! let mut stream = ByteStream::new(websocketz)
+//!     .with_pending_buffer(pending)
+//!     .with_max_message(512);
+//!
+//! stream.write_all(b"SSH-2.0-websocketz\r\n").await.expect("Failed to write");
+//! stream.flush().await.expect("Failed to flush");
+//!
+//! let mut banner = [0u8; 64];
+//! let len = stream.read(&mut banner).await.expect("Failed to read");
+//! # }
+//! ```
+
+use embedded_io_async::{ErrorType, Read, Write};
+
+use crate::{Message, WebSocket, error::StreamError, rng::RngCore};
+
+/// A [`WebSocket`] as a byte stream.
+///
+/// See the [module](crate::stream) documentation.
+#[derive(Debug)]
+pub struct ByteStream<'buf, RW, Rng> {
+    websocket: WebSocket<'buf, RW, Rng>,
+    /// Holds the part of the last message that did not fit into the read buffer of the caller.
+    pending: &'buf mut [u8],
+    /// Start of the unread bytes in `pending`.
+    start: usize,
+    /// End of the unread bytes in `pending`.
+    end: usize,
+    /// Maximum payload length of a sent message.
+    max_message: Option<usize>,
+}
+
+impl<'buf, RW, Rng> ByteStream<'buf, RW, Rng> {
+    /// Creates a new [`ByteStream`] over an established `websocket`.
+    ///
+    /// Without a pending buffer, every received message must fit into the buffer passed to [`Read::read`],
+    /// see [`ByteStream::with_pending_buffer`].
+    pub const fn new(websocket: WebSocket<'buf, RW, Rng>) -> Self {
+        Self {
+            websocket,
+            pending: &mut [],
+            start: 0,
+            end: 0,
+            max_message: None,
+        }
+    }
+
+    /// Sets the buffer holding the part of a received message that does not fit into the buffer passed to [`Read::read`].
+    ///
+    /// Must hold the largest expected message, unless the caller reads into a buffer at least as large.
+    /// Received bytes not read yet are discarded.
+    pub const fn with_pending_buffer(mut self, pending: &'buf mut [u8]) -> Self {
+        self.pending = pending;
+        self.start = 0;
+        self.end = 0;
+        self
+    }
+
+    /// Sets the maximum payload length of a sent message.
+    ///
+    /// Longer writes are partial, see [`Write::write`]. Unlimited by default.
+    /// A maximum of `0` is treated as `1`.
+    pub const fn with_max_message(mut self, max_message: usize) -> Self {
+        self.max_message = Some(max_message);
+        self
+    }
+
+    /// Returns the maximum payload length of a sent message, if any.
+    pub const fn max_message(&self) -> Option<usize> {
+        self.max_message
+    }
+
+    /// Returns a reference to the underlying [`WebSocket`].
+    pub const fn websocket(&self) -> &WebSocket<'buf, RW, Rng> {
+        &self.websocket
+    }
+
+    /// Returns a mutable reference to the underlying [`WebSocket`], e.g. to close it.
+    pub const fn websocket_mut(&mut self) -> &mut WebSocket<'buf, RW, Rng> {
+        &mut self.websocket
+    }
+
+    /// Returns the number of received bytes not read yet.
+    pub const fn pending(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Consumes the [`ByteStream`] and returns the underlying [`WebSocket`].
+    ///
+    /// Received bytes not read yet are discarded.
+    pub fn into_inner(self) -> WebSocket<'buf, RW, Rng> {
+        self.websocket
+    }
+}
+
+impl<RW, Rng> ErrorType for ByteStream<'_, RW, Rng>
+where
+    RW: ErrorType,
+{
+    type Error = StreamError<RW::Error>;
+}
+
+impl<RW, Rng> Read for ByteStream<'_, RW, Rng>
+where
+    RW: Read + Write,
+    Rng: RngCore,
+{
+    /// Reads the bytes of the received binary messages.
+    ///
+    /// `Ping` and `Close` messages are handled automatically as with [`next!`](crate::next).
+    /// Returns `Ok(0)` once the connection is closed.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.start < self.end {
+                let len = buf.len().min(self.end - self.start);
+
+                buf[..len].copy_from_slice(&self.pending[self.start..self.start + len]);
+
+                self.start += len;
+
+                return Ok(len);
+            }
+
+            let pending = &mut *self.pending;
+
+            // `None` if the message carries no bytes, so that it is not mistaken for EOF.
+            let read = self
+                .websocket
+                .with_next(|message| -> Result<_, StreamError<RW::Error>> {
+                    match message {
+                        Message::Binary(payload) if payload.is_empty() => Ok(None),
+                        Message::Binary(payload) => {
+                            let len = buf.len().min(payload.len());
+                            let rest = &payload[len..];
+
+                            buf[..len].copy_from_slice(&payload[..len]);
+
+                            pending
+                                .get_mut(..rest.len())
+                                .ok_or(StreamError::BufferTooSmall)?
+                                .copy_from_slice(rest);
+
+                            Ok(Some((len, rest.len())))
+                        }
+                        Message::Close(_) => Ok(Some((0, 0))),
+                        Message::Ping(_) | Message::Pong(_) => Ok(None),
+                        Message::Text(_) | Message::TextUnchecked(_) => Err(StreamError::Text),
+                    }
+                })
+                .await;
+
+            match read {
+                Some(Ok(Ok(Some((len, pending))))) => {
+                    self.start = 0;
+                    self.end = pending;
+
+                    return Ok(len);
+                }
+                Some(Ok(Ok(None))) => continue,
+                Some(Ok(Err(err))) => return Err(err),
+                Some(Err(err)) => return Err(StreamError::WebSocket(err.into())),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+impl<RW, Rng> Write for ByteStream<'_, RW, Rng>
+where
+    RW: Write,
+    Rng: RngCore,
+{
+    /// Sends `buf`, up to the maximum message length, as one binary message, fragmented if it does not fit into the write buffer.
+    ///
+    /// The message may not be transmitted until [`Write::flush`] is called, depending on the transport.
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let len = match self.max_message {
+            Some(max) => buf.len().min(max.max(1)),
+            None => buf.len(),
+        };
+
+        self.websocket
+            .send_binary_stream()
+            .finish(&buf[..len])
+            .await?;
+
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.websocket.flush().await?)
+    }
+}
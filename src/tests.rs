@@ -1,7 +1,7 @@
 use embedded_io_adapters::tokio_1::FromTokio;
 use rand::{SeedableRng, rngs::StdRng};
 
-use crate::{CloseCode, Message, WebSocket, next};
+use crate::{CloseCode, Message, MessageMut, WebSocket, next, next_bounded, next_mut};
 
 const SIZE: usize = 128;
 
@@ -71,15 +71,33 @@ mod macros {
         );
 
         let _ = next!(websocketz);
+        let _ = next_mut!(websocketz);
+        let _ = next_bounded!(websocketz, 4);
         let _ = send!(websocketz, Message::Text("Message"));
         let _ = send_fragmented!(websocketz, Message::Text("Message"), 2);
 
         let (mut websocketz_read, mut websocketz_write) = websocketz.split_with(split);
 
         let _ = next!(websocketz_read);
+        let _ = next_mut!(websocketz_read);
+        let _ = next_bounded!(websocketz_read, 4);
         let _ = send!(websocketz_write, Message::Text("Message"));
         let _ = send_fragmented!(websocketz_write, Message::Text("Message"), 2);
     }
+
+    #[test]
+    fn split_halves_only_carry_their_state() {
+        use core::mem::size_of;
+
+        use crate::{WebSocketRead, WebSocketWrite, mock::Noop};
+
+        type Full = WebSocket<'static, Noop, Noop>;
+        type Read = WebSocketRead<'static, Noop>;
+        type Write = WebSocketWrite<'static, Noop, Noop>;
+
+        assert!(size_of::<Read>() < size_of::<Full>());
+        assert!(size_of::<Write>() < size_of::<Read>());
+    }
 }
 
 mod client {
@@ -274,6 +292,140 @@ mod client {
         tokio::join!(server, client);
     }
 
+    #[tokio::test]
+    async fn receive_with_next() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            for text in STR_MESSAGES {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::text(
+                        fastwebsockets::Payload::Borrowed(text.as_bytes()),
+                    ))
+                    .await
+                    .expect("Failed to send text message");
+            }
+        };
+
+        let client = async move {
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut str_index = 0;
+
+            while let Some(result) = websocketz
+                .with_next(|message| match message {
+                    Message::Text(payload) => payload == STR_MESSAGES[str_index],
+                    _ => false,
+                })
+                .await
+            {
+                assert!(result.expect("Failed to read message"));
+                str_index += 1;
+            }
+
+            assert_eq!(str_index, STR_MESSAGES.len());
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn receive_mut() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            for binary in BINARY_MESSAGES {
+                let xored: std::vec::Vec<u8> = binary.iter().map(|byte| byte ^ 0xFF).collect();
+
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::binary(
+                        fastwebsockets::Payload::Owned(xored),
+                    ))
+                    .await
+                    .expect("Failed to send binary message");
+            }
+
+            for text in STR_MESSAGES {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        false,
+                        fastwebsockets::OpCode::Text,
+                        None,
+                        fastwebsockets::Payload::Borrowed(&text.as_bytes()[..4]),
+                    ))
+                    .await
+                    .expect("Failed to send text fragment");
+
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        true,
+                        fastwebsockets::OpCode::Continuation,
+                        None,
+                        fastwebsockets::Payload::Borrowed(&text.as_bytes()[4..]),
+                    ))
+                    .await
+                    .expect("Failed to send text fragment");
+            }
+        };
+
+        let client = async move {
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut bin_index = 0;
+            let mut str_index = 0;
+
+            loop {
+                match next_mut!(websocketz) {
+                    Some(Ok(MessageMut::Binary(payload))) => {
+                        payload.iter_mut().for_each(|byte| *byte ^= 0xFF);
+
+                        assert_eq!(payload, BINARY_MESSAGES[bin_index]);
+                        bin_index += 1;
+                    }
+                    Some(Ok(MessageMut::Text(payload))) => {
+                        payload.make_ascii_uppercase();
+
+                        assert_eq!(&*payload, STR_MESSAGES[str_index].to_ascii_uppercase());
+                        str_index += 1;
+                    }
+                    None => break,
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+
+            assert_eq!(bin_index, BINARY_MESSAGES.len());
+            assert_eq!(str_index, STR_MESSAGES.len());
+        };
+
+        tokio::join!(server, client);
+    }
+
     mod handshake {
         use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
@@ -287,6 +439,9 @@ mod client {
 
         macro_rules! quick_handshake_error {
             ($response:ident, $error:ident) => {
+                quick_handshake_error!($response, pattern HandshakeError::$error);
+            };
+            ($response:ident, pattern $pattern:pat $(if $guard:expr)?) => {
                 let (client, server) = tokio::io::duplex(16);
                 let read_buf = &mut [0u8; SIZE * 2];
                 let write_buf = &mut [0u8; SIZE * 2];
@@ -330,7 +485,7 @@ mod client {
                     {
                         Ok(_) => panic!("Expected error, but got Ok"),
                         Err(error) => {
-                            assert!(matches!(error, Error::Handshake(HandshakeError::$error)));
+                            assert!(matches!(error, Error::Handshake($pattern) $(if $guard)?));
                         }
                     }
                 };
@@ -350,6 +505,29 @@ mod client {
             quick_handshake_error!(RESPONSE, InvalidStatusCode);
         }
 
+        #[tokio::test]
+        async fn upgrade_required() {
+            const RESPONSE: &str = "HTTP/1.1 426 Upgrade Required\r\n\
+            Sec-WebSocket-Version: 13, 8, 7\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+            quick_handshake_error!(
+                RESPONSE,
+                pattern HandshakeError::UpgradeRequired(versions)
+                    if versions.versions().eq([13, 8, 7])
+            );
+        }
+
+        #[tokio::test]
+        async fn upgrade_required_without_versions() {
+            const RESPONSE: &str = "HTTP/1.1 426 Upgrade Required\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+            quick_handshake_error!(RESPONSE, InvalidStatusCode);
+        }
+
         #[tokio::test]
         async fn invalid_upgrade_header() {
             const RESPONSE: &str = "HTTP/1.1 101 Switching Protocols\r\n\
@@ -514,7 +692,7 @@ mod server {
         header::{CONNECTION, UPGRADE},
     };
     use http_body_util::Empty;
-    use tokio::io::AsyncWriteExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     use crate::{
         CloseFrame,
@@ -728,6 +906,12 @@ mod server {
 
         macro_rules! quick_handshake_error {
             ($request:ident, $error:ident) => {
+                quick_handshake_error!($request, $error, AcceptOptions::default());
+            };
+            ($request:ident, $error:ident, $options:expr) => {
+                quick_handshake_error!($request, pattern HandshakeError::$error, $options);
+            };
+            ($request:ident, pattern $pattern:pat $(if $guard:expr)?, $options:expr) => {
                 let (server, mut client) = tokio::io::duplex(16);
 
                 let read_buf = &mut [0u8; SIZE * 2];
@@ -736,7 +920,7 @@ mod server {
 
                 let server = async move {
                     match WebSocket::accept::<16>(
-                        AcceptOptions::default(),
+                        $options,
                         FromTokio::new(server),
                         StdRng::from_os_rng(),
                         read_buf,
@@ -747,7 +931,7 @@ mod server {
                     {
                         Ok(_) => panic!("Expected error, but got Ok"),
                         Err(error) => {
-                            assert!(matches!(error, Error::Handshake(HandshakeError::$error)));
+                            assert!(matches!(error, Error::Handshake($pattern) $(if $guard)?));
                         }
                     }
                 };
@@ -796,6 +980,23 @@ mod server {
             Sec-WebSocket-Version: 12\r\n\
             \r\n";
 
+            quick_handshake_error!(
+                REQUEST,
+                pattern HandshakeError::UnsupportedSecVersion(version)
+                    if version.as_bytes() == b"12" && version.versions().eq([12]),
+                AcceptOptions::default()
+            );
+        }
+
+        #[tokio::test]
+        async fn missing_sec_version() {
+            const REQUEST: &str = "GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            \r\n";
+
             quick_handshake_error!(REQUEST, MissingOrInvalidSecVersion);
         }
 
@@ -812,14 +1013,87 @@ mod server {
         }
 
         #[tokio::test]
-        async fn connection_closed() {
-            let (_, server) = tokio::io::duplex(16);
+        async fn invalid_sec_key() {
+            const REQUEST: &str = "GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: short-key\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            quick_handshake_error!(REQUEST, InvalidSecKey);
+        }
+
+        #[tokio::test]
+        async fn strict_missing_host() {
+            const REQUEST: &str = "GET / HTTP/1.1\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            quick_handshake_error!(
+                REQUEST,
+                MissingOrInvalidHost,
+                AcceptOptions::default().with_strict_request(true)
+            );
+        }
+
+        #[tokio::test]
+        async fn strict_repeated_host() {
+            const REQUEST: &str = "GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Host: example.com\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            quick_handshake_error!(
+                REQUEST,
+                MissingOrInvalidHost,
+                AcceptOptions::default().with_strict_request(true)
+            );
+        }
+
+        #[tokio::test]
+        async fn strict_invalid_path() {
+            const REQUEST: &str = "GET chat#room HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            quick_handshake_error!(
+                REQUEST,
+                InvalidPath,
+                AcceptOptions::default().with_strict_request(true)
+            );
+        }
+
+        #[tokio::test]
+        async fn lenient_request() {
+            const REQUEST: &str = "GET chat HTTP/1.1\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            let (server, mut client) = tokio::io::duplex(SIZE * 2);
 
             let read_buf = &mut [0u8; SIZE * 2];
             let write_buf = &mut [0u8; SIZE * 2];
             let fragments_buf = &mut [];
 
-            match WebSocket::accept::<16>(
+            client.write_all(REQUEST.as_bytes()).await.unwrap();
+
+            WebSocket::accept::<16>(
                 AcceptOptions::default(),
                 FromTokio::new(server),
                 StdRng::from_os_rng(),
@@ -828,54 +1102,248 @@ mod server {
                 fragments_buf,
             )
             .await
-            {
-                Ok(_) => panic!("Expected error, but got Ok"),
-                Err(error) => {
-                    assert!(matches!(
-                        error,
-                        Error::Handshake(HandshakeError::ConnectionClosed)
-                    ));
-                }
-            }
+            .expect("Lenient requests are accepted by default");
         }
 
         #[tokio::test]
-        async fn ok() {
-            let (server, client) = tokio::io::duplex(16);
+        async fn lenient_sec_key() {
+            const REQUEST: &str = "GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: short-key\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            let (server, mut client) = tokio::io::duplex(SIZE * 2);
 
-            // Handshake requires larger buffers than SIZE
             let read_buf = &mut [0u8; SIZE * 2];
             let write_buf = &mut [0u8; SIZE * 2];
             let fragments_buf = &mut [];
 
+            client.write_all(REQUEST.as_bytes()).await.unwrap();
+
+            WebSocket::accept::<16>(
+                AcceptOptions::default().with_lenient_sec_key(true),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Handshake failed");
+        }
+
+        #[tokio::test]
+        async fn pipelined_request_after_failed_upgrade() {
+            const REQUESTS: &str = "POST / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n\
+            GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n";
+
+            let (server, client) = tokio::io::duplex(16);
+
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [];
+
             let server = async move {
-                let mut websocketz = WebSocket::accept::<16>(
-                    AcceptOptions::default(),
+                let websocketz = WebSocket::server(
                     FromTokio::new(server),
                     StdRng::from_os_rng(),
                     read_buf,
                     write_buf,
                     fragments_buf,
-                )
-                .await
-                .unwrap();
+                );
+
+                let (mut websocketz, result) = websocketz
+                    .try_accept::<16, _, _, CustomError>(AcceptOptions::default(), |_| Ok(()))
+                    .await;
+
+                assert!(matches!(
+                    result,
+                    Err(Error::Handshake(HandshakeError::WrongHttpMethod))
+                ));
 
                 websocketz
-                    .send(Message::Close(Some(CloseFrame::new(
-                        CloseCode::Normal,
-                        "close",
-                    ))))
+                    .reject("400", "Bad Request", &[])
                     .await
-                    .unwrap();
+                    .expect("Failed to reject");
 
-                websocketz.into_inner()
+                let (_, result) = websocketz
+                    .try_accept::<16, _, _, CustomError>(AcceptOptions::default(), |_| Ok(()))
+                    .await;
+
+                result.expect("Handshake failed");
             };
 
             let client = async move {
-                let req = Request::builder()
-                    .method("GET")
-                    .uri("/")
-                    .header(UPGRADE, "websocket")
+                let (mut read, mut write) = tokio::io::split(client);
+
+                let mut response = std::vec::Vec::new();
+
+                let (written, read) = tokio::join!(
+                    write.write_all(REQUESTS.as_bytes()),
+                    read.read_to_end(&mut response)
+                );
+
+                written.unwrap();
+                read.unwrap();
+
+                let response = core::str::from_utf8(&response).unwrap();
+
+                assert!(response.starts_with(
+                    "HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\n\r\nHTTP/1.1 101 Switching Protocols\r\n"
+                ));
+            };
+
+            tokio::join!(server, client);
+        }
+
+        const REQUEST_WITH_FRAME: &[u8] = b"GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            \r\n\
+            \x81\x85\x00\x00\x00\x00Hello";
+
+        #[tokio::test]
+        async fn early_frames_are_kept() {
+            let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            client
+                .write_all(REQUEST_WITH_FRAME)
+                .await
+                .expect("Failed to write request");
+
+            let mut websocketz = WebSocket::accept::<16>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Handshake failed");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        }
+
+        #[tokio::test]
+        async fn reject_early_frames() {
+            let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            client
+                .write_all(REQUEST_WITH_FRAME)
+                .await
+                .expect("Failed to write request");
+
+            let result = WebSocket::accept::<16>(
+                AcceptOptions::default().with_reject_early_frames(true),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await;
+
+            assert!(matches!(
+                result,
+                Err(Error::Handshake(HandshakeError::EarlyFrames))
+            ));
+        }
+
+        #[tokio::test]
+        async fn connection_closed() {
+            let (_, server) = tokio::io::duplex(16);
+
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [];
+
+            match WebSocket::accept::<16>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            {
+                Ok(_) => panic!("Expected error, but got Ok"),
+                Err(error) => {
+                    assert!(matches!(
+                        error,
+                        Error::Handshake(HandshakeError::ConnectionClosed)
+                    ));
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn ok() {
+            let (server, client) = tokio::io::duplex(16);
+
+            // Handshake requires larger buffers than SIZE
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [];
+
+            let server = async move {
+                let mut websocketz = WebSocket::accept::<16>(
+                    AcceptOptions::default(),
+                    FromTokio::new(server),
+                    StdRng::from_os_rng(),
+                    read_buf,
+                    write_buf,
+                    fragments_buf,
+                )
+                .await
+                .unwrap();
+
+                websocketz
+                    .send(Message::Close(Some(CloseFrame::new(
+                        CloseCode::Normal,
+                        "close",
+                    ))))
+                    .await
+                    .unwrap();
+
+                websocketz.into_inner()
+            };
+
+            let client = async move {
+                let req = Request::builder()
+                    .method("GET")
+                    .uri("/")
+                    .header(UPGRADE, "websocket")
                     .header(CONNECTION, "upgrade")
                     .header(
                         "Sec-WebSocket-Key",
@@ -1002,6 +1470,86 @@ mod fragmentation {
     }
 }
 
+mod maybe_next {
+    use super::*;
+
+    #[tokio::test]
+    async fn yields_none_until_message_is_complete() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_fragmented(Message::Text("Hello, world!"), 4)
+                .await
+                .expect("Failed to send fragmented message");
+
+            websocketz
+                .send(Message::Ping(b"ping"))
+                .await
+                .expect("Failed to send ping message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Pong(payload))) => assert_eq!(payload, b"ping"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut pending = 0;
+
+            loop {
+                match websocketz.maybe_next().await {
+                    Some(Ok(Some(Message::Text(text)))) => {
+                        assert_eq!(text, "Hello, world!");
+
+                        break;
+                    }
+                    Some(Ok(None)) => pending += 1,
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+
+            // At least the three non-final fragments.
+            assert!(pending >= 3);
+
+            // The ping is answered without yielding a message.
+            loop {
+                match websocketz.maybe_next().await {
+                    Some(Ok(None)) => {}
+                    None => break,
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
 mod auto {
     use crate::{
         CloseFrame,
@@ -1062,7 +1610,7 @@ mod auto {
     }
 
     #[tokio::test]
-    async fn close() {
+    async fn toggle_pong_at_runtime() {
         let (client, server) = tokio::io::duplex(16);
 
         let client = async move {
@@ -1078,32 +1626,15 @@ mod auto {
                 fragments_buf,
             );
 
-            // Send a close frame
-            websocketz
-                .send(Message::Close(Some(CloseFrame::new(
-                    CloseCode::Normal,
-                    "close",
-                ))))
-                .await
-                .expect("Failed to send close message");
-
-            // Expect a close frame in response
-            match next!(websocketz) {
-                Some(Ok(Message::Close(Some(frame)))) => {
-                    assert_eq!(frame.code(), CloseCode::Normal);
-                    assert_eq!(frame.reason(), "close");
-                }
-                message => panic!("Unexpected message: {message:?}"),
-            }
-
-            // Ensure the connection is closed
-            assert!(next!(websocketz).is_none());
+            for (ping, pong) in [(b"one", b"manual".as_slice()), (b"two", b"two")] {
+                websocketz
+                    .send(Message::Ping(ping))
+                    .await
+                    .expect("Failed to send ping message");
 
-            // Attempt to send another message after close should fail
-            match websocketz.send(Message::Text("test")).await {
-                Ok(_) => panic!("Expected error after close, but got Ok"),
-                Err(error) => {
-                    assert!(matches!(error, Error::Write(WriteError::ConnectionClosed)));
+                match next!(websocketz) {
+                    Some(Ok(Message::Pong(payload))) => assert_eq!(payload, pong),
+                    message => panic!("Unexpected message: {message:?}"),
                 }
             }
         };
@@ -1121,126 +1652,3268 @@ mod auto {
                 fragments_buf,
             );
 
-            while next!(websocketz).is_some() {}
+            assert!(websocketz.auto_pong());
 
-            // Attempt to send another message after close should fail
-            match websocketz.send(Message::Text("test")).await {
-                Ok(_) => panic!("Expected error after close, but got Ok"),
-                Err(error) => {
-                    assert!(matches!(error, Error::Write(WriteError::ConnectionClosed)));
-                }
+            websocketz.set_auto_pong(false);
+
+            assert!(!websocketz.auto_pong());
+
+            match next!(websocketz) {
+                Some(Ok(Message::Ping(payload))) => assert_eq!(payload, b"one"),
+                message => panic!("Unexpected message: {message:?}"),
             }
+
+            websocketz
+                .send(Message::Pong(b"manual"))
+                .await
+                .expect("Failed to send pong message");
+
+            websocketz.set_auto_pong(true);
+
+            while next!(websocketz).is_some() {}
         };
 
         tokio::join!(server, client);
     }
-}
 
-mod protocol {
-    use tokio::io::AsyncWriteExt;
+    #[tokio::test]
+    async fn toggle_close_at_runtime() {
+        use tokio::io::AsyncWriteExt;
 
-    use crate::error::{Error, ProtocolError, ReadError};
+        let (client, mut server) = tokio::io::duplex(SIZE);
 
-    use super::*;
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
 
-    macro_rules! quick_protocol_error {
-        ($frame:ident, $error:ident) => {
-            let (client, mut server) = tokio::io::duplex(16);
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
 
-            let client = async move {
-                let read_buf = &mut [0u8; SIZE];
-                let write_buf = &mut [0u8; SIZE];
-                let fragments_buf = &mut [0u8; SIZE];
+        assert!(websocketz.auto_close());
 
-                let mut websocketz = WebSocket::client(
-                    FromTokio::new(client),
-                    StdRng::from_os_rng(),
-                    read_buf,
-                    write_buf,
-                    fragments_buf,
-                );
+        websocketz.set_auto_close(false);
 
-                match next!(websocketz) {
-                    Some(Err(error)) => {
-                        std::println!("Received error: {error:?}");
-                        assert!(matches!(
-                            error,
-                            Error::Read(ReadError::Protocol(ProtocolError::$error))
-                        ));
-                    }
-                    message => panic!("Unexpected message: {message:?}"),
+        assert!(!websocketz.auto_close());
+
+        server
+            .write_all(&[0x88, 0x00])
+            .await
+            .expect("Failed to write frame");
+
+        match next!(websocketz) {
+            Some(Ok(Message::Close(None))) => {}
+            message => panic!("Unexpected message: {message:?}"),
+        }
+
+        // The close frame was not answered, the connection is still open.
+        websocketz
+            .send(Message::Text("Still open"))
+            .await
+            .expect("Failed to send text message");
+    }
+
+    #[tokio::test]
+    async fn pong_payload_limit() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; 256];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Ping(&[7; 125]))
+                .await
+                .expect("Failed to send ping message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Pong(payload))) => {
+                    assert_eq!(payload, &[7; 16]);
                 }
-            };
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
 
-            let server = async move {
-                server.write_all($frame).await.unwrap();
+        let server = async move {
+            let read_buf = &mut [0u8; 256];
+            // Too small to echo the whole ping payload.
+            let write_buf = &mut [0u8; 32];
+            let fragments_buf = &mut [0u8; SIZE];
 
-                server
-            };
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_pong_payload_limit(16);
 
-            tokio::join!(client, server);
+            while next!(websocketz).is_some() {}
         };
+
+        tokio::join!(server, client);
     }
 
     #[tokio::test]
-    async fn invalid_close_frame() {
-        const FRAME: &[u8] = &[
-            0x88, // FIN=1, RSV1-3=0, opcode=0x8 (Close)
-            0x01, // MASK=0 (unmasked), payload length = 1
-            0x37, // Single byte of payload (invalid)
-        ];
+    async fn close() {
+        let (client, server) = tokio::io::duplex(16);
 
-        quick_protocol_error!(FRAME, InvalidCloseFrame);
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            // Send a close frame
+            websocketz
+                .send(Message::Close(Some(CloseFrame::new(
+                    CloseCode::Normal,
+                    "close",
+                ))))
+                .await
+                .expect("Failed to send close message");
+
+            // Expect a close frame in response
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => {
+                    assert_eq!(frame.code(), CloseCode::Normal);
+                    assert_eq!(frame.reason(), "close");
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            // Ensure the connection is closed
+            assert!(next!(websocketz).is_none());
+
+            // Attempt to send another message after close should fail
+            match websocketz.send(Message::Text("test")).await {
+                Ok(_) => panic!("Expected error after close, but got Ok"),
+                Err(error) => {
+                    assert!(matches!(error, Error::Write(WriteError::ConnectionClosed)));
+                }
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            while next!(websocketz).is_some() {}
+
+            // Attempt to send another message after close should fail
+            match websocketz.send(Message::Text("test")).await {
+                Ok(_) => panic!("Expected error after close, but got Ok"),
+                Err(error) => {
+                    assert!(matches!(error, Error::Write(WriteError::ConnectionClosed)));
+                }
+            }
+        };
+
+        tokio::join!(server, client);
     }
+}
 
-    #[tokio::test]
-    async fn invalid_close_code() {
-        const FRAME: &[u8] = &[
-            0x88, // FIN + opcode=0x8 (Close)
-            0x02, // Payload length = 2 (only status code, no reason)
-            0x03, 0xED, // Status code: 1005 (not allowed)
-        ];
+mod filter {
+    use crate::{FirstFrame, Verdict};
 
-        quick_protocol_error!(FRAME, InvalidCloseCode);
+    use super::*;
+
+    fn reject_binary(frame: &FirstFrame<'_>) -> Verdict {
+        match frame.is_binary() {
+            true => Verdict::Reject,
+            false => Verdict::Accept,
+        }
     }
 
     #[tokio::test]
-    async fn invalid_utf8_close() {
-        const FRAME: &[u8] = &[
-            0x88, // FIN + opcode=0x8 (Close)
-            0x03, // Payload length = 3 (2 bytes code + 1 byte invalid UTF-8)
-            0x03, 0xE8, // Status code: 1000 (normal closure)
-            0xFF, // Invalid UTF-8 byte
-        ];
+    async fn rejected_messages_are_skipped() {
+        let (client, server) = tokio::io::duplex(16);
 
-        quick_protocol_error!(FRAME, InvalidUTF8);
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        // Rejected fragments are never copied into the fragments buffer.
+        let fragments_buf = &mut [];
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            for (binary, text) in BINARY_MESSAGES.iter().zip(STR_MESSAGES) {
+                for (index, chunk) in binary.chunks(8).enumerate() {
+                    let opcode = match index {
+                        0 => fastwebsockets::OpCode::Binary,
+                        _ => fastwebsockets::OpCode::Continuation,
+                    };
+
+                    let fin = (index + 1) * 8 >= binary.len();
+
+                    fastwebsockets
+                        .write_frame(fastwebsockets::Frame::new(
+                            fin,
+                            opcode,
+                            None,
+                            fastwebsockets::Payload::Borrowed(chunk),
+                        ))
+                        .await
+                        .expect("Failed to send binary fragment");
+                }
+
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::binary(
+                        fastwebsockets::Payload::Borrowed(binary),
+                    ))
+                    .await
+                    .expect("Failed to send binary message");
+
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::text(
+                        fastwebsockets::Payload::Borrowed(text.as_bytes()),
+                    ))
+                    .await
+                    .expect("Failed to send text message");
+            }
+        };
+
+        let client = async move {
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_message_filter(reject_binary);
+
+            let mut str_index = 0;
+
+            loop {
+                match next!(websocketz) {
+                    Some(Ok(Message::Text(payload))) => {
+                        assert_eq!(payload, STR_MESSAGES[str_index]);
+                        str_index += 1;
+                    }
+                    None => break,
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+
+            assert_eq!(str_index, STR_MESSAGES.len());
+        };
+
+        tokio::join!(server, client);
     }
+}
+
+mod shutdown {
+    use tokio::io::AsyncReadExt;
+
+    use crate::{CloseFrame, WebSocketWrite};
+
+    use super::*;
 
     #[tokio::test]
-    async fn invalid_utf8_text() {
-        const FRAME: &[u8] = &[
-            0x81, // FIN + opcode 0x1 (text)
-            0x01, // payload length = 1
-            0xFF, // invalid UTF-8 byte
-        ];
+    async fn send_close_and_flush_is_idempotent() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
 
-        quick_protocol_error!(FRAME, InvalidUTF8);
+        let write_buf = &mut [0u8; SIZE];
+
+        let mut websocketz =
+            WebSocketWrite::client(FromTokio::new(client), StdRng::from_os_rng(), write_buf);
+
+        for _ in 0..3 {
+            websocketz
+                .send_close_and_flush(Some(CloseFrame::no_reason(CloseCode::Away)))
+                .await
+                .expect("Failed to close");
+        }
+
+        drop(websocketz);
+
+        let mut bytes = std::vec::Vec::new();
+        server
+            .read_to_end(&mut bytes)
+            .await
+            .expect("Failed to read");
+
+        // FIN + Close, masked payload of 2 bytes, 4 bytes mask, 2 bytes close code.
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(bytes[0], 0x88);
+        assert_eq!(bytes[1], 0x82);
     }
+}
+
+mod errors {
+    use std::io::ErrorKind;
+
+    use crate::{WebSocketWrite, options::ConnectOptions};
+
+    use super::*;
 
     #[tokio::test]
-    async fn invalid_fragment() {
-        const FRAMES: &[u8] = &[
-            // Start a fragmented text frame
-            0x01, // FIN = 0, opcode = 0x1 (Text, not final)
-            0x01, // Payload length = 1
-            0x41, // 'A'
-            // Try to send a new Binary message while the previous fragment isn't finished
-            0x82, // FIN = 1, opcode = 0x2 (Binary, complete)
-            0x01, // Payload length = 1
-            0x42, // 'B'
-        ];
+    async fn write_frame_exposes_io_error() {
+        let (client, server) = tokio::io::duplex(SIZE);
+        drop(server);
+
+        let write_buf = &mut [0u8; SIZE];
+
+        let mut websocketz =
+            WebSocketWrite::client(FromTokio::new(client), StdRng::from_os_rng(), write_buf);
 
-        quick_protocol_error!(FRAMES, InvalidFragment);
+        let error = websocketz
+            .send(Message::Text("Hello"))
+            .await
+            .expect_err("Send must fail");
+
+        assert_eq!(
+            error.io_error().map(|err| err.kind()),
+            Some(ErrorKind::BrokenPipe)
+        );
+        assert_eq!(
+            error.into_io_error().map(|err| err.kind()),
+            Some(ErrorKind::BrokenPipe)
+        );
+    }
+
+    #[tokio::test]
+    async fn write_http_exposes_io_error() {
+        let (client, server) = tokio::io::duplex(SIZE);
+        drop(server);
+
+        let read_buf = &mut [0u8; 1024];
+        let write_buf = &mut [0u8; 1024];
+        let fragments_buf = &mut [0u8; 1024];
+
+        let error = WebSocket::connect::<16>(
+            ConnectOptions::default(),
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .err()
+        .expect("Handshake must fail");
+
+        assert!(matches!(
+            error,
+            crate::error::Error::Write(crate::error::WriteError::WriteHttp(_))
+        ));
+        assert_eq!(
+            error.into_io_error().map(|err| err.kind()),
+            Some(ErrorKind::BrokenPipe)
+        );
+    }
+
+    #[test]
+    fn non_io_errors_have_no_io_error() {
+        let error = crate::error::Error::<std::io::Error>::PeerUnresponsive;
+
+        assert!(error.io_error().is_none());
+        assert!(error.into_io_error().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn into_std_io_error() {
+        async fn send(
+            websocketz: &mut WebSocketWrite<'_, FromTokio<tokio::io::DuplexStream>, StdRng>,
+        ) -> std::io::Result<()> {
+            websocketz.send(Message::Text("Hello")).await?;
+
+            Ok(())
+        }
+
+        let (client, server) = tokio::io::duplex(SIZE);
+        drop(server);
+
+        let write_buf = &mut [0u8; SIZE];
+
+        let mut websocketz =
+            WebSocketWrite::client(FromTokio::new(client), StdRng::from_os_rng(), write_buf);
+
+        // The underlying I/O error is returned as is.
+        let error = send(&mut websocketz).await.expect_err("Send must fail");
+
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+        assert!(error.get_ref().is_none());
+
+        let error = std::io::Error::from(crate::error::Error::<std::io::Error>::Write(
+            crate::error::WriteError::ConnectionClosed,
+        ));
+
+        assert_eq!(error.kind(), ErrorKind::NotConnected);
+        assert_eq!(std::format!("{error}"), "Write error: Connection closed");
+
+        let error =
+            std::io::Error::from(crate::error::MessageError::<std::io::Error>::MessageTooLarge);
+
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
+
+mod connect_any {
+    use tokio::io::DuplexStream;
+
+    use crate::{
+        error::{ConnectAnyError, Error, WriteError},
+        options::{AcceptOptions, ConnectOptions, ConnectTarget},
+    };
+
+    use super::*;
+
+    const TARGETS: &[ConnectTarget<'static, 'static>] = &[
+        ConnectTarget::new("down", ConnectOptions::new_unchecked("/")),
+        ConnectTarget::new("closed", ConnectOptions::new_unchecked("/")),
+        ConnectTarget::new("up", ConnectOptions::new_unchecked("/")),
+    ];
+
+    async fn open(
+        target: &ConnectTarget<'_, '_>,
+        up: &mut Option<DuplexStream>,
+    ) -> Result<(FromTokio<DuplexStream>, StdRng), &'static str> {
+        let stream = match target.endpoint() {
+            "closed" => tokio::io::duplex(16).0,
+            "up" => up.take().ok_or("Already connected")?,
+            _ => return Err("Unreachable"),
+        };
+
+        Ok((FromTokio::new(stream), StdRng::from_os_rng()))
+    }
+
+    #[tokio::test]
+    async fn failover() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut up = Some(client);
+
+            let (mut websocketz, index) = WebSocket::connect_any::<16, _, _>(
+                TARGETS,
+                async |target: &ConnectTarget<'_, '_>| open(target, &mut up).await,
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to connect");
+
+            assert_eq!(index, 2);
+
+            websocketz
+                .send(Message::Text("Hello"))
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::accept::<16>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to accept");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn last_error_is_returned() {
+        let read_buf = &mut [0u8; SIZE * 2];
+        let write_buf = &mut [0u8; SIZE * 2];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let error = WebSocket::connect_any::<16, _, _>(
+            &TARGETS[..2],
+            async |target: &ConnectTarget<'_, '_>| open(target, &mut None).await,
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .err()
+        .expect("Connect must fail");
+
+        assert!(matches!(
+            error,
+            ConnectAnyError::WebSocket(Error::Write(WriteError::WriteHttp(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn no_targets() {
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let error = WebSocket::connect_any::<16, _, _>(
+            &[],
+            async |target: &ConnectTarget<'_, '_>| open(target, &mut None).await,
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .err()
+        .expect("Connect must fail");
+
+        assert!(matches!(error, ConnectAnyError::NoTargets));
+    }
+}
+
+mod stats {
+    use core::{
+        convert::Infallible,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use crate::{
+        clock::Clock,
+        http::{Request, Response},
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    /// Advances by one millisecond on every call.
+    struct SteppingClock {
+        now: AtomicU64,
+    }
+
+    impl Clock for SteppingClock {
+        fn tick_hz(&self) -> u64 {
+            1_000
+        }
+
+        fn now(&self) -> u64 {
+            self.now.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_stats() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let (websocketz, (), result) = WebSocket::connect_with(
+                ConnectOptions::default().with_clock(&SteppingClock {
+                    now: AtomicU64::new(0),
+                }),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                |_: &Response<'_, 16>| Ok::<_, Infallible>(()),
+            )
+            .await
+            .expect("Failed to connect");
+
+            assert_eq!(result.stats(), websocketz.handshake_stats());
+
+            result.stats()
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let (websocketz, (), result) = WebSocket::accept_with(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                |_: &Request<'_, 16>| Ok::<_, Infallible>(()),
+            )
+            .await
+            .expect("Failed to accept");
+
+            assert_eq!(result.stats(), websocketz.handshake_stats());
+
+            result.stats()
+        };
+
+        let (server, client) = tokio::join!(server, client);
+
+        assert!(client.bytes_written() > 0);
+        assert!(server.bytes_written() > 0);
+        assert_eq!(client.bytes_written(), server.bytes_read());
+        assert_eq!(server.bytes_written(), client.bytes_read());
+
+        assert_eq!(client.duration(), Some(Duration::from_millis(1)));
+        assert_eq!(server.duration(), None);
+    }
+
+    #[test]
+    fn no_handshake() {
+        let websocketz = WebSocket::client(
+            crate::mock::Noop,
+            crate::mock::Noop,
+            &mut [],
+            &mut [],
+            &mut [],
+        );
+
+        assert_eq!(
+            websocketz.handshake_stats(),
+            crate::HandshakeStats::default()
+        );
+    }
+}
+
+#[cfg(feature = "danger-frames")]
+mod danger {
+    use crate::{
+        danger::RawFrame,
+        error::{FrameDecodeError, MessageError},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reserved_bits_are_rejected() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_raw(RawFrame::new(0x2, b"compressed").with_rsv(0b100))
+                .await
+                .expect("Failed to send raw frame");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Err(MessageError::ReadFrame(framez::ReadError::Decode(
+                    FrameDecodeError::ReservedBitsNotZero,
+                )))) => {}
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn invalid_text_without_validation() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_raw(RawFrame::new(0x1, &[0xFF, 0xFE]))
+                .await
+                .expect("Failed to send raw frame");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_text_validation(false);
+
+            match next!(websocketz) {
+                Some(Ok(Message::TextUnchecked(payload))) => assert_eq!(payload, &[0xFF, 0xFE]),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+#[cfg(feature = "pre-masked")]
+mod pre_masked {
+    use crate::{
+        error::{Error, WriteError},
+        pre_masked::FrameHeader,
+    };
+
+    use super::*;
+
+    fn masked(payload: &[u8], mask: [u8; 4]) -> std::vec::Vec<u8> {
+        payload
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| byte ^ mask[index % 4])
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn received_unmasked() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mask = [0x37, 0xfa, 0x21, 0x3d];
+
+            websocketz
+                .send_pre_masked(FrameHeader::text(), mask, &masked(b"Hello", mask))
+                .await
+                .expect("Failed to send pre-masked frame");
+
+            let (first, last) = BINARY_MESSAGES[0].split_at(2);
+
+            websocketz
+                .send_pre_masked(
+                    FrameHeader::binary().with_fin(false),
+                    mask,
+                    &masked(first, mask),
+                )
+                .await
+                .expect("Failed to send pre-masked fragment");
+
+            websocketz
+                .send_pre_masked(FrameHeader::continuation(), [0; 4], last)
+                .await
+                .expect("Failed to send pre-masked fragment");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(payload))) => assert_eq!(payload, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => assert_eq!(payload, BINARY_MESSAGES[0]),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn server_is_rejected() {
+        let (_client, server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let result = websocketz
+            .send_pre_masked(FrameHeader::binary(), [0; 4], b"masked")
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Write(WriteError::PreEncodedMaskMismatch))
+        ));
+    }
+}
+
+mod text_validation {
+    use super::*;
+
+    #[tokio::test]
+    async fn disabled() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for message in STR_MESSAGES {
+                websocketz
+                    .send(Message::Text(message))
+                    .await
+                    .expect("Failed to send message");
+
+                websocketz
+                    .send_fragmented(Message::Text(message), 8)
+                    .await
+                    .expect("Failed to send fragmented message");
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_text_validation(false);
+
+            for message in STR_MESSAGES.iter().flat_map(|message| [message, message]) {
+                match next!(websocketz) {
+                    Some(Ok(Message::TextUnchecked(payload))) => {
+                        assert_eq!(payload, message.as_bytes())
+                    }
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod close_wait {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{
+        CloseFrame,
+        error::{Error, WriteError},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn unacknowledged_close() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_close_wait_frames(2);
+
+        websocketz
+            .send(Message::Close(Some(CloseFrame::no_reason(CloseCode::Away))))
+            .await
+            .expect("Failed to send close message");
+
+        // The peer keeps sending text messages instead of acknowledging the close frame.
+        for _ in 0..5 {
+            server
+                .write_all(&[0x81, 0x01, b'a'])
+                .await
+                .expect("Failed to write frame");
+        }
+
+        for _ in 0..2 {
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "a"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        }
+
+        assert!(next!(websocketz).is_none());
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_close_with_pings() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_close_wait_frames(2);
+
+        websocketz
+            .send(Message::Close(Some(CloseFrame::no_reason(CloseCode::Away))))
+            .await
+            .expect("Failed to send close message");
+
+        // The peer keeps pinging instead of acknowledging the close frame.
+        for _ in 0..5 {
+            server
+                .write_all(&[0x89, 0x00])
+                .await
+                .expect("Failed to write frame");
+        }
+
+        for _ in 0..2 {
+            match next!(websocketz) {
+                Some(Ok(Message::Ping(payload))) => assert!(payload.is_empty()),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        }
+
+        assert!(next!(websocketz).is_none());
+
+        assert!(matches!(
+            websocketz.send(Message::Text("Closed")).await,
+            Err(Error::Write(WriteError::ConnectionClosed))
+        ));
+
+        drop(websocketz);
+
+        // Only the masked close frame was sent, the pings were not answered.
+        let mut received = Vec::new();
+
+        server
+            .read_to_end(&mut received)
+            .await
+            .expect("Failed to read");
+
+        assert_eq!(received.len(), 2 + 4 + 2);
+        assert_eq!(received[0], 0x88);
+    }
+
+    #[tokio::test]
+    async fn peer_close_is_not_counted() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_close_wait_frames(0);
+
+        websocketz
+            .send(Message::Close(None))
+            .await
+            .expect("Failed to send close message");
+
+        server
+            .write_all(&[0x88, 0x00])
+            .await
+            .expect("Failed to write frame");
+
+        match next!(websocketz) {
+            Some(Ok(Message::Close(None))) => {}
+            message => panic!("Unexpected message: {message:?}"),
+        }
+    }
+}
+
+mod close {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{
+        CloseFrame,
+        error::{Error, WriteError},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn discards_messages_until_peer_close() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .close(Some(CloseFrame::new(CloseCode::Normal, "Bye")))
+                .await
+                .expect("Failed to close");
+
+            assert!(matches!(
+                websocketz.send(Message::Text("Closed")).await,
+                Err(Error::Write(WriteError::ConnectionClosed))
+            ));
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for text in STR_MESSAGES {
+                websocketz
+                    .send(Message::Text(text))
+                    .await
+                    .expect("Failed to send text message");
+            }
+
+            // The client's close frame is answered automatically.
+            assert!(next!(websocketz).is_none());
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn eof_after_close_sent() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz.close(None).await.expect("Failed to close");
+        };
+
+        let server = async move {
+            let mut buf = [0u8; SIZE];
+
+            // The peer reads the close frame and drops the connection without acknowledging it.
+            let n = server.read(&mut buf).await.expect("Failed to read");
+
+            assert_eq!(buf[..n.min(1)], [0x88]);
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn pinging_peer_is_cut_off() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_close_wait_frames(2);
+
+        // The peer keeps pinging instead of acknowledging the close frame.
+        for _ in 0..10 {
+            server
+                .write_all(&[0x89, 0x00])
+                .await
+                .expect("Failed to write frame");
+        }
+
+        websocketz.close(None).await.expect("Failed to close");
+
+        drop(websocketz);
+
+        // Only the masked close frame was sent, the pings were not answered.
+        let mut received = Vec::new();
+
+        server
+            .read_to_end(&mut received)
+            .await
+            .expect("Failed to read");
+
+        assert_eq!(received.len(), 2 + 4);
+        assert_eq!(received[0], 0x88);
+    }
+}
+
+mod message_writer {
+    use crate::error::{Error, WriteError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn message_larger_than_write_buffer() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let payload: [u8; 200] = core::array::from_fn(|i| i as u8);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; 256];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(received))) => assert_eq!(received, payload),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello, world!"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            // Fragments carry at most 18 bytes.
+            let write_buf = &mut [0u8; 32];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut writer = websocketz.send_binary_stream();
+
+            assert!(writer.is_empty());
+
+            for chunk in payload[..150].chunks(50) {
+                writer.write(chunk).await.expect("Failed to write chunk");
+            }
+
+            assert!(!writer.is_empty());
+
+            writer
+                .finish(&payload[150..])
+                .await
+                .expect("Failed to finish message");
+
+            websocketz
+                .send_text_stream()
+                .finish(b"Hello, world!")
+                .await
+                .expect("Failed to send text message");
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn connection_closed() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        websocketz
+            .send(Message::Close(None))
+            .await
+            .expect("Failed to send close message");
+
+        assert!(matches!(
+            websocketz.send_text_stream().finish(b"Closed").await,
+            Err(Error::Write(WriteError::ConnectionClosed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn write_buffer_too_small() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; 14];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        assert!(matches!(
+            websocketz.send_binary_stream().write(b"chunk").await,
+            Err(Error::Write(WriteError::WriteFrame(
+                framez::WriteError::Encode(crate::error::FrameEncodeError::BufferTooSmall)
+            )))
+        ));
+    }
+}
+
+mod next_fragment {
+    use crate::{StreamItem, next_fragment};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn passes_fragments_through() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let payload: [u8; 100] = core::array::from_fn(|i| i as u8);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_fragmented(Message::Binary(&payload), 40)
+                .await
+                .expect("Failed to send fragmented message");
+
+            websocketz
+                .send(Message::Text("Hello, world!"))
+                .await
+                .expect("Failed to send text message");
+
+            websocketz
+                .send_fragmented(Message::Text("Hello, fragments!"), 8)
+                .await
+                .expect("Failed to send fragmented message");
+
+            websocketz
+                .send(Message::Ping(b"ping"))
+                .await
+                .expect("Failed to send ping message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Pong(payload))) => assert_eq!(payload, b"ping"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            // Too small to reassemble the binary message.
+            let fragments_buf = &mut [0u8; 16];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut received = [0u8; 100];
+            let mut len = 0;
+
+            for (first, fin) in [(true, false), (false, false), (false, true)] {
+                match next_fragment!(websocketz) {
+                    Some(Ok(StreamItem::Fragment(fragment))) => {
+                        assert!(fragment.is_binary());
+                        assert_eq!(fragment.is_first(), first);
+                        assert_eq!(fragment.is_final(), fin);
+
+                        received[len..][..fragment.payload().len()]
+                            .copy_from_slice(fragment.payload());
+                        len += fragment.payload().len();
+                    }
+                    item => panic!("Unexpected item: {item:?}"),
+                }
+            }
+
+            assert_eq!(received, payload);
+
+            match next_fragment!(websocketz) {
+                Some(Ok(StreamItem::Message(Message::Text(text)))) => {
+                    assert_eq!(text, "Hello, world!")
+                }
+                item => panic!("Unexpected item: {item:?}"),
+            }
+
+            let first = websocketz
+                .with_next_fragment(|item| match item {
+                    StreamItem::Fragment(fragment) => (
+                        fragment.is_text(),
+                        fragment.is_first(),
+                        fragment.payload() == b"Hello, f",
+                    ),
+                    item => panic!("Unexpected item: {item:?}"),
+                })
+                .await;
+
+            assert!(matches!(first, Some(Ok((true, true, true)))));
+
+            let mut fragments = 1;
+
+            loop {
+                match next_fragment!(websocketz) {
+                    Some(Ok(StreamItem::Fragment(fragment))) => {
+                        assert!(fragment.is_text());
+                        assert!(!fragment.is_first());
+
+                        fragments += 1;
+
+                        if fragment.is_final() {
+                            break;
+                        }
+                    }
+                    item => panic!("Unexpected item: {item:?}"),
+                }
+            }
+
+            assert_eq!(fragments, 3);
+
+            // The ping is answered automatically.
+            assert!(next_fragment!(websocketz).is_none());
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use tokio::io::DuplexStream;
+
+    use crate::{
+        error::TlsError,
+        options::{AcceptOptions, ConnectOptions},
+        tls::{TlsBufferPlan, TlsBuffers},
+    };
+
+    use super::*;
+
+    const PLAN: TlsBufferPlan = TlsBufferPlan::new(64, 32, SIZE * 2, SIZE * 2, SIZE);
+
+    /// Stands in for a TLS handshake, passing the stream through.
+    async fn handshake(
+        stream: DuplexStream,
+        buffers: TlsBuffers<'_>,
+    ) -> Result<FromTokio<DuplexStream>, &'static str> {
+        assert_eq!(buffers.read.len(), 64);
+        assert_eq!(buffers.write.len(), 32);
+
+        Ok(FromTokio::new(stream))
+    }
+
+    #[tokio::test]
+    async fn connect_accept() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let buffer = &mut [0u8; PLAN.len()];
+
+            let mut websocketz = WebSocket::connect_tls::<16, _, _, _>(
+                ConnectOptions::new_unchecked("/"),
+                client,
+                handshake,
+                StdRng::from_os_rng(),
+                buffer,
+                PLAN,
+            )
+            .await
+            .expect("Failed to connect");
+
+            websocketz
+                .send(Message::Text("Hello"))
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let buffer = &mut [0u8; PLAN.len()];
+
+            let mut websocketz = WebSocket::accept_tls::<16, _, _, _>(
+                AcceptOptions::default(),
+                server,
+                handshake,
+                StdRng::from_os_rng(),
+                buffer,
+                PLAN,
+            )
+            .await
+            .expect("Failed to accept");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(client, server);
+    }
+
+    #[tokio::test]
+    async fn buffer_too_small() {
+        let (client, _server) = tokio::io::duplex(16);
+
+        let buffer = &mut [0u8; PLAN.len() - 1];
+
+        let result = WebSocket::connect_tls::<16, _, _, _>(
+            ConnectOptions::new_unchecked("/"),
+            client,
+            handshake,
+            StdRng::from_os_rng(),
+            buffer,
+            PLAN,
+        )
+        .await;
+
+        assert!(matches!(result, Err(TlsError::BufferTooSmall)));
+    }
+
+    #[tokio::test]
+    async fn tls_error() {
+        let (client, _server) = tokio::io::duplex(16);
+
+        let buffer = &mut [0u8; PLAN.len()];
+
+        let result = WebSocket::<'_, FromTokio<DuplexStream>, _>::connect_tls::<16, _, _, _>(
+            ConnectOptions::new_unchecked("/"),
+            client,
+            async |_, _| Err("Handshake failure"),
+            StdRng::from_os_rng(),
+            buffer,
+            PLAN,
+        )
+        .await;
+
+        assert!(matches!(result, Err(TlsError::Tls("Handshake failure"))));
+    }
+}
+
+mod keepalive {
+    use core::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    use crate::{clock::Clock, error::Error, options::Keepalive};
+
+    use super::*;
+
+    /// A clock counting seconds, advanced manually.
+    struct ManualClock(AtomicU64);
+
+    impl ManualClock {
+        fn set(&self, secs: u64) {
+            self.0.store(secs, Ordering::Relaxed);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn tick_hz(&self) -> u64 {
+            1
+        }
+
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    #[tokio::test]
+    async fn pings_and_times_out() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let clock = ManualClock(AtomicU64::new(0));
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_keepalive(Keepalive::new(
+                Duration::from_secs(10),
+                Duration::from_secs(5),
+            ));
+
+            websocketz.tick(&clock).await.expect("Failed to tick");
+
+            clock.set(9);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+            assert_eq!(websocketz.unanswered_pings(), 0);
+
+            clock.set(10);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+            assert_eq!(websocketz.unanswered_pings(), 1);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Pong(payload))) => assert!(payload.is_empty()),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            // The pong restarts the interval.
+            clock.set(12);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+
+            clock.set(21);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+            assert_eq!(websocketz.unanswered_pings(), 0);
+
+            clock.set(22);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+            assert_eq!(websocketz.unanswered_pings(), 1);
+
+            // The pong is not read.
+            clock.set(26);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+
+            clock.set(27);
+            let result = websocketz.tick(&clock).await;
+            assert!(matches!(result, Err(Error::PingTimeout)));
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            // Pings are answered automatically, the last pong may fail once the client is gone.
+            if let Some(Ok(message)) = next!(websocketz) {
+                panic!("Unexpected message: {message:?}");
+            }
+        };
+
+        tokio::join!(client, server);
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let clock = ManualClock(AtomicU64::new(0));
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        assert_eq!(websocketz.keepalive(), None);
+
+        for secs in [0, 100, 1000] {
+            clock.set(secs);
+            websocketz.tick(&clock).await.expect("Failed to tick");
+        }
+
+        assert_eq!(websocketz.unanswered_pings(), 0);
+    }
+}
+
+#[cfg(feature = "dual-stack")]
+mod dual_stack {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::{
+        dual_stack::DualStack,
+        error::DualStackError,
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    const IPV6: &[IpAddr] = &[IpAddr::V6(Ipv6Addr::LOCALHOST)];
+    const IPV4: &[IpAddr] = &[IpAddr::V4(Ipv4Addr::LOCALHOST)];
+
+    #[tokio::test]
+    async fn falls_back_to_ipv4() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let mut client = Some(client);
+            let mut attempts = 0;
+
+            let (socket, address) = DualStack::new(IPV6, IPV4)
+                .connect(async |address: IpAddr| {
+                    attempts += 1;
+
+                    match address {
+                        IpAddr::V6(_) => Err("Network unreachable"),
+                        IpAddr::V4(_) => client.take().ok_or("Already connected"),
+                    }
+                })
+                .await
+                .expect("Failed to connect");
+
+            assert_eq!(address, IPV4[0]);
+            assert_eq!(attempts, 2);
+
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::connect::<16>(
+                ConnectOptions::new_unchecked("/"),
+                FromTokio::new(socket),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to connect");
+
+            websocketz
+                .send(Message::Text("Hello"))
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::accept::<16>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to accept");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(client, server);
+    }
+
+    #[tokio::test]
+    async fn last_error() {
+        let result = DualStack::new(IPV6, IPV4)
+            .connect(async |address: IpAddr| Err::<(), _>(address))
+            .await;
+
+        assert!(matches!(result, Err(DualStackError::Connect(address)) if address == IPV4[0]));
+
+        let result = DualStack::<IpAddr>::new(&[], &[])
+            .connect(async |_| Ok::<_, ()>(()))
+            .await;
+
+        assert!(matches!(result, Err(DualStackError::NoAddresses)));
+    }
+}
+
+mod redirects {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use crate::{
+        error::{Error, HandshakeError},
+        options::ConnectOptions,
+        websocket_core::generate_sec_accept,
+    };
+
+    use super::*;
+
+    /// Reads a handshake request, returning its request line and its `Sec-WebSocket-Accept`.
+    async fn read_request(server: &mut DuplexStream) -> (std::string::String, std::string::String) {
+        let mut request = std::vec::Vec::new();
+
+        while !request.ends_with(b"\r\n\r\n") {
+            let byte = server.read_u8().await.expect("Failed to read request");
+
+            request.push(byte);
+        }
+
+        let request = std::string::String::from_utf8(request).expect("Valid request");
+
+        let line = request.lines().next().expect("Missing request line");
+        let sec_key = request
+            .lines()
+            .find_map(|line| line.strip_prefix("sec-websocket-key: "))
+            .expect("Missing key");
+        let sec_accept = generate_sec_accept(sec_key.as_bytes());
+
+        (
+            line.into(),
+            std::string::String::from_utf8(sec_accept.to_vec()).expect("Valid accept"),
+        )
+    }
+
+    async fn switching_protocols(server: &mut DuplexStream, sec_accept: &str) {
+        let response = std::format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Accept: {sec_accept}\r\n\
+            \r\n"
+        );
+
+        server
+            .write_all(response.as_bytes())
+            .await
+            .expect("Failed to write response");
+    }
+
+    async fn connect(
+        client: DuplexStream,
+        max_redirects: u8,
+    ) -> Result<std::string::String, Error<std::io::Error>> {
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 4];
+        let fragments_buf = &mut [];
+
+        WebSocket::connect::<16>(
+            ConnectOptions::new("/ws")
+                .expect("Valid path")
+                .with_max_redirects(max_redirects),
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .map(|websocketz| websocketz.info().path().unwrap_or_default().into())
+    }
+
+    #[tokio::test]
+    async fn follows_relative_location() {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            let (line, _) = read_request(&mut server).await;
+
+            assert_eq!(line, "GET /ws HTTP/1.1");
+
+            server
+                .write_all(
+                    b"HTTP/1.1 301 Moved Permanently\r\n\
+                    Location: /ws/\r\n\
+                    Content-Length: 5\r\n\
+                    \r\n\
+                    moved",
+                )
+                .await
+                .expect("Failed to write response");
+
+            let (line, sec_accept) = read_request(&mut server).await;
+
+            assert_eq!(line, "GET /ws/ HTTP/1.1");
+
+            switching_protocols(&mut server, &sec_accept).await;
+
+            server
+        };
+
+        let client = async move { connect(client, 1).await.expect("Failed to connect") };
+
+        let (_server, path) = tokio::join!(server, client);
+
+        assert_eq!(path, "/ws/");
+    }
+
+    #[tokio::test]
+    async fn not_followed_by_default() {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            let _ = read_request(&mut server).await;
+
+            server
+                .write_all(
+                    b"HTTP/1.1 307 Temporary Redirect\r\n\
+                    Location: /ws/\r\n\
+                    Content-Length: 0\r\n\
+                    \r\n",
+                )
+                .await
+                .expect("Failed to write response");
+
+            server
+        };
+
+        let (_server, result) = tokio::join!(server, connect(client, 0));
+
+        match result {
+            Err(Error::Handshake(HandshakeError::Redirect(redirect))) => {
+                assert_eq!(redirect.status(), 307);
+                assert_eq!(redirect.location_str(), Some("/ws/"));
+                assert!(redirect.is_relative());
+                assert!(!redirect.is_truncated());
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn absolute_location_is_not_followed() {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            let _ = read_request(&mut server).await;
+
+            server
+                .write_all(
+                    b"HTTP/1.1 302 Found\r\n\
+                    Location: wss://example.com/ws\r\n\
+                    Content-Length: 0\r\n\
+                    \r\n",
+                )
+                .await
+                .expect("Failed to write response");
+
+            server
+        };
+
+        let (_server, result) = tokio::join!(server, connect(client, 3));
+
+        match result {
+            Err(Error::Handshake(HandshakeError::Redirect(redirect))) => {
+                assert_eq!(redirect.status(), 302);
+                assert_eq!(redirect.location(), b"wss://example.com/ws");
+                assert!(!redirect.is_relative());
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn closing_connection_is_not_followed() {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            let _ = read_request(&mut server).await;
+
+            server
+                .write_all(
+                    b"HTTP/1.1 301 Moved Permanently\r\n\
+                    Location: /ws/\r\n\
+                    Connection: close\r\n\
+                    Content-Length: 0\r\n\
+                    \r\n",
+                )
+                .await
+                .expect("Failed to write response");
+
+            server
+        };
+
+        let (_server, result) = tokio::join!(server, connect(client, 1));
+
+        assert!(matches!(
+            result,
+            Err(Error::Handshake(HandshakeError::Redirect(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn too_many_redirects() {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            for location in ["/a", "/b"] {
+                let _ = read_request(&mut server).await;
+
+                let response = std::format!(
+                    "HTTP/1.1 308 Permanent Redirect\r\n\
+                    Location: {location}\r\n\
+                    Content-Length: 0\r\n\
+                    \r\n"
+                );
+
+                server
+                    .write_all(response.as_bytes())
+                    .await
+                    .expect("Failed to write response");
+            }
+
+            server
+        };
+
+        let (_server, result) = tokio::join!(server, connect(client, 1));
+
+        match result {
+            Err(Error::Handshake(HandshakeError::Redirect(redirect))) => {
+                assert_eq!(redirect.location_str(), Some("/b"));
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+    }
+}
+
+mod handshake_info {
+    use crate::{
+        HandshakeCapture,
+        http::Header,
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn connect_accept_full() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 4];
+            let fragments_buf = &mut [0u8; SIZE];
+            let scratch = &mut [0u8; SIZE];
+
+            let headers = &[
+                Header {
+                    name: "Cookie",
+                    value: b"theme=dark",
+                },
+                Header {
+                    name: "X-Device",
+                    value: b"sensor-1",
+                },
+            ];
+
+            let (_websocketz, info) = WebSocket::connect_full::<16>(
+                ConnectOptions::new("/ws?token=abc")
+                    .expect("Valid path")
+                    .with_headers(headers)
+                    .expect("Valid headers")
+                    .with_protocols(&["chat", "superchat"]),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                HandshakeCapture::new(scratch).with_headers(&["x-session-id"]),
+            )
+            .await
+            .expect("Failed to connect");
+
+            assert_eq!(info.path(), None);
+            assert_eq!(info.protocol(), Some("superchat"));
+            assert!(info.cookies().eq([&b"session=42"[..]]));
+            assert_eq!(info.header("X-Session-Id"), Some(&b"42"[..]));
+            assert!(!info.is_truncated());
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 4];
+            let fragments_buf = &mut [0u8; SIZE];
+            let scratch = &mut [0u8; SIZE];
+
+            let headers = &[
+                Header {
+                    name: "Set-Cookie",
+                    value: b"session=42",
+                },
+                Header {
+                    name: "X-Session-Id",
+                    value: b"42",
+                },
+            ];
+
+            let (_websocketz, info) = WebSocket::accept_full::<16>(
+                AcceptOptions::default()
+                    .with_protocols(&["superchat"])
+                    .with_headers(headers),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                HandshakeCapture::new(scratch).with_headers(&["x-device"]),
+            )
+            .await
+            .expect("Failed to accept");
+
+            assert_eq!(info.path(), Some("/ws"));
+            assert_eq!(info.query(), Some("token=abc"));
+            assert_eq!(info.protocol(), Some("superchat"));
+            assert!(info.cookies().eq([&b"theme=dark"[..]]));
+            assert_eq!(info.header("x-device"), Some(&b"sensor-1"[..]));
+            assert!(!info.is_truncated());
+        };
+
+        tokio::join!(client, server);
+    }
+}
+
+mod pre_encoded {
+    use crate::{
+        PreEncodedMessage, WebSocketWrite,
+        error::{Error, WriteError},
+    };
+
+    use super::*;
+
+    async fn receive(client: tokio::io::DuplexStream) {
+        let mut fastwebsockets =
+            fastwebsockets::WebSocket::after_handshake(client, fastwebsockets::Role::Client);
+
+        let frame = fastwebsockets.read_frame().await.expect("Failed to read");
+
+        assert!(matches!(frame.opcode, fastwebsockets::OpCode::Binary));
+        assert_eq!(frame.payload, BINARY_MESSAGES[1]);
+    }
+
+    #[tokio::test]
+    async fn sent_to_many_clients() {
+        let buf = &mut [0u8; SIZE];
+        let message = PreEncodedMessage::encode(Message::Binary(BINARY_MESSAGES[1]), None, buf)
+            .expect("Failed to encode");
+
+        let (server_1, client_1) = tokio::io::duplex(16);
+        let (server_2, client_2) = tokio::io::duplex(16);
+
+        let server = async move {
+            for server in [server_1, server_2] {
+                let mut websocketz =
+                    WebSocketWrite::server(FromTokio::new(server), StdRng::from_os_rng(), &mut []);
+
+                websocketz
+                    .send_pre_encoded(&message)
+                    .await
+                    .expect("Failed to send pre-encoded message");
+            }
+        };
+
+        tokio::join!(server, receive(client_1), receive(client_2));
+    }
+
+    #[tokio::test]
+    async fn mask_mismatch() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let buf = &mut [0u8; SIZE];
+        let message = PreEncodedMessage::encode(Message::Binary(BINARY_MESSAGES[0]), None, buf)
+            .expect("Failed to encode");
+
+        let mut websocketz =
+            WebSocketWrite::client(FromTokio::new(client), StdRng::from_os_rng(), &mut []);
+
+        let error = websocketz.send_pre_encoded(&message).await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::Write(WriteError::PreEncodedMaskMismatch)
+        ));
+    }
+}
+
+mod integrity {
+    use crate::error::MessageError;
+
+    use super::*;
+
+    fn with_checksum(data: &[u8]) -> std::vec::Vec<u8> {
+        let mut payload = data.to_vec();
+        payload.push(checksum(data));
+        payload
+    }
+
+    fn checksum(data: &[u8]) -> u8 {
+        data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+    }
+
+    #[tokio::test]
+    async fn corrupted_messages_are_rejected() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            let valid = with_checksum(BINARY_MESSAGES[0]);
+
+            let mut corrupted = with_checksum(BINARY_MESSAGES[1]);
+            corrupted[0] ^= 0xFF;
+
+            for payload in [&valid, &corrupted] {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::binary(
+                        fastwebsockets::Payload::Borrowed(payload),
+                    ))
+                    .await
+                    .expect("Failed to send binary message");
+            }
+
+            // Fragmented messages are verified once reassembled.
+            let fragmented = with_checksum(BINARY_MESSAGES[2]);
+
+            for (index, chunk) in fragmented.chunks(8).enumerate() {
+                let opcode = match index {
+                    0 => fastwebsockets::OpCode::Binary,
+                    _ => fastwebsockets::OpCode::Continuation,
+                };
+
+                let fin = (index + 1) * 8 >= fragmented.len();
+
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(chunk),
+                    ))
+                    .await
+                    .expect("Failed to send fragment");
+            }
+
+            // Text messages are not verified.
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::text(
+                    fastwebsockets::Payload::Borrowed(STR_MESSAGES[0].as_bytes()),
+                ))
+                .await
+                .expect("Failed to send text message");
+        };
+
+        let client = async move {
+            let verifier = |payload: &[u8]| {
+                payload
+                    .split_last()
+                    .is_some_and(|(sum, data)| checksum(data) == *sum)
+            };
+
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_integrity_verifier(&verifier);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload, with_checksum(BINARY_MESSAGES[0]));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::IntegrityCheckFailed));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload, with_checksum(BINARY_MESSAGES[2]));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(payload))) => {
+                    assert_eq!(payload, STR_MESSAGES[0]);
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod progress {
+    use std::sync::Mutex;
+
+    use crate::Progress;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fragmented_messages_are_observed() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            // Messages sent in a single frame are not observed.
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::binary(
+                    fastwebsockets::Payload::Borrowed(BINARY_MESSAGES[0]),
+                ))
+                .await
+                .expect("Failed to send binary message");
+
+            let fragments: [(fastwebsockets::OpCode, bool, &[u8]); 5] = [
+                (fastwebsockets::OpCode::Binary, false, &[1; 8]),
+                (fastwebsockets::OpCode::Continuation, false, &[2; 8]),
+                (fastwebsockets::OpCode::Continuation, true, &[3; 4]),
+                (fastwebsockets::OpCode::Text, false, b"Hello, "),
+                (fastwebsockets::OpCode::Continuation, true, b"World!"),
+            ];
+
+            for (opcode, fin, chunk) in fragments {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(chunk),
+                    ))
+                    .await
+                    .expect("Failed to send fragment");
+            }
+        };
+
+        let observed = Mutex::new(std::vec::Vec::new());
+
+        let client = async {
+            let observer = |progress: Progress| {
+                observed.lock().expect("Poisoned").push(progress);
+            };
+
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_progress_observer(&observer);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload, BINARY_MESSAGES[0]);
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload.len(), 20);
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(payload))) => {
+                    assert_eq!(payload, "Hello, World!");
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+
+        let observed = observed.into_inner().expect("Poisoned");
+
+        assert_eq!(
+            observed,
+            [
+                Progress::new(false, 8, false),
+                Progress::new(false, 16, false),
+                Progress::new(false, 20, true),
+                Progress::new(true, 7, false),
+                Progress::new(true, 13, true),
+            ]
+        );
+    }
+}
+
+mod pings {
+    use crate::error::{Error, QueuePingError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn peer_unresponsive() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_max_unanswered_pings(2);
+
+        for _ in 0..2 {
+            websocketz
+                .send(Message::Ping(b"ping"))
+                .await
+                .expect("Failed to send ping message");
+        }
+
+        assert_eq!(websocketz.unanswered_pings(), 2);
+
+        match websocketz.send(Message::Ping(b"ping")).await {
+            Ok(_) => panic!("Expected PeerUnresponsive error, but got Ok"),
+            Err(error) => {
+                assert!(matches!(error, Error::PeerUnresponsive));
+            }
+        }
+
+        // Other messages are not affected.
+        websocketz
+            .send(Message::Text("text"))
+            .await
+            .expect("Failed to send text message");
+    }
+
+    #[tokio::test]
+    async fn pong_resets_unanswered_pings() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_max_unanswered_pings(1);
+
+            for _ in 0..3 {
+                websocketz
+                    .send(Message::Ping(b"ping"))
+                    .await
+                    .expect("Failed to send ping message");
+
+                assert_eq!(websocketz.unanswered_pings(), 1);
+
+                match next!(websocketz) {
+                    Some(Ok(Message::Pong(payload))) => {
+                        assert_eq!(payload, b"ping");
+                    }
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+
+                assert_eq!(websocketz.unanswered_pings(), 0);
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            while next!(websocketz).is_some() {}
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn queued_pings_are_sent_by_next() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            // Answers the pings automatically.
+            while next!(websocketz).is_some() {}
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for payload in [b"1", b"2"] {
+                websocketz
+                    .queue_ping(payload)
+                    .expect("Failed to queue ping");
+            }
+
+            assert_eq!(websocketz.queued_pings(), 2);
+
+            for expected in [b"1", b"2"] {
+                match next!(websocketz) {
+                    Some(Ok(Message::Pong(payload))) => assert_eq!(payload, expected),
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+
+                assert_eq!(websocketz.queued_pings(), 0);
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn queue_ping_errors() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        assert_eq!(
+            websocketz.queue_ping(&[0; 9]),
+            Err(QueuePingError::PayloadTooLong)
+        );
+
+        for _ in 0..4 {
+            websocketz
+                .queue_ping(&[0; 8])
+                .expect("Failed to queue ping");
+        }
+
+        assert_eq!(websocketz.queue_ping(b""), Err(QueuePingError::Full));
+
+        websocketz
+            .send(Message::Close(None))
+            .await
+            .expect("Failed to send close message");
+
+        assert_eq!(
+            websocketz.queue_ping(b""),
+            Err(QueuePingError::ConnectionClosed)
+        );
+    }
+
+    #[tokio::test]
+    async fn queued_pings_count_as_unanswered() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_max_unanswered_pings(2);
+
+        websocketz
+            .send(Message::Ping(b"ping"))
+            .await
+            .expect("Failed to send ping message");
+
+        websocketz
+            .queue_ping(b"ping")
+            .expect("Failed to queue ping");
+
+        assert_eq!(
+            websocketz.queue_ping(b"ping"),
+            Err(QueuePingError::PeerUnresponsive)
+        );
+    }
+}
+
+mod limits {
+    use crate::{error::MessageError, options::Limits};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn message_too_large() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            // The client is gone after sending the close frame.
+            fastwebsockets.set_auto_close(false);
+
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::text(
+                    fastwebsockets::Payload::Borrowed(b"small"),
+                ))
+                .await
+                .expect("Failed to send text message");
+
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::binary(
+                    fastwebsockets::Payload::Borrowed(BINARY_MESSAGES[1]),
+                ))
+                .await
+                .expect("Failed to send binary message");
+
+            match fastwebsockets.read_frame().await {
+                Ok(frame) => match frame.opcode {
+                    fastwebsockets::OpCode::Close => {
+                        let payload: &[u8] = frame.payload.as_ref();
+                        let code = u16::from_be_bytes([payload[0], payload[1]]);
+
+                        assert_eq!(code, 1009);
+                    }
+                    _ => panic!("Unexpected frame opcode"),
+                },
+                _ => panic!("Unexpected frame"),
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_limits(Limits::new().with_max_text(8).with_max_binary(16));
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(payload))) => {
+                    assert_eq!(payload, "small");
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::MessageTooLarge));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn fragmented_message_too_large() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            for (index, chunk) in BINARY_MESSAGES[1].chunks(8).enumerate() {
+                let opcode = match index {
+                    0 => fastwebsockets::OpCode::Binary,
+                    _ => fastwebsockets::OpCode::Continuation,
+                };
+
+                let fin = (index + 1) * 8 >= BINARY_MESSAGES[1].len();
+
+                if fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(chunk),
+                    ))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_close(false)
+            .with_limits(Limits::new().with_max_binary(16));
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::MessageTooLarge));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn frame_too_large_closes() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            // The client is gone after sending the close frame.
+            fastwebsockets.set_auto_close(false);
+
+            for (fin, opcode, payload) in [
+                (false, fastwebsockets::OpCode::Binary, &[0; 4][..]),
+                (true, fastwebsockets::OpCode::Continuation, &[0; 8][..]),
+            ] {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(payload),
+                    ))
+                    .await
+                    .expect("Failed to send fragment");
+            }
+
+            match fastwebsockets.read_frame().await {
+                Ok(frame) => match frame.opcode {
+                    fastwebsockets::OpCode::Close => {
+                        let payload: &[u8] = frame.payload.as_ref();
+                        let code = u16::from_be_bytes([payload[0], payload[1]]);
+
+                        assert_eq!(code, 1009);
+                    }
+                    _ => panic!("Unexpected frame opcode"),
+                },
+                _ => panic!("Unexpected frame"),
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            // The message fits, its second frame does not.
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_max_message_size(16)
+            .with_max_frame_size(4);
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::MessageTooLarge));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn frame_too_large_is_rejected_before_buffering() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = tokio::io::duplex(16);
+
+        let server = async move {
+            // Only the header of a 64 KiB binary frame, its payload never fits in the read buffer.
+            server
+                .write_all(&[0x82, 127, 0, 0, 0, 0, 0, 1, 0, 0])
+                .await
+                .expect("Failed to send header");
+
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            fastwebsockets.set_auto_close(false);
+
+            match fastwebsockets.read_frame().await {
+                Ok(frame) => match frame.opcode {
+                    fastwebsockets::OpCode::Close => {
+                        let payload: &[u8] = frame.payload.as_ref();
+                        let code = u16::from_be_bytes([payload[0], payload[1]]);
+
+                        assert_eq!(code, 1009);
+                    }
+                    _ => panic!("Unexpected frame opcode"),
+                },
+                _ => panic!("Unexpected frame"),
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_max_frame_size(SIZE);
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::MessageTooLarge));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn fragments_buffer_overflow_closes() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            // The client is gone after sending the close frame.
+            fastwebsockets.set_auto_close(false);
+
+            for (fin, opcode) in [
+                (false, fastwebsockets::OpCode::Binary),
+                (true, fastwebsockets::OpCode::Continuation),
+            ] {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(&[0; 8]),
+                    ))
+                    .await
+                    .expect("Failed to send fragment");
+            }
+
+            match fastwebsockets.read_frame().await {
+                Ok(frame) => match frame.opcode {
+                    fastwebsockets::OpCode::Close => {
+                        let payload: &[u8] = frame.payload.as_ref();
+                        let code = u16::from_be_bytes([payload[0], payload[1]]);
+
+                        assert_eq!(code, 1009);
+                    }
+                    _ => panic!("Unexpected frame opcode"),
+                },
+                _ => panic!("Unexpected frame"),
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; 12];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_close_on_fragments_overflow(true);
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::FragmentsBufferTooSmall));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn too_many_empty_fragments() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            // The client is gone after sending the close frame.
+            fastwebsockets.set_auto_close(false);
+
+            let frames = [
+                // An empty message.
+                (true, fastwebsockets::OpCode::Text),
+                // An empty fragmented message with 2 empty non-final fragments.
+                (false, fastwebsockets::OpCode::Text),
+                (false, fastwebsockets::OpCode::Continuation),
+                (true, fastwebsockets::OpCode::Continuation),
+                // An endless stream of empty non-final fragments.
+                (false, fastwebsockets::OpCode::Binary),
+                (false, fastwebsockets::OpCode::Continuation),
+                (false, fastwebsockets::OpCode::Continuation),
+            ];
+
+            for (fin, opcode) in frames {
+                fastwebsockets
+                    .write_frame(fastwebsockets::Frame::new(
+                        fin,
+                        opcode,
+                        None,
+                        fastwebsockets::Payload::Borrowed(&[]),
+                    ))
+                    .await
+                    .expect("Failed to send frame");
+            }
+
+            match fastwebsockets.read_frame().await {
+                Ok(frame) => match frame.opcode {
+                    fastwebsockets::OpCode::Close => {
+                        let payload: &[u8] = frame.payload.as_ref();
+                        let code = u16::from_be_bytes([payload[0], payload[1]]);
+
+                        assert_eq!(code, 1008);
+                    }
+                    _ => panic!("Unexpected frame opcode"),
+                },
+                _ => panic!("Unexpected frame"),
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_limits(Limits::new().with_max_empty_fragments(2));
+
+            for _ in 0..2 {
+                match next!(websocketz) {
+                    Some(Ok(Message::Text(payload))) => {
+                        assert_eq!(payload, "");
+                    }
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+
+            match next!(websocketz) {
+                Some(Err(error)) => {
+                    assert!(matches!(error, MessageError::TooManyEmptyFragments));
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod protocol {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::error::{MessageError, ProtocolError};
+
+    use super::*;
+
+    macro_rules! quick_protocol_error {
+        ($frame:ident, $error:ident) => {
+            quick_protocol_error!($frame, $error, 1);
+        };
+        ($frame:ident, $error:ident, $number:literal) => {
+            let (client, mut server) = tokio::io::duplex(16);
+
+            let client = async move {
+                let read_buf = &mut [0u8; SIZE];
+                let write_buf = &mut [0u8; SIZE];
+                let fragments_buf = &mut [0u8; SIZE];
+
+                let mut websocketz = WebSocket::client(
+                    FromTokio::new(client),
+                    StdRng::from_os_rng(),
+                    read_buf,
+                    write_buf,
+                    fragments_buf,
+                );
+
+                match next!(websocketz) {
+                    Some(Err(error)) => {
+                        std::println!("Received error: {error:?}");
+                        assert!(matches!(
+                            error,
+                            MessageError::Protocol {
+                                error: ProtocolError::$error,
+                                frame: $number
+                            }
+                        ));
+                    }
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            };
+
+            let server = async move {
+                server.write_all($frame).await.unwrap();
+
+                server
+            };
+
+            tokio::join!(client, server);
+        };
+    }
+
+    #[tokio::test]
+    async fn invalid_close_frame() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN=1, RSV1-3=0, opcode=0x8 (Close)
+            0x01, // MASK=0 (unmasked), payload length = 1
+            0x37, // Single byte of payload (invalid)
+        ];
+
+        quick_protocol_error!(FRAME, InvalidCloseFrame);
+    }
+
+    #[tokio::test]
+    async fn invalid_close_code() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN + opcode=0x8 (Close)
+            0x02, // Payload length = 2 (only status code, no reason)
+            0x03, 0xED, // Status code: 1005 (not allowed)
+        ];
+
+        quick_protocol_error!(FRAME, InvalidCloseCode);
+    }
+
+    #[tokio::test]
+    async fn reserved_close_code() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN + opcode=0x8 (Close)
+            0x02, // Payload length = 2 (only status code, no reason)
+            0x03, 0xEC, // Status code: 1004 (reserved)
+        ];
+
+        quick_protocol_error!(FRAME, InvalidCloseCode);
+    }
+
+    #[tokio::test]
+    async fn zero_close_code() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN + opcode=0x8 (Close)
+            0x02, // Payload length = 2 (only status code, no reason)
+            0x00, 0x00, // Status code: 0 (out of range)
+        ];
+
+        quick_protocol_error!(FRAME, InvalidCloseCode);
+    }
+
+    #[tokio::test]
+    async fn out_of_range_close_code() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN + opcode=0x8 (Close)
+            0x02, // Payload length = 2 (only status code, no reason)
+            0x13, 0x88, // Status code: 5000 (out of range)
+        ];
+
+        quick_protocol_error!(FRAME, InvalidCloseCode);
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8_close() {
+        const FRAME: &[u8] = &[
+            0x88, // FIN + opcode=0x8 (Close)
+            0x03, // Payload length = 3 (2 bytes code + 1 byte invalid UTF-8)
+            0x03, 0xE8, // Status code: 1000 (normal closure)
+            0xFF, // Invalid UTF-8 byte
+        ];
+
+        quick_protocol_error!(FRAME, InvalidUTF8);
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8_text() {
+        const FRAME: &[u8] = &[
+            0x81, // FIN + opcode 0x1 (text)
+            0x01, // payload length = 1
+            0xFF, // invalid UTF-8 byte
+        ];
+
+        quick_protocol_error!(FRAME, InvalidUTF8);
+    }
+
+    #[tokio::test]
+    async fn invalid_fragment() {
+        const FRAMES: &[u8] = &[
+            // Start a fragmented text frame
+            0x01, // FIN = 0, opcode = 0x1 (Text, not final)
+            0x01, // Payload length = 1
+            0x41, // 'A'
+            // Try to send a new Binary message while the previous fragment isn't finished
+            0x82, // FIN = 1, opcode = 0x2 (Binary, complete)
+            0x01, // Payload length = 1
+            0x42, // 'B'
+        ];
+
+        quick_protocol_error!(FRAMES, InvalidFragment, 2);
+    }
+
+    #[tokio::test]
+    async fn invalid_fragment_same_opcode() {
+        const FRAMES: &[u8] = &[
+            // Start a fragmented text frame
+            0x01, // FIN = 0, opcode = 0x1 (Text, not final)
+            0x01, // Payload length = 1
+            0x41, // 'A'
+            // Start a second fragmented Text message while the previous one isn't finished
+            0x01, // FIN = 0, opcode = 0x1 (Text, not final)
+            0x01, // Payload length = 1
+            0x42, // 'B'
+        ];
+
+        quick_protocol_error!(FRAMES, InvalidFragment, 2);
+    }
+
+    #[tokio::test]
+    async fn fragment_opcode_mismatch() {
+        const FRAMES: &[u8] = &[
+            // Start a fragmented text frame
+            0x01, // FIN = 0, opcode = 0x1 (Text, not final)
+            0x01, // Payload length = 1
+            0x41, // 'A'
+            // Continue the Text message with a Binary opcode instead of a continuation
+            0x02, // FIN = 0, opcode = 0x2 (Binary, not final)
+            0x01, // Payload length = 1
+            0x42, // 'B'
+        ];
+
+        quick_protocol_error!(FRAMES, FragmentOpcodeMismatch, 2);
     }
 
     #[tokio::test]
@@ -1252,6 +4925,3972 @@ mod protocol {
             0x41, // ASCII 'A'
         ];
 
-        quick_protocol_error!(FRAME, InvalidContinuationFrame);
+        quick_protocol_error!(FRAME, InvalidContinuationFrame);
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod pool {
+    use tokio::io::DuplexStream;
+
+    use crate::{
+        error::PoolError,
+        options::ConnectOptions,
+        pool::{Buffers, Connector, Pool},
+    };
+
+    use super::*;
+
+    async fn echo(server: DuplexStream) {
+        let io = hyper_util::rt::TokioIo::new(server);
+
+        hyper::server::conn::http1::Builder::new()
+            .serve_connection(
+                io,
+                hyper::service::service_fn(|mut req| async move {
+                    let (response, fut) = fastwebsockets::upgrade::upgrade(&mut req).unwrap();
+
+                    tokio::spawn(async move {
+                        let mut ws = fut.await.unwrap();
+
+                        while let Ok(frame) = ws.read_frame().await {
+                            match frame.opcode {
+                                fastwebsockets::OpCode::Text | fastwebsockets::OpCode::Binary => {
+                                    let frame = fastwebsockets::Frame::new(
+                                        true,
+                                        frame.opcode,
+                                        None,
+                                        frame.payload,
+                                    );
+
+                                    if ws.write_frame(frame).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                fastwebsockets::OpCode::Close => break,
+                                _ => {}
+                            }
+                        }
+                    });
+
+                    Ok::<_, fastwebsockets::WebSocketError>(response)
+                }),
+            )
+            .with_upgrades()
+            .await
+            .unwrap();
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Upstream unavailable")]
+    struct Unavailable;
+
+    #[derive(Default)]
+    struct Upstream {
+        connects: usize,
+        fail: bool,
+    }
+
+    impl Connector<FromTokio<DuplexStream>, StdRng> for Upstream {
+        type Error = Unavailable;
+
+        async fn connect(
+            &mut self,
+            _index: usize,
+        ) -> Result<(FromTokio<DuplexStream>, StdRng), Self::Error> {
+            if self.fail {
+                return Err(Unavailable);
+            }
+
+            self.connects += 1;
+
+            let (client, server) = tokio::io::duplex(16);
+
+            tokio::spawn(echo(server));
+
+            Ok((FromTokio::new(client), StdRng::from_os_rng()))
+        }
+    }
+
+    #[tokio::test]
+    async fn connects_lazily_and_reconnects() {
+        // Handshake requires larger buffers than SIZE
+        let mut buffers = [[[0u8; SIZE * 2]; 3]; 2];
+        let buffers = buffers
+            .iter_mut()
+            .map(|[read, write, fragments]| Buffers::new(read, write, fragments));
+
+        let mut pool =
+            Pool::<_, _, _, 16>::new(ConnectOptions::default(), Upstream::default(), buffers);
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.is_connected(0));
+        assert!(!pool.is_connected(1));
+
+        for index in 0..2 {
+            let websocketz = pool.get(index).await.expect("Failed to connect");
+
+            websocketz
+                .send(Message::Text("Hello"))
+                .await
+                .expect("Failed to send text message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        }
+
+        assert_eq!(pool.connector().connects, 2);
+
+        // Already connected slots are reused.
+        pool.get(0).await.expect("Failed to get connection");
+
+        assert_eq!(pool.connector().connects, 2);
+        assert_eq!(pool.ping(b"ping").await, 2);
+
+        pool.invalidate(0);
+
+        assert!(!pool.is_connected(0));
+        assert!(pool.is_connected(1));
+
+        let websocketz = pool.get(0).await.expect("Failed to reconnect");
+
+        websocketz
+            .send(Message::Binary(b"Hello"))
+            .await
+            .expect("Failed to send binary message");
+
+        match next!(websocketz) {
+            Some(Ok(Message::Binary(payload))) => assert_eq!(payload, b"Hello"),
+            message => panic!("Unexpected message: {message:?}"),
+        }
+
+        assert_eq!(pool.connector().connects, 3);
+    }
+
+    #[tokio::test]
+    async fn failed_connect_keeps_slot() {
+        let mut buffers = [[0u8; SIZE * 2]; 3];
+        let [read, write, fragments] = &mut buffers;
+
+        let mut pool = Pool::<_, _, _, 16>::new(
+            ConnectOptions::default(),
+            Upstream {
+                connects: 0,
+                fail: true,
+            },
+            [Buffers::new(read, write, fragments)],
+        );
+
+        assert!(matches!(
+            pool.get(0).await,
+            Err(PoolError::Connect(Unavailable))
+        ));
+        assert!(matches!(pool.get(1).await, Err(PoolError::InvalidIndex)));
+
+        pool.connector_mut().fail = false;
+
+        pool.get(0).await.expect("Failed to connect");
+
+        assert!(pool.is_connected(0));
+    }
+}
+
+mod subprotocols {
+    use core::convert::Infallible;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{
+        error::{Error, HandshakeError},
+        http::{Header, HeaderValues, Request, Response},
+        options::{AcceptOptions, ConnectOptions, ProtocolSelector},
+    };
+
+    use super::*;
+
+    async fn negotiate(
+        offered: &[&str],
+        supported: &[&str],
+        headers: &[Header<'_>],
+    ) -> (Result<Option<usize>, Error<std::io::Error>>, Option<usize>) {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            WebSocket::connect::<16>(
+                ConnectOptions::default().with_protocols(offered),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .map(|websocketz| websocketz.protocol())
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            WebSocket::accept::<16>(
+                AcceptOptions::default()
+                    .with_protocols(supported)
+                    .with_headers(headers),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to accept")
+            .protocol()
+        };
+
+        tokio::join!(client, server)
+    }
+
+    #[tokio::test]
+    async fn server_preference() {
+        let (client, server) = negotiate(&["chat", "superchat"], &["superchat", "chat"], &[]).await;
+
+        assert_eq!(client.expect("Failed to connect"), Some(1));
+        assert_eq!(server, Some(0));
+    }
+
+    #[tokio::test]
+    async fn handshake_result() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let (_, (), result) = WebSocket::connect_with(
+                ConnectOptions::default().with_protocols(&["mqtt", "graphql-ws"]),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                |_: &Response<'_, 16>| Ok::<_, Infallible>(()),
+            )
+            .await
+            .expect("Failed to connect");
+
+            result
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let (_, (), result) = WebSocket::accept_with(
+                AcceptOptions::default().with_protocols(&["graphql-ws"]),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+                |_: &Request<'_, 16>| Ok::<_, Infallible>(()),
+            )
+            .await
+            .expect("Failed to accept");
+
+            result
+        };
+
+        let (client, server) = tokio::join!(client, server);
+
+        assert_eq!(client.protocol(), Some("graphql-ws"));
+        assert_eq!(client.protocol_index(), Some(1));
+        assert_eq!(server.protocol(), Some("graphql-ws"));
+        assert_eq!(server.protocol_index(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn no_common_protocol() {
+        let (client, server) = negotiate(&["chat"], &["superchat"], &[]).await;
+
+        assert_eq!(client.expect("Failed to connect"), None);
+        assert_eq!(server, None);
+    }
+
+    #[tokio::test]
+    async fn not_offered() {
+        let headers = &[Header {
+            name: "Sec-WebSocket-Protocol",
+            value: b"superchat",
+        }];
+
+        let (client, _) = negotiate(&["chat"], &[], headers).await;
+
+        assert!(matches!(
+            client,
+            Err(Error::Handshake(HandshakeError::InvalidProtocol))
+        ));
+    }
+
+    #[tokio::test]
+    async fn multiple_selected() {
+        let headers = &[Header {
+            name: "Sec-WebSocket-Protocol",
+            value: b"superchat",
+        }];
+
+        let (client, _) = negotiate(&["chat", "superchat"], &["chat"], headers).await;
+
+        assert!(matches!(
+            client,
+            Err(Error::Handshake(HandshakeError::InvalidProtocol))
+        ));
+    }
+
+    #[tokio::test]
+    async fn offers_across_lines() {
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Protocol: chat, superchat\r\n\
+            Sec-WebSocket-Protocol: v2.json\r\n\
+            \r\n";
+
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 2];
+        let write_buf = &mut [0u8; SIZE * 2];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default().with_protocols(&["v2.json", "chat"]),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        assert_eq!(websocketz.protocol(), Some(0));
+
+        drop(websocketz);
+
+        let mut response = std::string::String::new();
+        client
+            .read_to_string(&mut response)
+            .await
+            .expect("Failed to read response");
+
+        assert!(response.contains("sec-websocket-protocol: v2.json\r\n"));
+    }
+
+    async fn accept_selected(
+        path: &str,
+        selector: ProtocolSelector<'_>,
+    ) -> (Option<usize>, std::string::String) {
+        let request = std::format!(
+            "GET {path} HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Authorization: Bearer v2\r\n\
+            Sec-WebSocket-Protocol: v1.json, v2.json\r\n\
+            \r\n"
+        );
+
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 2];
+        let write_buf = &mut [0u8; SIZE * 2];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(request.as_bytes())
+            .await
+            .expect("Failed to write request");
+
+        let websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default()
+                .with_protocols(&["v1.json", "v2.json"])
+                .with_protocol_selector(selector),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        let protocol = websocketz.protocol();
+
+        drop(websocketz);
+
+        let mut response = std::string::String::new();
+        client
+            .read_to_string(&mut response)
+            .await
+            .expect("Failed to read response");
+
+        (protocol, response)
+    }
+
+    #[tokio::test]
+    async fn selector_uses_request() {
+        let selector = |path: &str, headers: &[Header<'_>], mut offered: HeaderValues<'_, '_>| {
+            let authorized = headers
+                .iter()
+                .any(|header| header.name == "Authorization" && header.value == b"Bearer v2");
+
+            match path {
+                "/latest" if authorized && offered.any(|offered| offered == "v2.json") => {
+                    Some("v2.json")
+                }
+                _ => Some("v1.json"),
+            }
+        };
+
+        let (protocol, response) = accept_selected("/latest", &selector).await;
+
+        assert_eq!(protocol, Some(1));
+        assert!(response.contains("sec-websocket-protocol: v2.json\r\n"));
+
+        let (protocol, response) = accept_selected("/", &selector).await;
+
+        assert_eq!(protocol, Some(0));
+        assert!(response.contains("sec-websocket-protocol: v1.json\r\n"));
+    }
+
+    #[tokio::test]
+    async fn selector_not_offered() {
+        let (protocol, response) = accept_selected("/", &|_, _, _| Some("v3.json")).await;
+
+        assert_eq!(protocol, None);
+        assert!(!response.contains("sec-websocket-protocol"));
+
+        let (protocol, response) = accept_selected("/", &|_, _, _| None).await;
+
+        assert_eq!(protocol, None);
+        assert!(!response.contains("sec-websocket-protocol"));
+    }
+}
+
+mod extensions {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{extensions::SupportedExtension, options::AcceptOptions};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn accept_offered() {
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits, x-other\r\n\
+            Sec-WebSocket-Extensions: x-vendor; version=1\r\n\
+            \r\n";
+
+        const SUPPORTED: &[SupportedExtension<'static>] = &[
+            SupportedExtension::new("x-vendor").with_response("x-vendor; version=1"),
+            SupportedExtension::new("x-unused"),
+            SupportedExtension::new("x-other"),
+        ];
+
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 2];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default().with_extensions(SUPPORTED),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        assert!(websocketz.extensions().indices().eq([0, 2]));
+
+        drop(websocketz);
+
+        let mut response = std::string::String::new();
+        client
+            .read_to_string(&mut response)
+            .await
+            .expect("Failed to read response");
+
+        assert!(response.contains("sec-websocket-extensions: x-vendor; version=1, x-other\r\n"));
+    }
+}
+
+#[cfg(feature = "deflate")]
+mod permessage_deflate {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{
+        deflate::Inflater,
+        error::{Error, HandshakeError, MessageError},
+        extensions::SupportedExtension,
+        options::{AcceptOptions, ConnectOptions},
+        websocket_core::generate_sec_accept,
+    };
+
+    use super::*;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\n\
+        Host: localhost\r\n\
+        Upgrade: websocket\r\n\
+        Connection: upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\
+        \r\n";
+
+    async fn echo(client_deflate: bool, server_deflate: bool) {
+        let (client, server) = tokio::io::duplex(16);
+
+        let text = STR_MESSAGES.concat();
+        let binary = BINARY_MESSAGES.concat();
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 8];
+            let write_buf = &mut [0u8; SIZE * 8];
+            let fragments_buf = &mut [0u8; SIZE * 8];
+            let inflater = &mut Inflater::new();
+            let inflate_buf = &mut [0u8; SIZE * 8];
+
+            let mut websocketz = WebSocket::connect::<16>(
+                ConnectOptions::default().with_permessage_deflate(client_deflate),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to connect")
+            .with_inflater(inflater, inflate_buf);
+
+            assert_eq!(
+                websocketz.permessage_deflate(),
+                client_deflate && server_deflate
+            );
+
+            let messages = [
+                Message::Text(&text),
+                Message::Binary(&binary),
+                Message::Text("Hi"),
+            ];
+
+            for message in messages {
+                websocketz.send(message).await.expect("Failed to send");
+
+                match (next!(websocketz), message) {
+                    (Some(Ok(Message::Text(echoed))), Message::Text(sent)) => {
+                        assert_eq!(echoed, sent)
+                    }
+                    (Some(Ok(Message::Binary(echoed))), Message::Binary(sent)) => {
+                        assert_eq!(echoed, sent)
+                    }
+                    (message, _) => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 8];
+            let write_buf = &mut [0u8; SIZE * 8];
+            let fragments_buf = &mut [0u8; SIZE * 8];
+            let inflater = &mut Inflater::new();
+            let inflate_buf = &mut [0u8; SIZE * 8];
+
+            let mut websocketz = WebSocket::accept::<16>(
+                AcceptOptions::default().with_permessage_deflate(server_deflate),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to accept")
+            .with_inflater(inflater, inflate_buf);
+
+            assert_eq!(
+                websocketz.permessage_deflate(),
+                client_deflate && server_deflate
+            );
+
+            while let Some(Ok(message)) = next!(websocketz) {
+                match message {
+                    Message::Text(text) => {
+                        let text = std::string::String::from(text);
+
+                        websocketz.send(Message::Text(&text)).await
+                    }
+                    Message::Binary(binary) => {
+                        let binary = binary.to_vec();
+
+                        websocketz.send(Message::Binary(&binary)).await
+                    }
+                    _ => continue,
+                }
+                .expect("Failed to send");
+            }
+        };
+
+        tokio::join!(client, server);
+    }
+
+    #[tokio::test]
+    async fn negotiated() {
+        echo(true, true).await;
+    }
+
+    #[tokio::test]
+    async fn not_offered() {
+        echo(false, true).await;
+    }
+
+    #[tokio::test]
+    async fn not_accepted() {
+        echo(true, false).await;
+    }
+
+    #[tokio::test]
+    async fn server_decompresses() {
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 4];
+        let fragments_buf = &mut [0u8; SIZE];
+        let inflater = &mut Inflater::new();
+        let inflate_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let mut websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default().with_permessage_deflate(true),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept")
+        .with_inflater(inflater, inflate_buf);
+
+        let response = &mut [0u8; SIZE * 2];
+        let len = client
+            .read(response)
+            .await
+            .expect("Failed to read response");
+        let response = core::str::from_utf8(&response[..len]).expect("Valid response");
+
+        assert!(response.contains(
+            "sec-websocket-extensions: permessage-deflate; client_no_context_takeover; server_no_context_takeover\r\n"
+        ));
+
+        // "Hello" compressed in a single frame and in two fragments, RFC 7692, Section 7.2.3.1,
+        // masked with a zero key.
+        #[rustfmt::skip]
+        const FRAMES: &[u8] = &[
+            0xc1, 0x87, 0x00, 0x00, 0x00, 0x00, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00,
+            0x41, 0x83, 0x00, 0x00, 0x00, 0x00, 0xf2, 0x48, 0xcd,
+            0x80, 0x84, 0x00, 0x00, 0x00, 0x00, 0xc9, 0xc9, 0x07, 0x00,
+        ];
+
+        client
+            .write_all(FRAMES)
+            .await
+            .expect("Failed to write frames");
+
+        for _ in 0..2 {
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        }
+
+        // Corrupted payload.
+        client
+            .write_all(&[0xc2, 0x83, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff])
+            .await
+            .expect("Failed to write frame");
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::DecompressionFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn peek_compressed() {
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 4];
+        let fragments_buf = &mut [0u8; SIZE];
+        let inflater = &mut Inflater::new();
+        let inflate_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let mut websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default().with_permessage_deflate(true),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept")
+        .with_inflater(inflater, inflate_buf);
+
+        // "Hello" compressed in a single frame, masked with a zero key.
+        client
+            .write_all(&[
+                0xc1, 0x87, 0x00, 0x00, 0x00, 0x00, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00,
+            ])
+            .await
+            .expect("Failed to write frame");
+
+        assert!(matches!(
+            websocketz.peek_message(|_| ()).await,
+            Some(Err(MessageError::DecompressionFailed))
+        ));
+
+        // The compressed payload is never passed to `f`, and the message is still read decompressed.
+        match next!(websocketz) {
+            Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+            message => panic!("Unexpected message: {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn server_without_inflater() {
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 4];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let mut websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default().with_permessage_deflate(true),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        client
+            .write_all(&[
+                0xc1, 0x87, 0x00, 0x00, 0x00, 0x00, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00,
+            ])
+            .await
+            .expect("Failed to write frame");
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::DecompressionFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn server_does_not_accept_deflate_twice() {
+        const SUPPORTED: &[SupportedExtension<'static>] = &[
+            SupportedExtension::new("permessage-deflate"),
+            SupportedExtension::new("x-other"),
+        ];
+
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 4];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(
+                b"GET / HTTP/1.1\r\n\
+                Host: localhost\r\n\
+                Upgrade: websocket\r\n\
+                Connection: upgrade\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                Sec-WebSocket-Version: 13\r\n\
+                Sec-WebSocket-Extensions: permessage-deflate, x-other\r\n\
+                \r\n",
+            )
+            .await
+            .expect("Failed to write request");
+
+        let websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default()
+                .with_permessage_deflate(true)
+                .with_extensions(SUPPORTED),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        assert!(websocketz.permessage_deflate());
+
+        let response = &mut [0u8; SIZE * 2];
+        let len = client
+            .read(response)
+            .await
+            .expect("Failed to read response");
+        let response = core::str::from_utf8(&response[..len]).expect("Valid response");
+
+        assert_eq!(response.matches("permessage-deflate").count(), 1);
+        assert!(response.contains("x-other"));
+    }
+
+    async fn connect_raw(response_extension: &'static str) -> Result<(), Error<std::io::Error>> {
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let server = async move {
+            let request = &mut [0u8; SIZE * 4];
+            let len = server.read(request).await.expect("Failed to read request");
+            let request = core::str::from_utf8(&request[..len]).expect("Valid request");
+
+            assert!(request.contains(
+                "sec-websocket-extensions: permessage-deflate; client_no_context_takeover; server_no_context_takeover\r\n"
+            ));
+
+            let sec_key = request
+                .lines()
+                .find_map(|line| line.strip_prefix("sec-websocket-key: "))
+                .expect("Missing key");
+            let sec_accept = generate_sec_accept(sec_key.as_bytes());
+
+            let response = std::format!(
+                "HTTP/1.1 101 Switching Protocols\r\n\
+                Upgrade: websocket\r\n\
+                Connection: upgrade\r\n\
+                Sec-WebSocket-Accept: {}\r\n\
+                Sec-WebSocket-Extensions: {response_extension}\r\n\
+                \r\n",
+                core::str::from_utf8(&sec_accept).expect("Valid accept")
+            );
+
+            server
+                .write_all(response.as_bytes())
+                .await
+                .expect("Failed to write response");
+
+            // "Hello" compressed in a final block, RFC 7692, Section 7.2.3.4.
+            let _ = server
+                .write_all(&[0xc1, 0x08, 0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x00])
+                .await;
+
+            // The compressed message sent by the client.
+            let frame = &mut [0u8; SIZE * 4];
+            let len = server.read(frame).await.unwrap_or(0);
+
+            if len > 0 {
+                let frame = &mut frame[..len];
+
+                // FIN, RSV1, Text and masked.
+                assert_eq!(frame[0], 0xc1);
+                assert_eq!(frame[1] & 0x80, 0x80);
+
+                let len = usize::from(frame[1] & 0x7f);
+                let (mask, payload) = frame[2..].split_at_mut(4);
+
+                crate::mask::unmask(&mut payload[..len], [mask[0], mask[1], mask[2], mask[3]]);
+
+                let decompressed = &mut [0u8; SIZE * 4];
+                let decompressed_len = Inflater::new()
+                    .decompress(&payload[..len], decompressed)
+                    .expect("Valid payload");
+
+                assert_eq!(
+                    &decompressed[..decompressed_len],
+                    "Hello ".repeat(20).as_bytes()
+                );
+                assert!(len < decompressed_len);
+            }
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 4];
+            let write_buf = &mut [0u8; SIZE * 4];
+            let fragments_buf = &mut [0u8; SIZE];
+            let inflater = &mut Inflater::new();
+            let inflate_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::connect::<16>(
+                ConnectOptions::default().with_permessage_deflate(true),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await?
+            .with_inflater(inflater, inflate_buf);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            let text = "Hello ".repeat(20);
+
+            websocketz.send(Message::Text(&text)).await
+        };
+
+        let (_, client) = tokio::join!(server, client);
+
+        client
+    }
+
+    #[tokio::test]
+    async fn client_compresses() {
+        connect_raw("permessage-deflate; server_no_context_takeover")
+            .await
+            .expect("Failed to connect");
+    }
+
+    #[tokio::test]
+    async fn invalid_response() {
+        for response in [
+            "permessage-deflate",
+            "permessage-deflate; server_no_context_takeover; client_max_window_bits=10",
+            "permessage-deflate; server_no_context_takeover, permessage-deflate; server_no_context_takeover",
+        ] {
+            assert!(matches!(
+                connect_raw(response).await,
+                Err(Error::Handshake(HandshakeError::InvalidExtension))
+            ));
+        }
+    }
+}
+
+mod attach_writer {
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    use crate::{
+        CloseFrame,
+        error::{Error, WriteError},
+    };
+
+    use super::*;
+
+    fn split(
+        stream: FromTokio<DuplexStream>,
+    ) -> (
+        FromTokio<ReadHalf<DuplexStream>>,
+        FromTokio<WriteHalf<DuplexStream>>,
+    ) {
+        let (read, write) = tokio::io::split(stream.into_inner());
+
+        (FromTokio::new(read), FromTokio::new(write))
+    }
+
+    #[tokio::test]
+    async fn pong_and_close() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Ping(b"ping"))
+                .await
+                .expect("Failed to send ping message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Pong(payload))) => assert_eq!(payload, b"ping"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            websocketz
+                .send(Message::Close(Some(CloseFrame::new(
+                    CloseCode::Normal,
+                    "Bye",
+                ))))
+                .await
+                .expect("Failed to send close message");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => {
+                    assert_eq!(frame.code(), CloseCode::Normal);
+                    assert_eq!(frame.reason(), "Bye");
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let (mut read, mut write) = websocketz.split_with(split);
+
+            let mut attached = read.attach_writer(&mut write);
+
+            assert!(attached.with_next(|_| ()).await.is_none());
+
+            assert!(matches!(
+                write.send(Message::Text("Closed")).await,
+                Err(Error::Write(WriteError::ConnectionClosed))
+            ));
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn close_sent_by_writer() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            // Answers the server's close frame automatically.
+            assert!(next!(websocketz).is_none());
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let (mut read, mut write) = websocketz.split_with(split);
+
+            write
+                .send_close_and_flush(Some(CloseFrame::no_reason(CloseCode::Away)))
+                .await
+                .expect("Failed to send close message");
+
+            // The echoed close frame is returned instead of being answered again.
+            let code = read
+                .attach_writer(&mut write)
+                .with_next(|message| match message {
+                    Message::Close(Some(frame)) => frame.code(),
+                    message => panic!("Unexpected message: {message:?}"),
+                })
+                .await;
+
+            assert!(matches!(code, Some(Ok(CloseCode::Away))));
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod close_signal {
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    use crate::{
+        CloseSignal, PreEncodedMessage,
+        error::{Error, WriteError},
+    };
+
+    use super::*;
+
+    fn split(
+        stream: FromTokio<DuplexStream>,
+    ) -> (
+        FromTokio<ReadHalf<DuplexStream>>,
+        FromTokio<WriteHalf<DuplexStream>>,
+    ) {
+        let (read, write) = tokio::io::split(stream.into_inner());
+
+        (FromTokio::new(read), FromTokio::new(write))
+    }
+
+    #[tokio::test]
+    async fn requested_by_read_half() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_close(false);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => {
+                    assert_eq!(frame.code(), CloseCode::Away);
+                    assert_eq!(frame.reason(), "");
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let signal = CloseSignal::new();
+
+            let websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let (read, write) = websocketz.split_with(split);
+            let (read, mut write) = (
+                read.with_close_signal(&signal),
+                write.with_close_signal(&signal),
+            );
+
+            assert!(read.request_close(CloseCode::Away));
+
+            assert!(matches!(
+                write.send(Message::Text("Closed")).await,
+                Err(Error::Write(WriteError::ConnectionClosed))
+            ));
+
+            // Already sent.
+            write
+                .send_requested_close()
+                .await
+                .expect("Failed to send requested close");
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn sent_before_pre_encoded() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_close(false);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => assert_eq!(frame.code(), CloseCode::Away),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            assert!(next!(websocketz).is_none());
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+            let buf = &mut [0u8; SIZE];
+
+            let signal = CloseSignal::new();
+            let message = PreEncodedMessage::encode(Message::Text("Closed"), None, buf)
+                .expect("Failed to encode");
+
+            let websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let (read, write) = websocketz.split_with(split);
+            let (read, mut write) = (
+                read.with_close_signal(&signal),
+                write.with_close_signal(&signal),
+            );
+
+            assert!(read.request_close(CloseCode::Away));
+
+            assert!(matches!(
+                write.send_pre_encoded(&message).await,
+                Err(Error::Write(WriteError::ConnectionClosed))
+            ));
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn sent_before_automatic_replies() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_close(false);
+
+            websocketz
+                .send(Message::Ping(b"ping"))
+                .await
+                .expect("Failed to send ping");
+
+            // The Close frame is sent instead of the Pong.
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => assert_eq!(frame.code(), CloseCode::Away),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            websocketz
+                .send(Message::Close(None))
+                .await
+                .expect("Failed to send close");
+
+            assert!(next!(websocketz).is_none());
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let signal = CloseSignal::new();
+
+            let websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let (read, write) = websocketz.split_with(split);
+            let (mut read, mut write) = (
+                read.with_close_signal(&signal),
+                write.with_close_signal(&signal),
+            );
+
+            assert!(read.request_close(CloseCode::Away));
+
+            // The Ping is answered with the requested Close frame, the next message is the peer's Close frame.
+            let is_close = read
+                .attach_writer(&mut write)
+                .with_next(|message| message.is_close())
+                .await;
+
+            assert!(matches!(is_close, Some(Ok(true))));
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn without_signal() {
+        let (_client, server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let websocketz = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let (read, _write) = websocketz.split_with(split);
+
+        assert!(!read.request_close(CloseCode::Away));
+    }
+}
+
+mod config {
+    use core::time::Duration;
+
+    use crate::options::{Config, Keepalive};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn with_config() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let server = async move {
+            let mut fastwebsockets =
+                fastwebsockets::WebSocket::after_handshake(server, fastwebsockets::Role::Server);
+
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::text(
+                    fastwebsockets::Payload::Borrowed(b"small"),
+                ))
+                .await
+                .expect("Failed to send text message");
+
+            fastwebsockets
+                .write_frame(fastwebsockets::Frame::new(
+                    true,
+                    fastwebsockets::OpCode::Ping,
+                    None,
+                    fastwebsockets::Payload::Borrowed(b"ping"),
+                ))
+                .await
+                .expect("Failed to send ping message");
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let keepalive = Keepalive::new(Duration::from_secs(30), Duration::from_secs(10));
+
+            let config = Config::new()
+                .with_auto_pong(false)
+                .with_text_validation(false)
+                .with_keepalive(Some(keepalive));
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_config(config);
+
+            assert_eq!(websocketz.keepalive(), Some(keepalive));
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => assert_eq!(payload, b"small"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Ping(payload))) => assert_eq!(payload, b"ping"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod pause_reads {
+    use crate::error::MessageError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pause_and_resume() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for message in STR_MESSAGES {
+                websocketz
+                    .send(Message::Text(message))
+                    .await
+                    .expect("Failed to send message");
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for (index, message) in STR_MESSAGES.iter().enumerate() {
+                if index % 2 == 0 {
+                    websocketz.pause_reads();
+
+                    assert!(websocketz.reads_paused());
+                    assert!(matches!(
+                        next!(websocketz),
+                        Some(Err(MessageError::ReadsPaused))
+                    ));
+
+                    websocketz.resume_reads();
+                }
+
+                match next!(websocketz) {
+                    Some(Ok(Message::Text(text))) => assert_eq!(text, *message),
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod with_inner_async {
+    use embedded_io_async::Write;
+    use futures::FutureExt;
+
+    use crate::error::{Error, WriteError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn raw_frame_between_messages() {
+        // A masked text frame `hi` with a zero masking key.
+        const RAW: &[u8] = &[0x81, 0x82, 0, 0, 0, 0, b'h', b'i'];
+
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Text("before"))
+                .await
+                .expect("Failed to send message");
+
+            let written = websocketz
+                .with_inner_async(async |inner| {
+                    inner.write_all(RAW).await?;
+                    inner.flush().await?;
+
+                    Ok::<_, std::io::Error>(RAW.len())
+                })
+                .await
+                .expect("Stream is between frames")
+                .expect("Failed to write raw frame");
+
+            assert_eq!(written, RAW.len());
+
+            websocketz
+                .send(Message::Text("after"))
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for expected in ["before", "hi", "after"] {
+                match next!(websocketz) {
+                    Some(Ok(Message::Text(text))) => assert_eq!(text, expected),
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn refused_after_partial_frame() {
+        // Nobody reads, the frame does not fit into the duplex buffer.
+        let (client, _server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        // The send is dropped after writing the first 16 bytes of the frame.
+        assert!(
+            websocketz
+                .send(Message::Binary(&[0; 64]))
+                .now_or_never()
+                .is_none()
+        );
+
+        assert!(matches!(
+            websocketz.with_inner_async(async |_| ()).await,
+            Err(Error::Write(WriteError::PartialFrame))
+        ));
+    }
+}
+
+mod close_reasons {
+    use crate::{CloseReason, CloseReasons};
+
+    use super::*;
+
+    const UNAUTHORIZED: CloseReason = CloseReason::new(4001, "unauthorized");
+    const REASONS: CloseReasons = CloseReasons::new(&[UNAUTHORIZED]);
+
+    #[tokio::test]
+    async fn close_with_registered_reason() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_close(false);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Close(Some(frame)))) => {
+                    assert_eq!(REASONS.get(frame.code()), Some(UNAUTHORIZED));
+                    assert_eq!(frame.reason(), UNAUTHORIZED.name());
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Close(Some(UNAUTHORIZED.frame())))
+                .await
+                .expect("Failed to send close message");
+
+            while next!(websocketz).is_some() {}
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod connect_adaptive {
+    use crate::{
+        error::{Error, HttpDecodeError, ReadError},
+        http::Header,
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    /// Together with the 3 handshake headers, the server responds with 9 headers.
+    const HEADERS: &[Header<'static>] = &[
+        Header {
+            name: "x-header-1",
+            value: b"1",
+        },
+        Header {
+            name: "x-header-2",
+            value: b"2",
+        },
+        Header {
+            name: "x-header-3",
+            value: b"3",
+        },
+        Header {
+            name: "x-header-4",
+            value: b"4",
+        },
+        Header {
+            name: "x-header-5",
+            value: b"5",
+        },
+        Header {
+            name: "x-header-6",
+            value: b"6",
+        },
+    ];
+
+    async fn connect<const N1: usize, const N2: usize>() -> Result<(), Error<std::io::Error>> {
+        let (client, server) = tokio::io::duplex(SIZE * 2);
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let Ok(mut websocketz) = WebSocket::accept::<16>(
+                AcceptOptions::default().with_headers(HEADERS),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            else {
+                return;
+            };
+
+            let _ = websocketz.send(Message::Text("hello")).await;
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::connect_adaptive::<N1, N2>(
+                ConnectOptions::default(),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await?;
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            Ok(())
+        };
+
+        tokio::join!(server, client).1
+    }
+
+    fn is_too_many_headers(error: &Error<std::io::Error>) -> bool {
+        matches!(
+            error,
+            Error::Read(ReadError::ReadHttp(framez::ReadError::Decode(
+                HttpDecodeError::Parse(httparse::Error::TooManyHeaders)
+            )))
+        )
+    }
+
+    #[tokio::test]
+    async fn first_attempt() {
+        connect::<16, 32>().await.expect("Failed to connect");
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        connect::<4, 16>().await.expect("Failed to connect");
+    }
+
+    #[tokio::test]
+    async fn retry_too_small() {
+        let error = connect::<4, 8>()
+            .await
+            .expect_err("Expected too many headers");
+
+        assert!(is_too_many_headers(&error));
+    }
+
+    #[tokio::test]
+    async fn no_retry() {
+        let error = connect::<4, 4>()
+            .await
+            .expect_err("Expected too many headers");
+
+        assert!(is_too_many_headers(&error));
+    }
+}
+
+mod min_read_chunk {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Every masked text message of 1 byte is 7 bytes long.
+    const CHUNK: usize = 14;
+
+    #[tokio::test]
+    async fn coalesce_and_flush() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut client = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut server = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_min_read_chunk(CHUNK);
+
+        assert_eq!(server.min_read_chunk(), CHUNK);
+
+        macro_rules! next_text {
+            ($websocketz:expr) => {
+                tokio::time::timeout(
+                    Duration::from_millis(50),
+                    $websocketz.with_next(|message| match message {
+                        Message::Text(text) => std::string::String::from(text),
+                        message => panic!("Unexpected message: {message:?}"),
+                    }),
+                )
+                .await
+                .ok()
+                .map(|message| {
+                    message
+                        .expect("Connection closed")
+                        .expect("Failed to read message")
+                })
+            };
+        }
+
+        client
+            .send(Message::Text("a"))
+            .await
+            .expect("Failed to send message");
+
+        // Waits for more bytes, the read is cancelled by the deadline.
+        assert_eq!(next_text!(server), None);
+
+        // The bytes read before the deadline are not lost.
+        server.set_min_read_chunk(0);
+        assert_eq!(next_text!(server).as_deref(), Some("a"));
+
+        server.set_min_read_chunk(CHUNK);
+
+        client
+            .send(Message::Text("b"))
+            .await
+            .expect("Failed to send message");
+
+        assert_eq!(next_text!(server), None);
+
+        for text in ["c", "d"] {
+            client
+                .send(Message::Text(text))
+                .await
+                .expect("Failed to send message");
+        }
+
+        // Buffered frames are decoded before reading again.
+        assert_eq!(next_text!(server).as_deref(), Some("b"));
+        assert_eq!(next_text!(server).as_deref(), Some("c"));
+        assert_eq!(next_text!(server).as_deref(), Some("d"));
+    }
+
+    #[tokio::test]
+    async fn shift_partial_frame() {
+        let (client, server) = tokio::io::duplex(SIZE * 16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut client = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut server = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_min_read_chunk(SIZE / 2);
+
+        // Everything is written before reading, so that the read buffer fills up
+        // and frames straddle its end, forcing the buffer to be shifted.
+        for _ in 0..2 {
+            for binary in BINARY_MESSAGES {
+                client
+                    .send(Message::Binary(binary))
+                    .await
+                    .expect("Failed to send message");
+            }
+        }
+
+        for _ in 0..2 {
+            for binary in BINARY_MESSAGES {
+                let payload = server
+                    .with_next(|message| match message {
+                        Message::Binary(payload) => std::vec::Vec::from(payload),
+                        message => panic!("Unexpected message: {message:?}"),
+                    })
+                    .await
+                    .expect("Connection closed")
+                    .expect("Failed to read message");
+
+                assert_eq!(payload, *binary);
+            }
+        }
+    }
+}
+
+mod peek_message {
+    use crate::error::MessageError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn peek_then_read() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Text("route-a: hello"))
+                .await
+                .expect("Failed to send message");
+
+            websocketz
+                .send(Message::Binary(&[1, 2, 3]))
+                .await
+                .expect("Failed to send message");
+
+            websocketz
+                .send_fragmented(Message::Text("fragmented"), 4)
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            // Peeking twice sees the same message, masked payloads are restored.
+            for _ in 0..2 {
+                let route = websocketz
+                    .peek_message(|message| match message {
+                        Message::Text(text) => text.starts_with("route-a"),
+                        message => panic!("Unexpected message: {message:?}"),
+                    })
+                    .await
+                    .expect("Connection closed")
+                    .expect("Failed to peek message");
+
+                assert!(route);
+            }
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "route-a: hello"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            let first = websocketz
+                .peek_message(|message| match message {
+                    Message::Binary(payload) => payload[0],
+                    message => panic!("Unexpected message: {message:?}"),
+                })
+                .await
+                .expect("Connection closed")
+                .expect("Failed to peek message");
+
+            assert_eq!(first, 1);
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => assert_eq!(payload, &[1, 2, 3]),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            assert!(matches!(
+                websocketz.peek_message(|_| ()).await,
+                Some(Err(MessageError::FragmentedPeek))
+            ));
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "fragmented"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod connection_info {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{
+        extensions::SupportedExtension,
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn accept() {
+        const REQUEST: &[u8] = b"GET /chat?room=1 HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Origin: https://example.com\r\n\
+            Upgrade: websocket\r\n\
+            Connection: upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Protocol: v1.json, v2.json\r\n\
+            Sec-WebSocket-Extensions: x-vendor\r\n\
+            \r\n";
+
+        const SUPPORTED: &[SupportedExtension<'static>] = &[SupportedExtension::new("x-vendor")];
+
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; SIZE * 4];
+        let write_buf = &mut [0u8; SIZE * 2];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        client
+            .write_all(REQUEST)
+            .await
+            .expect("Failed to write request");
+
+        let websocketz = WebSocket::accept::<16>(
+            AcceptOptions::default()
+                .with_protocols(&["v2.json"])
+                .with_extensions(SUPPORTED),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .await
+        .expect("Failed to accept");
+
+        let info = websocketz.info();
+
+        assert_eq!(info.path(), Some("/chat?room=1"));
+        assert_eq!(info.origin(), Some("https://example.com"));
+        assert_eq!(info.protocol(), Some(0));
+        assert!(info.extensions().contains(0));
+    }
+
+    #[tokio::test]
+    async fn connect() {
+        let (client, server) = tokio::io::duplex(SIZE * 2);
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let websocketz = WebSocket::accept::<16>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to accept");
+
+            assert_eq!(websocketz.info().path(), Some("/ws"));
+            assert_eq!(websocketz.info().origin(), None);
+        };
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE * 2];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let websocketz = WebSocket::connect::<16>(
+                ConnectOptions::new("/ws").expect("Valid path"),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await
+            .expect("Failed to connect");
+
+            assert_eq!(websocketz.info().path(), Some("/ws"));
+            assert_eq!(websocketz.info().origin(), None);
+            assert_eq!(websocketz.info().protocol(), None);
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod encode_message_into {
+    use embedded_io_async::Write;
+
+    use crate::error::FrameEncodeError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn write_external_buffer() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            // The internal write buffer is too small for the message.
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; 4];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let dma = &mut [0u8; 32];
+
+            assert!(matches!(
+                websocketz.encode_message_into(Message::Text("external"), &mut dma[..8]),
+                Err(FrameEncodeError::BufferTooSmall)
+            ));
+
+            let len = websocketz
+                .encode_message_into(Message::Text("external"), dma)
+                .expect("Failed to encode message");
+
+            // Masked client frame: 2 bytes header, 4 bytes mask and the payload.
+            assert_eq!(len, 2 + 4 + 8);
+
+            websocketz
+                .inner_mut()
+                .write_all(&dma[..len])
+                .await
+                .expect("Failed to write frame");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "external"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod next_bounded {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_after_max_iterations() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Ping(b"1"))
+                .await
+                .expect("Failed to send ping");
+            websocketz
+                .send(Message::Ping(b"2"))
+                .await
+                .expect("Failed to send ping");
+            websocketz
+                .send(Message::Text("bounded"))
+                .await
+                .expect("Failed to send text");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .with_auto_pong(true);
+
+            assert!(matches!(next_bounded!(websocketz, 0), Some(Ok(None))));
+
+            let mut calls = 0;
+
+            let text = loop {
+                calls += 1;
+
+                match next_bounded!(websocketz, 1) {
+                    Some(Ok(None)) => continue,
+                    Some(Ok(Some(Message::Text(text)))) => break text,
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            };
+
+            assert_eq!(text, "bounded");
+            // The pings are answered internally, each taking at least one call.
+            assert!(calls >= 3);
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod config_error {
+    use tokio::io::{AsyncReadExt, DuplexStream};
+
+    use crate::{
+        error::{ConfigError, ConnectAnyError, Error},
+        options::{AcceptOptions, ConnectOptions, ConnectTarget},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_read_buffer_fails_before_writing() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let result = WebSocket::connect::<16>(
+            ConnectOptions::default(),
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            &mut [],
+            write_buf,
+            fragments_buf,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::EmptyReadBuffer))
+        ));
+
+        // The client was dropped without sending the request.
+        let n = server.read(&mut [0u8; SIZE]).await.expect("Failed to read");
+
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn empty_write_buffer() {
+        let (_client, server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+
+        let result = WebSocket::accept::<16>(
+            AcceptOptions::default(),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            &mut [],
+            &mut [],
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::EmptyWriteBuffer))
+        ));
+    }
+
+    #[tokio::test]
+    async fn required_fragments_buffer() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+
+        let result = WebSocket::connect::<16>(
+            ConnectOptions::default().with_require_fragments(true),
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            &mut [],
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::EmptyFragmentsBuffer))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stream_is_not_opened() {
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+
+        let targets = [ConnectTarget::new(
+            "server",
+            ConnectOptions::default().with_require_fragments(true),
+        )];
+
+        let error = WebSocket::connect_any::<16, _, _>(
+            &targets,
+            async |_: &ConnectTarget<'_, '_>| -> Result<(FromTokio<DuplexStream>, StdRng), ()> {
+                panic!("Stream must not be opened")
+            },
+            read_buf,
+            write_buf,
+            &mut [],
+        )
+        .await
+        .err()
+        .expect("Connect must fail");
+
+        assert!(matches!(
+            error,
+            ConnectAnyError::WebSocket(Error::Config(ConfigError::EmptyFragmentsBuffer))
+        ));
+    }
+}
+
+mod compressed_binary {
+    use crate::{
+        compression::{PayloadCodec, STORED, decompress},
+        error::{Error, FrameEncodeError, WriteError},
+    };
+
+    use super::*;
+
+    /// Never compresses, so payloads are always stored.
+    struct Uncompressed;
+
+    impl PayloadCodec for Uncompressed {
+        const ID: u8 = 1;
+
+        fn compress(&self, _: &[u8], _: &mut [u8]) -> Option<usize> {
+            None
+        }
+
+        fn decompress(&self, _: &[u8], _: &mut [u8]) -> Option<usize> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn stored_both_directions() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_compressed_binary(b"from client", &Uncompressed)
+                .await
+                .expect("Failed to send");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload[0], STORED);
+                    assert_eq!(
+                        decompress(&Uncompressed, payload, &mut []).expect("Valid payload"),
+                        b"from server"
+                    );
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(
+                        decompress(&Uncompressed, payload, &mut []).expect("Valid payload"),
+                        b"from client"
+                    );
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            websocketz
+                .send_compressed_binary(b"from server", &Uncompressed)
+                .await
+                .expect("Failed to send");
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn write_buffer_too_small() {
+        let (client, _server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; 16];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        // 14 bytes are reserved for the frame header and 1 for the compression header.
+        websocketz
+            .send_compressed_binary(b"a", &Uncompressed)
+            .await
+            .expect("Fits");
+
+        assert!(matches!(
+            websocketz
+                .send_compressed_binary(b"ab", &Uncompressed)
+                .await,
+            Err(Error::Write(WriteError::WriteFrame(
+                framez::WriteError::Encode(FrameEncodeError::BufferTooSmall)
+            )))
+        ));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn lz4() {
+        use crate::compression::Lz4;
+
+        const PAYLOAD: &[u8] = &[b'z'; 1000];
+
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_compressed_binary(PAYLOAD, &Lz4)
+                .await
+                .expect("Failed to send");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+            let decompressed = &mut [0u8; 1024];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => {
+                    assert_eq!(payload[0], Lz4::ID);
+                    assert!(payload.len() < PAYLOAD.len());
+                    assert_eq!(
+                        decompress(&Lz4, payload, decompressed).expect("Valid payload"),
+                        PAYLOAD
+                    );
+                }
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod binary_with {
+    use crate::error::{Error, FrameEncodeError, WriteError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn assembled_in_write_buffer() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_binary_with(9, |dst| {
+                    dst[..4].copy_from_slice(b"head");
+                    dst[4..].copy_from_slice(b"+body");
+                })
+                .await
+                .expect("Failed to send");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => assert_eq!(payload, b"head+body"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn write_buffer_too_small() {
+        let (client, _server) = tokio::io::duplex(16);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; 16];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        // 14 bytes are reserved for the frame header.
+        websocketz
+            .send_binary_with(2, |dst| dst.copy_from_slice(b"ab"))
+            .await
+            .expect("Fits");
+
+        assert!(matches!(
+            websocketz
+                .send_binary_with(3, |_| panic!("Must not be called"))
+                .await,
+            Err(Error::Write(WriteError::WriteFrame(
+                framez::WriteError::Encode(FrameEncodeError::BufferTooSmall)
+            )))
+        ));
+    }
+}
+
+mod download {
+    use std::vec::Vec;
+
+    use tokio::io::DuplexStream;
+
+    use crate::{download::Download, error::DownloadError};
+
+    use super::*;
+
+    const IMAGE: &[u8] = b"0123456789abcdefghij";
+
+    /// Requests the image from `offset` as 8 big-endian bytes.
+    fn request(offset: u64, dst: &mut [u8]) -> Option<usize> {
+        dst.get_mut(..8)?.copy_from_slice(&offset.to_be_bytes());
+
+        Some(8)
+    }
+
+    /// Serves `image` in chunks of `chunk` bytes from the requested offset, closing the connection after `limit` chunks.
+    async fn serve(stream: DuplexStream, image: &[u8], chunk: usize, limit: usize) -> u64 {
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::server(
+            FromTokio::new(stream),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let offset = match next!(websocketz) {
+            Some(Ok(Message::Binary(payload))) => {
+                u64::from_be_bytes(payload.try_into().expect("8 bytes"))
+            }
+            message => panic!("Unexpected message: {message:?}"),
+        };
+
+        for payload in image[offset as usize..].chunks(chunk).take(limit) {
+            websocketz
+                .send(Message::Binary(payload))
+                .await
+                .expect("Failed to send");
+        }
+
+        websocketz
+            .send_close_and_flush(None)
+            .await
+            .expect("Failed to close");
+
+        // Wait for the Close reply.
+        while let Some(Ok(_)) = next!(websocketz) {}
+
+        offset
+    }
+
+    async fn download(
+        stream: DuplexStream,
+        download: &mut Download,
+        received: &mut Vec<u8>,
+    ) -> Result<(), DownloadError<std::io::Error, ()>> {
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(stream),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        download
+            .run(&mut websocketz, request, async |offset, chunk| {
+                assert_eq!(offset, received.len() as u64);
+
+                received.extend_from_slice(chunk);
+
+                Ok(())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn complete() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let mut state = Download::new(IMAGE.len() as u64);
+        let mut received = Vec::new();
+
+        let (offset, result) = tokio::join!(
+            serve(server, IMAGE, 8, usize::MAX),
+            download(client, &mut state, &mut received)
+        );
+
+        result.expect("Download failed");
+
+        assert_eq!(offset, 0);
+        assert!(state.is_complete());
+        assert_eq!(received, IMAGE);
+    }
+
+    #[tokio::test]
+    async fn resume_after_interruption() {
+        let mut state = Download::new(IMAGE.len() as u64);
+        let mut received = Vec::new();
+
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let (_, result) = tokio::join!(
+            serve(server, IMAGE, 8, 1),
+            download(client, &mut state, &mut received)
+        );
+
+        assert!(
+            matches!(result, Err(DownloadError::Interrupted { offset: 8 })),
+            "{result:?}"
+        );
+        assert_eq!(state.offset(), 8);
+        assert_eq!(state.remaining(), 12);
+
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let (offset, result) = tokio::join!(
+            serve(server, IMAGE, 8, usize::MAX),
+            download(client, &mut state, &mut received)
+        );
+
+        result.expect("Download failed");
+
+        assert_eq!(offset, 8);
+        assert_eq!(received, IMAGE);
+    }
+
+    #[tokio::test]
+    async fn too_long() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let mut state = Download::new(10);
+        let mut received = Vec::new();
+
+        let (_, result) = tokio::join!(
+            serve(server, IMAGE, 8, usize::MAX),
+            download(client, &mut state, &mut received)
+        );
+
+        assert!(matches!(
+            result,
+            Err(DownloadError::TooLong { offset: 8, len: 8 })
+        ));
+        assert_eq!(received, &IMAGE[..8]);
+    }
+
+    #[tokio::test]
+    async fn failed_chunk_is_requested_again() {
+        let (client, _server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        let mut state = Download::resume(4, 4);
+
+        // Complete downloads send no request.
+        state
+            .run(
+                &mut websocketz,
+                |_, _| panic!("Must not request"),
+                async |_, _| Err::<(), ()>(()),
+            )
+            .await
+            .expect("Already complete");
+
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let mut state = Download::new(IMAGE.len() as u64);
+
+        let (_, result) = tokio::join!(serve(server, IMAGE, 8, usize::MAX), async {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            state
+                .run(&mut websocketz, request, async |offset, _| {
+                    if offset == 8 { Err(()) } else { Ok(()) }
+                })
+                .await
+        });
+
+        assert!(matches!(result, Err(DownloadError::Chunk(()))));
+        assert_eq!(state.offset(), 8);
+    }
+}
+
+mod golden_handshake {
+    use tokio::io::AsyncReadExt;
+
+    use crate::{
+        http::Header,
+        mock::{FixedRng, connect_request},
+        options::ConnectOptions,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn connect_sends_golden_request() {
+        const SEED: &[u8] = b"0123456789abcdef";
+
+        let headers = &[Header {
+            name: "Host",
+            value: b"localhost",
+        }];
+
+        let options = ConnectOptions::new_unchecked("/golden")
+            .with_headers_unchecked(headers)
+            .with_protocols(&["v1"]);
+
+        let expected = &mut [0u8; SIZE * 2];
+        let expected_len = connect_request(options, &mut FixedRng::new(SEED), expected)
+            .expect("Buffer large enough");
+
+        let (client, mut server) = tokio::io::duplex(SIZE * 4);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE * 2];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            // No response is sent, the handshake fails once the server is dropped.
+            let _ = WebSocket::connect::<16>(
+                options,
+                FromTokio::new(client),
+                FixedRng::new(SEED),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            )
+            .await;
+        };
+
+        let server = async move {
+            let request = &mut [0u8; SIZE * 2];
+            let len = server.read(request).await.expect("Failed to read request");
+
+            (*request, len)
+        };
+
+        let ((request, len), ()) = tokio::join!(server, client);
+
+        assert_eq!(&request[..len], &expected[..expected_len]);
+        assert!(
+            expected[..expected_len]
+                .windows(24)
+                .any(|window| window == crate::mock::sec_key(&mut FixedRng::new(SEED)))
+        );
+    }
+}
+
+mod roles {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::error::{FrameDecodeError, MessageError};
+
+    use super::*;
+
+    /// Bit of the second header byte indicating a masked frame.
+    const MASK_BIT: u8 = 0x80;
+
+    #[tokio::test]
+    async fn client_masks_outgoing_frames() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        websocketz
+            .send(Message::Text("Hi"))
+            .await
+            .expect("Failed to send");
+
+        let frame = &mut [0u8; 8];
+        server
+            .read_exact(frame)
+            .await
+            .expect("Failed to read frame");
+
+        assert_eq!(frame[1], MASK_BIT | 2);
+    }
+
+    #[tokio::test]
+    async fn server_does_not_mask_outgoing_frames() {
+        let (server, mut client) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        websocketz
+            .send(Message::Text("Hi"))
+            .await
+            .expect("Failed to send");
+
+        let frame = &mut [0u8; 4];
+        client
+            .read_exact(frame)
+            .await
+            .expect("Failed to read frame");
+
+        assert_eq!(frame, &[0x81, 2, b'H', b'i']);
+    }
+
+    #[tokio::test]
+    async fn client_rejects_masked_frames() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        server
+            .write_all(&[0x81, MASK_BIT | 2, 1, 2, 3, 4, b'H' ^ 1, b'i' ^ 2])
+            .await
+            .expect("Failed to write frame");
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::ReadFrame(framez::ReadError::Decode(
+                FrameDecodeError::MaskedFrameFromServer
+            ))))
+        ));
+    }
+
+    #[tokio::test]
+    async fn server_rejects_unmasked_frames() {
+        let (server, mut client) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::server(
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        client
+            .write_all(&[0x81, 2, b'H', b'i'])
+            .await
+            .expect("Failed to write frame");
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::ReadFrame(framez::ReadError::Decode(
+                FrameDecodeError::UnmaskedFrameFromClient
+            ))))
+        ));
+    }
+}
+
+mod frame_sequence {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{
+        FirstFrame, Verdict,
+        error::{FrameDecodeError, MessageError, ProtocolError},
+        extensions::CodecConfig,
+    };
+
+    use super::*;
+
+    fn reject_third_frame(frame: &FirstFrame<'_>) -> Verdict {
+        match frame.sequence() {
+            3 => Verdict::Reject,
+            _ => Verdict::Accept,
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_are_numbered() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_message_filter(reject_third_frame);
+
+        assert_eq!(websocketz.last_frame(), None);
+
+        server
+            .write_all(&[
+                0x89, 1, b'p', // Ping, answered automatically
+                0x81, 2, b'H', b'i', // Text
+                0x81, 2, b'N', b'o', // Text, rejected by the filter
+                0x81, 2, b'O', b'k', // Text
+                0x80, 0, // Continuation without a message
+            ])
+            .await
+            .expect("Failed to write frames");
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Hi")))));
+
+        let meta = websocketz.last_frame().expect("Frame received");
+
+        assert_eq!(meta.sequence(), 2);
+        assert!(meta.is_final());
+        assert_eq!(meta.payload_len(), 2);
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Ok")))));
+        assert_eq!(websocketz.last_frame().map(|meta| meta.sequence()), Some(4));
+
+        match next!(websocketz) {
+            Some(Err(error)) => {
+                assert_eq!(
+                    std::format!("{error}"),
+                    "Protocol error at frame #5: Invalid continuation frame"
+                );
+                assert!(matches!(
+                    error,
+                    MessageError::Protocol {
+                        error: ProtocolError::InvalidContinuationFrame,
+                        frame: 5
+                    }
+                ));
+            }
+            message => panic!("Unexpected message: {message:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn peeked_frames_are_not_counted() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        server
+            .write_all(&[0x81, 2, b'H', b'i'])
+            .await
+            .expect("Failed to write frame");
+
+        let peeked = websocketz
+            .peek_message(|message| matches!(message, Message::Text("Hi")))
+            .await;
+
+        assert!(matches!(peeked, Some(Ok(true))));
+        assert_eq!(websocketz.last_frame(), None);
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Hi")))));
+        assert_eq!(websocketz.last_frame().map(|meta| meta.sequence()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn reserved_bits_allowed_by_codec_config() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let config = CodecConfig::new().with_reserved_bits(CodecConfig::RSV2 | CodecConfig::RSV3);
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        )
+        .with_codec_config(config);
+
+        assert_eq!(websocketz.codec_config(), config);
+
+        server
+            .write_all(&[
+                0x81, 2, b'H', b'i', // Text
+                0xa2, 1, 0x01, // Binary with RSV2
+                0x99, 0, // Ping with RSV3, control frames never allow reserved bits
+            ])
+            .await
+            .expect("Failed to write frames");
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Hi")))));
+        assert_eq!(
+            websocketz.last_frame().map(|meta| meta.reserved_bits()),
+            Some(0)
+        );
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Ok(Message::Binary(&[0x01])))
+        ));
+        assert_eq!(
+            websocketz.last_frame().map(|meta| meta.reserved_bits()),
+            Some(CodecConfig::RSV2)
+        );
+
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::ReadFrame(framez::ReadError::Decode(
+                FrameDecodeError::ReservedBitsNotZero
+            ))))
+        ));
+    }
+}
+
+#[cfg(feature = "buffer-pool")]
+mod buffer_pool {
+    use crate::{
+        buffers::BufferPool,
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    static POOL: BufferPool<2, { SIZE * 2 }> = BufferPool::new();
+
+    #[tokio::test]
+    async fn pooled_handshake() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let mut server_checkout = POOL.checkout().expect("Slot must be free");
+        let mut client_checkout = POOL.checkout().expect("Slot must be free");
+
+        assert!(POOL.checkout().is_none());
+
+        let server = async {
+            let mut websocketz = WebSocket::accept_pooled::<16, { SIZE * 2 }>(
+                AcceptOptions::default(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                &mut server_checkout,
+            )
+            .await
+            .expect("Failed to accept");
+
+            match next!(websocketz) {
+                Some(Ok(Message::Text(text))) => assert_eq!(text, "Pooled"),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        let client = async {
+            let mut websocketz = WebSocket::connect_pooled::<16, { SIZE * 2 }>(
+                ConnectOptions::default(),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                &mut client_checkout,
+            )
+            .await
+            .expect("Failed to connect");
+
+            websocketz
+                .send(Message::Text("Pooled"))
+                .await
+                .expect("Failed to send");
+        };
+
+        tokio::join!(server, client);
+
+        drop((server_checkout, client_checkout));
+
+        assert_eq!(POOL.available(), 2);
+    }
+}
+
+mod estimated_sizes {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{
+        error::{Error, HttpEncodeError, WriteError},
+        http::{Header, estimated_request_size, estimated_response_size, headers_size},
+        options::{AcceptOptions, ConnectOptions},
+    };
+
+    use super::*;
+
+    const HEADERS: &[Header<'static>] = &[Header {
+        name: "Host",
+        value: b"localhost",
+    }];
+
+    const PROTOCOL: &[Header<'static>] = &[Header {
+        name: "sec-websocket-protocol",
+        value: b"chat",
+    }];
+
+    const REQUEST_SIZE: usize = estimated_request_size(
+        "/chat".len(),
+        headers_size(HEADERS) + headers_size(PROTOCOL),
+    );
+
+    const RESPONSE_SIZE: usize = estimated_response_size(headers_size(PROTOCOL));
+
+    // The estimates include the `permessage-deflate` headers with the `deflate` feature.
+    fn connect_options() -> ConnectOptions<'static, 'static> {
+        let options = ConnectOptions::new("/chat")
+            .expect("Valid path")
+            .with_headers(HEADERS)
+            .expect("Valid headers")
+            .with_protocols(&["chat"]);
+
+        #[cfg(feature = "deflate")]
+        let options = options.with_permessage_deflate(true);
+
+        options
+    }
+
+    fn accept_options() -> AcceptOptions<'static, 'static> {
+        let options = AcceptOptions::default().with_protocols(&["chat"]);
+
+        #[cfg(feature = "deflate")]
+        let options = options.with_permessage_deflate(true);
+
+        options
+    }
+
+    #[tokio::test]
+    async fn exact_buffers() {
+        let (client, server) = tokio::io::duplex(SIZE * 4);
+
+        let client = async move {
+            let read_buf = &mut [0u8; RESPONSE_SIZE];
+            let write_buf = &mut [0u8; REQUEST_SIZE];
+
+            WebSocket::connect::<16>(
+                connect_options(),
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                &mut [],
+            )
+            .await
+            .map(|websocketz| websocketz.protocol())
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; REQUEST_SIZE];
+            let write_buf = &mut [0u8; RESPONSE_SIZE];
+
+            WebSocket::accept::<16>(
+                accept_options(),
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                &mut [],
+            )
+            .await
+            .map(|websocketz| websocketz.protocol())
+        };
+
+        let (client, server) = tokio::join!(client, server);
+
+        assert_eq!(client.expect("Failed to connect"), Some(0));
+        assert_eq!(server.expect("Failed to accept"), Some(0));
+    }
+
+    #[tokio::test]
+    async fn response_one_byte_short() {
+        let (server, mut client) = tokio::io::duplex(SIZE * 4);
+
+        let request = &mut [0u8; REQUEST_SIZE];
+        let len =
+            crate::mock::connect_request(connect_options(), &mut StdRng::from_os_rng(), request)
+                .expect("Failed to encode request");
+
+        assert_eq!(len, REQUEST_SIZE);
+
+        client
+            .write_all(request)
+            .await
+            .expect("Failed to write request");
+
+        let read_buf = &mut [0u8; REQUEST_SIZE];
+        let write_buf = &mut [0u8; RESPONSE_SIZE - 1];
+
+        let result = WebSocket::accept::<16>(
+            accept_options(),
+            FromTokio::new(server),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            &mut [],
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Write(WriteError::WriteHttp(
+                framez::WriteError::Encode(HttpEncodeError::BufferTooSmall)
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn request_one_byte_short() {
+        let (client, _server) = tokio::io::duplex(SIZE * 4);
+
+        let read_buf = &mut [0u8; RESPONSE_SIZE];
+        let write_buf = &mut [0u8; REQUEST_SIZE - 1];
+
+        let result = WebSocket::connect::<16>(
+            connect_options(),
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            &mut [],
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Write(WriteError::WriteHttp(
+                framez::WriteError::Encode(HttpEncodeError::BufferTooSmall)
+            )))
+        ));
+    }
+}
+
+mod eof {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::error::{FrameDecodeError, MessageError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn between_frames() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        server
+            .write_all(&[0x81, 2, b'H', b'i'])
+            .await
+            .expect("Failed to write frame");
+
+        drop(server);
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Hi")))));
+        assert!(next!(websocketz).is_none());
+    }
+
+    #[tokio::test]
+    async fn mid_frame() {
+        let (client, mut server) = tokio::io::duplex(SIZE);
+
+        let read_buf = &mut [0u8; SIZE];
+        let write_buf = &mut [0u8; SIZE];
+        let fragments_buf = &mut [0u8; SIZE];
+
+        let mut websocketz = WebSocket::client(
+            FromTokio::new(client),
+            StdRng::from_os_rng(),
+            read_buf,
+            write_buf,
+            fragments_buf,
+        );
+
+        server
+            .write_all(&[0x81, 2, b'H', b'i', 0x82, 126, 0x01, 0x00, 1, 2, 3])
+            .await
+            .expect("Failed to write frames");
+
+        drop(server);
+
+        assert!(matches!(next!(websocketz), Some(Ok(Message::Text("Hi")))));
+        assert!(matches!(
+            next!(websocketz),
+            Some(Err(MessageError::ReadFrame(framez::ReadError::Decode(
+                FrameDecodeError::UnexpectedEof { received: 7 }
+            ))))
+        ));
+    }
+}
+
+mod flush {
+    use embedded_io_async::{ErrorType, Write};
+
+    use crate::WebSocketWrite;
+
+    use super::*;
+
+    /// A writer transmitting its bytes only when flushed.
+    struct Buffered {
+        buffer: [u8; SIZE],
+        written: usize,
+        transmitted: usize,
+    }
+
+    impl Buffered {
+        const fn new() -> Self {
+            Self {
+                buffer: [0; SIZE],
+                written: 0,
+                transmitted: 0,
+            }
+        }
+    }
+
+    impl ErrorType for Buffered {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Write for Buffered {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(SIZE - self.written);
+
+            self.buffer[self.written..self.written + len].copy_from_slice(&buf[..len]);
+            self.written += len;
+
+            Ok(len)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.transmitted = self.written;
+
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn send_flushes() {
+        let write_buf = &mut [0u8; SIZE];
+
+        let mut websocketz =
+            WebSocketWrite::server(Buffered::new(), StdRng::from_os_rng(), write_buf);
+
+        websocketz
+            .send(Message::Text("Hi"))
+            .await
+            .expect("Failed to send");
+
+        assert_eq!(websocketz.inner().transmitted, 4);
+    }
+
+    #[tokio::test]
+    async fn batched() {
+        let write_buf = &mut [0u8; SIZE];
+
+        let mut websocketz =
+            WebSocketWrite::server(Buffered::new(), StdRng::from_os_rng(), write_buf);
+
+        websocketz
+            .send_without_flush(Message::Text("Hi"))
+            .await
+            .expect("Failed to send");
+
+        websocketz
+            .send_without_flush(Message::Binary(b"Hey"))
+            .await
+            .expect("Failed to send");
+
+        assert_eq!(websocketz.inner().written, 9);
+        assert_eq!(websocketz.inner().transmitted, 0);
+
+        websocketz.flush().await.expect("Failed to flush");
+
+        let inner = websocketz.inner();
+
+        assert_eq!(inner.transmitted, 9);
+        assert_eq!(
+            &inner.buffer[..inner.written],
+            &[0x81, 2, b'H', b'i', 0x82, 3, b'H', b'e', b'y']
+        );
+    }
+}
+
+mod next_into {
+    use crate::error::MessageError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reassembles_into_buffer() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for message in STR_MESSAGES {
+                websocketz
+                    .send_fragmented(Message::Text(message), 8)
+                    .await
+                    .expect("Failed to send fragmented message");
+            }
+
+            websocketz
+                .send(Message::Binary(b"single"))
+                .await
+                .expect("Failed to send message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            // Too small for any of the fragmented messages.
+            let fragments_buf = &mut [0u8; 8];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let buffer = &mut [0u8; SIZE];
+
+            for expected in STR_MESSAGES {
+                let matched = websocketz
+                    .with_next_into(
+                        buffer,
+                        |message| matches!(message, Message::Text(text) if text == *expected),
+                    )
+                    .await
+                    .expect("Connection closed")
+                    .expect("Failed to read message");
+
+                assert!(matched);
+            }
+
+            let matched = websocketz
+                .with_next_into(&mut [0u8; 0], |message| {
+                    matches!(message, Message::Binary(b"single"))
+                })
+                .await
+                .expect("Connection closed")
+                .expect("Failed to read message");
+
+            assert!(matched);
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn buffer_too_small() {
+        let (client, server) = tokio::io::duplex(SIZE);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send_fragmented(Message::Binary(BINARY_MESSAGES[1]), 8)
+                .await
+                .expect("Failed to send fragmented message");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match websocketz.with_next_into(&mut [0u8; 16], |_| ()).await {
+                Some(Err(MessageError::FragmentsBufferTooSmall)) => {}
+                message => panic!("Unexpected message: {message:?}"),
+            }
+        };
+
+        tokio::join!(server, client);
+    }
+}
+
+mod stream {
+    use embedded_io_async::{Read, Write};
+
+    use crate::{CloseFrame, error::StreamError, stream::ByteStream};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn messages_are_flattened() {
+        let (client, server) = tokio::io::duplex(16);
+
+        // CONNECT split over two messages, followed by a PINGREQ in the same message.
+        let connect: [u8; 14] = [
+            0x10, 0x0C, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3C, 0x00, 0x00,
+        ];
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+            let pending = &mut [0u8; 16];
+
+            let websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut stream = ByteStream::new(websocketz).with_pending_buffer(pending);
+
+            stream.write_all(&connect).await.expect("Failed to write");
+            stream.flush().await.expect("Failed to flush");
+
+            let mut received = [0u8; 16];
+
+            // The rest of the first message is kept pending.
+            let len = stream
+                .read(&mut received[..3])
+                .await
+                .expect("Failed to read");
+            assert_eq!(len, 3);
+            assert_eq!(stream.pending(), 5);
+
+            stream
+                .read_exact(&mut received[3..])
+                .await
+                .expect("Failed to read");
+
+            assert_eq!(&received[..14], &connect);
+            assert_eq!(&received[14..], &[0xC0, 0x00]);
+
+            let mut eof = [0u8; 1];
+            assert_eq!(stream.read(&mut eof).await.expect("Failed to read"), 0);
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            match next!(websocketz) {
+                Some(Ok(Message::Binary(payload))) => assert_eq!(payload, connect),
+                message => panic!("Unexpected message: {message:?}"),
+            }
+
+            websocketz
+                .send(Message::Binary(&connect[..8]))
+                .await
+                .expect("Failed to send");
+            websocketz
+                .send(Message::Binary(&[]))
+                .await
+                .expect("Failed to send");
+
+            let mut rest = [0u8; 8];
+            rest[..6].copy_from_slice(&connect[8..]);
+            rest[6..].copy_from_slice(&[0xC0, 0x00]);
+
+            websocketz
+                .send(Message::Binary(&rest))
+                .await
+                .expect("Failed to send");
+
+            websocketz
+                .close(Some(CloseFrame::no_reason(CloseCode::Normal)))
+                .await
+                .expect("Failed to close");
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn text_and_pending_overflow_are_rejected() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+            let pending = &mut [0u8; 4];
+
+            let websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut stream = ByteStream::new(websocketz).with_pending_buffer(pending);
+
+            let mut received = [0u8; 2];
+
+            assert!(matches!(
+                stream.read(&mut received).await,
+                Err(StreamError::Text)
+            ));
+            assert!(matches!(
+                stream.read(&mut received).await,
+                Err(StreamError::BufferTooSmall)
+            ));
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            websocketz
+                .send(Message::Text("mqtt"))
+                .await
+                .expect("Failed to send");
+            websocketz
+                .send(Message::Binary(&[0; 8]))
+                .await
+                .expect("Failed to send");
+        };
+
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn writes_are_split_at_max_message() {
+        let (client, server) = tokio::io::duplex(16);
+
+        let client = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let websocketz = WebSocket::client(
+                FromTokio::new(client),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            let mut stream = ByteStream::new(websocketz).with_max_message(4);
+
+            assert_eq!(stream.write(b"abcdef").await.expect("Failed to write"), 4);
+
+            stream.write_all(b"ghij").await.expect("Failed to write");
+            stream.flush().await.expect("Failed to flush");
+        };
+
+        let server = async move {
+            let read_buf = &mut [0u8; SIZE];
+            let write_buf = &mut [0u8; SIZE];
+            let fragments_buf = &mut [0u8; SIZE];
+
+            let mut websocketz = WebSocket::server(
+                FromTokio::new(server),
+                StdRng::from_os_rng(),
+                read_buf,
+                write_buf,
+                fragments_buf,
+            );
+
+            for expected in [&b"abcd"[..], b"ghij"] {
+                match next!(websocketz) {
+                    Some(Ok(Message::Binary(payload))) => assert_eq!(payload, expected),
+                    message => panic!("Unexpected message: {message:?}"),
+                }
+            }
+        };
+
+        tokio::join!(server, client);
     }
 }
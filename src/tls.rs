@@ -0,0 +1,227 @@
+//! WebSocket connections over TLS (`wss://`).
+//!
+//! TLS itself is not part of this library. [`WebSocket::connect_tls`](crate::WebSocket::connect_tls) and [`WebSocket::accept_tls`](crate::WebSocket::accept_tls)
+//! take a callback performing the TLS handshake over the underlying stream, e.g. with [`embedded-tls`](https://docs.rs/embedded-tls/latest/embedded_tls/)
+//! or [`esp-mbedtls`](https://github.com/esp-rs/esp-mbedtls), and returning the encrypted [`embedded_io_async::Read`] + [`embedded_io_async::Write`] stream.
+//! The WebSocket handshake is then performed over that stream.
+//!
+//! A single buffer is split by a [`TlsBufferPlan`] into the record buffers of the TLS connection and the buffers of the WebSocket connection.
+//!
+//! With the `embedded-tls` feature, `open_embedded_tls` opens an `embedded_tls::TlsConnection` in the record buffers.
+//!
+//! # Example
+//!
+//! Connecting with `embedded-tls`, without the `embedded-tls` feature:
+//!
+#![cfg_attr(feature = "client", doc = "```")]
+#![cfg_attr(not(feature = "client"), doc = "```ignore")]
+//! # use websocketz::mock::Noop;
+//! # async fn run(socket: Noop, rng: Noop) {
+//! use websocketz::{
+//!     WebSocket,
+//!     options::ConnectOptions,
+//!     tls::{TlsBufferPlan, TlsBuffers},
+//! };
+//!
+//! const PLAN: TlsBufferPlan = TlsBufferPlan::new(
+//!     TlsBufferPlan::RECORD_LEN,
+//!     TlsBufferPlan::RECORD_LEN,
+//!     1024,
+//!     1024,
+//!     1024,
+//! );
+//!
+//! let buffer = &mut [0u8; PLAN.len()];
+//!
+//! let websocketz = WebSocket::connect_tls::<16, _, _, _>(
+//!     ConnectOptions::new("/ws").expect("Valid path"),
+//!     socket,
+//!     async |socket, buffers: TlsBuffers<'_>| {
+//!         // let config = TlsConfig::new().with_server_name("example.com");
+//!         // let mut tls = TlsConnection::new(socket, buffers.read, buffers.write);
+//!         //
+//!         // tls.open(TlsContext::new(&config, UnsecureProvider::new::<Aes128GcmSha256>(tls_rng)))
+//!         //     .await?;
+//!         //
+//!         // Ok(tls)
+//!         # let _ = buffers;
+//!         # Ok::<_, core::convert::Infallible>(socket)
+//!     },
+//!     rng,
+//!     buffer,
+//!     PLAN,
+//! )
+//! .await
+//! .expect("Handshake failed");
+//! # }
+//! ```
+
+#[cfg(feature = "embedded-tls")]
+use embedded_io_async::{Read, Write};
+#[cfg(feature = "embedded-tls")]
+use embedded_tls::{CryptoProvider, TlsConfig, TlsConnection, TlsContext};
+
+use crate::buffers::Buffers;
+
+/// The record buffers of a TLS connection.
+#[derive(Debug)]
+pub struct TlsBuffers<'buf> {
+    /// The buffer for incoming records.
+    pub read: &'buf mut [u8],
+    /// The buffer for outgoing records.
+    pub write: &'buf mut [u8],
+}
+
+/// The lengths of the buffers of a WebSocket connection over TLS, carved out of a single buffer.
+///
+/// See the [module](crate::tls) docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsBufferPlan {
+    tls_read: usize,
+    tls_write: usize,
+    read: usize,
+    write: usize,
+    fragments: usize,
+}
+
+impl TlsBufferPlan {
+    /// The length of a buffer holding a TLS record of the maximum size, including the record header and the encryption overhead.
+    ///
+    /// The read buffer of a TLS connection must hold a whole record, unless the peer is known to send smaller records,
+    /// e.g. after negotiating the `max_fragment_length` extension.
+    pub const RECORD_LEN: usize = 16640;
+
+    /// Creates a new [`TlsBufferPlan`] with the lengths of the TLS record buffers and of the WebSocket buffers.
+    pub const fn new(
+        tls_read: usize,
+        tls_write: usize,
+        read: usize,
+        write: usize,
+        fragments: usize,
+    ) -> Self {
+        Self {
+            tls_read,
+            tls_write,
+            read,
+            write,
+            fragments,
+        }
+    }
+
+    /// Returns the length of the buffer needed by the plan.
+    pub const fn len(&self) -> usize {
+        self.tls_read + self.tls_write + self.read + self.write + self.fragments
+    }
+
+    /// Returns `true` if the plan does not need a buffer.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits `buffer` into the TLS record buffers and the WebSocket buffers.
+    ///
+    /// Returns `None` if `buffer` is shorter than [`TlsBufferPlan::len`]. Remaining bytes are not used.
+    pub fn split<'buf>(&self, buffer: &'buf mut [u8]) -> Option<(TlsBuffers<'buf>, Buffers<'buf>)> {
+        let buffer = buffer.get_mut(..self.len())?;
+
+        let (tls_read, buffer) = buffer.split_at_mut(self.tls_read);
+        let (tls_write, buffer) = buffer.split_at_mut(self.tls_write);
+        let (read, buffer) = buffer.split_at_mut(self.read);
+        let (write, fragments) = buffer.split_at_mut(self.write);
+
+        Some((
+            TlsBuffers {
+                read: tls_read,
+                write: tls_write,
+            },
+            Buffers::new(read, write, fragments),
+        ))
+    }
+}
+
+/// Opens an `embedded-tls` connection over `socket` in the record buffers of `buffers`.
+///
+/// Performs the TLS handshake with `config`, e.g. the server name, and `provider`, the cipher suite, RNG and certificate verifier.
+/// Pass it to [`WebSocket::connect_tls`](crate::WebSocket::connect_tls):
+///
+#[cfg_attr(feature = "client", doc = "```")]
+#[cfg_attr(not(feature = "client"), doc = "```ignore")]
+/// # use websocketz::mock::Noop;
+/// # async fn run<P>(socket: Noop, rng: Noop, provider: P)
+/// # where
+/// #     P: embedded_tls::CryptoProvider<CipherSuite = embedded_tls::Aes128GcmSha256>,
+/// # {
+/// use embedded_tls::TlsConfig;
+/// use websocketz::{
+///     WebSocket,
+///     options::ConnectOptions,
+///     tls::{TlsBufferPlan, TlsBuffers, open_embedded_tls},
+/// };
+///
+/// const PLAN: TlsBufferPlan = TlsBufferPlan::new(
+///     TlsBufferPlan::RECORD_LEN,
+///     TlsBufferPlan::RECORD_LEN,
+///     1024,
+///     1024,
+///     1024,
+/// );
+///
+/// let buffer = &mut [0u8; PLAN.len()];
+///
+/// // E.g. `UnsecureProvider::new::<Aes128GcmSha256>(tls_rng)`, which does not verify the certificate of the server.
+/// let config = TlsConfig::new().with_server_name("example.com");
+///
+/// let websocketz = WebSocket::connect_tls::<16, _, _, _>(
+///     ConnectOptions::new("/ws").expect("Valid path"),
+///     socket,
+///     async |socket, buffers: TlsBuffers<'_>| open_embedded_tls(socket, buffers, &config, provider).await,
+///     rng,
+///     buffer,
+///     PLAN,
+/// )
+/// .await
+/// .expect("Handshake failed");
+/// # }
+/// ```
+#[cfg(feature = "embedded-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-tls")))]
+pub async fn open_embedded_tls<'buf, S, P>(
+    socket: S,
+    buffers: TlsBuffers<'buf>,
+    config: &TlsConfig<'_>,
+    provider: P,
+) -> Result<TlsConnection<'buf, S, P::CipherSuite>, embedded_tls::TlsError>
+where
+    S: Read + Write,
+    P: CryptoProvider,
+{
+    let mut tls = TlsConnection::new(socket, buffers.read, buffers.write);
+
+    tls.open(TlsContext::new(config, provider)).await?;
+
+    Ok(tls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        let plan = TlsBufferPlan::new(5, 4, 3, 2, 1);
+
+        assert_eq!(plan.len(), 15);
+
+        let buffer = &mut [0u8; 16];
+
+        let (tls, buffers) = plan.split(buffer).unwrap();
+
+        assert_eq!(tls.read.len(), 5);
+        assert_eq!(tls.write.len(), 4);
+        assert_eq!(buffers.read.len(), 3);
+        assert_eq!(buffers.write.len(), 2);
+        assert_eq!(buffers.fragments.len(), 1);
+
+        assert!(plan.split(&mut [0u8; 14]).is_none());
+    }
+}
@@ -0,0 +1,17 @@
+//! Handshake tracing.
+//!
+//! Enabled with the `handshake-trace` feature and emitted through the `log` and/or `defmt` features.
+
+/// Traces a handshake decision point at the given level (`debug` or `warn`).
+///
+/// Arguments are not evaluated if tracing is disabled.
+macro_rules! handshake_trace {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(all(feature = "handshake-trace", feature = "log"))]
+        ::log::$level!($($arg)+);
+        #[cfg(all(feature = "handshake-trace", feature = "defmt"))]
+        ::defmt::$level!($($arg)+);
+    };
+}
+
+pub(crate) use handshake_trace;
@@ -1,15 +1,36 @@
+use core::cell::RefCell;
+
 use embedded_io_async::{Read, Write};
-use framez::{
-    Framed,
-    state::{ReadState, ReadWriteState, WriteState},
-};
-use rand::RngCore;
+use framez::state::{ReadState, WriteState};
 
+#[cfg(feature = "buffer-pool")]
+use crate::buffers::{Buffers, Checkout};
+#[cfg(feature = "deflate")]
+use crate::deflate::Inflater;
+use crate::{
+    CloseCode, CloseFrame, CloseSignal, ConnectionInfo, FragmentsState, FrameMeta, FrameMut,
+    HandshakeCapture, HandshakeInfo, HandshakeResult, HandshakeStats, IntegrityVerifier, Message,
+    MessageFilter, MessageWriter, OnFrame, OpCode, PreEncodedMessage, ProgressObserver, StreamItem,
+    WebSocketCore,
+    clock::Clock,
+    compression::PayloadCodec,
+    error::{Error, FrameEncodeError, MessageError, ProtocolError, QueuePingError, WriteError},
+    extensions::{AcceptedExtensions, CodecConfig},
+    http::{Header, Request},
+    options::{AcceptOptions, Config, Keepalive, Limits, Profile},
+    rng::RngCore,
+    websocket_core::{Core, ReadCore, WriteCore, check_buffers},
+};
+#[cfg(feature = "client")]
 use crate::{
-    FragmentsState, Frame, FramesCodec, Message, OnFrame, WebSocketCore,
-    error::{Error, ProtocolError},
-    http::{Request, Response},
-    options::{AcceptOptions, ConnectOptions},
+    error::ConnectAnyError,
+    http::{HeaderExt, Response},
+    options::{ConnectOptions, ConnectTarget},
+};
+#[cfg(feature = "tls")]
+use crate::{
+    error::TlsError,
+    tls::{TlsBufferPlan, TlsBuffers},
 };
 
 /// A WebSocket connection.
@@ -22,10 +43,16 @@ use crate::{
 pub struct WebSocket<'buf, RW, Rng> {
     #[doc(hidden)]
     pub core: WebSocketCore<'buf, RW, Rng>,
+    /// Path, origin, subprotocol and extensions of the handshake.
+    ///
+    /// Dropped by [`WebSocket::split_with`], the split instances do not expose it.
+    pub(crate) info: ConnectionInfo,
 }
 
 impl<'buf, RW, Rng> WebSocket<'buf, RW, Rng> {
     /// Creates a new [`WebSocket`] client after a successful handshake.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
     pub const fn client(
         inner: RW,
         rng: Rng,
@@ -37,10 +64,11 @@ impl<'buf, RW, Rng> WebSocket<'buf, RW, Rng> {
             core: WebSocketCore::client(
                 inner,
                 rng,
-                read_buffer,
-                write_buffer,
+                ReadState::new(read_buffer),
+                WriteState::new(write_buffer),
                 FragmentsState::new(fragments_buffer),
             ),
+            info: ConnectionInfo::new(),
         }
     }
 
@@ -56,10 +84,11 @@ impl<'buf, RW, Rng> WebSocket<'buf, RW, Rng> {
             core: WebSocketCore::server(
                 inner,
                 rng,
-                read_buffer,
-                write_buffer,
+                ReadState::new(read_buffer),
+                WriteState::new(write_buffer),
                 FragmentsState::new(fragments_buffer),
             ),
+            info: ConnectionInfo::new(),
         }
     }
 
@@ -67,6 +96,8 @@ impl<'buf, RW, Rng> WebSocket<'buf, RW, Rng> {
     ///
     /// # Generic Parameters
     /// `N`: The maximum number of headers to accept in the handshake response.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
     pub async fn connect<const N: usize>(
         options: ConnectOptions<'_, '_>,
         inner: RW,
@@ -94,268 +125,1796 @@ impl<'buf, RW, Rng> WebSocket<'buf, RW, Rng> {
 
     /// Creates a new [`WebSocket`] client and performs the handshake with a custom response handler.
     ///
+    /// Returns the value of `on_response` and the [`HandshakeResult`], e.g. the negotiated subprotocol, alongside the connection.
+    ///
     /// # Generic Parameters
     /// `N`: The maximum number of headers to accept in the handshake response.
-    pub async fn connect_with<const N: usize, F, T, E>(
-        options: ConnectOptions<'_, '_>,
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub async fn connect_with<'p, const N: usize, F, T, E>(
+        options: ConnectOptions<'p, '_>,
         inner: RW,
         rng: Rng,
         read_buffer: &'buf mut [u8],
         write_buffer: &'buf mut [u8],
         fragments_buffer: &'buf mut [u8],
         on_response: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
+    ) -> Result<(Self, T, HandshakeResult<'p>), Error<RW::Error, E>>
     where
         F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
         RW: Read + Write,
         Rng: RngCore,
     {
-        Self::client(inner, rng, read_buffer, write_buffer, fragments_buffer)
-            .client_handshake::<N, _, _, _>(options, on_response)
-            .await
+        check_buffers(
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+            options.require_fragments,
+        )?;
+
+        let protocols = options.protocols;
+
+        let (websocketz, custom) =
+            Self::client(inner, rng, read_buffer, write_buffer, fragments_buffer)
+                .client_handshake::<N, _, _, _>(options, on_response)
+                .await?;
+
+        let result = HandshakeResult::new(
+            protocols,
+            websocketz.protocol(),
+            websocketz.handshake_stats(),
+        );
+
+        Ok((websocketz, custom, result))
     }
 
-    /// Creates a new [`WebSocket`] server and performs the handshake.
+    /// Creates a new [`WebSocket`] client and performs the handshake,
+    /// copying the negotiated subprotocol, the accepted extensions, the cookies and the additional headers of `capture` from the response.
     ///
     /// # Generic Parameters
-    /// `N`: The maximum number of headers to accept in the handshake request.
-    pub async fn accept<const N: usize>(
-        options: AcceptOptions<'_, '_>,
+    /// `N`: The maximum number of headers to accept in the handshake response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run<Rng: rand_core::RngCore>(rng: Rng) {
+    /// # use websocketz::mock::Noop;
+    /// use websocketz::{HandshakeCapture, WebSocket, options::ConnectOptions};
+    ///
+    /// let read_buffer = &mut [0u8; 1024];
+    /// let write_buffer = &mut [0u8; 1024];
+    /// let fragments_buffer = &mut [0u8; 1024];
+    /// let scratch = &mut [0u8; 256];
+    ///
+    /// let (websocketz, info) = WebSocket::connect_full::<16>(
+    ///     ConnectOptions::new("/ws").expect("Valid path").with_protocols(&["chat"]),
+    ///     Noop,
+    ///     rng,
+    ///     read_buffer,
+    ///     write_buffer,
+    ///     fragments_buffer,
+    ///     HandshakeCapture::new(scratch).with_headers(&["x-session-id"]),
+    /// )
+    /// .await
+    /// .expect("Handshake failed");
+    ///
+    /// let session_id = info.header("x-session-id");
+    /// # let _ = (websocketz, session_id);
+    /// # }
+    /// ```
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub async fn connect_full<'s, const N: usize>(
+        options: ConnectOptions<'_, '_>,
         inner: RW,
         rng: Rng,
         read_buffer: &'buf mut [u8],
         write_buffer: &'buf mut [u8],
         fragments_buffer: &'buf mut [u8],
-    ) -> Result<Self, Error<RW::Error>>
+        capture: HandshakeCapture<'s>,
+    ) -> Result<(Self, HandshakeInfo<'s>), Error<RW::Error>>
     where
         RW: Read + Write,
+        Rng: RngCore,
     {
-        Ok(Self::accept_with::<N, _, _, _>(
+        let capture = RefCell::new(capture);
+
+        let (websocketz, (), _) = Self::connect_with::<N, _, _, _>(
             options,
             inner,
             rng,
             read_buffer,
             write_buffer,
             fragments_buffer,
-            |_| Ok(()),
+            |response| {
+                let mut capture = capture.borrow_mut();
+
+                if let Some(protocol) = response
+                    .headers()
+                    .header_value_str("sec-websocket-protocol")
+                {
+                    capture.protocol(protocol);
+                }
+
+                capture.headers(response.headers(), "set-cookie");
+
+                Ok(())
+            },
         )
-        .await?
-        .0)
+        .await?;
+
+        Ok((websocketz, capture.into_inner().finish()))
     }
 
-    /// Creates a new [`WebSocket`] server and performs the handshake with a custom request handler.
+    /// Creates a new [`WebSocket`] client and performs the handshake,
+    /// parsing the response again with `N2` headers if it has more than `N1` headers.
+    ///
+    /// The response is parsed again from the read buffer, so no reconnect is needed.
     ///
     /// # Generic Parameters
-    /// `N`: The maximum number of headers to accept in the handshake request.
-    pub async fn accept_with<const N: usize, F, T, E>(
-        options: AcceptOptions<'_, '_>,
+    /// `N1`: The number of headers to try first.
+    ///
+    /// `N2`: The maximum number of headers to accept in the handshake response.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub async fn connect_adaptive<const N1: usize, const N2: usize>(
+        options: ConnectOptions<'_, '_>,
         inner: RW,
         rng: Rng,
         read_buffer: &'buf mut [u8],
         write_buffer: &'buf mut [u8],
         fragments_buffer: &'buf mut [u8],
-        on_request: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
+    ) -> Result<Self, Error<RW::Error>>
     where
-        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
         RW: Read + Write,
+        Rng: RngCore,
     {
-        Self::server(inner, rng, read_buffer, write_buffer, fragments_buffer)
-            .server_handshake::<N, _, _, _>(options, on_request)
-            .await
-    }
+        check_buffers(
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+            options.require_fragments,
+        )?;
 
-    /// Sets whether to automatically send a Pong response.
-    #[inline]
-    pub const fn with_auto_pong(mut self, auto_pong: bool) -> Self {
-        self.core.set_auto_pong(auto_pong);
-        self
-    }
+        let (core, result) = Self::client(inner, rng, read_buffer, write_buffer, fragments_buffer)
+            .core
+            .client_handshake_adaptive::<N1, N2>(options)
+            .await;
 
-    /// Sets whether to automatically close the connection on receiving a Close frame.
-    #[inline]
-    pub const fn with_auto_close(mut self, auto_close: bool) -> Self {
-        self.core.set_auto_close(auto_close);
-        self
+        result.map(|info| Self { core, info })
     }
 
-    /// Returns reference to the reader/writer.
-    #[inline]
-    pub const fn inner(&self) -> &RW {
-        self.core.inner()
-    }
+    /// Tries the `targets` in order until a handshake succeeds,
+    /// returning the connected [`WebSocket`] along with the index of the target used.
+    ///
+    /// `open` opens a stream to the given target, along with the random number generator to use for the connection.
+    /// The buffers are reused for every attempt.
+    ///
+    /// If no target could be connected, the error of the last one is returned.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake response.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub async fn connect_any<const N: usize, F, C>(
+        targets: &[ConnectTarget<'_, '_>],
+        mut open: F,
+        read_buffer: &'buf mut [u8],
+        write_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+    ) -> Result<(Self, usize), ConnectAnyError<RW::Error, C>>
+    where
+        F: AsyncFnMut(&ConnectTarget<'_, '_>) -> Result<(RW, Rng), C>,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let mut buffers = (read_buffer, write_buffer, fragments_buffer);
+        let mut error = ConnectAnyError::NoTargets;
 
-    /// Returns mutable reference to the reader/writer.
-    #[inline]
-    pub const fn inner_mut(&mut self) -> &mut RW {
-        self.core.inner_mut()
-    }
+        for (index, target) in targets.iter().enumerate() {
+            let (read, write, fragments) = &buffers;
 
-    /// Consumes the [`WebSocket`] and returns the reader/writer.
-    #[inline]
-    pub fn into_inner(self) -> RW {
-        self.core.into_inner()
-    }
+            if let Err(err) =
+                check_buffers(read, write, fragments, target.options().require_fragments)
+            {
+                error = ConnectAnyError::WebSocket(Error::Config(err));
 
-    /// Returns the number of bytes that can be framed.
-    #[inline]
-    pub const fn framable(&self) -> usize {
-        self.core.framable()
+                continue;
+            }
+
+            let (inner, rng) = match open(target).await {
+                Ok(opened) => opened,
+                Err(err) => {
+                    error = ConnectAnyError::Connect(err);
+
+                    continue;
+                }
+            };
+
+            let (read, write, fragments) = buffers;
+
+            let (core, result) = WebSocketCore::client(
+                inner,
+                rng,
+                ReadState::new(read),
+                WriteState::new(write),
+                FragmentsState::new(fragments),
+            )
+            .client_handshake_recoverable::<N, _, _, _>(target.options(), |_| Ok(()))
+            .await;
+
+            match result {
+                Ok(((), info)) => return Ok((Self { core, info }, index)),
+                Err(err) => {
+                    buffers = core.into_buffers();
+                    error = ConnectAnyError::WebSocket(err);
+                }
+            }
+        }
+
+        Err(error)
     }
 
-    async fn client_handshake<const N: usize, F, T, E>(
-        self,
+    /// Performs the TLS handshake with `tls` over `stream`, then creates a new [`WebSocket`] client and performs the handshake over the TLS stream.
+    ///
+    /// `buffer` is split by `plan` into the record buffers passed to `tls` and the buffers of the [`WebSocket`].
+    ///
+    /// See the [`tls`](crate::tls) module.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake response.
+    #[cfg(all(feature = "client", feature = "tls"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "client", feature = "tls"))))]
+    pub async fn connect_tls<const N: usize, S, F, T>(
         options: ConnectOptions<'_, '_>,
-        on_response: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
+        stream: S,
+        tls: F,
+        rng: Rng,
+        buffer: &'buf mut [u8],
+        plan: TlsBufferPlan,
+    ) -> Result<Self, TlsError<RW::Error, T>>
     where
-        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        F: AsyncFnOnce(S, TlsBuffers<'buf>) -> Result<RW, T>,
         RW: Read + Write,
         Rng: RngCore,
     {
-        let (core, custom) = self
-            .core
-            .client_handshake::<N, _, _, _>(options, on_response)
-            .await?;
+        let (tls_buffers, buffers) = plan.split(buffer).ok_or(TlsError::BufferTooSmall)?;
+
+        let inner = tls(stream, tls_buffers).await.map_err(TlsError::Tls)?;
 
-        Ok((Self { core }, custom))
+        Ok(Self::connect::<N>(
+            options,
+            inner,
+            rng,
+            buffers.read,
+            buffers.write,
+            buffers.fragments,
+        )
+        .await?)
     }
 
-    async fn server_handshake<const N: usize, F, T, E>(
-        self,
-        options: AcceptOptions<'_, '_>,
-        on_request: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
+    /// Creates a new [`WebSocket`] client with the buffers of `checkout` and performs the handshake.
+    ///
+    /// See [`BufferPool`](crate::buffers::BufferPool).
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake response.
+    /// `SZ`: The size of the buffers of the pool.
+    #[cfg(all(feature = "client", feature = "buffer-pool"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "client", feature = "buffer-pool"))))]
+    pub async fn connect_pooled<const N: usize, const SZ: usize>(
+        options: ConnectOptions<'_, '_>,
+        inner: RW,
+        rng: Rng,
+        checkout: &'buf mut Checkout<'_, SZ>,
+    ) -> Result<Self, Error<RW::Error>>
     where
-        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
         RW: Read + Write,
+        Rng: RngCore,
     {
-        let (core, custom) = self
-            .core
-            .server_handshake::<N, _, _, _>(options, on_request)
-            .await?;
+        let Buffers {
+            read,
+            write,
+            fragments,
+        } = checkout.buffers();
 
-        Ok((Self { core }, custom))
+        Self::connect::<N>(options, inner, rng, read, write, fragments).await
     }
 
-    /// Sends a WebSocket message.
-    pub async fn send(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
+    /// Creates a new [`WebSocket`] server and performs the handshake.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    pub async fn accept<const N: usize>(
+        options: AcceptOptions<'_, '_>,
+        inner: RW,
+        rng: Rng,
+        read_buffer: &'buf mut [u8],
+        write_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+    ) -> Result<Self, Error<RW::Error>>
     where
-        RW: Write,
-        Rng: RngCore,
+        RW: Read + Write,
     {
-        self.core.send(message).await
+        Ok(Self::accept_with::<N, _, _, _>(
+            options,
+            inner,
+            rng,
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+            |_| Ok(()),
+        )
+        .await?
+        .0)
     }
 
-    /// Sends a fragmented WebSocket message.
-    pub async fn send_fragmented(
-        &mut self,
-        message: Message<'_>,
-        fragment_size: usize,
-    ) -> Result<(), Error<RW::Error>>
+    /// Creates a new [`WebSocket`] server and performs the handshake with a custom request handler.
+    ///
+    /// Returns the value of `on_request` and the [`HandshakeResult`], e.g. the negotiated subprotocol, alongside the connection.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    pub async fn accept_with<'p, const N: usize, F, T, E>(
+        options: AcceptOptions<'p, '_>,
+        inner: RW,
+        rng: Rng,
+        read_buffer: &'buf mut [u8],
+        write_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+        on_request: F,
+    ) -> Result<(Self, T, HandshakeResult<'p>), Error<RW::Error, E>>
     where
-        RW: Write,
-        Rng: RngCore,
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
     {
-        self.core.send_fragmented(message, fragment_size).await
+        check_buffers(
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+            options.require_fragments,
+        )?;
+
+        let protocols = options.protocols;
+
+        let (websocketz, custom) =
+            Self::server(inner, rng, read_buffer, write_buffer, fragments_buffer)
+                .server_handshake::<N, _, _, _>(options, on_request)
+                .await?;
+
+        let result = HandshakeResult::new(
+            protocols,
+            websocketz.protocol(),
+            websocketz.handshake_stats(),
+        );
+
+        Ok((websocketz, custom, result))
     }
 
-    /// Splits the [`WebSocket`] into a [`WebSocketRead`] and a [`WebSocketWrite`] with the provided `split` function.
-    ///
-    /// # Note
+    /// Creates a new [`WebSocket`] server and performs the handshake,
+    /// copying the path, the query, the offered extensions, the cookies and the additional headers of `capture` from the request,
+    /// as well as the negotiated subprotocol.
     ///
-    /// `auto_pong` and `auto_close` will `NOT` be applied to the split instances.
-    pub fn split_with<F, R, W>(
-        self,
-        split: F,
-    ) -> (WebSocketRead<'buf, R>, WebSocketWrite<'buf, W, Rng>)
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    pub async fn accept_full<'s, const N: usize>(
+        options: AcceptOptions<'_, '_>,
+        inner: RW,
+        rng: Rng,
+        read_buffer: &'buf mut [u8],
+        write_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+        capture: HandshakeCapture<'s>,
+    ) -> Result<(Self, HandshakeInfo<'s>), Error<RW::Error>>
     where
-        F: FnOnce(RW) -> (R, W),
+        RW: Read + Write,
     {
-        let (codec, inner, state) = self.core.framed.into_parts();
-        let (read_codec, write_codec) = codec.split();
-
-        let (read, write) = split(inner);
+        let protocols = options.protocols;
+        let capture = RefCell::new(capture);
 
-        let framed_read = Framed::from_parts(
-            read_codec,
-            read,
-            ReadWriteState::new(state.read, WriteState::empty()),
-        );
+        let (websocketz, (), _) = Self::accept_with::<N, _, _, _>(
+            options,
+            inner,
+            rng,
+            read_buffer,
+            write_buffer,
+            fragments_buffer,
+            |request| {
+                let mut capture = capture.borrow_mut();
 
-        let framed_write = Framed::from_parts(
-            write_codec,
-            write,
-            ReadWriteState::new(ReadState::empty(), state.write),
-        );
+                capture.target(request.path());
+                capture.headers(request.headers(), "cookie");
 
-        (
-            WebSocketRead::new_from_framed(framed_read, self.core.fragments_state),
-            WebSocketWrite::new_from_framed(framed_write),
+                Ok(())
+            },
         )
-    }
+        .await?;
 
-    #[doc(hidden)]
-    pub const fn auto(
-        &self,
-    ) -> impl FnOnce(Frame<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static {
-        self.core.auto()
-    }
+        let mut capture = capture.into_inner();
 
-    #[doc(hidden)]
-    pub const fn caller(&self) -> crate::functions::ReadAutoCaller {
-        crate::functions::ReadAutoCaller
+        if let Some(protocol) = websocketz.protocol().and_then(|index| protocols.get(index)) {
+            capture.protocol(protocol);
+        }
+
+        Ok((websocketz, capture.finish()))
     }
-}
 
-/// Read half of a WebSocket connection.
-#[derive(Debug)]
-pub struct WebSocketRead<'buf, RW> {
-    #[doc(hidden)]
-    pub core: WebSocketCore<'buf, RW, ()>,
-}
+    /// Performs the TLS handshake with `tls` over `stream`, then creates a new [`WebSocket`] server and performs the handshake over the TLS stream.
+    ///
+    /// `buffer` is split by `plan` into the record buffers passed to `tls` and the buffers of the [`WebSocket`].
+    ///
+    /// See the [`tls`](crate::tls) module.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub async fn accept_tls<const N: usize, S, F, T>(
+        options: AcceptOptions<'_, '_>,
+        stream: S,
+        tls: F,
+        rng: Rng,
+        buffer: &'buf mut [u8],
+        plan: TlsBufferPlan,
+    ) -> Result<Self, TlsError<RW::Error, T>>
+    where
+        F: AsyncFnOnce(S, TlsBuffers<'buf>) -> Result<RW, T>,
+        RW: Read + Write,
+    {
+        let (tls_buffers, buffers) = plan.split(buffer).ok_or(TlsError::BufferTooSmall)?;
+
+        let inner = tls(stream, tls_buffers).await.map_err(TlsError::Tls)?;
+
+        Ok(Self::accept::<N>(
+            options,
+            inner,
+            rng,
+            buffers.read,
+            buffers.write,
+            buffers.fragments,
+        )
+        .await?)
+    }
+
+    /// Creates a new [`WebSocket`] server with the buffers of `checkout` and performs the handshake.
+    ///
+    /// See [`BufferPool`](crate::buffers::BufferPool).
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    /// `SZ`: The size of the buffers of the pool.
+    #[cfg(feature = "buffer-pool")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "buffer-pool")))]
+    pub async fn accept_pooled<const N: usize, const SZ: usize>(
+        options: AcceptOptions<'_, '_>,
+        inner: RW,
+        rng: Rng,
+        checkout: &'buf mut Checkout<'_, SZ>,
+    ) -> Result<Self, Error<RW::Error>>
+    where
+        RW: Read + Write,
+    {
+        let Buffers {
+            read,
+            write,
+            fragments,
+        } = checkout.buffers();
+
+        Self::accept::<N>(options, inner, rng, read, write, fragments).await
+    }
+
+    /// Performs the handshake on a [`WebSocket`] created with [`WebSocket::server`], handing it back whether the handshake succeeds or not.
+    ///
+    /// HTTP/1.1 clients may send further requests on the same connection after a failed upgrade, e.g. a retry or a fallback poll request.
+    /// If the request was read but rejected ([`Error::Handshake`] other than [`HandshakeError::ConnectionClosed`](crate::error::HandshakeError::ConnectionClosed)),
+    /// it has been consumed from the read buffer, so the failed request can be answered with [`WebSocket::reject`]
+    /// and the next request can be read by calling this method again.
+    ///
+    /// After any other error the connection should be dropped.
+    ///
+    /// # Generic Parameters
+    /// `N`: The maximum number of headers to accept in the handshake request.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use embedded_io_async::{Read, Write};
+    /// # use websocketz::{WebSocket, error::Error, options::AcceptOptions, rng::RngCore};
+    /// # async fn accept<'buf, RW: Read + Write, Rng: RngCore>(
+    /// #     mut websocketz: WebSocket<'buf, RW, Rng>,
+    /// # ) -> Result<WebSocket<'buf, RW, Rng>, Error<RW::Error>> {
+    /// loop {
+    ///     let (mut back, result) = websocketz
+    ///         .try_accept::<16, _, _, _>(AcceptOptions::default(), |_| Ok(()))
+    ///         .await;
+    ///
+    ///     match result {
+    ///         Ok(()) => return Ok(back),
+    ///         Err(Error::Handshake(_)) => {
+    ///             back.reject("400", "Bad Request", &[]).await?;
+    ///
+    ///             websocketz = back;
+    ///         }
+    ///         Err(err) => return Err(err),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn try_accept<const N: usize, F, T, E>(
+        self,
+        options: AcceptOptions<'_, '_>,
+        on_request: F,
+    ) -> (Self, Result<T, Error<RW::Error, E>>)
+    where
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+    {
+        if let Err(err) = self.core.check_buffers(options.require_fragments) {
+            return (self, Err(Error::Config(err)));
+        }
+
+        let Self { core, info } = self;
+
+        let (core, result) = core
+            .server_handshake_recoverable::<N, _, _, _>(options, on_request)
+            .await;
+
+        match result {
+            Ok((custom, info)) => (Self { core, info }, Ok(custom)),
+            Err(err) => (Self { core, info }, Err(err)),
+        }
+    }
+
+    /// Answers a rejected handshake request with an HTTP response without a body, keeping the connection open.
+    ///
+    /// A `content-length: 0` header is always included. See [`WebSocket::try_accept`].
+    ///
+    /// `code` must be three digits and `reason` must not contain control characters other than tabs,
+    /// otherwise [`HttpEncodeError::InvalidStatusLine`](crate::error::HttpEncodeError::InvalidStatusLine) is returned.
+    /// The headers are checked with [`validate_header`](crate::http::validate_header).
+    pub async fn reject(
+        &mut self,
+        code: &str,
+        reason: &str,
+        headers: &[Header<'_>],
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        self.core.send_http_response(code, reason, headers).await
+    }
+
+    /// Sets whether to automatically send a Pong response.
+    #[inline]
+    pub const fn with_auto_pong(mut self, auto_pong: bool) -> Self {
+        self.core.set_auto_pong(auto_pong);
+        self
+    }
+
+    /// Sets whether to automatically send a Pong response, e.g. to answer pings manually for a while.
+    ///
+    /// Applies from the next frame read. See [`WebSocket::with_auto_pong`].
+    #[inline]
+    pub const fn set_auto_pong(&mut self, auto_pong: bool) {
+        self.core.set_auto_pong(auto_pong);
+    }
+
+    /// Returns whether a Pong response is sent automatically.
+    #[inline]
+    pub const fn auto_pong(&self) -> bool {
+        self.core.auto_pong()
+    }
+
+    /// Sets whether to automatically close the connection with [`CloseCode::Size`](crate::CloseCode::Size) (1009)
+    /// when a fragmented message does not fit into the fragments buffer.
+    ///
+    /// Only applies if `auto_close` is enabled. The Close frame is sent before [`MessageError::FragmentsBufferTooSmall`] is returned,
+    /// so that the peer learns why the connection is closed. Disabled by default.
+    #[inline]
+    pub const fn with_close_on_fragments_overflow(mut self, close: bool) -> Self {
+        self.core.set_close_on_fragments_overflow(close);
+        self
+    }
+
+    /// Sets the maximum number of ping payload bytes echoed in an automatic Pong response.
+    ///
+    /// Longer payloads are truncated, so that peers sending large pings can still be answered with a small write buffer.
+    ///
+    /// # Note
+    ///
+    /// The specification requires a Pong to carry the exact payload of the Ping it answers,
+    /// peers checking it may consider a truncated Pong as a protocol violation. Disabled by default.
+    #[inline]
+    pub const fn with_pong_payload_limit(mut self, limit: u8) -> Self {
+        self.core.set_pong_payload_limit(Some(limit));
+        self
+    }
+
+    /// Sets whether to automatically close the connection on receiving a Close frame.
+    #[inline]
+    pub const fn with_auto_close(mut self, auto_close: bool) -> Self {
+        self.core.set_auto_close(auto_close);
+        self
+    }
+
+    /// Sets whether to automatically close the connection on receiving a Close frame.
+    ///
+    /// Applies from the next frame read. See [`WebSocket::with_auto_close`].
+    #[inline]
+    pub const fn set_auto_close(&mut self, auto_close: bool) {
+        self.core.set_auto_close(auto_close);
+    }
+
+    /// Returns whether the connection is closed automatically on receiving a Close frame.
+    #[inline]
+    pub const fn auto_close(&self) -> bool {
+        self.core.auto_close()
+    }
+
+    /// Sets the size limits for received messages.
+    ///
+    /// If `auto_close` is enabled, a message exceeding the size limits is answered with a [`CloseCode::Size`](crate::CloseCode::Size) close frame
+    /// and a message exceeding the maximum number of empty fragments with a [`CloseCode::Policy`](crate::CloseCode::Policy) close frame.
+    #[inline]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.core.set_limits(limits);
+        self
+    }
+
+    /// Sets the maximum payload size of a single received data frame, see [`Limits::with_max_frame`].
+    ///
+    /// Frames exceeding it are rejected with [`MessageError::MessageTooLarge`] as soon as their header is read, before their payload is buffered,
+    /// and, if `auto_close` is enabled, answered with a [`CloseCode::Size`](crate::CloseCode::Size) close frame.
+    /// Other limits set with [`WebSocket::with_limits`] are kept.
+    ///
+    /// # Note
+    ///
+    /// The rest of a rejected frame is never read, so every following read fails with the same error.
+    #[inline]
+    pub const fn with_max_frame_size(mut self, max: usize) -> Self {
+        self.core.set_max_frame_size(max);
+        self
+    }
+
+    /// Sets the maximum payload size of a received text or binary message, see [`Limits::with_max_message`].
+    ///
+    /// Messages exceeding it are rejected with [`MessageError::MessageTooLarge`] before they overflow the fragments buffer
+    /// and, if `auto_close` is enabled, answered with a [`CloseCode::Size`](crate::CloseCode::Size) close frame.
+    /// Other limits set with [`WebSocket::with_limits`] are kept.
+    #[inline]
+    pub const fn with_max_message_size(mut self, max: usize) -> Self {
+        self.core.set_max_message_size(max);
+        self
+    }
+
+    /// Applies the settings of the given [`Profile`].
+    ///
+    /// Replaces the limits, `auto_pong`, `auto_close` and the maximum number of unanswered pings.
+    #[inline]
+    pub const fn with_profile(mut self, profile: Profile) -> Self {
+        self.core.set_limits(profile.limits());
+        self.core.set_auto_pong(profile.auto_pong());
+        self.core.set_auto_close(profile.auto_close());
+        self.core
+            .set_max_unanswered_pings(profile.max_unanswered_pings());
+        self.core.set_close_wait_frames(profile.close_wait_frames());
+        self
+    }
+
+    /// Applies the settings of the given [`Config`].
+    ///
+    /// Replaces the limits, the auto handling, keepalive and text validation settings.
+    #[inline]
+    pub const fn with_config(mut self, config: Config) -> Self {
+        self.core.set_limits(config.limits());
+        self.core.set_auto_pong(config.auto_pong);
+        self.core.set_auto_close(config.auto_close);
+        self.core.set_pong_payload_limit(config.pong_payload_limit);
+        self.core
+            .set_close_on_fragments_overflow(config.close_on_fragments_overflow);
+        self.core
+            .set_max_unanswered_pings(config.max_unanswered_pings);
+        self.core.set_close_wait_frames(config.close_wait_frames);
+        self.core.set_text_validation(config.text_validation);
+        self.core.set_keepalive(config.keepalive);
+        self
+    }
+
+    /// Sets the maximum number of pings that may be sent without receiving a pong.
+    ///
+    /// Once reached, sending another ping returns [`Error::PeerUnresponsive`].
+    /// Receiving any pong resets the count.
+    ///
+    /// # Note
+    ///
+    /// The count is `NOT` shared between split instances. See [`WebSocket::split_with`].
+    #[inline]
+    pub const fn with_max_unanswered_pings(mut self, max: usize) -> Self {
+        self.core.set_max_unanswered_pings(Some(max));
+        self
+    }
+
+    /// Returns the statistics of the handshake that established the connection.
+    ///
+    /// All zero for connections created without a handshake, e.g. with [`WebSocket::client`].
+    #[inline]
+    pub const fn handshake_stats(&self) -> HandshakeStats {
+        self.core.handshake_stats()
+    }
+
+    /// Returns the index of the negotiated subprotocol in the protocols of the handshake options.
+    ///
+    /// `None` if no subprotocol was negotiated.
+    /// See [`ConnectOptions::with_protocols`] and [`AcceptOptions::with_protocols`].
+    #[inline]
+    pub const fn protocol(&self) -> Option<usize> {
+        self.info.protocol()
+    }
+
+    /// Returns the extensions accepted in the handshake.
+    ///
+    /// Indexed by their position in the supported extensions of the handshake options.
+    /// Always empty for clients. See [`AcceptOptions::with_extensions`].
+    #[inline]
+    pub const fn extensions(&self) -> AcceptedExtensions {
+        self.info.extensions()
+    }
+
+    /// Returns whether the `permessage-deflate` extension was negotiated in the handshake.
+    ///
+    /// See the [`deflate`](crate::deflate) module.
+    #[inline]
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn permessage_deflate(&self) -> bool {
+        self.core.codec.deflate()
+    }
+
+    /// Applies the parameters of the extensions implemented by the application to the decoding of received frames,
+    /// e.g. after checking the [`WebSocket::extensions`] accepted in the handshake.
+    ///
+    /// Without a [`CodecConfig`], frames with reserved bits not used by `permessage-deflate` are rejected.
+    /// Preserved by [`WebSocket::split_with`].
+    ///
+    /// ```
+    /// # async fn vendor() {
+    /// # use websocketz::mock::Noop;
+    /// use websocketz::{
+    ///     WebSocket,
+    ///     extensions::{CodecConfig, SupportedExtension},
+    ///     next,
+    ///     options::AcceptOptions,
+    /// };
+    ///
+    /// const SUPPORTED: &[SupportedExtension<'static>] = &[SupportedExtension::new("x-vendor")];
+    ///
+    /// # let (read_buffer, write_buffer, fragments_buffer) = (&mut [0u8; 1024], &mut [0u8; 1024], &mut [0u8; 1024]);
+    /// let websocketz = WebSocket::accept::<16>(
+    ///     AcceptOptions::default().with_extensions(SUPPORTED),
+    ///     Noop,
+    ///     Noop,
+    ///     read_buffer,
+    ///     write_buffer,
+    ///     fragments_buffer,
+    /// )
+    /// .await
+    /// .expect("Handshake failed");
+    ///
+    /// // `x-vendor` marks frames with RSV2.
+    /// let config = match websocketz.extensions().contains(0) {
+    ///     true => CodecConfig::new().with_reserved_bits(CodecConfig::RSV2),
+    ///     false => CodecConfig::new(),
+    /// };
+    ///
+    /// let mut websocketz = websocketz.with_codec_config(config);
+    ///
+    /// loop {
+    ///     match next!(websocketz) {
+    ///         Some(Ok(_)) => {}
+    ///         _ => break,
+    ///     }
+    ///
+    ///     // Whether the last frame of the message was marked.
+    ///     let marked = websocketz
+    ///         .last_frame()
+    ///         .is_some_and(|meta| meta.reserved_bits() & CodecConfig::RSV2 != 0);
+    /// #   let _ = marked;
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn with_codec_config(mut self, config: CodecConfig) -> Self {
+        self.core.codec.set_config(config);
+        self
+    }
+
+    /// Changes the parameters applied to the decoding of received frames without consuming the [`WebSocket`].
+    ///
+    /// Takes effect from the next frame decoded. See [`WebSocket::with_codec_config`].
+    #[inline]
+    pub const fn set_codec_config(&mut self, config: CodecConfig) {
+        self.core.codec.set_config(config);
+    }
+
+    /// Returns the parameters applied to the decoding of received frames.
+    #[inline]
+    pub const fn codec_config(&self) -> CodecConfig {
+        self.core.codec.config()
+    }
+
+    /// Returns information about the connection collected in the handshake.
+    ///
+    /// Servers store the path and the `Origin` header of the request, clients the path of their request.
+    /// Both store the negotiated subprotocol and the accepted extensions.
+    #[inline]
+    pub const fn info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+
+    /// Sets the maximum number of frames to read after sending a Close frame while waiting for the peer's Close frame.
+    ///
+    /// Once exceeded, reading returns `None` as if the connection was closed, so that a peer never acknowledging the Close frame
+    /// can not keep the read loop alive. The peer's Close frame itself is not counted. Unlimited by default.
+    /// Pings received in the meantime are counted and returned without being answered.
+    ///
+    /// # Note
+    ///
+    /// The limit is `NOT` applied by split instances, the read half is not aware of Close frames sent by the write half.
+    /// See [`WebSocket::split_with`].
+    #[inline]
+    pub const fn with_close_wait_frames(mut self, max: usize) -> Self {
+        self.core.set_close_wait_frames(Some(max));
+        self
+    }
+
+    /// Returns the number of pings sent since the last received pong.
+    #[inline]
+    pub const fn unanswered_pings(&self) -> usize {
+        self.core.unanswered_pings()
+    }
+
+    /// Queues a Ping frame to be sent by the next read, e.g. by [`next!`](crate::next).
+    ///
+    /// Lets applications reading in a single task originate pings without sending them in between reads.
+    /// Up to 4 pings with a payload of at most 8 bytes can be queued. Queued pings are sent in order before reading the next frame,
+    /// and are discarded once the connection is closed. A read cancelled while sending a queued ping may drop it.
+    ///
+    /// Not supported by split instances, see [`WebSocket::split_with`].
+    ///
+    /// # Errors
+    /// - [`QueuePingError::Full`]: The queue already holds 4 pings.
+    /// - [`QueuePingError::PayloadTooLong`]: `payload` is longer than 8 bytes.
+    /// - [`QueuePingError::PeerUnresponsive`]: The unanswered and queued pings already reach the limit set with [`WebSocket::with_max_unanswered_pings`].
+    /// - [`QueuePingError::ConnectionClosed`]: The connection is closed.
+    #[inline]
+    pub fn queue_ping(&mut self, payload: &[u8]) -> Result<(), QueuePingError> {
+        self.core.queue_ping(payload)
+    }
+
+    /// Returns the number of pings queued with [`WebSocket::queue_ping`] and not sent yet.
+    #[inline]
+    pub const fn queued_pings(&self) -> usize {
+        self.core.queued_pings()
+    }
+
+    /// Enables keepalive pings sent by [`WebSocket::tick`].
+    ///
+    /// # Note
+    ///
+    /// The keepalive is `NOT` shared between split instances. See [`WebSocket::split_with`].
+    #[inline]
+    pub const fn with_keepalive(mut self, keepalive: Keepalive) -> Self {
+        self.core.set_keepalive(Some(keepalive));
+        self
+    }
+
+    /// Enables or disables keepalive pings at runtime, restarting the keepalive timers.
+    #[inline]
+    pub const fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.core.set_keepalive(keepalive);
+    }
+
+    /// Returns the keepalive, `None` if disabled.
+    #[inline]
+    pub const fn keepalive(&self) -> Option<Keepalive> {
+        self.core.keepalive()
+    }
+
+    /// Drives the keepalive set with [`WebSocket::with_keepalive`], reading the current time from `clock`.
+    ///
+    /// Sends a `Ping` if no frame has been received for [`Keepalive::interval`] since the last received frame or keepalive ping,
+    /// as observed by the calls to this method. Frames and pongs are received while reading,
+    /// so this method should be called regularly in between reads, e.g. whenever a read times out.
+    ///
+    /// Does nothing if the keepalive is disabled or the connection is closed.
+    ///
+    /// # Errors
+    /// - [`Error::PingTimeout`]: No `Pong` was received within [`Keepalive::timeout`] after the last keepalive `Ping`.
+    /// - Any error of [`WebSocket::send`] while sending the `Ping`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn sleep(_: core::time::Duration) {}
+    /// use core::{pin::pin, time::Duration};
+    ///
+    /// use embedded_io_async::{Read, Write};
+    /// use futures::future::{Either, select};
+    /// use websocketz::{WebSocket, clock::Clock, error::Error, next, options::Keepalive, rng::RngCore};
+    ///
+    /// async fn run<RW: Read + Write, Rng: RngCore>(
+    ///     websocketz: WebSocket<'_, RW, Rng>,
+    ///     clock: &dyn Clock,
+    /// ) -> Result<(), Error<RW::Error>> {
+    ///     let mut websocketz = websocketz
+    ///         .with_keepalive(Keepalive::new(Duration::from_secs(30), Duration::from_secs(10)));
+    ///
+    ///     loop {
+    ///         {
+    ///             let read = pin!(async { next!(websocketz).map(|message| message.map(|m| m.is_close())) });
+    ///             let timeout = pin!(sleep(Duration::from_secs(1)));
+    ///
+    ///             match select(read, timeout).await {
+    ///                 Either::Left((None | Some(Ok(true)), _)) => return Ok(()),
+    ///                 Either::Left((Some(Err(err)), _)) => return Err(err.into()),
+    ///                 _ => {}
+    ///             }
+    ///         }
+    ///
+    ///         websocketz.tick(clock).await?;
+    ///     }
+    /// }
+    /// ```
+    pub async fn tick(&mut self, clock: &dyn Clock) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.tick(clock).await
+    }
+
+    /// Returns the metadata of the last frame received, including control frames and frames of rejected messages.
+    ///
+    /// `None` if no frame was received yet. Frames are numbered from `1`, the number of the offending frame is also reported
+    /// by [`MessageError::Protocol`], e.g. to correlate errors with packet captures. Peeked frames are not counted.
+    #[inline]
+    pub const fn last_frame(&self) -> Option<FrameMeta> {
+        self.core.last_frame()
+    }
+
+    /// Pauses reading.
+    ///
+    /// While paused, reading returns [`MessageError::ReadsPaused`] without reading from the underlying I/O,
+    /// so that an application draining messages into a slow sink (e.g. flash) can stop pulling frames
+    /// and let the transport's flow control (e.g. the TCP receive window) push back on the peer.
+    /// `Ping` and `Close` messages are not answered while paused.
+    ///
+    /// [`MessageError::ReadsPaused`] is not fatal: the connection is left untouched and reading continues
+    /// where it stopped after [`WebSocket::resume_reads`].
+    #[inline]
+    pub const fn pause_reads(&mut self) {
+        self.core.set_reads_paused(true);
+    }
+
+    /// Resumes reading paused with [`WebSocket::pause_reads`].
+    #[inline]
+    pub const fn resume_reads(&mut self) {
+        self.core.set_reads_paused(false);
+    }
+
+    /// Returns whether reading is paused.
+    #[inline]
+    pub const fn reads_paused(&self) -> bool {
+        self.core.reads_paused()
+    }
+
+    /// Sets the minimum number of new bytes to read from the underlying I/O before decoding frames.
+    ///
+    /// Instead of decoding after every read, reading continues until at least `min` new bytes are buffered,
+    /// the read buffer is full or the end of the stream is reached.
+    /// This lets low-power devices (e.g. radios) stay asleep between receive windows instead of waking up for every few bytes.
+    /// Bytes already buffered are always decoded first, so complete frames are never held back. `0` by default.
+    ///
+    /// # Note
+    ///
+    /// A frame shorter than `min` is only returned once more bytes arrive.
+    /// Reading can be cancelled without losing bytes, e.g. by a deadline of the application,
+    /// frames completed by the bytes read so far are then returned by the next read.
+    /// See also [`WebSocket::set_min_read_chunk`].
+    #[inline]
+    pub const fn with_min_read_chunk(mut self, min: usize) -> Self {
+        self.core.set_min_read_chunk(min);
+        self
+    }
+
+    /// Sets the minimum number of new bytes to read before decoding frames, e.g. to `0` to flush a low-power mode.
+    ///
+    /// See [`WebSocket::with_min_read_chunk`].
+    #[inline]
+    pub const fn set_min_read_chunk(&mut self, min: usize) {
+        self.core.set_min_read_chunk(min);
+    }
+
+    /// Returns the minimum number of new bytes to read before decoding frames.
+    #[inline]
+    pub const fn min_read_chunk(&self) -> usize {
+        self.core.min_read_chunk()
+    }
+
+    /// Sets a filter invoked on the first frame of every received data message.
+    ///
+    /// Messages rejected by the filter are skipped.
+    /// Continuation frames of rejected fragmented messages are discarded without being reassembled.
+    #[inline]
+    pub const fn with_message_filter(mut self, filter: MessageFilter) -> Self {
+        self.core.set_message_filter(Some(filter));
+        self
+    }
+
+    /// Sets a verifier invoked on the payload of every received binary message.
+    ///
+    /// For fragmented messages, the verifier is invoked once on the reassembled payload.
+    /// Messages failing the check are not returned and surface as [`MessageError::IntegrityCheckFailed`](crate::error::MessageError::IntegrityCheckFailed).
+    #[inline]
+    pub const fn with_integrity_verifier(mut self, verifier: &'buf dyn IntegrityVerifier) -> Self {
+        self.core.set_integrity_verifier(Some(verifier));
+        self
+    }
+
+    /// Sets an observer invoked with the [`Progress`](crate::Progress) of every received fragmented message,
+    /// after each of its fragments is appended to the fragments buffer.
+    ///
+    /// The observer sees the number of bytes received so far, not the payload,
+    /// e.g. to display the progress of an OTA update while the message is reassembled as usual.
+    #[inline]
+    pub const fn with_progress_observer(mut self, observer: &'buf dyn ProgressObserver) -> Self {
+        self.core.set_progress_observer(Some(observer));
+        self
+    }
+
+    /// Sets the decompressor and the buffer for received messages compressed with `permessage-deflate`.
+    ///
+    /// Compressed messages are decompressed into `buffer` once reassembled, size limits, the text validation
+    /// and the integrity verifier apply to the decompressed payload. Messages that are corrupted or do not fit
+    /// surface as [`MessageError::DecompressionFailed`](crate::error::MessageError::DecompressionFailed),
+    /// as do all compressed messages if no buffer is set. See the [`deflate`](crate::deflate) module.
+    #[inline]
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn with_inflater(
+        mut self,
+        inflater: &'buf mut Inflater,
+        buffer: &'buf mut [u8],
+    ) -> Self {
+        self.core.set_inflate(inflater, buffer);
+        self
+    }
+
+    /// Sets whether the payload of received text messages is validated as UTF-8.
+    ///
+    /// Enabled by default. If disabled, text messages are returned as [`Message::TextUnchecked`] without validation,
+    /// saving a pass over the payload when a later layer (e.g. a JSON deserializer) validates it anyway.
+    /// The message filter still sees such messages as text.
+    #[inline]
+    pub const fn with_text_validation(mut self, validate_text: bool) -> Self {
+        self.core.set_text_validation(validate_text);
+        self
+    }
+
+    /// Returns reference to the reader/writer.
+    #[inline]
+    pub const fn inner(&self) -> &RW {
+        self.core.inner()
+    }
+
+    /// Returns mutable reference to the reader/writer.
+    #[inline]
+    pub const fn inner_mut(&mut self) -> &mut RW {
+        self.core.inner_mut()
+    }
+
+    /// Gives scoped async access to the reader/writer, e.g. to send a TCP keepalive or an out-of-band command.
+    ///
+    /// Bytes already read stay in the read buffer, and `f` only runs between two written frames:
+    /// if a frame was only partly written, because the future sending it was dropped or the write failed,
+    /// `f` is not called and [`WriteError::PartialFrame`](crate::error::WriteError::PartialFrame) is returned.
+    /// Bytes written or read in `f` are not seen by the [`WebSocket`], and must therefore not interfere with the WebSocket stream.
+    pub async fn with_inner_async<F, T>(&mut self, f: F) -> Result<T, Error<RW::Error>>
+    where
+        F: AsyncFnOnce(&mut RW) -> T,
+        RW: Write,
+    {
+        if self.core.state.mid_frame() {
+            return Err(Error::Write(WriteError::PartialFrame));
+        }
+
+        Ok(f(self.core.inner_mut()).await)
+    }
+
+    /// Consumes the [`WebSocket`] and returns the reader/writer.
+    #[inline]
+    pub fn into_inner(self) -> RW {
+        self.core.into_inner()
+    }
+
+    /// Returns the number of bytes that can be framed.
+    #[inline]
+    pub const fn framable(&self) -> usize {
+        self.core.framable()
+    }
+
+    #[cfg(feature = "client")]
+    async fn client_handshake<const N: usize, F, T, E>(
+        self,
+        options: ConnectOptions<'_, '_>,
+        on_response: F,
+    ) -> Result<(Self, T), Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let (core, custom, info) = self
+            .core
+            .client_handshake::<N, _, _, _>(options, on_response)
+            .await?;
+
+        Ok((Self { core, info }, custom))
+    }
+
+    async fn server_handshake<const N: usize, F, T, E>(
+        self,
+        options: AcceptOptions<'_, '_>,
+        on_request: F,
+    ) -> Result<(Self, T), Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+    {
+        let (core, custom, info) = self
+            .core
+            .server_handshake::<N, _, _, _>(options, on_request)
+            .await?;
+
+        Ok((Self { core, info }, custom))
+    }
+
+    /// Sends a WebSocket message.
+    ///
+    /// The writer is flushed after every frame, so that buffering transports, e.g. TLS adapters, transmit it right away.
+    /// See [`WebSocket::send_without_flush`] to batch several messages.
+    pub async fn send(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send(message).await
+    }
+
+    /// Sends a WebSocket message without flushing the writer.
+    ///
+    /// Lets several messages be written before a single [`WebSocket::flush`], e.g. to fill a TLS record or a UART buffer.
+    /// Messages may not be transmitted until the writer is flushed, depending on the transport.
+    pub async fn send_without_flush(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_without_flush(message).await
+    }
+
+    /// Flushes the writer, transmitting the messages sent with [`WebSocket::send_without_flush`].
+    pub async fn flush(&mut self) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        self.core.flush().await
+    }
+
+    /// Sends a Close frame, if none has been sent yet, and flushes the writer.
+    ///
+    /// Repeated calls, or calls after a Close frame has already been sent (including automatic Close replies), are no-ops.
+    pub async fn send_close_and_flush(
+        &mut self,
+        frame: Option<CloseFrame<'_>>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_close_and_flush(frame).await
+    }
+
+    /// Closes the connection gracefully.
+    ///
+    /// Sends a Close frame, if none has been sent yet, then reads until the peer's Close frame or EOF,
+    /// discarding data messages and pings, which are not answered anymore, as required by RFC 6455 before closing the underlying connection.
+    /// The wait is bounded by [`WebSocket::with_close_wait_frames`], if set.
+    ///
+    /// Split instances can not complete the closing handshake with this method.
+    /// Send a Close frame with [`WebSocketWrite::send_close_and_flush`] and read the reply with the [`WebSocketRead`] instead.
+    ///
+    /// # Errors
+    /// - [`Error::Write`]: Sending the Close frame failed.
+    /// - [`Error::Message`]: Reading failed before the peer's Close frame was received.
+    pub async fn close(&mut self, frame: Option<CloseFrame<'_>>) -> Result<(), Error<RW::Error>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        self.core.send_close_and_flush(frame).await?;
+
+        loop {
+            match self.maybe_next().await {
+                Some(Ok(Some(Message::Close(_)))) | None => return Ok(()),
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(Error::Message(err)),
+            }
+        }
+    }
+
+    /// Compresses `payload` with `codec` and sends it as a binary message, prefixed with a 1-byte header.
+    ///
+    /// The payload is compressed directly into the write buffer, which must hold the frame header, the 1-byte header and the compressed payload,
+    /// or the uncompressed payload if compressing it does not make it smaller. See the [`compression`](crate::compression) module.
+    pub async fn send_compressed_binary<C>(
+        &mut self,
+        payload: &[u8],
+        codec: &C,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        C: PayloadCodec,
+    {
+        self.core.send_compressed_binary(payload, codec).await
+    }
+
+    /// Sends a binary message of `len` bytes, written by `write` directly into the write buffer.
+    ///
+    /// Lets callers assemble a payload from several parts, e.g. a header and a body, without another buffer.
+    /// The write buffer must be at least `14 + len` bytes long, 14 bytes being reserved for the largest possible frame header
+    /// regardless of `len`. Otherwise `write` is not called and [`FrameEncodeError::BufferTooSmall`] is returned.
+    pub async fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        F: FnOnce(&mut [u8]),
+    {
+        self.core.send_binary_with(len, write).await
+    }
+
+    /// Sends a [`PreEncodedMessage`] by writing its bytes and flushing the writer.
+    ///
+    /// The message must be masked if this is a client and unmasked if this is a server,
+    /// otherwise [`WriteError::PreEncodedMaskMismatch`](crate::error::WriteError::PreEncodedMaskMismatch) is returned.
+    pub async fn send_pre_encoded(
+        &mut self,
+        message: &PreEncodedMessage<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_pre_encoded(message).await
+    }
+
+    /// Encodes `message` as a single frame into `dst` without sending it, returning the number of bytes written.
+    ///
+    /// The internal write buffer is not used, so platforms handing DMA-capable buffers to their driver can encode into them directly.
+    /// Frames of clients are masked as by [`WebSocket::send`].
+    ///
+    /// # Note
+    ///
+    /// The frame must be written to the underlying I/O as a whole by the caller, between complete frames sent by this [`WebSocket`].
+    /// The state of the connection is `NOT` updated, e.g. an encoded Close frame does not close the connection.
+    pub fn encode_message_into(
+        &mut self,
+        message: Message<'_>,
+        dst: &mut [u8],
+    ) -> Result<usize, FrameEncodeError>
+    where
+        Rng: RngCore,
+    {
+        self.core.encode_message_into(message, dst)
+    }
+
+    /// Sends a [`RawFrame`](crate::danger::RawFrame) as-is, without any protocol checks.
+    ///
+    /// Fails if the connection is closed, but the frame itself does not update the state of the connection,
+    /// e.g. a raw close frame does not close it.
+    #[cfg(feature = "danger-frames")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "danger-frames")))]
+    pub async fn send_raw(
+        &mut self,
+        frame: crate::danger::RawFrame<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_raw(frame).await
+    }
+
+    /// Sends a data frame whose `payload` was already masked with `mask`, e.g. by a hardware offload engine.
+    ///
+    /// The header is encoded with `mask` and the payload is written as-is, without drawing a key from the random number generator.
+    /// Only clients mask their frames, servers get [`WriteError::PreEncodedMaskMismatch`](crate::error::WriteError::PreEncodedMaskMismatch).
+    ///
+    /// # Note
+    ///
+    /// The key must be unpredictable to the network, as required from every client (RFC 6455, section 10.3).
+    /// Frames of a fragmented message must not be interleaved with other data messages.
+    /// See the [`pre_masked`](crate::pre_masked) module.
+    #[cfg(feature = "pre-masked")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pre-masked")))]
+    pub async fn send_pre_masked(
+        &mut self,
+        header: crate::pre_masked::FrameHeader,
+        mask: [u8; 4],
+        payload: &[u8],
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_pre_masked(header, mask, payload).await
+    }
+
+    /// Sends a fragmented WebSocket message.
+    pub async fn send_fragmented(
+        &mut self,
+        message: Message<'_>,
+        fragment_size: usize,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_fragmented(message, fragment_size).await
+    }
+
+    /// Starts sending a text message incrementally, chunk by chunk.
+    ///
+    /// Nothing is sent until the first chunk is written. See [`MessageWriter`].
+    #[inline]
+    pub const fn send_text_stream(&mut self) -> MessageWriter<'_, 'buf, RW, Rng> {
+        self.core.message_writer(OpCode::Text, None)
+    }
+
+    /// Starts sending a binary message incrementally, chunk by chunk.
+    ///
+    /// Nothing is sent until the first chunk is written. See [`MessageWriter`].
+    #[inline]
+    pub const fn send_binary_stream(&mut self) -> MessageWriter<'_, 'buf, RW, Rng> {
+        self.core.message_writer(OpCode::Binary, None)
+    }
+
+    /// Reads the next [`Message`] and passes it to `f`.
+    ///
+    /// A function alternative to the [`next!`](crate::next) macro with the same semantics, including the automatic handling of `Ping` and `Close` messages.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    ///   EOF in the middle of a frame is an error, see [`FrameDecodeError::UnexpectedEof`](crate::error::FrameDecodeError::UnexpectedEof).
+    pub async fn with_next<F, T>(&mut self, f: F) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        crate::next!(self).map(|result| result.map(f))
+    }
+
+    /// Reads the next [`StreamItem`], passing the frames of fragmented messages through, and passes it to `f`.
+    ///
+    /// A function alternative to the [`next_fragment!`](crate::next_fragment) macro with the same semantics.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message or a fragment was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    pub async fn with_next_fragment<F, T>(
+        &mut self,
+        f: F,
+    ) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(StreamItem<'_>) -> T,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        crate::next_fragment!(self).map(|result| result.map(f))
+    }
+
+    /// Reads and handles at most one frame, returning the [`Message`] it completes, if any.
+    ///
+    /// A single iteration of the [`next!`](crate::next) macro, for building read loops and combinators on top of a [`WebSocket`].
+    /// `Ping` and `Close` messages are handled automatically as with [`next!`](crate::next).
+    ///
+    /// # Return
+    /// - `Some(Ok(Some(Message)))`: A message was successfully read.
+    /// - `Some(Ok(None))`: No complete message was read yet, e.g. after a control frame handled internally,
+    ///   a non-final fragment or a partial read. The caller should read again.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run<Rng: websocketz::rng::RngCore>(mut websocketz: websocketz::WebSocket<'_, websocketz::mock::Noop, Rng>) {
+    /// use websocketz::Message;
+    ///
+    /// loop {
+    ///     match websocketz.maybe_next().await {
+    ///         Some(Ok(Some(Message::Text(text)))) => println!("{text}"),
+    ///         Some(Ok(_)) => continue,
+    ///         Some(Err(_)) | None => break,
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn maybe_next(
+        &mut self,
+    ) -> Option<Result<Option<Message<'_>>, MessageError<RW::Error>>>
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        self.caller()
+            .call(
+                self.auto(),
+                &mut self.core.codec,
+                &mut self.core.inner,
+                &mut self.core.read,
+                &mut self.core.write,
+                &mut self.core.fragments_state,
+                &mut self.core.state,
+            )
+            .await
+    }
+
+    /// Reads the next [`Message`], reassembling a fragmented message into `buffer` instead of the fragments buffer, and passes it to `f`.
+    ///
+    /// Lets every message be reassembled into its own sink, e.g. a flash-page-aligned buffer, without copying it out of the fragments buffer.
+    /// Single frame messages are passed from the read buffer, as with [`WebSocket::with_next`].
+    /// A message partially reassembled into the fragments buffer by a cancelled read is continued in `buffer`.
+    ///
+    /// # Note
+    ///
+    /// Cancelling this method in the middle of a fragmented message discards the message, its remaining fragments are skipped by the next read.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::FragmentsBufferTooSmall))`: The message does not fit into `buffer`.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    pub async fn with_next_into<F, T>(
+        &mut self,
+        buffer: &mut [u8],
+        f: F,
+    ) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let Some(mut fragments_state) = self.core.fragments_state.with_buffer(buffer) else {
+            return Some(Err(MessageError::FragmentsBufferTooSmall));
+        };
+
+        loop {
+            let result = self
+                .caller()
+                .call(
+                    self.auto(),
+                    &mut self.core.codec,
+                    &mut self.core.inner,
+                    &mut self.core.read,
+                    &mut self.core.write,
+                    &mut fragments_state,
+                    &mut self.core.state,
+                )
+                .await;
+
+            let result = match result {
+                Some(Ok(None)) => {
+                    self.core.fragments_state.track(&fragments_state);
+
+                    continue;
+                }
+                Some(Ok(Some(message))) => Some(Ok(f(message))),
+                Some(Err(err)) => Some(Err(err)),
+                None => None,
+            };
+
+            self.core.fragments_state.track(&fragments_state);
+
+            return result;
+        }
+    }
+
+    /// Reads the next [`Message`] and passes it to `f` without consuming it.
+    ///
+    /// The message is returned again by the next read, e.g. to route a connection by the first bytes of a message
+    /// before handing it to the component consuming it.
+    /// `Ping` and `Close` messages are not answered until they are read, and message filters and integrity verifiers are not applied.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::FragmentedPeek))`: The next message is fragmented, only single frame messages can be peeked.
+    ///   The message can still be read.
+    /// - `Some(Err(MessageError::DecompressionFailed))`: The next message is compressed with `permessage-deflate`, peeking does not decompress.
+    ///   The message can still be read.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    ///   EOF in the middle of a frame is an error, see [`FrameDecodeError::UnexpectedEof`](crate::error::FrameDecodeError::UnexpectedEof).
+    pub async fn peek_message<F, T>(&mut self, f: F) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read,
+    {
+        self.core.peek_message(f).await
+    }
+
+    /// Splits the [`WebSocket`] into a [`WebSocketRead`] and a [`WebSocketWrite`] with the provided `split` function.
+    ///
+    /// # Note
+    ///
+    /// `auto_pong`, `auto_close` and the maximum number of unanswered pings will `NOT` be applied to the split instances,
+    /// unless a [`WebSocketWrite`] is attached to the [`WebSocketRead`] with [`WebSocketRead::attach_writer`],
+    /// in which case `auto_pong` and `auto_close` are applied as configured on this [`WebSocket`].
+    pub fn split_with<F, R, W>(
+        self,
+        split: F,
+    ) -> (WebSocketRead<'buf, R>, WebSocketWrite<'buf, W, Rng>)
+    where
+        F: FnOnce(RW) -> (R, W),
+    {
+        let Core {
+            codec,
+            inner,
+            read,
+            write,
+            fragments_state,
+            state,
+        } = self.core;
+
+        let (read_codec, write_codec) = codec.split();
+
+        let (read_inner, write_inner) = split(inner);
+
+        let mut read_core = Core::new(read_codec, read_inner, read, (), fragments_state);
+
+        // Only used by the read half once a writer is attached.
+        read_core.state = state;
+
+        (
+            WebSocketRead {
+                core: read_core,
+                close_signal: None,
+            },
+            WebSocketWrite {
+                core: Core::new(write_codec, write_inner, None, write, ()),
+            },
+        )
+    }
+
+    #[doc(hidden)]
+    pub const fn auto(
+        &self,
+    ) -> impl FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static {
+        self.core.auto()
+    }
+
+    #[doc(hidden)]
+    pub const fn caller(&self) -> crate::functions::ReadAutoCaller {
+        crate::functions::ReadAutoCaller
+    }
+}
+
+/// Read half of a WebSocket connection.
+#[derive(Debug)]
+pub struct WebSocketRead<'buf, RW> {
+    #[doc(hidden)]
+    pub core: ReadCore<'buf, RW>,
+    close_signal: Option<&'buf CloseSignal>,
+}
+
+impl<'buf, RW> WebSocketRead<'buf, RW> {
+    /// Creates a new [`WebSocketRead`] client after a successful handshake.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    pub const fn client(
+        inner: RW,
+        read_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+    ) -> Self {
+        Self {
+            core: Core::client(
+                inner,
+                (),
+                ReadState::new(read_buffer),
+                (),
+                FragmentsState::new(fragments_buffer),
+            ),
+            close_signal: None,
+        }
+    }
+
+    /// Creates a new [`WebSocketRead`] server after a successful handshake.
+    pub const fn server(
+        inner: RW,
+        read_buffer: &'buf mut [u8],
+        fragments_buffer: &'buf mut [u8],
+    ) -> Self {
+        Self {
+            core: Core::server(
+                inner,
+                (),
+                ReadState::new(read_buffer),
+                (),
+                FragmentsState::new(fragments_buffer),
+            ),
+            close_signal: None,
+        }
+    }
+
+    /// Returns the metadata of the last frame received.
+    ///
+    /// See [`WebSocket::last_frame`].
+    #[inline]
+    pub const fn last_frame(&self) -> Option<FrameMeta> {
+        self.core.last_frame()
+    }
+
+    /// Applies the parameters of the extensions implemented by the application to the decoding of the frames read by this half.
+    ///
+    /// Only the read half decodes frames, so the [`WebSocketWrite`] has no such parameters.
+    /// A config applied to the [`WebSocket`] before [`WebSocket::split_with`] is kept. See [`WebSocket::with_codec_config`].
+    #[inline]
+    pub const fn with_codec_config(mut self, config: CodecConfig) -> Self {
+        self.core.codec.set_config(config);
+        self
+    }
+
+    /// Changes the parameters applied to the decoding of the frames read by this half without consuming the [`WebSocketRead`].
+    ///
+    /// Takes effect from the next frame decoded. See [`WebSocketRead::with_codec_config`].
+    #[inline]
+    pub const fn set_codec_config(&mut self, config: CodecConfig) {
+        self.core.codec.set_config(config);
+    }
+
+    /// Returns the parameters applied to the decoding of the frames read by this half.
+    #[inline]
+    pub const fn codec_config(&self) -> CodecConfig {
+        self.core.codec.config()
+    }
+
+    /// Sets the size limits for received messages.
+    #[inline]
+    pub const fn with_limits(mut self, limits: Limits) -> Self {
+        self.core.set_limits(limits);
+        self
+    }
+
+    /// Sets the maximum payload size of a single received data frame.
+    ///
+    /// See [`WebSocket::with_max_frame_size`].
+    #[inline]
+    pub const fn with_max_frame_size(mut self, max: usize) -> Self {
+        self.core.set_max_frame_size(max);
+        self
+    }
+
+    /// Sets the maximum payload size of a received text or binary message.
+    ///
+    /// See [`WebSocket::with_max_message_size`].
+    #[inline]
+    pub const fn with_max_message_size(mut self, max: usize) -> Self {
+        self.core.set_max_message_size(max);
+        self
+    }
+
+    /// Applies the limits of the given [`Profile`].
+    ///
+    /// See [`WebSocket::with_profile`].
+    #[inline]
+    pub const fn with_profile(mut self, profile: Profile) -> Self {
+        self.core.set_limits(profile.limits());
+        self
+    }
+
+    /// Sets a filter invoked on the first frame of every received data message.
+    ///
+    /// See [`WebSocket::with_message_filter`].
+    #[inline]
+    pub const fn with_message_filter(mut self, filter: MessageFilter) -> Self {
+        self.core.set_message_filter(Some(filter));
+        self
+    }
 
-impl<'buf, RW> WebSocketRead<'buf, RW> {
-    const fn new_from_framed(
-        framed: Framed<'buf, FramesCodec<()>, RW>,
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self {
-            core: WebSocketCore::new_from_framed(framed, fragments_state),
-        }
+    /// Sets a verifier invoked on the payload of every received binary message.
+    ///
+    /// See [`WebSocket::with_integrity_verifier`].
+    #[inline]
+    pub const fn with_integrity_verifier(mut self, verifier: &'buf dyn IntegrityVerifier) -> Self {
+        self.core.set_integrity_verifier(Some(verifier));
+        self
     }
 
-    /// Creates a new [`WebSocketRead`] client after a successful handshake.
-    pub const fn client(
-        inner: RW,
-        read_buffer: &'buf mut [u8],
-        fragments_buffer: &'buf mut [u8],
-    ) -> Self {
-        Self {
-            core: WebSocketCore::client(
-                inner,
-                (),
-                read_buffer,
-                &mut [],
-                FragmentsState::new(fragments_buffer),
-            ),
-        }
+    /// Sets an observer invoked with the progress of every received fragmented message.
+    ///
+    /// See [`WebSocket::with_progress_observer`].
+    #[inline]
+    pub const fn with_progress_observer(mut self, observer: &'buf dyn ProgressObserver) -> Self {
+        self.core.set_progress_observer(Some(observer));
+        self
     }
 
-    /// Creates a new [`WebSocketRead`] server after a successful handshake.
-    pub const fn server(
-        inner: RW,
-        read_buffer: &'buf mut [u8],
-        fragments_buffer: &'buf mut [u8],
+    /// Sets the decompressor and the buffer for received messages compressed with `permessage-deflate`.
+    ///
+    /// See [`WebSocket::with_inflater`].
+    #[inline]
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    pub const fn with_inflater(
+        mut self,
+        inflater: &'buf mut Inflater,
+        buffer: &'buf mut [u8],
     ) -> Self {
-        Self {
-            core: WebSocketCore::server(
-                inner,
-                (),
-                read_buffer,
-                &mut [],
-                FragmentsState::new(fragments_buffer),
-            ),
+        self.core.set_inflate(inflater, buffer);
+        self
+    }
+
+    /// Sets whether the payload of received text messages is validated as UTF-8.
+    ///
+    /// See [`WebSocket::with_text_validation`].
+    #[inline]
+    pub const fn with_text_validation(mut self, validate_text: bool) -> Self {
+        self.core.set_text_validation(validate_text);
+        self
+    }
+
+    /// Sets the [`CloseSignal`] shared with the corresponding [`WebSocketWrite`].
+    ///
+    /// See [`WebSocketRead::request_close`].
+    #[inline]
+    pub const fn with_close_signal(mut self, signal: &'buf CloseSignal) -> Self {
+        self.close_signal = Some(signal);
+        self
+    }
+
+    /// Requests the connection to be closed with `code`.
+    ///
+    /// The Close frame is sent by the next operation on the [`WebSocketWrite`] sharing the [`CloseSignal`] of this half.
+    /// Messages sent afterwards fail with [`WriteError::ConnectionClosed`](crate::error::WriteError::ConnectionClosed).
+    ///
+    /// Returns `false` if no [`CloseSignal`] is set with [`WebSocketRead::with_close_signal`].
+    pub fn request_close(&self, code: CloseCode) -> bool {
+        match self.close_signal {
+            Some(signal) => {
+                signal.request(code);
+
+                true
+            }
+            None => false,
         }
     }
 
@@ -371,6 +1930,17 @@ impl<'buf, RW> WebSocketRead<'buf, RW> {
         self.core.inner_mut()
     }
 
+    /// Gives scoped async access to the reader, e.g. to query or adjust the underlying socket.
+    ///
+    /// Bytes already read stay in the read buffer and are framed once `f` returns.
+    /// Bytes read in `f` are lost to the [`WebSocketRead`], so `f` must not consume any part of the WebSocket stream.
+    pub async fn with_inner_async<F, T>(&mut self, f: F) -> T
+    where
+        F: AsyncFnOnce(&mut RW) -> T,
+    {
+        f(self.core.inner_mut()).await
+    }
+
     /// Consumes the [`WebSocketRead`] and returns the reader.
     #[inline]
     pub fn into_inner(self) -> RW {
@@ -383,6 +1953,176 @@ impl<'buf, RW> WebSocketRead<'buf, RW> {
         self.core.framable()
     }
 
+    /// Pauses reading.
+    ///
+    /// See [`WebSocket::pause_reads`].
+    #[inline]
+    pub const fn pause_reads(&mut self) {
+        self.core.set_reads_paused(true);
+    }
+
+    /// Resumes reading paused with [`WebSocketRead::pause_reads`].
+    #[inline]
+    pub const fn resume_reads(&mut self) {
+        self.core.set_reads_paused(false);
+    }
+
+    /// Returns whether reading is paused.
+    #[inline]
+    pub const fn reads_paused(&self) -> bool {
+        self.core.reads_paused()
+    }
+
+    /// Sets the minimum number of new bytes to read from the underlying I/O before decoding frames.
+    ///
+    /// See [`WebSocket::with_min_read_chunk`].
+    #[inline]
+    pub const fn with_min_read_chunk(mut self, min: usize) -> Self {
+        self.core.set_min_read_chunk(min);
+        self
+    }
+
+    /// Sets the minimum number of new bytes to read before decoding frames.
+    ///
+    /// See [`WebSocket::set_min_read_chunk`].
+    #[inline]
+    pub const fn set_min_read_chunk(&mut self, min: usize) {
+        self.core.set_min_read_chunk(min);
+    }
+
+    /// Returns the minimum number of new bytes to read before decoding frames.
+    #[inline]
+    pub const fn min_read_chunk(&self) -> usize {
+        self.core.min_read_chunk()
+    }
+
+    /// Reads the next [`Message`] and passes it to `f`.
+    ///
+    /// A function alternative to the [`next!`](crate::next) macro with the same semantics.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocketRead::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    ///   EOF in the middle of a frame is an error, see [`FrameDecodeError::UnexpectedEof`](crate::error::FrameDecodeError::UnexpectedEof).
+    pub async fn with_next<F, T>(&mut self, f: F) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read,
+    {
+        crate::next!(self).map(|result| result.map(f))
+    }
+
+    /// Reads the next [`StreamItem`], passing the frames of fragmented messages through, and passes it to `f`.
+    ///
+    /// See [`WebSocket::with_next_fragment`].
+    pub async fn with_next_fragment<F, T>(
+        &mut self,
+        f: F,
+    ) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(StreamItem<'_>) -> T,
+        RW: Read,
+    {
+        crate::next_fragment!(self).map(|result| result.map(f))
+    }
+
+    /// Reads and handles at most one frame, returning the [`Message`] it completes, if any.
+    ///
+    /// See [`WebSocket::maybe_next`].
+    pub async fn maybe_next(
+        &mut self,
+    ) -> Option<Result<Option<Message<'_>>, MessageError<RW::Error>>>
+    where
+        RW: Read,
+    {
+        self.caller()
+            .call(
+                (),
+                &mut self.core.codec,
+                &mut self.core.inner,
+                &mut self.core.read,
+                &mut self.core.write,
+                &mut self.core.fragments_state,
+                &mut self.core.state,
+            )
+            .await
+    }
+
+    /// Reads the next [`Message`], reassembling a fragmented message into `buffer` instead of the fragments buffer, and passes it to `f`.
+    ///
+    /// See [`WebSocket::with_next_into`].
+    pub async fn with_next_into<F, T>(
+        &mut self,
+        buffer: &mut [u8],
+        f: F,
+    ) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read,
+    {
+        let Some(mut fragments_state) = self.core.fragments_state.with_buffer(buffer) else {
+            return Some(Err(MessageError::FragmentsBufferTooSmall));
+        };
+
+        loop {
+            let result = self
+                .caller()
+                .call(
+                    (),
+                    &mut self.core.codec,
+                    &mut self.core.inner,
+                    &mut self.core.read,
+                    &mut self.core.write,
+                    &mut fragments_state,
+                    &mut self.core.state,
+                )
+                .await;
+
+            let result = match result {
+                Some(Ok(None)) => {
+                    self.core.fragments_state.track(&fragments_state);
+
+                    continue;
+                }
+                Some(Ok(Some(message))) => Some(Ok(f(message))),
+                Some(Err(err)) => Some(Err(err)),
+                None => None,
+            };
+
+            self.core.fragments_state.track(&fragments_state);
+
+            return result;
+        }
+    }
+
+    /// Reads the next [`Message`] and passes it to `f` without consuming it.
+    ///
+    /// See [`WebSocket::peek_message`].
+    pub async fn peek_message<F, T>(&mut self, f: F) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read,
+    {
+        self.core.peek_message(f).await
+    }
+
+    /// Attaches `writer` to the [`WebSocketRead`] for the duration of the returned [`AttachedRead`].
+    ///
+    /// Reading through the [`AttachedRead`] answers `Ping` and `Close` messages automatically with `writer`,
+    /// as an unsplit [`WebSocket`] does.
+    pub const fn attach_writer<'a, W, Rng>(
+        &'a mut self,
+        writer: &'a mut WebSocketWrite<'buf, W, Rng>,
+    ) -> AttachedRead<'a, 'buf, RW, W, Rng> {
+        AttachedRead {
+            read: self,
+            write: writer,
+        }
+    }
+
     #[doc(hidden)]
     pub const fn auto(&self) {}
 
@@ -392,34 +2132,126 @@ impl<'buf, RW> WebSocketRead<'buf, RW> {
     }
 }
 
+/// A [`WebSocketRead`] with an attached [`WebSocketWrite`].
+///
+/// Created with [`WebSocketRead::attach_writer`].
+///
+/// # Example
+///
+/// ```
+/// # use websocketz::mock::Noop;
+/// # async fn run<Rng: websocketz::rng::RngCore>(websocketz: websocketz::WebSocket<'_, Noop, Rng>) {
+/// use websocketz::Message;
+///
+/// let (mut read, mut write) = websocketz.split_with(|_| (Noop, Noop));
+///
+/// loop {
+///     // `Ping` and `Close` messages are answered with `write` while reading.
+///     let greeted = read
+///         .attach_writer(&mut write)
+///         .with_next(|message| matches!(message, Message::Text("Hello")))
+///         .await;
+///
+///     match greeted {
+///         Some(Ok(true)) => {
+///             let _ = write.send(Message::Text("Hello back")).await;
+///         }
+///         Some(Ok(false)) => {}
+///         _ => break,
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AttachedRead<'a, 'buf, R, W, Rng> {
+    read: &'a mut WebSocketRead<'buf, R>,
+    write: &'a mut WebSocketWrite<'buf, W, Rng>,
+}
+
+impl<R, W, Rng> AttachedRead<'_, '_, R, W, Rng> {
+    /// Reads the next [`Message`] and passes it to `f`.
+    ///
+    /// Same as [`WebSocket::with_next`], including the automatic handling of `Ping` and `Close` messages,
+    /// which are answered with the attached [`WebSocketWrite`].
+    /// A `Close` frame sent by the attached [`WebSocketWrite`] is taken into account.
+    ///
+    /// # Return
+    /// - `Some(Ok(T))`: A message was successfully read and passed to `f`.
+    /// - `Some(Err(MessageError::ReadsPaused))`: Reading is paused with [`WebSocket::pause_reads`], nothing was read.
+    ///   Reading can continue once resumed.
+    /// - `Some(Err(MessageError))`: Any other error occurred while reading a message. The caller should stop reading.
+    /// - `None`: The WebSocket connection has been closed (EOF) between two frames. The caller should stop reading.
+    ///   EOF in the middle of a frame is an error, see [`FrameDecodeError::UnexpectedEof`](crate::error::FrameDecodeError::UnexpectedEof).
+    pub async fn with_next<F, T>(&mut self, f: F) -> Option<Result<T, MessageError<R::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        R: Read,
+        W: Write<Error = R::Error>,
+        Rng: RngCore,
+    {
+        loop {
+            self.read.core.state.closed |= self.write.core.state.closed;
+
+            let auto = self.read.core.auto();
+
+            match crate::functions::next_attached(auto, &mut self.read.core, &mut self.write.core)
+                .await
+            {
+                Some(Ok(None)) => continue,
+                Some(Ok(Some(message))) => return Some(Ok(f(message.into_message()))),
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            }
+        }
+    }
+}
+
 /// Write half of a WebSocket connection.
 #[derive(Debug)]
 pub struct WebSocketWrite<'buf, RW, Rng> {
     #[doc(hidden)]
-    pub core: WebSocketCore<'buf, RW, Rng>,
+    pub core: WriteCore<'buf, RW, Rng>,
 }
 
 impl<'buf, RW, Rng> WebSocketWrite<'buf, RW, Rng> {
-    const fn new_from_framed(framed: Framed<'buf, FramesCodec<Rng>, RW>) -> Self {
-        Self {
-            core: WebSocketCore::new_from_framed(framed, FragmentsState::empty()),
-        }
-    }
-
     /// Creates a new [`WebSocketWrite`] client after a successful handshake.
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
     pub const fn client(inner: RW, rng: Rng, write_buffer: &'buf mut [u8]) -> Self {
         Self {
-            core: WebSocketCore::client(inner, rng, &mut [], write_buffer, FragmentsState::empty()),
+            core: Core::client(inner, rng, None, WriteState::new(write_buffer), ()),
         }
     }
 
     /// Creates a new [`WebSocketWrite`] server after a successful handshake.
     pub const fn server(inner: RW, rng: Rng, write_buffer: &'buf mut [u8]) -> Self {
         Self {
-            core: WebSocketCore::server(inner, rng, &mut [], write_buffer, FragmentsState::empty()),
+            core: Core::server(inner, rng, None, WriteState::new(write_buffer), ()),
         }
     }
 
+    /// Sets the [`CloseSignal`] shared with the corresponding [`WebSocketRead`].
+    ///
+    /// A close requested with [`WebSocketRead::request_close`] is sent before any frame sent by this half.
+    /// Frames sent with the [`send!`](crate::send) macros bypass the signal.
+    #[inline]
+    pub const fn with_close_signal(mut self, signal: &'buf CloseSignal) -> Self {
+        self.core.read = Some(signal);
+        self
+    }
+
+    /// Sends the Close frame requested through the [`CloseSignal`] of this half, if any and if none has been sent yet.
+    ///
+    /// Every frame sent by this half, including automatic replies sent through an [`AttachedRead`], is preceded by this check,
+    /// see [`WebSocketWrite::with_close_signal`].
+    pub async fn send_requested_close(&mut self) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_requested_close().await
+    }
+
     /// Returns reference to the writer.
     #[inline]
     pub const fn inner(&self) -> &RW {
@@ -432,6 +2264,22 @@ impl<'buf, RW, Rng> WebSocketWrite<'buf, RW, Rng> {
         self.core.inner_mut()
     }
 
+    /// Gives scoped async access to the writer, e.g. to send a TCP keepalive or an out-of-band command.
+    ///
+    /// `f` only runs between two written frames, see [`WebSocket::with_inner_async`].
+    /// Bytes written in `f` land between two frames on the wire and must therefore not corrupt the WebSocket stream.
+    pub async fn with_inner_async<F, T>(&mut self, f: F) -> Result<T, Error<RW::Error>>
+    where
+        F: AsyncFnOnce(&mut RW) -> T,
+        RW: Write,
+    {
+        if self.core.state.mid_frame() {
+            return Err(Error::Write(WriteError::PartialFrame));
+        }
+
+        Ok(f(self.core.inner_mut()).await)
+    }
+
     /// Consumes the [`WebSocketWrite`] and returns the writer.
     #[inline]
     pub fn into_inner(self) -> RW {
@@ -439,6 +2287,8 @@ impl<'buf, RW, Rng> WebSocketWrite<'buf, RW, Rng> {
     }
 
     /// Sends a WebSocket message.
+    ///
+    /// See [`WebSocket::send`].
     pub async fn send(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
     where
         RW: Write,
@@ -447,6 +2297,141 @@ impl<'buf, RW, Rng> WebSocketWrite<'buf, RW, Rng> {
         self.core.send(message).await
     }
 
+    /// Sends a WebSocket message without flushing the writer.
+    ///
+    /// See [`WebSocket::send_without_flush`].
+    pub async fn send_without_flush(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_without_flush(message).await
+    }
+
+    /// Flushes the writer.
+    ///
+    /// See [`WebSocket::flush`].
+    pub async fn flush(&mut self) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        self.core.flush().await
+    }
+
+    /// Sends a Close frame, if none has been sent yet, and flushes the writer.
+    ///
+    /// Repeated calls are no-ops, so several tasks sharing this half may race to shut the connection down.
+    ///
+    /// # Note
+    ///
+    /// A Close frame received by the corresponding [`WebSocketRead`] is not visible to this half.
+    /// Call this method to reply to it.
+    pub async fn send_close_and_flush(
+        &mut self,
+        frame: Option<CloseFrame<'_>>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_close_and_flush(frame).await
+    }
+
+    /// Compresses `payload` with `codec` and sends it as a binary message, prefixed with a 1-byte header.
+    ///
+    /// See [`WebSocket::send_compressed_binary`].
+    pub async fn send_compressed_binary<C>(
+        &mut self,
+        payload: &[u8],
+        codec: &C,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        C: PayloadCodec,
+    {
+        self.core.send_compressed_binary(payload, codec).await
+    }
+
+    /// Sends a binary message of `len` bytes, written by `write` directly into the write buffer.
+    ///
+    /// See [`WebSocket::send_binary_with`].
+    pub async fn send_binary_with<F>(
+        &mut self,
+        len: usize,
+        write: F,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        F: FnOnce(&mut [u8]),
+    {
+        self.core.send_binary_with(len, write).await
+    }
+
+    /// Sends a [`PreEncodedMessage`] by writing its bytes and flushing the writer.
+    ///
+    /// See [`WebSocket::send_pre_encoded`].
+    pub async fn send_pre_encoded(
+        &mut self,
+        message: &PreEncodedMessage<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_pre_encoded(message).await
+    }
+
+    /// Encodes `message` as a single frame into `dst` without sending it, returning the number of bytes written.
+    ///
+    /// See [`WebSocket::encode_message_into`].
+    pub fn encode_message_into(
+        &mut self,
+        message: Message<'_>,
+        dst: &mut [u8],
+    ) -> Result<usize, FrameEncodeError>
+    where
+        Rng: RngCore,
+    {
+        self.core.encode_message_into(message, dst)
+    }
+
+    /// Sends a [`RawFrame`](crate::danger::RawFrame) as-is, without any protocol checks.
+    ///
+    /// Fails if the connection is closed, but the frame itself does not update the state of the connection,
+    /// e.g. a raw close frame does not close it.
+    #[cfg(feature = "danger-frames")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "danger-frames")))]
+    pub async fn send_raw(
+        &mut self,
+        frame: crate::danger::RawFrame<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_raw(frame).await
+    }
+
+    /// Sends a data frame whose `payload` was already masked with `mask`.
+    ///
+    /// See [`WebSocket::send_pre_masked`].
+    #[cfg(feature = "pre-masked")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pre-masked")))]
+    pub async fn send_pre_masked(
+        &mut self,
+        header: crate::pre_masked::FrameHeader,
+        mask: [u8; 4],
+        payload: &[u8],
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+    {
+        self.core.send_pre_masked(header, mask, payload).await
+    }
+
     /// Sends a fragmented WebSocket message.
     pub async fn send_fragmented(
         &mut self,
@@ -459,4 +2444,22 @@ impl<'buf, RW, Rng> WebSocketWrite<'buf, RW, Rng> {
     {
         self.core.send_fragmented(message, fragment_size).await
     }
+
+    /// Starts sending a text message incrementally, chunk by chunk.
+    ///
+    /// A close requested through the [`CloseSignal`] of this half is sent before the next fragment.
+    /// See [`WebSocket::send_text_stream`].
+    #[inline]
+    pub const fn send_text_stream(&mut self) -> MessageWriter<'_, 'buf, RW, Rng> {
+        self.core.message_writer(OpCode::Text, self.core.read)
+    }
+
+    /// Starts sending a binary message incrementally, chunk by chunk.
+    ///
+    /// A close requested through the [`CloseSignal`] of this half is sent before the next fragment.
+    /// See [`WebSocket::send_binary_stream`].
+    #[inline]
+    pub const fn send_binary_stream(&mut self) -> MessageWriter<'_, 'buf, RW, Rng> {
+        self.core.message_writer(OpCode::Binary, self.core.read)
+    }
 }
@@ -1,20 +1,46 @@
 use base64::{Engine as _, engine::general_purpose};
 use embedded_io_async::{Read, Write};
-use framez::Framed;
+use framez::{
+    Framed,
+    state::{ReadState, ReadWriteState, WriteState},
+};
 use httparse::Header;
-use rand::RngCore;
 
 use sha1::{Digest, Sha1};
 
+#[cfg(feature = "deflate")]
+use crate::deflate::Inflater;
 use crate::{
-    CloseCode, CloseFrame, FramesCodec, Message, OpCode,
-    error::{Error, HandshakeError, ProtocolError, ReadError, WriteError},
-    frame::Frame,
+    CloseCode, CloseFrame, CloseSignal, Fragment, FramesCodec, IntegrityVerifier, Message,
+    MessageMut, MessageWriter, OpCode, PreEncodedMessage, Progress, ProgressObserver, StreamItem,
+    clock::{Clock, ticks_to_duration},
+    close_signal::CloseSignalSlot,
+    codec::{Peek, Staged},
+    compression::PayloadCodec,
+    error::{
+        ConfigError, Error, FrameDecodeError, FrameEncodeError, HandshakeError, MessageError,
+        ProtocolError, QueuePingError, ReadError, WriteError,
+    },
+    extensions::AcceptedExtensions,
+    filter::{FirstFrame, MessageFilter, Verdict},
+    frame::{Frame, FrameMeta, FrameMut},
+    handshake_trace,
     http::{
-        HeaderExt, InRequestCodec, InResponseCodec, OutRequest, OutRequestCodec, OutResponse,
-        OutResponseCodec, Request, Response,
+        HeaderExt, InRequestCodec, OutResponse, OutResponseCodec, Request, SecVersion,
+        WEBSOCKET_GUID, is_valid_path,
     },
-    options::{AcceptOptions, ConnectOptions},
+    info::ConnectionInfo,
+    integrity::Verifier,
+    options::{AcceptOptions, Keepalive, Limits},
+    progress::Observer,
+    rng::RngCore,
+    stats::{Counted, HandshakeStats},
+};
+#[cfg(feature = "client")]
+use crate::{
+    error::HttpDecodeError,
+    http::{InResponseCodec, OutRequest, OutRequestCodec, Redirect, Response, SkipBodyCodec},
+    options::ConnectOptions,
 };
 
 #[derive(Debug)]
@@ -22,6 +48,19 @@ use crate::{
 pub struct FragmentsState<'buf> {
     fragmented: Option<Fragmented>,
     fragments_buffer: &'buf mut [u8],
+    /// Filter invoked on the first frame of every data message.
+    filter: Option<MessageFilter>,
+    /// Size limits for received messages.
+    limits: Limits,
+    /// Verifier invoked on the payload of every binary message.
+    verifier: Option<Verifier<'buf>>,
+    /// Observer invoked after every fragment appended to the fragments buffer.
+    observer: Option<Observer<'buf>>,
+    /// Validate the payload of text messages as UTF-8.
+    validate_text: bool,
+    /// Decompressor and buffer for messages compressed with `permessage-deflate`.
+    #[cfg(feature = "deflate")]
+    inflate: Option<Inflate<'buf>>,
 }
 
 impl<'buf> FragmentsState<'buf> {
@@ -30,19 +69,157 @@ impl<'buf> FragmentsState<'buf> {
         Self {
             fragmented: None,
             fragments_buffer,
+            filter: None,
+            limits: Limits::new(),
+            verifier: None,
+            observer: None,
+            validate_text: true,
+            #[cfg(feature = "deflate")]
+            inflate: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) const fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    #[inline]
+    pub(crate) const fn set_filter(&mut self, filter: Option<MessageFilter>) {
+        self.filter = filter;
+    }
+
+    #[inline]
+    pub(crate) const fn set_verifier(&mut self, verifier: Option<&'buf dyn IntegrityVerifier>) {
+        self.verifier = match verifier {
+            Some(verifier) => Some(Verifier(verifier)),
+            None => None,
+        };
+    }
+
+    #[inline]
+    pub(crate) const fn set_observer(&mut self, observer: Option<&'buf dyn ProgressObserver>) {
+        self.observer = match observer {
+            Some(observer) => Some(Observer(observer)),
+            None => None,
+        };
+    }
+
+    #[inline]
+    pub(crate) const fn set_text_validation(&mut self, validate_text: bool) {
+        self.validate_text = validate_text;
+    }
+
+    /// Invokes the observer with the progress of the message being reassembled.
+    #[inline]
+    fn observe(&self, fragmented: &Fragmented, is_final: bool) {
+        if let Some(observer) = self.observer {
+            observer.0.on_progress(Progress::new(
+                fragmented.opcode == OpCode::Text,
+                fragmented.index,
+                is_final,
+            ));
         }
     }
 
     #[inline]
-    pub(crate) const fn empty() -> Self {
-        Self::new(&mut [])
+    #[cfg(feature = "deflate")]
+    pub(crate) const fn set_inflate(
+        &mut self,
+        inflater: &'buf mut Inflater,
+        buffer: &'buf mut [u8],
+    ) {
+        self.inflate = Some(Inflate { inflater, buffer });
+    }
+
+    /// Returns a state with the same configuration reassembling messages into `buffer`.
+    ///
+    /// A message partially reassembled into the fragments buffer is copied into `buffer`.
+    /// Returns `None` if it does not fit.
+    ///
+    /// The returned state has no inflate buffer, compressed messages fail with [`OnFrameError::DecompressionFailed`].
+    pub(crate) fn with_buffer<'a>(&self, buffer: &'a mut [u8]) -> Option<FragmentsState<'a>>
+    where
+        'buf: 'a,
+    {
+        if let Some(fragmented) = self.fragmented.filter(|fragmented| !fragmented.rejected) {
+            buffer
+                .get_mut(..fragmented.index)?
+                .copy_from_slice(&self.fragments_buffer[..fragmented.index]);
+        }
+
+        Some(FragmentsState {
+            fragmented: self.fragmented,
+            fragments_buffer: buffer,
+            filter: self.filter,
+            limits: self.limits,
+            verifier: self.verifier,
+            observer: self.observer,
+            validate_text: self.validate_text,
+            #[cfg(feature = "deflate")]
+            inflate: None,
+        })
+    }
+
+    /// Tracks the message being reassembled by `other`, created with [`FragmentsState::with_buffer`].
+    ///
+    /// Its fragments are not in the fragments buffer, so its remaining fragments are discarded
+    /// if reading continues with this state.
+    pub(crate) fn track(&mut self, other: &FragmentsState<'_>) {
+        self.fragmented = other.fragmented.map(|fragmented| Fragmented {
+            index: 0,
+            rejected: true,
+            passthrough: false,
+            ..fragmented
+        });
     }
 }
 
+/// Decompressor and buffer for messages compressed with `permessage-deflate`.
 #[derive(Debug)]
+#[cfg(feature = "deflate")]
+struct Inflate<'buf> {
+    inflater: &'buf mut Inflater,
+    buffer: &'buf mut [u8],
+}
+
+#[cfg(feature = "deflate")]
+impl Inflate<'_> {
+    /// Decompresses the payload of a compressed message into the inflate buffer, checking the decompressed length against `limits`.
+    fn inflate<'a>(
+        inflate: Option<&'a mut Self>,
+        limits: &Limits,
+        opcode: OpCode,
+        payload: &[u8],
+    ) -> Result<&'a mut [u8], OnFrameError> {
+        let inflate = inflate.ok_or(OnFrameError::DecompressionFailed)?;
+
+        let len = inflate
+            .inflater
+            .decompress(payload, inflate.buffer)
+            .ok_or(OnFrameError::DecompressionFailed)?;
+
+        if limits.exceeds(opcode, len) {
+            return Err(OnFrameError::MessageTooLarge);
+        }
+
+        Ok(&mut inflate.buffer[..len])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 struct Fragmented {
     opcode: OpCode,
+    /// The first frame of the message is compressed with `permessage-deflate`.
+    #[cfg(feature = "deflate")]
+    compressed: bool,
     index: usize,
+    /// The message was rejected by the filter and its fragments are discarded.
+    rejected: bool,
+    /// The fragments are passed through to the application instead of being reassembled.
+    passthrough: bool,
+    /// Number of empty non-final fragments received for this message.
+    empty: usize,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -51,6 +228,10 @@ struct Auto {
     pong: bool,
     /// Auto close frame handling.
     close: bool,
+    /// Maximum number of ping payload bytes echoed in an auto pong.
+    pong_payload_limit: Option<u8>,
+    /// Auto close with [`CloseCode::Size`] when a message does not fit into the fragments buffer.
+    close_on_fragments_overflow: bool,
 }
 
 impl Auto {
@@ -59,6 +240,122 @@ impl Auto {
         Self {
             pong: true,
             close: true,
+            pong_payload_limit: None,
+            close_on_fragments_overflow: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pings {
+    /// Number of pings sent since the last received pong.
+    unanswered: usize,
+    /// Maximum number of unanswered pings before the peer is considered unresponsive.
+    max: Option<usize>,
+}
+
+impl Pings {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            unanswered: 0,
+            max: None,
+        }
+    }
+}
+
+/// Pings queued by the application, sent by the next read.
+#[derive(Debug, Clone, Copy)]
+struct PingQueue {
+    payloads: [[u8; PingQueue::MAX_PAYLOAD_LEN]; PingQueue::CAPACITY],
+    lens: [u8; PingQueue::CAPACITY],
+    /// Number of queued pings.
+    len: usize,
+}
+
+impl PingQueue {
+    const CAPACITY: usize = 4;
+    const MAX_PAYLOAD_LEN: usize = 8;
+
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            payloads: [[0; Self::MAX_PAYLOAD_LEN]; Self::CAPACITY],
+            lens: [0; Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, payload: &[u8]) -> Result<(), QueuePingError> {
+        if payload.len() > Self::MAX_PAYLOAD_LEN {
+            return Err(QueuePingError::PayloadTooLong);
+        }
+
+        if self.len == Self::CAPACITY {
+            return Err(QueuePingError::Full);
+        }
+
+        self.payloads[self.len][..payload.len()].copy_from_slice(payload);
+        self.lens[self.len] = payload.len() as u8;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<([u8; Self::MAX_PAYLOAD_LEN], usize)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let ping = (self.payloads[0], self.lens[0] as usize);
+
+        self.payloads.copy_within(1..self.len, 0);
+        self.lens.copy_within(1..self.len, 0);
+        self.len -= 1;
+
+        Some(ping)
+    }
+}
+
+/// Keepalive pings sent by [`WebSocket::tick`](crate::WebSocket::tick).
+#[derive(Debug, Clone, Copy)]
+struct KeepaliveState {
+    keepalive: Keepalive,
+    /// Number of the next frame to be received at the last tick.
+    next_frame: u64,
+    /// Clock ticks of the last tick that observed a received frame or sent a ping, `None` until the first tick.
+    active: Option<u64>,
+    /// Clock ticks when the unanswered ping was sent.
+    ping_sent: Option<u64>,
+    /// A pong was received since the last tick.
+    pong_received: bool,
+}
+
+/// What [`ConnectionState::on_tick`] asks the caller to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeepaliveTick {
+    /// Nothing to do.
+    Idle,
+    /// Send a ping.
+    Ping,
+    /// No pong was received in time.
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CloseWait {
+    /// Number of frames received since the connection was closed.
+    frames: usize,
+    /// Maximum number of frames read while waiting for the peer's close frame.
+    max: Option<usize>,
+}
+
+impl CloseWait {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            frames: 0,
+            max: None,
         }
     }
 }
@@ -72,6 +369,24 @@ pub struct ConnectionState {
     pub closed: bool,
     /// Auto handling of ping/pong and close frames.
     auto: Auto,
+    /// Tracking of unanswered pings.
+    pings: Pings,
+    /// Pings queued by the application.
+    ping_queue: PingQueue,
+    /// Tracking of frames received while waiting for the peer's close frame.
+    close_wait: CloseWait,
+    /// Keepalive pings, `None` if disabled.
+    keepalive: Option<KeepaliveState>,
+    /// Statistics of the handshake that established the connection.
+    handshake: HandshakeStats,
+    /// Reading is paused by the application.
+    reads_paused: bool,
+    /// Minimum number of bytes to buffer before decoding, `0` to decode after every read.
+    min_read_chunk: usize,
+    /// The last frame received, `None` until the first one.
+    last_frame: Option<FrameMeta>,
+    /// A frame is being written, or its write was cancelled or failed after it started.
+    mid_frame: bool,
 }
 
 impl ConnectionState {
@@ -81,306 +396,1291 @@ impl ConnectionState {
         Self {
             closed: false,
             auto: Auto::positive(),
+            pings: Pings::new(),
+            ping_queue: PingQueue::new(),
+            close_wait: CloseWait::new(),
+            keepalive: None,
+            handshake: HandshakeStats::new(),
+            reads_paused: false,
+            min_read_chunk: 0,
+            last_frame: None,
+            mid_frame: false,
         }
     }
-}
-
-#[derive(Debug)]
-#[doc(hidden)]
-pub struct WebSocketCore<'buf, RW, Rng> {
-    pub framed: Framed<'buf, FramesCodec<Rng>, RW>,
-    pub fragments_state: FragmentsState<'buf>,
-    pub state: ConnectionState,
-}
 
-impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
+    /// Returns the number of the next frame to be received.
     #[inline]
-    const fn from_framed(
-        framed: Framed<'buf, FramesCodec<Rng>, RW>,
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self {
-            framed,
-            fragments_state,
-            state: ConnectionState::new(),
+    pub(crate) const fn next_frame_sequence(&self) -> u64 {
+        match self.last_frame {
+            Some(meta) => meta.sequence().wrapping_add(1),
+            None => 1,
         }
     }
 
+    /// Must be called for every frame received, returning its number.
     #[inline]
-    pub(crate) const fn new_from_framed(
-        framed: Framed<'buf, FramesCodec<Rng>, RW>,
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self::from_framed(framed, fragments_state)
+    pub(crate) const fn on_frame_received(&mut self, frame: &FrameMut<'_>) -> u64 {
+        let sequence = self.next_frame_sequence();
+
+        self.last_frame = Some(FrameMeta::new(
+            sequence,
+            frame.is_final(),
+            frame.reserved_bits(),
+            frame.payload().len(),
+        ));
+
+        sequence
     }
 
+    /// Must be called before writing the bytes of a frame.
     #[inline]
-    const fn new(
-        inner: RW,
-        rng: Rng,
-        read_buffer: &'buf mut [u8],
-        write_buffer: &'buf mut [u8],
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self::new_from_framed(
-            Framed::new(FramesCodec::new(rng), inner, read_buffer, write_buffer),
-            fragments_state,
-        )
+    pub(crate) const fn on_write_started(&mut self) {
+        self.mid_frame = true;
     }
 
+    /// Must be called once the bytes of a frame are written, or if no byte was written.
     #[inline]
-    pub(crate) const fn client(
-        inner: RW,
-        rng: Rng,
-        read_buffer: &'buf mut [u8],
-        write_buffer: &'buf mut [u8],
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self::new(inner, rng, read_buffer, write_buffer, fragments_state).into_server()
+    pub(crate) const fn on_write_finished(&mut self) {
+        self.mid_frame = false;
     }
 
+    /// Returns whether only a part of a frame was written, e.g. because the future writing it was dropped.
     #[inline]
-    pub(crate) const fn server(
-        inner: RW,
-        rng: Rng,
-        read_buffer: &'buf mut [u8],
-        write_buffer: &'buf mut [u8],
-        fragments_state: FragmentsState<'buf>,
-    ) -> Self {
-        Self::new(inner, rng, read_buffer, write_buffer, fragments_state).into_client()
+    pub(crate) const fn mid_frame(&self) -> bool {
+        self.mid_frame
     }
 
+    /// Returns whether reading is paused by the application.
     #[inline]
-    const fn into_client(mut self) -> Self {
-        self.framed.codec_mut().set_mask(false);
-        self.framed.codec_mut().set_unmask(true);
-        self
+    pub(crate) const fn reads_paused(&self) -> bool {
+        self.reads_paused
     }
 
+    /// Returns the minimum number of bytes to buffer before decoding.
     #[inline]
-    const fn into_server(mut self) -> Self {
-        self.framed.codec_mut().set_mask(true);
-        self.framed.codec_mut().set_unmask(false);
-        self
+    pub(crate) const fn min_read_chunk(&self) -> usize {
+        self.min_read_chunk
     }
 
+    /// Returns whether a Close frame must be sent automatically before surfacing `err`.
     #[inline]
-    pub(crate) const fn set_auto_pong(&mut self, auto_pong: bool) {
-        self.state.auto.pong = auto_pong;
+    pub(crate) const fn auto_closes_on(&self, err: &OnFrameError) -> bool {
+        if !self.auto.close || self.closed {
+            return false;
+        }
+
+        match err {
+            OnFrameError::MessageTooLarge | OnFrameError::TooManyEmptyFragments => true,
+            OnFrameError::FragmentsBufferTooSmall => self.auto.close_on_fragments_overflow,
+            OnFrameError::Protocol(_) | OnFrameError::IntegrityCheckFailed => false,
+            #[cfg(feature = "deflate")]
+            OnFrameError::DecompressionFailed => false,
+        }
     }
 
+    /// Must be called before sending a ping.
+    ///
+    /// Returns `false` if the maximum number of unanswered pings has been reached.
     #[inline]
-    pub(crate) const fn set_auto_close(&mut self, auto_close: bool) {
-        self.state.auto.close = auto_close;
+    pub(crate) const fn can_ping(&self) -> bool {
+        match self.pings.max {
+            Some(max) => self.pings.unanswered < max,
+            None => true,
+        }
     }
 
-    /// Returns reference to the reader/writer.
+    /// Must be called after a ping has been sent.
     #[inline]
-    pub(crate) const fn inner(&self) -> &RW {
-        self.framed.inner()
+    pub(crate) const fn on_ping_sent(&mut self) {
+        self.pings.unanswered = self.pings.unanswered.saturating_add(1);
     }
 
-    /// Returns mutable reference to the reader/writer.
-    #[inline]
-    pub(crate) const fn inner_mut(&mut self) -> &mut RW {
-        self.framed.inner_mut()
+    /// Queues a ping to be sent by the next read.
+    pub(crate) fn queue_ping(&mut self, payload: &[u8]) -> Result<(), QueuePingError> {
+        if self.closed {
+            return Err(QueuePingError::ConnectionClosed);
+        }
+
+        if let Some(max) = self.pings.max {
+            if self.pings.unanswered.saturating_add(self.ping_queue.len) >= max {
+                return Err(QueuePingError::PeerUnresponsive);
+            }
+        }
+
+        self.ping_queue.push(payload)
     }
 
-    /// Consumes the [`WebsocketsCore`] and returns the reader/writer.
-    #[inline]
-    pub(crate) fn into_inner(self) -> RW {
-        self.framed.into_parts().1
+    /// Removes the next queued ping, returning its payload buffer and length.
+    ///
+    /// Discards the queue once the connection is closed.
+    pub(crate) fn pop_queued_ping(&mut self) -> Option<([u8; PingQueue::MAX_PAYLOAD_LEN], usize)> {
+        if self.closed {
+            self.ping_queue.len = 0;
+        }
+
+        self.ping_queue.pop()
     }
 
-    /// Returns the number of bytes that can be framed.
+    /// Returns the number of queued pings.
     #[inline]
-    pub(crate) const fn framable(&self) -> usize {
-        self.framed.framable()
+    pub(crate) const fn queued_pings(&self) -> usize {
+        self.ping_queue.len
     }
 
-    fn generate_sec_key(&mut self) -> [u8; 24]
-    where
-        Rng: RngCore,
-    {
-        let mut key: [u8; 16] = [0; 16];
-
-        debug_assert!(key.len() == 16, "Key should be 16 bytes long");
-
-        self.framed.codec_mut().rng_mut().fill_bytes(&mut key);
+    /// Must be called after a pong has been received.
+    #[inline]
+    pub(crate) const fn on_pong_received(&mut self) {
+        self.pings.unanswered = 0;
 
-        // 24 = ((4 * key.len() + 2) / 3 + 3) & !3 = ((4 * 16 + 2) / 3 + 3) & !3
-        let mut encoded: [u8; 24] = [0; 24];
+        if let Some(keepalive) = &mut self.keepalive {
+            keepalive.pong_received = true;
+        }
+    }
 
-        general_purpose::STANDARD
-            .encode_slice(key, &mut encoded)
-            .expect("Bug: sec_key encoding failed");
+    #[inline]
+    pub(crate) const fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.keepalive = match keepalive {
+            Some(keepalive) => Some(KeepaliveState {
+                keepalive,
+                next_frame: self.next_frame_sequence(),
+                active: None,
+                ping_sent: None,
+                pong_received: false,
+            }),
+            None => None,
+        };
+    }
 
-        encoded
+    #[inline]
+    pub(crate) const fn keepalive(&self) -> Option<Keepalive> {
+        match self.keepalive {
+            Some(state) => Some(state.keepalive),
+            None => None,
+        }
     }
 
-    fn generate_sec_accept(sec_key: &[u8]) -> [u8; 28] {
-        let mut sha1 = Sha1::new();
+    /// Must be called on every tick, with the current clock ticks `now` at `tick_hz` ticks per second.
+    pub(crate) fn on_tick(&mut self, now: u64, tick_hz: u64) -> KeepaliveTick {
+        let next_frame = self.next_frame_sequence();
 
-        sha1.update(sec_key);
-        sha1.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+        let Some(state) = &mut self.keepalive else {
+            return KeepaliveTick::Idle;
+        };
 
-        let hash = sha1.finalize();
+        if self.closed {
+            return KeepaliveTick::Idle;
+        }
 
-        debug_assert!(hash.len() == 20, "SHA1 hash should be 20 bytes long");
+        if core::mem::take(&mut state.pong_received) {
+            state.ping_sent = None;
+        }
 
-        // 28 = ((4 * hash.len() + 2) / 3 + 3) & !3 = ((4 * 20 + 2) / 3 + 3) & !3
-        let mut encoded: [u8; 28] = [0; 28];
+        if state.next_frame != next_frame {
+            state.next_frame = next_frame;
+            state.active = Some(now);
+        }
 
-        general_purpose::STANDARD
-            .encode_slice(hash, &mut encoded)
-            .expect("Bug: sec_accept encoding failed");
+        let active = *state.active.get_or_insert(now);
+        let elapsed = |since: u64| ticks_to_duration(now.saturating_sub(since), tick_hz);
 
-        encoded
+        match state.ping_sent {
+            Some(sent) if elapsed(sent) >= state.keepalive.timeout => KeepaliveTick::Timeout,
+            Some(_) => KeepaliveTick::Idle,
+            None if elapsed(active) >= state.keepalive.interval => KeepaliveTick::Ping,
+            None => KeepaliveTick::Idle,
+        }
     }
 
-    pub(crate) async fn client_handshake<const N: usize, F, T, E>(
-        mut self,
-        options: ConnectOptions<'_, '_>,
-        on_response: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
-    where
-        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
-        RW: Read + Write,
-        Rng: RngCore,
-    {
-        let sec_key = self.generate_sec_key();
+    /// Must be called after a keepalive ping has been sent at the clock ticks `now`.
+    #[inline]
+    pub(crate) const fn on_keepalive_ping_sent(&mut self, now: u64) {
+        if let Some(state) = &mut self.keepalive {
+            state.active = Some(now);
+            state.ping_sent = Some(now);
+        }
+    }
 
-        let headers = &[
-            Header {
-                name: "upgrade",
-                value: b"websocket",
-            },
-            Header {
-                name: "connection",
-                value: b"upgrade",
-            },
-            Header {
-                name: "sec-websocket-version",
-                value: b"13",
-            },
-            Header {
-                name: "sec-websocket-key",
-                value: &sec_key,
-            },
-        ];
+    /// Must be called for every non-close frame received after the connection was closed.
+    ///
+    /// Returns `false` if the maximum number of frames to read while waiting for the peer's close frame has been exceeded.
+    #[inline]
+    pub(crate) const fn on_close_wait_frame(&mut self) -> bool {
+        self.close_wait.frames = self.close_wait.frames.saturating_add(1);
 
-        let request = OutRequest::get_unchecked(options.path, headers, options.headers);
+        match self.close_wait.max {
+            Some(max) => self.close_wait.frames <= max,
+            None => true,
+        }
+    }
+}
 
-        let (codec, inner, state) = self.framed.into_parts();
+/// The state of a WebSocket connection.
+///
+/// The read, write and fragments states are generic so that the read and write halves of a split connection
+/// can replace the parts they do not need with `()`, instead of carrying empty buffers and unused state.
+/// The write half keeps its [`CloseSignal`] in place of the read state.
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct Core<RW, Rng, R, W, F> {
+    pub codec: FramesCodec<Rng>,
+    pub inner: RW,
+    pub read: R,
+    pub write: W,
+    pub fragments_state: F,
+    pub state: ConnectionState,
+}
 
-        let mut framed = Framed::from_parts(OutRequestCodec::new(), inner, state.reset());
+/// The core of a full WebSocket connection.
+#[doc(hidden)]
+pub type WebSocketCore<'buf, RW, Rng> =
+    Core<RW, Rng, ReadState<'buf>, WriteState<'buf>, FragmentsState<'buf>>;
 
-        framed
-            .send(request)
-            .await
-            .map_err(|err| Error::Write(WriteError::WriteHttp(err)))?;
+/// The core of the read half of a split connection.
+#[doc(hidden)]
+pub type ReadCore<'buf, RW> = Core<RW, (), ReadState<'buf>, (), FragmentsState<'buf>>;
 
-        let (_, inner, state) = framed.into_parts();
+/// The core of the write half of a split connection.
+#[doc(hidden)]
+pub type WriteCore<'buf, RW, Rng> = Core<RW, Rng, Option<&'buf CloseSignal>, WriteState<'buf>, ()>;
+
+/// The result of checking a handshake request.
+struct Accepted<'a, T> {
+    /// The `Sec-WebSocket-Accept` value.
+    accept_key: [u8; 28],
+    /// The selected subprotocol.
+    protocol: Option<&'a str>,
+    /// The accepted extensions.
+    extensions: AcceptedExtensions,
+    /// The `permessage-deflate` extension was accepted.
+    #[cfg(feature = "deflate")]
+    deflate: bool,
+    /// The result of the request callback.
+    custom: T,
+}
 
-        let mut framed = Framed::from_parts(InResponseCodec::<N>::new(), inner, state.reset());
+/// The result of checking a handshake response.
+#[cfg(feature = "client")]
+struct Negotiated<T> {
+    /// The index of the selected subprotocol.
+    protocol: Option<usize>,
+    /// The `permessage-deflate` extension was negotiated.
+    #[cfg(feature = "deflate")]
+    deflate: bool,
+    /// The result of the response callback.
+    custom: T,
+}
 
-        let custom = match framez::next!(framed) {
-            None => {
-                return Err(Error::Handshake(HandshakeError::ConnectionClosed));
-            }
-            Some(Err(err)) => {
-                return Err(Error::Read(ReadError::ReadHttp(err)));
-            }
-            Some(Ok(response)) => {
-                let custom = on_response(&response).map_err(HandshakeError::Other)?;
+/// Checks the buffers of a new connection, so that an invalid configuration fails on construction instead of deep inside a read or write.
+pub(crate) fn check_buffers(
+    read: &[u8],
+    write: &[u8],
+    fragments: &[u8],
+    require_fragments: bool,
+) -> Result<(), ConfigError> {
+    if read.is_empty() {
+        handshake_trace!(warn, "Empty read buffer");
+
+        return Err(ConfigError::EmptyReadBuffer);
+    }
 
-                if !matches!(response.code(), 101) {
-                    return Err(Error::Handshake(HandshakeError::InvalidStatusCode));
-                }
+    if write.is_empty() {
+        handshake_trace!(warn, "Empty write buffer");
 
-                if !response
-                    .headers()
-                    .header_value_str("upgrade")
-                    .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
-                {
-                    return Err(Error::Handshake(HandshakeError::MissingOrInvalidUpgrade));
-                }
+        return Err(ConfigError::EmptyWriteBuffer);
+    }
 
-                if !response
-                    .headers()
-                    .header_value_str("connection")
-                    .is_some_and(|v| v.eq_ignore_ascii_case("upgrade"))
-                {
-                    return Err(Error::Handshake(HandshakeError::MissingOrInvalidConnection));
-                }
+    if require_fragments && fragments.is_empty() {
+        handshake_trace!(warn, "Empty fragments buffer");
 
-                let sec_accept = Self::generate_sec_accept(&sec_key);
+        return Err(ConfigError::EmptyFragmentsBuffer);
+    }
 
-                if response
-                    .headers()
-                    .header_value("sec-websocket-accept")
-                    .is_none_or(|v| v != sec_accept)
-                {
-                    return Err(Error::Handshake(HandshakeError::MissingOrInvalidAccept));
-                }
+    Ok(())
+}
 
-                custom
-            }
-        };
+impl<RW, Rng, R, W, F> Core<RW, Rng, R, W, F> {
+    #[inline]
+    pub(crate) const fn new(
+        codec: FramesCodec<Rng>,
+        inner: RW,
+        read: R,
+        write: W,
+        fragments_state: F,
+    ) -> Self {
+        Self {
+            codec,
+            inner,
+            read,
+            write,
+            fragments_state,
+            state: ConnectionState::new(),
+        }
+    }
 
-        let (_, inner, state) = framed.into_parts();
+    #[inline]
+    #[cfg(feature = "client")]
+    pub(crate) const fn client(inner: RW, rng: Rng, read: R, write: W, fragments_state: F) -> Self {
+        let this =
+            Self::new(FramesCodec::new(rng), inner, read, write, fragments_state).into_client();
 
-        let framed = Framed::from_parts(codec, inner, state);
+        debug_assert!(
+            this.codec.is_client(),
+            "Bug: client must mask and not unmask"
+        );
 
-        Ok((Self::from_framed(framed, self.fragments_state), custom))
+        this
     }
 
-    pub(crate) async fn server_handshake<const N: usize, F, T, E>(
-        self,
-        options: AcceptOptions<'_, '_>,
-        on_request: F,
-    ) -> Result<(Self, T), Error<RW::Error, E>>
-    where
-        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
-        RW: Read + Write,
-    {
-        let (codec, inner, state) = self.framed.into_parts();
+    #[inline]
+    pub(crate) const fn server(inner: RW, rng: Rng, read: R, write: W, fragments_state: F) -> Self {
+        let this =
+            Self::new(FramesCodec::new(rng), inner, read, write, fragments_state).into_server();
 
-        let mut framed = Framed::from_parts(InRequestCodec::<N>::new(), inner, state);
+        debug_assert!(
+            this.codec.is_server(),
+            "Bug: server must unmask and not mask"
+        );
 
-        let (accept_key, custom) = match framez::next!(framed) {
-            None => {
-                return Err(Error::Handshake(HandshakeError::ConnectionClosed));
-            }
-            Some(Err(err)) => {
-                return Err(Error::Read(ReadError::ReadHttp(err)));
-            }
-            Some(Ok(request)) => {
-                let custom = on_request(&request).map_err(HandshakeError::Other)?;
+        this
+    }
 
-                if !matches!(request.method(), "GET") {
-                    return Err(Error::Handshake(HandshakeError::WrongHttpMethod));
+    /// Clients mask the frames they send and expect unmasked frames. (RFC 6455, section 5.1)
+    #[inline]
+    #[cfg(feature = "client")]
+    const fn into_client(mut self) -> Self {
+        self.codec.set_mask(true);
+        self.codec.set_unmask(false);
+        self
+    }
+
+    /// Servers expect masked frames and send unmasked frames. (RFC 6455, section 5.1)
+    #[inline]
+    const fn into_server(mut self) -> Self {
+        self.codec.set_mask(false);
+        self.codec.set_unmask(true);
+        self
+    }
+
+    #[inline]
+    pub(crate) const fn set_auto_pong(&mut self, auto_pong: bool) {
+        self.state.auto.pong = auto_pong;
+    }
+
+    #[inline]
+    pub(crate) const fn set_pong_payload_limit(&mut self, limit: Option<u8>) {
+        self.state.auto.pong_payload_limit = limit;
+    }
+
+    #[inline]
+    pub(crate) const fn set_close_on_fragments_overflow(&mut self, close: bool) {
+        self.state.auto.close_on_fragments_overflow = close;
+    }
+
+    #[inline]
+    pub(crate) const fn set_auto_close(&mut self, auto_close: bool) {
+        self.state.auto.close = auto_close;
+    }
+
+    #[inline]
+    pub(crate) const fn auto_pong(&self) -> bool {
+        self.state.auto.pong
+    }
+
+    #[inline]
+    pub(crate) const fn auto_close(&self) -> bool {
+        self.state.auto.close
+    }
+
+    #[inline]
+    pub(crate) const fn set_max_unanswered_pings(&mut self, max: Option<usize>) {
+        self.state.pings.max = max;
+    }
+
+    #[inline]
+    pub(crate) const fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        self.state.set_keepalive(keepalive);
+    }
+
+    #[inline]
+    pub(crate) const fn keepalive(&self) -> Option<Keepalive> {
+        self.state.keepalive()
+    }
+
+    #[inline]
+    pub(crate) const fn set_close_wait_frames(&mut self, max: Option<usize>) {
+        self.state.close_wait.max = max;
+    }
+
+    #[inline]
+    pub(crate) const fn set_reads_paused(&mut self, paused: bool) {
+        self.state.reads_paused = paused;
+    }
+
+    #[inline]
+    pub(crate) const fn reads_paused(&self) -> bool {
+        self.state.reads_paused
+    }
+
+    #[inline]
+    pub(crate) const fn set_min_read_chunk(&mut self, min: usize) {
+        self.state.min_read_chunk = min;
+    }
+
+    #[inline]
+    pub(crate) const fn min_read_chunk(&self) -> usize {
+        self.state.min_read_chunk
+    }
+
+    #[inline]
+    pub(crate) const fn unanswered_pings(&self) -> usize {
+        self.state.pings.unanswered
+    }
+
+    #[inline]
+    pub(crate) fn queue_ping(&mut self, payload: &[u8]) -> Result<(), QueuePingError> {
+        self.state.queue_ping(payload)
+    }
+
+    #[inline]
+    pub(crate) const fn queued_pings(&self) -> usize {
+        self.state.queued_pings()
+    }
+
+    #[inline]
+    pub(crate) const fn handshake_stats(&self) -> HandshakeStats {
+        self.state.handshake
+    }
+
+    #[inline]
+    pub(crate) const fn last_frame(&self) -> Option<FrameMeta> {
+        self.state.last_frame
+    }
+
+    /// Returns reference to the reader/writer.
+    #[inline]
+    pub(crate) const fn inner(&self) -> &RW {
+        &self.inner
+    }
+
+    /// Returns mutable reference to the reader/writer.
+    #[inline]
+    pub(crate) const fn inner_mut(&mut self) -> &mut RW {
+        &mut self.inner
+    }
+
+    /// Consumes the [`Core`] and returns the reader/writer.
+    #[inline]
+    pub(crate) fn into_inner(self) -> RW {
+        self.inner
+    }
+}
+
+impl<'buf, RW, Rng, W> Core<RW, Rng, ReadState<'buf>, W, FragmentsState<'buf>> {
+    #[inline]
+    pub(crate) const fn set_limits(&mut self, limits: Limits) {
+        self.fragments_state.set_limits(limits);
+        self.codec.set_limits(limits);
+    }
+
+    #[inline]
+    pub(crate) const fn set_max_frame_size(&mut self, max: usize) {
+        self.set_limits(self.fragments_state.limits.with_max_frame(max));
+    }
+
+    #[inline]
+    pub(crate) const fn set_max_message_size(&mut self, max: usize) {
+        self.set_limits(self.fragments_state.limits.with_max_message(max));
+    }
+
+    #[inline]
+    pub(crate) const fn set_message_filter(&mut self, filter: Option<MessageFilter>) {
+        self.fragments_state.set_filter(filter);
+    }
+
+    #[inline]
+    pub(crate) const fn set_integrity_verifier(
+        &mut self,
+        verifier: Option<&'buf dyn IntegrityVerifier>,
+    ) {
+        self.fragments_state.set_verifier(verifier);
+    }
+
+    #[inline]
+    pub(crate) const fn set_progress_observer(
+        &mut self,
+        observer: Option<&'buf dyn ProgressObserver>,
+    ) {
+        self.fragments_state.set_observer(observer);
+    }
+
+    #[inline]
+    pub(crate) const fn set_text_validation(&mut self, validate_text: bool) {
+        self.fragments_state.set_text_validation(validate_text);
+    }
+
+    #[inline]
+    #[cfg(feature = "deflate")]
+    pub(crate) const fn set_inflate(
+        &mut self,
+        inflater: &'buf mut Inflater,
+        buffer: &'buf mut [u8],
+    ) {
+        self.fragments_state.set_inflate(inflater, buffer);
+    }
+
+    /// Returns the number of bytes that can be framed.
+    #[inline]
+    pub(crate) const fn framable(&self) -> usize {
+        self.read.framable()
+    }
+
+    /// Reads the next frame and passes it to `f` as a [`Message`] without consuming it.
+    pub(crate) async fn peek_message<F, T>(
+        &mut self,
+        f: F,
+    ) -> Option<Result<T, MessageError<RW::Error>>>
+    where
+        F: FnOnce(Message<'_>) -> T,
+        RW: Read,
+    {
+        if self.state.reads_paused() {
+            return Some(Err(MessageError::ReadsPaused));
+        }
+
+        if self.fragments_state.fragmented.is_some() {
+            return Some(Err(MessageError::FragmentedPeek));
+        }
+
+        let is_server = self.codec.is_server();
+
+        loop {
+            let mut frame = match crate::functions::maybe_next(
+                &mut self.read,
+                &mut Peek::new(&mut self.codec),
+                &mut self.inner,
+                self.state.min_read_chunk(),
+            )
+            .await
+            {
+                Some(Ok(Some(frame))) => frame,
+                Some(Ok(None)) => continue,
+                Some(Err(framez::ReadError::Decode(FrameDecodeError::FrameTooLarge))) => {
+                    return Some(Err(MessageError::MessageTooLarge));
                 }
+                Some(Err(err)) => return Some(Err(MessageError::ReadFrame(err))),
+                None => return None,
+            };
 
-                // http version must be 1.1 or higher
-                if request.version() < 1 {
-                    return Err(Error::Handshake(HandshakeError::WrongHttpVersion));
+            let message = match frame.opcode() {
+                OpCode::Text | OpCode::Binary | OpCode::Continuation if !frame.is_final() => {
+                    Err(MessageError::FragmentedPeek)
+                }
+                OpCode::Continuation => Err(MessageError::FragmentedPeek),
+                // Peeking does not inflate, the payload can not be passed to `f` as is.
+                OpCode::Text | OpCode::Binary if frame.is_compressed() => {
+                    Err(MessageError::DecompressionFailed)
                 }
+                OpCode::Text if self.fragments_state.validate_text => {
+                    match core::str::from_utf8(frame.payload()) {
+                        Ok(text) => Ok(Message::Text(text)),
+                        Err(_) => Err(MessageError::Protocol {
+                            error: ProtocolError::InvalidUTF8,
+                            frame: self.state.next_frame_sequence(),
+                        }),
+                    }
+                }
+                OpCode::Text => Ok(Message::TextUnchecked(frame.payload())),
+                OpCode::Binary => Ok(Message::Binary(frame.payload())),
+                OpCode::Ping => Ok(Message::Ping(frame.payload())),
+                OpCode::Pong => Ok(Message::Pong(frame.payload())),
+                OpCode::Close => {
+                    let close = Frame::new(frame.is_final(), frame.opcode(), frame.payload());
+
+                    WebSocketCore::<RW, Rng>::extract_close_frame(&close)
+                        .map(Message::Close)
+                        .map_err(|error| MessageError::Protocol {
+                            error,
+                            frame: self.state.next_frame_sequence(),
+                        })
+                }
+            };
 
-                if !request
-                    .headers()
-                    .header_value_str("sec-websocket-version")
-                    .is_some_and(|v| v.eq_ignore_ascii_case("13"))
+            let result = message.map(f);
+
+            // Restore the masked payload to be decoded again by the next read.
+            if is_server {
+                frame.unmask();
+            }
+
+            return Some(result);
+        }
+    }
+
+    #[doc(hidden)]
+    pub const fn auto(
+        &self,
+    ) -> impl FnOnce(FrameMut<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static {
+        let auto = self.state.auto;
+        let closed = self.state.closed;
+
+        move |frame| {
+            // Nothing is sent after a Close frame, pings received while waiting for the peer's Close frame are returned unanswered.
+            if auto.pong && !closed && frame.opcode() == OpCode::Ping {
+                let payload = frame.into_frame().payload();
+
+                let payload = match auto.pong_payload_limit {
+                    Some(limit) if payload.len() > limit as usize => &payload[..limit as usize],
+                    _ => payload,
+                };
+
+                return Ok(OnFrame::Send(Message::Pong(payload)));
+            }
+
+            if auto.close && frame.opcode() == OpCode::Close && !closed {
+                let close_frame =
+                    match WebSocketCore::<RW, Rng>::extract_close_frame(&frame.into_frame()) {
+                        Ok(close_frame) => close_frame,
+                        Err(err) => return Err(err),
+                    };
+
+                match close_frame {
+                    Some(frame) => {
+                        return Ok(OnFrame::Send(Message::Close(Some(frame))));
+                    }
+                    None => {
+                        return Ok(OnFrame::Send(Message::Close(Some(CloseFrame::no_reason(
+                            CloseCode::Normal,
+                        )))));
+                    }
+                }
+            }
+
+            Ok(OnFrame::Noop(frame))
+        }
+    }
+}
+
+impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
+    /// Consumes the [`WebSocketCore`] and returns its codec, reader/writer, read/write state and fragments state.
+    #[inline]
+    fn into_parts(
+        self,
+    ) -> (
+        FramesCodec<Rng>,
+        RW,
+        ReadWriteState<'buf>,
+        FragmentsState<'buf>,
+    ) {
+        (
+            self.codec,
+            self.inner,
+            ReadWriteState::new(self.read, self.write),
+            self.fragments_state,
+        )
+    }
+
+    /// Creates a new [`WebSocketCore`] from its parts with a fresh [`ConnectionState`].
+    #[inline]
+    fn from_parts(
+        codec: FramesCodec<Rng>,
+        inner: RW,
+        state: ReadWriteState<'buf>,
+        fragments_state: FragmentsState<'buf>,
+    ) -> Self {
+        Self::new(codec, inner, state.read, state.write, fragments_state)
+    }
+
+    /// Consumes the [`WebSocketCore`] and returns the read, write and fragments buffers.
+    #[inline]
+    #[cfg(feature = "client")]
+    pub(crate) fn into_buffers(self) -> (&'buf mut [u8], &'buf mut [u8], &'buf mut [u8]) {
+        (
+            self.read.buffer,
+            self.write.buffer,
+            self.fragments_state.fragments_buffer,
+        )
+    }
+
+    /// Checks the buffers of this connection, see [`check_buffers`].
+    #[inline]
+    pub(crate) fn check_buffers(&self, require_fragments: bool) -> Result<(), ConfigError> {
+        check_buffers(
+            self.read.buffer,
+            self.write.buffer,
+            self.fragments_state.fragments_buffer,
+            require_fragments,
+        )
+    }
+
+    /// Checks that the key is a base64-encoded 16-byte value. (RFC 6455)
+    fn is_valid_sec_key(sec_key: &[u8]) -> bool {
+        let mut key: [u8; 16] = [0; 16];
+
+        sec_key.len() == 24
+            && general_purpose::STANDARD
+                .decode_slice(sec_key, &mut key)
+                .is_ok_and(|len| len == 16)
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) async fn client_handshake<const N: usize, F, T, E>(
+        self,
+        options: ConnectOptions<'_, '_>,
+        on_response: F,
+    ) -> Result<(Self, T, ConnectionInfo), Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let (this, result) = self
+            .client_handshake_recoverable::<N, F, T, E>(options, on_response)
+            .await;
+
+        result.map(|(custom, info)| (this, custom, info))
+    }
+
+    /// Performs the client handshake, always handing `self` back so that its buffers can be reused if the handshake fails.
+    #[cfg(feature = "client")]
+    pub(crate) async fn client_handshake_recoverable<const N: usize, F, T, E>(
+        mut self,
+        options: ConnectOptions<'_, '_>,
+        on_response: F,
+    ) -> (Self, Result<(T, ConnectionInfo), Error<RW::Error, E>>)
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        debug_assert!(
+            self.codec.is_client(),
+            "Bug: client handshake performed without the client role"
+        );
+
+        let mut sec_key = generate_sec_key(self.codec.rng_mut());
+
+        let (mut codec, mut inner, state, fragments_state) = self.into_parts();
+
+        let mut counted = Counted::new(&mut inner, options.clock);
+
+        let mut redirect = None;
+
+        let (state, result) = Self::exchange::<N, _, _, _>(
+            &mut counted,
+            &mut codec,
+            state,
+            &mut sec_key,
+            &options,
+            &on_response,
+            &mut redirect,
+        )
+        .await;
+
+        let stats = counted.finish();
+
+        let mut this = Self::from_parts(codec, inner, state, fragments_state);
+
+        this.state.handshake = stats;
+
+        let path = redirect
+            .as_ref()
+            .and_then(Redirect::location_str)
+            .unwrap_or(options.path);
+
+        let result = result.map(|negotiated| {
+            let mut info = ConnectionInfo::new();
+
+            info.set_path_and_origin(path, None);
+            info.set_protocol(negotiated.protocol);
+
+            #[cfg(feature = "deflate")]
+            this.codec.set_deflate(negotiated.deflate);
+
+            (negotiated.custom, info)
+        });
+
+        (this, result)
+    }
+
+    /// Performs the client handshake, parsing the response again with `N2` headers if it has more than `N1` headers.
+    ///
+    /// The response is parsed again from the read buffer, without reading it again from the connection.
+    #[cfg(feature = "client")]
+    pub(crate) async fn client_handshake_adaptive<const N1: usize, const N2: usize>(
+        mut self,
+        options: ConnectOptions<'_, '_>,
+    ) -> (Self, Result<ConnectionInfo, Error<RW::Error>>)
+    where
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        debug_assert!(
+            self.codec.is_client(),
+            "Bug: client handshake performed without the client role"
+        );
+
+        let mut sec_key = generate_sec_key(self.codec.rng_mut());
+
+        let (mut codec, mut inner, state, fragments_state) = self.into_parts();
+
+        let mut counted = Counted::new(&mut inner, options.clock);
+
+        let mut redirect = None;
+
+        let (state, result) = Self::exchange::<N1, _, _, _>(
+            &mut counted,
+            &mut codec,
+            state,
+            &mut sec_key,
+            &options,
+            &|_| Ok(()),
+            &mut redirect,
+        )
+        .await;
+
+        let (state, result) = match result {
+            Err(Error::Read(ReadError::ReadHttp(framez::ReadError::Decode(
+                HttpDecodeError::Parse(httparse::Error::TooManyHeaders),
+            )))) if N2 > N1 => {
+                handshake_trace!(debug, "Too many response headers, retrying with {}", N2);
+
+                Self::read_response::<N2, _, _, _>(&mut counted, state, &sec_key, &options, &|_| {
+                    Ok(())
+                })
+                .await
+            }
+            result => (state, result),
+        };
+
+        let stats = counted.finish();
+
+        let mut this = Self::from_parts(codec, inner, state, fragments_state);
+
+        this.state.handshake = stats;
+
+        let path = redirect
+            .as_ref()
+            .and_then(Redirect::location_str)
+            .unwrap_or(options.path);
+
+        let result = result.map(|negotiated| {
+            let mut info = ConnectionInfo::new();
+
+            info.set_path_and_origin(path, None);
+            info.set_protocol(negotiated.protocol);
+
+            #[cfg(feature = "deflate")]
+            this.codec.set_deflate(negotiated.deflate);
+
+            info
+        });
+
+        (this, result)
+    }
+
+    /// Sends the handshake request and reads the response, following redirects up to [`ConnectOptions::max_redirects`] times.
+    ///
+    /// `redirect` is set to the last redirect followed, whose location is the path of the last request.
+    /// A new `sec_key` is generated for every request.
+    #[cfg(feature = "client")]
+    async fn exchange<const N: usize, F, T, E>(
+        counted: &mut Counted<'_, '_, RW>,
+        codec: &mut FramesCodec<Rng>,
+        mut state: ReadWriteState<'buf>,
+        sec_key: &mut [u8; 24],
+        options: &ConnectOptions<'_, '_>,
+        on_response: &F,
+        redirect: &mut Option<Redirect>,
+    ) -> (
+        ReadWriteState<'buf>,
+        Result<Negotiated<T>, Error<RW::Error, E>>,
+    )
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+        Rng: RngCore,
+    {
+        let mut redirects = 0;
+
+        loop {
+            let path = redirect
+                .as_ref()
+                .and_then(Redirect::location_str)
+                .unwrap_or(options.path);
+
+            let (next, result) =
+                match Self::send_request(counted, state, options, path, sec_key).await {
+                    (next, Ok(())) => {
+                        Self::read_response::<N, _, _, _>(
+                            counted,
+                            next.reset(),
+                            sec_key,
+                            options,
+                            on_response,
+                        )
+                        .await
+                    }
+                    (next, Err(err)) => return (next, Err(err)),
+                };
+
+            state = next;
+
+            let followed = match result {
+                Err(Error::Handshake(HandshakeError::Redirect(followed)))
+                    if redirects < options.max_redirects =>
                 {
-                    return Err(Error::Handshake(HandshakeError::MissingOrInvalidSecVersion));
+                    followed
                 }
+                result => return (state, result),
+            };
+
+            let Some(len) = followed.follow() else {
+                return (
+                    state,
+                    Err(Error::Handshake(HandshakeError::Redirect(followed))),
+                );
+            };
+
+            handshake_trace!(debug, "Following redirect: {}", followed.status());
+
+            let (next, skipped) = Self::skip_body(counted, state, len).await;
+
+            state = next;
+
+            if let Err(err) = skipped {
+                return (state, Err(err));
+            }
+
+            *redirect = Some(followed);
+            *sec_key = generate_sec_key(codec.rng_mut());
+
+            redirects += 1;
+        }
+    }
+
+    /// Discards `len` bytes of a response body, starting with the bytes already in the read buffer.
+    #[cfg(feature = "client")]
+    async fn skip_body<E>(
+        counted: &mut Counted<'_, '_, RW>,
+        state: ReadWriteState<'buf>,
+        len: usize,
+    ) -> (ReadWriteState<'buf>, Result<(), Error<RW::Error, E>>)
+    where
+        RW: Read,
+    {
+        if len == 0 {
+            return (state, Ok(()));
+        }
+
+        let mut framed = Framed::from_parts(SkipBodyCodec::new(len), counted, state);
+
+        let result = loop {
+            match framez::next!(framed) {
+                None => break Err(Error::Handshake(HandshakeError::ConnectionClosed)),
+                Some(Err(err)) => break Err(Error::Read(ReadError::ReadHttp(err))),
+                Some(Ok(0)) => break Ok(()),
+                Some(Ok(_)) => {}
+            }
+        };
+
+        let (_, _, state) = framed.into_parts();
+
+        (state, result)
+    }
+
+    /// Sends the handshake request for `path`.
+    #[cfg(feature = "client")]
+    async fn send_request<E>(
+        counted: &mut Counted<'_, '_, RW>,
+        state: ReadWriteState<'buf>,
+        options: &ConnectOptions<'_, '_>,
+        path: &str,
+        sec_key: &[u8],
+    ) -> (ReadWriteState<'buf>, Result<(), Error<RW::Error, E>>)
+    where
+        RW: Write,
+    {
+        let (headers, len) = connect_request_headers(options, sec_key);
+        let headers = &headers[..len];
+
+        let request = OutRequest::get_unchecked(
+            path,
+            headers,
+            options.headers,
+            options.dynamic_headers,
+            options.protocols,
+        );
+
+        let mut framed = Framed::from_parts(OutRequestCodec::new(), counted, state.reset());
+
+        let sent = framed
+            .send(request)
+            .await
+            .map_err(|err| Error::Write(WriteError::WriteHttp(err)));
+
+        if sent.is_err() {
+            handshake_trace!(warn, "Failed to send handshake request");
+        }
+
+        let (_, _, state) = framed.into_parts();
+
+        (state, sent)
+    }
+
+    /// Reads and checks the handshake response, starting with the bytes already in the read buffer.
+    #[cfg(feature = "client")]
+    async fn read_response<const N: usize, F, T, E>(
+        counted: &mut Counted<'_, '_, RW>,
+        state: ReadWriteState<'buf>,
+        sec_key: &[u8],
+        options: &ConnectOptions<'_, '_>,
+        on_response: &F,
+    ) -> (
+        ReadWriteState<'buf>,
+        Result<Negotiated<T>, Error<RW::Error, E>>,
+    )
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read,
+    {
+        let mut framed = Framed::from_parts(InResponseCodec::<N>::new(), counted, state);
+
+        let result = match framez::next!(framed) {
+            None => {
+                handshake_trace!(warn, "Connection closed before handshake response");
+
+                Err(Error::Handshake(HandshakeError::ConnectionClosed))
+            }
+            Some(Err(err)) => {
+                handshake_trace!(warn, "Failed to read handshake response");
+
+                Err(Error::Read(ReadError::ReadHttp(err)))
+            }
+            Some(Ok(response)) => Self::check_response(&response, sec_key, options, on_response),
+        };
+
+        let (_, _, state) = framed.into_parts();
+
+        (state, result)
+    }
+
+    #[cfg(feature = "client")]
+    fn check_response<const N: usize, F, T, E>(
+        response: &Response<'_, N>,
+        sec_key: &[u8],
+        options: &ConnectOptions<'_, '_>,
+        on_response: &F,
+    ) -> Result<Negotiated<T>, Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Response<'a, N>) -> Result<T, E>,
+        RW: Read,
+    {
+        handshake_trace!(debug, "Handshake response status: {}", response.code());
+
+        let custom = on_response(response).map_err(|err| {
+            handshake_trace!(warn, "Handshake response rejected by callback");
+
+            HandshakeError::Other(err)
+        })?;
+
+        if !matches!(response.code(), 101) {
+            handshake_trace!(warn, "Invalid handshake status code: {}", response.code());
+
+            if let Some(redirect) = Redirect::from_response(response) {
+                return Err(Error::Handshake(HandshakeError::Redirect(redirect)));
+            }
+
+            if let (426, Some(versions)) = (
+                response.code(),
+                response.headers().header_value("sec-websocket-version"),
+            ) {
+                return Err(Error::Handshake(HandshakeError::UpgradeRequired(
+                    SecVersion::new(versions),
+                )));
+            }
+
+            return Err(Error::Handshake(HandshakeError::InvalidStatusCode));
+        }
+
+        if !response
+            .headers()
+            .header_value_str("upgrade")
+            .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+        {
+            handshake_trace!(warn, "Missing or invalid header: {}", "upgrade");
+
+            return Err(Error::Handshake(HandshakeError::MissingOrInvalidUpgrade));
+        }
+
+        if !response
+            .headers()
+            .header_value_str("connection")
+            .is_some_and(|v| v.eq_ignore_ascii_case("upgrade"))
+        {
+            handshake_trace!(warn, "Missing or invalid header: {}", "connection");
+
+            return Err(Error::Handshake(HandshakeError::MissingOrInvalidConnection));
+        }
+
+        let sec_accept = generate_sec_accept(sec_key);
+
+        if response
+            .headers()
+            .header_value("sec-websocket-accept")
+            .is_none_or(|v| v != sec_accept)
+        {
+            handshake_trace!(
+                warn,
+                "Missing or invalid header: {}",
+                "sec-websocket-accept"
+            );
+
+            return Err(Error::Handshake(HandshakeError::MissingOrInvalidAccept));
+        }
+
+        let protocols = options.protocols;
+
+        // Without offered subprotocols, the header is left to the user's callback.
+        let protocol = if protocols.is_empty() {
+            None
+        } else {
+            let mut selected = response.header_values("sec-websocket-protocol");
+
+            match (selected.next(), selected.next()) {
+                (None, _) => None,
+                (Some(selected), None) => match protocols.iter().position(|p| *p == selected) {
+                    Some(index) => Some(index),
+                    None => {
+                        handshake_trace!(warn, "Subprotocol not offered: {}", selected);
+
+                        return Err(Error::Handshake(HandshakeError::InvalidProtocol));
+                    }
+                },
+                (Some(_), Some(_)) => {
+                    handshake_trace!(warn, "Multiple subprotocols selected");
 
-                let sec_key = request
+                    return Err(Error::Handshake(HandshakeError::InvalidProtocol));
+                }
+            }
+        };
+
+        // Without the offer, the header is left to the user's callback.
+        #[cfg(feature = "deflate")]
+        let deflate = {
+            let mut answered = response
+                .extensions()
+                .filter(|extension| extension.name().eq_ignore_ascii_case(crate::deflate::NAME));
+
+            match (answered.next(), answered.next()) {
+                (None, _) => false,
+                (Some(_), _) if !options.permessage_deflate => false,
+                (Some(answer), None) if crate::deflate::is_valid_response(&answer) => true,
+                _ => {
+                    handshake_trace!(warn, "Invalid extension response: {}", "permessage-deflate");
+
+                    return Err(Error::Handshake(HandshakeError::InvalidExtension));
+                }
+            }
+        };
+
+        handshake_trace!(debug, "Client handshake completed");
+
+        Ok(Negotiated {
+            protocol,
+            #[cfg(feature = "deflate")]
+            deflate,
+            custom,
+        })
+    }
+
+    pub(crate) async fn server_handshake<const N: usize, F, T, E>(
+        self,
+        options: AcceptOptions<'_, '_>,
+        on_request: F,
+    ) -> Result<(Self, T, ConnectionInfo), Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+    {
+        let (this, result) = self
+            .server_handshake_recoverable::<N, F, T, E>(options, on_request)
+            .await;
+
+        result.map(|(custom, info)| (this, custom, info))
+    }
+
+    /// Performs the server handshake, always handing `self` back so that further requests can be read from the same connection if the handshake fails.
+    pub(crate) async fn server_handshake_recoverable<const N: usize, F, T, E>(
+        self,
+        options: AcceptOptions<'_, '_>,
+        on_request: F,
+    ) -> (Self, Result<(T, ConnectionInfo), Error<RW::Error, E>>)
+    where
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read + Write,
+    {
+        debug_assert!(
+            self.codec.is_server(),
+            "Bug: server handshake performed without the server role"
+        );
+
+        let (codec, mut inner, state, fragments_state) = self.into_parts();
+
+        let mut counted = Counted::new(&mut inner, options.clock);
+
+        let mut framed = Framed::from_parts(InRequestCodec::<N>::new(), &mut counted, state);
+
+        let mut info = ConnectionInfo::new();
+
+        let result = match framez::next!(framed) {
+            None => {
+                handshake_trace!(warn, "Connection closed before handshake request");
+
+                Err(Error::Handshake(HandshakeError::ConnectionClosed))
+            }
+            Some(Err(err)) => {
+                handshake_trace!(warn, "Failed to read handshake request");
+
+                Err(Error::Read(ReadError::ReadHttp(err)))
+            }
+            Some(Ok(request)) => {
+                let origin = request
                     .headers()
-                    .header_value("sec-websocket-key")
-                    .ok_or(Error::Handshake(HandshakeError::MissingSecKey))?;
+                    .header_value("origin")
+                    .and_then(|origin| core::str::from_utf8(origin).ok());
+
+                info.set_path_and_origin(request.path(), origin);
+
+                Self::check_request(&request, &options, &on_request)
+            }
+        };
+
+        let (_, _, state) = framed.into_parts();
+
+        // Bytes following the request are kept in the read state and framed after the handshake.
+        let result = match result {
+            Ok(_) if options.reject_early_frames && state.read.framable() > 0 => {
+                handshake_trace!(warn, "Frames received before handshake response");
+
+                Err(Error::Handshake(HandshakeError::EarlyFrames))
+            }
+            result => result,
+        };
 
-                (Self::generate_sec_accept(sec_key), custom)
+        let Accepted {
+            accept_key,
+            protocol,
+            extensions,
+            #[cfg(feature = "deflate")]
+            deflate,
+            custom,
+        } = match result {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                return (
+                    Self::from_parts(codec, inner, state, fragments_state),
+                    Err(err),
+                );
             }
         };
 
@@ -401,57 +1701,263 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                 name: "sec-websocket-accept",
                 value: &accept_key,
             },
+            Header {
+                name: "sec-websocket-protocol",
+                value: protocol.map_or(&[], str::as_bytes),
+            },
         ];
 
-        let response = OutResponse::switching_protocols(headers, options.headers);
+        let headers = match protocol {
+            Some(_) => &headers[..],
+            None => &headers[..4],
+        };
 
-        let (_, inner, state) = framed.into_parts();
+        #[cfg(feature = "deflate")]
+        let deflate_headers = &mut [Header {
+            name: "",
+            value: &[],
+        }; 6];
 
-        let mut framed = Framed::from_parts(OutResponseCodec::new(), inner, state);
+        // Answered in its own header line, after the accepted extensions of the user's table.
+        #[cfg(feature = "deflate")]
+        let headers = match deflate {
+            true => {
+                deflate_headers[..headers.len()].copy_from_slice(headers);
+                deflate_headers[headers.len()] = crate::deflate::RESPONSE;
 
-        framed
-            .send(response)
-            .await
-            .map_err(|err| Error::Write(WriteError::WriteHttp(err)))?;
+                &deflate_headers[..=headers.len()]
+            }
+            false => headers,
+        };
+
+        let response = OutResponse::switching_protocols(headers, options.headers)
+            .with_extensions(options.extensions, extensions);
 
-        let (_, inner, state) = framed.into_parts();
+        let mut framed = Framed::from_parts(OutResponseCodec::new(), &mut counted, state);
 
-        let framed = Framed::from_parts(codec, inner, state);
+        let sent = framed.send(response).await;
 
-        Ok((Self::from_framed(framed, self.fragments_state), custom))
+        let (_, _, state) = framed.into_parts();
+
+        let stats = counted.finish();
+
+        let mut this = Self::from_parts(codec, inner, state, fragments_state);
+
+        this.state.handshake = stats;
+
+        info.set_protocol(protocol.and_then(|protocol| {
+            options
+                .protocols
+                .iter()
+                .position(|supported| *supported == protocol)
+        }));
+        info.set_extensions(extensions);
+
+        #[cfg(feature = "deflate")]
+        this.codec.set_deflate(deflate);
+
+        if let Err(err) = sent {
+            handshake_trace!(warn, "Failed to send handshake response");
+
+            return (this, Err(Error::Write(WriteError::WriteHttp(err))));
+        }
+
+        handshake_trace!(debug, "Server handshake completed");
+
+        (this, Ok((custom, info)))
     }
 
-    #[doc(hidden)]
-    pub const fn auto(
-        &self,
-    ) -> impl FnOnce(Frame<'_>) -> Result<OnFrame<'_>, ProtocolError> + 'static {
-        let state = self.state;
+    fn check_request<'o, const N: usize, F, T, E>(
+        request: &Request<'_, N>,
+        options: &AcceptOptions<'o, '_>,
+        on_request: &F,
+    ) -> Result<Accepted<'o, T>, Error<RW::Error, E>>
+    where
+        F: for<'a> Fn(&Request<'a, N>) -> Result<T, E>,
+        RW: Read,
+    {
+        handshake_trace!(
+            debug,
+            "Handshake request: {} {}",
+            request.method(),
+            request.path()
+        );
 
-        move |frame| {
-            if state.auto.pong && frame.opcode() == OpCode::Ping {
-                return Ok(OnFrame::Send(Message::Pong(frame.payload())));
+        let custom = on_request(request).map_err(|err| {
+            handshake_trace!(warn, "Handshake request rejected by callback");
+
+            HandshakeError::Other(err)
+        })?;
+
+        if !matches!(request.method(), "GET") {
+            handshake_trace!(warn, "Wrong handshake method: {}", request.method());
+
+            return Err(Error::Handshake(HandshakeError::WrongHttpMethod));
+        }
+
+        // http version must be 1.1 or higher
+        if request.version() < 1 {
+            handshake_trace!(warn, "Wrong HTTP version: 1.{}", request.version());
+
+            return Err(Error::Handshake(HandshakeError::WrongHttpVersion));
+        }
+
+        if options.strict_request {
+            Self::check_request_strict(request).map_err(Error::Handshake)?;
+        }
+
+        match request.headers().header_value("sec-websocket-version") {
+            None => {
+                handshake_trace!(
+                    warn,
+                    "Missing or invalid header: {}",
+                    "sec-websocket-version"
+                );
+
+                return Err(Error::Handshake(HandshakeError::MissingOrInvalidSecVersion));
+            }
+            Some(version) if version.trim_ascii() != b"13" => {
+                handshake_trace!(
+                    warn,
+                    "Missing or invalid header: {}",
+                    "sec-websocket-version"
+                );
+
+                return Err(Error::Handshake(HandshakeError::UnsupportedSecVersion(
+                    SecVersion::new(version),
+                )));
             }
+            Some(_) => {}
+        }
 
-            if state.auto.close && frame.opcode() == OpCode::Close && !state.closed {
-                let close_frame = match Self::extract_close_frame(&frame) {
-                    Ok(close_frame) => close_frame,
-                    Err(err) => return Err(err),
-                };
+        let sec_key = request
+            .headers()
+            .header_value("sec-websocket-key")
+            .ok_or_else(|| {
+                handshake_trace!(warn, "Missing or invalid header: {}", "sec-websocket-key");
 
-                match close_frame {
-                    Some(frame) => {
-                        return Ok(OnFrame::Send(Message::Close(Some(frame))));
-                    }
-                    None => {
-                        return Ok(OnFrame::Send(Message::Close(Some(CloseFrame::no_reason(
-                            CloseCode::Normal,
-                        )))));
-                    }
+                Error::Handshake(HandshakeError::MissingSecKey)
+            })?;
+
+        if !options.lenient_sec_key && !Self::is_valid_sec_key(sec_key) {
+            handshake_trace!(warn, "Missing or invalid header: {}", "sec-websocket-key");
+
+            return Err(Error::Handshake(HandshakeError::InvalidSecKey));
+        }
+
+        let is_offered = |protocol: &str| {
+            request
+                .header_values("sec-websocket-protocol")
+                .any(|offered| offered == protocol)
+        };
+
+        let protocol = match options.protocol_selector {
+            Some(select) => select(
+                request.path(),
+                request.headers(),
+                request.header_values("sec-websocket-protocol"),
+            )
+            .filter(|selected| {
+                let offered = is_offered(selected);
+
+                if !offered {
+                    handshake_trace!(warn, "Selected subprotocol not offered: {}", selected);
                 }
-            }
 
-            Ok(OnFrame::Noop(frame))
+                offered
+            }),
+            None => options
+                .protocols
+                .iter()
+                .copied()
+                .find(|protocol| is_offered(protocol)),
+        };
+
+        #[cfg(feature = "deflate")]
+        let deflate = options.permessage_deflate
+            && request
+                .extensions()
+                .any(|offer| crate::deflate::is_acceptable_offer(&offer));
+
+        // With built-in compression enabled, `permessage-deflate` is negotiated above and never accepted from the table,
+        // so it is not answered twice.
+        #[cfg(feature = "deflate")]
+        let extensions = AcceptedExtensions::negotiate(
+            request.extensions().filter(|offer| {
+                !(options.permessage_deflate
+                    && offer.name().eq_ignore_ascii_case(crate::deflate::NAME))
+            }),
+            options.extensions,
+        );
+
+        #[cfg(not(feature = "deflate"))]
+        let extensions = AcceptedExtensions::negotiate(request.extensions(), options.extensions);
+
+        Ok(Accepted {
+            accept_key: generate_sec_accept(sec_key),
+            protocol,
+            extensions,
+            #[cfg(feature = "deflate")]
+            deflate,
+            custom,
+        })
+    }
+
+    /// Checks the `Host` header and the path, see [`AcceptOptions::with_strict_request`].
+    fn check_request_strict<const N: usize, E>(
+        request: &Request<'_, N>,
+    ) -> Result<(), HandshakeError<E>> {
+        let mut hosts = request
+            .headers()
+            .iter()
+            .filter(|header| header.name.eq_ignore_ascii_case("host"));
+
+        let host_valid = match (hosts.next(), hosts.next()) {
+            (Some(host), None) => !host.value.trim_ascii().is_empty(),
+            _ => false,
+        };
+
+        // HTTP/1.0 requests are rejected before, the check applies to HTTP/1.1.
+        if request.version() == 1 && !host_valid {
+            handshake_trace!(warn, "Missing or invalid header: {}", "host");
+
+            return Err(HandshakeError::MissingOrInvalidHost);
+        }
+
+        if !request.path().starts_with('/') || !is_valid_path(request.path()) {
+            handshake_trace!(warn, "Invalid request path: {}", request.path());
+
+            return Err(HandshakeError::InvalidPath);
         }
+
+        Ok(())
+    }
+
+    /// Sends an HTTP response without a body, e.g. to reject a failed upgrade request while keeping the connection alive.
+    pub(crate) async fn send_http_response(
+        &mut self,
+        code: &str,
+        reason: &str,
+        headers: &[Header<'_>],
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        let content_length = &[Header {
+            name: "content-length",
+            value: b"0",
+        }];
+
+        let response = OutResponse::new(code, reason, content_length, headers);
+
+        framez::functions::send(
+            &mut self.write,
+            &mut OutResponseCodec::new(),
+            &mut self.inner,
+            response,
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteHttp(err)))
     }
 
     fn extract_close_frame<'this>(
@@ -485,10 +1991,138 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
         Ok(None)
     }
 
+    /// Runs the message filter, if any, on the first frame of a data message.
+    fn is_rejected(
+        fragments_state: &FragmentsState<'_>,
+        frame: &FrameMut<'_>,
+        sequence: u64,
+    ) -> bool {
+        fragments_state.filter.is_some_and(|filter| {
+            filter(&FirstFrame::new(
+                sequence,
+                frame.is_final(),
+                frame.opcode(),
+                frame.payload(),
+            )) == Verdict::Reject
+        })
+    }
+
+    /// Handles a received frame like [`WebSocketCore::on_frame`], but passes the frames of fragmented data messages through
+    /// instead of reassembling them in the fragments buffer.
+    ///
+    /// Messages compressed with `permessage-deflate` are still reassembled, since they can only be decompressed as a whole.
+    pub(crate) fn on_fragment<'this>(
+        fragments_state: &'this mut FragmentsState<'_>,
+        frame: FrameMut<'this>,
+        sequence: u64,
+    ) -> Option<Result<Option<StreamItem<'this>>, OnFrameError>> {
+        #[cfg(feature = "deflate")]
+        let compressed = frame.is_compressed();
+        #[cfg(not(feature = "deflate"))]
+        let compressed = false;
+
+        let passthrough = match frame.opcode() {
+            OpCode::Text | OpCode::Binary => {
+                !frame.is_final() && !compressed && fragments_state.fragmented.is_none()
+            }
+            OpCode::Continuation => fragments_state
+                .fragmented
+                .is_some_and(|fragmented| fragmented.passthrough),
+            _ => false,
+        };
+
+        if !passthrough {
+            return Self::on_frame(fragments_state, frame, sequence).map(|result| {
+                result.map(|message| {
+                    message.map(|message| StreamItem::Message(message.into_message()))
+                })
+            });
+        }
+
+        let fin = frame.is_final();
+        let len = frame.payload().len();
+
+        if let Some(fragmented) = fragments_state.fragmented.as_mut() {
+            if !fin && len == 0 {
+                fragmented.empty += 1;
+
+                if fragments_state
+                    .limits
+                    .exceeds_empty_fragments(fragmented.empty)
+                {
+                    return Some(Err(OnFrameError::TooManyEmptyFragments));
+                }
+            }
+
+            if fragments_state
+                .limits
+                .exceeds(fragmented.opcode, fragmented.index + len)
+            {
+                return Some(Err(OnFrameError::MessageTooLarge));
+            }
+
+            fragmented.index += len;
+
+            let text = fragmented.opcode == OpCode::Text;
+
+            if fin {
+                fragments_state.fragmented = None;
+            }
+
+            return Some(Ok(Some(StreamItem::Fragment(Fragment::new(
+                text,
+                false,
+                fin,
+                frame.into_payload(),
+            )))));
+        }
+
+        if fragments_state.limits.exceeds(frame.opcode(), len) {
+            return Some(Err(OnFrameError::MessageTooLarge));
+        }
+
+        let empty = usize::from(len == 0);
+
+        if fragments_state.limits.exceeds_empty_fragments(empty) {
+            return Some(Err(OnFrameError::TooManyEmptyFragments));
+        }
+
+        let rejected = Self::is_rejected(fragments_state, &frame, sequence);
+
+        fragments_state.fragmented = Some(Fragmented {
+            opcode: frame.opcode(),
+            #[cfg(feature = "deflate")]
+            compressed: false,
+            index: len,
+            rejected,
+            passthrough: !rejected,
+            empty,
+        });
+
+        if rejected {
+            return Some(Ok(None));
+        }
+
+        Some(Ok(Some(StreamItem::Fragment(Fragment::new(
+            frame.opcode() == OpCode::Text,
+            true,
+            false,
+            frame.into_payload(),
+        )))))
+    }
+
+    /// Handles a received frame, `sequence` being its number on the connection.
     pub(crate) fn on_frame<'this>(
         fragments_state: &'this mut FragmentsState<'_>,
-        frame: Frame<'this>,
-    ) -> Option<Result<Option<Message<'this>>, OnFrameError>> {
+        frame: FrameMut<'this>,
+        sequence: u64,
+    ) -> Option<Result<Option<MessageMut<'this>>, OnFrameError>> {
+        let len = frame.payload().len();
+
+        if fragments_state.limits.exceeds(frame.opcode(), len) {
+            return Some(Err(OnFrameError::MessageTooLarge));
+        }
+
         match frame.opcode() {
             OpCode::Text | OpCode::Binary => {
                 if frame.is_final() {
@@ -496,13 +2130,48 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                         return Some(Err(OnFrameError::Protocol(ProtocolError::InvalidFragment)));
                     }
 
-                    match frame.opcode() {
+                    if Self::is_rejected(fragments_state, &frame, sequence) {
+                        return Some(Ok(None));
+                    }
+
+                    let opcode = frame.opcode();
+
+                    #[cfg(feature = "deflate")]
+                    let compressed = frame.is_compressed();
+
+                    let payload = frame.into_payload();
+
+                    #[cfg(feature = "deflate")]
+                    let payload = match compressed {
+                        false => payload,
+                        true => match Inflate::inflate(
+                            fragments_state.inflate.as_mut(),
+                            &fragments_state.limits,
+                            opcode,
+                            payload,
+                        ) {
+                            Ok(payload) => payload,
+                            Err(err) => return Some(Err(err)),
+                        },
+                    };
+
+                    match opcode {
                         OpCode::Binary => {
-                            return Some(Ok(Some(Message::Binary(frame.payload()))));
+                            if fragments_state
+                                .verifier
+                                .is_some_and(|verifier| !verifier.0.verify(payload))
+                            {
+                                return Some(Err(OnFrameError::IntegrityCheckFailed));
+                            }
+
+                            return Some(Ok(Some(MessageMut::Binary(payload))));
+                        }
+                        OpCode::Text if !fragments_state.validate_text => {
+                            return Some(Ok(Some(MessageMut::TextUnchecked(payload))));
                         }
-                        OpCode::Text => match core::str::from_utf8(frame.payload()) {
+                        OpCode::Text => match core::str::from_utf8_mut(payload) {
                             Ok(text) => {
-                                return Some(Ok(Some(Message::Text(text))));
+                                return Some(Ok(Some(MessageMut::Text(text))));
                             }
                             Err(_) => {
                                 return Some(Err(OnFrameError::Protocol(
@@ -514,6 +2183,36 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                     }
                 }
 
+                if let Some(fragmented) = fragments_state.fragmented.as_ref() {
+                    if fragmented.opcode != frame.opcode() {
+                        return Some(Err(OnFrameError::Protocol(
+                            ProtocolError::FragmentOpcodeMismatch,
+                        )));
+                    }
+
+                    return Some(Err(OnFrameError::Protocol(ProtocolError::InvalidFragment)));
+                }
+
+                let empty = usize::from(frame.payload().is_empty());
+
+                if fragments_state.limits.exceeds_empty_fragments(empty) {
+                    return Some(Err(OnFrameError::TooManyEmptyFragments));
+                }
+
+                if Self::is_rejected(fragments_state, &frame, sequence) {
+                    fragments_state.fragmented = Some(Fragmented {
+                        opcode: frame.opcode(),
+                        #[cfg(feature = "deflate")]
+                        compressed: frame.is_compressed(),
+                        index: 0,
+                        rejected: true,
+                        passthrough: false,
+                        empty,
+                    });
+
+                    return Some(Ok(None));
+                }
+
                 if frame.payload().len() > fragments_state.fragments_buffer.len() {
                     return Some(Err(OnFrameError::FragmentsBufferTooSmall));
                 }
@@ -521,10 +2220,18 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                 fragments_state.fragments_buffer[..frame.payload().len()]
                     .copy_from_slice(frame.payload());
 
-                fragments_state.fragmented = Some(Fragmented {
+                let fragmented = Fragmented {
                     opcode: frame.opcode(),
+                    #[cfg(feature = "deflate")]
+                    compressed: frame.is_compressed(),
                     index: frame.payload().len(),
-                });
+                    rejected: false,
+                    passthrough: false,
+                    empty,
+                };
+
+                fragments_state.observe(&fragmented, false);
+                fragments_state.fragmented = Some(fragmented);
             }
             OpCode::Continuation => {
                 let message = match fragments_state.fragmented.as_mut() {
@@ -534,6 +2241,33 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                         )));
                     }
                     Some(fragmented) => {
+                        if !frame.is_final() && frame.payload().is_empty() {
+                            fragmented.empty += 1;
+
+                            if fragments_state
+                                .limits
+                                .exceeds_empty_fragments(fragmented.empty)
+                            {
+                                return Some(Err(OnFrameError::TooManyEmptyFragments));
+                            }
+                        }
+
+                        // Fragments of a message partially passed through are discarded as well.
+                        if fragmented.rejected || fragmented.passthrough {
+                            if frame.is_final() {
+                                fragments_state.fragmented = None;
+                            }
+
+                            return Some(Ok(None));
+                        }
+
+                        if fragments_state
+                            .limits
+                            .exceeds(fragmented.opcode, fragmented.index + frame.payload().len())
+                        {
+                            return Some(Err(OnFrameError::MessageTooLarge));
+                        }
+
                         if fragmented.index + frame.payload().len()
                             > fragments_state.fragments_buffer.len()
                         {
@@ -546,23 +2280,55 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
 
                         fragmented.index += frame.payload().len();
 
+                        let fragmented = *fragmented;
+
+                        fragments_state.observe(&fragmented, frame.is_final());
+
                         if frame.is_final() {
+                            let payload = &mut fragments_state.fragments_buffer[..fragmented.index];
+
+                            #[cfg(feature = "deflate")]
+                            let payload = match fragmented.compressed {
+                                false => payload,
+                                true => match Inflate::inflate(
+                                    fragments_state.inflate.as_mut(),
+                                    &fragments_state.limits,
+                                    fragmented.opcode,
+                                    payload,
+                                ) {
+                                    Ok(payload) => payload,
+                                    Err(err) => {
+                                        fragments_state.fragmented = None;
+
+                                        return Some(Err(err));
+                                    }
+                                },
+                            };
+
                             match fragmented.opcode {
-                                OpCode::Text => {
-                                    match core::str::from_utf8(
-                                        &fragments_state.fragments_buffer[..fragmented.index],
-                                    ) {
-                                        Ok(text) => Some(Message::Text(text)),
-                                        Err(_) => {
-                                            return Some(Err(OnFrameError::Protocol(
-                                                ProtocolError::InvalidUTF8,
-                                            )));
-                                        }
+                                OpCode::Text if !fragments_state.validate_text => {
+                                    Some(MessageMut::TextUnchecked(payload))
+                                }
+                                OpCode::Text => match core::str::from_utf8_mut(payload) {
+                                    Ok(text) => Some(MessageMut::Text(text)),
+                                    Err(_) => {
+                                        return Some(Err(OnFrameError::Protocol(
+                                            ProtocolError::InvalidUTF8,
+                                        )));
                                     }
+                                },
+                                OpCode::Binary => {
+                                    if fragments_state
+                                        .verifier
+                                        .is_some_and(|verifier| !verifier.0.verify(payload))
+                                    {
+                                        fragments_state.fragmented = None;
+
+                                        return Some(Err(OnFrameError::IntegrityCheckFailed));
+                                    }
+
+                                    Some(MessageMut::Binary(payload))
                                 }
-                                OpCode::Binary => Some(Message::Binary(
-                                    &fragments_state.fragments_buffer[..fragmented.index],
-                                )),
                                 _ => unreachable!(
                                     "Opcode can only be set to OpCode::Text | OpCode::Binary in the first match branch"
                                 ),
@@ -580,39 +2346,311 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
                 }
             }
             OpCode::Close => {
-                let close_frame = match Self::extract_close_frame(&frame) {
+                let close_frame = match Self::extract_close_frame(&frame.into_frame()) {
                     Ok(close_frame) => close_frame,
                     Err(err) => return Some(Err(OnFrameError::Protocol(err))),
                 };
 
-                return Some(Ok(Some(Message::Close(close_frame))));
+                return Some(Ok(Some(MessageMut::Close(close_frame))));
             }
             OpCode::Ping => {
-                return Some(Ok(Some(Message::Ping(frame.payload()))));
+                return Some(Ok(Some(MessageMut::Ping(frame.into_payload()))));
             }
             OpCode::Pong => {
-                return Some(Ok(Some(Message::Pong(frame.payload()))));
+                return Some(Ok(Some(MessageMut::Pong(frame.into_payload()))));
             }
         }
 
         Some(Ok(None))
     }
+}
+
+impl<'buf, RW, Rng, R, F> Core<RW, Rng, R, WriteState<'buf>, F> {
+    pub(crate) const fn message_writer(
+        &mut self,
+        opcode: OpCode,
+        close_signal: Option<&'buf CloseSignal>,
+    ) -> MessageWriter<'_, 'buf, RW, Rng> {
+        MessageWriter::new(
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
+            &mut self.state,
+            close_signal,
+            opcode,
+        )
+    }
+
+    pub(crate) fn encode_message_into(
+        &mut self,
+        message: Message<'_>,
+        dst: &mut [u8],
+    ) -> Result<usize, FrameEncodeError>
+    where
+        Rng: RngCore,
+    {
+        framez::encode::Encoder::encode(&mut self.codec, message, dst)
+    }
+
+    /// Sends the Close frame requested through the [`CloseSignal`] of a write half, if any and if none has been sent yet.
+    ///
+    /// Every method sending a frame calls it first.
+    pub(crate) async fn send_requested_close(&mut self) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        crate::functions::send_requested_close(
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
+            &mut self.state,
+            self.read.close_signal(),
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))
+    }
 
     pub(crate) async fn send(&mut self, message: Message<'_>) -> Result<(), Error<RW::Error>>
     where
         RW: Write,
         Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
     {
+        self.send_requested_close().await?;
+
         crate::functions::send(
-            &mut self.framed.core.codec,
-            &mut self.framed.core.inner,
-            &mut self.framed.core.state.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
+            &mut self.state,
+            message,
+        )
+        .await
+    }
+
+    pub(crate) async fn tick(&mut self, clock: &dyn Clock) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        let now = clock.now();
+
+        match self.state.on_tick(now, clock.tick_hz()) {
+            KeepaliveTick::Idle => Ok(()),
+            KeepaliveTick::Timeout => Err(Error::PingTimeout),
+            KeepaliveTick::Ping => {
+                self.send(Message::Ping(&[])).await?;
+                self.state.on_keepalive_ping_sent(now);
+
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) async fn send_without_flush(
+        &mut self,
+        message: Message<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        self.send_requested_close().await?;
+
+        crate::functions::send_without_flush(
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
             &mut self.state,
             message,
         )
         .await
     }
 
+    pub(crate) async fn flush(&mut self) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+    {
+        crate::functions::flush(&mut self.inner).await
+    }
+
+    pub(crate) async fn send_close_and_flush(
+        &mut self,
+        frame: Option<CloseFrame<'_>>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        self.send_requested_close().await?;
+
+        if self.state.closed {
+            return Ok(());
+        }
+
+        // Sending flushes the writer.
+        self.send(Message::Close(frame)).await
+    }
+
+    pub(crate) async fn send_compressed_binary<C>(
+        &mut self,
+        payload: &[u8],
+        codec: &C,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+        C: PayloadCodec,
+    {
+        // Compressed right after the space reserved for the frame header, without another buffer.
+        self.send_staged(|dst| crate::compression::compress(codec, payload, dst))
+            .await
+    }
+
+    pub(crate) async fn send_binary_with<W>(
+        &mut self,
+        len: usize,
+        write: W,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+        W: FnOnce(&mut [u8]),
+    {
+        self.send_staged(|dst| {
+            let dst = dst.get_mut(..len)?;
+
+            write(dst);
+
+            Some(len)
+        })
+        .await
+    }
+
+    /// Sends a final binary frame whose payload is written by `write` at [`Staged::OFFSET`] of the write buffer.
+    ///
+    /// `write` returns the length of the payload, or `None` if it does not fit.
+    pub(crate) async fn send_staged<W>(&mut self, write: W) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+        W: FnOnce(&mut [u8]) -> Option<usize>,
+    {
+        self.send_requested_close().await?;
+
+        if self.state.closed {
+            return Err(Error::Write(WriteError::ConnectionClosed));
+        }
+
+        let len = self
+            .write
+            .buffer
+            .get_mut(Staged::OFFSET..)
+            .and_then(write)
+            .ok_or(Error::Write(WriteError::WriteFrame(
+                framez::WriteError::Encode(FrameEncodeError::BufferTooSmall),
+            )))?;
+
+        crate::functions::send_frame(
+            &mut self.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.state,
+            Staged { len },
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))
+    }
+
+    pub(crate) async fn send_pre_encoded(
+        &mut self,
+        message: &PreEncodedMessage<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        self.send_requested_close().await?;
+
+        crate::functions::send_pre_encoded(&self.codec, &mut self.inner, &mut self.state, message)
+            .await
+    }
+
+    #[cfg(feature = "pre-masked")]
+    pub(crate) async fn send_pre_masked(
+        &mut self,
+        header: crate::pre_masked::FrameHeader,
+        mask: [u8; 4],
+        payload: &[u8],
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        self.send_requested_close().await?;
+
+        if self.state.closed {
+            return Err(Error::Write(WriteError::ConnectionClosed));
+        }
+
+        if !self.codec.is_client() {
+            return Err(Error::Write(WriteError::PreEncodedMaskMismatch));
+        }
+
+        let frame = crate::pre_masked::PreMasked {
+            header,
+            mask,
+            payload,
+        };
+
+        crate::functions::send_frame(
+            &mut self.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.state,
+            frame,
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))
+    }
+
+    #[cfg(feature = "danger-frames")]
+    pub(crate) async fn send_raw(
+        &mut self,
+        frame: crate::danger::RawFrame<'_>,
+    ) -> Result<(), Error<RW::Error>>
+    where
+        RW: Write,
+        Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
+    {
+        self.send_requested_close().await?;
+
+        if self.state.closed {
+            return Err(Error::Write(WriteError::ConnectionClosed));
+        }
+
+        crate::functions::send_frame(
+            &mut self.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.state,
+            frame,
+        )
+        .await
+        .map_err(|err| Error::Write(WriteError::WriteFrame(err)))
+    }
+
     pub(crate) async fn send_fragmented(
         &mut self,
         message: Message<'_>,
@@ -621,11 +2659,14 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
     where
         RW: Write,
         Rng: RngCore,
+        R: CloseSignalSlot<'buf>,
     {
+        self.send_requested_close().await?;
+
         crate::functions::send_fragmented(
-            &mut self.framed.core.codec,
-            &mut self.framed.core.inner,
-            &mut self.framed.core.state.write,
+            &mut self.codec,
+            &mut self.inner,
+            &mut self.write,
             &mut self.state,
             message,
             fragment_size,
@@ -634,11 +2675,101 @@ impl<'buf, RW, Rng> WebSocketCore<'buf, RW, Rng> {
     }
 }
 
+/// Generates a `Sec-WebSocket-Key` from 16 random bytes.
+#[cfg(feature = "client")]
+pub(crate) fn generate_sec_key<Rng: RngCore>(rng: &mut Rng) -> [u8; 24] {
+    let mut key: [u8; 16] = [0; 16];
+
+    debug_assert!(key.len() == 16, "Key should be 16 bytes long");
+
+    rng.fill_bytes(&mut key);
+
+    // 24 = ((4 * key.len() + 2) / 3 + 3) & !3 = ((4 * 16 + 2) / 3 + 3) & !3
+    let mut encoded: [u8; 24] = [0; 24];
+
+    general_purpose::STANDARD
+        .encode_slice(key, &mut encoded)
+        .expect("Bug: sec_key encoding failed");
+
+    encoded
+}
+
+/// Computes the `Sec-WebSocket-Accept` value answering `sec_key`.
+pub(crate) fn generate_sec_accept(sec_key: &[u8]) -> [u8; 28] {
+    let mut sha1 = Sha1::new();
+
+    sha1.update(sec_key);
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+
+    let hash = sha1.finalize();
+
+    debug_assert!(hash.len() == 20, "SHA1 hash should be 20 bytes long");
+
+    // 28 = ((4 * hash.len() + 2) / 3 + 3) & !3 = ((4 * 20 + 2) / 3 + 3) & !3
+    let mut encoded: [u8; 28] = [0; 28];
+
+    general_purpose::STANDARD
+        .encode_slice(hash, &mut encoded)
+        .expect("Bug: sec_accept encoding failed");
+
+    encoded
+}
+
+/// The headers of the client handshake request generated by the client.
+///
+/// See [`ConnectOptions::RESERVED_HEADERS`].
+#[cfg(feature = "client")]
+pub(crate) const fn request_headers(sec_key: &[u8]) -> [Header<'_>; 4] {
+    [
+        Header {
+            name: "upgrade",
+            value: b"websocket",
+        },
+        Header {
+            name: "connection",
+            value: b"upgrade",
+        },
+        Header {
+            name: "sec-websocket-version",
+            value: b"13",
+        },
+        Header {
+            name: "sec-websocket-key",
+            value: sec_key,
+        },
+    ]
+}
+
+/// Returns the headers of the handshake request with `options`, of which the first `len` are sent.
+#[cfg(feature = "client")]
+#[cfg_attr(not(feature = "deflate"), allow(unused_variables))]
+pub(crate) const fn connect_request_headers<'a>(
+    options: &ConnectOptions<'_, '_>,
+    sec_key: &'a [u8],
+) -> ([Header<'a>; 5], usize) {
+    let [upgrade, connection, version, key] = request_headers(sec_key);
+
+    #[cfg(feature = "deflate")]
+    if options.permessage_deflate {
+        return (
+            [upgrade, connection, version, key, crate::deflate::OFFER],
+            5,
+        );
+    }
+
+    let unused = Header {
+        name: "",
+        value: &[],
+    };
+
+    ([upgrade, connection, version, key, unused], 4)
+}
+
 #[derive(Debug)]
 #[doc(hidden)]
 pub enum OnFrame<'a> {
     Send(Message<'a>),
-    Noop(Frame<'a>),
+    Noop(FrameMut<'a>),
 }
 
 #[derive(Debug)]
@@ -646,15 +2777,24 @@ pub enum OnFrame<'a> {
 pub enum OnFrameError {
     Protocol(ProtocolError),
     FragmentsBufferTooSmall,
+    MessageTooLarge,
+    TooManyEmptyFragments,
+    IntegrityCheckFailed,
+    #[cfg(feature = "deflate")]
+    DecompressionFailed,
 }
 
-impl<I> From<OnFrameError> for Error<I> {
-    fn from(err: OnFrameError) -> Self {
-        match err {
-            OnFrameError::Protocol(err) => Error::Read(ReadError::Protocol(err)),
-            OnFrameError::FragmentsBufferTooSmall => {
-                Error::Read(ReadError::FragmentsBufferTooSmall)
-            }
+impl OnFrameError {
+    /// Converts the error of the frame numbered `frame` into a [`MessageError`].
+    pub(crate) fn into_message_error<I>(self, frame: u64) -> MessageError<I> {
+        match self {
+            OnFrameError::Protocol(error) => MessageError::Protocol { error, frame },
+            OnFrameError::FragmentsBufferTooSmall => MessageError::FragmentsBufferTooSmall,
+            OnFrameError::MessageTooLarge => MessageError::MessageTooLarge,
+            OnFrameError::TooManyEmptyFragments => MessageError::TooManyEmptyFragments,
+            OnFrameError::IntegrityCheckFailed => MessageError::IntegrityCheckFailed,
+            #[cfg(feature = "deflate")]
+            OnFrameError::DecompressionFailed => MessageError::DecompressionFailed,
         }
     }
 }